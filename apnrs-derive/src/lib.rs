@@ -0,0 +1,199 @@
+//! Derive macro for `apnrs` custom notification data, enabled in `apnrs` with the `derive`
+//! feature.
+
+use proc_macro::TokenStream;
+use quote::quote;
+use syn::{parse_macro_input, Data, DeriveInput, ExprPath, Fields, LitStr};
+
+/// The keys APNs reserves at the top level of a payload.
+const RESERVED_KEYS: &[&str] = &["aps"];
+
+/// Derives `apnrs::ApnsCustomData`, so the struct's fields are sent as top-level keys of a
+/// payload next to `aps`.
+///
+/// Each named field becomes one key, named after the field unless renamed. Using a key APNs
+/// reserves, such as `aps`, or the same key twice fails to compile. The field types must
+/// implement `serde::Serialize`.
+///
+/// # Attributes
+///
+/// * `#[apns(rename = "key")]` - Sends the field under `key` instead of its name.
+/// * `#[apns(skip_serializing_if = "path")]` - Leaves the key out when the function at `path`
+///   returns `true` for the field, such as `Option::is_none`.
+///
+/// # Example
+///
+/// ```rust
+/// use apnrs::{ApnsCustomData, Aps, Badge, CustomPayload, Sound};
+///
+/// #[derive(ApnsCustomData)]
+/// struct Order {
+///     #[apns(rename = "order-id")]
+///     id: u64,
+///     #[apns(skip_serializing_if = "Option::is_none")]
+///     courier: Option<String>,
+/// }
+///
+/// let aps = Aps {
+///     alert: "Your order has shipped".to_string(),
+///     content_available: None,
+///     badge: Badge::Unchanged,
+///     sound: Sound::Default,
+///     category: None,
+///     thread_id: None,
+///     summary_arg: None,
+///     summary_arg_count: None,
+///     launch_image: None,
+///     alert_fields: Default::default(),
+///     extra_fields: Default::default(),
+/// };
+/// let payload = CustomPayload::new(aps, Order { id: 42, courier: None });
+///
+/// let json = serde_json::to_value(&payload).unwrap();
+/// assert_eq!(json["order-id"], 42);
+/// assert!(json.get("courier").is_none());
+/// ```
+///
+/// The `aps` key is reserved for APNs:
+///
+/// ```rust,compile_fail
+/// use apnrs::ApnsCustomData;
+///
+/// #[derive(ApnsCustomData)]
+/// struct Shadowing {
+///     aps: String,
+/// }
+/// ```
+#[proc_macro_derive(ApnsCustomData, attributes(apns))]
+pub fn derive_apns_custom_data(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    expand(&input)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
+
+/// One field of the struct and how it is sent.
+struct Field {
+    ident: syn::Ident,
+    key: LitStr,
+    skip_serializing_if: Option<ExprPath>,
+}
+
+fn expand(input: &DeriveInput) -> syn::Result<proc_macro2::TokenStream> {
+    let fields = match &input.data {
+        Data::Struct(data) => match &data.fields {
+            Fields::Named(fields) => &fields.named,
+            Fields::Unit => return Ok(implementation(input, &[])),
+            Fields::Unnamed(_) => {
+                return Err(syn::Error::new_spanned(
+                    &input.ident,
+                    "ApnsCustomData needs named fields to use as payload keys",
+                ))
+            }
+        },
+        _ => {
+            return Err(syn::Error::new_spanned(
+                &input.ident,
+                "ApnsCustomData can only be derived for structs",
+            ))
+        }
+    };
+
+    let mut parsed: Vec<Field> = Vec::new();
+    for field in fields {
+        let field = parse_field(field)?;
+        let key = field.key.value();
+        if RESERVED_KEYS.contains(&key.as_str()) {
+            return Err(syn::Error::new(
+                field.key.span(),
+                format!(
+                    "`{}` is reserved by APNs and cannot be a custom data key",
+                    key
+                ),
+            ));
+        }
+        if parsed.iter().any(|other| other.key.value() == key) {
+            return Err(syn::Error::new(
+                field.key.span(),
+                format!("the key `{}` is used by more than one field", key),
+            ));
+        }
+        parsed.push(field);
+    }
+    Ok(implementation(input, &parsed))
+}
+
+/// Reads a field's name and `#[apns(...)]` attributes.
+fn parse_field(field: &syn::Field) -> syn::Result<Field> {
+    let ident = field.ident.clone().expect("a named field");
+    let mut key = None;
+    let mut skip_serializing_if = None;
+    for attr in field
+        .attrs
+        .iter()
+        .filter(|attr| attr.path().is_ident("apns"))
+    {
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("rename") {
+                key = Some(meta.value()?.parse::<LitStr>()?);
+                Ok(())
+            } else if meta.path.is_ident("skip_serializing_if") {
+                let path = meta.value()?.parse::<LitStr>()?;
+                skip_serializing_if = Some(path.parse::<ExprPath>()?);
+                Ok(())
+            } else {
+                Err(meta.error("expected `rename` or `skip_serializing_if`"))
+            }
+        })?;
+    }
+    // Spanned at the field, so errors about its key point there
+    let key = key.unwrap_or_else(|| {
+        let name = ident.to_string();
+        let name = name.strip_prefix("r#").unwrap_or(&name);
+        LitStr::new(name, ident.span())
+    });
+    Ok(Field {
+        ident,
+        key,
+        skip_serializing_if,
+    })
+}
+
+/// Generates the `ApnsCustomData` implementation.
+fn implementation(input: &DeriveInput, fields: &[Field]) -> proc_macro2::TokenStream {
+    let name = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+    let keys = fields.iter().map(|field| &field.key);
+    let entries = fields.iter().map(|field| {
+        let Field {
+            ident,
+            key,
+            skip_serializing_if,
+        } = field;
+        let entry = quote! {
+            ::apnrs::__serde::ser::SerializeMap::serialize_entry(map, #key, &self.#ident)?;
+        };
+        match skip_serializing_if {
+            Some(skip) => quote! {
+                if !#skip(&self.#ident) {
+                    #entry
+                }
+            },
+            None => entry,
+        }
+    });
+
+    quote! {
+        impl #impl_generics ::apnrs::ApnsCustomData for #name #ty_generics #where_clause {
+            const KEYS: &'static [&'static str] = &[#(#keys),*];
+
+            fn serialize_fields<__ApnsMap: ::apnrs::__serde::ser::SerializeMap>(
+                &self,
+                map: &mut __ApnsMap,
+            ) -> ::core::result::Result<(), __ApnsMap::Error> {
+                #(#entries)*
+                ::core::result::Result::Ok(())
+            }
+        }
+    }
+}
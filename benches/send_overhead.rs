@@ -0,0 +1,88 @@
+//! Measures the work `ApnsClient::send` does before a request reaches the connection, and
+//! fails if it exceeds `FAST_PATH_BUDGET`.
+//!
+//! Sends go through the whole pipeline of a dry-run client, validation, automatic priority,
+//! send hooks, header building, and serialization included, and stop short of the network.
+//!
+//! Run with `cargo bench --bench send_overhead`.
+
+use apnrs::{ApnsClient, ApnsPayload, Aps, Badge, SendHook, Sound, FAST_PATH_BUDGET};
+use openssl::ec::{EcGroup, EcKey};
+use openssl::nid::Nid;
+use openssl::pkey::PKey;
+use std::hint::black_box;
+use std::process;
+use std::time::Instant;
+
+const ITERATIONS: u32 = 100_000;
+
+const DEVICE_TOKEN: &str = "3f2a9c6f0e4d4b8a9c1d2e3f4a5b6c7d8e9f0a1b2c3d4e5f6a7b8c9d0e1f8a0f";
+
+/// A hook doing no work, so the benchmark covers calling the hooks of a client that has some.
+struct NoopHook;
+
+impl SendHook for NoopHook {}
+
+fn main() {
+    let group = EcGroup::from_curve_name(Nid::X9_62_PRIME256V1).expect("P-256 is supported");
+    let key = PKey::from_ec_key(EcKey::generate(&group).expect("key generation succeeds"))
+        .and_then(|key| key.private_key_to_pem_pkcs8())
+        .expect("key encodes as PKCS#8");
+
+    let client = ApnsClient::builder()
+        .auth_key_pem(key)
+        .team_id("TEAM_ID")
+        .key_id("KEY_ID")
+        .dry_run(true)
+        .auto_priority(true)
+        .hook(NoopHook)
+        .build()
+        .expect("client builds");
+    let payload = ApnsPayload {
+        aps: Aps {
+            alert: "Your code is 123456".to_string(),
+            content_available: None,
+            badge: Badge::Unchanged,
+            sound: Sound::Default,
+            category: None,
+            thread_id: None,
+            summary_arg: None,
+            summary_arg_count: None,
+            launch_image: None,
+            alert_fields: Default::default(),
+            extra_fields: Default::default(),
+        },
+        custom_key: None,
+        custom_fields: Default::default(),
+    };
+
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .expect("runtime builds");
+
+    // The first send signs the provider token, which later ones reuse
+    runtime
+        .block_on(client.send(DEVICE_TOKEN, "com.example.app", &payload))
+        .expect("dry run succeeds");
+
+    let started = Instant::now();
+    for _ in 0..ITERATIONS {
+        let outcome = runtime.block_on(client.send(
+            black_box(DEVICE_TOKEN),
+            black_box("com.example.app"),
+            black_box(&payload),
+        ));
+        let _ = black_box(outcome);
+    }
+    let average = started.elapsed() / ITERATIONS;
+
+    println!(
+        "send overhead: {:?} per request (budget {:?})",
+        average, FAST_PATH_BUDGET
+    );
+    if average > FAST_PATH_BUDGET {
+        eprintln!("send overhead exceeds FAST_PATH_BUDGET");
+        process::exit(1);
+    }
+}
@@ -0,0 +1,54 @@
+/*
+ * C API of apnrs, built with the `capi` feature:
+ *
+ *     cargo rustc --release --lib --features capi --crate-type cdylib
+ *
+ * Strings are NUL-terminated UTF-8. A failed call records a message that
+ * apnrs_last_error() returns on the same thread.
+ */
+
+#ifndef APNRS_H
+#define APNRS_H
+
+#include <stdbool.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+/* A client sharing one HTTP/2 connection to APNs between sends. */
+typedef struct ApnrsClient ApnrsClient;
+
+/*
+ * Builds a client signing provider tokens with the .p8 auth key at
+ * auth_key_path, sending to the sandbox if sandbox is true.
+ *
+ * Returns the client, to be freed with apnrs_client_free(), or NULL on error.
+ */
+ApnrsClient *apnrs_client_new(const char *auth_key_path, const char *team_id,
+                              const char *key_id, bool sandbox);
+
+/*
+ * Sends the JSON payload payload_json to a device, blocking until APNs answers.
+ *
+ * Returns the HTTP status APNs answered with: 200 if it accepted the
+ * notification, or an error status with the reason in apnrs_last_error().
+ * Returns -1 if the notification could not be sent.
+ */
+int apnrs_send(const ApnrsClient *client, const char *device_token,
+               const char *topic, const char *payload_json);
+
+/*
+ * Returns the message of the last call on this thread that failed, or NULL if
+ * it succeeded. The string is valid until the next call on the same thread.
+ */
+const char *apnrs_last_error(void);
+
+/* Frees a client. Does nothing if client is NULL. */
+void apnrs_client_free(ApnrsClient *client);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif /* APNRS_H */
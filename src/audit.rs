@@ -0,0 +1,113 @@
+//! A local audit trail of provider token signing.
+
+use serde::{Deserialize, Serialize};
+use std::sync::Mutex;
+
+/// Why a new provider token was signed.
+///
+/// # Variants
+///
+/// * `Initial` - The client had not signed a token yet.
+/// * `Expired` - The previous token was due for renewal.
+/// * `Invalidated` - APNs answered `ExpiredProviderToken`, so the previous token was discarded early.
+/// * `Rotated` - The auth key was replaced with
+///   [`ApnsClient::rotate_credentials`](crate::ApnsClient::rotate_credentials).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SigningReason {
+    Initial,
+    Expired,
+    Invalidated,
+    Rotated,
+}
+
+/// Describes one signed provider token without revealing it.
+///
+/// # Fields
+///
+/// * `token_sha256` - The hex-encoded SHA-256 hash of the signed token.
+/// * `team_id` - The `iss` claim, your Apple Developer team ID.
+/// * `key_id` - The `kid` header, the ID of the signing key.
+/// * `issued_at` - The `iat` claim, in seconds since the Unix epoch.
+/// * `previous_issued_at` - The `iat` of the token this one replaced, if any.
+/// * `reason` - Why the token was signed.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SigningRecord {
+    pub token_sha256: String,
+    pub team_id: String,
+    pub key_id: String,
+    pub issued_at: u64,
+    pub previous_issued_at: Option<u64>,
+    pub reason: SigningReason,
+}
+
+impl SigningRecord {
+    /// Returns how long the previous token was used before this one replaced it, in seconds.
+    pub fn rotated_after(&self) -> Option<u64> {
+        self.previous_issued_at
+            .map(|previous| self.issued_at.saturating_sub(previous))
+    }
+}
+
+/// Receives a [`SigningRecord`] every time the client signs a provider token.
+///
+/// Records are only kept locally; the tokens themselves are never recorded, and nothing is
+/// sent anywhere. Apple expects a token to be reused for at least 20 minutes and replaced
+/// within an hour, which the records let an audit verify.
+///
+/// # Example
+///
+/// ```rust
+/// use apnrs::{MemorySigningAudit, SigningAudit, SigningReason, SigningRecord};
+///
+/// let audit = MemorySigningAudit::new();
+/// audit.record(SigningRecord {
+///     token_sha256: "9f86d081884c7d65".to_string(),
+///     team_id: "TEAM_ID".to_string(),
+///     key_id: "KEY_ID".to_string(),
+///     issued_at: 1_700_003_000,
+///     previous_issued_at: Some(1_700_000_000),
+///     reason: SigningReason::Expired,
+/// });
+///
+/// assert_eq!(audit.records()[0].rotated_after(), Some(3000));
+/// ```
+pub trait SigningAudit: Send + Sync {
+    /// Records a newly signed token.
+    fn record(&self, record: SigningRecord);
+
+    /// Returns every record kept, oldest first.
+    fn records(&self) -> Vec<SigningRecord>;
+}
+
+/// A [`SigningAudit`] that keeps records in memory for the lifetime of the process.
+#[derive(Debug, Default)]
+pub struct MemorySigningAudit {
+    records: Mutex<Vec<SigningRecord>>,
+}
+
+impl MemorySigningAudit {
+    /// Creates an empty audit trail.
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl SigningAudit for MemorySigningAudit {
+    fn record(&self, record: SigningRecord) {
+        self.records.lock().unwrap().push(record);
+    }
+
+    fn records(&self) -> Vec<SigningRecord> {
+        self.records.lock().unwrap().clone()
+    }
+}
+
+/// Returns the hex-encoded SHA-256 hash of `token`.
+#[cfg(feature = "signing")]
+pub(crate) fn token_hash(token: &str) -> String {
+    openssl::sha::sha256(token.as_bytes())
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
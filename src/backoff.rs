@@ -0,0 +1,146 @@
+//! Strategies for spacing out retries.
+
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+use std::time::Duration;
+
+/// Decides how long to wait before each retry.
+///
+/// A [`RetryPolicy`](crate::RetryPolicy) asks its backoff for the delay before every retry,
+/// and caps the answer at its maximum delay. Besides the strategies in this crate, any
+/// closure taking the retry number and the previous delay is a backoff, so strategies from
+/// crates such as `backoff` or `tryhard` can be reused by calling them from one.
+///
+/// # Example
+///
+/// ```rust
+/// use apnrs::{Backoff, RetryPolicy};
+/// use std::time::Duration;
+///
+/// // Wait a second longer before each retry
+/// let linear = |retry: u32, _previous: Duration| Duration::from_secs(retry.into());
+/// assert_eq!(linear.delay(3, Duration::from_secs(2)), Duration::from_secs(3));
+///
+/// let policy = RetryPolicy::new().backoff(linear);
+/// ```
+pub trait Backoff: Send + Sync {
+    /// Returns the delay before retry number `retry` (starting at 1), given the delay before
+    /// the previous retry (zero before the first).
+    fn delay(&self, retry: u32, previous: Duration) -> Duration;
+}
+
+impl<F> Backoff for F
+where
+    F: Fn(u32, Duration) -> Duration + Send + Sync,
+{
+    fn delay(&self, retry: u32, previous: Duration) -> Duration {
+        self(retry, previous)
+    }
+}
+
+/// Doubles the delay after each retry, starting at `base`: the default backoff of a
+/// [`RetryPolicy`](crate::RetryPolicy).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ExponentialBackoff {
+    base: Duration,
+}
+
+impl ExponentialBackoff {
+    /// Creates a backoff waiting `base` before the first retry.
+    pub fn new(base: Duration) -> Self {
+        ExponentialBackoff { base }
+    }
+}
+
+impl Backoff for ExponentialBackoff {
+    fn delay(&self, retry: u32, _previous: Duration) -> Duration {
+        let factor = 2u32.saturating_pow(retry.saturating_sub(1));
+        self.base.checked_mul(factor).unwrap_or(Duration::MAX)
+    }
+}
+
+/// Waits the same delay before every retry.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FixedBackoff {
+    delay: Duration,
+}
+
+impl FixedBackoff {
+    /// Creates a backoff waiting `delay` before every retry.
+    pub fn new(delay: Duration) -> Self {
+        FixedBackoff { delay }
+    }
+}
+
+impl Backoff for FixedBackoff {
+    fn delay(&self, _retry: u32, _previous: Duration) -> Duration {
+        self.delay
+    }
+}
+
+/// Grows the delay along the Fibonacci sequence, `base`, `base`, `2 × base`, `3 × base`,
+/// `5 × base`, and so on, more gently than doubling it.
+///
+/// # Example
+///
+/// ```rust
+/// use apnrs::{Backoff, FibonacciBackoff};
+/// use std::time::Duration;
+///
+/// let backoff = FibonacciBackoff::new(Duration::from_secs(1));
+/// let delays: Vec<u64> = (1..=5)
+///     .map(|retry| backoff.delay(retry, Duration::ZERO).as_secs())
+///     .collect();
+/// assert_eq!(delays, [1, 1, 2, 3, 5]);
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FibonacciBackoff {
+    base: Duration,
+}
+
+impl FibonacciBackoff {
+    /// Creates a backoff waiting `base` before the first two retries.
+    pub fn new(base: Duration) -> Self {
+        FibonacciBackoff { base }
+    }
+}
+
+impl Backoff for FibonacciBackoff {
+    fn delay(&self, retry: u32, _previous: Duration) -> Duration {
+        let (mut current, mut next) = (1u32, 1u32);
+        for _ in 1..retry {
+            (current, next) = (next, current.saturating_add(next));
+        }
+        self.base.checked_mul(current).unwrap_or(Duration::MAX)
+    }
+}
+
+/// Waits a random delay between `base` and three times the previous delay, so senders
+/// retrying after the same failure spread out instead of retrying in lockstep.
+///
+/// This is the "decorrelated jitter" strategy; the policy's maximum delay keeps it from
+/// growing without bound.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DecorrelatedJitter {
+    base: Duration,
+}
+
+impl DecorrelatedJitter {
+    /// Creates a backoff waiting at least `base` before every retry.
+    pub fn new(base: Duration) -> Self {
+        DecorrelatedJitter { base }
+    }
+}
+
+impl Backoff for DecorrelatedJitter {
+    fn delay(&self, _retry: u32, previous: Duration) -> Duration {
+        let high = previous.saturating_mul(3).max(self.base);
+        let spread = (high - self.base).as_nanos();
+        if spread == 0 {
+            return self.base;
+        }
+        let offset = u128::from(RandomState::new().build_hasher().finish()) % (spread + 1);
+        let offset = Duration::from_nanos(u64::try_from(offset).unwrap_or(u64::MAX));
+        self.base.saturating_add(offset)
+    }
+}
@@ -0,0 +1,90 @@
+//! The badge of the app icon.
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+/// What a notification does to the badge of the app icon, sent as the `badge` key.
+///
+/// # Variants
+///
+/// * `Set` - Shows the number as the badge. `Set(0)` clears it, like `Clear`.
+/// * `Clear` - Removes the badge, sent as `0`.
+/// * `Unchanged` - Leaves the badge as it is; the `badge` key is omitted.
+///
+/// # Example
+///
+/// ```rust
+/// use apnrs::{ApnsPayload, Aps, Badge, Sound};
+///
+/// let payload = ApnsPayload {
+///     aps: Aps {
+///         alert: "All caught up".to_string(),
+///         content_available: None,
+///         badge: Badge::Clear,
+///         sound: Sound::None,
+///         category: None,
+///         thread_id: None,
+///         summary_arg: None,
+///         summary_arg_count: None,
+///         launch_image: None,
+///         alert_fields: Default::default(),
+///         extra_fields: Default::default(),
+///     },
+///     custom_key: None,
+///     custom_fields: Default::default(),
+/// };
+///
+/// let json = serde_json::to_value(&payload).unwrap();
+/// assert_eq!(json["aps"]["badge"], 0);
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum Badge {
+    Set(u32),
+    Clear,
+    #[default]
+    Unchanged,
+}
+
+impl Badge {
+    /// Returns `true` if the badge is left as it is.
+    pub fn is_unchanged(&self) -> bool {
+        *self == Badge::Unchanged
+    }
+
+    /// Returns the number sent as the `badge` key, or `None` if the key is omitted.
+    pub fn value(&self) -> Option<u32> {
+        match self {
+            Badge::Set(count) => Some(*count),
+            Badge::Clear => Some(0),
+            Badge::Unchanged => None,
+        }
+    }
+}
+
+impl From<u32> for Badge {
+    /// Shows `count` as the badge, or clears it if `count` is 0.
+    fn from(count: u32) -> Self {
+        match count {
+            0 => Badge::Clear,
+            count => Badge::Set(count),
+        }
+    }
+}
+
+impl From<Option<u32>> for Badge {
+    /// Leaves the badge unchanged for `None`.
+    fn from(count: Option<u32>) -> Self {
+        count.map_or(Badge::Unchanged, Badge::from)
+    }
+}
+
+impl Serialize for Badge {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.value().serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for Badge {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        Option::<u32>::deserialize(deserializer).map(Badge::from)
+    }
+}
@@ -0,0 +1,368 @@
+//! Throughput benchmark for tuning concurrency and connection settings, enabled with the
+//! `bench` feature.
+//!
+//! ```text
+//! apnrs-bench mock --notifications 100000 --concurrency 500 --latency-ms 40 --error-rate 0.01
+//! apnrs-bench sandbox --key key.p8 --team TEAM_ID --key-id KEY_ID --topic com.example.app \
+//!     --token DEVICE_TOKEN --notifications 10000 --concurrency 200 --connections 4
+//! ```
+
+use apnrs::http::Response;
+use apnrs::{
+    ApnsClient, ApnsError, ApnsPayload, ApnsResponse, Aps, Badge, Bytes, ConnectionStrategy,
+    CoreClient, Credentials, Environment, PreparedRequest, SendOptions, Sound, Transport,
+    TransportFuture,
+};
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use futures_util::stream::{self, StreamExt};
+use openssl::ec::{EcGroup, EcKey};
+use openssl::nid::Nid;
+use openssl::pkey::PKey;
+use openssl::rand::rand_bytes;
+use std::collections::BTreeMap;
+use std::future::Future;
+use std::process::ExitCode;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+#[derive(Parser)]
+#[command(
+    name = "apnrs-bench",
+    version,
+    about = "Measure notification throughput, latency, and errors"
+)]
+struct Cli {
+    #[command(subcommand)]
+    target: Target,
+}
+
+#[derive(Subcommand)]
+enum Target {
+    /// Send to a simulated APNs in process, measuring the crate's own overhead
+    Mock(MockArgs),
+    /// Send to the APNs sandbox, measuring the network and connection settings too
+    Sandbox(Box<SandboxArgs>),
+}
+
+/// The shape of the load, shared by both targets.
+#[derive(Args)]
+struct Load {
+    /// Number of notifications to send
+    #[arg(long, default_value_t = 10_000)]
+    notifications: usize,
+
+    /// Requests in flight at once
+    #[arg(long, default_value_t = 100)]
+    concurrency: usize,
+
+    /// Size of the alert text, in bytes
+    #[arg(long, default_value_t = 128)]
+    alert_size: usize,
+}
+
+#[derive(Args)]
+struct MockArgs {
+    #[command(flatten)]
+    load: Load,
+
+    /// Time the simulated APNs takes to answer, in milliseconds
+    #[arg(long, default_value_t = 40)]
+    latency_ms: u64,
+
+    /// Random extra time added to each answer, up to this many milliseconds
+    #[arg(long, default_value_t = 20)]
+    jitter_ms: u64,
+
+    /// Fraction of requests answered with 429 TooManyRequests
+    #[arg(long, default_value_t = 0.0)]
+    error_rate: f64,
+}
+
+#[derive(Args)]
+struct SandboxArgs {
+    #[command(flatten)]
+    load: Load,
+
+    /// Path to the APNs auth key (.p8)
+    #[arg(long)]
+    key: String,
+
+    /// Apple Developer team ID
+    #[arg(long)]
+    team: String,
+
+    /// Key ID of the auth key
+    #[arg(long)]
+    key_id: String,
+
+    /// Topic, usually the app's bundle ID
+    #[arg(long)]
+    topic: String,
+
+    /// Device token every notification is sent to
+    #[arg(long)]
+    token: String,
+
+    /// HTTP/2 connections to keep open to APNs
+    #[arg(long, default_value_t = 1)]
+    connections: usize,
+
+    /// How requests are spread over the connections
+    #[arg(long, value_enum, default_value_t = Strategy::RoundRobin)]
+    strategy: Strategy,
+
+    /// Client-wide cap on requests in flight
+    #[arg(long)]
+    max_in_flight: Option<usize>,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum Strategy {
+    RoundRobin,
+    LeastLoaded,
+}
+
+impl From<Strategy> for ConnectionStrategy {
+    fn from(strategy: Strategy) -> Self {
+        match strategy {
+            Strategy::RoundRobin => ConnectionStrategy::RoundRobin,
+            Strategy::LeastLoaded => ConnectionStrategy::LeastLoaded,
+        }
+    }
+}
+
+/// An APNs stand-in answering after a configurable delay, rejecting a share of requests.
+struct MockApns {
+    latency: Duration,
+    jitter_ms: u64,
+    error_rate: f64,
+}
+
+impl Transport for MockApns {
+    fn send(&self, _request: PreparedRequest) -> TransportFuture<'_> {
+        Box::pin(async move {
+            let jitter = match self.jitter_ms {
+                0 => 0,
+                jitter_ms => random() % (jitter_ms + 1),
+            };
+            tokio::time::sleep(self.latency + Duration::from_millis(jitter)).await;
+
+            let throttled = (random() as f64 / u64::MAX as f64) < self.error_rate;
+            let response = if throttled {
+                Response::builder()
+                    .status(429)
+                    .body(Bytes::from_static(br#"{"reason":"TooManyRequests"}"#))
+            } else {
+                Response::builder()
+                    .status(200)
+                    .header("apns-id", "00000000-0000-4000-8000-000000000000")
+                    .body(Bytes::new())
+            };
+            Ok(response.expect("mock response is valid"))
+        })
+    }
+}
+
+/// What one benchmark run measured.
+#[derive(Default)]
+struct Report {
+    latencies: Vec<Duration>,
+    statuses: BTreeMap<String, usize>,
+    errors: BTreeMap<String, usize>,
+    elapsed: Duration,
+}
+
+#[tokio::main]
+async fn main() -> ExitCode {
+    let cli = Cli::parse();
+
+    let report = match cli.target {
+        Target::Mock(args) => mock(args).await,
+        Target::Sandbox(args) => sandbox(*args).await,
+    };
+    match report {
+        Ok(report) => {
+            report.print();
+            ExitCode::SUCCESS
+        }
+        Err(e) => {
+            eprintln!("error: {}", e);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+async fn mock(args: MockArgs) -> Result<Report, ApnsError> {
+    let group = EcGroup::from_curve_name(Nid::X9_62_PRIME256V1).expect("P-256 is supported");
+    let key = PKey::from_ec_key(EcKey::generate(&group).expect("key generation succeeds"))
+        .and_then(|key| key.private_key_to_pem_pkcs8())
+        .expect("key encodes as PKCS#8");
+    let transport = MockApns {
+        latency: Duration::from_millis(args.latency_ms),
+        jitter_ms: args.jitter_ms,
+        error_rate: args.error_rate,
+    };
+    let client = Arc::new(
+        CoreClient::new(transport, Credentials::new(&key, "TEAM_ID", "KEY_ID")?)
+            .environment(Environment::Sandbox),
+    );
+    let payload = Arc::new(payload(args.load.alert_size));
+
+    let report = run(&args.load, move |_| {
+        let (client, payload) = (client.clone(), payload.clone());
+        async move {
+            client
+                .send(&"0".repeat(64), "com.example.app", &payload)
+                .await
+        }
+    })
+    .await;
+    Ok(report)
+}
+
+async fn sandbox(args: SandboxArgs) -> Result<Report, ApnsError> {
+    let mut builder = ApnsClient::builder()
+        .auth_key_path(&args.key)
+        .team_id(&args.team)
+        .key_id(&args.key_id)
+        .environment(Environment::Sandbox)
+        .connections(args.connections)
+        .connection_strategy(args.strategy.into());
+    if let Some(max) = args.max_in_flight {
+        builder = builder.max_in_flight(max);
+    }
+    let client = builder.build()?;
+    client.connect().await?;
+    let payload = client.prepare(&payload(args.load.alert_size))?;
+    let (topic, token) = (Arc::new(args.topic), Arc::new(args.token));
+    let options = Arc::new(SendOptions::new());
+
+    let report = run(&args.load, move |_| {
+        let (client, payload) = (client.clone(), payload.clone());
+        let (topic, token, options) = (topic.clone(), token.clone(), options.clone());
+        async move {
+            client
+                .send_prepared(&token, &topic, &payload, &options)
+                .await
+                .map(|outcome| outcome.response)
+        }
+    })
+    .await;
+    Ok(report)
+}
+
+/// Runs `send` `load.notifications` times with `load.concurrency` in flight, timing each.
+async fn run<F, Fut>(load: &Load, send: F) -> Report
+where
+    F: Fn(usize) -> Fut,
+    Fut: Future<Output = Result<ApnsResponse, ApnsError>>,
+{
+    let started = Instant::now();
+    let results: Vec<_> = stream::iter(0..load.notifications)
+        .map(|i| {
+            let send = send(i);
+            async move {
+                let started = Instant::now();
+                let result = send.await;
+                (started.elapsed(), result)
+            }
+        })
+        .buffer_unordered(load.concurrency.max(1))
+        .collect()
+        .await;
+
+    let mut report = Report {
+        elapsed: started.elapsed(),
+        ..Report::default()
+    };
+    for (latency, result) in results {
+        match result {
+            Ok(response) => {
+                report.latencies.push(latency);
+                let status = match &response.error {
+                    Some(reason) => format!("{} {}", response.status.as_u16(), reason.as_str()),
+                    None => response.status.as_u16().to_string(),
+                };
+                *report.statuses.entry(status).or_default() += 1;
+            }
+            Err(e) => *report.errors.entry(e.to_string()).or_default() += 1,
+        }
+    }
+    report.latencies.sort();
+    report
+}
+
+impl Report {
+    fn print(&self) {
+        let answered = self.latencies.len();
+        let failed: usize = self.errors.values().sum();
+        let total = answered + failed;
+        let rate = total as f64 / self.elapsed.as_secs_f64();
+
+        println!("sent:                 {} in {:?}", total, self.elapsed);
+        println!("throughput:           {:.0}/s", rate);
+        println!("latency p50:          {:?}", self.percentile(0.50));
+        println!("latency p90:          {:?}", self.percentile(0.90));
+        println!("latency p99:          {:?}", self.percentile(0.99));
+        println!(
+            "latency max:          {:?}",
+            self.latencies.last().copied().unwrap_or_default()
+        );
+        for (status, count) in &self.statuses {
+            println!(
+                "{:<22}{} ({:.2}%)",
+                format!("{}:", status),
+                count,
+                share(*count, total)
+            );
+        }
+        for (error, count) in &self.errors {
+            println!(
+                "error:                {} x{} ({:.2}%)",
+                error,
+                count,
+                share(*count, total)
+            );
+        }
+    }
+
+    /// Returns the latency `fraction` of the answered requests stayed under.
+    fn percentile(&self, fraction: f64) -> Duration {
+        if self.latencies.is_empty() {
+            return Duration::ZERO;
+        }
+        let index = ((self.latencies.len() - 1) as f64 * fraction).round() as usize;
+        self.latencies[index]
+    }
+}
+
+fn share(count: usize, total: usize) -> f64 {
+    100.0 * count as f64 / total.max(1) as f64
+}
+
+/// Builds an alert payload whose text is `alert_size` bytes long.
+fn payload(alert_size: usize) -> ApnsPayload {
+    ApnsPayload {
+        aps: Aps {
+            alert: "x".repeat(alert_size),
+            content_available: None,
+            badge: Badge::Unchanged,
+            sound: Sound::Default,
+            category: None,
+            thread_id: None,
+            summary_arg: None,
+            summary_arg_count: None,
+            launch_image: None,
+            alert_fields: Default::default(),
+            extra_fields: Default::default(),
+        },
+        custom_key: None,
+        custom_fields: Default::default(),
+    }
+}
+
+fn random() -> u64 {
+    let mut bytes = [0u8; 8];
+    rand_bytes(&mut bytes).expect("OpenSSL failed to generate random bytes");
+    u64::from_le_bytes(bytes)
+}
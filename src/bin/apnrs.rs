@@ -0,0 +1,591 @@
+//! Command-line interface for sending push notifications, enabled with the `cli` feature.
+//!
+//! ```text
+//! apnrs send --key key.p8 --team TEAM_ID --key-id KEY_ID --topic com.example.app \
+//!     --token DEVICE_TOKEN --alert "hi" --sandbox [--dry-run]
+//! apnrs broadcast --key key.p8 --team TEAM_ID --key-id KEY_ID --topic com.example.app \
+//!     --input devices.csv --alert "Hi {name}" --concurrency 64 --output results.ndjson
+//! apnrs tail --socket /run/apnrs.sock --reason Unregistered
+//! apnrs plan --devices 5000000 --payload-size 512 --concurrency 200 --rate-limit 2000
+//! apnrs token --key key.p8 --team TEAM_ID --key-id KEY_ID
+//! ```
+
+use apnrs::{
+    ApnsClient, ApnsError, ApnsPayload, Aps, Badge, CapacityPlan, Environment, ResultsWriter,
+    SendEvent, SendOutcome, Sound, Template,
+};
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use futures_util::stream::{self, StreamExt};
+use std::collections::HashMap;
+use std::path::Path;
+use std::process::ExitCode;
+use std::time::Duration;
+
+#[derive(Parser)]
+#[command(name = "apnrs", version, about = "Send push notifications via APNs")]
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Send a single push notification to one device
+    Send(Box<SendArgs>),
+    /// Send a templated notification to every device listed in a CSV or JSONL file
+    Broadcast(Box<BroadcastArgs>),
+    /// Live-tail the send events of a running process serving them with `ApnsClient::serve_events`
+    Tail(TailArgs),
+    /// Estimate the duration, connections, and memory of a send, for sizing senders
+    Plan(PlanArgs),
+    /// Decode the provider token the credentials sign, to diagnose InvalidProviderToken
+    Token(Credentials),
+}
+
+/// Credentials and environment shared by all subcommands that talk to APNs.
+#[derive(Args)]
+struct Credentials {
+    /// Path to the APNs auth key (.p8)
+    #[arg(long)]
+    key: String,
+
+    /// Apple Developer team ID
+    #[arg(long)]
+    team: String,
+
+    /// Key ID of the auth key
+    #[arg(long)]
+    key_id: String,
+
+    /// Use the sandbox environment instead of production
+    #[arg(long)]
+    sandbox: bool,
+}
+
+#[derive(Args)]
+struct SendArgs {
+    #[command(flatten)]
+    credentials: Credentials,
+
+    /// Topic, usually the app's bundle ID
+    #[arg(long)]
+    topic: String,
+
+    /// Device token of the target device
+    #[arg(long)]
+    token: String,
+
+    /// Alert message to display
+    #[arg(long, default_value = "")]
+    alert: String,
+
+    /// Badge number to display on the app icon (0 clears it)
+    #[arg(long)]
+    badge: Option<u32>,
+
+    /// Name of the sound to play ("default" for the system sound)
+    #[arg(long)]
+    sound: Option<String>,
+
+    /// Notification category
+    #[arg(long)]
+    category: Option<String>,
+
+    /// Thread identifier for grouping notifications
+    #[arg(long)]
+    thread_id: Option<String>,
+
+    /// Text summarizing the notification in its thread's group summary
+    #[arg(long)]
+    summary_arg: Option<String>,
+
+    /// Number of items the notification adds to its thread's group summary
+    #[arg(long)]
+    summary_arg_count: Option<u32>,
+
+    /// Launch image or storyboard shown when the app is opened from the notification
+    #[arg(long)]
+    launch_image: Option<String>,
+
+    /// Mark the notification as a content-available (background) push
+    #[arg(long)]
+    content_available: bool,
+
+    /// Value of the custom_key field
+    #[arg(long)]
+    custom: Option<String>,
+
+    /// Print the signed request instead of sending it
+    #[arg(long)]
+    dry_run: bool,
+}
+
+#[derive(Args)]
+struct BroadcastArgs {
+    #[command(flatten)]
+    credentials: Credentials,
+
+    /// Topic, usually the app's bundle ID
+    #[arg(long)]
+    topic: String,
+
+    /// CSV file with a header row, or JSONL file of objects, with a `token` column or key;
+    /// the other columns or keys are the row's template variables
+    #[arg(long)]
+    input: String,
+
+    /// Format of the input file (detected from its extension by default)
+    #[arg(long, value_enum)]
+    format: Option<InputFormat>,
+
+    /// Alert message, with `{name}` placeholders filled from each row
+    #[arg(long)]
+    alert: String,
+
+    /// Badge number to display on the app icon (0 clears it)
+    #[arg(long)]
+    badge: Option<u32>,
+
+    /// Name of the sound to play ("default" for the system sound)
+    #[arg(long)]
+    sound: Option<String>,
+
+    /// Notification category
+    #[arg(long)]
+    category: Option<String>,
+
+    /// Thread identifier for grouping notifications
+    #[arg(long)]
+    thread_id: Option<String>,
+
+    /// Requests in flight at once
+    #[arg(long, default_value_t = 64)]
+    concurrency: usize,
+
+    /// File the outcome for each token is written to, as newline-delimited JSON
+    #[arg(long, default_value = "results.ndjson")]
+    output: String,
+
+    /// Prepare every request without sending it
+    #[arg(long)]
+    dry_run: bool,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum InputFormat {
+    Csv,
+    Jsonl,
+}
+
+/// One device to broadcast to, with the values of its template variables.
+struct Recipient {
+    token: String,
+    vars: HashMap<String, String>,
+}
+
+#[derive(Args)]
+struct TailArgs {
+    /// Unix socket the process serves its events on
+    #[arg(long)]
+    socket: String,
+
+    /// Only show events for this topic
+    #[arg(long)]
+    topic: Option<String>,
+
+    /// Only show events with this rejection reason (e.g. Unregistered)
+    #[arg(long)]
+    reason: Option<String>,
+
+    /// Only show events for device tokens starting with this prefix
+    #[arg(long)]
+    token_prefix: Option<String>,
+}
+
+#[derive(Args)]
+struct PlanArgs {
+    /// Number of devices to send to
+    #[arg(long)]
+    devices: u64,
+
+    /// Payload size in bytes
+    #[arg(long, default_value_t = 256)]
+    payload_size: usize,
+
+    /// Requests in flight at once (the sender's worker count)
+    #[arg(long, default_value_t = 4)]
+    concurrency: usize,
+
+    /// Maximum notifications per second
+    #[arg(long)]
+    rate_limit: Option<f64>,
+
+    /// Time APNs takes to answer one request, in milliseconds
+    #[arg(long, default_value_t = 100)]
+    latency_ms: u64,
+
+    /// Requests one HTTP/2 connection carries at once
+    #[arg(long, default_value_t = apnrs::DEFAULT_STREAMS_PER_CONNECTION)]
+    streams_per_connection: usize,
+
+    /// The sender's queue capacity
+    #[arg(long, default_value_t = 1024)]
+    queue_capacity: usize,
+}
+
+impl TailArgs {
+    fn matches(&self, event: &SendEvent) -> bool {
+        self.topic.as_ref().is_none_or(|t| &event.topic == t)
+            && self
+                .reason
+                .as_ref()
+                .is_none_or(|r| event.reason.as_ref() == Some(r))
+            && self
+                .token_prefix
+                .as_ref()
+                .is_none_or(|p| event.device_token.starts_with(p.as_str()))
+    }
+}
+
+impl Credentials {
+    fn client(&self, dry_run: bool) -> Result<ApnsClient, apnrs::ApnsError> {
+        let environment = if self.sandbox {
+            Environment::Sandbox
+        } else {
+            Environment::Production
+        };
+
+        ApnsClient::builder()
+            .auth_key_path(&self.key)
+            .team_id(&self.team)
+            .key_id(&self.key_id)
+            .environment(environment)
+            .dry_run(dry_run)
+            .build()
+    }
+}
+
+#[tokio::main]
+async fn main() -> ExitCode {
+    let cli = Cli::parse();
+
+    match cli.command {
+        Command::Send(args) => send(*args).await,
+        Command::Broadcast(args) => broadcast(*args).await,
+        Command::Tail(args) => tail(args).await,
+        Command::Plan(args) => plan(args),
+        Command::Token(credentials) => token(credentials).await,
+    }
+}
+
+async fn send(args: SendArgs) -> ExitCode {
+    let client = match args.credentials.client(args.dry_run) {
+        Ok(client) => client,
+        Err(e) => {
+            eprintln!("error: {}", e);
+            return ExitCode::FAILURE;
+        }
+    };
+
+    let payload = ApnsPayload {
+        aps: Aps {
+            alert: args.alert,
+            content_available: args.content_available.then_some(true),
+            badge: Badge::from(args.badge),
+            sound: Sound::from(args.sound),
+            category: args.category,
+            thread_id: args.thread_id,
+            summary_arg: args.summary_arg,
+            summary_arg_count: args.summary_arg_count,
+            launch_image: args.launch_image,
+            alert_fields: Default::default(),
+            extra_fields: Default::default(),
+        },
+        custom_key: args.custom,
+        custom_fields: Default::default(),
+    };
+
+    match client.send(&args.token, &args.topic, &payload).await {
+        Ok(SendOutcome {
+            dry_run: Some(request),
+            ..
+        }) => {
+            println!("{}", request);
+            ExitCode::SUCCESS
+        }
+        Ok(outcome) => {
+            let response = outcome.response;
+            let status = response.status;
+            let apns_id = response.apns_id.as_deref().unwrap_or("-");
+            match response.error {
+                None => {
+                    println!("{} apns-id={}", status, apns_id);
+                    ExitCode::SUCCESS
+                }
+                Some(reason) => {
+                    eprintln!("{} {} apns-id={}", status, reason, apns_id);
+                    ExitCode::FAILURE
+                }
+            }
+        }
+        Err(e) => {
+            eprintln!("error: {}", e);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+async fn broadcast(args: BroadcastArgs) -> ExitCode {
+    match run_broadcast(args).await {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("error: {}", e);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+async fn run_broadcast(args: BroadcastArgs) -> Result<(), ApnsError> {
+    let client = args.credentials.client(args.dry_run)?;
+    let mut template = Template::new(&args.alert)?;
+    if let Some(badge) = args.badge {
+        template = template.badge(badge);
+    }
+    if let Some(sound) = args.sound {
+        template = template.sound(sound);
+    }
+    if let Some(category) = args.category {
+        template = template.category(category);
+    }
+    if let Some(thread_id) = args.thread_id {
+        template = template.thread_id(thread_id);
+    }
+
+    let format = match args.format {
+        Some(format) => format,
+        None => match Path::new(&args.input).extension().and_then(|e| e.to_str()) {
+            Some("csv") => InputFormat::Csv,
+            Some("jsonl" | "ndjson") => InputFormat::Jsonl,
+            _ => {
+                return Err(ApnsError::Config(format!(
+                    "cannot tell the format of {} from its extension; pass --format",
+                    args.input
+                )))
+            }
+        },
+    };
+    let input = tokio::fs::read_to_string(&args.input).await?;
+    let recipients = match format {
+        InputFormat::Csv => parse_csv(&input)?,
+        InputFormat::Jsonl => parse_jsonl(&input)?,
+    };
+
+    let mut results = ResultsWriter::new(tokio::fs::File::create(&args.output).await?);
+    let (client, template, topic) = (&client, &template, args.topic.as_str());
+    let mut sends = stream::iter(recipients)
+        .map(|recipient| async move {
+            let result = match template.render(&recipient.vars) {
+                Ok(payload) => client.send(&recipient.token, topic, &payload).await,
+                Err(e) => Err(e.into()),
+            };
+            (recipient.token, result)
+        })
+        .buffer_unordered(args.concurrency.max(1));
+
+    let (mut accepted, mut rejected, mut failed) = (0, 0, 0);
+    while let Some((token, result)) = sends.next().await {
+        match &result {
+            Ok(outcome) if outcome.response.error.is_none() => accepted += 1,
+            Ok(_) => rejected += 1,
+            Err(_) => failed += 1,
+        }
+        results.write(&token, &result).await?;
+    }
+    results.finish().await?;
+
+    println!("accepted:         {}", accepted);
+    println!("rejected:         {}", rejected);
+    println!("failed:           {}", failed);
+    println!("results:          {}", args.output);
+    Ok(())
+}
+
+/// Reads recipients from CSV with a header row naming a `token` column.
+fn parse_csv(input: &str) -> Result<Vec<Recipient>, ApnsError> {
+    let mut lines = input
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty());
+    let Some((_, header)) = lines.next() else {
+        return Ok(Vec::new());
+    };
+    let columns = split_csv_line(header)
+        .ok_or_else(|| ApnsError::Config("line 1: unterminated quote".to_string()))?;
+    let token_column = columns
+        .iter()
+        .position(|column| column == "token")
+        .ok_or_else(|| ApnsError::Config("the CSV header has no token column".to_string()))?;
+
+    lines
+        .map(|(index, line)| {
+            let fields = split_csv_line(line).ok_or_else(|| {
+                ApnsError::Config(format!("line {}: unterminated quote", index + 1))
+            })?;
+            if fields.len() != columns.len() {
+                return Err(ApnsError::Config(format!(
+                    "line {}: expected {} fields, found {}",
+                    index + 1,
+                    columns.len(),
+                    fields.len()
+                )));
+            }
+            let mut vars: HashMap<String, String> = columns.iter().cloned().zip(fields).collect();
+            let token = vars.remove(&columns[token_column]).unwrap_or_default();
+            Ok(Recipient { token, vars })
+        })
+        .collect()
+}
+
+/// Splits one CSV line into its fields, unquoting `"..."` fields and `""` inside them.
+///
+/// # Returns
+///
+/// The fields, or `None` if a quoted field is never closed.
+fn split_csv_line(line: &str) -> Option<Vec<String>> {
+    let mut fields = Vec::new();
+    let mut field = String::new();
+    let mut chars = line.chars().peekable();
+    let mut quoted = false;
+    while let Some(c) = chars.next() {
+        match (c, quoted) {
+            ('"', true) if chars.peek() == Some(&'"') => {
+                field.push('"');
+                chars.next();
+            }
+            ('"', true) => quoted = false,
+            ('"', false) if field.is_empty() => quoted = true,
+            (',', false) => fields.push(std::mem::take(&mut field)),
+            (c, _) => field.push(c),
+        }
+    }
+    if quoted {
+        return None;
+    }
+    fields.push(field);
+    Some(fields)
+}
+
+/// Reads recipients from JSONL, one object with a `token` key per line.
+fn parse_jsonl(input: &str) -> Result<Vec<Recipient>, ApnsError> {
+    input
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .map(|(index, line)| {
+            let row: serde_json::Map<String, serde_json::Value> = serde_json::from_str(line)
+                .map_err(|e| ApnsError::Config(format!("line {}: {}", index + 1, e)))?;
+            let mut vars: HashMap<String, String> = row
+                .into_iter()
+                .map(|(key, value)| match value {
+                    serde_json::Value::String(value) => (key, value),
+                    value => (key, value.to_string()),
+                })
+                .collect();
+            let token = vars
+                .remove("token")
+                .ok_or_else(|| ApnsError::Config(format!("line {}: no token key", index + 1)))?;
+            Ok(Recipient { token, vars })
+        })
+        .collect()
+}
+
+fn plan(args: PlanArgs) -> ExitCode {
+    let mut plan = CapacityPlan::new(args.devices, args.payload_size)
+        .concurrency(args.concurrency)
+        .latency(Duration::from_millis(args.latency_ms))
+        .streams_per_connection(args.streams_per_connection)
+        .queue_capacity(args.queue_capacity);
+    if let Some(rate_limit) = args.rate_limit {
+        plan = plan.rate_limit(rate_limit);
+    }
+    let estimate = plan.estimate();
+
+    println!("duration:         {:?}", estimate.duration);
+    println!("throughput:       {:.0}/s", estimate.throughput);
+    println!("in flight:        {}", estimate.peak_in_flight);
+    println!("connections:      {}", estimate.peak_connections);
+    println!(
+        "memory:           {:.1} MiB",
+        estimate.peak_memory_bytes as f64 / (1024.0 * 1024.0)
+    );
+    ExitCode::SUCCESS
+}
+
+async fn token(credentials: Credentials) -> ExitCode {
+    let token = match credentials.client(false) {
+        Ok(client) => apnrs::debug::inspect_token(&client).await,
+        Err(e) => Err(e),
+    };
+    let token = match token {
+        Ok(token) => token,
+        Err(e) => {
+            eprintln!("error: {}", e);
+            return ExitCode::FAILURE;
+        }
+    };
+
+    println!("kid:              {}", token.kid.as_deref().unwrap_or("-"));
+    println!("iss:              {}", token.iss);
+    println!("iat:              {}", token.iat);
+    println!("age:              {:?}", token.age);
+    println!("remaining:        {:?}", token.remaining);
+    println!("renews in:        {:?}", token.renews_in);
+    ExitCode::SUCCESS
+}
+
+#[cfg(unix)]
+async fn tail(args: TailArgs) -> ExitCode {
+    use tokio::io::{AsyncBufReadExt, BufReader};
+
+    let stream = match tokio::net::UnixStream::connect(&args.socket).await {
+        Ok(stream) => stream,
+        Err(e) => {
+            eprintln!("error: unable to connect to {}: {}", args.socket, e);
+            return ExitCode::FAILURE;
+        }
+    };
+
+    let mut lines = BufReader::new(stream).lines();
+    loop {
+        let line = match lines.next_line().await {
+            Ok(Some(line)) => line,
+            Ok(None) => return ExitCode::SUCCESS,
+            Err(e) => {
+                eprintln!("error: {}", e);
+                return ExitCode::FAILURE;
+            }
+        };
+        let Ok(event) = serde_json::from_str::<SendEvent>(&line) else {
+            continue;
+        };
+        if args.matches(&event) {
+            println!(
+                "{}.{:03} {} {} {} {} apns-id={} {}ms",
+                event.timestamp_ms / 1000,
+                event.timestamp_ms % 1000,
+                event.status.map_or("---".to_string(), |s| s.to_string()),
+                event.reason.as_deref().unwrap_or("-"),
+                event.topic,
+                event.device_token,
+                event.apns_id.as_deref().unwrap_or("-"),
+                event.latency_ms,
+            );
+        }
+    }
+}
+
+#[cfg(not(unix))]
+async fn tail(_args: TailArgs) -> ExitCode {
+    eprintln!("error: tail is only supported on unix platforms");
+    ExitCode::FAILURE
+}
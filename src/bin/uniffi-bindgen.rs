@@ -0,0 +1,11 @@
+//! Generates the Kotlin, Swift, and Python bindings of the `uniffi` feature.
+//!
+//! ```sh
+//! cargo rustc --release --lib --features uniffi --crate-type cdylib
+//! cargo run --features uniffi --bin uniffi-bindgen -- generate \
+//!     --library target/release/libapnrs.so --language kotlin --out-dir bindings
+//! ```
+
+fn main() {
+    uniffi::uniffi_bindgen_main()
+}
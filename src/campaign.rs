@@ -0,0 +1,629 @@
+//! Long-running sends of one notification, or a few variants of it, to many devices.
+
+use crate::{
+    validate_against_rules, validate_topic, ApnsClient, ApnsError, ApnsPayload, BatchReport,
+    DeviceToken, PushType, ResultsWriter, SendOutcome, MAX_PAYLOAD_SIZE,
+};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, VecDeque};
+use std::fmt;
+use std::fs;
+use std::path::Path;
+use std::time::{Duration, Instant};
+use tokio_util::sync::CancellationToken;
+
+/// The version of the exported campaign format written by this crate.
+const STATE_VERSION: u32 = 1;
+
+/// How far a campaign has progressed.
+///
+/// # Fields
+///
+/// * `sent` - Notifications APNs answered, whether it accepted them or not.
+/// * `succeeded` - Notifications APNs accepted.
+/// * `failed` - Notifications APNs rejected.
+/// * `skipped` - Devices not sent to because the client's token store marked them invalid.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CampaignProgress {
+    pub sent: u64,
+    pub succeeded: u64,
+    pub failed: u64,
+    #[serde(default)]
+    pub skipped: u64,
+}
+
+/// What a campaign would do if it were run now, as found by [`Campaign::dry_run`].
+///
+/// # Fields
+///
+/// * `devices` - Devices still to be sent to.
+/// * `lint_warnings` - Violations of Apple's payload rules, counted once per device whose
+///   variant breaks them; see [`validate_against_rules`].
+/// * `size_violations` - Devices whose variant is larger than APNs accepts.
+/// * `invalid_tokens` - Devices whose token is malformed or marked invalid in the client's
+///   token store, and would be skipped or rejected.
+/// * `estimated_duration` - How long sending to the remaining valid devices would take at the
+///   client's average request latency so far, or `None` if it has not sent anything yet.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DryRunReport {
+    pub devices: usize,
+    pub lint_warnings: usize,
+    pub size_violations: usize,
+    pub invalid_tokens: usize,
+    pub estimated_duration: Option<Duration>,
+}
+
+/// How a campaign tests its notification on a sample of devices before sending it to the
+/// rest, for [`Campaign::run_with_canary`].
+///
+/// # Example
+///
+/// ```rust,no_run
+/// use apnrs::{ApnsClient, Campaign, Canary};
+/// use std::time::Duration;
+///
+/// # async fn run(client: ApnsClient, mut campaign: Campaign) -> Result<(), apnrs::ApnsError> {
+/// let canary = Canary::new(0.01)
+///     .wait(Duration::from_secs(300))
+///     .max_failure_rate(0.02)
+///     .max_unregistered_rate(0.1);
+///
+/// let run = campaign.run_with_canary(&client, &canary).await?;
+/// if !run.passed {
+///     eprintln!(
+///         "canary failed ({:.1}% failed, {:.1}% unregistered); {} devices left",
+///         run.failure_rate * 100.0,
+///         run.unregistered_rate * 100.0,
+///         campaign.remaining()
+///     );
+/// }
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct Canary {
+    fraction: f64,
+    min_devices: usize,
+    wait: Duration,
+    max_failure_rate: f64,
+    max_unregistered_rate: f64,
+}
+
+impl Canary {
+    /// Sends to `fraction` of the remaining devices first, such as `0.01` for 1%, waiting a
+    /// minute after the sample before checking it. The campaign proceeds if at most 5% of
+    /// the sample failed and at most 20% of it was unregistered.
+    pub fn new(fraction: f64) -> Self {
+        Canary {
+            fraction: fraction.clamp(0.0, 1.0),
+            min_devices: 1,
+            wait: Duration::from_secs(60),
+            max_failure_rate: 0.05,
+            max_unregistered_rate: 0.2,
+        }
+    }
+
+    /// Sends to at least `devices` devices first, however small the fraction (defaults to
+    /// 1), so a small campaign still gets a meaningful sample.
+    pub fn min_devices(mut self, devices: usize) -> Self {
+        self.min_devices = devices.max(1);
+        self
+    }
+
+    /// Sets how long to wait after the sample before checking it and sending to the rest
+    /// (defaults to a minute), leaving time to notice problems APNs does not report, such
+    /// as crash reports from the devices sent to.
+    pub fn wait(mut self, wait: Duration) -> Self {
+        self.wait = wait;
+        self
+    }
+
+    /// Sets the share of the sample that may fail, counting rejections other than dead
+    /// tokens, throttling included (defaults to `0.05`).
+    pub fn max_failure_rate(mut self, rate: f64) -> Self {
+        self.max_failure_rate = rate;
+        self
+    }
+
+    /// Sets the share of the sample whose tokens may turn out unregistered or otherwise
+    /// invalid (defaults to `0.2`); a higher share suggests a stale or wrong token list.
+    pub fn max_unregistered_rate(mut self, rate: f64) -> Self {
+        self.max_unregistered_rate = rate;
+        self
+    }
+
+    /// Returns how many of `devices` the sample holds.
+    fn sample_size(&self, devices: usize) -> usize {
+        let share = (devices as f64 * self.fraction).ceil() as usize;
+        share.max(self.min_devices).min(devices)
+    }
+}
+
+/// What [`Campaign::run_with_canary`] did.
+///
+/// # Fields
+///
+/// * `canary` - The report of the sample sent first.
+/// * `failure_rate` - The share of the sample that failed, throttling included.
+/// * `unregistered_rate` - The share of the sample whose tokens were unregistered or
+///   otherwise invalid.
+/// * `passed` - `true` if both rates were within the [`Canary`]'s limits, so the campaign
+///   went on to the rest of its devices.
+/// * `rest` - The report of the rest of the campaign, or `None` if the canary did not pass
+///   and the campaign was halted with those devices still queued.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CanaryReport {
+    pub canary: BatchReport,
+    pub failure_rate: f64,
+    pub unregistered_rate: f64,
+    pub passed: bool,
+    pub rest: Option<BatchReport>,
+}
+
+/// Sends one of several payload variants to each of a list of device tokens, and can be
+/// paused, exported to a file, and resumed later, possibly on another host.
+///
+/// Tokens are assigned a variant round-robin when they are added, so a resumed campaign
+/// sends every remaining device the variant it was originally assigned.
+///
+/// # Example
+///
+/// ```rust,no_run
+/// use apnrs::{ApnsClient, ApnsPayload, Aps, Campaign};
+/// use std::sync::atomic::{AtomicBool, Ordering};
+///
+/// # async fn run(client: ApnsClient, payload: ApnsPayload, stop: AtomicBool) -> Result<(), apnrs::ApnsError> {
+/// let mut campaign = Campaign::new("com.example.app", vec![payload])?;
+/// campaign.add_tokens(vec!["TOKEN_1".to_string(), "TOKEN_2".to_string()]);
+///
+/// // Send until told to stop for maintenance, then save what is left.
+/// campaign.run_until(&client, || stop.load(Ordering::Relaxed)).await?;
+/// campaign.export("campaign.json")?;
+///
+/// // Later, possibly on another host:
+/// let mut campaign = Campaign::import("campaign.json")?;
+/// campaign.run_until(&client, || false).await?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Campaign {
+    version: u32,
+    topic: String,
+    variants: Vec<ApnsPayload>,
+    pending: VecDeque<String>,
+    assignments: HashMap<String, usize>,
+    progress: CampaignProgress,
+}
+
+// Lists the number of pending devices rather than their tokens
+impl fmt::Debug for Campaign {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Campaign")
+            .field("topic", &self.topic)
+            .field("variants", &self.variants)
+            .field("pending", &self.pending.len())
+            .field("progress", &self.progress)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Campaign {
+    /// Creates an empty campaign sending `variants` under `topic`.
+    ///
+    /// # Returns
+    ///
+    /// The campaign, or an `ApnsError` if the topic is invalid or no variants were given.
+    pub fn new(topic: impl Into<String>, variants: Vec<ApnsPayload>) -> Result<Self, ApnsError> {
+        let topic = topic.into();
+        validate_topic(&topic)?;
+        if variants.is_empty() {
+            return Err(ApnsError::Config(
+                "a campaign needs at least one variant".to_string(),
+            ));
+        }
+
+        Ok(Campaign {
+            version: STATE_VERSION,
+            topic,
+            variants,
+            pending: VecDeque::new(),
+            assignments: HashMap::new(),
+            progress: CampaignProgress::default(),
+        })
+    }
+
+    /// Queues device tokens, assigning each a variant. Tokens already in the campaign are
+    /// skipped, so a device listed twice is only sent to once.
+    ///
+    /// # Returns
+    ///
+    /// How many tokens were skipped as duplicates.
+    pub fn add_tokens(&mut self, tokens: impl IntoIterator<Item = String>) -> usize {
+        let mut duplicates = 0;
+        for token in tokens {
+            if self.assignments.contains_key(&token) {
+                duplicates += 1;
+                continue;
+            }
+            let variant = self.assignments.len() % self.variants.len();
+            self.assignments.insert(token.clone(), variant);
+            self.pending.push_back(token);
+        }
+        duplicates
+    }
+
+    /// Returns the topic notifications are sent under.
+    pub fn topic(&self) -> &str {
+        &self.topic
+    }
+
+    /// Returns the payload variants.
+    pub fn variants(&self) -> &[ApnsPayload] {
+        &self.variants
+    }
+
+    /// Returns the index of the variant assigned to `device_token`.
+    pub fn variant_of(&self, device_token: &str) -> Option<usize> {
+        self.assignments.get(device_token).copied()
+    }
+
+    /// Returns the number of devices still to be sent to.
+    pub fn remaining(&self) -> usize {
+        self.pending.len()
+    }
+
+    /// Returns the progress counters.
+    pub fn progress(&self) -> &CampaignProgress {
+        &self.progress
+    }
+
+    /// Checks every remaining (token, payload) pair without sending anything.
+    ///
+    /// Each variant is checked once against Apple's payload rules and size limit, and the
+    /// findings are counted for every device assigned to it.
+    ///
+    /// # Returns
+    ///
+    /// The aggregate report, or an `ApnsError` if a variant cannot be serialized.
+    ///
+    /// # Example
+    ///
+    /// ```rust,no_run
+    /// use apnrs::{ApnsClient, Campaign};
+    ///
+    /// # fn run(client: ApnsClient, campaign: Campaign) -> Result<(), apnrs::ApnsError> {
+    /// let report = campaign.dry_run(&client)?;
+    /// println!(
+    ///     "{} devices, {} invalid tokens, {} oversized, {} lint warnings, ~{:?}",
+    ///     report.devices,
+    ///     report.invalid_tokens,
+    ///     report.size_violations,
+    ///     report.lint_warnings,
+    ///     report.estimated_duration,
+    /// );
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn dry_run(&self, client: &ApnsClient) -> Result<DryRunReport, ApnsError> {
+        let mut findings = Vec::with_capacity(self.variants.len());
+        for variant in &self.variants {
+            let value = serde_json::to_value(variant)?;
+            let warnings =
+                validate_against_rules(&value, PushType::Alert).map_or_else(|v| v.len(), |_| 0);
+            let oversized = serde_json::to_vec(&value)?.len() > MAX_PAYLOAD_SIZE;
+            findings.push((warnings, oversized));
+        }
+
+        let mut report = DryRunReport {
+            devices: self.pending.len(),
+            ..DryRunReport::default()
+        };
+        for token in &self.pending {
+            if token.parse::<DeviceToken>().is_err() || client.token_store().is_invalid(token) {
+                report.invalid_tokens += 1;
+            }
+            let variant = self.assignments.get(token).copied().unwrap_or(0);
+            let (warnings, oversized) = findings[variant];
+            report.lint_warnings += warnings;
+            report.size_violations += usize::from(oversized);
+        }
+
+        let latency = client.stats().average_latency;
+        if !latency.is_zero() {
+            // Campaigns send one notification at a time
+            let sendable = report.devices - report.invalid_tokens;
+            report.estimated_duration = Some(latency.mul_f64(sendable as f64));
+        }
+        Ok(report)
+    }
+
+    /// Sends the next queued notification.
+    ///
+    /// # Returns
+    ///
+    /// `None` once every device has been sent to. Tokens the client's token store marks
+    /// invalid are skipped with `ApnsError::InvalidToken`. If the send fails before APNs
+    /// answers, the token stays at the front of the queue so the campaign can be resumed
+    /// after the problem is fixed.
+    ///
+    /// # Cancel safety
+    ///
+    /// The token is only taken off the queue once APNs answers, so dropping the future
+    /// mid-send leaves it queued.
+    pub async fn send_next(
+        &mut self,
+        client: &ApnsClient,
+    ) -> Option<Result<SendOutcome, ApnsError>> {
+        let token = self.pending.front()?.clone();
+        if client.token_store().is_invalid(&token) {
+            self.pending.pop_front();
+            self.progress.skipped += 1;
+            return Some(Err(ApnsError::InvalidToken));
+        }
+        let variant = self.assignments.get(&token).copied().unwrap_or(0);
+
+        match client
+            .send(&token, &self.topic, &self.variants[variant])
+            .await
+        {
+            Ok(outcome) => {
+                self.pending.pop_front();
+                self.progress.sent += 1;
+                if outcome.response.is_success() {
+                    self.progress.succeeded += 1;
+                } else {
+                    self.progress.failed += 1;
+                }
+                Some(Ok(outcome))
+            }
+            Err(error) => Some(Err(error)),
+        }
+    }
+
+    /// Sends queued notifications until every device has been sent to or `stop` returns
+    /// `true`, which pauses the campaign.
+    ///
+    /// # Returns
+    ///
+    /// A [`BatchReport`] of the notifications sent by this run when finished or paused, or
+    /// the first error raised before APNs answered. Skipped invalid tokens are not errors.
+    pub async fn run_until(
+        &mut self,
+        client: &ApnsClient,
+        mut stop: impl FnMut() -> bool,
+    ) -> Result<BatchReport, ApnsError> {
+        let started = Instant::now();
+        let mut report = BatchReport::new();
+        while !stop() {
+            let Some(token) = self.pending.front().cloned() else {
+                break;
+            };
+            let Some(result) = self.send_next(client).await else {
+                break;
+            };
+            if let Err(error) = &result {
+                if !matches!(error, ApnsError::InvalidToken) {
+                    return result.map(|_| report);
+                }
+            }
+            report.record(&token, &result);
+        }
+        report.duration = started.elapsed();
+        Ok(report)
+    }
+
+    /// Sends queued notifications until every device has been sent to or `cancel` is
+    /// cancelled, which pauses the campaign.
+    ///
+    /// Unlike [`run_until`](Campaign::run_until), cancelling aborts the notification being
+    /// sent rather than waiting for APNs to answer it; its token stays queued, so the
+    /// campaign can be exported and resumed later.
+    ///
+    /// # Returns
+    ///
+    /// A [`BatchReport`] of the notifications APNs answered before the campaign finished or
+    /// was cancelled, or the first error raised before APNs answered.
+    ///
+    /// # Example
+    ///
+    /// ```rust,no_run
+    /// use apnrs::{ApnsClient, Campaign, CancellationToken};
+    ///
+    /// # async fn run(client: ApnsClient, mut campaign: Campaign) -> Result<(), apnrs::ApnsError> {
+    /// let cancel = CancellationToken::new();
+    /// let abort = cancel.clone();
+    /// tokio::spawn(async move {
+    ///     tokio::signal::ctrl_c().await.ok();
+    ///     abort.cancel();
+    /// });
+    ///
+    /// let report = campaign.run_until_cancelled(&client, &cancel).await?;
+    /// println!("{} delivered, {} left", report.delivered, campaign.remaining());
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn run_until_cancelled(
+        &mut self,
+        client: &ApnsClient,
+        cancel: &CancellationToken,
+    ) -> Result<BatchReport, ApnsError> {
+        let started = Instant::now();
+        let mut report = BatchReport::new();
+        while let Some(token) = self.pending.front().cloned() {
+            let result = tokio::select! {
+                biased;
+                _ = cancel.cancelled() => break,
+                result = self.send_next(client) => result,
+            };
+            let Some(result) = result else {
+                break;
+            };
+            if let Err(error) = &result {
+                if !matches!(error, ApnsError::InvalidToken) {
+                    return result.map(|_| report);
+                }
+            }
+            report.record(&token, &result);
+        }
+        report.duration = started.elapsed();
+        Ok(report)
+    }
+
+    /// Like [`run_until`](Campaign::run_until), but streams the result of every notification,
+    /// including skipped invalid tokens, to `results`.
+    ///
+    /// Notifications that fail before APNs answers are not written, since they stay queued
+    /// and are sent again when the campaign is resumed.
+    ///
+    /// # Returns
+    ///
+    /// A [`BatchReport`] of the notifications sent by this run when finished or paused, or
+    /// the first error raised before APNs answered or while writing a result.
+    pub async fn run_until_recording(
+        &mut self,
+        client: &ApnsClient,
+        mut stop: impl FnMut() -> bool,
+        results: &mut ResultsWriter,
+    ) -> Result<BatchReport, ApnsError> {
+        let started = Instant::now();
+        let mut report = BatchReport::new();
+        while !stop() {
+            let Some(token) = self.pending.front().cloned() else {
+                break;
+            };
+            let Some(result) = self.send_next(client).await else {
+                break;
+            };
+            if let Err(error) = &result {
+                if !matches!(error, ApnsError::InvalidToken) {
+                    return result.map(|_| report);
+                }
+            }
+            results.write(&token, &result).await?;
+            report.record(&token, &result);
+        }
+        report.duration = started.elapsed();
+        Ok(report)
+    }
+
+    /// Sends to a sample of the remaining devices first, spread evenly over the token list,
+    /// waits, and only sends to the rest if the sample's failure and unregistered rates are
+    /// within the limits of `canary`.
+    ///
+    /// A canary that does not pass halts the campaign with the rest of its devices still
+    /// queued, so it can be fixed, exported, or resumed with [`run_until`](Campaign::run_until)
+    /// once the problem is understood.
+    ///
+    /// # Returns
+    ///
+    /// A [`CanaryReport`] of the sample and, if it passed, of the rest of the campaign, or
+    /// the first error raised before APNs answered.
+    pub async fn run_with_canary(
+        &mut self,
+        client: &ApnsClient,
+        canary: &Canary,
+    ) -> Result<CanaryReport, ApnsError> {
+        let size = canary.sample_size(self.pending.len());
+        self.move_to_front(size);
+        let mut left = size;
+        let sample = self
+            .run_until(client, || {
+                let done = left == 0;
+                left = left.saturating_sub(1);
+                done
+            })
+            .await?;
+        if !canary.wait.is_zero() {
+            tokio::time::sleep(canary.wait).await;
+        }
+
+        let total = sample.total().max(1) as f64;
+        let failure_rate = (sample.failed + sample.throttled) as f64 / total;
+        let unregistered_rate = sample.unregistered as f64 / total;
+        let passed = failure_rate <= canary.max_failure_rate
+            && unregistered_rate <= canary.max_unregistered_rate;
+        let rest = if passed {
+            Some(self.run_until(client, || false).await?)
+        } else {
+            None
+        };
+        Ok(CanaryReport {
+            canary: sample,
+            failure_rate,
+            unregistered_rate,
+            passed,
+            rest,
+        })
+    }
+
+    /// Moves `count` pending tokens, spread evenly over the queue, to its front.
+    fn move_to_front(&mut self, count: usize) {
+        let len = self.pending.len();
+        if count == 0 || count >= len {
+            return;
+        }
+        let stride = len as f64 / count as f64;
+        let mut sample = VecDeque::with_capacity(len);
+        let mut rest = VecDeque::with_capacity(len - count);
+        for (i, token) in self.pending.drain(..).enumerate() {
+            if sample.len() < count && i == (sample.len() as f64 * stride) as usize {
+                sample.push_back(token);
+            } else {
+                rest.push_back(token);
+            }
+        }
+        sample.append(&mut rest);
+        self.pending = sample;
+    }
+
+    /// Writes the campaign's remaining tokens, variant assignments, and progress to a JSON
+    /// file.
+    ///
+    /// The file is written next to `path` first and then renamed into place, so an
+    /// interrupted export never leaves a truncated file behind.
+    pub fn export(&self, path: impl AsRef<Path>) -> Result<(), ApnsError> {
+        let path = path.as_ref();
+        let mut tmp = path.as_os_str().to_owned();
+        tmp.push(".tmp");
+
+        fs::write(&tmp, serde_json::to_vec_pretty(self)?)?;
+        fs::rename(&tmp, path)?;
+        Ok(())
+    }
+
+    /// Reads a campaign written by [`Campaign::export`].
+    ///
+    /// # Returns
+    ///
+    /// The campaign, or an `ApnsError` if the file cannot be read, was written by an
+    /// incompatible version of this crate, or holds a campaign [`Campaign::new`] would not
+    /// have created, such as one without variants or assigning a token to a missing one.
+    pub fn import(path: impl AsRef<Path>) -> Result<Self, ApnsError> {
+        let campaign: Campaign = serde_json::from_slice(&fs::read(path)?)?;
+        if campaign.version != STATE_VERSION {
+            return Err(ApnsError::Config(format!(
+                "unsupported campaign state version {}",
+                campaign.version
+            )));
+        }
+        validate_topic(&campaign.topic)?;
+        if campaign.variants.is_empty() {
+            return Err(ApnsError::Config(
+                "a campaign needs at least one variant".to_string(),
+            ));
+        }
+        if let Some(variant) = campaign
+            .assignments
+            .values()
+            .find(|&&variant| variant >= campaign.variants.len())
+        {
+            return Err(ApnsError::Config(format!(
+                "campaign state assigns a token to variant {} but has {} variants",
+                variant,
+                campaign.variants.len()
+            )));
+        }
+        Ok(campaign)
+    }
+}
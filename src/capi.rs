@@ -0,0 +1,191 @@
+//! A C-compatible API, enabled with the `capi` feature and declared in `include/apnrs.h`.
+
+use crate::client::ApnsClient;
+use crate::environment::Environment;
+use crate::options::SendOptions;
+use serde_json::Value;
+use std::cell::RefCell;
+use std::ffi::{c_char, c_int, CStr, CString};
+use std::ptr;
+use tokio::runtime::Runtime;
+
+thread_local! {
+    /// The message of the last call on this thread that failed.
+    static LAST_ERROR: RefCell<Option<CString>> = const { RefCell::new(None) };
+}
+
+/// A client and the runtime its sends are driven on, opaque to C callers.
+pub struct ApnrsClient {
+    runtime: Runtime,
+    client: ApnsClient,
+}
+
+/// Records `message` as the last error of this thread.
+fn set_last_error(message: impl Into<String>) {
+    // Interior NULs would truncate the message on the C side anyway
+    let message = message.into().replace('\0', " ");
+    let message = CString::new(message).expect("NULs were replaced");
+    LAST_ERROR.with(|last| *last.borrow_mut() = Some(message));
+}
+
+fn clear_last_error() {
+    LAST_ERROR.with(|last| *last.borrow_mut() = None);
+}
+
+/// Reads a NUL-terminated UTF-8 argument, recording an error if it is NULL or not UTF-8.
+///
+/// # Safety
+///
+/// `value` must be NULL or point to a NUL-terminated string.
+unsafe fn read_str<'a>(name: &str, value: *const c_char) -> Option<&'a str> {
+    if value.is_null() {
+        set_last_error(format!("{} is NULL", name));
+        return None;
+    }
+    match CStr::from_ptr(value).to_str() {
+        Ok(value) => Some(value),
+        Err(_) => {
+            set_last_error(format!("{} is not valid UTF-8", name));
+            None
+        }
+    }
+}
+
+/// Builds a client signing provider tokens with the `.p8` auth key at `auth_key_path`, sending
+/// to the sandbox if `sandbox` is true.
+///
+/// # Returns
+///
+/// The client, to be freed with `apnrs_client_free`, or NULL with `apnrs_last_error` set.
+///
+/// # Safety
+///
+/// The string arguments must be NULL or point to NUL-terminated strings.
+#[no_mangle]
+pub unsafe extern "C" fn apnrs_client_new(
+    auth_key_path: *const c_char,
+    team_id: *const c_char,
+    key_id: *const c_char,
+    sandbox: bool,
+) -> *mut ApnrsClient {
+    clear_last_error();
+    let (Some(auth_key_path), Some(team_id), Some(key_id)) = (
+        read_str("auth_key_path", auth_key_path),
+        read_str("team_id", team_id),
+        read_str("key_id", key_id),
+    ) else {
+        return ptr::null_mut();
+    };
+
+    let runtime = match Runtime::new() {
+        Ok(runtime) => runtime,
+        Err(e) => {
+            set_last_error(format!("failed to start the runtime: {}", e));
+            return ptr::null_mut();
+        }
+    };
+    let environment = if sandbox {
+        Environment::Sandbox
+    } else {
+        Environment::Production
+    };
+    let built = {
+        let _runtime = runtime.enter();
+        ApnsClient::builder()
+            .auth_key_path(auth_key_path)
+            .team_id(team_id)
+            .key_id(key_id)
+            .environment(environment)
+            .build()
+    };
+    match built {
+        Ok(client) => Box::into_raw(Box::new(ApnrsClient { runtime, client })),
+        Err(e) => {
+            set_last_error(e.to_string());
+            ptr::null_mut()
+        }
+    }
+}
+
+/// Sends the JSON payload `payload_json` to a device, blocking until APNs answers.
+///
+/// # Returns
+///
+/// The HTTP status APNs answered with: 200 if it accepted the notification, or an error status
+/// with the reason in `apnrs_last_error`. -1 if the notification could not be sent, with
+/// `apnrs_last_error` set.
+///
+/// # Safety
+///
+/// `client` must be NULL or a client from `apnrs_client_new` that has not been freed, and the
+/// string arguments NULL or pointers to NUL-terminated strings.
+#[no_mangle]
+pub unsafe extern "C" fn apnrs_send(
+    client: *const ApnrsClient,
+    device_token: *const c_char,
+    topic: *const c_char,
+    payload_json: *const c_char,
+) -> c_int {
+    clear_last_error();
+    let Some(client) = client.as_ref() else {
+        set_last_error("client is NULL");
+        return -1;
+    };
+    let (Some(device_token), Some(topic), Some(payload_json)) = (
+        read_str("device_token", device_token),
+        read_str("topic", topic),
+        read_str("payload_json", payload_json),
+    ) else {
+        return -1;
+    };
+    let payload: Value = match serde_json::from_str(payload_json) {
+        Ok(payload) => payload,
+        Err(e) => {
+            set_last_error(format!("payload_json is not valid JSON: {}", e));
+            return -1;
+        }
+    };
+
+    let sent = client.runtime.block_on(client.client.send_as(
+        device_token,
+        topic,
+        None,
+        None,
+        &payload,
+        &SendOptions::new(),
+    ));
+    match sent {
+        Ok(outcome) => {
+            let response = outcome.response;
+            if let Some(reason) = &response.error {
+                set_last_error(reason.to_string());
+            }
+            c_int::from(response.status.as_u16())
+        }
+        Err(e) => {
+            set_last_error(e.to_string());
+            -1
+        }
+    }
+}
+
+/// Returns the message of the last call on this thread that failed, or NULL if it succeeded.
+///
+/// The string is owned by the library and valid until the next call on the same thread.
+#[no_mangle]
+pub extern "C" fn apnrs_last_error() -> *const c_char {
+    LAST_ERROR.with(|last| last.borrow().as_ref().map_or(ptr::null(), |e| e.as_ptr()))
+}
+
+/// Frees a client from `apnrs_client_new`. Does nothing if `client` is NULL.
+///
+/// # Safety
+///
+/// `client` must be NULL or a client from `apnrs_client_new` that has not been freed, and
+/// must not be in use by another thread.
+#[no_mangle]
+pub unsafe extern "C" fn apnrs_client_free(client: *mut ApnrsClient) {
+    if !client.is_null() {
+        drop(Box::from_raw(client));
+    }
+}
@@ -0,0 +1,286 @@
+//! Channel management and broadcast pushes for Live Activities at scale.
+
+use crate::client::read_response;
+use crate::error::ApnsError;
+use crate::live_activity::LiveActivityPayload;
+use crate::push_type::PushType;
+use crate::telemetry::{self, RequestLabels};
+use crate::{ApnsClient, SendOutcome};
+use bytes::Bytes;
+use http::header::HeaderValue;
+use http::Response;
+use serde::{Deserialize, Serialize};
+use std::time::Instant;
+
+/// Whether APNs keeps the latest message sent to a channel for devices that were offline.
+///
+/// # Variants
+///
+/// * `NoStorage` - Messages are only delivered to devices that are reachable when they are sent.
+/// * `MostRecent` - The most recent message is stored and delivered once a device reconnects.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum MessageStoragePolicy {
+    NoStorage,
+    MostRecent,
+}
+
+impl MessageStoragePolicy {
+    /// Returns the value APNs uses for this policy.
+    pub fn as_u8(&self) -> u8 {
+        match self {
+            MessageStoragePolicy::NoStorage => 0,
+            MessageStoragePolicy::MostRecent => 1,
+        }
+    }
+
+    fn from_u8(value: u8) -> Option<Self> {
+        match value {
+            0 => Some(MessageStoragePolicy::NoStorage),
+            1 => Some(MessageStoragePolicy::MostRecent),
+            _ => None,
+        }
+    }
+}
+
+/// The configuration of a broadcast channel.
+///
+/// # Fields
+///
+/// * `channel_id` - The identifier APNs assigned to the channel.
+/// * `message_storage_policy` - Whether the latest message is stored for offline devices.
+/// * `push_type` - The push type of the channel's messages, as APNs reports it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChannelInfo {
+    pub channel_id: String,
+    pub message_storage_policy: MessageStoragePolicy,
+    pub push_type: String,
+}
+
+/// The body of a channel creation request and of a channel read response.
+#[derive(Serialize, Deserialize)]
+struct ChannelConfig {
+    #[serde(rename = "message-storage-policy")]
+    message_storage_policy: u8,
+    #[serde(rename = "push-type")]
+    push_type: String,
+}
+
+/// The body of a response listing an app's channels.
+#[derive(Deserialize)]
+struct ChannelList {
+    channels: Vec<String>,
+}
+
+/// A client for an app's broadcast channels, sharing the credentials, environment, and
+/// connection pool of an [`ApnsClient`].
+///
+/// Channels are created, read, and deleted through Apple's channel management API, and
+/// Live Activity payloads broadcast to a channel reach every activity subscribed to it.
+///
+/// # Example
+///
+/// ```rust,no_run
+/// use apnrs::{ApnsClient, LiveActivityPayload, MessageStoragePolicy};
+/// use serde_json::json;
+///
+/// # async fn run(client: ApnsClient) -> Result<(), apnrs::ApnsError> {
+/// let channels = client.channels("com.example.app");
+/// let channel_id = channels.create(MessageStoragePolicy::MostRecent).await?;
+///
+/// let payload = LiveActivityPayload::update(json!({ "home": 2, "away": 1 }));
+/// channels.broadcast(&channel_id, &payload).await?;
+///
+/// channels.delete(&channel_id).await?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone)]
+pub struct ChannelClient {
+    client: ApnsClient,
+    bundle_id: String,
+}
+
+impl ChannelClient {
+    /// Creates a client for the channels of the app with the given bundle ID.
+    pub fn new(client: ApnsClient, bundle_id: impl Into<String>) -> Self {
+        ChannelClient {
+            client,
+            bundle_id: bundle_id.into(),
+        }
+    }
+
+    /// Returns the bundle ID whose channels this client manages.
+    pub fn bundle_id(&self) -> &str {
+        &self.bundle_id
+    }
+
+    /// Creates a Live Activity channel.
+    ///
+    /// # Returns
+    ///
+    /// The ID of the new channel, or an `ApnsError` if APNs rejected the request.
+    pub async fn create(&self, policy: MessageStoragePolicy) -> Result<String, ApnsError> {
+        let body = serde_json::to_vec(&ChannelConfig {
+            message_storage_policy: policy.as_u8(),
+            push_type: "LiveActivity".to_string(),
+        })?;
+        let response = self
+            .request(reqwest::Method::POST, "channels", None, Some(body))
+            .await?;
+
+        response
+            .headers()
+            .get("apns-channel-id")
+            .and_then(|v| v.to_str().ok())
+            .map(str::to_string)
+            .ok_or_else(|| ApnsError::Config("APNs did not return an apns-channel-id".to_string()))
+    }
+
+    /// Reads the configuration of a channel.
+    ///
+    /// # Returns
+    ///
+    /// The channel's configuration, or an `ApnsError` if APNs rejected the request.
+    pub async fn get(&self, channel_id: &str) -> Result<ChannelInfo, ApnsError> {
+        let response = self
+            .request(reqwest::Method::GET, "channels", Some(channel_id), None)
+            .await?;
+        let config: ChannelConfig = serde_json::from_slice(response.body())?;
+
+        Ok(ChannelInfo {
+            channel_id: channel_id.to_string(),
+            message_storage_policy: MessageStoragePolicy::from_u8(config.message_storage_policy)
+                .ok_or_else(|| {
+                    ApnsError::Config(format!(
+                        "unknown message storage policy {}",
+                        config.message_storage_policy
+                    ))
+                })?,
+            push_type: config.push_type,
+        })
+    }
+
+    /// Lists the IDs of every channel of the app.
+    ///
+    /// # Returns
+    ///
+    /// The channel IDs, or an `ApnsError` if APNs rejected the request.
+    pub async fn list(&self) -> Result<Vec<String>, ApnsError> {
+        let response = self
+            .request(reqwest::Method::GET, "all-channels", None, None)
+            .await?;
+        let list: ChannelList = serde_json::from_slice(response.body())?;
+        Ok(list.channels)
+    }
+
+    /// Deletes a channel. Activities subscribed to it stop receiving broadcasts.
+    ///
+    /// # Returns
+    ///
+    /// `Ok(())`, or an `ApnsError` if APNs rejected the request.
+    pub async fn delete(&self, channel_id: &str) -> Result<(), ApnsError> {
+        self.request(reqwest::Method::DELETE, "channels", Some(channel_id), None)
+            .await?;
+        Ok(())
+    }
+
+    /// Broadcasts a Live Activity payload to every activity subscribed to a channel.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing either the outcome of the request or an `ApnsError`.
+    pub async fn broadcast<S: Serialize>(
+        &self,
+        channel_id: &str,
+        payload: &LiveActivityPayload<S>,
+    ) -> Result<SendOutcome, ApnsError> {
+        let body = serde_json::to_vec(payload)?;
+        let mut headers = self.client.app_headers(&self.bundle_id).await?;
+        headers.insert("apns-channel-id", HeaderValue::from_str(channel_id)?);
+        headers.insert(
+            "apns-push-type",
+            HeaderValue::from_static(PushType::LiveActivity.as_str()),
+        );
+        let url = self
+            .client
+            .environment_for(&self.bundle_id)
+            .broadcast_url(&self.bundle_id);
+
+        let labels = RequestLabels {
+            topic: &self.bundle_id,
+            push_type: Some(PushType::LiveActivity),
+        };
+        let started = Instant::now();
+        let response = match self
+            .client
+            .http()
+            .post(&url)
+            .headers(headers)
+            .body(body)
+            .send()
+            .await
+        {
+            Ok(response) => response,
+            Err(e) => {
+                telemetry::transport_failed(
+                    self.client.metric_labels(),
+                    &labels,
+                    &e,
+                    started.elapsed(),
+                );
+                return Err(e.into());
+            }
+        };
+        self.client.check_pins(&response)?;
+        let answer = read_response(response).await?;
+        telemetry::response_received(
+            self.client.metric_labels(),
+            &labels,
+            answer.response.status.as_u16(),
+            answer.response.error.as_ref(),
+            started.elapsed(),
+        );
+
+        Ok(answer.into_outcome(None))
+    }
+
+    /// Sends a channel management request, turning non-success answers into errors.
+    async fn request(
+        &self,
+        method: reqwest::Method,
+        resource: &str,
+        channel_id: Option<&str>,
+        body: Option<Vec<u8>>,
+    ) -> Result<Response<Bytes>, ApnsError> {
+        let mut headers = self.client.app_headers(&self.bundle_id).await?;
+        if let Some(channel_id) = channel_id {
+            headers.insert("apns-channel-id", HeaderValue::from_str(channel_id)?);
+        }
+        let url = format!(
+            "{}/1/apps/{}/{}",
+            self.client
+                .environment_for(&self.bundle_id)
+                .channel_management_url(),
+            self.bundle_id,
+            resource
+        );
+
+        let mut request = self.client.http().request(method, &url).headers(headers);
+        if let Some(body) = body {
+            request = request.body(body);
+        }
+        let response = request.send().await?;
+        self.client.check_pins(&response)?;
+        let answer = read_response(response).await?;
+
+        let response = answer.response;
+        if response.is_success() {
+            Ok(response.into_raw())
+        } else {
+            Err(ApnsError::Rejected {
+                status: response.status,
+                reason: response.error,
+            })
+        }
+    }
+}
@@ -0,0 +1,115 @@
+//! Deciding what to do with a notification that could not be delivered.
+
+use crate::{ApnsError, ErrorReason};
+use http::StatusCode;
+
+/// What should happen to a notification after a failed attempt.
+///
+/// # Variants
+///
+/// * `Retry` - The failure is transient; the notification may be sent again.
+/// * `Permanent` - Sending the same notification again will fail the same way.
+/// * `DeadToken` - The device token is no longer valid and should not be used again.
+/// * `Critical` - A permanent failure that points to a configuration or credential problem
+///   and should be brought to an operator's attention.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum FailureClass {
+    Retry,
+    Permanent,
+    DeadToken,
+    Critical,
+}
+
+/// Classifies failed sends for the retry engine.
+///
+/// Both methods default to the decisions of [`DefaultClassifier`], so an implementation
+/// only needs to override the cases it disagrees with.
+///
+/// # Example
+///
+/// ```rust
+/// use apnrs::{DefaultClassifier, ErrorReason, FailureClass, FailureClassifier, StatusCode};
+///
+/// struct PageOnTokenChurn;
+///
+/// impl FailureClassifier for PageOnTokenChurn {
+///     fn classify_rejection(&self, status: StatusCode, reason: Option<&ErrorReason>) -> FailureClass {
+///         match reason {
+///             Some(ErrorReason::TooManyProviderTokenUpdates) => FailureClass::Critical,
+///             Some(ErrorReason::Other(_)) => FailureClass::Retry,
+///             _ => DefaultClassifier.classify_rejection(status, reason),
+///         }
+///     }
+/// }
+/// ```
+pub trait FailureClassifier: Send + Sync {
+    /// Classifies a notification APNs answered with a non-success status.
+    fn classify_rejection(&self, status: StatusCode, reason: Option<&ErrorReason>) -> FailureClass {
+        default_rejection_class(status, reason)
+    }
+
+    /// Classifies a notification that failed before APNs answered.
+    fn classify_error(&self, error: &ApnsError) -> FailureClass {
+        default_error_class(error)
+    }
+}
+
+/// The classification used when none is configured.
+///
+/// * Unregistered, expired, and mismatched device tokens, and tokens the client's
+///   [`TokenStore`](crate::TokenStore) already marked invalid, are `DeadToken`.
+/// * Throttling, server errors, idle timeouts, expired provider tokens, transport errors,
+///   and exceeded send deadlines are `Retry`.
+/// * Invalid or missing credentials, forbidden requests, and servers failing certificate
+///   pinning are `Critical`.
+/// * Everything else, including reasons this crate does not know about, is `Permanent`.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct DefaultClassifier;
+
+impl FailureClassifier for DefaultClassifier {}
+
+fn default_rejection_class(status: StatusCode, reason: Option<&ErrorReason>) -> FailureClass {
+    match reason {
+        Some(ErrorReason::Unregistered)
+        | Some(ErrorReason::ExpiredToken)
+        | Some(ErrorReason::BadDeviceToken)
+        | Some(ErrorReason::DeviceTokenNotForTopic) => FailureClass::DeadToken,
+        Some(ErrorReason::TooManyRequests)
+        | Some(ErrorReason::TooManyProviderTokenUpdates)
+        | Some(ErrorReason::InternalServerError)
+        | Some(ErrorReason::ServiceUnavailable)
+        | Some(ErrorReason::Shutdown)
+        | Some(ErrorReason::IdleTimeout)
+        | Some(ErrorReason::ExpiredProviderToken) => FailureClass::Retry,
+        Some(ErrorReason::InvalidProviderToken)
+        | Some(ErrorReason::MissingProviderToken)
+        | Some(ErrorReason::UnrelatedKeyIdInToken)
+        | Some(ErrorReason::BadCertificate)
+        | Some(ErrorReason::BadCertificateEnvironment)
+        | Some(ErrorReason::Forbidden) => FailureClass::Critical,
+        Some(_) => FailureClass::Permanent,
+        None => match status {
+            StatusCode::GONE => FailureClass::DeadToken,
+            StatusCode::TOO_MANY_REQUESTS
+            | StatusCode::INTERNAL_SERVER_ERROR
+            | StatusCode::SERVICE_UNAVAILABLE => FailureClass::Retry,
+            StatusCode::FORBIDDEN => FailureClass::Critical,
+            _ => FailureClass::Permanent,
+        },
+    }
+}
+
+fn default_error_class(error: &ApnsError) -> FailureClass {
+    match error {
+        ApnsError::Http(_) | ApnsError::DeadlineExceeded(_) => FailureClass::Retry,
+        ApnsError::InvalidToken | ApnsError::InvalidDeviceToken(_) => FailureClass::DeadToken,
+        ApnsError::Rejected { status, reason } => default_rejection_class(*status, reason.as_ref()),
+        ApnsError::Io(_)
+        | ApnsError::Config(_)
+        | ApnsError::CertificateRequired(_)
+        | ApnsError::PinMismatch(_) => FailureClass::Critical,
+        #[cfg(feature = "signing")]
+        ApnsError::Jwt(_) | ApnsError::Signer(_) => FailureClass::Critical,
+        _ => FailureClass::Permanent,
+    }
+}
@@ -0,0 +1,3568 @@
+//! A reusable APNs client.
+
+use crate::audit::SigningAudit;
+use crate::channel::ChannelClient;
+use crate::classify::FailureClass;
+use crate::clock::{Clock, SystemClock};
+use crate::custom_data::{ApnsCustomData, CustomPayload};
+use crate::device_registry::{DeviceRegistry, UserDevice};
+use crate::dry_run::PreparedRequest;
+use crate::environment::Environment;
+use crate::error::{ApnsError, ErrorReason};
+use crate::events::{self, DeliveryEvent, EventPublisher, SendEvent};
+use crate::health::HealthReport;
+use crate::hooks::{AfterSend, BeforeSend, OutgoingRequest, SendContext, SendHook};
+use crate::live_activity::{LiveActivityPayload, LiveActivityStartPayload};
+use crate::localize::{LocaleResolution, LocalizedAlert};
+use crate::mdm::MdmNotification;
+use crate::notification::{Notification, NotificationError, Priority};
+use crate::options::{Credentials, SendOptions};
+use crate::pinning::{CertificatePin, PublicKeyPins};
+use crate::prepared::PreparedPayload;
+use crate::privacy::TokenHasher;
+#[cfg(feature = "logging")]
+use crate::privacy::{mask_token, wire_request, wire_response, WIRE_LOG_TARGET};
+use crate::profile::Profile;
+use crate::proxy::ProxyConfig;
+use crate::push_type::PushType;
+use crate::registry::AppRegistry;
+use crate::request::{new_apns_id, PushRequest};
+use crate::response::ApnsResponse;
+#[cfg(feature = "schema")]
+use crate::schema::CustomDataSchema;
+use crate::secrets::AuthKeySecret;
+use crate::shard::{ConnectionStrategy, Shards};
+use crate::shutdown::{Lifecycle, ShutdownReport};
+use crate::signer::{LocalSigner, TokenSigner};
+use crate::simulator::Simulator;
+use crate::stats::{ClientStats, StatsRecorder};
+use crate::telemetry::{self, Labeler, MetricLabels, RequestLabels};
+use crate::token::TokenCache;
+use crate::token_store::{MemoryTokenStore, TokenStore};
+use crate::topic::{bundle_id, validate_request_topic, validate_topic, validate_website_push_id};
+use crate::trace_context::TraceContext;
+use crate::transport::insert_option_headers;
+use crate::trim::{TrimReport, TrimStrategy, MAX_PAYLOAD_SIZE};
+use crate::web_push::WebPushPayload;
+use crate::ApnsPayload;
+use bytes::Bytes;
+use futures_util::future;
+use futures_util::stream::{self, BoxStream, StreamExt};
+use http::header::{HeaderMap, HeaderValue, CONTENT_TYPE};
+use http::{Response, StatusCode};
+use serde::Serialize;
+use serde_json::value::RawValue;
+use std::collections::HashMap;
+use std::env;
+use std::fs;
+use std::future::Future;
+use std::io;
+use std::path::Path;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::{broadcast, Semaphore};
+use tokio::task::JoinHandle;
+use zeroize::Zeroizing;
+
+/// The most time [`ApnsClient::send`] may spend preparing a request once its provider token
+/// is cached, before the request is handed to the connection.
+///
+/// The `send_overhead` benchmark (`cargo bench --bench send_overhead`) fails if a dry-run
+/// `send`, which goes through the whole pipeline but the network, takes longer than this on
+/// average.
+pub const FAST_PATH_BUDGET: Duration = Duration::from_micros(50);
+
+/// How long establishing a connection to APNs may take by default.
+pub const DEFAULT_CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// How long a single request to APNs may take by default, from sending it to reading the
+/// whole response.
+pub const DEFAULT_REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// How long a whole send may take by default, including signing a provider token and
+/// resending a trimmed payload.
+pub const DEFAULT_SEND_DEADLINE: Duration = Duration::from_secs(60);
+
+/// How often an HTTP/2 ping is sent on each connection by default, so NATs and firewalls
+/// do not drop it while idle.
+pub const DEFAULT_KEEPALIVE_INTERVAL: Duration = Duration::from_secs(60);
+
+/// How many times a request is resent by default after its connection to APNs is lost.
+pub const DEFAULT_CONNECTION_RETRIES: u32 = 2;
+
+/// How long an unused connection stays in the pool by default when keepalive pings are
+/// turned off; with pings, idle connections are kept until a ping goes unanswered.
+pub const DEFAULT_POOL_IDLE_TIMEOUT: Duration = Duration::from_secs(90);
+
+/// How long to wait for a ping's acknowledgement by default before the connection is
+/// considered dead and replaced.
+pub const DEFAULT_KEEPALIVE_TIMEOUT: Duration = Duration::from_secs(20);
+
+/// The device token [`ApnsClient::verify_credentials`] posts to: well-formed, but never
+/// issued to a device, so APNs answers `BadDeviceToken` once the credentials pass.
+const VERIFICATION_DEVICE_TOKEN: &str =
+    "0000000000000000000000000000000000000000000000000000000000000000";
+
+/// The payload [`ApnsClient::verify_credentials`] posts, a silent background push.
+const VERIFICATION_PAYLOAD: &str = r#"{"aps":{"content-available":1}}"#;
+
+/// The result of a successfully delivered request.
+///
+/// # Fields
+///
+/// * `response` - The response from the APNs server, including why it rejected the
+///   notification, if it did.
+/// * `trimmed` - What the trimming strategy removed, if the payload had to be shrunk and resent.
+/// * `failure` - How an [`ApnsSender`](crate::ApnsSender) classified the rejection; always
+///   `None` for accepted notifications and for sends made directly through the client.
+/// * `locale` - For [`ApnsClient::send_localized`], which translation of the alert was sent.
+/// * `dry_run` - For a dry run, the request that would have been sent; APNs was not contacted
+///   and `response` is a synthetic `200 OK`.
+/// * `fallback` - With [`environment_fallback`](ApnsClientBuilder::environment_fallback), the
+///   environment the notification was resent to after the configured one answered
+///   `BadDeviceToken`; `response` is then the answer of that environment.
+#[derive(Debug)]
+pub struct SendOutcome {
+    pub response: ApnsResponse,
+    pub trimmed: Option<TrimReport>,
+    pub failure: Option<FailureClass>,
+    pub locale: Option<LocaleResolution>,
+    pub dry_run: Option<PreparedRequest>,
+    pub fallback: Option<Environment>,
+}
+
+/// A client for sending push notifications that holds credentials and a pooled HTTP/2 connection.
+///
+/// Clients are cheap to clone and can be shared between tasks.
+///
+/// # Example
+///
+/// ```rust,no_run
+/// use apnrs::{ApnsClient, ApnsPayload, Aps, Badge, Environment, Sound};
+///
+/// # async fn run() -> Result<(), apnrs::ApnsError> {
+/// let client = ApnsClient::builder()
+///     .auth_key_path("path/to/auth/key")
+///     .team_id("TEAM_ID")
+///     .key_id("KEY_ID")
+///     .environment(Environment::Sandbox)
+///     .build()?;
+///
+/// let payload = ApnsPayload {
+///     aps: Aps {
+///         alert: "Hello, world!".to_string(),
+///         content_available: None,
+///         badge: Badge::Unchanged,
+///         sound: Sound::None,
+///         category: None,
+///         thread_id: None,
+///         summary_arg: None,
+///         summary_arg_count: None,
+///         launch_image: None,
+///         alert_fields: Default::default(),
+///         extra_fields: Default::default(),
+///     },
+///     custom_key: None,
+///     custom_fields: Default::default(),
+/// };
+///
+/// let outcome = client.send("DEVICE_TOKEN", "com.example.app", &payload).await?;
+/// println!("Notification sent: {:?}", outcome.response.status);
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone)]
+pub struct ApnsClient {
+    inner: Arc<ClientInner>,
+}
+
+struct ClientInner {
+    tokens: Option<TokenCache>,
+    credentials: HashMap<String, Credentials>,
+    registry: AppRegistry,
+    profiles: HashMap<String, Profile>,
+    #[cfg(feature = "schema")]
+    custom_data_schema: Option<CustomDataSchema>,
+    has_certificate: bool,
+    environment: Environment,
+    trim_strategy: Option<Arc<dyn TrimStrategy>>,
+    http: Shards,
+    /// Caps the requests in flight on all clones of the client together, if set.
+    in_flight: Option<Semaphore>,
+    public_key_pins: PublicKeyPins,
+    send_deadline: Duration,
+    connection_retries: u32,
+    dry_run: bool,
+    simulator: Option<Simulator>,
+    environment_fallback: bool,
+    auto_priority: bool,
+    #[cfg(feature = "logging")]
+    wire_logging: bool,
+    lifecycle: Arc<Lifecycle>,
+    stats: StatsRecorder,
+    events: EventPublisher,
+    metric_labels: Labeler,
+    unregistered_hooks: Vec<UnregisteredHook>,
+    send_hooks: Vec<Arc<dyn SendHook>>,
+    token_store: Arc<dyn TokenStore>,
+    device_registry: Option<Arc<dyn DeviceRegistry>>,
+}
+
+/// Returns an HTTP/2 client builder using the TLS backend selected by the crate's features,
+/// preferring rustls when both `rustls` and `native-tls` are enabled.
+pub(crate) fn http_builder() -> reqwest::ClientBuilder {
+    let builder = reqwest::Client::builder().http2_prior_knowledge();
+    #[cfg(feature = "rustls")]
+    let builder = builder.use_rustls_tls();
+    builder
+}
+
+/// Loads a PKCS#12 client certificate for the TLS backend in use.
+#[cfg(not(feature = "rustls"))]
+fn tls_identity(der: &[u8], password: &str) -> Result<reqwest::Identity, ApnsError> {
+    Ok(reqwest::Identity::from_pkcs12_der(der, password)?)
+}
+
+/// Loads a PKCS#12 client certificate for the TLS backend in use.
+///
+/// rustls only reads PEM, so the archive is converted with OpenSSL first.
+#[cfg(feature = "rustls")]
+fn tls_identity(der: &[u8], password: &str) -> Result<reqwest::Identity, ApnsError> {
+    let invalid = |e: openssl::error::ErrorStack| {
+        ApnsError::Config(format!("invalid PKCS#12 certificate: {}", e))
+    };
+    let archive = openssl::pkcs12::Pkcs12::from_der(der)
+        .and_then(|archive| archive.parse2(password))
+        .map_err(invalid)?;
+    let (Some(key), Some(certificate)) = (archive.pkey, archive.cert) else {
+        return Err(ApnsError::Config(
+            "PKCS#12 certificate has no private key or certificate".to_string(),
+        ));
+    };
+
+    let mut pem = Zeroizing::new(key.private_key_to_pem_pkcs8().map_err(invalid)?);
+    pem.extend(certificate.to_pem().map_err(invalid)?);
+    for issuer in archive.ca.into_iter().flatten() {
+        pem.extend(issuer.to_pem().map_err(invalid)?);
+    }
+    Ok(reqwest::Identity::from_pem(&pem)?)
+}
+
+/// A PKCS#12 client certificate and its password, wiped from memory when dropped.
+type Pkcs12 = (Zeroizing<Vec<u8>>, Zeroizing<String>);
+
+/// Where the passphrase of an encrypted auth key is read from when the client is built.
+enum KeyPassphrase {
+    Callback(Box<dyn FnOnce() -> String + Send>),
+    Env(String),
+}
+
+impl KeyPassphrase {
+    fn resolve(self) -> Result<Zeroizing<String>, ApnsError> {
+        match self {
+            KeyPassphrase::Callback(callback) => Ok(Zeroizing::new(callback())),
+            KeyPassphrase::Env(name) => env::var(&name).map(Zeroizing::new).map_err(|_| {
+                ApnsError::Config(format!("auth key passphrase variable {} is not set", name))
+            }),
+        }
+    }
+}
+
+/// A callback run with the device token and timestamp of an unregistered device.
+type UnregisteredHook = Arc<dyn Fn(&str, Option<u64>) + Send + Sync>;
+
+/// A builder for [`ApnsClient`].
+#[derive(Default)]
+pub struct ApnsClientBuilder {
+    key: Option<Zeroizing<Vec<u8>>>,
+    key_path: Option<String>,
+    key_passphrase: Option<KeyPassphrase>,
+    team_id: Option<String>,
+    key_id: Option<String>,
+    signer: Option<Arc<dyn TokenSigner>>,
+    clock: Option<Arc<dyn Clock>>,
+    environment: Option<Environment>,
+    trim_strategy: Option<Arc<dyn TrimStrategy>>,
+    unregistered_hooks: Vec<UnregisteredHook>,
+    send_hooks: Vec<Arc<dyn SendHook>>,
+    token_store: Option<Arc<dyn TokenStore>>,
+    device_registry: Option<Arc<dyn DeviceRegistry>>,
+    metric_labels: Option<MetricLabels>,
+    signing_audit: Option<Arc<dyn SigningAudit>>,
+    certificate: Option<Pkcs12>,
+    certificate_path: Option<(String, String)>,
+    #[cfg(all(feature = "keychain", target_os = "macos"))]
+    key_keychain: Option<String>,
+    #[cfg(all(feature = "keychain", target_os = "macos"))]
+    certificate_keychain: Option<String>,
+    token_hasher: Option<TokenHasher>,
+    credentials: HashMap<String, Credentials>,
+    registry: AppRegistry,
+    profiles: HashMap<String, Profile>,
+    #[cfg(feature = "schema")]
+    custom_data_schema: Option<CustomDataSchema>,
+    pins: Vec<CertificatePin>,
+    proxy: Option<ProxyConfig>,
+    connect_timeout: Option<Duration>,
+    request_timeout: Option<Duration>,
+    send_deadline: Option<Duration>,
+    keepalive_interval: Option<Option<Duration>>,
+    keepalive_timeout: Option<Duration>,
+    connection_retries: Option<u32>,
+    pool_idle_timeout: Option<Option<Duration>>,
+    pool_max_idle_per_host: Option<usize>,
+    connections: Option<usize>,
+    connection_strategy: ConnectionStrategy,
+    max_in_flight: Option<usize>,
+    preconnect: bool,
+    dry_run: bool,
+    simulator: Option<Simulator>,
+    environment_fallback: bool,
+    auto_priority: bool,
+    #[cfg(feature = "logging")]
+    wire_logging: bool,
+    http_client: Option<reqwest::Client>,
+}
+
+impl ApnsClientBuilder {
+    /// Reads the APNs auth key (`.p8`) from the given path when the client is built.
+    pub fn auth_key_path(mut self, path: impl Into<String>) -> Self {
+        self.key_path = Some(path.into());
+        self
+    }
+
+    /// Uses the given APNs auth key, PEM-encoded or DER-encoded as PKCS#8 or SEC1; the
+    /// format is detected when the client is built.
+    pub fn auth_key_pem(mut self, pem: impl Into<Vec<u8>>) -> Self {
+        self.key = Some(Zeroizing::new(pem.into()));
+        self
+    }
+
+    /// Uses the auth key, team ID, and key ID fetched from a secrets manager with a
+    /// [`SecretSource`](crate::SecretSource).
+    pub fn auth_key_secret(mut self, secret: AuthKeySecret) -> Self {
+        self.key = Some(secret.key_pem);
+        self.team_id = Some(secret.team_id);
+        self.key_id = Some(secret.key_id);
+        self
+    }
+
+    /// Decrypts an auth key stored as encrypted PKCS#8 with the passphrase `passphrase`
+    /// returns, called once when the client is built; see
+    /// [`LocalSigner::from_encrypted_pem`].
+    ///
+    /// # Example
+    ///
+    /// ```rust,no_run
+    /// use apnrs::ApnsClient;
+    ///
+    /// # fn prompt(_: &str) -> String { unimplemented!() }
+    /// # fn run() -> Result<(), apnrs::ApnsError> {
+    /// let client = ApnsClient::builder()
+    ///     .auth_key_path("keys/AuthKey.enc.p8")
+    ///     .auth_key_passphrase(|| prompt("Auth key passphrase: "))
+    ///     .team_id("TEAM_ID")
+    ///     .key_id("KEY_ID")
+    ///     .build()?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn auth_key_passphrase<F>(mut self, passphrase: F) -> Self
+    where
+        F: FnOnce() -> String + Send + 'static,
+    {
+        self.key_passphrase = Some(KeyPassphrase::Callback(Box::new(passphrase)));
+        self
+    }
+
+    /// Decrypts an auth key stored as encrypted PKCS#8 with the passphrase in the
+    /// environment variable `name`, read when the client is built.
+    pub fn auth_key_passphrase_env(mut self, name: impl Into<String>) -> Self {
+        self.key_passphrase = Some(KeyPassphrase::Env(name.into()));
+        self
+    }
+
+    /// Authenticates with a PKCS#12 (`.p12`) client certificate and its password, as
+    /// required for MDM pushes.
+    ///
+    /// A client may have both a certificate and an auth key; provider tokens are then used
+    /// for every push type except those APNs only accepts with a certificate.
+    pub fn certificate_pkcs12(
+        mut self,
+        der: impl Into<Vec<u8>>,
+        password: impl Into<String>,
+    ) -> Self {
+        self.certificate = Some((Zeroizing::new(der.into()), Zeroizing::new(password.into())));
+        self
+    }
+
+    /// Reads a PKCS#12 (`.p12`) client certificate from the given path when the client is
+    /// built; see [`certificate_pkcs12`](ApnsClientBuilder::certificate_pkcs12).
+    pub fn certificate_pkcs12_path(
+        mut self,
+        path: impl Into<String>,
+        password: impl Into<String>,
+    ) -> Self {
+        self.certificate_path = Some((path.into(), password.into()));
+        self
+    }
+
+    /// Reads the APNs auth key labelled `label` from the macOS Keychain when the client is
+    /// built, instead of from a `.p8` file on disk.
+    ///
+    /// Import the key as extractable first, for example with
+    /// `security import AuthKey_ABC123.p8 -t priv -f openssl -x -l "APNs ABC123"`.
+    #[cfg(all(feature = "keychain", target_os = "macos"))]
+    pub fn auth_key_keychain(mut self, label: impl Into<String>) -> Self {
+        self.key_keychain = Some(label.into());
+        self
+    }
+
+    /// Reads the client certificate identity labelled `label`, with its private key, from
+    /// the macOS Keychain when the client is built; see
+    /// [`certificate_pkcs12`](ApnsClientBuilder::certificate_pkcs12).
+    #[cfg(all(feature = "keychain", target_os = "macos"))]
+    pub fn certificate_keychain(mut self, label: impl Into<String>) -> Self {
+        self.certificate_keychain = Some(label.into());
+        self
+    }
+
+    /// Registers the credentials of another team or key under `name`, which is either a
+    /// bundle ID or a logical app name.
+    ///
+    /// Requests to a topic whose bundle ID is registered, or sent with
+    /// [`SendOptions::app`](crate::SendOptions::app) naming a registered app, are signed with
+    /// those credentials; one provider token is cached per key. Other requests use the
+    /// client's own auth key, which can be left out if every request matches a registration.
+    ///
+    /// # Example
+    ///
+    /// ```rust,no_run
+    /// use apnrs::{ApnsClient, Credentials};
+    ///
+    /// # fn run() -> Result<(), apnrs::ApnsError> {
+    /// let client = ApnsClient::builder()
+    ///     .credentials(
+    ///         "com.acme.shop",
+    ///         Credentials::from_key_path("keys/acme.p8", "ACME_TEAM", "ACME_KEY")?,
+    ///     )
+    ///     .credentials(
+    ///         "globex",
+    ///         Credentials::from_key_path("keys/globex.p8", "GLOBEX_TEAM", "GLOBEX_KEY")?,
+    ///     )
+    ///     .build()?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn credentials(mut self, name: impl Into<String>, credentials: Credentials) -> Self {
+        self.credentials.insert(name.into(), credentials);
+        self
+    }
+
+    /// Routes each notification to the credentials and environment registered for its
+    /// topic's app, so one client serves many tenants; see [`AppRegistry`].
+    ///
+    /// Registered apps take precedence over apps registered with
+    /// [`credentials`](ApnsClientBuilder::credentials), and per-send
+    /// [`SendOptions`] credentials or app names take precedence over both. The client's own
+    /// auth key can be left out if every request goes to a registered app.
+    pub fn app_registry(mut self, registry: AppRegistry) -> Self {
+        self.registry = registry;
+        self
+    }
+
+    /// Registers `profile` under `name`, replacing any profile registered under it before,
+    /// for sending with [`ApnsClient::send_with_profile`].
+    ///
+    /// A profile with credentials counts as registered credentials, so the client's own
+    /// auth key can be left out if every send goes through a profile that has them.
+    ///
+    /// # Example
+    ///
+    /// ```rust,no_run
+    /// use apnrs::{ApnsClient, Credentials, Environment, Priority, Profile, PushType};
+    ///
+    /// # fn run() -> Result<(), apnrs::ApnsError> {
+    /// let client = ApnsClient::builder()
+    ///     .profile(
+    ///         "consumer-app",
+    ///         Profile::new("com.example.app")
+    ///             .priority(Priority::Immediate)
+    ///             .credentials(Credentials::from_key_path("keys/consumer.p8", "TEAM", "KEY")?),
+    ///     )
+    ///     .profile(
+    ///         "calls",
+    ///         Profile::new("com.example.phone")
+    ///             .push_type(PushType::Voip)
+    ///             .environment(Environment::Sandbox)
+    ///             .credentials(Credentials::from_key_path("keys/phone.p8", "TEAM", "KEY")?),
+    ///     )
+    ///     .build()?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn profile(mut self, name: impl Into<String>, profile: Profile) -> Self {
+        self.profiles.insert(name.into(), profile);
+        self
+    }
+
+    /// Checks the custom data of every payload against `schema` before sending, failing the
+    /// send with [`ApnsError::InvalidCustomData`] instead of delivering data the app cannot
+    /// parse.
+    ///
+    /// # Example
+    ///
+    /// ```rust,no_run
+    /// use apnrs::{ApnsClient, CustomDataSchema};
+    ///
+    /// # fn run() -> Result<(), Box<dyn std::error::Error>> {
+    /// let schema: serde_json::Value =
+    ///     serde_json::from_str(&std::fs::read_to_string("schemas/order-push.json")?)?;
+    ///
+    /// let client = ApnsClient::builder()
+    ///     .auth_key_path("path/to/auth/key")
+    ///     .team_id("TEAM_ID")
+    ///     .key_id("KEY_ID")
+    ///     .custom_data_schema(CustomDataSchema::new(&schema)?)
+    ///     .build()?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[cfg(feature = "schema")]
+    pub fn custom_data_schema(mut self, schema: CustomDataSchema) -> Self {
+        self.custom_data_schema = Some(schema);
+        self
+    }
+
+    /// Pins a root certificate or public key the APNs server must present, to detect TLS
+    /// interception on the way to Apple; call it once per pin to allow several during a
+    /// rotation.
+    ///
+    /// Pinned roots replace the system trust store, so the handshake fails for a chain
+    /// issued by any other authority. Pinned public keys are checked against the server's
+    /// leaf certificate on every response, which fails with [`ApnsError::PinMismatch`] when
+    /// none matches; the request has been sent by then, so pin a root as well to keep
+    /// requests off intercepted connections entirely. Pins also apply to broadcast and
+    /// channel management requests.
+    pub fn pin(mut self, pin: CertificatePin) -> Self {
+        self.pins.push(pin);
+        self
+    }
+
+    /// Signs provider tokens with `signer` instead of an auth key held in memory, so the key
+    /// can stay in a KMS, HSM, or PKCS#11 token.
+    ///
+    /// The team ID is still required; the key ID comes from the signer.
+    ///
+    /// # Example
+    ///
+    /// ```rust,no_run
+    /// use apnrs::{ApnsClient, TokenSigner};
+    ///
+    /// # fn run(kms_signer: impl TokenSigner + 'static) -> Result<(), apnrs::ApnsError> {
+    /// let client = ApnsClient::builder()
+    ///     .token_signer(kms_signer)
+    ///     .team_id("TEAM_ID")
+    ///     .build()?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn token_signer(mut self, signer: impl TokenSigner + 'static) -> Self {
+        self.signer = Some(Arc::new(signer));
+        self
+    }
+
+    /// Sets the clock the client's provider tokens are issued at (defaults to the
+    /// [`SystemClock`]), to correct for a skewed host clock or to sign reproducible tokens in
+    /// tests.
+    ///
+    /// Registered [`Credentials`] keep their own clock; see [`Credentials::clock`].
+    pub fn clock(mut self, clock: impl Clock + 'static) -> Self {
+        self.clock = Some(Arc::new(clock));
+        self
+    }
+
+    /// Sends every request, including broadcast and channel management requests, through
+    /// `proxy` instead of connecting to APNs directly.
+    pub fn proxy(mut self, proxy: ProxyConfig) -> Self {
+        self.proxy = Some(proxy);
+        self
+    }
+
+    /// Sends through `client` instead of an HTTP client built by the builder, so a reqwest
+    /// client an application has already tuned is shared rather than configured twice.
+    ///
+    /// The client must speak HTTP/2 to APNs, for example by being built with
+    /// `http2_prior_knowledge`. Its proxy, timeouts, keepalive, pool, client certificate,
+    /// and trusted roots are used as they are, so setting any of them on this builder as
+    /// well, or pinning certificates, makes [`build`](ApnsClientBuilder::build) fail with
+    /// [`ApnsError::Config`]. The send deadline still applies.
+    ///
+    /// # Example
+    ///
+    /// ```rust,no_run
+    /// use apnrs::ApnsClient;
+    /// use std::time::Duration;
+    ///
+    /// # fn run() -> Result<(), Box<dyn std::error::Error>> {
+    /// let http = apnrs::reqwest::Client::builder()
+    ///     .http2_prior_knowledge()
+    ///     .proxy(apnrs::reqwest::Proxy::https("http://egress.internal:3128")?)
+    ///     .timeout(Duration::from_secs(10))
+    ///     .build()?;
+    ///
+    /// let client = ApnsClient::builder()
+    ///     .auth_key_path("path/to/auth/key")
+    ///     .team_id("TEAM_ID")
+    ///     .key_id("KEY_ID")
+    ///     .http_client(http)
+    ///     .build()?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn http_client(mut self, client: reqwest::Client) -> Self {
+        self.http_client = Some(client);
+        self
+    }
+
+    /// Sets how long establishing a connection to APNs may take (defaults to
+    /// [`DEFAULT_CONNECT_TIMEOUT`]).
+    pub fn connect_timeout(mut self, timeout: Duration) -> Self {
+        self.connect_timeout = Some(timeout);
+        self
+    }
+
+    /// Sets how long a single request may take, from sending it to reading the whole
+    /// response (defaults to [`DEFAULT_REQUEST_TIMEOUT`]).
+    ///
+    /// Requests that time out fail with [`ApnsError::Http`], so a hung connection cannot
+    /// stall a sender's workers.
+    pub fn request_timeout(mut self, timeout: Duration) -> Self {
+        self.request_timeout = Some(timeout);
+        self
+    }
+
+    /// Sets how long a whole send may take, including signing a provider token and resending
+    /// a trimmed payload (defaults to [`DEFAULT_SEND_DEADLINE`]).
+    ///
+    /// Sends that miss the deadline fail with [`ApnsError::DeadlineExceeded`]. A sender's
+    /// retries each get their own deadline.
+    pub fn send_deadline(mut self, deadline: Duration) -> Self {
+        self.send_deadline = Some(deadline);
+        self
+    }
+
+    /// Sets how often an HTTP/2 ping is sent on each connection, including idle ones, or
+    /// `None` to send none (defaults to [`DEFAULT_KEEPALIVE_INTERVAL`]).
+    ///
+    /// NATs and firewalls silently drop connections that stay idle too long, which otherwise
+    /// only shows up as a failure on the next send. With pings, idle connections are kept
+    /// open, and a connection whose ping goes unanswered is closed and replaced before it
+    /// is used.
+    pub fn keepalive_interval(mut self, interval: Option<Duration>) -> Self {
+        self.keepalive_interval = Some(interval);
+        self
+    }
+
+    /// Sets how long to wait for a ping's acknowledgement before the connection is closed
+    /// (defaults to [`DEFAULT_KEEPALIVE_TIMEOUT`]).
+    pub fn keepalive_timeout(mut self, timeout: Duration) -> Self {
+        self.keepalive_timeout = Some(timeout);
+        self
+    }
+
+    /// Sets how many times a request is resent after its connection to APNs is lost, for
+    /// example to a `GOAWAY` or a reset, or `0` to never resend (defaults to
+    /// [`DEFAULT_CONNECTION_RETRIES`]).
+    ///
+    /// The lost connection is dropped from the pool, so the request goes out on a new one;
+    /// only a failure that persists is returned, as [`ApnsError::Http`]. APNs may have
+    /// processed a request whose connection was lost after it was sent, so such a request is
+    /// only resent with the same `apns-id` it was first sent with, set with
+    /// [`SendOptions::apns_id`] and assigned to every request by an
+    /// [`ApnsSender`](crate::ApnsSender). Requests APNs refused unprocessed are resent
+    /// either way.
+    ///
+    /// # Example
+    ///
+    /// ```rust,no_run
+    /// use apnrs::ApnsClient;
+    ///
+    /// # fn run() -> Result<(), apnrs::ApnsError> {
+    /// let client = ApnsClient::builder()
+    ///     .auth_key_path("path/to/key")
+    ///     .team_id("TEAM_ID")
+    ///     .key_id("KEY_ID")
+    ///     .connection_retries(4)
+    ///     .build()?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn connection_retries(mut self, retries: u32) -> Self {
+        self.connection_retries = Some(retries);
+        self
+    }
+
+    /// Sets how long an unused connection stays in the pool before it is closed, or `None` to
+    /// keep it until it fails (defaults to `None` with keepalive pings, and to
+    /// [`DEFAULT_POOL_IDLE_TIMEOUT`] without).
+    pub fn pool_idle_timeout(mut self, timeout: Option<Duration>) -> Self {
+        self.pool_idle_timeout = Some(timeout);
+        self
+    }
+
+    /// Sets how many unused connections to each APNs host are kept in the pool (defaults to
+    /// no limit).
+    ///
+    /// One HTTP/2 connection carries many requests at once, so a handful is plenty even for
+    /// high-volume senders.
+    pub fn pool_max_idle_per_host(mut self, max: usize) -> Self {
+        self.pool_max_idle_per_host = Some(max);
+        self
+    }
+
+    /// Keeps `connections` parallel HTTP/2 connections to each APNs host and spreads
+    /// requests across them (defaults to 1).
+    ///
+    /// One connection carries only as many requests at once as APNs allows streams on it,
+    /// all over a single TCP path, which caps the throughput of very high-volume senders.
+    /// Each connection has its own pool, so settings such as keepalive pings and the
+    /// certificate apply to every one of them, and [`connect`](ApnsClient::connect) opens
+    /// them all.
+    ///
+    /// # Example
+    ///
+    /// ```rust,no_run
+    /// use apnrs::{ApnsClient, ConnectionStrategy};
+    ///
+    /// # fn run() -> Result<(), apnrs::ApnsError> {
+    /// let client = ApnsClient::builder()
+    ///     .auth_key_path("path/to/key")
+    ///     .team_id("TEAM_ID")
+    ///     .key_id("KEY_ID")
+    ///     .connections(8)
+    ///     .connection_strategy(ConnectionStrategy::LeastLoaded)
+    ///     .build()?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn connections(mut self, connections: usize) -> Self {
+        self.connections = Some(connections.max(1));
+        self
+    }
+
+    /// Sets how requests are spread over the [`connections`](ApnsClientBuilder::connections)
+    /// (defaults to [`ConnectionStrategy::RoundRobin`]).
+    pub fn connection_strategy(mut self, strategy: ConnectionStrategy) -> Self {
+        self.connection_strategy = strategy;
+        self
+    }
+
+    /// Caps the requests to APNs in flight at once across every task sharing the client and
+    /// its clones, senders and fan-outs included (defaults to no limit).
+    ///
+    /// Sends beyond the limit wait for a slot, and the wait counts against the
+    /// [send deadline](ApnsClientBuilder::send_deadline). Unlike the concurrency of a
+    /// single [`FanOut`](crate::FanOut) or the workers of a sender, the limit holds however
+    /// many of them the application runs, so together they cannot overwhelm the connection
+    /// or trip APNs' limits.
+    ///
+    /// # Example
+    ///
+    /// ```rust,no_run
+    /// use apnrs::ApnsClient;
+    ///
+    /// # fn run() -> Result<(), apnrs::ApnsError> {
+    /// let client = ApnsClient::builder()
+    ///     .auth_key_path("path/to/key")
+    ///     .team_id("TEAM_ID")
+    ///     .key_id("KEY_ID")
+    ///     .max_in_flight(500)
+    ///     .build()?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn max_in_flight(mut self, max: usize) -> Self {
+        self.max_in_flight = Some(max.max(1));
+        self
+    }
+
+    /// Opens the connection to APNs in the background as soon as the client is built, so the
+    /// first send does not pay for the TCP and TLS handshakes (defaults to `false`).
+    ///
+    /// Has no effect outside of a tokio runtime; call [`ApnsClient::connect`] to open the
+    /// connection and wait for it instead.
+    pub fn preconnect(mut self, preconnect: bool) -> Self {
+        self.preconnect = preconnect;
+        self
+    }
+
+    /// Prepares every request in full, signing the provider token and building the headers,
+    /// but returns it in [`SendOutcome::dry_run`] instead of sending it (defaults to `false`).
+    ///
+    /// For staging environments and CI: sends answer a synthetic `200 OK` and never contact
+    /// APNs, so they are not counted in stats, events, or metrics. Channel management
+    /// requests are still sent. Use [`SendOptions::dry_run`] for a single send instead.
+    pub fn dry_run(mut self, dry_run: bool) -> Self {
+        self.dry_run = dry_run;
+        self
+    }
+
+    /// Writes every request's payload as an `.apns` file for the iOS Simulator, and pushes it
+    /// to one if the [`Simulator`] is set to, instead of sending it to APNs.
+    ///
+    /// For local development without a device: requests are prepared in full and answered
+    /// as dry runs, so the code path is the same as in production. A dry run, of the client
+    /// or of a single send, writes no file.
+    pub fn simulator(mut self, simulator: Simulator) -> Self {
+        self.simulator = Some(simulator);
+        self
+    }
+
+    /// Logs every request to APNs in full, with its method, URL, headers, and body, and every
+    /// response with its status, headers, and body, for troubleshooting (defaults to `false`).
+    ///
+    /// Records are emitted at debug level under the `apnrs::wire` target, so they can be
+    /// enabled separately from the crate's other logs. The provider token is replaced by
+    /// `<redacted>` and the device token in the URL is masked.
+    #[cfg(feature = "logging")]
+    pub fn wire_logging(mut self, enabled: bool) -> Self {
+        self.wire_logging = enabled;
+        self
+    }
+
+    /// Sets your Apple Developer team ID.
+    pub fn team_id(mut self, team_id: impl Into<String>) -> Self {
+        self.team_id = Some(team_id.into());
+        self
+    }
+
+    /// Sets the key ID associated with the APNs auth key.
+    pub fn key_id(mut self, key_id: impl Into<String>) -> Self {
+        self.key_id = Some(key_id.into());
+        self
+    }
+
+    /// Sets the environment to deliver to (defaults to [`Environment::Production`]).
+    pub fn environment(mut self, environment: Environment) -> Self {
+        self.environment = Some(environment);
+        self
+    }
+
+    /// Resends a notification once to the other environment when APNs answers
+    /// `BadDeviceToken`, as it does for tokens of development builds sent to production
+    /// and the other way around (defaults to `false`).
+    ///
+    /// Meant for development, where TestFlight and local builds mix both kinds of tokens.
+    /// [`SendOutcome::fallback`] names the environment a resent notification went to.
+    ///
+    /// # Example
+    ///
+    /// ```rust,no_run
+    /// use apnrs::ApnsClient;
+    ///
+    /// # async fn run(payload: apnrs::ApnsPayload) -> Result<(), apnrs::ApnsError> {
+    /// let client = ApnsClient::builder()
+    ///     .auth_key_path("path/to/auth/key")
+    ///     .team_id("TEAM_ID")
+    ///     .key_id("KEY_ID")
+    ///     .environment_fallback(true)
+    ///     .build()?;
+    ///
+    /// let outcome = client.send("DEVICE_TOKEN", "com.example.app", &payload).await?;
+    /// if let Some(environment) = outcome.fallback {
+    ///     println!("delivered through {:?}", environment);
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn environment_fallback(mut self, fallback: bool) -> Self {
+        self.environment_fallback = fallback;
+        self
+    }
+
+    /// Picks the priority Apple recommends for sends that do not set one (defaults to
+    /// `false`).
+    ///
+    /// When enabled, a send without a priority gets the one
+    /// [`Priority::recommended`] returns for its push type and payload: `10` for alerts,
+    /// `5` for background and content-available-only pushes, and the value suited to the
+    /// push type for VoIP and Live Activity pushes. When disabled, such sends carry no
+    /// `apns-priority` header and APNs applies its own default of `10`, which it throttles
+    /// or rejects for background pushes.
+    ///
+    /// # Example
+    ///
+    /// ```rust,no_run
+    /// use apnrs::{ApnsClient, ApnsPayload, Aps};
+    /// use std::collections::HashMap;
+    ///
+    /// # async fn run() -> Result<(), apnrs::ApnsError> {
+    /// let client = ApnsClient::builder()
+    ///     .auth_key_path("path/to/auth/key")
+    ///     .team_id("TEAM_ID")
+    ///     .key_id("KEY_ID")
+    ///     .auto_priority(true)
+    ///     .build()?;
+    ///
+    /// // Sent with priority 5, since it only wakes the app
+    /// let sync = ApnsPayload {
+    ///     aps: Aps::background(),
+    ///     custom_key: None,
+    ///     custom_fields: HashMap::new(),
+    /// };
+    /// client.send("DEVICE_TOKEN", "com.example.app", &sync).await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn auto_priority(mut self, enabled: bool) -> Self {
+        self.auto_priority = enabled;
+        self
+    }
+
+    /// Registers a strategy used to shrink and resend a payload once when APNs answers
+    /// `413 PayloadTooLarge`.
+    ///
+    /// Without a strategy, 413 responses are returned unchanged.
+    pub fn trim_strategy(mut self, strategy: impl TrimStrategy + 'static) -> Self {
+        self.trim_strategy = Some(Arc::new(strategy));
+        self
+    }
+
+    /// Registers a callback run whenever APNs answers `410` or `Unregistered`, meaning
+    /// the device token must no longer be used.
+    ///
+    /// The callback receives the device token and, when APNs provides it, the time the token
+    /// was last confirmed invalid in milliseconds since the Unix epoch. It runs for every
+    /// send made through the client, including those made by senders and batch APIs, so it
+    /// should return quickly; hand slow work such as database writes off to a task.
+    ///
+    /// # Example
+    ///
+    /// ```rust,no_run
+    /// use apnrs::ApnsClient;
+    ///
+    /// let client = ApnsClient::builder()
+    ///     .auth_key_path("path/to/auth/key")
+    ///     .team_id("TEAM_ID")
+    ///     .key_id("KEY_ID")
+    ///     .on_unregistered(|token, timestamp| {
+    ///         println!("{} unregistered at {:?}", token, timestamp);
+    ///     })
+    ///     .build();
+    /// ```
+    pub fn on_unregistered<F>(mut self, hook: F) -> Self
+    where
+        F: Fn(&str, Option<u64>) + Send + Sync + 'static,
+    {
+        self.unregistered_hooks.push(Arc::new(hook));
+        self
+    }
+
+    /// Registers a hook run around every request the client sends; see [`SendHook`].
+    ///
+    /// # Example
+    ///
+    /// ```rust,no_run
+    /// use apnrs::{ApnsClient, SendContext, SendHook};
+    /// use std::time::Duration;
+    ///
+    /// struct Audit;
+    ///
+    /// impl SendHook for Audit {
+    ///     fn after_send(
+    ///         &self,
+    ///         context: &SendContext<'_>,
+    ///         result: Result<&apnrs::ApnsResponse, &apnrs::ApnsError>,
+    ///         latency: Duration,
+    ///     ) {
+    ///         println!("{} answered in {:?}: {}", context.topic, latency, result.is_ok());
+    ///     }
+    /// }
+    ///
+    /// let client = ApnsClient::builder()
+    ///     .auth_key_path("path/to/auth/key")
+    ///     .team_id("TEAM_ID")
+    ///     .key_id("KEY_ID")
+    ///     .hook(Audit)
+    ///     .build();
+    /// ```
+    pub fn hook(mut self, hook: impl SendHook + 'static) -> Self {
+        self.send_hooks.push(Arc::new(hook));
+        self
+    }
+
+    /// Registers a callback run before every request is sent, which may change its headers
+    /// or payload, or fail the send; see [`SendHook::before_send`].
+    ///
+    /// # Example
+    ///
+    /// ```rust,no_run
+    /// use apnrs::ApnsClient;
+    ///
+    /// let client = ApnsClient::builder()
+    ///     .auth_key_path("path/to/auth/key")
+    ///     .team_id("TEAM_ID")
+    ///     .key_id("KEY_ID")
+    ///     .before_send(|_context, request| {
+    ///         let mut payload = request.payload()?;
+    ///         payload["sent-by"] = "billing".into();
+    ///         request.set_payload(&payload)
+    ///     })
+    ///     .build();
+    /// ```
+    pub fn before_send<F>(self, hook: F) -> Self
+    where
+        F: Fn(&SendContext<'_>, &mut OutgoingRequest) -> Result<(), ApnsError>
+            + Send
+            + Sync
+            + 'static,
+    {
+        self.hook(BeforeSend(hook))
+    }
+
+    /// Registers a callback run once APNs has answered every request or it has failed; see
+    /// [`SendHook::after_send`].
+    pub fn after_send<F>(self, hook: F) -> Self
+    where
+        F: Fn(&SendContext<'_>, Result<&ApnsResponse, &ApnsError>, Duration)
+            + Send
+            + Sync
+            + 'static,
+    {
+        self.hook(AfterSend(hook))
+    }
+
+    /// Sets the store used to record and look up invalid device tokens (defaults to a
+    /// [`MemoryTokenStore`]).
+    pub fn token_store(mut self, store: impl TokenStore + 'static) -> Self {
+        self.token_store = Some(Arc::new(store));
+        self
+    }
+
+    /// Sets the registry [`ApnsClient::send_to_user`] looks up a user's devices in.
+    pub fn device_registry(mut self, registry: impl DeviceRegistry + 'static) -> Self {
+        self.device_registry = Some(Arc::new(registry));
+        self
+    }
+
+    /// Records a [`SigningRecord`](crate::SigningRecord) in `audit` every time a provider
+    /// token is signed, so token reuse can be audited.
+    ///
+    /// Only a hash of each token is recorded, together with its claims and when it replaced
+    /// the previous one; records stay in the process and are never sent to APNs.
+    ///
+    /// # Example
+    ///
+    /// ```rust,no_run
+    /// use apnrs::{ApnsClient, MemorySigningAudit};
+    ///
+    /// # fn run() -> Result<(), apnrs::ApnsError> {
+    /// let client = ApnsClient::builder()
+    ///     .auth_key_path("path/to/auth/key")
+    ///     .team_id("TEAM_ID")
+    ///     .key_id("KEY_ID")
+    ///     .signing_audit(MemorySigningAudit::new())
+    ///     .build()?;
+    ///
+    /// for record in client.signing_audit().map(|a| a.records()).unwrap_or_default() {
+    ///     println!("{} signed at {} ({:?})", record.token_sha256, record.issued_at, record.reason);
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn signing_audit(mut self, audit: impl SigningAudit + 'static) -> Self {
+        self.signing_audit = Some(Arc::new(audit));
+        self
+    }
+
+    /// Replaces device tokens with their keyed hash in everything the client hands to
+    /// analytics sinks: [`SendEvent`]s from [`ApnsClient::subscribe_events`] and
+    /// [`ApnsClient::serve_events`].
+    ///
+    /// Unregistered hooks and the token store still receive raw tokens, since they need them
+    /// to stop sending to the device.
+    ///
+    /// # Example
+    ///
+    /// ```rust,no_run
+    /// use apnrs::{ApnsClient, TokenHasher};
+    ///
+    /// # fn run(key: &[u8]) -> Result<(), Box<dyn std::error::Error>> {
+    /// let client = ApnsClient::builder()
+    ///     .auth_key_path("path/to/auth/key")
+    ///     .team_id("TEAM_ID")
+    ///     .key_id("KEY_ID")
+    ///     .token_hasher(TokenHasher::new(key)?)
+    ///     .build()?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn token_hasher(mut self, hasher: TokenHasher) -> Self {
+        self.token_hasher = Some(hasher);
+        self
+    }
+
+    /// Sets which labels are attached to the metrics emitted with the `metrics` feature, and
+    /// how many distinct values each may take (defaults to [`MetricLabels::new`]).
+    ///
+    /// # Example
+    ///
+    /// ```rust,no_run
+    /// use apnrs::{ApnsClient, MetricLabel, MetricLabels};
+    ///
+    /// let client = ApnsClient::builder()
+    ///     .auth_key_path("path/to/auth/key")
+    ///     .team_id("TEAM_ID")
+    ///     .key_id("KEY_ID")
+    ///     .metric_labels(
+    ///         MetricLabels::new()
+    ///             .with(MetricLabel::Topic)
+    ///             .max_values(MetricLabel::Topic, 50)
+    ///             .tenant("acme"),
+    ///     )
+    ///     .build();
+    /// ```
+    pub fn metric_labels(mut self, labels: MetricLabels) -> Self {
+        self.metric_labels = Some(labels);
+        self
+    }
+
+    /// Builds the client.
+    ///
+    /// # Returns
+    ///
+    /// An `ApnsClient`, or an `ApnsError` if a required setting is missing, settings
+    /// conflict, or the key or certificate cannot be read or parsed.
+    pub fn build(mut self) -> Result<ApnsClient, ApnsError> {
+        let certificate = match (self.certificate.take(), self.certificate_path.take()) {
+            (Some(certificate), _) => Some(certificate),
+            (None, Some((path, password))) => {
+                Some((Zeroizing::new(fs::read(path)?), Zeroizing::new(password)))
+            }
+            (None, None) => None,
+        };
+        #[cfg(all(feature = "keychain", target_os = "macos"))]
+        let certificate = match (certificate, self.certificate_keychain.take()) {
+            (Some(certificate), _) => Some(certificate),
+            (None, Some(label)) => {
+                let (der, password) = crate::keychain::identity_pkcs12(&label)?;
+                Some((Zeroizing::new(der), Zeroizing::new(password)))
+            }
+            (None, None) => None,
+        };
+        let (http, public_key_pins) = match self.http_client.take() {
+            Some(http) => {
+                let conflicts: Vec<&str> = [
+                    ("proxy", self.proxy.is_some()),
+                    ("connect_timeout", self.connect_timeout.is_some()),
+                    ("request_timeout", self.request_timeout.is_some()),
+                    ("keepalive_interval", self.keepalive_interval.is_some()),
+                    ("keepalive_timeout", self.keepalive_timeout.is_some()),
+                    ("pool_idle_timeout", self.pool_idle_timeout.is_some()),
+                    (
+                        "pool_max_idle_per_host",
+                        self.pool_max_idle_per_host.is_some(),
+                    ),
+                    ("certificate", certificate.is_some()),
+                    ("pin", !self.pins.is_empty()),
+                    ("connections", self.connections.is_some_and(|n| n > 1)),
+                ]
+                .into_iter()
+                .filter_map(|(setting, set)| set.then_some(setting))
+                .collect();
+                if !conflicts.is_empty() {
+                    return Err(ApnsError::Config(format!(
+                        "{} must be configured on the provided HTTP client",
+                        conflicts.join(", ")
+                    )));
+                }
+                (vec![http], PublicKeyPins::new(&[]))
+            }
+            None => {
+                let mut clients = Vec::new();
+                let mut pins = PublicKeyPins::new(&[]);
+                for _ in 0..self.connections.unwrap_or(1) {
+                    let (http, public_key_pins) = self.build_http(certificate.as_ref())?;
+                    clients.push(http);
+                    pins = public_key_pins;
+                }
+                (clients, pins)
+            }
+        };
+        let http = Shards::new(http, self.connection_strategy);
+
+        let key = match (self.key, self.key_path) {
+            (Some(key), _) => Some(key),
+            (None, Some(path)) => Some(Zeroizing::new(fs::read(path)?)),
+            (None, None) => None,
+        };
+        #[cfg(all(feature = "keychain", target_os = "macos"))]
+        let key = match (key, self.key_keychain.take()) {
+            (Some(key), _) => Some(key),
+            (None, Some(label)) => Some(Zeroizing::new(crate::keychain::auth_key_pem(&label)?)),
+            (None, None) => None,
+        };
+        let signer: Option<Arc<dyn TokenSigner>> = match (key, self.signer) {
+            (Some(key), _) => {
+                let key_id = self
+                    .key_id
+                    .ok_or_else(|| ApnsError::Config("missing key ID".to_string()))?;
+                let signer = match self.key_passphrase {
+                    Some(passphrase) => LocalSigner::from_encrypted_pem(
+                        &key,
+                        passphrase.resolve()?.as_bytes(),
+                        key_id,
+                    )?,
+                    None => LocalSigner::from_pem(&key, key_id)?,
+                };
+                Some(Arc::new(signer))
+            }
+            (None, Some(signer)) => Some(signer),
+            (None, None)
+                if certificate.is_some()
+                    || !self.credentials.is_empty()
+                    || !self.registry.is_empty()
+                    || self.profiles.values().any(Profile::has_credentials) =>
+            {
+                None
+            }
+            (None, None) => {
+                return Err(ApnsError::Config(
+                    "missing auth key, token signer, certificate, or registered credentials"
+                        .to_string(),
+                ))
+            }
+        };
+        let tokens = match signer {
+            Some(signer) => {
+                let team_id = self
+                    .team_id
+                    .ok_or_else(|| ApnsError::Config("missing team ID".to_string()))?;
+                let clock = self.clock.unwrap_or_else(|| Arc::new(SystemClock));
+                Some(TokenCache::new(signer, team_id, self.signing_audit, clock))
+            }
+            None => None,
+        };
+
+        let client = ApnsClient {
+            inner: Arc::new(ClientInner {
+                tokens,
+                credentials: self.credentials,
+                registry: self.registry,
+                profiles: self.profiles,
+                #[cfg(feature = "schema")]
+                custom_data_schema: self.custom_data_schema,
+                has_certificate: certificate.is_some(),
+                environment: self.environment.unwrap_or(Environment::Production),
+                trim_strategy: self.trim_strategy,
+                http,
+                in_flight: self.max_in_flight.map(Semaphore::new),
+                public_key_pins,
+                send_deadline: self.send_deadline.unwrap_or(DEFAULT_SEND_DEADLINE),
+                connection_retries: self
+                    .connection_retries
+                    .unwrap_or(DEFAULT_CONNECTION_RETRIES),
+                dry_run: self.dry_run,
+                simulator: self.simulator,
+                environment_fallback: self.environment_fallback,
+                auto_priority: self.auto_priority,
+                #[cfg(feature = "logging")]
+                wire_logging: self.wire_logging,
+                lifecycle: Arc::default(),
+                stats: StatsRecorder::default(),
+                events: EventPublisher::new(self.token_hasher),
+                metric_labels: Labeler::new(self.metric_labels.unwrap_or_default()),
+                unregistered_hooks: self.unregistered_hooks,
+                send_hooks: self.send_hooks,
+                token_store: self
+                    .token_store
+                    .unwrap_or_else(|| Arc::new(MemoryTokenStore::new())),
+                device_registry: self.device_registry,
+            }),
+        };
+
+        if self.preconnect && !self.dry_run && client.inner.simulator.is_none() {
+            if let Ok(runtime) = tokio::runtime::Handle::try_current() {
+                let client = client.clone();
+                // A failure here shows up again, with its cause, on the first send
+                runtime.spawn(async move { client.connect().await });
+            }
+        }
+        Ok(client)
+    }
+
+    /// Builds the HTTP client from the builder's connection settings, with the public keys
+    /// it pins.
+    fn build_http(
+        &self,
+        certificate: Option<&Pkcs12>,
+    ) -> Result<(reqwest::Client, PublicKeyPins), ApnsError> {
+        let mut http = http_builder()
+            .connect_timeout(self.connect_timeout.unwrap_or(DEFAULT_CONNECT_TIMEOUT))
+            .timeout(self.request_timeout.unwrap_or(DEFAULT_REQUEST_TIMEOUT));
+        let keepalive_interval = self
+            .keepalive_interval
+            .unwrap_or(Some(DEFAULT_KEEPALIVE_INTERVAL));
+        match keepalive_interval {
+            Some(interval) => {
+                http = http
+                    .http2_keep_alive_interval(interval)
+                    .http2_keep_alive_timeout(
+                        self.keepalive_timeout.unwrap_or(DEFAULT_KEEPALIVE_TIMEOUT),
+                    )
+                    .http2_keep_alive_while_idle(true);
+            }
+            None => http = http.http2_keep_alive_interval(None),
+        }
+        // Pings detect dead connections, so idle ones no longer need to be dropped
+        let pool_idle_timeout = self.pool_idle_timeout.unwrap_or(match keepalive_interval {
+            Some(_) => None,
+            None => Some(DEFAULT_POOL_IDLE_TIMEOUT),
+        });
+        http = http.pool_idle_timeout(pool_idle_timeout);
+        if let Some(max) = self.pool_max_idle_per_host {
+            http = http.pool_max_idle_per_host(max);
+        }
+        if let Some((der, password)) = certificate {
+            http = http.identity(tls_identity(der, password)?);
+        }
+        if let Some(proxy) = &self.proxy {
+            http = http.proxy(proxy.to_reqwest()?);
+        }
+        for pin in &self.pins {
+            if let CertificatePin::Root(der) = pin {
+                http = http
+                    .tls_built_in_root_certs(false)
+                    .add_root_certificate(reqwest::Certificate::from_der(der)?);
+            }
+        }
+        let public_key_pins = PublicKeyPins::new(&self.pins);
+        let http = http.tls_info(!public_key_pins.is_empty()).build()?;
+        Ok((http, public_key_pins))
+    }
+}
+
+impl ApnsClient {
+    /// Returns a builder for configuring a new client.
+    pub fn builder() -> ApnsClientBuilder {
+        ApnsClientBuilder::default()
+    }
+
+    /// Returns the environment this client delivers to, unless a topic's app is registered
+    /// with another in its [`AppRegistry`].
+    pub fn environment(&self) -> Environment {
+        self.inner.environment
+    }
+
+    /// Returns the environment notifications to `topic` are delivered to.
+    pub fn environment_for(&self, topic: &str) -> Environment {
+        self.inner
+            .registry
+            .get(topic)
+            .map_or(self.inner.environment, |app| app.environment)
+    }
+
+    /// Returns the registry routing topics to each app's credentials and environment.
+    pub fn app_registry(&self) -> &AppRegistry {
+        &self.inner.registry
+    }
+
+    /// Returns the profile registered under `name` with
+    /// [`ApnsClientBuilder::profile`], if any.
+    pub fn profile(&self, name: &str) -> Option<&Profile> {
+        self.inner.profiles.get(name)
+    }
+
+    /// Opens a connection to APNs and adds it to the pool, so later sends reuse it instead
+    /// of connecting first. With several [`connections`](ApnsClientBuilder::connections),
+    /// opens every one of them.
+    ///
+    /// # Returns
+    ///
+    /// An `ApnsError` if the server cannot be reached or fails certificate pinning.
+    ///
+    /// # Example
+    ///
+    /// ```rust,no_run
+    /// use apnrs::ApnsClient;
+    ///
+    /// # async fn run(client: ApnsClient) -> Result<(), apnrs::ApnsError> {
+    /// // Connect during startup, before the service reports itself ready
+    /// client.connect().await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn connect(&self) -> Result<(), ApnsError> {
+        self.connect_to(self.inner.environment).await
+    }
+
+    /// Opens connections to the APNs host of `environment`, as [`connect`](ApnsClient::connect)
+    /// does for the client's own environment.
+    ///
+    /// Each APNs host has its own connections in the pool, so a client sending to both
+    /// sandbox and production tokens, with [`SendOptions::environment`] or an
+    /// [`AppRegistry`], can warm up both at startup.
+    ///
+    /// # Returns
+    ///
+    /// An `ApnsError` if the server cannot be reached or fails certificate pinning.
+    ///
+    /// # Example
+    ///
+    /// ```rust,no_run
+    /// use apnrs::{ApnsClient, Environment};
+    ///
+    /// # async fn run(client: ApnsClient) -> Result<(), apnrs::ApnsError> {
+    /// client.connect().await?;
+    /// client.connect_to(Environment::Sandbox).await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn connect_to(&self, environment: Environment) -> Result<(), ApnsError> {
+        // Any answer means the connection is up; APNs rejects the request itself
+        let connects = self.inner.http.all().map(|http| async move {
+            let response = http.head(environment.base_url()).send().await?;
+            self.check_pins(&response)
+        });
+        future::try_join_all(connects).await?;
+        Ok(())
+    }
+
+    /// Checks that the client can push, without sending a notification, for readiness
+    /// probes at startup.
+    ///
+    /// Signs a provider token if none is cached or the cached one is due for renewal, then
+    /// opens or reuses a connection to APNs as [`connect`](ApnsClient::connect) does and
+    /// times its answer.
+    ///
+    /// # Returns
+    ///
+    /// A [`HealthReport`] with the endpoint, connection, latency, and token age, or an
+    /// `ApnsError` if no token can be signed or the server cannot be reached or fails
+    /// certificate pinning.
+    ///
+    /// # Example
+    ///
+    /// ```rust,no_run
+    /// use apnrs::ApnsClient;
+    ///
+    /// # async fn run(client: ApnsClient) -> Result<(), apnrs::ApnsError> {
+    /// let health = client.health_check().await?;
+    /// println!("{} via {:?} in {:?}", health.endpoint, health.remote_addr, health.latency);
+    /// assert!(health.is_http2());
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn health_check(&self) -> Result<HealthReport, ApnsError> {
+        self.provider_headers().await?;
+        let token_age = self.inner.tokens.as_ref().and_then(TokenCache::age);
+
+        let endpoint = self.inner.environment.base_url();
+        let started = Instant::now();
+        // Any answer means the connection is up; APNs rejects the request itself
+        let response = self.inner.http.primary().head(endpoint).send().await?;
+        let latency = started.elapsed();
+        self.check_pins(&response)?;
+
+        Ok(HealthReport {
+            environment: self.inner.environment,
+            endpoint: endpoint.to_string(),
+            remote_addr: response.remote_addr(),
+            http_version: format!("{:?}", response.version()),
+            latency,
+            token_age,
+        })
+    }
+
+    /// Checks that APNs accepts the client's credentials for `topic`, without delivering a
+    /// notification, so a wrong key, team ID, key ID, or topic is caught at deploy time
+    /// rather than on the first real send.
+    ///
+    /// Signs a provider token with the credentials sends to `topic` would use and posts a
+    /// background push to a device token that cannot exist, in the environment the topic is
+    /// sent to. APNs checks the provider token and topic before the device token, so its
+    /// answer of `BadDeviceToken` means both were accepted. Any other answer does not confirm
+    /// them and is returned as an error. The probe waits for a slot under
+    /// [`max_in_flight`](ApnsClientBuilder::max_in_flight) and is counted in the client's
+    /// stats like a send.
+    ///
+    /// # Returns
+    ///
+    /// `Ok(())` if APNs answers `BadDeviceToken`, `ApnsError::Rejected` with the status and
+    /// reason APNs gave otherwise, such as `InvalidProviderToken` for a key, team ID, or key
+    /// ID that do not belong together or `TopicDisallowed` for a topic the key may not push
+    /// to, or another `ApnsError`, such as `ApnsError::Http`, if APNs cannot be reached.
+    ///
+    /// # Example
+    ///
+    /// ```rust,no_run
+    /// use apnrs::{ApnsClient, ApnsError, ErrorReason};
+    ///
+    /// # async fn run(client: ApnsClient) {
+    /// match client.verify_credentials("com.example.app").await {
+    ///     Ok(()) => println!("credentials accepted"),
+    ///     Err(ApnsError::Rejected { reason: Some(ErrorReason::InvalidProviderToken), .. }) => {
+    ///         eprintln!("the key, team ID, and key ID do not match")
+    ///     }
+    ///     Err(ApnsError::Rejected { reason: Some(ErrorReason::TopicDisallowed), .. }) => {
+    ///         eprintln!("the key may not push to com.example.app")
+    ///     }
+    ///     Err(e) => eprintln!("could not verify credentials: {}", e),
+    /// }
+    /// # }
+    /// ```
+    pub async fn verify_credentials(&self, topic: &str) -> Result<(), ApnsError> {
+        validate_topic(topic)?;
+        let options = SendOptions::new();
+        let credentials = self.credentials_for(topic, &options)?;
+        let mut headers = self
+            .headers_for(topic, Some(PushType::Background), credentials)
+            .await?;
+        headers.insert(
+            "apns-priority",
+            HeaderValue::from_static(Priority::Normal.as_str()),
+        );
+        let url = self
+            .environment_for(topic)
+            .device_url(VERIFICATION_DEVICE_TOKEN);
+
+        let inner = &self.inner;
+        // The probe counts against the in-flight limit like any other request
+        let permit = match &inner.in_flight {
+            Some(in_flight) => Some(in_flight.acquire().await.expect("semaphore never closed")),
+            None => None,
+        };
+        let started = Instant::now();
+        let response = inner
+            .http
+            .primary()
+            .post(&url)
+            .headers(headers)
+            .body(VERIFICATION_PAYLOAD)
+            .send()
+            .await;
+        let answer = match response {
+            Ok(response) => match self.check_pins(&response) {
+                Ok(()) => read_response(response).await,
+                Err(e) => Err(e),
+            },
+            Err(e) => {
+                inner.stats.transport_failed(started.elapsed());
+                Err(e.into())
+            }
+        };
+        drop(permit);
+        let response = answer?.response;
+        inner.stats.response_received(
+            response.status.as_u16(),
+            response.error.as_ref(),
+            started.elapsed(),
+        );
+        match response.error {
+            Some(ErrorReason::BadDeviceToken) => Ok(()),
+            reason => Err(ApnsError::Rejected {
+                status: response.status,
+                reason,
+            }),
+        }
+    }
+
+    /// Stops accepting new sends and waits up to `timeout` for pending ones to finish, for a
+    /// clean exit on `SIGTERM`.
+    ///
+    /// Pending sends include those in progress on any clone of the client and every
+    /// notification queued in a sender built on it; senders keep delivering their queues
+    /// until the timeout. After it, queued notifications are discarded, and their receipts
+    /// resolve to [`ApnsError::ClientShutDown`], as do all new sends and enqueues from the
+    /// moment `shutdown` is called.
+    ///
+    /// # Returns
+    ///
+    /// How many pending sends completed and how many were dropped.
+    ///
+    /// # Example
+    ///
+    /// ```rust,no_run
+    /// use apnrs::ApnsClient;
+    /// use std::time::Duration;
+    ///
+    /// # async fn run(client: ApnsClient) {
+    /// tokio::signal::ctrl_c().await.unwrap();
+    /// let report = client.shutdown(Duration::from_secs(20)).await;
+    /// println!("flushed {}, dropped {}", report.flushed, report.dropped);
+    /// # }
+    /// ```
+    pub async fn shutdown(&self, timeout: Duration) -> ShutdownReport {
+        self.inner.lifecycle.shutdown(timeout).await
+    }
+
+    /// Returns the tracker of pending sends, which queued notifications also register with.
+    pub(crate) fn lifecycle(&self) -> &Arc<Lifecycle> {
+        &self.inner.lifecycle
+    }
+
+    /// Returns a snapshot of how many notifications this client has sent, how many
+    /// failed and why, the average request latency, and the depth of sender queues.
+    pub fn stats(&self) -> ClientStats {
+        self.inner.stats.snapshot()
+    }
+
+    /// Returns the store of device tokens APNs has reported as invalid.
+    pub fn token_store(&self) -> &dyn TokenStore {
+        &*self.inner.token_store
+    }
+
+    /// Returns the registry of users' devices, if one was configured with
+    /// [`ApnsClientBuilder::device_registry`].
+    pub fn device_registry(&self) -> Option<&dyn DeviceRegistry> {
+        self.inner.device_registry.as_deref()
+    }
+
+    /// Returns the audit trail of signed provider tokens, if one was configured with
+    /// [`ApnsClientBuilder::signing_audit`].
+    pub fn signing_audit(&self) -> Option<&dyn SigningAudit> {
+        self.inner.tokens.as_ref()?.audit()
+    }
+
+    /// Returns the cache of the client's own provider tokens, if it has an auth key.
+    pub(crate) fn token_cache(&self) -> Option<&TokenCache> {
+        self.inner.tokens.as_ref()
+    }
+
+    /// Replaces the client's auth key without rebuilding it, for rotating a `.p8` key while
+    /// the service keeps running.
+    ///
+    /// The cached provider token is discarded, so the next request signs a new one with the
+    /// new key; requests already prepared finish with the old token. Every clone of the
+    /// client, and every sender built from it, switches over at once.
+    ///
+    /// # Returns
+    ///
+    /// An `ApnsError` if the key cannot be parsed, in which case the previous key stays in
+    /// use, or if the client was built without an auth key.
+    ///
+    /// # Example
+    ///
+    /// ```rust,no_run
+    /// use apnrs::ApnsClient;
+    ///
+    /// # fn run(client: ApnsClient) -> Result<(), apnrs::ApnsError> {
+    /// let key = std::fs::read("path/to/new/key")?;
+    /// client.rotate_credentials(&key, "NEW_KEY_ID")?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn rotate_credentials(
+        &self,
+        key_pem: &[u8],
+        key_id: impl Into<String>,
+    ) -> Result<(), ApnsError> {
+        self.rotate_signer(LocalSigner::from_pem(key_pem, key_id)?)
+    }
+
+    /// Replaces the client's token signer without rebuilding it, like
+    /// [`rotate_credentials`](ApnsClient::rotate_credentials) for keys kept outside the
+    /// process.
+    ///
+    /// # Returns
+    ///
+    /// An `ApnsError` if the client was built without an auth key or token signer.
+    pub fn rotate_signer(&self, signer: impl TokenSigner + 'static) -> Result<(), ApnsError> {
+        match &self.inner.tokens {
+            Some(tokens) => {
+                tokens.rotate(Arc::new(signer));
+                Ok(())
+            }
+            None => Err(ApnsError::Config(
+                "the client has no auth key to rotate".to_string(),
+            )),
+        }
+    }
+
+    /// Subscribes to a [`SendEvent`] for every request this client makes from now on.
+    ///
+    /// Subscribers that fall more than 1024 events behind miss the oldest ones.
+    pub fn subscribe_events(&self) -> broadcast::Receiver<SendEvent> {
+        self.inner.events.subscribe()
+    }
+
+    /// Streams a [`DeliveryEvent`] for every step in the life of the notifications this
+    /// client and the senders built on it handle from now on: when they are queued, sent,
+    /// retried, fail, or find a token unregistered, and when a provider token is signed or
+    /// a sender is throttled.
+    ///
+    /// Any number of consumers can subscribe, each receiving every event, so metrics,
+    /// audit logs, and token cleanup need not be wired into each place notifications are
+    /// sent from. Consumers that fall more than 1024 events behind miss the oldest ones.
+    /// The stream ends once the client and all its clones are dropped.
+    ///
+    /// # Example
+    ///
+    /// ```rust,no_run
+    /// use apnrs::{ApnsClient, DeliveryEvent};
+    /// use futures_util::StreamExt;
+    ///
+    /// # fn run(client: ApnsClient) {
+    /// let mut events = client.events();
+    /// tokio::spawn(async move {
+    ///     while let Some(event) = events.next().await {
+    ///         if let DeliveryEvent::Unregistered { device_token, .. } = event {
+    ///             println!("removing {}", device_token);
+    ///         }
+    ///     }
+    /// });
+    /// # }
+    /// ```
+    pub fn events(&self) -> BoxStream<'static, DeliveryEvent> {
+        self.inner.events.deliveries()
+    }
+
+    /// Streams this client's send events as newline-delimited JSON to every process that
+    /// connects to a unix socket at `path`, such as `apnrs tail --socket <path>`.
+    ///
+    /// Abort the returned handle to stop serving.
+    ///
+    /// # Returns
+    ///
+    /// The handle of the serving task, or an error if the socket cannot be bound.
+    #[cfg(unix)]
+    pub fn serve_events(&self, path: impl AsRef<Path>) -> io::Result<JoinHandle<()>> {
+        let listener = tokio::net::UnixListener::bind(path)?;
+        let client = self.clone();
+        Ok(tokio::spawn(events::serve_unix(
+            move || client.subscribe_events(),
+            listener,
+        )))
+    }
+
+    /// Returns a client for the broadcast channels of the app with the given bundle ID.
+    pub fn channels(&self, bundle_id: impl Into<String>) -> ChannelClient {
+        ChannelClient::new(self.clone(), bundle_id)
+    }
+
+    /// Returns the shared HTTP client, so channel requests reuse its connections.
+    pub(crate) fn http(&self) -> &reqwest::Client {
+        self.inner.http.primary()
+    }
+
+    /// Checks that `response` arrived on a connection presenting a pinned public key.
+    pub(crate) fn check_pins(&self, response: &reqwest::Response) -> Result<(), ApnsError> {
+        self.inner.public_key_pins.check(response)
+    }
+
+    /// Returns the `authorization` and `content-type` headers for the cached provider token,
+    /// or only `content-type` if the client authenticates with a certificate alone.
+    pub(crate) async fn provider_headers(&self) -> Result<HeaderMap, ApnsError> {
+        match &self.inner.tokens {
+            Some(tokens) => self.refreshed_headers(tokens).await,
+            None if self.inner.has_certificate => Ok(certificate_headers()),
+            None => Err(ApnsError::Config(
+                "no credentials are registered for this app and the client has no auth key"
+                    .to_string(),
+            )),
+        }
+    }
+
+    /// Returns the headers of the provider token cached in `tokens`, publishing a
+    /// [`DeliveryEvent::TokenRefreshed`] if a new one had to be signed.
+    async fn refreshed_headers(&self, tokens: &TokenCache) -> Result<HeaderMap, ApnsError> {
+        let (headers, refreshed) = tokens.refreshing_headers().await?;
+        if let Some(key_id) = refreshed {
+            self.inner.events.token_refreshed(tokens.team_id(), &key_id);
+        }
+        Ok(headers)
+    }
+
+    /// Returns the provider headers for requests about the app with the given bundle ID,
+    /// signed with the credentials registered for it if any.
+    pub(crate) async fn app_headers(&self, bundle_id: &str) -> Result<HeaderMap, ApnsError> {
+        let credentials = match self.inner.registry.get(bundle_id) {
+            Some(app) => Some(&app.credentials),
+            None => self.inner.credentials.get(bundle_id),
+        };
+        match credentials {
+            Some(credentials) => self.refreshed_headers(credentials.tokens()).await,
+            None => self.provider_headers().await,
+        }
+    }
+
+    /// Returns the credentials a request to `topic` is signed with, or `None` for the
+    /// client's own.
+    fn credentials_for<'a>(
+        &'a self,
+        topic: &str,
+        options: &'a SendOptions,
+    ) -> Result<Option<&'a TokenCache>, ApnsError> {
+        if let Some(credentials) = &options.credentials {
+            return Ok(Some(credentials.tokens()));
+        }
+        let credentials = match &options.app {
+            Some(app) => Some(self.inner.credentials.get(app).ok_or_else(|| {
+                ApnsError::Config(format!("no credentials are registered for app {:?}", app))
+            })?),
+            None => match self.inner.registry.get(topic) {
+                Some(app) => Some(&app.credentials),
+                None => self.inner.credentials.get(bundle_id(topic)),
+            },
+        };
+        Ok(credentials.map(Credentials::tokens))
+    }
+
+    /// Returns the labeler applying the configured metric labels.
+    pub(crate) fn metric_labels(&self) -> &Labeler {
+        &self.inner.metric_labels
+    }
+
+    /// Returns the live counters, so senders can report their queue depth.
+    pub(crate) fn stats_recorder(&self) -> &StatsRecorder {
+        &self.inner.stats
+    }
+
+    /// Returns the event publisher, so senders can report what they queue and retry.
+    pub(crate) fn event_publisher(&self) -> &EventPublisher {
+        &self.inner.events
+    }
+
+    /// Sends a push notification to an Apple device.
+    ///
+    /// The topic is validated before anything is sent, so a malformed bundle ID fails with
+    /// `ApnsError::InvalidTopic` instead of an opaque header error or an APNs rejection.
+    /// Likewise, push types that APNs only accepts with certificate-based authentication,
+    /// such as `mdm`, fail with `ApnsError::CertificateRequired` instead of a generic 403.
+    ///
+    /// Payloads larger than the push type allows (4 KB, or 5 KB for VoIP) are trimmed
+    /// before sending if a trimming strategy is registered, and otherwise fail with
+    /// `ApnsError::InvalidNotification` naming the actual size and the limit, without a
+    /// round trip to APNs. If APNs still answers `413 PayloadTooLarge`, the payload is
+    /// trimmed and resent once. The returned outcome reports what was removed.
+    ///
+    /// This is the client's fast path for latency-critical pushes such as one-time codes:
+    /// nothing is queued or batched, the provider token and its headers are reused until
+    /// they are due for renewal, and the request goes straight onto the shared connection.
+    /// Preparing the request stays within [`FAST_PATH_BUDGET`].
+    ///
+    /// # Arguments
+    ///
+    /// * `device_token` - The device token of the target device. A
+    ///   [`&DeviceToken`](crate::DeviceToken) can be passed here and to every other send
+    ///   API, so malformed tokens are caught when they are parsed rather than answered with
+    ///   `BadDeviceToken`.
+    /// * `topic` - The topic (usually the app's bundle ID) for the notification.
+    /// * `payload` - The payload of the notification.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing either the outcome of the request or an `ApnsError`.
+    pub async fn send(
+        &self,
+        device_token: &str,
+        topic: &str,
+        payload: &ApnsPayload,
+    ) -> Result<SendOutcome, ApnsError> {
+        self.send_as(
+            device_token,
+            topic,
+            None,
+            None,
+            payload,
+            &SendOptions::new(),
+        )
+        .await
+    }
+
+    /// Sends a push notification with per-request settings, such as the credentials of
+    /// another team.
+    ///
+    /// Behaves exactly like [`send`](ApnsClient::send) otherwise, over the same connections:
+    /// a request signed with other credentials does not need a second client.
+    ///
+    /// # Arguments
+    ///
+    /// * `device_token` - The device token of the target device.
+    /// * `topic` - The topic (usually the app's bundle ID) for the notification.
+    /// * `payload` - The payload of the notification.
+    /// * `options` - The settings for this request.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing either the outcome of the request or an `ApnsError`.
+    ///
+    /// # Example
+    ///
+    /// ```rust,no_run
+    /// use apnrs::{ApnsClient, ApnsPayload, Credentials, SendOptions};
+    ///
+    /// # async fn run(client: ApnsClient, payload: ApnsPayload) -> Result<(), apnrs::ApnsError> {
+    /// let partner = Credentials::from_key_path("path/to/partner/key", "PARTNER_TEAM", "PARTNER_KEY")?;
+    /// let options = SendOptions::new().credentials(partner);
+    ///
+    /// client
+    ///     .send_with("DEVICE_TOKEN", "com.partner.app", &payload, &options)
+    ///     .await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn send_with(
+        &self,
+        device_token: &str,
+        topic: &str,
+        payload: &ApnsPayload,
+        options: &SendOptions,
+    ) -> Result<SendOutcome, ApnsError> {
+        self.send_as(device_token, topic, None, None, payload, options)
+            .await
+    }
+
+    /// Sends a payload to the app of the profile registered under `profile`, with the
+    /// profile's environment, push type, priority, and credentials, so per-app defaults are
+    /// set once on the client instead of at every call site.
+    ///
+    /// The topic is the profile's bundle ID, with the suffix of its push type if it has
+    /// one; see [`Profile::topic`].
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing either the outcome of the request or an `ApnsError`, which is
+    /// `ApnsError::Config` if no profile is registered under `profile`.
+    ///
+    /// # Example
+    ///
+    /// ```rust,no_run
+    /// use apnrs::{ApnsClient, ApnsPayload};
+    ///
+    /// # async fn run(client: ApnsClient, payload: ApnsPayload) -> Result<(), apnrs::ApnsError> {
+    /// client.send_with_profile("consumer-app", "DEVICE_TOKEN", &payload).await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn send_with_profile(
+        &self,
+        profile: &str,
+        device_token: &str,
+        payload: &ApnsPayload,
+    ) -> Result<SendOutcome, ApnsError> {
+        let profile = self.profile(profile).ok_or_else(|| {
+            ApnsError::Config(format!("no profile is registered as {:?}", profile))
+        })?;
+        self.send_with(device_token, &profile.topic(), payload, &profile.options())
+            .await
+    }
+
+    /// Sends a [`PushRequest`] built elsewhere, such as one read back from storage, with its
+    /// options, as [`send_with`](ApnsClient::send_with) does.
+    ///
+    /// The send is abandoned with `ApnsError::DeadlineExceeded` if the request's
+    /// [deadline](PushRequest::deadline) passes first. Its traffic class only matters to
+    /// senders and is ignored.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing either the outcome of the request or an `ApnsError`.
+    ///
+    /// # Example
+    ///
+    /// ```rust,no_run
+    /// use apnrs::{ApnsClient, PushRequest};
+    ///
+    /// # async fn run(client: ApnsClient, stored: &str) -> Result<(), Box<dyn std::error::Error>> {
+    /// let request: PushRequest = serde_json::from_str(stored)?;
+    /// client.send_request(&request).await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn send_request(&self, request: &PushRequest) -> Result<SendOutcome, ApnsError> {
+        let send = self.send_with(
+            &request.device_token,
+            &request.topic,
+            &request.payload,
+            &request.options,
+        );
+        request.within_deadline(send).await
+    }
+
+    /// Sends a JSON payload produced elsewhere, such as by another system, exactly as given.
+    ///
+    /// The JSON is only checked to be well-formed, not parsed into the typed structs, so
+    /// its keys, order, and formatting reach APNs unchanged. Size limits, the custom data
+    /// schema, and a registered trim strategy still apply, and the headers are set as for
+    /// any other send.
+    ///
+    /// # Arguments
+    ///
+    /// * `device_token` - The device token of the target device.
+    /// * `topic` - The topic (usually the app's bundle ID) for the notification.
+    /// * `json` - The payload, a JSON object with an `aps` dictionary.
+    /// * `options` - The settings for this request.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing either the outcome of the request or an `ApnsError`, which is
+    /// `ApnsError::Serialization` if `json` is not well-formed and
+    /// `ApnsError::InvalidNotification` if it is not an object.
+    ///
+    /// # Example
+    ///
+    /// ```rust,no_run
+    /// use apnrs::{ApnsClient, SendOptions};
+    ///
+    /// # async fn run(client: ApnsClient) -> Result<(), apnrs::ApnsError> {
+    /// let json = r#"{"aps":{"alert":"Your order has shipped"},"order-id":42}"#;
+    /// client
+    ///     .send_raw("DEVICE_TOKEN", "com.example.app", json, &SendOptions::new())
+    ///     .await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn send_raw(
+        &self,
+        device_token: &str,
+        topic: &str,
+        json: &str,
+        options: &SendOptions,
+    ) -> Result<SendOutcome, ApnsError> {
+        let payload = RawValue::from_string(json.to_string())?;
+        if !payload.get().trim_start().starts_with('{') {
+            return Err(NotificationError::NotAnObject.into());
+        }
+        self.send_as(device_token, topic, None, None, &payload, options)
+            .await
+    }
+
+    /// Serializes and size-checks a payload once, for sending to many devices with
+    /// [`send_prepared`](ApnsClient::send_prepared).
+    ///
+    /// The custom data schema and a registered trim strategy apply as they do when sending,
+    /// against the limit of an alert push; use [`prepare_for`](ApnsClient::prepare_for) for
+    /// push types with another limit.
+    ///
+    /// # Arguments
+    ///
+    /// * `payload` - The payload to prepare.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing either the prepared payload or an `ApnsError` if the payload
+    /// breaks the schema or is too large.
+    pub fn prepare<P: Serialize + ?Sized>(
+        &self,
+        payload: &P,
+    ) -> Result<PreparedPayload, ApnsError> {
+        let (json, trimmed) = self.serialize_payload(payload, None)?;
+        Ok(PreparedPayload::new(json, trimmed))
+    }
+
+    /// Serializes and size-checks a payload once, as [`prepare`](ApnsClient::prepare) does,
+    /// against the limit of `push_type`, such as the 5 KB of a VoIP push.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing either the prepared payload or an `ApnsError` if the payload
+    /// breaks the schema or is too large for `push_type`.
+    ///
+    /// # Example
+    ///
+    /// ```rust,no_run
+    /// use apnrs::{ApnsClient, ApnsPayload, PushType, SendOptions};
+    ///
+    /// # async fn run(client: ApnsClient, payload: ApnsPayload, tokens: Vec<String>) -> Result<(), apnrs::ApnsError> {
+    /// let prepared = client.prepare_for(&payload, PushType::Voip)?;
+    /// let options = SendOptions::new().push_type(PushType::Voip);
+    /// for token in &tokens {
+    ///     client
+    ///         .send_prepared(token, "com.example.app.voip", &prepared, &options)
+    ///         .await?;
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn prepare_for<P: Serialize + ?Sized>(
+        &self,
+        payload: &P,
+        push_type: PushType,
+    ) -> Result<PreparedPayload, ApnsError> {
+        let (json, trimmed) = self.serialize_payload(payload, Some(push_type))?;
+        Ok(PreparedPayload::new(json, trimmed))
+    }
+
+    /// Sends a payload prepared with [`prepare`](ApnsClient::prepare) or
+    /// [`prepare_for`](ApnsClient::prepare_for).
+    ///
+    /// The body is shared rather than copied, so fanning one payload out to many devices
+    /// serializes it only once. Unlike other sends, the payload is not trimmed: one larger
+    /// than the limit of the push type in `options` fails with
+    /// `ApnsError::InvalidNotification`, and if APNs answers `413 Payload Too Large` the
+    /// response is returned as is. Push types that need a certificate, such as `mdm`, fail
+    /// with `ApnsError::CertificateRequired` on a client without one.
+    ///
+    /// # Arguments
+    ///
+    /// * `device_token` - The device token of the target device.
+    /// * `topic` - The topic (usually the app's bundle ID) for the notification.
+    /// * `payload` - The prepared payload.
+    /// * `options` - The settings for this request.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing either the outcome of the request or an `ApnsError`.
+    ///
+    /// # Example
+    ///
+    /// ```rust,no_run
+    /// use apnrs::{ApnsClient, ApnsPayload, SendOptions};
+    ///
+    /// # async fn run(client: ApnsClient, payload: ApnsPayload, tokens: Vec<String>) -> Result<(), apnrs::ApnsError> {
+    /// let prepared = client.prepare(&payload)?;
+    /// let options = SendOptions::new();
+    /// for token in &tokens {
+    ///     client
+    ///         .send_prepared(token, "com.example.app", &prepared, &options)
+    ///         .await?;
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn send_prepared(
+        &self,
+        device_token: &str,
+        topic: &str,
+        payload: &PreparedPayload,
+        options: &SendOptions,
+    ) -> Result<SendOutcome, ApnsError> {
+        let _pending = self
+            .inner
+            .lifecycle
+            .start()
+            .ok_or(ApnsError::ClientShutDown)?;
+        // Without a certificate APNs answers certificate-only push types with a bare 403
+        if let Some(push_type) = options.push_type.filter(PushType::requires_certificate) {
+            if !self.inner.has_certificate {
+                return Err(ApnsError::CertificateRequired(push_type));
+            }
+        }
+        validate_request_topic(topic, options.push_type)?;
+        let push_type = options.push_type.unwrap_or(PushType::Alert);
+        if payload.len() > push_type.max_payload_size() {
+            return Err(NotificationError::TooLarge {
+                push_type,
+                size: payload.len(),
+                limit: push_type.max_payload_size(),
+            }
+            .into());
+        }
+        let delivery = Delivery {
+            device_token,
+            topic,
+            push_type: options.push_type,
+            priority: self.priority_for(options.priority, options.push_type, payload.as_bytes()),
+            options,
+        };
+        let send = async {
+            let outcome = self
+                .post_with_fallback(delivery, payload.as_bytes().clone())
+                .await?;
+            Ok(SendOutcome {
+                trimmed: payload.trimmed().cloned(),
+                ..outcome
+            })
+        };
+        self.within_deadline(topic, options, send).await
+    }
+
+    /// Sends a push notification with its alert text localized for the device's locale.
+    ///
+    /// The alert is resolved through the locale's fallback chain (`fr-CA` → `fr` → the
+    /// default text) and replaces `payload.aps.alert`. The returned outcome reports which
+    /// locale was used, so missing translations can be spotted instead of silently falling
+    /// back.
+    ///
+    /// # Arguments
+    ///
+    /// * `device_token` - The device token of the target device.
+    /// * `topic` - The topic (usually the app's bundle ID) for the notification.
+    /// * `payload` - The payload of the notification; its alert text is replaced.
+    /// * `alert` - The alert text in each available language.
+    /// * `locale` - The device's locale, such as `fr-CA`.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing either the outcome of the request or an `ApnsError`.
+    ///
+    /// # Example
+    ///
+    /// ```rust,no_run
+    /// use apnrs::{ApnsClient, ApnsPayload, LocalizedAlert};
+    ///
+    /// # async fn run(client: ApnsClient, payload: ApnsPayload) -> Result<(), apnrs::ApnsError> {
+    /// let alert = LocalizedAlert::new("Your order has shipped")
+    ///     .translation("fr", "Votre commande a été expédiée");
+    ///
+    /// let outcome = client
+    ///     .send_localized("DEVICE_TOKEN", "com.example.app", &payload, &alert, "fr-CA")
+    ///     .await?;
+    /// if let Some(locale) = outcome.locale.filter(|l| l.is_fallback()) {
+    ///     println!("missing {} translation, sent {:?}", locale.requested, locale.used);
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn send_localized(
+        &self,
+        device_token: &str,
+        topic: &str,
+        payload: &ApnsPayload,
+        alert: &LocalizedAlert,
+        locale: &str,
+    ) -> Result<SendOutcome, ApnsError> {
+        let (text, resolution) = alert.resolve(locale);
+
+        #[cfg(feature = "tracing")]
+        if resolution.is_fallback() {
+            tracing::debug!(
+                requested = %resolution.requested,
+                used = resolution.used.as_deref().unwrap_or("default"),
+                "no translation for locale, falling back"
+            );
+        }
+
+        let mut payload = payload.clone();
+        payload.aps.alert = text.to_string();
+        let mut outcome = self.send(device_token, topic, &payload).await?;
+        outcome.locale = Some(resolution);
+        Ok(outcome)
+    }
+
+    /// Sends a push notification whose custom data is a typed struct, serialized as keys next
+    /// to `aps`.
+    ///
+    /// # Arguments
+    ///
+    /// * `device_token` - The device token of the target device.
+    /// * `topic` - The topic (usually the app's bundle ID) for the notification.
+    /// * `payload` - The `aps` dictionary and the custom data.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing either the outcome of the request or an `ApnsError`.
+    ///
+    /// # Example
+    ///
+    /// ```rust,no_run
+    /// use apnrs::{ApnsClient, ApnsCustomData, Aps, CustomPayload};
+    ///
+    /// // For example a struct with `#[derive(ApnsCustomData)]`
+    /// # async fn run<D: ApnsCustomData + Sync>(client: ApnsClient, aps: Aps, data: D) -> Result<(), apnrs::ApnsError> {
+    /// let payload = CustomPayload::new(aps, data);
+    /// client.send_custom("DEVICE_TOKEN", "com.example.app", &payload).await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn send_custom<D: ApnsCustomData + Sync>(
+        &self,
+        device_token: &str,
+        topic: &str,
+        payload: &CustomPayload<D>,
+    ) -> Result<SendOutcome, ApnsError> {
+        self.send_as(
+            device_token,
+            topic,
+            None,
+            None,
+            payload,
+            &SendOptions::new(),
+        )
+        .await
+    }
+
+    /// Sends a notification of a specific push type, such as a VoIP or background push.
+    ///
+    /// The notification is validated against its push type's rules before anything is
+    /// sent, the push type is sent as `apns-push-type`, its priority (if set) as
+    /// `apns-priority`, and the topic is derived from the bundle ID in `topic` with the
+    /// push type's suffix (for example `.voip`); see [`PushType::topic_for`].
+    ///
+    /// # Arguments
+    ///
+    /// * `device_token` - The device token of the target device; for VoIP pushes, the
+    ///   PushKit token, and for location pushes, the location push token.
+    /// * `topic` - The app's bundle ID.
+    /// * `notification` - The notification to send.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing either the outcome of the request or an `ApnsError`.
+    ///
+    /// # Example
+    ///
+    /// ```rust,no_run
+    /// use apnrs::{ApnsClient, Notification};
+    /// use serde_json::json;
+    ///
+    /// # async fn run(client: ApnsClient) -> Result<(), apnrs::ApnsError> {
+    /// let call = Notification::voip(json!({ "caller": "Anna", "call_id": "42" }));
+    /// client.send_notification("PUSHKIT_TOKEN", "com.example.app", &call).await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn send_notification(
+        &self,
+        device_token: &str,
+        topic: &str,
+        notification: &Notification,
+    ) -> Result<SendOutcome, ApnsError> {
+        notification.validate()?;
+        let topic = notification.push_type.topic_for(topic);
+        self.send_as(
+            device_token,
+            &topic,
+            Some(notification.push_type),
+            notification.priority,
+            &notification.payload,
+            &SendOptions::new(),
+        )
+        .await
+    }
+
+    /// Sends a [`Notification`] in the background, so a caller such as a request handler can
+    /// carry on without waiting for APNs.
+    ///
+    /// The send is spawned onto the tokio runtime and counted as pending until it completes,
+    /// so [`shutdown`](Self::shutdown) waits for it. Awaiting the returned handle is
+    /// optional; when it is dropped instead, failures are only observed through stats,
+    /// [events](Self::subscribe_events), and [`on_unregistered`](ApnsClientBuilder::on_unregistered)
+    /// hooks.
+    ///
+    /// # Arguments
+    ///
+    /// * `device_token` - The device token of the target device.
+    /// * `topic` - The app's bundle ID.
+    /// * `notification` - The notification to send.
+    ///
+    /// # Returns
+    ///
+    /// The handle of the spawned send, resolving to its outcome; `ApnsError::InvalidNotification`
+    /// if the notification breaks the rules of its push type, or `ApnsError::ClientShutDown`
+    /// if the client is shutting down.
+    ///
+    /// # Example
+    ///
+    /// ```rust,no_run
+    /// use apnrs::{ApnsClient, Notification};
+    /// use serde_json::json;
+    ///
+    /// # fn run(client: ApnsClient) -> Result<(), apnrs::ApnsError> {
+    /// let refresh = Notification::background().custom("inbox", json!(3));
+    /// client.send_detached("DEVICE_TOKEN", "com.example.app", refresh)?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn send_detached(
+        &self,
+        device_token: impl Into<String>,
+        topic: impl Into<String>,
+        notification: Notification,
+    ) -> Result<JoinHandle<Result<SendOutcome, ApnsError>>, ApnsError> {
+        notification.validate()?;
+        let pending = self
+            .inner
+            .lifecycle
+            .start()
+            .ok_or(ApnsError::ClientShutDown)?;
+        let client = self.clone();
+        let device_token = device_token.into();
+        let topic = notification.push_type.topic_for(&topic.into());
+        Ok(tokio::spawn(async move {
+            let _pending = pending;
+            client
+                .dispatch(
+                    &device_token,
+                    &topic,
+                    Some(notification.push_type),
+                    notification.priority,
+                    &notification.payload,
+                    &SendOptions::new(),
+                )
+                .await
+        }))
+    }
+
+    /// Sends an MDM push asking a managed device to contact its MDM server.
+    ///
+    /// MDM pushes are sent with the `mdm` push type and must be authenticated with the MDM
+    /// push certificate, configured with
+    /// [`certificate_pkcs12`](ApnsClientBuilder::certificate_pkcs12).
+    ///
+    /// # Arguments
+    ///
+    /// * `device_token` - The device token the device reported in its `TokenUpdate` check-in.
+    /// * `topic` - The topic of the MDM push certificate, such as
+    ///   `com.apple.mgmt.External.<UUID>`.
+    /// * `notification` - The notification carrying the device's push magic.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing either the outcome of the request or an `ApnsError`;
+    /// `ApnsError::CertificateRequired` if the client has no certificate.
+    ///
+    /// # Example
+    ///
+    /// ```rust,no_run
+    /// use apnrs::{ApnsClient, MdmNotification};
+    ///
+    /// # async fn run() -> Result<(), apnrs::ApnsError> {
+    /// let client = ApnsClient::builder()
+    ///     .certificate_pkcs12_path("mdm-push.p12", "password")
+    ///     .build()?;
+    ///
+    /// let wake = MdmNotification::new("PUSH_MAGIC");
+    /// client
+    ///     .send_mdm("DEVICE_TOKEN", "com.apple.mgmt.External.0f4e2d6c", &wake)
+    ///     .await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn send_mdm(
+        &self,
+        device_token: &str,
+        topic: &str,
+        notification: &MdmNotification,
+    ) -> Result<SendOutcome, ApnsError> {
+        self.send_as(
+            device_token,
+            topic,
+            Some(PushType::Mdm),
+            None,
+            notification,
+            &SendOptions::new(),
+        )
+        .await
+    }
+
+    /// Sends a Safari web push to a website's subscriber.
+    ///
+    /// The website push ID and the payload are validated before anything is sent, and the
+    /// push is sent with the `alert` push type.
+    ///
+    /// # Arguments
+    ///
+    /// * `device_token` - The device token Safari returned when the user allowed notifications.
+    /// * `website_push_id` - The website push ID, such as `web.com.example`.
+    /// * `payload` - The web push payload.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing either the outcome of the request or an `ApnsError`.
+    ///
+    /// # Example
+    ///
+    /// ```rust,no_run
+    /// use apnrs::{ApnsClient, WebPushPayload};
+    ///
+    /// # async fn run(client: ApnsClient) -> Result<(), apnrs::ApnsError> {
+    /// let payload = WebPushPayload::new("New reply", "Anna replied to your post.")
+    ///     .url_args(["posts", "42"]);
+    /// client.send_web_push("DEVICE_TOKEN", "web.com.example", &payload).await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn send_web_push(
+        &self,
+        device_token: &str,
+        website_push_id: &str,
+        payload: &WebPushPayload,
+    ) -> Result<SendOutcome, ApnsError> {
+        validate_website_push_id(website_push_id)?;
+        payload.validate()?;
+        self.send_as(
+            device_token,
+            website_push_id,
+            Some(PushType::Alert),
+            None,
+            payload,
+            &SendOptions::new(),
+        )
+        .await
+    }
+
+    /// Sends a Live Activity update or end push.
+    ///
+    /// The push is sent with the `liveactivity` push type under the bundle ID in `topic`
+    /// followed by `.push-type.liveactivity`.
+    ///
+    /// # Arguments
+    ///
+    /// * `push_token` - The activity's push token, from `Activity.pushTokenUpdates`.
+    /// * `topic` - The app's bundle ID.
+    /// * `payload` - The Live Activity payload.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing either the outcome of the request or an `ApnsError`.
+    ///
+    /// # Example
+    ///
+    /// ```rust,no_run
+    /// use apnrs::{ApnsClient, LiveActivityPayload};
+    /// use serde_json::json;
+    ///
+    /// # async fn run(client: ApnsClient) -> Result<(), apnrs::ApnsError> {
+    /// let payload = LiveActivityPayload::end(json!({ "home": 3, "away": 1 }))
+    ///     .dismissal_date(1_700_003_600);
+    /// client.send_live_activity("PUSH_TOKEN", "com.example.app", &payload).await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn send_live_activity<S: Serialize + Sync>(
+        &self,
+        push_token: &str,
+        topic: &str,
+        payload: &LiveActivityPayload<S>,
+    ) -> Result<SendOutcome, ApnsError> {
+        let topic = PushType::LiveActivity.topic_for(topic);
+        self.send_as(
+            push_token,
+            &topic,
+            Some(PushType::LiveActivity),
+            None,
+            payload,
+            &SendOptions::new(),
+        )
+        .await
+    }
+
+    /// Starts a Live Activity remotely (iOS 17.2 and later).
+    ///
+    /// The push is sent with the `liveactivity` push type under the bundle ID in `topic`
+    /// followed by `.push-type.liveactivity`.
+    ///
+    /// # Arguments
+    ///
+    /// * `push_to_start_token` - The app's push-to-start token, from
+    ///   `Activity.pushToStartTokenUpdates`.
+    /// * `topic` - The app's bundle ID.
+    /// * `payload` - The start payload.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing either the outcome of the request or an `ApnsError`.
+    ///
+    /// # Example
+    ///
+    /// ```rust,no_run
+    /// use apnrs::{ApnsClient, LiveActivityStartPayload};
+    /// use serde_json::json;
+    ///
+    /// # async fn run(client: ApnsClient) -> Result<(), apnrs::ApnsError> {
+    /// let payload = LiveActivityStartPayload::new(
+    ///     "MatchAttributes",
+    ///     json!({ "homeTeam": "Lions", "awayTeam": "Tigers" }),
+    ///     json!({ "home": 0, "away": 0 }),
+    /// )
+    /// .alert("Kick-off", "Lions vs Tigers has started");
+    /// client.start_live_activity("PUSH_TO_START_TOKEN", "com.example.app", &payload).await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn start_live_activity<A: Serialize + Sync, S: Serialize + Sync>(
+        &self,
+        push_to_start_token: &str,
+        topic: &str,
+        payload: &LiveActivityStartPayload<A, S>,
+    ) -> Result<SendOutcome, ApnsError> {
+        let topic = PushType::LiveActivity.topic_for(topic);
+        self.send_as(
+            push_to_start_token,
+            &topic,
+            Some(PushType::LiveActivity),
+            None,
+            payload,
+            &SendOptions::new(),
+        )
+        .await
+    }
+
+    /// Updates a Live Activity to `content_state`, timestamped now.
+    ///
+    /// See [`send_live_activity`](ApnsClient::send_live_activity) for the arguments.
+    pub async fn update_live_activity<S: Serialize + Sync>(
+        &self,
+        push_token: &str,
+        topic: &str,
+        content_state: S,
+    ) -> Result<SendOutcome, ApnsError> {
+        let payload = LiveActivityPayload::update(content_state);
+        self.send_live_activity(push_token, topic, &payload).await
+    }
+
+    /// Ends a Live Activity with a final `content_state`, timestamped now.
+    ///
+    /// See [`send_live_activity`](ApnsClient::send_live_activity) for the arguments.
+    pub async fn end_live_activity<S: Serialize + Sync>(
+        &self,
+        push_token: &str,
+        topic: &str,
+        content_state: S,
+    ) -> Result<SendOutcome, ApnsError> {
+        let payload = LiveActivityPayload::end(content_state);
+        self.send_live_activity(push_token, topic, &payload).await
+    }
+
+    /// Sends any serializable payload with an optional push type and priority, unless the
+    /// client is shutting down.
+    pub(crate) async fn send_as<P: Serialize + Sync + ?Sized>(
+        &self,
+        device_token: &str,
+        topic: &str,
+        push_type: Option<PushType>,
+        priority: Option<Priority>,
+        payload: &P,
+        options: &SendOptions,
+    ) -> Result<SendOutcome, ApnsError> {
+        let _pending = self
+            .inner
+            .lifecycle
+            .start()
+            .ok_or(ApnsError::ClientShutDown)?;
+        self.dispatch(device_token, topic, push_type, priority, payload, options)
+            .await
+    }
+
+    /// Sends any serializable payload with an optional push type and priority, inside a
+    /// tracing span, failing if it takes longer than the send deadline.
+    ///
+    /// Sender workers call this directly, so queued notifications are still delivered while
+    /// the client shuts down.
+    pub(crate) async fn dispatch<P: Serialize + Sync + ?Sized>(
+        &self,
+        device_token: &str,
+        topic: &str,
+        push_type: Option<PushType>,
+        priority: Option<Priority>,
+        payload: &P,
+        options: &SendOptions,
+    ) -> Result<SendOutcome, ApnsError> {
+        let send = self.send_payload(device_token, topic, push_type, priority, payload, options);
+        self.within_deadline(topic, options, send).await
+    }
+
+    /// Runs a send inside a tracing span, failing if it takes longer than the send deadline.
+    ///
+    /// The span carries the trace and parent span IDs of the options' trace context, if
+    /// they have one.
+    async fn within_deadline(
+        &self,
+        #[cfg_attr(not(feature = "tracing"), allow(unused_variables))] topic: &str,
+        #[cfg_attr(not(feature = "tracing"), allow(unused_variables))] options: &SendOptions,
+        send: impl Future<Output = Result<SendOutcome, ApnsError>>,
+    ) -> Result<SendOutcome, ApnsError> {
+        #[cfg(feature = "tracing")]
+        let send = {
+            let trace = options.trace_context.as_ref();
+            tracing::Instrument::instrument(
+                send,
+                tracing::info_span!(
+                    "apns.send",
+                    topic = topic,
+                    environment = ?self.environment_for(topic),
+                    trace_id = trace.map(TraceContext::trace_id),
+                    parent_span_id = trace.map(TraceContext::parent_id),
+                    apns_id = tracing::field::Empty,
+                    status = tracing::field::Empty,
+                    latency_ms = tracing::field::Empty,
+                ),
+            )
+        };
+
+        let deadline = self.inner.send_deadline;
+        tokio::time::timeout(deadline, send)
+            .await
+            .unwrap_or(Err(ApnsError::DeadlineExceeded(deadline)))
+    }
+
+    /// Builds the exact request [`send_request`](ApnsClient::send_request) would post for
+    /// `request`, without sending it, so it can be inspected or audited and then sent with
+    /// [`execute`](ApnsClient::execute).
+    ///
+    /// The payload is validated, serialized, and trimmed, the provider token signed or
+    /// reused, and the `before_send` hooks run, exactly as for a send. The URL is that of
+    /// the environment the topic is sent to, unless the request's options name another.
+    ///
+    /// # Returns
+    ///
+    /// The prepared request, or the `ApnsError` the send would have failed with before
+    /// reaching APNs.
+    ///
+    /// # Example
+    ///
+    /// ```rust,no_run
+    /// use apnrs::{ApnsClient, ApnsPayload, PushRequest};
+    ///
+    /// # async fn run(client: ApnsClient, payload: ApnsPayload) -> Result<(), apnrs::ApnsError> {
+    /// let request = PushRequest::new("DEVICE_TOKEN", "com.example.app", payload);
+    /// let prepared = client.prepare_request(&request).await?;
+    ///
+    /// println!("POST {}", prepared.url);
+    /// println!("{:?}", prepared.redacted_headers());
+    /// println!("{}", String::from_utf8_lossy(&prepared.body));
+    ///
+    /// let outcome = client.execute(prepared).await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn prepare_request(
+        &self,
+        request: &PushRequest,
+    ) -> Result<PreparedRequest, ApnsError> {
+        let options = &request.options;
+        if let Some(push_type) = options.push_type.filter(PushType::requires_certificate) {
+            if !self.inner.has_certificate {
+                return Err(ApnsError::CertificateRequired(push_type));
+            }
+        }
+        validate_request_topic(&request.topic, options.push_type)?;
+        let (body, _) = self.serialize_payload(&request.payload, options.push_type)?;
+
+        let delivery = Delivery {
+            device_token: &request.device_token,
+            topic: &request.topic,
+            push_type: options.push_type,
+            priority: self.priority_for(options.priority, options.push_type, &body),
+            options,
+        };
+        let environment = options
+            .environment
+            .unwrap_or_else(|| self.environment_for(&request.topic));
+        let (url, request) = self.build_request(environment, delivery, body).await?;
+        Ok(PreparedRequest {
+            url,
+            headers: request.headers,
+            body: request.body.to_vec(),
+        })
+    }
+
+    /// Sends a request built by [`prepare_request`](ApnsClient::prepare_request), or
+    /// assembled by hand, exactly as it is.
+    ///
+    /// Nothing about the request is changed: it is not trimmed, its provider token is not
+    /// renewed if it has expired since it was prepared, and it is not resent to the other
+    /// environment or after a lost connection. APNs' answer is recorded in the client's
+    /// stats, events, and token store, and the `after_send` hooks run, as for any send.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing either the outcome of the request or an `ApnsError`.
+    pub async fn execute(&self, request: PreparedRequest) -> Result<SendOutcome, ApnsError> {
+        let _pending = self
+            .inner
+            .lifecycle
+            .start()
+            .ok_or(ApnsError::ClientShutDown)?;
+        let header = |name: &str| request.headers.get(name).and_then(|v| v.to_str().ok());
+        let mut options = SendOptions::new();
+        if let Some(apns_id) = header("apns-id") {
+            options = options.apns_id(apns_id);
+        }
+        let push_type = header("apns-push-type").and_then(PushType::from_header);
+        let environment = match request.url.starts_with(Environment::Sandbox.base_url()) {
+            true => Environment::Sandbox,
+            false => Environment::Production,
+        };
+        let device_token = request.device_token().to_string();
+        let topic = request.topic().unwrap_or_default().to_string();
+
+        let PreparedRequest { url, headers, body } = request;
+        let delivery = Delivery {
+            device_token: &device_token,
+            topic: &topic,
+            push_type,
+            priority: None,
+            options: &options,
+        };
+        let request = OutgoingRequest {
+            headers,
+            body: Bytes::from(body),
+        };
+        let post = async {
+            let answer = self.post_built(environment, delivery, url, request).await?;
+            Ok(answer.into_outcome(None))
+        };
+        self.within_deadline(&topic, &options, post).await
+    }
+
+    /// Sends several notifications to one device, one after another over the same
+    /// connection.
+    ///
+    /// Each notification is sent only after APNs has answered the previous one, so they
+    /// arrive in order. With several [`connections`](ApnsClientBuilder::connections), one
+    /// is picked for the whole call and every notification goes out on it. A failed notification does not stop the remaining ones, but once the
+    /// token is known to be invalid the remaining ones are skipped.
+    ///
+    /// # Arguments
+    ///
+    /// * `device_token` - The device token of the target device.
+    /// * `topic` - The topic (usually the app's bundle ID) for the notifications.
+    /// * `payloads` - The payloads to send, in delivery order.
+    ///
+    /// # Returns
+    ///
+    /// One result per payload, in the same order as `payloads`. Skipped payloads are
+    /// `ApnsError::InvalidToken`.
+    pub async fn send_all_to(
+        &self,
+        device_token: &str,
+        topic: &str,
+        payloads: &[ApnsPayload],
+    ) -> Vec<Result<SendOutcome, ApnsError>> {
+        let options = SendOptions {
+            connection: Some(self.inner.http.choose()),
+            ..SendOptions::new()
+        };
+        let mut outcomes = Vec::with_capacity(payloads.len());
+        for payload in payloads {
+            if self.inner.token_store.is_invalid(device_token) {
+                outcomes.push(Err(ApnsError::InvalidToken));
+                continue;
+            }
+            outcomes.push(self.send_with(device_token, topic, payload, &options).await);
+        }
+        outcomes
+    }
+
+    /// Sends a notification to every device the client's [`DeviceRegistry`] lists for a
+    /// user, each on the topic it was registered with.
+    ///
+    /// The devices are sent to concurrently. Devices APNs answers `410 Unregistered` for are
+    /// removed from the registry, as are devices whose token the client's token store
+    /// already knows is invalid, which are not sent to and yield `ApnsError::InvalidToken`.
+    ///
+    /// # Arguments
+    ///
+    /// * `user_id` - The user, as the registry knows them.
+    /// * `payload` - The payload of the notification.
+    ///
+    /// # Returns
+    ///
+    /// One result per device, in the order the registry listed them, or `ApnsError::Config`
+    /// if the client has no device registry.
+    ///
+    /// # Example
+    ///
+    /// ```rust,no_run
+    /// use apnrs::{ApnsClient, ApnsPayload, MemoryDeviceRegistry, UserDevice};
+    ///
+    /// # async fn run(payload: ApnsPayload) -> Result<(), apnrs::ApnsError> {
+    /// let registry = MemoryDeviceRegistry::new();
+    /// registry.register("user-42", UserDevice::new("PHONE_TOKEN", "com.example.app"));
+    ///
+    /// let client = ApnsClient::builder()
+    ///     .auth_key_path("path/to/auth/key")
+    ///     .team_id("TEAM_ID")
+    ///     .key_id("KEY_ID")
+    ///     .device_registry(registry)
+    ///     .build()?;
+    ///
+    /// for (device, result) in client.send_to_user("user-42", &payload).await? {
+    ///     if let Err(e) = result {
+    ///         eprintln!("{} on {}: {}", device.device_token, device.topic, e);
+    ///     }
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn send_to_user(
+        &self,
+        user_id: &str,
+        payload: &ApnsPayload,
+    ) -> Result<Vec<(UserDevice, Result<SendOutcome, ApnsError>)>, ApnsError> {
+        let registry = self.inner.device_registry.as_ref().ok_or_else(|| {
+            ApnsError::Config("no device registry is configured for sending to users".to_string())
+        })?;
+        let devices = registry.devices(user_id);
+        let sends: Vec<_> = devices
+            .iter()
+            .map(|device| async move {
+                if self.inner.token_store.is_invalid(&device.device_token) {
+                    return Err(ApnsError::InvalidToken);
+                }
+                self.send(&device.device_token, &device.topic, payload)
+                    .await
+            })
+            .collect();
+        let results: Vec<_> = stream::iter(sends)
+            .buffered(devices.len().max(1))
+            .collect()
+            .await;
+
+        for (device, result) in devices.iter().zip(&results) {
+            let unregistered = match result {
+                Ok(outcome) => outcome.response.is_unregistered(),
+                Err(e) => matches!(e, ApnsError::InvalidToken),
+            };
+            if unregistered {
+                registry.remove(user_id, &device.device_token);
+            }
+        }
+        Ok(devices.into_iter().zip(results).collect())
+    }
+
+    /// Sends notifications that differ in device token, topic, payload, and options,
+    /// multiplexed over the client's shared connections.
+    ///
+    /// Up to `concurrency` requests are in flight at once, still within the client's
+    /// [`max_in_flight`](ApnsClientBuilder::max_in_flight); each is sent as by
+    /// [`send_request`](ApnsClient::send_request), so a failed notification does not stop the
+    /// others.
+    ///
+    /// # Arguments
+    ///
+    /// * `requests` - The notifications to send.
+    /// * `concurrency` - How many requests are in flight at once.
+    ///
+    /// # Returns
+    ///
+    /// One result per request, in the same order as `requests`.
+    ///
+    /// # Example
+    ///
+    /// ```rust,no_run
+    /// use apnrs::{ApnsClient, ApnsPayload, PushRequest, PushType, SendOptions};
+    ///
+    /// # async fn run(client: ApnsClient, order: ApnsPayload, call: ApnsPayload) {
+    /// let requests = [
+    ///     PushRequest::new("CUSTOMER_TOKEN", "com.example.shop", order),
+    ///     PushRequest::new("COURIER_TOKEN", "com.example.courier.voip", call)
+    ///         .options(SendOptions::new().push_type(PushType::Voip)),
+    /// ];
+    /// for (request, result) in requests.iter().zip(client.send_batch(&requests, 32).await) {
+    ///     if let Err(e) = result {
+    ///         eprintln!("{} on {}: {}", request.device_token, request.topic, e);
+    ///     }
+    /// }
+    /// # }
+    /// ```
+    pub async fn send_batch(
+        &self,
+        requests: &[PushRequest],
+        concurrency: usize,
+    ) -> Vec<Result<SendOutcome, ApnsError>> {
+        let sends: Vec<_> = requests
+            .iter()
+            .map(|request| self.send_request(request))
+            .collect();
+        stream::iter(sends)
+            .buffered(concurrency.max(1))
+            .collect()
+            .await
+    }
+
+    /// Sends the payload, trimming and resending it once if APNs answers 413.
+    async fn send_payload<P: Serialize + Sync + ?Sized>(
+        &self,
+        device_token: &str,
+        topic: &str,
+        push_type: Option<PushType>,
+        priority: Option<Priority>,
+        payload: &P,
+        options: &SendOptions,
+    ) -> Result<SendOutcome, ApnsError> {
+        let push_type = options.push_type.or(push_type);
+        let priority = options.priority.or(priority);
+        // Without a certificate APNs answers certificate-only push types with a bare 403
+        if let Some(push_type) = push_type.filter(PushType::requires_certificate) {
+            if !self.inner.has_certificate {
+                return Err(ApnsError::CertificateRequired(push_type));
+            }
+        }
+        validate_request_topic(topic, push_type)?;
+        let (body, trimmed) = self.serialize_payload(payload, push_type)?;
+        let priority = self.priority_for(priority, push_type, &body);
+
+        let delivery = Delivery {
+            device_token,
+            topic,
+            push_type,
+            priority,
+            options,
+        };
+        let outcome = self.post_with_fallback(delivery, body).await?;
+        let outcome = SendOutcome { trimmed, ..outcome };
+
+        if outcome.response.status != StatusCode::PAYLOAD_TOO_LARGE || outcome.trimmed.is_some() {
+            return Ok(outcome);
+        }
+        // APNs may count the payload differently than we do; shrink it and retry once
+        let limit = push_type.map_or(MAX_PAYLOAD_SIZE, |t| t.max_payload_size());
+        let Some((body, report)) = self.trim_payload(payload, limit)? else {
+            return Ok(outcome);
+        };
+        let outcome = self.post_with_fallback(delivery, Bytes::from(body)).await?;
+
+        Ok(SendOutcome {
+            trimmed: Some(report),
+            ..outcome
+        })
+    }
+
+    /// Returns `priority`, or with [`auto_priority`](ApnsClientBuilder::auto_priority) the
+    /// priority recommended for `body` if `priority` is `None`.
+    fn priority_for(
+        &self,
+        priority: Option<Priority>,
+        push_type: Option<PushType>,
+        body: &[u8],
+    ) -> Option<Priority> {
+        if priority.is_some() || !self.inner.auto_priority {
+            return priority;
+        }
+        let payload = serde_json::from_slice(body).ok()?;
+        Priority::recommended(push_type, &payload)
+    }
+
+    /// Checks `payload` against the custom data schema and serializes it, trimming it with
+    /// the registered strategy if it is larger than `push_type` allows.
+    ///
+    /// # Returns
+    ///
+    /// The request body and what trimming removed, or an `ApnsError` if the payload breaks
+    /// the schema or is still too large.
+    fn serialize_payload<P: Serialize + ?Sized>(
+        &self,
+        payload: &P,
+        push_type: Option<PushType>,
+    ) -> Result<(Bytes, Option<TrimReport>), ApnsError> {
+        #[cfg(feature = "schema")]
+        if let Some(schema) = &self.inner.custom_data_schema {
+            schema.validate(payload)?;
+        }
+
+        let limit = push_type.map_or(MAX_PAYLOAD_SIZE, |t| t.max_payload_size());
+        let body = serde_json::to_vec(payload)?;
+        if body.len() <= limit {
+            return Ok((Bytes::from(body), None));
+        }
+        // APNs would only reject the payload after a round trip, so trim it now or fail fast
+        match self.trim_payload(payload, limit)? {
+            Some((smaller, report)) if smaller.len() <= limit => {
+                Ok((Bytes::from(smaller), Some(report)))
+            }
+            smaller => Err(NotificationError::TooLarge {
+                push_type: push_type.unwrap_or(PushType::Alert),
+                size: smaller.map_or(body.len(), |(smaller, _)| smaller.len()),
+                limit,
+            }
+            .into()),
+        }
+    }
+
+    /// Posts `body` to the topic's environment, then to the other one if the client falls
+    /// back on `BadDeviceToken` and the first answered with it.
+    async fn post_with_fallback(
+        &self,
+        delivery: Delivery<'_>,
+        body: Bytes,
+    ) -> Result<SendOutcome, ApnsError> {
+        let (environment, fallback) = match delivery.options.environment {
+            Some(environment) => (environment, false),
+            None => (
+                self.environment_for(delivery.topic),
+                self.inner.environment_fallback,
+            ),
+        };
+        let retry_body = fallback.then(|| body.clone());
+        let outcome = self
+            .post_renewing(environment, delivery, body)
+            .await?
+            .into_outcome(None);
+
+        let Some(body) = retry_body else {
+            return Ok(outcome);
+        };
+        if outcome.response.error != Some(ErrorReason::BadDeviceToken) {
+            return Ok(outcome);
+        }
+        let other = environment.other();
+        #[cfg(feature = "logging")]
+        log::info!(
+            "{:?} rejected {} as BadDeviceToken, resending to {:?}",
+            environment,
+            mask_token(delivery.device_token),
+            other
+        );
+        #[cfg(feature = "tracing")]
+        tracing::info!(environment = ?other, "device token rejected, resending to the other environment");
+        let answer = self.post_renewing(other, delivery, body).await?;
+
+        Ok(SendOutcome {
+            fallback: Some(other),
+            ..answer.into_outcome(None)
+        })
+    }
+
+    /// Posts `body` like [`post`](Self::post), and once more with a newly signed provider
+    /// token if APNs answers `ExpiredProviderToken`, e.g. after the host clock drifted.
+    ///
+    /// The token is only discarded if the cache allows it, so a burst of rejections does
+    /// not sign more often than APNs permits; otherwise the rejection is returned as is.
+    async fn post_renewing(
+        &self,
+        environment: Environment,
+        delivery: Delivery<'_>,
+        body: Bytes,
+    ) -> Result<Answer, ApnsError> {
+        let answer = self
+            .post_reconnecting(environment, delivery, body.clone())
+            .await?;
+        if answer.response.error != Some(ErrorReason::ExpiredProviderToken) {
+            return Ok(answer);
+        }
+        let credentials = self.credentials_for(delivery.topic, delivery.options)?;
+        let Some(tokens) = credentials.or(self.inner.tokens.as_ref()) else {
+            return Ok(answer);
+        };
+        if !tokens.invalidate() {
+            return Ok(answer);
+        }
+        #[cfg(feature = "logging")]
+        log::info!(
+            "provider token expired, resending to {} on {} with a new one",
+            mask_token(delivery.device_token),
+            delivery.topic
+        );
+        #[cfg(feature = "tracing")]
+        tracing::info!("provider token expired, resending with a new one");
+        self.inner.events.retried(
+            delivery.topic,
+            delivery.device_token,
+            delivery.options.apns_id.as_deref(),
+            1,
+            ErrorReason::ExpiredProviderToken.as_str(),
+        );
+        self.post_reconnecting(environment, delivery, body).await
+    }
+
+    /// Posts `body` like [`post`](Self::post), resending it on a new connection when the
+    /// connection it went out on is lost, up to the configured number of times.
+    ///
+    /// A request that may have reached APNs is only resent if it carries an `apns-id`.
+    async fn post_reconnecting(
+        &self,
+        environment: Environment,
+        delivery: Delivery<'_>,
+        body: Bytes,
+    ) -> Result<Answer, ApnsError> {
+        let mut attempt = 0;
+        loop {
+            match self.post(environment, delivery, body.clone()).await {
+                Err(ApnsError::Http(e))
+                    if e.is_connection_lost()
+                        && attempt < self.inner.connection_retries
+                        && (e.is_refused() || delivery.options.apns_id.is_some()) =>
+                {
+                    attempt += 1;
+                    #[cfg(feature = "logging")]
+                    log::info!(
+                        "connection to APNs lost, resending to {} on {} (attempt {}): {}",
+                        mask_token(delivery.device_token),
+                        delivery.topic,
+                        attempt,
+                        e
+                    );
+                    #[cfg(feature = "tracing")]
+                    tracing::info!(attempt, error = %e, "connection to APNs lost, resending");
+                    self.inner.events.retried(
+                        delivery.topic,
+                        delivery.device_token,
+                        delivery.options.apns_id.as_deref(),
+                        attempt,
+                        &e.to_string(),
+                    );
+                }
+                result => return result,
+            }
+        }
+    }
+
+    /// Shrinks `payload` to `limit` bytes with the registered trimming strategy.
+    ///
+    /// # Returns
+    ///
+    /// The trimmed body and what was removed, or `None` if no strategy is registered or it
+    /// could not remove anything.
+    fn trim_payload<P: Serialize + ?Sized>(
+        &self,
+        payload: &P,
+        limit: usize,
+    ) -> Result<Option<(Vec<u8>, TrimReport)>, ApnsError> {
+        let Some(strategy) = &self.inner.trim_strategy else {
+            return Ok(None);
+        };
+        let mut value = serde_json::to_value(payload)?;
+        let report = strategy.trim(&mut value, limit);
+        if report.is_unchanged() {
+            return Ok(None);
+        }
+
+        #[cfg(feature = "logging")]
+        log::warn!(
+            "payload too large, trimmed it from {} to {} bytes",
+            report.original_size,
+            report.trimmed_size
+        );
+        #[cfg(feature = "tracing")]
+        tracing::warn!(
+            removed_keys = ?report.removed_keys,
+            alert_truncated_bytes = report.alert_truncated_bytes,
+            original_size = report.original_size,
+            trimmed_size = report.trimmed_size,
+            "payload too large, sending trimmed payload"
+        );
+
+        Ok(Some((serde_json::to_vec(&value)?, report)))
+    }
+
+    /// Builds the headers for a request to `topic`, reusing the cached provider token.
+    ///
+    /// This is the part of the work `send` does before handing the request to the
+    /// connection that depends on the provider token; see [`FAST_PATH_BUDGET`].
+    ///
+    /// # Returns
+    ///
+    /// The headers, or an `ApnsError` if a new provider token had to be signed and could not
+    /// be, or the topic is not a valid header value.
+    pub async fn request_headers(
+        &self,
+        topic: &str,
+        push_type: Option<PushType>,
+    ) -> Result<HeaderMap, ApnsError> {
+        self.headers_for(topic, push_type, None).await
+    }
+
+    /// Builds the headers for a request to `topic`, signed from `credentials` if given and
+    /// from the client's own key otherwise.
+    async fn headers_for(
+        &self,
+        topic: &str,
+        push_type: Option<PushType>,
+        credentials: Option<&TokenCache>,
+    ) -> Result<HeaderMap, ApnsError> {
+        let mut headers = match (push_type, credentials) {
+            (Some(push_type), _) if push_type.requires_certificate() => certificate_headers(),
+            (_, Some(tokens)) => self.refreshed_headers(tokens).await?,
+            (_, None) => self.provider_headers().await?,
+        };
+        headers.insert("apns-topic", HeaderValue::from_str(topic)?);
+        if let Some(push_type) = push_type {
+            headers.insert(
+                "apns-push-type",
+                HeaderValue::from_static(push_type.as_str()),
+            );
+        }
+        Ok(headers)
+    }
+
+    /// Posts `body` to the device URL in `environment` with the cached provider token.
+    ///
+    /// The response body is read in full; for rejected notifications it is parsed to
+    /// extract the reason, and unregistered tokens are recorded and reported to hooks.
+    async fn post(
+        &self,
+        environment: Environment,
+        delivery: Delivery<'_>,
+        body: Bytes,
+    ) -> Result<Answer, ApnsError> {
+        let (url, request) = self.build_request(environment, delivery, body).await?;
+        self.post_built(environment, delivery, url, request).await
+    }
+
+    /// Builds the request posting `body` to the device URL in `environment`: signs or
+    /// reuses the provider token, sets the headers, and runs the `before_send` hooks.
+    ///
+    /// # Returns
+    ///
+    /// The URL and the request, or an `ApnsError` if no token could be signed, a header
+    /// is not valid, or a hook failed the send.
+    async fn build_request(
+        &self,
+        environment: Environment,
+        delivery: Delivery<'_>,
+        body: Bytes,
+    ) -> Result<(String, OutgoingRequest), ApnsError> {
+        let Delivery {
+            device_token,
+            topic,
+            push_type,
+            priority,
+            options,
+        } = delivery;
+        let credentials = self.credentials_for(topic, options)?;
+        let mut headers = self.headers_for(topic, push_type, credentials).await?;
+        if let Some(priority) = priority {
+            headers.insert("apns-priority", HeaderValue::from_static(priority.as_str()));
+        }
+        insert_option_headers(&mut headers, options)?;
+        let context = SendContext {
+            device_token,
+            topic,
+            push_type,
+            environment,
+        };
+        let mut request = OutgoingRequest { headers, body };
+        for hook in &self.inner.send_hooks {
+            hook.before_send(&context, &mut request)?;
+        }
+        Ok((environment.device_url(device_token), request))
+    }
+
+    /// Posts a request built by [`build_request`](ApnsClient::build_request) to `url`, or
+    /// answers it in dry-run mode or from the simulator.
+    async fn post_built(
+        &self,
+        environment: Environment,
+        delivery: Delivery<'_>,
+        url: String,
+        request: OutgoingRequest,
+    ) -> Result<Answer, ApnsError> {
+        let Delivery {
+            device_token,
+            topic,
+            push_type,
+            options,
+            ..
+        } = delivery;
+        let inner = &self.inner;
+        let context = SendContext {
+            device_token,
+            topic,
+            push_type,
+            environment,
+        };
+        let OutgoingRequest { headers, body } = request;
+        let dry_run = inner.dry_run || options.dry_run;
+        if dry_run || inner.simulator.is_some() {
+            let answer = Answer::dry_run(PreparedRequest {
+                url,
+                headers,
+                body: body.to_vec(),
+            });
+            let answer = match &inner.simulator {
+                Some(simulator) if !dry_run => {
+                    let apns_id = answer.response.apns_id.as_deref().unwrap_or_default();
+                    simulator
+                        .deliver(topic, apns_id, &body)
+                        .await
+                        .map(|()| answer)
+                }
+                _ => Ok(answer),
+            };
+            for hook in &inner.send_hooks {
+                hook.after_send(
+                    &context,
+                    answer.as_ref().map(|a| &a.response),
+                    Duration::ZERO,
+                );
+            }
+            return answer;
+        }
+
+        #[cfg(feature = "tracing")]
+        tracing::debug!(bytes = body.len(), "dispatching request to APNs");
+        #[cfg(feature = "logging")]
+        log::debug!(
+            "sending notification to {} on {} ({} bytes)",
+            mask_token(device_token),
+            topic,
+            body.len()
+        );
+        #[cfg(feature = "logging")]
+        if inner.wire_logging {
+            log::debug!(target: WIRE_LOG_TARGET, "{}", wire_request(&url, &headers, &body));
+        }
+
+        let labels = RequestLabels { topic, push_type };
+        let trace_id = options.trace_context.as_ref().map(TraceContext::trace_id);
+        // Waiting for a slot is not part of the request's latency
+        let permit = match &inner.in_flight {
+            Some(in_flight) => Some(in_flight.acquire().await.expect("semaphore never closed")),
+            None => None,
+        };
+        let started = Instant::now();
+        // The connection counts the request as in flight until its body has been read
+        let http = inner.http.pick(options.connection);
+        let response = http.post(&url).headers(headers).body(body).send().await;
+        let answer = match response {
+            Ok(response) => match self.check_pins(&response) {
+                Ok(()) => read_response(response).await,
+                Err(e) => Err(e),
+            },
+            Err(e) => {
+                #[cfg(feature = "logging")]
+                log::warn!(
+                    "request to APNs for {} on {} failed: {}",
+                    mask_token(device_token),
+                    topic,
+                    e
+                );
+                telemetry::transport_failed(&inner.metric_labels, &labels, &e, started.elapsed());
+                inner.stats.transport_failed(started.elapsed());
+                inner.events.transport_failed(
+                    topic,
+                    device_token,
+                    options.apns_id.as_deref(),
+                    &e,
+                    started.elapsed(),
+                    trace_id,
+                );
+                Err(e.into())
+            }
+        };
+        drop(http);
+        drop(permit);
+        let latency = started.elapsed();
+        #[cfg(feature = "logging")]
+        if let (true, Ok(answer)) = (inner.wire_logging, &answer) {
+            log::debug!(target: WIRE_LOG_TARGET, "{}", wire_response(answer.response.raw()));
+        }
+        for hook in &inner.send_hooks {
+            hook.after_send(&context, answer.as_ref().map(|a| &a.response), latency);
+        }
+
+        let answer = answer?;
+        let response = &answer.response;
+        let status = response.status.as_u16();
+        let reason = &response.error;
+        telemetry::response_received(
+            &inner.metric_labels,
+            &labels,
+            status,
+            reason.as_ref(),
+            latency,
+        );
+        inner
+            .stats
+            .response_received(status, reason.as_ref(), latency);
+        inner
+            .events
+            .response_received(topic, device_token, response, latency, trace_id);
+        inner.events.answered(
+            topic,
+            device_token,
+            status,
+            reason.as_ref(),
+            response.apns_id.as_deref(),
+            response.unregistered_at,
+        );
+
+        #[cfg(feature = "tracing")]
+        {
+            let span = tracing::Span::current();
+            let latency_ms = latency.as_millis() as u64;
+            let apns_id = response.apns_id.as_deref().unwrap_or_default();
+            span.record("status", status);
+            span.record("apns_id", apns_id);
+            span.record("latency_ms", latency_ms);
+            match reason {
+                None => tracing::debug!(status, apns_id, latency_ms, "received APNs response"),
+                Some(reason) => tracing::warn!(
+                    status,
+                    %reason,
+                    apns_id,
+                    latency_ms,
+                    "APNs rejected the notification"
+                ),
+            }
+        }
+
+        #[cfg(feature = "logging")]
+        match reason {
+            None => log::debug!(
+                "APNs answered {} for {} on {} in {:?} (apns-id {})",
+                status,
+                mask_token(device_token),
+                topic,
+                latency,
+                response.apns_id.as_deref().unwrap_or("none")
+            ),
+            Some(reason) => log::warn!(
+                "APNs rejected the notification to {} on {} with {} {} (apns-id {})",
+                mask_token(device_token),
+                topic,
+                status,
+                reason,
+                response.apns_id.as_deref().unwrap_or("none")
+            ),
+        }
+
+        if response.is_unregistered() {
+            inner
+                .token_store
+                .mark_invalid(device_token, response.unregistered_at);
+            for hook in &inner.unregistered_hooks {
+                hook(device_token, response.unregistered_at);
+            }
+        }
+
+        Ok(answer)
+    }
+}
+
+/// Where and how a notification is sent, apart from its body.
+#[derive(Clone, Copy)]
+struct Delivery<'a> {
+    device_token: &'a str,
+    topic: &'a str,
+    push_type: Option<PushType>,
+    priority: Option<Priority>,
+    options: &'a SendOptions,
+}
+
+/// Returns the headers of a request authenticated by the client certificate alone.
+fn certificate_headers() -> HeaderMap {
+    let mut headers = HeaderMap::new();
+    headers.insert(CONTENT_TYPE, HeaderValue::from_static("application/json"));
+    headers
+}
+
+/// An APNs response, or the stand-in for one in a dry run.
+pub(crate) struct Answer {
+    pub(crate) response: ApnsResponse,
+    pub(crate) dry_run: Option<PreparedRequest>,
+}
+
+impl Answer {
+    /// Answers a dry run with `200 OK` and the `apns-id` the request would have been sent
+    /// with, or one as APNs would have assigned.
+    fn dry_run(request: PreparedRequest) -> Self {
+        let apns_id = match request.headers.get("apns-id") {
+            Some(apns_id) => apns_id.clone(),
+            None => HeaderValue::from_str(&new_apns_id()).expect("a UUID is a valid header value"),
+        };
+        let response = Response::builder()
+            .status(StatusCode::OK)
+            .header("apns-id", apns_id)
+            .body(Bytes::new())
+            .expect("a static status and a valid header");
+        Answer {
+            response: ApnsResponse::from_raw(response),
+            dry_run: Some(request),
+        }
+    }
+
+    pub(crate) fn into_outcome(self, trimmed: Option<TrimReport>) -> SendOutcome {
+        SendOutcome {
+            response: self.response,
+            trimmed,
+            failure: None,
+            locale: None,
+            dry_run: self.dry_run,
+            fallback: None,
+        }
+    }
+}
+
+/// Buffers a response and parses the reason APNs gave if the notification was rejected.
+pub(crate) async fn read_response(response: reqwest::Response) -> Result<Answer, ApnsError> {
+    Ok(Answer {
+        response: ApnsResponse::read(response).await?,
+        dry_run: None,
+    })
+}
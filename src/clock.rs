@@ -0,0 +1,54 @@
+//! The source of the current time provider tokens are issued at.
+
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Tells the time provider tokens are issued at, as their `iat` claim.
+///
+/// APNs rejects tokens whose `iat` is more than an hour off from its own clock, so a host
+/// with a skewed clock can supply a corrected time, and tests a fixed one to get the same
+/// token every run. Set it with
+/// [`ApnsClientBuilder::clock`](crate::ApnsClientBuilder::clock) or
+/// [`Credentials::clock`](crate::Credentials::clock); without one, the [`SystemClock`] is
+/// used.
+///
+/// # Example
+///
+/// ```rust
+/// use apnrs::{Clock, SystemClock};
+/// use std::time::{Duration, SystemTime};
+///
+/// /// Corrects for a host clock known to run 90 seconds fast.
+/// struct SkewedClock {
+///     offset: Duration,
+/// }
+///
+/// impl Clock for SkewedClock {
+///     fn now(&self) -> SystemTime {
+///         SystemClock.now() - self.offset
+///     }
+/// }
+///
+/// let clock = SkewedClock { offset: Duration::from_secs(90) };
+/// assert!(clock.unix_time() + 90 >= SystemClock.unix_time());
+/// ```
+pub trait Clock: Send + Sync {
+    /// Returns the current time.
+    fn now(&self) -> SystemTime;
+
+    /// Returns the current time in seconds since the Unix epoch, or 0 if it is earlier.
+    fn unix_time(&self) -> u64 {
+        self.now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |d| d.as_secs())
+    }
+}
+
+/// The host's clock, as read with [`SystemTime::now`].
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> SystemTime {
+        SystemTime::now()
+    }
+}
@@ -0,0 +1,90 @@
+//! Typed custom data sent next to the `aps` dictionary.
+
+use crate::Aps;
+use serde::ser::{Error, SerializeMap, Serializer};
+use serde::Serialize;
+use serde_json::Value;
+use std::collections::HashMap;
+
+/// The top-level keys of an [`ApnsPayload`](crate::ApnsPayload) that its `custom_fields`
+/// must not use.
+const RESERVED_FIELDS: &[&str] = &["aps", "custom_key"];
+
+/// Custom data whose fields are sent as top-level keys of a payload, next to `aps`.
+///
+/// Derive it with `#[derive(ApnsCustomData)]` from the `derive` feature, which checks at
+/// compile time that no field uses a key APNs reserves and generates the serialization.
+/// Implementing it by hand is only needed for data whose keys are not known up front.
+///
+/// # Example
+///
+/// ```rust
+/// use apnrs::ApnsCustomData;
+/// use serde::ser::SerializeMap;
+///
+/// struct Order {
+///     id: u64,
+/// }
+///
+/// impl ApnsCustomData for Order {
+///     const KEYS: &'static [&'static str] = &["order-id"];
+///
+///     fn serialize_fields<M: SerializeMap>(&self, map: &mut M) -> Result<(), M::Error> {
+///         map.serialize_entry("order-id", &self.id)
+///     }
+/// }
+/// ```
+pub trait ApnsCustomData {
+    /// The keys the data may add to the payload.
+    const KEYS: &'static [&'static str];
+
+    /// Writes the data's keys and values into the payload's top-level dictionary.
+    fn serialize_fields<M: SerializeMap>(&self, map: &mut M) -> Result<(), M::Error>;
+}
+
+/// A payload made of the `aps` dictionary and typed custom data, sent with
+/// [`ApnsClient::send_custom`](crate::ApnsClient::send_custom).
+///
+/// # Fields
+///
+/// * `aps` - The APS payload.
+/// * `data` - The custom data, serialized as keys next to `aps`.
+#[derive(Debug, Clone)]
+pub struct CustomPayload<D> {
+    pub aps: Aps,
+    pub data: D,
+}
+
+impl<D: ApnsCustomData> CustomPayload<D> {
+    /// Creates a payload sending `data` next to `aps`.
+    pub fn new(aps: Aps, data: D) -> Self {
+        CustomPayload { aps, data }
+    }
+}
+
+impl<D: ApnsCustomData> Serialize for CustomPayload<D> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut map = serializer.serialize_map(None)?;
+        map.serialize_entry("aps", &self.aps)?;
+        self.data.serialize_fields(&mut map)?;
+        map.end()
+    }
+}
+
+/// Serializes the `custom_fields` of an [`ApnsPayload`](crate::ApnsPayload), failing if one
+/// of them would overwrite `aps` or `custom_key`.
+pub(crate) fn serialize_custom_fields<S: Serializer>(
+    fields: &HashMap<String, Value>,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    if let Some(key) = RESERVED_FIELDS
+        .iter()
+        .find(|key| fields.contains_key(**key))
+    {
+        return Err(S::Error::custom(format!(
+            "custom field \"{}\" collides with a key of the payload",
+            key
+        )));
+    }
+    fields.serialize(serializer)
+}
@@ -0,0 +1,26 @@
+//! Conversions from the epoch integers APNs uses to date-time types.
+
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Returns the point in time `secs` seconds after the Unix epoch.
+pub(crate) fn from_unix_secs(secs: u64) -> SystemTime {
+    UNIX_EPOCH + Duration::from_secs(secs)
+}
+
+/// Returns the point in time `millis` milliseconds after the Unix epoch.
+#[cfg(feature = "signing")]
+pub(crate) fn from_unix_millis(millis: u64) -> SystemTime {
+    UNIX_EPOCH + Duration::from_millis(millis)
+}
+
+/// Converts `time` to a `chrono` date-time in UTC.
+#[cfg(feature = "chrono")]
+pub(crate) fn utc(time: SystemTime) -> chrono::DateTime<chrono::Utc> {
+    time.into()
+}
+
+/// Converts `time` to a `time` date-time at UTC.
+#[cfg(feature = "time")]
+pub(crate) fn offset(time: SystemTime) -> time::OffsetDateTime {
+    time.into()
+}
@@ -0,0 +1,88 @@
+//! Diagnostics for authentication problems, such as APNs rejecting the provider token.
+
+use crate::token::TOKEN_LIFETIME;
+use crate::{ApnsClient, ApnsError, Claims};
+use http::header::AUTHORIZATION;
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+/// How long after its `iat` APNs accepts a provider token.
+const APNS_TOKEN_VALIDITY: Duration = Duration::from_secs(60 * 60);
+
+/// The decoded provider token of a client, as reported by [`inspect_token`].
+///
+/// # Fields
+///
+/// * `kid` - The key ID in the token header, which must match the ID of the auth key.
+/// * `iss` - The issuer claim, which must be the team ID the key belongs to.
+/// * `iat` - The issued at claim, as a Unix timestamp.
+/// * `age` - How long ago the token was issued, by the client's clock.
+/// * `remaining` - How much longer APNs accepts the token, or zero if it has expired.
+/// * `renews_in` - How long until the client signs a new token, or zero if the next
+///   request does.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TokenInfo {
+    pub kid: Option<String>,
+    pub iss: String,
+    pub iat: u64,
+    pub age: Duration,
+    pub remaining: Duration,
+    pub renews_in: Duration,
+}
+
+impl TokenInfo {
+    /// Returns `true` if APNs rejects the token as expired.
+    pub fn is_expired(&self) -> bool {
+        self.remaining.is_zero()
+    }
+}
+
+/// Decodes the provider token the client currently sends, signing one first if none is
+/// cached, to diagnose `InvalidProviderToken` and `ExpiredProviderToken` errors.
+///
+/// The token is decoded without verifying its signature. Tokens of credentials registered
+/// per topic or app are not inspected, only the client's own.
+///
+/// # Returns
+///
+/// The decoded [`TokenInfo`], or an `ApnsError` if no token can be signed or the client
+/// authenticates with a certificate alone.
+///
+/// # Example
+///
+/// ```rust,no_run
+/// use apnrs::ApnsClient;
+///
+/// # async fn run(client: ApnsClient) -> Result<(), apnrs::ApnsError> {
+/// let token = apnrs::debug::inspect_token(&client).await?;
+/// println!(
+///     "kid {:?}, iss {}, issued {:?} ago, valid for {:?}",
+///     token.kid, token.iss, token.age, token.remaining
+/// );
+/// # Ok(())
+/// # }
+/// ```
+pub async fn inspect_token(client: &ApnsClient) -> Result<TokenInfo, ApnsError> {
+    let tokens = client.token_cache().ok_or_else(|| {
+        ApnsError::Config("the client has no auth key to sign provider tokens with".to_string())
+    })?;
+    let headers = tokens.headers().await?;
+    let token = headers
+        .get(AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("bearer "))
+        .ok_or_else(|| ApnsError::Config("the client sends no provider token".to_string()))?;
+
+    let decoded = jwt::dangerous_insecure_decode::<Claims>(token)?;
+    let age = Duration::from_secs(tokens.unix_time().saturating_sub(decoded.claims.iat));
+    Ok(TokenInfo {
+        kid: decoded.header.kid,
+        iss: decoded.claims.iss,
+        iat: decoded.claims.iat,
+        age,
+        remaining: APNS_TOKEN_VALIDITY.saturating_sub(age),
+        renews_in: tokens
+            .age()
+            .map_or(Duration::ZERO, |age| TOKEN_LIFETIME.saturating_sub(age)),
+    })
+}
@@ -0,0 +1,353 @@
+//! A queryable SQLite record of every request sent to APNs, enabled with the `delivery-log`
+//! feature.
+
+use crate::privacy::TokenHasher;
+use crate::{ApnsClient, SendEvent};
+use rusqlite::{params, Connection, Row};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::time::{SystemTime, UNIX_EPOCH};
+use tokio::sync::broadcast::error::{RecvError, TryRecvError};
+use tokio::sync::broadcast::Receiver;
+use tokio::task::JoinHandle;
+
+/// How many events are written together at most, in one transaction.
+const MAX_BATCH_SIZE: usize = 256;
+
+const SCHEMA: &str = "
+    CREATE TABLE IF NOT EXISTS deliveries (
+        id INTEGER PRIMARY KEY,
+        timestamp_ms INTEGER NOT NULL,
+        topic TEXT NOT NULL,
+        device_token TEXT NOT NULL,
+        status INTEGER,
+        reason TEXT,
+        apns_id TEXT,
+        latency_ms INTEGER NOT NULL,
+        trace_id TEXT
+    );
+    CREATE INDEX IF NOT EXISTS deliveries_by_token ON deliveries (device_token, timestamp_ms);
+    CREATE INDEX IF NOT EXISTS deliveries_by_apns_id ON deliveries (apns_id);
+    CREATE INDEX IF NOT EXISTS deliveries_by_time ON deliveries (timestamp_ms);
+";
+
+/// Created after the schema, once logs written before trace IDs were recorded have the
+/// column.
+const TRACE_ID_INDEX: &str =
+    "CREATE INDEX IF NOT EXISTS deliveries_by_trace_id ON deliveries (trace_id);";
+
+const COLUMNS: &str =
+    "timestamp_ms, topic, device_token, status, reason, apns_id, latency_ms, trace_id";
+
+/// Records every request a client makes to APNs in a SQLite database, and answers questions
+/// such as "what did we send to this user yesterday" from it.
+///
+/// Each request is stored as a [`SendEvent`]: when it completed, its topic, the device
+/// token, APNs' status and reason or the transport error, the `apns-id`, the latency, and
+/// the ID of the distributed trace it belongs to, if it was sent with a
+/// [`TraceContext`](crate::TraceContext).
+/// Build the client with a [`TokenHasher`] so only keyed hashes of device tokens are
+/// stored, and give the log the same hasher with
+/// [`token_hasher`](DeliveryLog::token_hasher) so queries by device token find them.
+///
+/// Events are written in batches on tokio's blocking pool, never on the tasks sending
+/// requests. If writing falls more than 1024 events behind, the oldest are skipped. Old
+/// records are kept until [`prune`](DeliveryLog::prune) removes them.
+///
+/// # Example
+///
+/// ```rust,no_run
+/// use apnrs::{ApnsClient, DeliveryLog, TokenHasher};
+/// use std::time::{Duration, SystemTime};
+///
+/// # async fn run() -> Result<(), Box<dyn std::error::Error>> {
+/// let hasher = TokenHasher::new(b"audit-secret")?;
+/// let client = ApnsClient::builder()
+///     .auth_key_path("path/to/key")
+///     .team_id("TEAM_ID")
+///     .key_id("KEY_ID")
+///     .token_hasher(hasher.clone())
+///     .build()?;
+///
+/// let log = DeliveryLog::open("deliveries.sqlite")?.token_hasher(hasher);
+/// log.spawn(&client);
+///
+/// // Later: everything sent to a user's devices in the last day
+/// let day = Duration::from_secs(24 * 60 * 60);
+/// let now = SystemTime::now();
+/// for event in log.sent_to(["DEVICE_TOKEN", "OTHER_DEVICE_TOKEN"], now - day, now)? {
+///     println!("{} {} {:?} {:?}", event.timestamp_ms, event.topic, event.status, event.reason);
+/// }
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone)]
+pub struct DeliveryLog {
+    connection: Arc<Mutex<Connection>>,
+    hasher: Option<TokenHasher>,
+}
+
+impl DeliveryLog {
+    /// Opens the log stored at `path`, creating it if it does not exist.
+    ///
+    /// # Returns
+    ///
+    /// The log, or an error if the database cannot be opened or its table created.
+    pub fn open(path: impl AsRef<Path>) -> rusqlite::Result<Self> {
+        let connection = Connection::open(path)?;
+        // The log is appended to constantly, and may be queried while it is
+        connection.pragma_update(None, "journal_mode", "WAL")?;
+        connection.pragma_update(None, "synchronous", "NORMAL")?;
+        Self::with_connection(connection)
+    }
+
+    /// Opens a log kept in memory, which is lost when the last clone is dropped.
+    ///
+    /// # Returns
+    ///
+    /// The log, or an error if SQLite cannot create the table.
+    pub fn open_in_memory() -> rusqlite::Result<Self> {
+        Self::with_connection(Connection::open_in_memory()?)
+    }
+
+    fn with_connection(connection: Connection) -> rusqlite::Result<Self> {
+        connection.execute_batch(SCHEMA)?;
+        let has_trace_id = connection
+            .prepare("SELECT 1 FROM pragma_table_info('deliveries') WHERE name = 'trace_id'")?
+            .exists([])?;
+        if !has_trace_id {
+            connection.execute_batch("ALTER TABLE deliveries ADD COLUMN trace_id TEXT")?;
+        }
+        connection.execute_batch(TRACE_ID_INDEX)?;
+        Ok(DeliveryLog {
+            connection: Arc::new(Mutex::new(connection)),
+            hasher: None,
+        })
+    }
+
+    /// Hashes device tokens passed to [`sent_to`](DeliveryLog::sent_to) with `hasher`, which
+    /// must be the one the logged client was built with.
+    pub fn token_hasher(mut self, hasher: TokenHasher) -> Self {
+        self.hasher = Some(hasher);
+        self
+    }
+
+    /// Starts recording the requests of `client`.
+    ///
+    /// The task runs until the client and all its clones are dropped, writing what is left
+    /// first; abort the returned handle to stop it earlier.
+    ///
+    /// # Panics
+    ///
+    /// Panics if called outside of a tokio runtime.
+    pub fn spawn(&self, client: &ApnsClient) -> JoinHandle<()> {
+        tokio::spawn(self.clone().run(client.subscribe_events()))
+    }
+
+    /// Writes events from `events` as they arrive, together with any already waiting.
+    async fn run(self, mut events: Receiver<SendEvent>) {
+        loop {
+            let first = match events.recv().await {
+                Ok(event) => event,
+                Err(RecvError::Lagged(_skipped)) => {
+                    #[cfg(feature = "tracing")]
+                    tracing::warn!(
+                        skipped = _skipped,
+                        "delivery log fell behind; events skipped"
+                    );
+                    #[cfg(feature = "logging")]
+                    log::warn!("delivery log fell behind; {} events skipped", _skipped);
+                    continue;
+                }
+                Err(RecvError::Closed) => break,
+            };
+            let mut batch = vec![first];
+            while batch.len() < MAX_BATCH_SIZE {
+                match events.try_recv() {
+                    Ok(event) => batch.push(event),
+                    Err(TryRecvError::Lagged(_)) => continue,
+                    Err(_) => break,
+                }
+            }
+            let log = self.clone();
+            let written = tokio::task::spawn_blocking(move || log.record(&batch)).await;
+            if let Ok(Err(_error)) = written {
+                #[cfg(feature = "tracing")]
+                tracing::warn!(error = %_error, "delivery log could not write events");
+                #[cfg(feature = "logging")]
+                log::warn!("delivery log could not write events: {}", _error);
+            }
+        }
+    }
+
+    /// Writes `events` to the log in one transaction.
+    ///
+    /// Events reach the log on their own once it is [spawned](DeliveryLog::spawn); this
+    /// adds events from elsewhere, such as another process's event stream.
+    ///
+    /// # Returns
+    ///
+    /// `Ok(())` once every event is stored, or the error SQLite failed with, in which case
+    /// none are.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use apnrs::{DeliveryLog, SendEvent};
+    /// use std::time::{Duration, UNIX_EPOCH};
+    ///
+    /// let log = DeliveryLog::open_in_memory()?;
+    /// log.record(&[SendEvent {
+    ///     timestamp_ms: 1_700_000_000_000,
+    ///     topic: "com.example.app".to_string(),
+    ///     device_token: "DEVICE_TOKEN".to_string(),
+    ///     status: Some(410),
+    ///     reason: Some("Unregistered".to_string()),
+    ///     apns_id: Some("EC1BF194-B3B2-424A-89A9-5A918A6E6B5D".to_string()),
+    ///     latency_ms: 42,
+    ///     trace_id: Some("4bf92f3577b34da6a3ce929d0e0e4736".to_string()),
+    /// }])?;
+    ///
+    /// let since = UNIX_EPOCH + Duration::from_secs(1_699_990_000);
+    /// let until = UNIX_EPOCH + Duration::from_secs(1_700_010_000);
+    /// assert_eq!(log.sent_to(["DEVICE_TOKEN"], since, until)?[0].status, Some(410));
+    /// assert!(log.sent_to(["OTHER_TOKEN"], since, until)?.is_empty());
+    /// assert_eq!(log.by_apns_id("EC1BF194-B3B2-424A-89A9-5A918A6E6B5D")?.len(), 1);
+    /// assert_eq!(log.by_trace_id("4bf92f3577b34da6a3ce929d0e0e4736")?.len(), 1);
+    /// assert_eq!(log.prune(until)?, 1);
+    /// # Ok::<(), apnrs::rusqlite::Error>(())
+    /// ```
+    pub fn record(&self, events: &[SendEvent]) -> rusqlite::Result<()> {
+        let mut connection = self.connection.lock().unwrap();
+        let transaction = connection.transaction()?;
+        {
+            let mut insert = transaction.prepare_cached(&format!(
+                "INSERT INTO deliveries ({}) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+                COLUMNS
+            ))?;
+            for event in events {
+                insert.execute(params![
+                    event.timestamp_ms,
+                    event.topic,
+                    event.device_token,
+                    event.status,
+                    event.reason,
+                    event.apns_id,
+                    event.latency_ms,
+                    event.trace_id,
+                ])?;
+            }
+        }
+        transaction.commit()
+    }
+
+    /// Returns every request to any of `device_tokens` that completed from `since` up to,
+    /// but not including, `until`, oldest first.
+    ///
+    /// Pass all of a user's device tokens to see everything sent to them. Tokens are hashed
+    /// with the log's [`token_hasher`](DeliveryLog::token_hasher) first, if it has one.
+    ///
+    /// # Returns
+    ///
+    /// The matching events, or the error SQLite failed with.
+    pub fn sent_to<I>(
+        &self,
+        device_tokens: I,
+        since: SystemTime,
+        until: SystemTime,
+    ) -> rusqlite::Result<Vec<SendEvent>>
+    where
+        I: IntoIterator,
+        I::Item: AsRef<str>,
+    {
+        let connection = self.connection.lock().unwrap();
+        let mut query = connection.prepare_cached(&format!(
+            "SELECT {} FROM deliveries
+             WHERE device_token = ?1 AND timestamp_ms >= ?2 AND timestamp_ms < ?3",
+            COLUMNS
+        ))?;
+        let mut events = Vec::new();
+        for device_token in device_tokens {
+            let device_token = match &self.hasher {
+                Some(hasher) => hasher.hash(device_token.as_ref()),
+                None => device_token.as_ref().to_string(),
+            };
+            let rows = query.query_map(
+                params![device_token, epoch_ms(since), epoch_ms(until)],
+                event_from_row,
+            )?;
+            for event in rows {
+                events.push(event?);
+            }
+        }
+        events.sort_by_key(|event| event.timestamp_ms);
+        Ok(events)
+    }
+
+    /// Returns every request recorded with `apns_id`, oldest first, such as the attempts
+    /// at one notification a sender retried.
+    ///
+    /// # Returns
+    ///
+    /// The matching events, or the error SQLite failed with.
+    pub fn by_apns_id(&self, apns_id: &str) -> rusqlite::Result<Vec<SendEvent>> {
+        let connection = self.connection.lock().unwrap();
+        let mut query = connection.prepare_cached(&format!(
+            "SELECT {} FROM deliveries WHERE apns_id = ?1 ORDER BY timestamp_ms, id",
+            COLUMNS
+        ))?;
+        let rows = query.query_map(params![apns_id], event_from_row)?;
+        rows.collect()
+    }
+
+    /// Returns every request sent as part of the distributed trace `trace_id`, oldest
+    /// first, such as the pushes one API request caused.
+    ///
+    /// # Returns
+    ///
+    /// The matching events, or the error SQLite failed with.
+    pub fn by_trace_id(&self, trace_id: &str) -> rusqlite::Result<Vec<SendEvent>> {
+        let connection = self.connection.lock().unwrap();
+        let mut query = connection.prepare_cached(&format!(
+            "SELECT {} FROM deliveries WHERE trace_id = ?1 ORDER BY timestamp_ms, id",
+            COLUMNS
+        ))?;
+        let rows = query.query_map(params![trace_id], event_from_row)?;
+        rows.collect()
+    }
+
+    /// Deletes the records of requests that completed before `before`, to enforce a
+    /// retention period.
+    ///
+    /// # Returns
+    ///
+    /// How many records were deleted, or the error SQLite failed with.
+    pub fn prune(&self, before: SystemTime) -> rusqlite::Result<usize> {
+        let connection = self.connection.lock().unwrap();
+        connection.execute(
+            "DELETE FROM deliveries WHERE timestamp_ms < ?1",
+            params![epoch_ms(before)],
+        )
+    }
+}
+
+/// Reads a row selected with [`COLUMNS`].
+fn event_from_row(row: &Row<'_>) -> rusqlite::Result<SendEvent> {
+    Ok(SendEvent {
+        timestamp_ms: row.get(0)?,
+        topic: row.get(1)?,
+        device_token: row.get(2)?,
+        status: row.get(3)?,
+        reason: row.get(4)?,
+        apns_id: row.get(5)?,
+        latency_ms: row.get(6)?,
+        trace_id: row.get(7)?,
+    })
+}
+
+/// Returns `time` in milliseconds since the Unix epoch, as events record it.
+fn epoch_ms(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or(0)
+}
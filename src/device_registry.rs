@@ -0,0 +1,100 @@
+//! Looking up the devices a user has registered for notifications.
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+/// A device a user has registered, with the topic its app is sent on.
+///
+/// # Fields
+///
+/// * `device_token` - The device token the app registered.
+/// * `topic` - The topic (usually the app's bundle ID) notifications to the device are sent on.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct UserDevice {
+    pub device_token: String,
+    pub topic: String,
+}
+
+impl UserDevice {
+    /// Creates a device with its token and topic.
+    pub fn new(device_token: impl Into<String>, topic: impl Into<String>) -> Self {
+        UserDevice {
+            device_token: device_token.into(),
+            topic: topic.into(),
+        }
+    }
+}
+
+/// Maps users to the devices they have registered, so a notification can be sent to a user
+/// rather than a device.
+///
+/// [`ApnsClient::send_to_user`](crate::ApnsClient::send_to_user) sends to every device the
+/// registry lists for a user and removes the ones APNs answers `410 Unregistered` for.
+/// Implement this trait to read devices from your own database;
+/// [`MemoryDeviceRegistry`] keeps them in memory.
+///
+/// # Example
+///
+/// ```rust
+/// use apnrs::{DeviceRegistry, MemoryDeviceRegistry, UserDevice};
+///
+/// let registry = MemoryDeviceRegistry::new();
+/// registry.register("user-42", UserDevice::new("PHONE_TOKEN", "com.example.app"));
+/// registry.register("user-42", UserDevice::new("WATCH_TOKEN", "com.example.app.watchkitapp"));
+///
+/// registry.remove("user-42", "WATCH_TOKEN");
+/// assert_eq!(
+///     registry.devices("user-42"),
+///     vec![UserDevice::new("PHONE_TOKEN", "com.example.app")]
+/// );
+/// ```
+pub trait DeviceRegistry: Send + Sync {
+    /// Returns every device registered for `user_id`.
+    fn devices(&self, user_id: &str) -> Vec<UserDevice>;
+
+    /// Removes `device_token` from the devices of `user_id`.
+    fn remove(&self, user_id: &str, device_token: &str);
+}
+
+/// A [`DeviceRegistry`] that keeps devices in memory for the lifetime of the process.
+#[derive(Debug, Default)]
+pub struct MemoryDeviceRegistry {
+    users: Mutex<HashMap<String, Vec<UserDevice>>>,
+}
+
+impl MemoryDeviceRegistry {
+    /// Creates an empty registry.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Registers `device` for `user_id`, replacing an earlier registration of the same
+    /// device token.
+    pub fn register(&self, user_id: impl Into<String>, device: UserDevice) {
+        let mut users = self.users.lock().unwrap();
+        let devices = users.entry(user_id.into()).or_default();
+        devices.retain(|existing| existing.device_token != device.device_token);
+        devices.push(device);
+    }
+}
+
+impl DeviceRegistry for MemoryDeviceRegistry {
+    fn devices(&self, user_id: &str) -> Vec<UserDevice> {
+        self.users
+            .lock()
+            .unwrap()
+            .get(user_id)
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    fn remove(&self, user_id: &str, device_token: &str) {
+        let mut users = self.users.lock().unwrap();
+        if let Some(devices) = users.get_mut(user_id) {
+            devices.retain(|device| device.device_token != device_token);
+            if devices.is_empty() {
+                users.remove(user_id);
+            }
+        }
+    }
+}
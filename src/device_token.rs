@@ -0,0 +1,204 @@
+//! Validated APNs device tokens.
+
+use crate::privacy::mask_token;
+use serde::{Deserialize, Serialize};
+use std::error::Error;
+use std::fmt;
+use std::ops::Deref;
+use std::str::FromStr;
+
+/// The shortest device token APNs issues, in bytes.
+pub const MIN_DEVICE_TOKEN_LEN: usize = 32;
+
+/// The longest device token APNs may issue, in bytes.
+pub const MAX_DEVICE_TOKEN_LEN: usize = 100;
+
+/// Describes why a string or byte slice is not a device token.
+///
+/// # Variants
+///
+/// * `Empty` - The token is empty.
+/// * `InvalidCharacter` - The token contains a character other than a hex digit.
+/// * `OddLength` - The token has an odd number of hex digits, so it is not whole bytes.
+/// * `InvalidLength` - The token is shorter than [`MIN_DEVICE_TOKEN_LEN`] or longer than
+///   [`MAX_DEVICE_TOKEN_LEN`] bytes.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DeviceTokenError {
+    Empty,
+    InvalidCharacter { character: char, index: usize },
+    OddLength { len: usize },
+    InvalidLength { bytes: usize },
+}
+
+impl fmt::Display for DeviceTokenError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DeviceTokenError::Empty => write!(f, "device token is empty"),
+            DeviceTokenError::InvalidCharacter { character, index } => write!(
+                f,
+                "device token contains non-hex character {:?} at position {}",
+                character, index
+            ),
+            DeviceTokenError::OddLength { len } => {
+                write!(f, "device token has an odd number of hex digits ({})", len)
+            }
+            DeviceTokenError::InvalidLength { bytes } => write!(
+                f,
+                "device token is {} bytes long, expected {} to {}",
+                bytes, MIN_DEVICE_TOKEN_LEN, MAX_DEVICE_TOKEN_LEN
+            ),
+        }
+    }
+}
+
+impl Error for DeviceTokenError {}
+
+/// A device token checked to be hex-encoded and of a length APNs issues.
+///
+/// Tokens are stored as lowercase hex. `DeviceToken` dereferences to `str`, so it can be
+/// passed to every send API in place of a string. Its `Debug` output shows only the first six
+/// and last four characters, so tokens do not end up in logs.
+///
+/// # Example
+///
+/// ```rust
+/// use apnrs::{DeviceToken, DeviceTokenError};
+///
+/// let token: DeviceToken = "3F2A9C1E7B4D6A8F0E2C5B7D9A1F3E6C8B0D2A4F6E8C1B3D5A7F9E2C4B6D8A0F"
+///     .parse()
+///     .unwrap();
+/// assert!(token.starts_with("3f2a"));
+/// assert_eq!(format!("{:?}", token), r#"DeviceToken("3f2a9c…8a0f")"#);
+///
+/// let token = DeviceToken::try_from(&[0xab; 32][..]).unwrap();
+/// assert_eq!(token.len(), 64);
+///
+/// assert_eq!(
+///     "3f2a-9c1e".parse::<DeviceToken>(),
+///     Err(DeviceTokenError::InvalidCharacter { character: '-', index: 4 })
+/// );
+/// ```
+#[derive(Clone, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct DeviceToken(String);
+
+impl fmt::Debug for DeviceToken {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("DeviceToken")
+            .field(&mask_token(&self.0))
+            .finish()
+    }
+}
+
+impl DeviceToken {
+    /// Returns the token as lowercase hex.
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    /// Returns the raw token, as delivered to
+    /// `application(_:didRegisterForRemoteNotificationsWithDeviceToken:)`, for storing it in
+    /// binary form.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use apnrs::DeviceToken;
+    ///
+    /// let raw = vec![0x3f; 32];
+    /// let token = DeviceToken::try_from(raw.clone()).unwrap();
+    /// assert_eq!(token.to_bytes(), raw);
+    /// ```
+    pub fn to_bytes(&self) -> Vec<u8> {
+        self.0
+            .as_bytes()
+            .chunks(2)
+            .map(|pair| {
+                let pair = std::str::from_utf8(pair).expect("a device token is ASCII hex");
+                u8::from_str_radix(pair, 16).expect("a device token is ASCII hex")
+            })
+            .collect()
+    }
+}
+
+impl FromStr for DeviceToken {
+    type Err = DeviceTokenError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.is_empty() {
+            return Err(DeviceTokenError::Empty);
+        }
+        if let Some((index, character)) = s.char_indices().find(|(_, c)| !c.is_ascii_hexdigit()) {
+            return Err(DeviceTokenError::InvalidCharacter { character, index });
+        }
+        if !s.len().is_multiple_of(2) {
+            return Err(DeviceTokenError::OddLength { len: s.len() });
+        }
+        let bytes = s.len() / 2;
+        if !(MIN_DEVICE_TOKEN_LEN..=MAX_DEVICE_TOKEN_LEN).contains(&bytes) {
+            return Err(DeviceTokenError::InvalidLength { bytes });
+        }
+        Ok(DeviceToken(s.to_ascii_lowercase()))
+    }
+}
+
+impl TryFrom<&[u8]> for DeviceToken {
+    type Error = DeviceTokenError;
+
+    /// Hex-encodes a raw token, as delivered to
+    /// `application(_:didRegisterForRemoteNotificationsWithDeviceToken:)`.
+    fn try_from(bytes: &[u8]) -> Result<Self, Self::Error> {
+        if bytes.is_empty() {
+            return Err(DeviceTokenError::Empty);
+        }
+        if !(MIN_DEVICE_TOKEN_LEN..=MAX_DEVICE_TOKEN_LEN).contains(&bytes.len()) {
+            return Err(DeviceTokenError::InvalidLength { bytes: bytes.len() });
+        }
+        Ok(DeviceToken(
+            bytes.iter().map(|byte| format!("{:02x}", byte)).collect(),
+        ))
+    }
+}
+
+impl TryFrom<Vec<u8>> for DeviceToken {
+    type Error = DeviceTokenError;
+
+    /// Hex-encodes a raw token, like the conversion from `&[u8]`.
+    fn try_from(bytes: Vec<u8>) -> Result<Self, Self::Error> {
+        DeviceToken::try_from(bytes.as_slice())
+    }
+}
+
+impl TryFrom<String> for DeviceToken {
+    type Error = DeviceTokenError;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        s.parse()
+    }
+}
+
+impl From<DeviceToken> for String {
+    fn from(token: DeviceToken) -> Self {
+        token.0
+    }
+}
+
+impl Deref for DeviceToken {
+    type Target = str;
+
+    fn deref(&self) -> &str {
+        &self.0
+    }
+}
+
+impl AsRef<str> for DeviceToken {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+impl fmt::Display for DeviceToken {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
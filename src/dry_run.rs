@@ -0,0 +1,97 @@
+//! Requests prepared in dry-run mode or for inspection instead of being sent.
+
+use crate::privacy::{mask_url, RedactedHeaders};
+use http::header::{HeaderValue, AUTHORIZATION};
+use http::HeaderMap;
+use std::fmt;
+
+/// The request a send would have made to APNs, returned in
+/// [`SendOutcome::dry_run`](crate::SendOutcome) when the client is in
+/// [dry-run mode](crate::ApnsClientBuilder::dry_run) or the send asked for a
+/// [dry run](crate::SendOptions::dry_run), and by
+/// [`ApnsClient::prepare_request`](crate::ApnsClient::prepare_request) for sending later with
+/// [`ApnsClient::execute`](crate::ApnsClient::execute).
+///
+/// The payload was serialized, validated, and trimmed, and the provider token signed, exactly
+/// as for a real send. Its `Display` output renders the request as HTTP text with the
+/// `authorization` header redacted, for logs and CI output.
+///
+/// # Fields
+///
+/// * `url` - The URL the request would have been posted to.
+/// * `headers` - The request headers, including the signed `authorization` header.
+/// * `body` - The JSON payload.
+///
+/// # Example
+///
+/// ```rust,no_run
+/// use apnrs::{ApnsClient, ApnsPayload};
+///
+/// # async fn run(payload: ApnsPayload) -> Result<(), apnrs::ApnsError> {
+/// let client = ApnsClient::builder()
+///     .auth_key_path("path/to/auth/key")
+///     .team_id("TEAM_ID")
+///     .key_id("KEY_ID")
+///     .dry_run(true)
+///     .build()?;
+///
+/// let outcome = client.send("DEVICE_TOKEN", "com.example.app", &payload).await?;
+/// let request = outcome.dry_run.expect("a dry run");
+/// assert_eq!(request.headers["apns-topic"], "com.example.app");
+/// println!("{}", request);
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone)]
+pub struct PreparedRequest {
+    pub url: String,
+    pub headers: HeaderMap,
+    pub body: Vec<u8>,
+}
+
+impl PreparedRequest {
+    /// Returns the device token the request is addressed to, the last segment of its URL.
+    pub fn device_token(&self) -> &str {
+        self.url.rsplit_once('/').map_or("", |(_, token)| token)
+    }
+
+    /// Returns the request's `apns-topic` header, if it has one.
+    pub fn topic(&self) -> Option<&str> {
+        self.headers.get("apns-topic")?.to_str().ok()
+    }
+
+    /// Returns the headers with the provider token in `authorization` replaced by
+    /// `bearer <redacted>`, for audit logs that must not hold a usable token.
+    pub fn redacted_headers(&self) -> HeaderMap {
+        let mut headers = self.headers.clone();
+        if headers.contains_key(AUTHORIZATION) {
+            headers.insert(AUTHORIZATION, HeaderValue::from_static("bearer <redacted>"));
+        }
+        headers
+    }
+}
+
+impl fmt::Debug for PreparedRequest {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PreparedRequest")
+            .field("url", &mask_url(&self.url))
+            .field("headers", &RedactedHeaders(&self.headers))
+            .field("body", &String::from_utf8_lossy(&self.body))
+            .finish()
+    }
+}
+
+impl fmt::Display for PreparedRequest {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "POST {}", self.url)?;
+        for (name, value) in &self.headers {
+            match value.to_str() {
+                _ if name == AUTHORIZATION => writeln!(f, "{}: bearer <redacted>", name)?,
+                Ok(value) => writeln!(f, "{}: {}", name, value)?,
+                Err(_) => writeln!(f, "{}: <binary>", name)?,
+            }
+        }
+        writeln!(f)?;
+        write!(f, "{}", String::from_utf8_lossy(&self.body))
+    }
+}
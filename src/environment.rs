@@ -0,0 +1,70 @@
+//! The APNs environments.
+
+use crate::error::ApnsError;
+use serde::{Deserialize, Serialize};
+use std::str::FromStr;
+
+/// The APNs environment notifications are delivered to.
+///
+/// # Variants
+///
+/// * `Production` - `api.push.apple.com`, for App Store and TestFlight builds.
+/// * `Sandbox` - `api.sandbox.push.apple.com`, for development builds.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Environment {
+    Production,
+    Sandbox,
+}
+
+impl FromStr for Environment {
+    type Err = ApnsError;
+
+    /// Parses `production` or `sandbox` (case-insensitive).
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "production" => Ok(Environment::Production),
+            "sandbox" => Ok(Environment::Sandbox),
+            _ => Err(ApnsError::Config(format!(
+                "unknown environment {:?}, expected \"production\" or \"sandbox\"",
+                s
+            ))),
+        }
+    }
+}
+
+impl Environment {
+    /// Returns the base URL of the APNs server for this environment.
+    pub fn base_url(&self) -> &'static str {
+        match self {
+            Environment::Production => "https://api.push.apple.com",
+            Environment::Sandbox => "https://api.sandbox.push.apple.com",
+        }
+    }
+
+    /// Returns the other environment, where tokens of the other kind of build are valid.
+    pub fn other(&self) -> Environment {
+        match self {
+            Environment::Production => Environment::Sandbox,
+            Environment::Sandbox => Environment::Production,
+        }
+    }
+
+    /// Returns the URL notifications for `device_token` are posted to.
+    pub fn device_url(&self, device_token: &str) -> String {
+        format!("{}/3/device/{}", self.base_url(), device_token)
+    }
+
+    /// Returns the base URL of the broadcast channel management API for this environment.
+    pub fn channel_management_url(&self) -> &'static str {
+        match self {
+            Environment::Production => "https://api-manage-broadcast.push.apple.com:2196",
+            Environment::Sandbox => "https://api-manage-broadcast.sandbox.push.apple.com:2195",
+        }
+    }
+
+    /// Returns the URL broadcasts to the channels of `bundle_id` are posted to.
+    pub fn broadcast_url(&self, bundle_id: &str) -> String {
+        format!("{}/4/broadcasts/apps/{}", self.base_url(), bundle_id)
+    }
+}
@@ -0,0 +1,783 @@
+//! Error types returned by the APNs client.
+
+#[cfg(feature = "signing")]
+use crate::SignerError;
+use crate::{
+    DefaultClassifier, DeviceTokenError, FailureClass, FailureClassifier, NotificationError,
+    PayloadError, PushType, SchemaViolation, TemplateError, TopicError,
+};
+use http::StatusCode;
+use serde::{Deserialize, Serialize};
+use std::error::Error;
+use std::fmt;
+use std::time::Duration;
+
+/// Represents an error that occurred while preparing or sending a push notification.
+///
+/// # Variants
+///
+/// * `Io` - The auth key could not be read from disk.
+/// * `Jwt` - The auth key could not be parsed or the provider token could not be signed.
+/// * `Signer` - A [`TokenSigner`](crate::TokenSigner) failed to sign the provider token.
+/// * `Serialization` - The payload could not be serialized to JSON.
+/// * `InvalidHeader` - A value could not be used as an HTTP header (for example a malformed topic).
+/// * `Http` - The request could not be delivered to the APNs server.
+/// * `Config` - The client was built with missing or inconsistent settings.
+/// * `InvalidTopic` - The topic is not a valid `apns-topic` value.
+/// * `InvalidNotification` - The notification breaks the payload rules of its push type.
+/// * `InvalidDeviceToken` - A string or byte slice is not a well-formed device token.
+/// * `InvalidPayload` - A JSON value could not be converted into an
+///   [`ApnsPayload`](crate::ApnsPayload).
+/// * `InvalidTemplate` - A [`Template`](crate::Template) could not be parsed or rendered.
+/// * `InvalidCustomData` - The payload's custom data does not match a `CustomDataSchema`,
+///   with every violation.
+/// * `QueueClosed` - The sender's workers have stopped, so the notification cannot be delivered.
+/// * `InvalidToken` - The device token is known to be invalid, so the notification was not sent.
+/// * `CertificateRequired` - The push type can only be sent with certificate-based
+///   authentication, but the client has no certificate.
+/// * `PinMismatch` - The APNs server presented a public key matching none of the client's
+///   [pins](crate::CertificatePin), so the connection may be intercepted; holds the base64
+///   SHA-256 of the presented key, if there was one.
+/// * `DeadlineExceeded` - The send did not complete within the client's
+///   [send deadline](crate::ApnsClientBuilder::send_deadline), or within the time left
+///   before the notification's own [deadline](crate::PushRequest::deadline).
+/// * `ClientShutDown` - [`ApnsClient::shutdown`](crate::ApnsClient::shutdown) was called, so
+///   the notification was not sent.
+/// * `Cancelled` - The scheduled notification was [cancelled](crate::ApnsSender::cancel)
+///   before it was due, or its sender's
+///   [cancellation token](crate::ApnsSenderBuilder::cancellation_token) was cancelled
+///   before it was sent.
+/// * `Superseded` - A newer notification to the same device token with the same collapse ID
+///   replaced it while the sender was [coalescing](crate::ApnsSenderBuilder::coalesce_window).
+/// * `Rejected` - APNs answered a channel management request with a non-success status.
+/// * `Blocked` - A [`SendHook`](crate::SendHook) refused to let the request be sent, with its
+///   reason.
+/// * `DuplicateToken` - The device token already appeared earlier in a batch that
+///   [deduplicates](crate::FanOut::deduplicate) tokens, so the notification was not sent
+///   again.
+/// * `QuotaExceeded` - The app, named by its bundle ID, used up the daily
+///   [quota](crate::Quota) of `limit` notifications its sender allows, so the notification
+///   was not queued.
+/// * `Expired` - The notification's `apns-expiration` passed while its sender was
+///   [spooling](crate::ApnsSenderBuilder::spool_outages) it during an APNs outage, so it
+///   was not sent.
+#[derive(Debug)]
+pub enum ApnsError {
+    Io(std::io::Error),
+    #[cfg(feature = "signing")]
+    Jwt(jsonwebtoken::errors::Error),
+    #[cfg(feature = "signing")]
+    Signer(SignerError),
+    Serialization(serde_json::Error),
+    InvalidHeader(http::header::InvalidHeaderValue),
+    Http(TransportError),
+    Config(String),
+    InvalidTopic(TopicError),
+    InvalidNotification(NotificationError),
+    InvalidDeviceToken(DeviceTokenError),
+    InvalidPayload(PayloadError),
+    InvalidTemplate(TemplateError),
+    InvalidCustomData(Vec<SchemaViolation>),
+    QueueClosed,
+    InvalidToken,
+    CertificateRequired(PushType),
+    PinMismatch(Option<String>),
+    DeadlineExceeded(Duration),
+    ClientShutDown,
+    Cancelled,
+    Superseded,
+    Rejected {
+        status: StatusCode,
+        reason: Option<ErrorReason>,
+    },
+    Blocked(String),
+    DuplicateToken,
+    QuotaExceeded {
+        app: String,
+        limit: u64,
+    },
+    Expired,
+}
+
+impl ApnsError {
+    /// Returns a stable code for this error, such as `APNRS-INVALID-TOPIC`, or for
+    /// `Rejected` errors with a reason, the reason's [code](ErrorReason::code).
+    ///
+    /// Codes never change once assigned and are included in the `Display` output, so
+    /// alerting rules and runbooks can match on them even if messages are reworded.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use apnrs::{ApnsError, ErrorReason};
+    /// use http::StatusCode;
+    ///
+    /// let error = ApnsError::Rejected {
+    ///     status: StatusCode::GONE,
+    ///     reason: Some(ErrorReason::Unregistered),
+    /// };
+    /// assert_eq!(error.code(), "APNRS-410-UNREGISTERED");
+    /// assert_eq!(
+    ///     error.to_string(),
+    ///     "APNs rejected the request with 410 Gone: Unregistered [APNRS-410-UNREGISTERED]"
+    /// );
+    /// ```
+    pub fn code(&self) -> &'static str {
+        match self {
+            ApnsError::Io(_) => "APNRS-IO",
+            #[cfg(feature = "signing")]
+            ApnsError::Jwt(_) => "APNRS-JWT",
+            #[cfg(feature = "signing")]
+            ApnsError::Signer(_) => "APNRS-SIGNER",
+            ApnsError::Serialization(_) => "APNRS-SERIALIZATION",
+            ApnsError::InvalidHeader(_) => "APNRS-INVALID-HEADER",
+            ApnsError::Http(_) => "APNRS-TRANSPORT",
+            ApnsError::Config(_) => "APNRS-CONFIG",
+            ApnsError::InvalidTopic(_) => "APNRS-INVALID-TOPIC",
+            ApnsError::InvalidNotification(_) => "APNRS-INVALID-NOTIFICATION",
+            ApnsError::InvalidDeviceToken(_) => "APNRS-INVALID-DEVICE-TOKEN",
+            ApnsError::InvalidPayload(_) => "APNRS-INVALID-PAYLOAD",
+            ApnsError::InvalidTemplate(_) => "APNRS-INVALID-TEMPLATE",
+            ApnsError::InvalidCustomData(_) => "APNRS-INVALID-CUSTOM-DATA",
+            ApnsError::QueueClosed => "APNRS-QUEUE-CLOSED",
+            ApnsError::InvalidToken => "APNRS-INVALID-TOKEN",
+            ApnsError::CertificateRequired(_) => "APNRS-CERTIFICATE-REQUIRED",
+            ApnsError::PinMismatch(_) => "APNRS-PIN-MISMATCH",
+            ApnsError::DeadlineExceeded(_) => "APNRS-DEADLINE-EXCEEDED",
+            ApnsError::ClientShutDown => "APNRS-CLIENT-SHUT-DOWN",
+            ApnsError::Cancelled => "APNRS-CANCELLED",
+            ApnsError::Superseded => "APNRS-SUPERSEDED",
+            ApnsError::Rejected {
+                reason: Some(reason),
+                ..
+            } => reason.code(),
+            ApnsError::Rejected { reason: None, .. } => "APNRS-REJECTED",
+            ApnsError::Blocked(_) => "APNRS-BLOCKED",
+            ApnsError::DuplicateToken => "APNRS-DUPLICATE-TOKEN",
+            ApnsError::QuotaExceeded { .. } => "APNRS-QUOTA-EXCEEDED",
+            ApnsError::Expired => "APNRS-EXPIRED",
+        }
+    }
+
+    /// Returns `true` if the failure is transient and sending again may succeed, as the
+    /// [`DefaultClassifier`] decides: transport errors, exceeded deadlines, throttling, and
+    /// APNs server errors.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use apnrs::{ApnsError, ErrorReason};
+    /// use http::StatusCode;
+    ///
+    /// let error = ApnsError::Rejected {
+    ///     status: StatusCode::SERVICE_UNAVAILABLE,
+    ///     reason: Some(ErrorReason::ServiceUnavailable),
+    /// };
+    /// assert!(error.is_retriable());
+    /// assert!(!error.is_token_invalid());
+    /// ```
+    pub fn is_retriable(&self) -> bool {
+        DefaultClassifier.classify_error(self) == FailureClass::Retry
+    }
+
+    /// Returns `true` if the device token is no longer valid and should be removed: APNs
+    /// reported it unregistered, expired, malformed, or not for the topic, or the client's
+    /// [`TokenStore`](crate::TokenStore) already knew it to be invalid.
+    pub fn is_token_invalid(&self) -> bool {
+        DefaultClassifier.classify_error(self) == FailureClass::DeadToken
+    }
+
+    /// Returns `true` if the auth key, provider token, or certificate is at fault: it could
+    /// not be read or signed with, APNs rejected it, or the push type needs a certificate
+    /// the client lacks. Such errors need an operator, not a retry; see
+    /// [`ErrorReason::is_credential_problem`].
+    pub fn is_credential_problem(&self) -> bool {
+        match self {
+            ApnsError::Io(_) | ApnsError::CertificateRequired(_) => true,
+            #[cfg(feature = "signing")]
+            ApnsError::Jwt(_) | ApnsError::Signer(_) => true,
+            ApnsError::Rejected {
+                reason: Some(reason),
+                ..
+            } => reason.is_credential_problem(),
+            ApnsError::Rejected {
+                status,
+                reason: None,
+            } => *status == StatusCode::FORBIDDEN,
+            _ => false,
+        }
+    }
+
+    /// Returns `true` if APNs asked the provider to slow down, with `429 Too Many
+    /// Requests`; see [`ErrorReason::is_throttled`].
+    pub fn is_throttled(&self) -> bool {
+        match self {
+            ApnsError::Rejected {
+                reason: Some(reason),
+                ..
+            } => reason.is_throttled(),
+            ApnsError::Rejected {
+                status,
+                reason: None,
+            } => *status == StatusCode::TOO_MANY_REQUESTS,
+            _ => false,
+        }
+    }
+
+    /// Writes the message describing this error, without its code.
+    fn fmt_message(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ApnsError::Io(e) => write!(f, "unable to read auth key: {}", e),
+            #[cfg(feature = "signing")]
+            ApnsError::Jwt(e) => write!(f, "unable to sign provider token: {}", e),
+            #[cfg(feature = "signing")]
+            ApnsError::Signer(e) => write!(f, "token signer failed: {}", e),
+            ApnsError::Serialization(e) => write!(f, "unable to serialize payload: {}", e),
+            ApnsError::InvalidHeader(e) => write!(f, "invalid header value: {}", e),
+            ApnsError::Http(e) => write!(f, "request to APNs failed: {}", e),
+            ApnsError::Config(msg) => write!(f, "invalid client configuration: {}", msg),
+            ApnsError::InvalidTopic(e) => write!(f, "invalid topic: {}", e),
+            ApnsError::InvalidNotification(e) => write!(f, "invalid notification: {}", e),
+            ApnsError::InvalidDeviceToken(e) => write!(f, "invalid device token: {}", e),
+            ApnsError::InvalidPayload(e) => write!(f, "invalid payload: {}", e),
+            ApnsError::InvalidTemplate(e) => write!(f, "invalid template: {}", e),
+            ApnsError::InvalidCustomData(violations) => {
+                let violations: Vec<String> = violations.iter().map(|v| v.to_string()).collect();
+                write!(f, "invalid custom data: {}", violations.join("; "))
+            }
+            ApnsError::QueueClosed => write!(f, "the sender queue is closed"),
+            ApnsError::InvalidToken => write!(f, "the device token is known to be invalid"),
+            ApnsError::CertificateRequired(PushType::Mdm) => write!(
+                f,
+                "APNs only accepts mdm pushes authenticated with the MDM push certificate \
+                 (issued through the Apple Push Certificates Portal for your MDM vendor \
+                 certificate), not with a token-based .p8 key; sending would fail with 403. \
+                 Configure it with ApnsClientBuilder::certificate_pkcs12_path"
+            ),
+            ApnsError::CertificateRequired(push_type) => write!(
+                f,
+                "APNs only accepts {} pushes authenticated with a certificate, not with a \
+                 token-based .p8 key; configure one with \
+                 ApnsClientBuilder::certificate_pkcs12_path",
+                push_type
+            ),
+            ApnsError::PinMismatch(Some(pin)) => write!(
+                f,
+                "the APNs server presented public key sha256//{}, which matches no pinned key",
+                pin
+            ),
+            ApnsError::PinMismatch(None) => write!(
+                f,
+                "the APNs server presented no certificate to check against the pinned keys"
+            ),
+            ApnsError::DeadlineExceeded(deadline) => {
+                write!(f, "the send did not complete within {:?}", deadline)
+            }
+            ApnsError::ClientShutDown => write!(f, "the client is shutting down"),
+            ApnsError::Cancelled => write!(f, "the notification was cancelled"),
+            ApnsError::Superseded => {
+                write!(
+                    f,
+                    "a newer notification with the same collapse ID replaced it"
+                )
+            }
+            ApnsError::Rejected {
+                status,
+                reason: Some(reason),
+            } => write!(
+                f,
+                "APNs rejected the request with {}: {}",
+                status,
+                reason.as_str()
+            ),
+            ApnsError::Rejected {
+                status,
+                reason: None,
+            } => write!(f, "APNs rejected the request with {}", status),
+            ApnsError::Blocked(reason) => write!(f, "a send hook blocked the request: {}", reason),
+            ApnsError::DuplicateToken => {
+                write!(f, "the device token was already sent to in this batch")
+            }
+            ApnsError::QuotaExceeded { app, limit } => write!(
+                f,
+                "{} used up its daily quota of {} notifications",
+                app, limit
+            ),
+            ApnsError::Expired => {
+                write!(f, "the notification expired before APNs could be reached")
+            }
+        }
+    }
+}
+
+impl fmt::Display for ApnsError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.fmt_message(f)?;
+        write!(f, " [{}]", self.code())
+    }
+}
+
+impl Error for ApnsError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            ApnsError::Io(e) => Some(e),
+            #[cfg(feature = "signing")]
+            ApnsError::Jwt(e) => Some(e),
+            #[cfg(feature = "signing")]
+            ApnsError::Signer(e) => Some(&**e),
+            ApnsError::Serialization(e) => Some(e),
+            ApnsError::InvalidHeader(e) => Some(e),
+            ApnsError::Http(e) => Some(e),
+            ApnsError::InvalidTopic(e) => Some(e),
+            ApnsError::InvalidNotification(e) => Some(e),
+            ApnsError::InvalidDeviceToken(e) => Some(e),
+            ApnsError::InvalidPayload(e) => Some(e),
+            ApnsError::InvalidTemplate(e) => Some(e),
+            ApnsError::Config(_)
+            | ApnsError::InvalidCustomData(_)
+            | ApnsError::QueueClosed
+            | ApnsError::InvalidToken
+            | ApnsError::CertificateRequired(_)
+            | ApnsError::PinMismatch(_)
+            | ApnsError::DeadlineExceeded(_)
+            | ApnsError::ClientShutDown
+            | ApnsError::Cancelled
+            | ApnsError::Superseded
+            | ApnsError::Rejected { .. }
+            | ApnsError::Blocked(_)
+            | ApnsError::DuplicateToken
+            | ApnsError::QuotaExceeded { .. }
+            | ApnsError::Expired => None,
+        }
+    }
+}
+
+impl From<std::io::Error> for ApnsError {
+    fn from(e: std::io::Error) -> Self {
+        ApnsError::Io(e)
+    }
+}
+
+#[cfg(feature = "signing")]
+impl From<jsonwebtoken::errors::Error> for ApnsError {
+    fn from(e: jsonwebtoken::errors::Error) -> Self {
+        ApnsError::Jwt(e)
+    }
+}
+
+impl From<TopicError> for ApnsError {
+    fn from(e: TopicError) -> Self {
+        ApnsError::InvalidTopic(e)
+    }
+}
+
+impl From<NotificationError> for ApnsError {
+    fn from(e: NotificationError) -> Self {
+        ApnsError::InvalidNotification(e)
+    }
+}
+
+impl From<DeviceTokenError> for ApnsError {
+    fn from(e: DeviceTokenError) -> Self {
+        ApnsError::InvalidDeviceToken(e)
+    }
+}
+
+impl From<PayloadError> for ApnsError {
+    fn from(e: PayloadError) -> Self {
+        ApnsError::InvalidPayload(e)
+    }
+}
+
+impl From<TemplateError> for ApnsError {
+    fn from(e: TemplateError) -> Self {
+        ApnsError::InvalidTemplate(e)
+    }
+}
+
+impl From<serde_json::Error> for ApnsError {
+    fn from(e: serde_json::Error) -> Self {
+        ApnsError::Serialization(e)
+    }
+}
+
+impl From<http::header::InvalidHeaderValue> for ApnsError {
+    fn from(e: http::header::InvalidHeaderValue) -> Self {
+        ApnsError::InvalidHeader(e)
+    }
+}
+
+#[cfg(feature = "tokio")]
+impl From<reqwest::Error> for ApnsError {
+    fn from(e: reqwest::Error) -> Self {
+        ApnsError::Http(e.into())
+    }
+}
+
+impl From<TransportError> for ApnsError {
+    fn from(e: TransportError) -> Self {
+        ApnsError::Http(e)
+    }
+}
+
+/// An error raised by the HTTP transport while talking to APNs.
+///
+/// The underlying error is available through [`Error::source`], without tying the public
+/// API to a particular HTTP client. A custom [`Transport`](crate::Transport) creates one with
+/// [`new`](TransportError::new), [`timeout`](TransportError::timeout),
+/// [`connect`](TransportError::connect), [`connection_lost`](TransportError::connection_lost),
+/// or [`refused`](TransportError::refused).
+#[derive(Debug)]
+pub struct TransportError {
+    source: Box<dyn Error + Send + Sync>,
+    timeout: bool,
+    connect: bool,
+    connection_lost: bool,
+    refused: bool,
+}
+
+impl TransportError {
+    /// Wraps an error of the transport.
+    pub fn new(source: impl Into<Box<dyn Error + Send + Sync>>) -> Self {
+        TransportError {
+            source: source.into(),
+            timeout: false,
+            connect: false,
+            connection_lost: false,
+            refused: false,
+        }
+    }
+
+    /// Wraps an error of a request that timed out.
+    pub fn timeout(source: impl Into<Box<dyn Error + Send + Sync>>) -> Self {
+        TransportError {
+            timeout: true,
+            ..Self::new(source)
+        }
+    }
+
+    /// Wraps an error of a connection to APNs that could not be established.
+    pub fn connect(source: impl Into<Box<dyn Error + Send + Sync>>) -> Self {
+        TransportError {
+            connect: true,
+            ..Self::new(source)
+        }
+    }
+
+    /// Wraps an error of a request whose connection to APNs was closed or reset while it was
+    /// in flight, such as by a `GOAWAY`.
+    pub fn connection_lost(source: impl Into<Box<dyn Error + Send + Sync>>) -> Self {
+        TransportError {
+            connection_lost: true,
+            ..Self::new(source)
+        }
+    }
+
+    /// Wraps an error of a request APNs refused without processing it, because its
+    /// connection was going away, so it can be resent without being delivered twice.
+    pub fn refused(source: impl Into<Box<dyn Error + Send + Sync>>) -> Self {
+        TransportError {
+            connection_lost: true,
+            refused: true,
+            ..Self::new(source)
+        }
+    }
+
+    /// Returns `true` if the request timed out.
+    pub fn is_timeout(&self) -> bool {
+        self.timeout
+    }
+
+    /// Returns `true` if the connection to APNs could not be established.
+    pub fn is_connect(&self) -> bool {
+        self.connect
+    }
+
+    /// Returns `true` if the connection to APNs was closed or reset while the request was in
+    /// flight; APNs may or may not have processed it.
+    pub fn is_connection_lost(&self) -> bool {
+        self.connection_lost
+    }
+
+    /// Returns `true` if APNs refused the request without processing it.
+    pub fn is_refused(&self) -> bool {
+        self.refused
+    }
+
+    /// Marks the error as a lost connection, or a refused request, if anything in the chain
+    /// of `source` says so.
+    #[cfg(any(feature = "tokio", feature = "h2"))]
+    fn classified(mut self) -> Self {
+        let mut current: Option<&(dyn Error + 'static)> = Some(&*self.source);
+        while let Some(error) = current {
+            if let Some(e) = error.downcast_ref::<h2::Error>() {
+                if e.reason() == Some(h2::Reason::REFUSED_STREAM)
+                    || (e.is_go_away() && e.is_remote())
+                {
+                    self.refused = true;
+                    self.connection_lost = true;
+                    break;
+                }
+                if e.is_go_away() || e.is_io() {
+                    self.connection_lost = true;
+                    break;
+                }
+            }
+            if let Some(e) = error.downcast_ref::<std::io::Error>() {
+                use std::io::ErrorKind::*;
+                if matches!(
+                    e.kind(),
+                    ConnectionReset | ConnectionAborted | BrokenPipe | NotConnected | UnexpectedEof
+                ) {
+                    self.connection_lost = true;
+                    break;
+                }
+            }
+            current = error.source();
+        }
+        self
+    }
+}
+
+#[cfg(feature = "tokio")]
+impl From<reqwest::Error> for TransportError {
+    fn from(e: reqwest::Error) -> Self {
+        TransportError {
+            timeout: e.is_timeout(),
+            connect: e.is_connect(),
+            connection_lost: false,
+            refused: false,
+            source: Box::new(e),
+        }
+        .classified()
+    }
+}
+
+#[cfg(any(feature = "tokio", feature = "h2"))]
+impl From<h2::Error> for TransportError {
+    fn from(e: h2::Error) -> Self {
+        Self::new(e).classified()
+    }
+}
+
+impl fmt::Display for TransportError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.source.fmt(f)
+    }
+}
+
+impl Error for TransportError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        Some(&*self.source)
+    }
+}
+
+/// The reason APNs gave for rejecting a notification, as documented by Apple.
+///
+/// Reasons introduced after this crate was released are preserved in `Other`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(from = "String", into = "String")]
+pub enum ErrorReason {
+    BadCollapseId,
+    BadDeviceToken,
+    BadExpirationDate,
+    BadMessageId,
+    BadPriority,
+    BadTopic,
+    DeviceTokenNotForTopic,
+    DuplicateHeaders,
+    IdleTimeout,
+    InvalidPushType,
+    MissingDeviceToken,
+    MissingTopic,
+    PayloadEmpty,
+    TopicDisallowed,
+    BadCertificate,
+    BadCertificateEnvironment,
+    ExpiredProviderToken,
+    Forbidden,
+    InvalidProviderToken,
+    MissingProviderToken,
+    UnrelatedKeyIdInToken,
+    BadPath,
+    MethodNotAllowed,
+    ExpiredToken,
+    Unregistered,
+    PayloadTooLarge,
+    TooManyProviderTokenUpdates,
+    TooManyRequests,
+    InternalServerError,
+    ServiceUnavailable,
+    Shutdown,
+    Other(String),
+}
+
+impl ErrorReason {
+    /// Returns the reason string exactly as APNs sends it.
+    pub fn as_str(&self) -> &str {
+        match self {
+            ErrorReason::BadCollapseId => "BadCollapseId",
+            ErrorReason::BadDeviceToken => "BadDeviceToken",
+            ErrorReason::BadExpirationDate => "BadExpirationDate",
+            ErrorReason::BadMessageId => "BadMessageId",
+            ErrorReason::BadPriority => "BadPriority",
+            ErrorReason::BadTopic => "BadTopic",
+            ErrorReason::DeviceTokenNotForTopic => "DeviceTokenNotForTopic",
+            ErrorReason::DuplicateHeaders => "DuplicateHeaders",
+            ErrorReason::IdleTimeout => "IdleTimeout",
+            ErrorReason::InvalidPushType => "InvalidPushType",
+            ErrorReason::MissingDeviceToken => "MissingDeviceToken",
+            ErrorReason::MissingTopic => "MissingTopic",
+            ErrorReason::PayloadEmpty => "PayloadEmpty",
+            ErrorReason::TopicDisallowed => "TopicDisallowed",
+            ErrorReason::BadCertificate => "BadCertificate",
+            ErrorReason::BadCertificateEnvironment => "BadCertificateEnvironment",
+            ErrorReason::ExpiredProviderToken => "ExpiredProviderToken",
+            ErrorReason::Forbidden => "Forbidden",
+            ErrorReason::InvalidProviderToken => "InvalidProviderToken",
+            ErrorReason::MissingProviderToken => "MissingProviderToken",
+            ErrorReason::UnrelatedKeyIdInToken => "UnrelatedKeyIdInToken",
+            ErrorReason::BadPath => "BadPath",
+            ErrorReason::MethodNotAllowed => "MethodNotAllowed",
+            ErrorReason::ExpiredToken => "ExpiredToken",
+            ErrorReason::Unregistered => "Unregistered",
+            ErrorReason::PayloadTooLarge => "PayloadTooLarge",
+            ErrorReason::TooManyProviderTokenUpdates => "TooManyProviderTokenUpdates",
+            ErrorReason::TooManyRequests => "TooManyRequests",
+            ErrorReason::InternalServerError => "InternalServerError",
+            ErrorReason::ServiceUnavailable => "ServiceUnavailable",
+            ErrorReason::Shutdown => "Shutdown",
+            ErrorReason::Other(reason) => reason,
+        }
+    }
+
+    /// Returns a stable code for this reason, such as `APNRS-410-UNREGISTERED`, made of the
+    /// HTTP status APNs answers it with and the reason's name.
+    ///
+    /// Codes never change once assigned, so alerting rules and runbooks can match on them
+    /// even if messages are reworded. Reasons unknown to this crate are `APNRS-000-OTHER`.
+    pub fn code(&self) -> &'static str {
+        match self {
+            ErrorReason::BadCollapseId => "APNRS-400-BAD-COLLAPSE-ID",
+            ErrorReason::BadDeviceToken => "APNRS-400-BAD-DEVICE-TOKEN",
+            ErrorReason::BadExpirationDate => "APNRS-400-BAD-EXPIRATION-DATE",
+            ErrorReason::BadMessageId => "APNRS-400-BAD-MESSAGE-ID",
+            ErrorReason::BadPriority => "APNRS-400-BAD-PRIORITY",
+            ErrorReason::BadTopic => "APNRS-400-BAD-TOPIC",
+            ErrorReason::DeviceTokenNotForTopic => "APNRS-400-DEVICE-TOKEN-NOT-FOR-TOPIC",
+            ErrorReason::DuplicateHeaders => "APNRS-400-DUPLICATE-HEADERS",
+            ErrorReason::IdleTimeout => "APNRS-400-IDLE-TIMEOUT",
+            ErrorReason::InvalidPushType => "APNRS-400-INVALID-PUSH-TYPE",
+            ErrorReason::MissingDeviceToken => "APNRS-400-MISSING-DEVICE-TOKEN",
+            ErrorReason::MissingTopic => "APNRS-400-MISSING-TOPIC",
+            ErrorReason::PayloadEmpty => "APNRS-400-PAYLOAD-EMPTY",
+            ErrorReason::TopicDisallowed => "APNRS-400-TOPIC-DISALLOWED",
+            ErrorReason::BadCertificate => "APNRS-403-BAD-CERTIFICATE",
+            ErrorReason::BadCertificateEnvironment => "APNRS-403-BAD-CERTIFICATE-ENVIRONMENT",
+            ErrorReason::ExpiredProviderToken => "APNRS-403-EXPIRED-PROVIDER-TOKEN",
+            ErrorReason::Forbidden => "APNRS-403-FORBIDDEN",
+            ErrorReason::InvalidProviderToken => "APNRS-403-INVALID-PROVIDER-TOKEN",
+            ErrorReason::MissingProviderToken => "APNRS-403-MISSING-PROVIDER-TOKEN",
+            ErrorReason::UnrelatedKeyIdInToken => "APNRS-403-UNRELATED-KEY-ID-IN-TOKEN",
+            ErrorReason::BadPath => "APNRS-404-BAD-PATH",
+            ErrorReason::MethodNotAllowed => "APNRS-405-METHOD-NOT-ALLOWED",
+            ErrorReason::ExpiredToken => "APNRS-410-EXPIRED-TOKEN",
+            ErrorReason::Unregistered => "APNRS-410-UNREGISTERED",
+            ErrorReason::PayloadTooLarge => "APNRS-413-PAYLOAD-TOO-LARGE",
+            ErrorReason::TooManyProviderTokenUpdates => "APNRS-429-TOO-MANY-PROVIDER-TOKEN-UPDATES",
+            ErrorReason::TooManyRequests => "APNRS-429-TOO-MANY-REQUESTS",
+            ErrorReason::InternalServerError => "APNRS-500-INTERNAL-SERVER-ERROR",
+            ErrorReason::ServiceUnavailable => "APNRS-503-SERVICE-UNAVAILABLE",
+            ErrorReason::Shutdown => "APNRS-503-SHUTDOWN",
+            ErrorReason::Other(_) => "APNRS-000-OTHER",
+        }
+    }
+
+    /// Returns `true` for reasons blaming the provider's credentials rather than the
+    /// notification: an invalid or missing provider token, a key ID unrelated to
+    /// the team, a bad certificate or one for the other environment, a forbidden request,
+    /// or a topic the credentials may not push to.
+    pub fn is_credential_problem(&self) -> bool {
+        matches!(
+            self,
+            ErrorReason::InvalidProviderToken
+                | ErrorReason::MissingProviderToken
+                | ErrorReason::UnrelatedKeyIdInToken
+                | ErrorReason::BadCertificate
+                | ErrorReason::BadCertificateEnvironment
+                | ErrorReason::Forbidden
+                | ErrorReason::TopicDisallowed
+        )
+    }
+
+    /// Returns `true` for reasons APNs answers with `429 Too Many Requests`: too many
+    /// notifications to one device, or provider tokens renewed too often.
+    pub fn is_throttled(&self) -> bool {
+        matches!(
+            self,
+            ErrorReason::TooManyRequests | ErrorReason::TooManyProviderTokenUpdates
+        )
+    }
+}
+
+impl From<String> for ErrorReason {
+    fn from(reason: String) -> Self {
+        match reason.as_str() {
+            "BadCollapseId" => ErrorReason::BadCollapseId,
+            "BadDeviceToken" => ErrorReason::BadDeviceToken,
+            "BadExpirationDate" => ErrorReason::BadExpirationDate,
+            "BadMessageId" => ErrorReason::BadMessageId,
+            "BadPriority" => ErrorReason::BadPriority,
+            "BadTopic" => ErrorReason::BadTopic,
+            "DeviceTokenNotForTopic" => ErrorReason::DeviceTokenNotForTopic,
+            "DuplicateHeaders" => ErrorReason::DuplicateHeaders,
+            "IdleTimeout" => ErrorReason::IdleTimeout,
+            "InvalidPushType" => ErrorReason::InvalidPushType,
+            "MissingDeviceToken" => ErrorReason::MissingDeviceToken,
+            "MissingTopic" => ErrorReason::MissingTopic,
+            "PayloadEmpty" => ErrorReason::PayloadEmpty,
+            "TopicDisallowed" => ErrorReason::TopicDisallowed,
+            "BadCertificate" => ErrorReason::BadCertificate,
+            "BadCertificateEnvironment" => ErrorReason::BadCertificateEnvironment,
+            "ExpiredProviderToken" => ErrorReason::ExpiredProviderToken,
+            "Forbidden" => ErrorReason::Forbidden,
+            "InvalidProviderToken" => ErrorReason::InvalidProviderToken,
+            "MissingProviderToken" => ErrorReason::MissingProviderToken,
+            "UnrelatedKeyIdInToken" => ErrorReason::UnrelatedKeyIdInToken,
+            "BadPath" => ErrorReason::BadPath,
+            "MethodNotAllowed" => ErrorReason::MethodNotAllowed,
+            "ExpiredToken" => ErrorReason::ExpiredToken,
+            "Unregistered" => ErrorReason::Unregistered,
+            "PayloadTooLarge" => ErrorReason::PayloadTooLarge,
+            "TooManyProviderTokenUpdates" => ErrorReason::TooManyProviderTokenUpdates,
+            "TooManyRequests" => ErrorReason::TooManyRequests,
+            "InternalServerError" => ErrorReason::InternalServerError,
+            "ServiceUnavailable" => ErrorReason::ServiceUnavailable,
+            "Shutdown" => ErrorReason::Shutdown,
+            _ => ErrorReason::Other(reason),
+        }
+    }
+}
+
+impl From<ErrorReason> for String {
+    fn from(reason: ErrorReason) -> Self {
+        match reason {
+            ErrorReason::Other(reason) => reason,
+            reason => reason.as_str().to_string(),
+        }
+    }
+}
+
+impl fmt::Display for ErrorReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} [{}]", self.as_str(), self.code())
+    }
+}
+
+/// The JSON body APNs returns alongside an error status.
+#[cfg(feature = "signing")]
+#[derive(Debug, Deserialize)]
+pub(crate) struct ErrorBody {
+    pub reason: ErrorReason,
+    pub timestamp: Option<u64>,
+}
@@ -0,0 +1,468 @@
+//! A live feed of send events, for dashboards and the `apnrs tail` command, and of the
+//! lifecycle of every notification, for observability and housekeeping consumers.
+
+use crate::privacy::{mask_token, TokenHasher};
+use crate::{ApnsResponse, ErrorReason};
+use futures_util::stream::{self, BoxStream, StreamExt};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio::sync::broadcast;
+
+/// How many events a slow subscriber may fall behind before it starts missing them.
+const EVENT_CAPACITY: usize = 1024;
+
+/// Describes one request made to APNs.
+///
+/// # Fields
+///
+/// * `timestamp_ms` - When the request completed, in milliseconds since the Unix epoch.
+/// * `topic` - The topic the notification was sent under.
+/// * `device_token` - The device token of the target device, or its keyed hash if the client
+///   was built with a [`TokenHasher`](crate::TokenHasher).
+/// * `status` - The HTTP status APNs answered with, or `None` if the request failed in transit.
+/// * `reason` - The reason APNs gave for rejecting the notification, or a description of
+///   the transport error.
+/// * `apns_id` - The `apns-id` APNs assigned to the notification, if it answered.
+/// * `latency_ms` - How long the request took, in milliseconds.
+/// * `trace_id` - The ID of the distributed trace the send belongs to, if it was sent with a
+///   [`TraceContext`](crate::TraceContext).
+#[derive(Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SendEvent {
+    pub timestamp_ms: u64,
+    pub topic: String,
+    pub device_token: String,
+    pub status: Option<u16>,
+    pub reason: Option<String>,
+    pub apns_id: Option<String>,
+    pub latency_ms: u64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub trace_id: Option<String>,
+}
+
+impl fmt::Debug for SendEvent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SendEvent")
+            .field("timestamp_ms", &self.timestamp_ms)
+            .field("topic", &self.topic)
+            .field("device_token", &mask_token(&self.device_token))
+            .field("status", &self.status)
+            .field("reason", &self.reason)
+            .field("apns_id", &self.apns_id)
+            .field("latency_ms", &self.latency_ms)
+            .field("trace_id", &self.trace_id)
+            .finish()
+    }
+}
+
+/// A step in the life of a notification, or a change in the client sending it, as streamed
+/// by [`ApnsClient::events`](crate::ApnsClient::events).
+///
+/// Device tokens are the keyed hashes of the tokens if the client was built with a
+/// [`TokenHasher`](crate::TokenHasher), as in [`SendEvent`].
+///
+/// # Variants
+///
+/// * `Enqueued` - A notification was added to the queue of an
+///   [`ApnsSender`](crate::ApnsSender) or [`ApnsSenderHandle`](crate::ApnsSenderHandle).
+/// * `Sent` - APNs accepted a notification.
+/// * `Retried` - A notification is being sent again, for the `attempt`th time, after it
+///   failed with `reason`.
+/// * `Failed` - A notification failed: APNs rejected it with `status` and `reason`, or the
+///   request failed in transit, without a status and with the transport error as `reason`.
+///   A notification that is retried afterwards fails once per attempt.
+/// * `Unregistered` - APNs answered that the device token is no longer active, since
+///   `unregistered_at` in milliseconds since the Unix epoch if it said when. Such tokens
+///   can be removed from storage.
+/// * `TokenRefreshed` - A new provider token was signed for `team_id` with the key
+///   `key_id`.
+/// * `CircuitOpened` - APNs throttled a sender, which holds off every worker sending to it
+///   for `pause`.
+/// * `JournalFailed` - A sender's journal could not record a notification, failing with
+///   `error`, and the notification was queued in memory only; see
+///   `ApnsSenderBuilder::journal_fallback` (with the `journal` feature).
+#[derive(Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum DeliveryEvent {
+    Enqueued {
+        topic: String,
+        device_token: String,
+        apns_id: Option<String>,
+    },
+    Sent {
+        topic: String,
+        device_token: String,
+        apns_id: Option<String>,
+    },
+    Retried {
+        topic: String,
+        device_token: String,
+        apns_id: Option<String>,
+        attempt: u32,
+        reason: String,
+    },
+    Failed {
+        topic: String,
+        device_token: String,
+        apns_id: Option<String>,
+        status: Option<u16>,
+        reason: String,
+    },
+    Unregistered {
+        topic: String,
+        device_token: String,
+        unregistered_at: Option<u64>,
+    },
+    TokenRefreshed {
+        team_id: String,
+        key_id: String,
+    },
+    CircuitOpened {
+        pause: Duration,
+    },
+    JournalFailed {
+        error: String,
+    },
+}
+
+impl DeliveryEvent {
+    /// Returns the device token the event is about, if it is about a notification.
+    pub fn device_token(&self) -> Option<&str> {
+        match self {
+            DeliveryEvent::Enqueued { device_token, .. }
+            | DeliveryEvent::Sent { device_token, .. }
+            | DeliveryEvent::Retried { device_token, .. }
+            | DeliveryEvent::Failed { device_token, .. }
+            | DeliveryEvent::Unregistered { device_token, .. } => Some(device_token),
+            DeliveryEvent::TokenRefreshed { .. }
+            | DeliveryEvent::CircuitOpened { .. }
+            | DeliveryEvent::JournalFailed { .. } => None,
+        }
+    }
+}
+
+impl fmt::Debug for DeliveryEvent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DeliveryEvent::Enqueued {
+                topic,
+                device_token,
+                apns_id,
+            } => f
+                .debug_struct("Enqueued")
+                .field("topic", topic)
+                .field("device_token", &mask_token(device_token))
+                .field("apns_id", apns_id)
+                .finish(),
+            DeliveryEvent::Sent {
+                topic,
+                device_token,
+                apns_id,
+            } => f
+                .debug_struct("Sent")
+                .field("topic", topic)
+                .field("device_token", &mask_token(device_token))
+                .field("apns_id", apns_id)
+                .finish(),
+            DeliveryEvent::Retried {
+                topic,
+                device_token,
+                apns_id,
+                attempt,
+                reason,
+            } => f
+                .debug_struct("Retried")
+                .field("topic", topic)
+                .field("device_token", &mask_token(device_token))
+                .field("apns_id", apns_id)
+                .field("attempt", attempt)
+                .field("reason", reason)
+                .finish(),
+            DeliveryEvent::Failed {
+                topic,
+                device_token,
+                apns_id,
+                status,
+                reason,
+            } => f
+                .debug_struct("Failed")
+                .field("topic", topic)
+                .field("device_token", &mask_token(device_token))
+                .field("apns_id", apns_id)
+                .field("status", status)
+                .field("reason", reason)
+                .finish(),
+            DeliveryEvent::Unregistered {
+                topic,
+                device_token,
+                unregistered_at,
+            } => f
+                .debug_struct("Unregistered")
+                .field("topic", topic)
+                .field("device_token", &mask_token(device_token))
+                .field("unregistered_at", unregistered_at)
+                .finish(),
+            DeliveryEvent::TokenRefreshed { team_id, key_id } => f
+                .debug_struct("TokenRefreshed")
+                .field("team_id", team_id)
+                .field("key_id", key_id)
+                .finish(),
+            DeliveryEvent::CircuitOpened { pause } => f
+                .debug_struct("CircuitOpened")
+                .field("pause", pause)
+                .finish(),
+            DeliveryEvent::JournalFailed { error } => f
+                .debug_struct("JournalFailed")
+                .field("error", error)
+                .finish(),
+        }
+    }
+}
+
+/// Publishes [`SendEvent`]s and [`DeliveryEvent`]s to any number of subscribers.
+pub(crate) struct EventPublisher {
+    tx: broadcast::Sender<SendEvent>,
+    delivery: broadcast::Sender<DeliveryEvent>,
+    hasher: Option<TokenHasher>,
+}
+
+impl EventPublisher {
+    /// Creates a publisher, hashing device tokens with `hasher` if given.
+    pub(crate) fn new(hasher: Option<TokenHasher>) -> Self {
+        EventPublisher {
+            tx: broadcast::channel(EVENT_CAPACITY).0,
+            delivery: broadcast::channel(EVENT_CAPACITY).0,
+            hasher,
+        }
+    }
+
+    /// Returns the device token as it may appear in events.
+    fn token(&self, device_token: &str) -> String {
+        match &self.hasher {
+            Some(hasher) => hasher.hash(device_token),
+            None => device_token.to_string(),
+        }
+    }
+
+    /// Returns a new subscription to events published from now on.
+    pub(crate) fn subscribe(&self) -> broadcast::Receiver<SendEvent> {
+        self.tx.subscribe()
+    }
+
+    /// Returns `true` if anyone is listening, so callers can skip building events.
+    pub(crate) fn has_subscribers(&self) -> bool {
+        self.tx.receiver_count() > 0
+    }
+
+    /// Returns a stream of the delivery events published from now on, skipping those a
+    /// slow consumer fell too far behind to receive.
+    pub(crate) fn deliveries(&self) -> BoxStream<'static, DeliveryEvent> {
+        stream::unfold(self.delivery.subscribe(), |mut rx| async move {
+            loop {
+                match rx.recv().await {
+                    Ok(event) => return Some((event, rx)),
+                    Err(broadcast::error::RecvError::Lagged(_skipped)) => {
+                        #[cfg(feature = "tracing")]
+                        tracing::warn!(
+                            skipped = _skipped,
+                            "delivery event consumer fell behind; events skipped"
+                        );
+                        #[cfg(feature = "logging")]
+                        log::warn!(
+                            "delivery event consumer fell behind; {} events skipped",
+                            _skipped
+                        );
+                    }
+                    Err(broadcast::error::RecvError::Closed) => return None,
+                }
+            }
+        })
+        .boxed()
+    }
+
+    /// Publishes a delivery event built by `event` from the device token as it may appear
+    /// in events, if anyone is listening.
+    fn deliver(&self, device_token: &str, event: impl FnOnce(String) -> DeliveryEvent) {
+        if self.delivery.receiver_count() > 0 {
+            let _ = self.delivery.send(event(self.token(device_token)));
+        }
+    }
+
+    /// Publishes that a notification was added to a sender's queue.
+    pub(crate) fn enqueued(&self, topic: &str, device_token: &str, apns_id: Option<&str>) {
+        self.deliver(device_token, |device_token| DeliveryEvent::Enqueued {
+            topic: topic.to_string(),
+            device_token,
+            apns_id: apns_id.map(str::to_string),
+        });
+    }
+
+    /// Publishes what became of a notification APNs answered.
+    pub(crate) fn answered(
+        &self,
+        topic: &str,
+        device_token: &str,
+        status: u16,
+        reason: Option<&ErrorReason>,
+        apns_id: Option<&str>,
+        unregistered_at: Option<u64>,
+    ) {
+        self.deliver(device_token, |device_token| match reason {
+            None if (200..300).contains(&status) => DeliveryEvent::Sent {
+                topic: topic.to_string(),
+                device_token,
+                apns_id: apns_id.map(str::to_string),
+            },
+            Some(ErrorReason::Unregistered) => DeliveryEvent::Unregistered {
+                topic: topic.to_string(),
+                device_token,
+                unregistered_at,
+            },
+            _ => DeliveryEvent::Failed {
+                topic: topic.to_string(),
+                device_token,
+                apns_id: apns_id.map(str::to_string),
+                status: Some(status),
+                reason: reason.map_or_else(|| status.to_string(), |r| r.as_str().to_string()),
+            },
+        });
+    }
+
+    /// Publishes that a notification is being sent again after it failed with `reason`.
+    pub(crate) fn retried(
+        &self,
+        topic: &str,
+        device_token: &str,
+        apns_id: Option<&str>,
+        attempt: u32,
+        reason: &str,
+    ) {
+        self.deliver(device_token, |device_token| DeliveryEvent::Retried {
+            topic: topic.to_string(),
+            device_token,
+            apns_id: apns_id.map(str::to_string),
+            attempt,
+            reason: reason.to_string(),
+        });
+    }
+
+    /// Publishes that a new provider token was signed.
+    pub(crate) fn token_refreshed(&self, team_id: &str, key_id: &str) {
+        if self.delivery.receiver_count() > 0 {
+            let _ = self.delivery.send(DeliveryEvent::TokenRefreshed {
+                team_id: team_id.to_string(),
+                key_id: key_id.to_string(),
+            });
+        }
+    }
+
+    /// Publishes that a throttled sender holds off its workers for `pause`.
+    pub(crate) fn circuit_opened(&self, pause: Duration) {
+        if self.delivery.receiver_count() > 0 {
+            let _ = self.delivery.send(DeliveryEvent::CircuitOpened { pause });
+        }
+    }
+
+    /// Publishes that a sender's journal failed to record a notification with `error`.
+    #[cfg(feature = "journal")]
+    pub(crate) fn journal_failed(&self, error: &str) {
+        if self.delivery.receiver_count() > 0 {
+            let _ = self.delivery.send(DeliveryEvent::JournalFailed {
+                error: error.to_string(),
+            });
+        }
+    }
+
+    /// Publishes an event for an answered request.
+    pub(crate) fn response_received(
+        &self,
+        topic: &str,
+        device_token: &str,
+        response: &ApnsResponse,
+        latency: Duration,
+        trace_id: Option<&str>,
+    ) {
+        if !self.has_subscribers() {
+            return;
+        }
+        let _ = self.tx.send(SendEvent {
+            timestamp_ms: now_ms(),
+            topic: topic.to_string(),
+            device_token: self.token(device_token),
+            status: Some(response.status.as_u16()),
+            reason: response.error.as_ref().map(|r| r.as_str().to_string()),
+            apns_id: response.apns_id.clone(),
+            latency_ms: latency.as_millis() as u64,
+            trace_id: trace_id.map(str::to_string),
+        });
+    }
+
+    /// Publishes an event for a request that failed before APNs answered.
+    pub(crate) fn transport_failed(
+        &self,
+        topic: &str,
+        device_token: &str,
+        apns_id: Option<&str>,
+        error: &reqwest::Error,
+        latency: Duration,
+        trace_id: Option<&str>,
+    ) {
+        self.deliver(device_token, |device_token| DeliveryEvent::Failed {
+            topic: topic.to_string(),
+            device_token,
+            apns_id: apns_id.map(str::to_string),
+            status: None,
+            reason: error.to_string(),
+        });
+        if !self.has_subscribers() {
+            return;
+        }
+        let _ = self.tx.send(SendEvent {
+            timestamp_ms: now_ms(),
+            topic: topic.to_string(),
+            device_token: self.token(device_token),
+            status: None,
+            reason: Some(error.to_string()),
+            apns_id: None,
+            latency_ms: latency.as_millis() as u64,
+            trace_id: trace_id.map(str::to_string),
+        });
+    }
+}
+
+fn now_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or(0)
+}
+
+/// Streams a client's send events as newline-delimited JSON to every connection on a
+/// unix socket, which `apnrs tail --socket <path>` attaches to.
+#[cfg(unix)]
+pub(crate) async fn serve_unix(
+    mut events: impl FnMut() -> broadcast::Receiver<SendEvent>,
+    listener: tokio::net::UnixListener,
+) {
+    use tokio::io::AsyncWriteExt;
+
+    while let Ok((mut stream, _)) = listener.accept().await {
+        let mut rx = events();
+        tokio::spawn(async move {
+            loop {
+                let event = match rx.recv().await {
+                    Ok(event) => event,
+                    Err(broadcast::error::RecvError::Lagged(_)) => continue,
+                    Err(broadcast::error::RecvError::Closed) => break,
+                };
+                let Ok(mut line) = serde_json::to_vec(&event) else {
+                    continue;
+                };
+                line.push(b'\n');
+                if stream.write_all(&line).await.is_err() {
+                    break;
+                }
+            }
+        });
+    }
+}
@@ -0,0 +1,339 @@
+//! Sending one prepared payload to a stream of device tokens, pulled only as fast as they
+//! can be sent.
+
+use crate::sender::Pacer;
+use crate::{ApnsClient, ApnsError, DeviceToken, PreparedPayload, SendOptions, SendOutcome};
+use futures_util::stream::{BoxStream, Stream, StreamExt};
+use std::collections::HashSet;
+use std::future::Future;
+use std::panic;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use tokio::task::JoinSet;
+use tokio_util::sync::{CancellationToken, WaitForCancellationFutureOwned};
+
+/// How many notifications a fan-out keeps in flight by default.
+const DEFAULT_CONCURRENCY: usize = 16;
+
+/// Sends one [`PreparedPayload`] to every device token of a stream, such as rows read from
+/// a database cursor, without collecting the tokens first.
+///
+/// Tokens are pulled from the stream only when a request can be started, i.e. when fewer
+/// than [`concurrency`](FanOut::concurrency) requests are in flight and the
+/// [`rate_limit`](FanOut::rate_limit) allows another, so a slow APNs slows down reading the
+/// cursor instead of buffering tokens in memory. Results are yielded as APNs answers, not in
+/// the order of the tokens. Tokens the client's token store marks invalid are not sent to
+/// and yield `ApnsError::InvalidToken`. With [`deduplicate`](FanOut::deduplicate), repeats
+/// of a token are not sent to either and yield `ApnsError::DuplicateToken`, so recording
+/// the results in a [`BatchReport`](crate::BatchReport) counts how many were skipped.
+///
+/// Each notification is sent on its own tokio task, so a fan-out uses every worker thread of
+/// the runtime. The tasks belong to the returned [`FanOutResults`]: dropping it aborts every
+/// request still in flight, and [`FanOutResults::cancel`] stops the fan-out while still
+/// yielding the results of requests that already completed. A fan-out can also be stopped
+/// from elsewhere, such as a shutdown handler, through a
+/// [`cancellation_token`](FanOut::cancellation_token).
+///
+/// # Example
+///
+/// ```rust,no_run
+/// use apnrs::{ApnsClient, ApnsPayload, DeviceToken};
+/// use futures_util::stream::{Stream, StreamExt};
+///
+/// # async fn run(
+/// #     client: ApnsClient,
+/// #     payload: ApnsPayload,
+/// #     tokens: impl Stream<Item = DeviceToken> + Send + 'static,
+/// # ) -> Result<(), apnrs::ApnsError> {
+/// let prepared = client.prepare(&payload)?;
+/// let mut results = client
+///     .fan_out("com.example.app", prepared)
+///     .concurrency(64)
+///     .rate_limit(500.0)
+///     .send(tokens);
+///
+/// while let Some((token, result)) = results.next().await {
+///     if let Err(e) = result {
+///         eprintln!("{}: {}", token, e);
+///     }
+/// }
+/// # Ok(())
+/// # }
+/// ```
+pub struct FanOut {
+    client: ApnsClient,
+    topic: Arc<str>,
+    payload: PreparedPayload,
+    options: Arc<SendOptions>,
+    concurrency: usize,
+    rate_limit: Option<f64>,
+    deduplicate: bool,
+    cancel: Option<CancellationToken>,
+}
+
+impl ApnsClient {
+    /// Starts a fan-out of `payload` under `topic`; see [`FanOut`].
+    pub fn fan_out(&self, topic: impl Into<String>, payload: PreparedPayload) -> FanOut {
+        FanOut {
+            client: self.clone(),
+            topic: Arc::from(topic.into()),
+            payload,
+            options: Arc::default(),
+            concurrency: DEFAULT_CONCURRENCY,
+            rate_limit: None,
+            deduplicate: false,
+            cancel: None,
+        }
+    }
+}
+
+impl FanOut {
+    /// Sets how many notifications are in flight at once (defaults to 16).
+    pub fn concurrency(mut self, concurrency: usize) -> Self {
+        self.concurrency = concurrency.max(1);
+        self
+    }
+
+    /// Caps the notifications sent at `per_second` a second, spaced evenly (defaults to no
+    /// limit).
+    pub fn rate_limit(mut self, per_second: f64) -> Self {
+        self.rate_limit = Some(per_second).filter(|rate| *rate > 0.0);
+        self
+    }
+
+    /// Sends to each device token only once, for recipient lists where users appear twice
+    /// after reinstalling the app (defaults to `false`).
+    ///
+    /// Every token sent to is remembered until the stream ends, taking memory in proportion
+    /// to the number of distinct tokens.
+    ///
+    /// # Example
+    ///
+    /// ```rust,no_run
+    /// use apnrs::{ApnsClient, ApnsPayload, BatchReport, DeviceToken};
+    /// use futures_util::stream::{self, StreamExt};
+    ///
+    /// # async fn run(client: ApnsClient, payload: ApnsPayload, tokens: Vec<DeviceToken>) -> Result<(), apnrs::ApnsError> {
+    /// let prepared = client.prepare(&payload)?;
+    /// let mut results = client
+    ///     .fan_out("com.example.app", prepared)
+    ///     .deduplicate(true)
+    ///     .send(stream::iter(tokens));
+    ///
+    /// let mut report = BatchReport::new();
+    /// while let Some((token, result)) = results.next().await {
+    ///     report.record(&token, &result);
+    /// }
+    /// println!("skipped {} duplicate tokens", report.duplicates);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn deduplicate(mut self, deduplicate: bool) -> Self {
+        self.deduplicate = deduplicate;
+        self
+    }
+
+    /// Stops the fan-out once `token` is cancelled, as [`FanOutResults::cancel`] does: the
+    /// stream yields the results of requests that had already completed and ends.
+    ///
+    /// # Example
+    ///
+    /// ```rust,no_run
+    /// use apnrs::{ApnsClient, ApnsPayload, BatchReport, CancellationToken, DeviceToken};
+    /// use futures_util::stream::{self, StreamExt};
+    ///
+    /// # async fn run(client: ApnsClient, payload: ApnsPayload, tokens: Vec<DeviceToken>) -> Result<(), apnrs::ApnsError> {
+    /// let cancel = CancellationToken::new();
+    /// let prepared = client.prepare(&payload)?;
+    /// let mut results = client
+    ///     .fan_out("com.example.app", prepared)
+    ///     .cancellation_token(cancel.clone())
+    ///     .send(stream::iter(tokens));
+    ///
+    /// // Cancelled by an operator, e.g. from an admin endpoint
+    /// # cancel.cancel();
+    /// let mut report = BatchReport::new();
+    /// while let Some((token, result)) = results.next().await {
+    ///     report.record(&token, &result);
+    /// }
+    /// println!("delivered {} before the fan-out was cancelled", report.delivered);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn cancellation_token(mut self, token: CancellationToken) -> Self {
+        self.cancel = Some(token);
+        self
+    }
+
+    /// Sets the options every notification is sent with.
+    pub fn options(mut self, options: SendOptions) -> Self {
+        self.options = Arc::new(options);
+        self
+    }
+
+    /// Sends the payload to every token of `tokens`.
+    ///
+    /// # Returns
+    ///
+    /// A stream of each token with the result of sending to it, which ends once every token
+    /// has been answered. Nothing is sent until the stream is polled, which must happen
+    /// within a tokio runtime.
+    pub fn send<S>(self, tokens: S) -> FanOutResults
+    where
+        S: Stream<Item = DeviceToken> + Send + 'static,
+    {
+        let FanOut {
+            client,
+            topic,
+            payload,
+            options,
+            concurrency,
+            rate_limit,
+            deduplicate,
+            cancel,
+        } = self;
+        let pacer = rate_limit.map(|rate| Arc::new(Pacer::new(rate)));
+        let mut seen = deduplicate.then(HashSet::new);
+
+        let sends = tokens
+            .map(move |token| {
+                let duplicate = seen
+                    .as_mut()
+                    .is_some_and(|seen| !seen.insert(token.clone()));
+                (token, duplicate)
+            })
+            .then(move |(token, duplicate)| {
+                let pacer = pacer.clone().filter(|_| !duplicate);
+                async move {
+                    if let Some(pacer) = pacer {
+                        pacer.wait().await;
+                    }
+                    (token, duplicate)
+                }
+            })
+            .map(move |(token, duplicate)| -> PendingSend {
+                let client = client.clone();
+                let topic = Arc::clone(&topic);
+                let payload = payload.clone();
+                let options = Arc::clone(&options);
+                Box::pin(async move {
+                    if duplicate {
+                        return (token, Err(ApnsError::DuplicateToken));
+                    }
+                    if client.token_store().is_invalid(&token) {
+                        return (token, Err(ApnsError::InvalidToken));
+                    }
+                    let result = client
+                        .send_prepared(&token, &topic, &payload, &options)
+                        .await;
+                    (token, result)
+                })
+            })
+            .boxed();
+        FanOutResults {
+            sends: Some(sends),
+            tasks: JoinSet::new(),
+            concurrency,
+            cancelled: cancel.map(|token| Box::pin(token.cancelled_owned())),
+        }
+    }
+}
+
+/// A send to be started on its own task.
+type PendingSend = Pin<Box<dyn Future<Output = FanOutResult> + Send>>;
+
+/// A device token with the result of sending to it.
+type FanOutResult = (DeviceToken, Result<SendOutcome, ApnsError>);
+
+/// The results of a [`FanOut`], as a stream of each token with the result of sending to it.
+///
+/// Every request in flight runs on a task owned by this stream, so dropping it aborts them
+/// instead of leaving them running in the background.
+///
+/// # Example
+///
+/// ```rust,no_run
+/// use apnrs::{ApnsClient, ApnsPayload, DeviceToken};
+/// use futures_util::stream::{self, StreamExt};
+///
+/// # async fn run(client: ApnsClient, payload: ApnsPayload, tokens: Vec<DeviceToken>) -> Result<(), apnrs::ApnsError> {
+/// let prepared = client.prepare(&payload)?;
+/// let mut results = client
+///     .fan_out("com.example.app", prepared)
+///     .send(stream::iter(tokens));
+///
+/// let mut failures = 0;
+/// while let Some((_, result)) = results.next().await {
+///     if result.is_err() {
+///         failures += 1;
+///         if failures == 100 {
+///             // Stop sending, but still see the answers to requests already completed
+///             results.cancel();
+///         }
+///     }
+/// }
+/// # Ok(())
+/// # }
+/// ```
+pub struct FanOutResults {
+    sends: Option<BoxStream<'static, PendingSend>>,
+    tasks: JoinSet<FanOutResult>,
+    concurrency: usize,
+    /// Resolves once the fan-out's cancellation token is cancelled.
+    cancelled: Option<Pin<Box<WaitForCancellationFutureOwned>>>,
+}
+
+impl FanOutResults {
+    /// Stops the fan-out: no further tokens are pulled, and requests still in flight are
+    /// aborted. The stream then yields the results of requests that had already completed
+    /// and ends.
+    pub fn cancel(&mut self) {
+        self.sends = None;
+        self.tasks.abort_all();
+    }
+
+    /// Returns the number of requests in flight.
+    pub fn in_flight(&self) -> usize {
+        self.tasks.len()
+    }
+}
+
+impl Stream for FanOutResults {
+    type Item = FanOutResult;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = &mut *self;
+        if let Some(cancelled) = &mut this.cancelled {
+            if cancelled.as_mut().poll(cx).is_ready() {
+                this.cancelled = None;
+                this.cancel();
+            }
+        }
+        while this.tasks.len() < this.concurrency {
+            let Some(sends) = &mut this.sends else {
+                break;
+            };
+            match sends.poll_next_unpin(cx) {
+                Poll::Ready(Some(send)) => {
+                    this.tasks.spawn(send);
+                }
+                Poll::Ready(None) => this.sends = None,
+                Poll::Pending => break,
+            }
+        }
+
+        loop {
+            return match this.tasks.poll_join_next(cx) {
+                Poll::Ready(Some(Ok(result))) => Poll::Ready(Some(result)),
+                Poll::Ready(Some(Err(e))) if e.is_panic() => panic::resume_unwind(e.into_panic()),
+                // Aborted by `cancel`
+                Poll::Ready(Some(Err(_))) => continue,
+                Poll::Ready(None) if this.sends.is_none() => Poll::Ready(None),
+                // Waiting for the next token, whose stream has registered the waker
+                Poll::Ready(None) => Poll::Pending,
+                Poll::Pending => Poll::Pending,
+            };
+        }
+    }
+}
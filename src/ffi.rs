@@ -0,0 +1,175 @@
+//! UniFFI bindings for Kotlin, Swift, and Python, enabled with the `uniffi` feature.
+
+use crate::client::ApnsClient;
+use crate::environment::Environment;
+use crate::error::ApnsError;
+use crate::{ApnsPayload, Aps, Badge, Sound};
+use std::fmt;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// The credentials and environment of a [`PushClient`].
+///
+/// # Fields
+///
+/// * `auth_key_path` - The path of the `.p8` auth key file.
+/// * `team_id` - The team ID of the Apple developer account.
+/// * `key_id` - The ID of the auth key.
+/// * `sandbox` - Sends to the sandbox environment instead of production.
+#[derive(Debug, Clone, uniffi::Record)]
+pub struct ClientConfig {
+    pub auth_key_path: String,
+    pub team_id: String,
+    pub key_id: String,
+    pub sandbox: bool,
+}
+
+/// A notification to send with [`PushClient::send`].
+///
+/// # Fields
+///
+/// * `alert` - The alert text.
+/// * `badge` - The badge to show, `0` to clear it, or `None` to leave it unchanged.
+/// * `sound` - The sound to play (`"default"` for the system sound), or `None` for silence.
+/// * `category` - The notification category.
+/// * `thread_id` - The thread identifier for grouping notifications.
+/// * `content_available` - Marks the notification as a background update.
+/// * `custom_key` - The value of the payload's `custom_key`.
+#[derive(Debug, Clone, uniffi::Record)]
+pub struct NotificationPayload {
+    pub alert: String,
+    #[uniffi(default = None)]
+    pub badge: Option<u32>,
+    #[uniffi(default = None)]
+    pub sound: Option<String>,
+    #[uniffi(default = None)]
+    pub category: Option<String>,
+    #[uniffi(default = None)]
+    pub thread_id: Option<String>,
+    #[uniffi(default = false)]
+    pub content_available: bool,
+    #[uniffi(default = None)]
+    pub custom_key: Option<String>,
+}
+
+/// APNs' answer to a notification.
+///
+/// # Fields
+///
+/// * `status` - The HTTP status APNs answered with.
+/// * `apns_id` - The `apns-id` of the notification.
+/// * `reason` - The reason APNs gave for rejecting the notification, if it did.
+#[derive(Debug, Clone, uniffi::Record)]
+pub struct SendResult {
+    pub status: u16,
+    pub apns_id: Option<String>,
+    pub reason: Option<String>,
+}
+
+/// An error from [`PushClient`], raised as an exception in foreign languages.
+///
+/// # Variants
+///
+/// * `Apns` - The client could not be built or the notification could not be sent, with the
+///   stable code and message of the underlying `ApnsError`.
+#[derive(Debug, uniffi::Error)]
+pub enum PushError {
+    Apns { code: String, message: String },
+}
+
+impl fmt::Display for PushError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PushError::Apns { code, message } => write!(f, "{}: {}", code, message),
+        }
+    }
+}
+
+impl std::error::Error for PushError {}
+
+impl From<ApnsError> for PushError {
+    fn from(e: ApnsError) -> Self {
+        PushError::Apns {
+            code: e.code().to_string(),
+            message: e.to_string(),
+        }
+    }
+}
+
+impl From<NotificationPayload> for ApnsPayload {
+    fn from(payload: NotificationPayload) -> Self {
+        ApnsPayload {
+            aps: Aps {
+                alert: payload.alert,
+                content_available: payload.content_available.then_some(true),
+                badge: Badge::from(payload.badge),
+                sound: Sound::from(payload.sound),
+                category: payload.category,
+                thread_id: payload.thread_id,
+                summary_arg: None,
+                summary_arg_count: None,
+                launch_image: None,
+                alert_fields: Default::default(),
+                extra_fields: Default::default(),
+            },
+            custom_key: payload.custom_key,
+            custom_fields: Default::default(),
+        }
+    }
+}
+
+/// An [`ApnsClient`] for foreign languages, sharing one HTTP/2 connection between sends.
+#[derive(uniffi::Object)]
+pub struct PushClient {
+    client: ApnsClient,
+}
+
+#[uniffi::export(async_runtime = "tokio")]
+impl PushClient {
+    /// Builds a client signing provider tokens with the auth key in `config`.
+    #[uniffi::constructor]
+    pub fn new(config: ClientConfig) -> Result<Arc<Self>, PushError> {
+        let environment = if config.sandbox {
+            Environment::Sandbox
+        } else {
+            Environment::Production
+        };
+        let client = ApnsClient::builder()
+            .auth_key_path(config.auth_key_path)
+            .team_id(config.team_id)
+            .key_id(config.key_id)
+            .environment(environment)
+            .build()?;
+        Ok(Arc::new(PushClient { client }))
+    }
+
+    /// Sends a notification to one device.
+    pub async fn send(
+        &self,
+        device_token: String,
+        topic: String,
+        payload: NotificationPayload,
+    ) -> Result<SendResult, PushError> {
+        let outcome = self
+            .client
+            .send(&device_token, &topic, &payload.into())
+            .await?;
+        let response = outcome.response;
+        Ok(SendResult {
+            status: response.status.as_u16(),
+            apns_id: response.apns_id,
+            reason: response.error.map(|reason| reason.to_string()),
+        })
+    }
+
+    /// Waits up to `timeout_ms` milliseconds for pending sends, then refuses new ones.
+    ///
+    /// Returns the number of sends that did not finish in time.
+    pub async fn shutdown(&self, timeout_ms: u64) -> u64 {
+        let report = self
+            .client
+            .shutdown(Duration::from_millis(timeout_ms))
+            .await;
+        report.dropped as u64
+    }
+}
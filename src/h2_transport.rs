@@ -0,0 +1,247 @@
+//! A [`Transport`] speaking HTTP/2 directly over `h2`, enabled with the `h2` feature.
+
+use crate::dry_run::PreparedRequest;
+use crate::environment::Environment;
+use crate::error::TransportError;
+use crate::transport::{Transport, TransportFuture};
+use bytes::{Bytes, BytesMut};
+use h2::client::SendRequest;
+use http::header::CONTENT_TYPE;
+use http::{HeaderValue, Method, Request, Response, Uri};
+use std::fmt;
+use std::future::Future;
+use std::time::Duration;
+use tokio::net::TcpStream;
+use tokio::sync::Mutex;
+use tokio_native_tls::TlsConnector;
+
+/// How long establishing a connection to APNs may take by default.
+pub const DEFAULT_H2_CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// How long a single request may take by default, from sending it to reading the whole
+/// response.
+pub const DEFAULT_H2_REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// A [`Transport`] holding one HTTP/2 connection to APNs, built on `h2` and tokio without
+/// reqwest or hyper.
+///
+/// The connection is opened on the first send, or up front with [`connect`](Self::connect),
+/// and shared by every request, each on its own stream. When APNs closes it, for example
+/// after a `GOAWAY`, the next send opens a new one; a request is only retried on the new
+/// connection if it was never sent on the old one. [`close`](Self::close) drops it.
+///
+/// Use it with a [`CoreClient`](crate::CoreClient):
+///
+/// # Example
+///
+/// ```rust,no_run
+/// use apnrs::{ApnsPayload, CoreClient, Credentials, Environment, H2Transport};
+/// use std::time::Duration;
+///
+/// # async fn run(payload: ApnsPayload) -> Result<(), apnrs::ApnsError> {
+/// let transport = H2Transport::new()?.request_timeout(Duration::from_secs(10));
+/// transport.connect(Environment::Sandbox).await?;
+///
+/// let credentials = Credentials::from_key_path("path/to/auth/key", "TEAM_ID", "KEY_ID")?;
+/// let client = CoreClient::new(transport, credentials).environment(Environment::Sandbox);
+/// let response = client.send("DEVICE_TOKEN", "com.example.app", &payload).await?;
+/// println!("{} {:?}", response.status, response.error);
+///
+/// client.transport().close().await;
+/// # Ok(())
+/// # }
+/// ```
+pub struct H2Transport {
+    tls: TlsConnector,
+    connect_timeout: Duration,
+    request_timeout: Duration,
+    connection: Mutex<Option<Connection>>,
+}
+
+/// An open connection and the authority it was opened to.
+struct Connection {
+    authority: String,
+    sender: SendRequest<Bytes>,
+}
+
+impl H2Transport {
+    /// Creates a transport connecting with the platform's TLS library.
+    ///
+    /// # Returns
+    ///
+    /// The transport, or a `TransportError` if the TLS library could not be set up.
+    pub fn new() -> Result<Self, TransportError> {
+        let tls = native_tls::TlsConnector::builder()
+            .request_alpns(&["h2"])
+            .build()
+            .map_err(TransportError::connect)?;
+        Ok(H2Transport {
+            tls: TlsConnector::from(tls),
+            connect_timeout: DEFAULT_H2_CONNECT_TIMEOUT,
+            request_timeout: DEFAULT_H2_REQUEST_TIMEOUT,
+            connection: Mutex::new(None),
+        })
+    }
+
+    /// Sets how long establishing a connection, including the TLS and HTTP/2 handshakes,
+    /// may take.
+    pub fn connect_timeout(mut self, timeout: Duration) -> Self {
+        self.connect_timeout = timeout;
+        self
+    }
+
+    /// Sets how long a single request may take, from sending it to reading the whole
+    /// response.
+    pub fn request_timeout(mut self, timeout: Duration) -> Self {
+        self.request_timeout = timeout;
+        self
+    }
+
+    /// Opens the connection to `environment` ahead of the first send, replacing any open
+    /// connection to another host.
+    ///
+    /// # Returns
+    ///
+    /// `Ok(())` once the connection is ready, or a `TransportError` if it could not be
+    /// established.
+    pub async fn connect(&self, environment: Environment) -> Result<(), TransportError> {
+        let uri: Uri = environment
+            .base_url()
+            .parse()
+            .map_err(TransportError::connect)?;
+        self.sender(&uri).await.map(drop)
+    }
+
+    /// Drops the connection. Requests in flight finish, and the next send opens a new one.
+    pub async fn close(&self) {
+        self.connection.lock().await.take();
+    }
+
+    /// Returns a handle to the connection to `uri`'s host, opening it if needed.
+    async fn sender(&self, uri: &Uri) -> Result<SendRequest<Bytes>, TransportError> {
+        let authority = uri
+            .authority()
+            .ok_or_else(|| TransportError::connect(format!("{} has no host", uri)))?;
+        let mut connection = self.connection.lock().await;
+        if let Some(open) = connection.as_ref() {
+            if open.authority == authority.as_str() {
+                return Ok(open.sender.clone());
+            }
+        }
+        let host = authority.host();
+        let port = authority.port_u16().unwrap_or(443);
+        let sender = within(self.connect_timeout, TransportError::connect, async {
+            let tcp = TcpStream::connect((host, port))
+                .await
+                .map_err(TransportError::connect)?;
+            let tls = self
+                .tls
+                .connect(host, tcp)
+                .await
+                .map_err(TransportError::connect)?;
+            let (sender, driver) = h2::client::handshake(tls)
+                .await
+                .map_err(TransportError::connect)?;
+            // The connection is driven in the background until APNs or `close` ends it
+            tokio::spawn(async move {
+                let _ = driver.await;
+            });
+            Ok(sender)
+        })
+        .await?;
+        *connection = Some(Connection {
+            authority: authority.to_string(),
+            sender: sender.clone(),
+        });
+        Ok(sender)
+    }
+
+    /// Forgets the connection to `authority`, if it is still the current one.
+    async fn discard(&self, authority: &str) {
+        let mut connection = self.connection.lock().await;
+        if connection
+            .as_ref()
+            .is_some_and(|open| open.authority == authority)
+        {
+            *connection = None;
+        }
+    }
+
+    async fn post(&self, request: PreparedRequest) -> Result<Response<Bytes>, TransportError> {
+        let uri: Uri = request.url.parse().map_err(TransportError::new)?;
+        let authority = uri.authority().map(|a| a.to_string()).unwrap_or_default();
+
+        // A connection APNs closed is only noticed once a stream is requested on it, before
+        // anything was sent, so the request can safely go out on a fresh one
+        let mut sender = match self.sender(&uri).await?.ready().await {
+            Ok(sender) => sender,
+            Err(_) => {
+                self.discard(&authority).await;
+                self.sender(&uri)
+                    .await?
+                    .ready()
+                    .await
+                    .map_err(TransportError::connect)?
+            }
+        };
+
+        let mut head = Request::builder()
+            .method(Method::POST)
+            .uri(uri)
+            .body(())
+            .map_err(TransportError::new)?;
+        *head.headers_mut() = request.headers;
+        head.headers_mut()
+            .insert(CONTENT_TYPE, HeaderValue::from_static("application/json"));
+
+        let result = within(self.request_timeout, TransportError::timeout, async {
+            let (response, mut stream) = sender
+                .send_request(head, false)
+                .map_err(TransportError::from)?;
+            stream
+                .send_data(Bytes::from(request.body), true)
+                .map_err(TransportError::from)?;
+            let (parts, mut body) = response.await.map_err(TransportError::from)?.into_parts();
+            let mut buffer = BytesMut::new();
+            while let Some(chunk) = body.data().await {
+                let chunk = chunk.map_err(TransportError::from)?;
+                let _ = body.flow_control().release_capacity(chunk.len());
+                buffer.extend_from_slice(&chunk);
+            }
+            Ok(Response::from_parts(parts, buffer.freeze()))
+        })
+        .await;
+        if let Err(e) = &result {
+            if !e.is_timeout() {
+                self.discard(&authority).await;
+            }
+        }
+        result
+    }
+}
+
+/// Runs `future`, failing with an error made by `error` if it takes longer than `timeout`.
+async fn within<T>(
+    timeout: Duration,
+    error: impl FnOnce(String) -> TransportError,
+    future: impl Future<Output = Result<T, TransportError>>,
+) -> Result<T, TransportError> {
+    tokio::time::timeout(timeout, future)
+        .await
+        .unwrap_or_else(|_| Err(error(format!("timed out after {:?}", timeout))))
+}
+
+impl Transport for H2Transport {
+    fn send(&self, request: PreparedRequest) -> TransportFuture<'_> {
+        Box::pin(self.post(request))
+    }
+}
+
+impl fmt::Debug for H2Transport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("H2Transport")
+            .field("connect_timeout", &self.connect_timeout)
+            .field("request_timeout", &self.request_timeout)
+            .finish_non_exhaustive()
+    }
+}
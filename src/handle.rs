@@ -0,0 +1,140 @@
+//! A cloneable handle feeding notifications to a single background dispatch task.
+
+use crate::sender::{lane, Job, LaneReceiver, Queue, Receipt, ReceiptId};
+use crate::{ApnsClient, ApnsError, PushRequest, SendOutcome};
+use futures_util::stream::{self, StreamExt};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use tokio::sync::oneshot;
+
+/// How many notifications the dispatch task sends at once.
+const MAX_IN_FLIGHT: usize = 16;
+
+/// A cheap, cloneable handle to a background task that sends notifications through one
+/// [`ApnsClient`], like an `mpsc::Sender`.
+///
+/// Clones share the same queue and task, so a handle can be passed to every task or thread
+/// that produces notifications while the connection and credentials stay with the task. The
+/// task sends up to 16 notifications at once and exits once every handle is dropped and the
+/// queue is drained. Unlike an [`ApnsSender`](crate::ApnsSender), failed notifications are
+/// not retried.
+///
+/// # Example
+///
+/// ```rust,no_run
+/// use apnrs::{ApnsClient, ApnsSenderHandle, PushRequest};
+///
+/// # async fn run(client: ApnsClient, requests: Vec<PushRequest>) -> Result<(), apnrs::ApnsError> {
+/// let handle = ApnsSenderHandle::spawn(client, 1024);
+///
+/// for request in requests {
+///     let handle = handle.clone();
+///     tokio::spawn(async move {
+///         if let Err(e) = handle.send(request).await {
+///             eprintln!("Error sending notification: {}", e);
+///         }
+///     });
+/// }
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone)]
+pub struct ApnsSenderHandle {
+    client: ApnsClient,
+    queue: Queue,
+    next_id: Arc<AtomicU64>,
+}
+
+impl ApnsSenderHandle {
+    /// Spawns the dispatch task for `client`, with room for `capacity` notifications of
+    /// each [`TrafficClass`](crate::TrafficClass) waiting to be sent; the highest class
+    /// waiting is sent first.
+    ///
+    /// # Panics
+    ///
+    /// Panics if called outside of a tokio runtime.
+    pub fn spawn(client: ApnsClient, capacity: usize) -> Self {
+        let (tx, rx) = lane(capacity.max(1));
+        tokio::spawn(run_dispatcher(client.clone(), rx));
+        ApnsSenderHandle {
+            client,
+            queue: Queue::Shared(tx),
+            next_id: Arc::default(),
+        }
+    }
+
+    /// Adds a notification to the queue, waiting for room if the queue is full.
+    ///
+    /// # Returns
+    ///
+    /// A [`Receipt`] that resolves once the notification has been sent,
+    /// `ApnsError::QueueClosed` if the dispatch task has stopped, or
+    /// `ApnsError::ClientShutDown` if the client is shutting down.
+    pub async fn enqueue(&self, mut request: PushRequest) -> Result<Receipt, ApnsError> {
+        let pending = self
+            .client
+            .lifecycle()
+            .start()
+            .ok_or(ApnsError::ClientShutDown)?;
+        let apns_id = request.assign_apns_id().to_string();
+        let (reply, rx) = oneshot::channel();
+        let job = Job {
+            request,
+            reply,
+            pending,
+            #[cfg(feature = "journal")]
+            entry: None,
+        };
+        self.queue.push(&self.client, job).await?;
+        Ok(Receipt {
+            id: ReceiptId(self.next_id.fetch_add(1, Ordering::Relaxed)),
+            apns_id,
+            rx,
+        })
+    }
+
+    /// Adds a notification to the queue and waits until it has been sent.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing either the outcome of the request or an `ApnsError`.
+    pub async fn send(&self, request: PushRequest) -> Result<SendOutcome, ApnsError> {
+        self.enqueue(request).await?.await
+    }
+}
+
+/// Sends the jobs from `rx` until every handle is dropped and the queue is drained.
+async fn run_dispatcher(client: ApnsClient, rx: LaneReceiver) {
+    let jobs = stream::unfold(
+        rx,
+        |mut rx| async move { rx.recv().await.map(|job| (job, rx)) },
+    );
+    jobs.for_each_concurrent(MAX_IN_FLIGHT, |mut job| {
+        let client = &client;
+        async move {
+            client.stats_recorder().dequeued();
+            let request = &job.request;
+            let result = if client.lifecycle().is_aborted() {
+                Err(ApnsError::ClientShutDown)
+            } else if client.token_store().is_invalid(&request.device_token) {
+                Err(ApnsError::InvalidToken)
+            } else {
+                let send = client.dispatch(
+                    &request.device_token,
+                    &request.topic,
+                    None,
+                    None,
+                    &request.payload,
+                    &request.options,
+                );
+                request.within_deadline(send).await
+            };
+            job.settle();
+
+            // The caller may have dropped the receipt; that is not an error
+            let _ = job.reply.send(result);
+            drop(job.pending);
+        }
+    })
+    .await;
+}
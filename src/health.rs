@@ -0,0 +1,34 @@
+//! Readiness probes of the connection to APNs and the cached provider token.
+
+use crate::environment::Environment;
+use serde::{Deserialize, Serialize};
+use std::net::SocketAddr;
+use std::time::Duration;
+
+/// What [`ApnsClient::health_check`](crate::ApnsClient::health_check) found.
+///
+/// # Fields
+///
+/// * `environment` - The environment the client sends to by default.
+/// * `endpoint` - The base URL of that environment.
+/// * `remote_addr` - The address of the APNs server the client is connected to, if known.
+/// * `http_version` - The HTTP version the connection speaks, such as `HTTP/2.0`.
+/// * `latency` - How long APNs took to answer the probe.
+/// * `token_age` - How long ago the cached provider token was signed, or `None` if the
+///   client authenticates with a certificate alone.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HealthReport {
+    pub environment: Environment,
+    pub endpoint: String,
+    pub remote_addr: Option<SocketAddr>,
+    pub http_version: String,
+    pub latency: Duration,
+    pub token_age: Option<Duration>,
+}
+
+impl HealthReport {
+    /// Returns `true` if the connection speaks HTTP/2, which APNs requires for sends.
+    pub fn is_http2(&self) -> bool {
+        self.http_version == "HTTP/2.0"
+    }
+}
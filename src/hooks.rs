@@ -0,0 +1,157 @@
+//! Hooks run around every request the client sends, for auditing, payload mutation,
+//! authorization, or custom metrics.
+
+use crate::privacy::RedactedHeaders;
+use crate::{ApnsError, ApnsResponse, Environment, PushType};
+use bytes::Bytes;
+use http::HeaderMap;
+use serde_json::Value;
+use std::fmt;
+use std::time::Duration;
+
+/// Where a request is going, as seen by a [`SendHook`].
+///
+/// # Fields
+///
+/// * `device_token` - The device token of the target device.
+/// * `topic` - The topic the notification is sent under.
+/// * `push_type` - The push type of the notification, if the send set one.
+/// * `environment` - The APNs environment the request is sent to.
+#[derive(Debug, Clone, Copy)]
+pub struct SendContext<'a> {
+    pub device_token: &'a str,
+    pub topic: &'a str,
+    pub push_type: Option<PushType>,
+    pub environment: Environment,
+}
+
+/// A request about to be sent to APNs, which [`SendHook::before_send`] may change.
+///
+/// # Fields
+///
+/// * `headers` - The request headers, including `authorization` and `apns-topic`.
+/// * `body` - The JSON payload.
+#[derive(Clone)]
+pub struct OutgoingRequest {
+    pub headers: HeaderMap,
+    pub body: Bytes,
+}
+
+impl fmt::Debug for OutgoingRequest {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("OutgoingRequest")
+            .field("headers", &RedactedHeaders(&self.headers))
+            .field("body", &String::from_utf8_lossy(&self.body))
+            .finish()
+    }
+}
+
+impl OutgoingRequest {
+    /// Parses the body as JSON.
+    pub fn payload(&self) -> Result<Value, ApnsError> {
+        Ok(serde_json::from_slice(&self.body)?)
+    }
+
+    /// Replaces the body with `payload`.
+    ///
+    /// The size limit was checked before the hook ran, so a hook that grows the payload is
+    /// responsible for keeping it within the limit.
+    pub fn set_payload(&mut self, payload: &Value) -> Result<(), ApnsError> {
+        self.body = Bytes::from(serde_json::to_vec(payload)?);
+        Ok(())
+    }
+}
+
+/// Runs around every request an [`ApnsClient`](crate::ApnsClient) sends, including retries
+/// and those made by senders, batch APIs, and dry runs.
+///
+/// Register hooks with [`ApnsClientBuilder::hook`](crate::ApnsClientBuilder::hook); they run
+/// in the order they were registered. Both methods do nothing by default, so a hook only
+/// implements the ones it needs. Hooks run on the task sending the request and should
+/// return quickly.
+///
+/// # Example
+///
+/// ```rust
+/// use apnrs::{ApnsError, OutgoingRequest, SendContext, SendHook};
+///
+/// /// Only lets tenants send under their own bundle IDs.
+/// struct TenantGuard {
+///     allowed: Vec<String>,
+/// }
+///
+/// impl SendHook for TenantGuard {
+///     fn before_send(
+///         &self,
+///         context: &SendContext<'_>,
+///         _request: &mut OutgoingRequest,
+///     ) -> Result<(), ApnsError> {
+///         if self.allowed.iter().any(|topic| topic == context.topic) {
+///             Ok(())
+///         } else {
+///             Err(ApnsError::Blocked(format!("{} is not allowed", context.topic)))
+///         }
+///     }
+/// }
+/// ```
+pub trait SendHook: Send + Sync {
+    /// Runs before the request is sent, and may change its headers or payload.
+    ///
+    /// # Returns
+    ///
+    /// `Ok(())` to send the request, or an error, typically `ApnsError::Blocked`, that the
+    /// send fails with instead.
+    fn before_send(
+        &self,
+        context: &SendContext<'_>,
+        request: &mut OutgoingRequest,
+    ) -> Result<(), ApnsError> {
+        let _ = (context, request);
+        Ok(())
+    }
+
+    /// Runs once APNs has answered the request or it has failed, with how long that took.
+    fn after_send(
+        &self,
+        context: &SendContext<'_>,
+        result: Result<&ApnsResponse, &ApnsError>,
+        latency: Duration,
+    ) {
+        let _ = (context, result, latency);
+    }
+}
+
+/// Adapts a closure registered with
+/// [`ApnsClientBuilder::before_send`](crate::ApnsClientBuilder::before_send).
+pub(crate) struct BeforeSend<F>(pub(crate) F);
+
+impl<F> SendHook for BeforeSend<F>
+where
+    F: Fn(&SendContext<'_>, &mut OutgoingRequest) -> Result<(), ApnsError> + Send + Sync,
+{
+    fn before_send(
+        &self,
+        context: &SendContext<'_>,
+        request: &mut OutgoingRequest,
+    ) -> Result<(), ApnsError> {
+        (self.0)(context, request)
+    }
+}
+
+/// Adapts a closure registered with
+/// [`ApnsClientBuilder::after_send`](crate::ApnsClientBuilder::after_send).
+pub(crate) struct AfterSend<F>(pub(crate) F);
+
+impl<F> SendHook for AfterSend<F>
+where
+    F: Fn(&SendContext<'_>, Result<&ApnsResponse, &ApnsError>, Duration) + Send + Sync,
+{
+    fn after_send(
+        &self,
+        context: &SendContext<'_>,
+        result: Result<&ApnsResponse, &ApnsError>,
+        latency: Duration,
+    ) {
+        (self.0)(context, result, latency)
+    }
+}
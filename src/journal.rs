@@ -0,0 +1,400 @@
+//! A disk-backed journal of queued notifications, enabled with the `journal` feature.
+
+use crate::error::ApnsError;
+use crate::PushRequest;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufRead, BufReader, Write};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::time::SystemTime;
+
+/// The notifications left in a journal when it is opened, with their entries and the time
+/// they are scheduled for.
+pub(crate) type Recovered = Vec<(JournalEntry, PushRequest, Option<SystemTime>)>;
+
+/// One line of the journal.
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "op", rename_all = "snake_case")]
+enum Record {
+    /// A notification was accepted into the queue, or scheduled for `deliver_at`.
+    Enqueue {
+        id: u64,
+        request: Box<PushRequest>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        deliver_at: Option<SystemTime>,
+    },
+    /// A scheduled notification was moved to `deliver_at`.
+    Reschedule { id: u64, deliver_at: SystemTime },
+    /// A notification needs no further delivery attempts.
+    Done { id: u64 },
+}
+
+/// An append-only log of the notifications in a sender's queue, so those not yet delivered
+/// when the process stops are resumed when it starts again.
+///
+/// Each enqueued notification appends a line, and so does each one a worker has finished
+/// with; the file is compacted when it is opened and emptied whenever the queue drains.
+pub(crate) struct Journal {
+    state: Mutex<State>,
+}
+
+struct State {
+    file: File,
+    /// The length of the file up to its last complete line.
+    len: u64,
+    /// Whether the file may end in part of a line, because a write failed and removing what
+    /// it wrote failed too, so the next line must start on a line of its own.
+    torn: bool,
+    next_id: u64,
+    live: usize,
+    /// How many notifications are queued in memory only, because the journal could not be
+    /// written when they were accepted.
+    unwritten: usize,
+}
+
+/// A notification recorded in the journal, kept there until it is completed.
+pub(crate) struct JournalEntry {
+    journal: Arc<Journal>,
+    /// The notification's ID in the journal, or `None` if it is queued in memory only.
+    id: Option<u64>,
+}
+
+impl Journal {
+    /// Opens the journal at `path`, creating it if it does not exist.
+    ///
+    /// # Returns
+    ///
+    /// The journal and the notifications it holds that were never completed, in the order
+    /// they were enqueued, or an `ApnsError` if the file cannot be read or rewritten.
+    pub(crate) fn open(path: &Path) -> Result<(Arc<Journal>, Recovered), ApnsError> {
+        let mut pending = BTreeMap::new();
+        let mut next_id = 0;
+        match File::open(path) {
+            Ok(file) => {
+                for line in BufReader::new(file).lines() {
+                    // A crash mid-write leaves a torn last line; skip it
+                    let Ok(record) = serde_json::from_str::<Record>(&line?) else {
+                        continue;
+                    };
+                    match record {
+                        Record::Enqueue {
+                            id,
+                            request,
+                            deliver_at,
+                        } => {
+                            next_id = next_id.max(id + 1);
+                            pending.insert(id, (*request, deliver_at));
+                        }
+                        Record::Reschedule { id, deliver_at } => {
+                            if let Some((_, scheduled)) = pending.get_mut(&id) {
+                                *scheduled = Some(deliver_at);
+                            }
+                        }
+                        Record::Done { id } => {
+                            pending.remove(&id);
+                        }
+                    }
+                }
+            }
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+            Err(e) => return Err(e.into()),
+        }
+
+        // Rewrite the journal with only the pending notifications, then swap it in
+        let mut compacted = path.as_os_str().to_owned();
+        compacted.push(".tmp");
+        let mut file = File::create(&compacted)?;
+        for (id, (request, deliver_at)) in &pending {
+            let record = Record::Enqueue {
+                id: *id,
+                request: Box::new(request.clone()),
+                deliver_at: *deliver_at,
+            };
+            writeln!(file, "{}", serde_json::to_string(&record)?)?;
+        }
+        file.sync_all()?;
+        let len = file.metadata()?.len();
+        fs::rename(&compacted, path)?;
+
+        let journal = Arc::new(Journal {
+            state: Mutex::new(State {
+                file: OpenOptions::new().append(true).open(path)?,
+                len,
+                torn: false,
+                next_id,
+                live: pending.len(),
+                unwritten: 0,
+            }),
+        });
+        let recovered = pending
+            .into_iter()
+            .map(|(id, (request, deliver_at))| {
+                let entry = JournalEntry {
+                    journal: journal.clone(),
+                    id: Some(id),
+                };
+                (entry, request, deliver_at)
+            })
+            .collect();
+        Ok((journal, recovered))
+    }
+
+    /// Records a notification accepted into the queue, or scheduled for `deliver_at`.
+    ///
+    /// # Returns
+    ///
+    /// The entry to complete once the notification is delivered, or an `ApnsError` if it
+    /// could not be written.
+    pub(crate) fn append(
+        self: &Arc<Self>,
+        request: &PushRequest,
+        deliver_at: Option<SystemTime>,
+    ) -> Result<JournalEntry, ApnsError> {
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        let id = state.next_id;
+        state.write(&Record::Enqueue {
+            id,
+            request: Box::new(request.clone()),
+            deliver_at,
+        })?;
+        state.next_id += 1;
+        state.live += 1;
+        Ok(JournalEntry {
+            journal: self.clone(),
+            id: Some(id),
+        })
+    }
+
+    /// Accounts for a notification queued in memory only because it could not be written,
+    /// unless `limit` such notifications are already queued.
+    ///
+    /// # Returns
+    ///
+    /// The entry to complete once the notification is delivered, or `None` if the limit is
+    /// reached.
+    pub(crate) fn hold_unwritten(self: &Arc<Self>, limit: usize) -> Option<JournalEntry> {
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        if state.unwritten >= limit {
+            return None;
+        }
+        state.unwritten += 1;
+        Some(JournalEntry {
+            journal: self.clone(),
+            id: None,
+        })
+    }
+}
+
+impl State {
+    /// Appends a record as one line.
+    ///
+    /// A write that fails partway is cut off again, so the next record is not glued onto
+    /// the torn line and lost with it when the journal is read back.
+    fn write(&mut self, record: &Record) -> io::Result<()> {
+        let mut line = format!("{}\n", serde_json::to_string(record)?);
+        if self.torn {
+            line.insert(0, '\n');
+        }
+        match self.file.write_all(line.as_bytes()) {
+            Ok(()) => {
+                self.len += line.len() as u64;
+                self.torn = false;
+                Ok(())
+            }
+            Err(e) => {
+                self.torn = self.truncate(self.len).is_err();
+                Err(e)
+            }
+        }
+    }
+
+    /// Cuts the file back to `len` bytes.
+    fn truncate(&mut self, len: u64) -> io::Result<()> {
+        self.file.set_len(len)?;
+        self.len = len;
+        self.torn = false;
+        Ok(())
+    }
+}
+
+impl JournalEntry {
+    /// Records that the scheduled notification is now due at `deliver_at`.
+    pub(crate) fn reschedule(&self, deliver_at: SystemTime) {
+        let Some(id) = self.id else { return };
+        let mut state = self.journal.state.lock().unwrap_or_else(|e| e.into_inner());
+        let written = state.write(&Record::Reschedule { id, deliver_at });
+        // A lost write only means the notification is sent at its old time after a restart
+        if let Err(_e) = written {
+            #[cfg(feature = "tracing")]
+            tracing::warn!(error = %_e, "failed to reschedule a notification in the journal");
+            #[cfg(feature = "logging")]
+            log::warn!("failed to reschedule a notification in the journal: {}", _e);
+        }
+    }
+
+    /// Removes the notification from the journal, so it is not resumed after a restart.
+    pub(crate) fn complete(self) {
+        // A notification queued in memory only frees its slot when the entry is dropped
+        let Some(id) = self.id else { return };
+        let mut state = self.journal.state.lock().unwrap_or_else(|e| e.into_inner());
+        state.live -= 1;
+        // Start over once nothing is pending, so the file does not grow without bound
+        let written = if state.live == 0 {
+            state.truncate(0)
+        } else {
+            state.write(&Record::Done { id })
+        };
+        // A lost write only means the notification is sent again after a restart
+        if let Err(_e) = written {
+            #[cfg(feature = "tracing")]
+            tracing::warn!(error = %_e, "failed to complete a notification in the journal");
+            #[cfg(feature = "logging")]
+            log::warn!("failed to complete a notification in the journal: {}", _e);
+        }
+    }
+}
+
+impl Drop for JournalEntry {
+    /// Frees the slot of a notification queued in memory only, whether it was completed or
+    /// dropped without being settled, such as by an aborted worker.
+    fn drop(&mut self) {
+        if self.id.is_none() {
+            let mut state = self.journal.state.lock().unwrap_or_else(|e| e.into_inner());
+            state.unwritten -= 1;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    /// A journal path unique to the test, removed when dropped.
+    struct TempPath(PathBuf);
+
+    impl TempPath {
+        fn new(name: &str) -> Self {
+            let path =
+                std::env::temp_dir().join(format!("apnrs-journal-{}-{}", std::process::id(), name));
+            let _ = fs::remove_file(&path);
+            TempPath(path)
+        }
+    }
+
+    impl Drop for TempPath {
+        fn drop(&mut self) {
+            let _ = fs::remove_file(&self.0);
+        }
+    }
+
+    fn request(token: &str) -> PushRequest {
+        let payload = serde_json::json!({ "aps": { "alert": "Hello" } });
+        PushRequest::new(token, "com.example.app", payload.try_into().unwrap())
+    }
+
+    fn tokens(recovered: &Recovered) -> Vec<(&str, Option<SystemTime>)> {
+        recovered
+            .iter()
+            .map(|(_, request, deliver_at)| (request.device_token.as_str(), *deliver_at))
+            .collect()
+    }
+
+    fn unwritten(journal: &Journal) -> usize {
+        journal.state.lock().unwrap().unwritten
+    }
+
+    #[test]
+    fn unwritten_slots_are_freed_when_entries_are_dropped() {
+        let path = TempPath::new("unwritten");
+        let (journal, _) = Journal::open(&path.0).unwrap();
+
+        let first = journal.hold_unwritten(2).unwrap();
+        let second = journal.hold_unwritten(2).unwrap();
+        assert!(journal.hold_unwritten(2).is_none());
+
+        // Completed and dropped entries, such as those of an aborted worker, free their slots
+        first.complete();
+        drop(second);
+        assert_eq!(unwritten(&journal), 0);
+
+        let held: Vec<_> = (0..2).map(|_| journal.hold_unwritten(2).unwrap()).collect();
+        assert!(journal.hold_unwritten(2).is_none());
+        drop(held);
+        assert!(journal.hold_unwritten(2).is_some());
+    }
+
+    #[test]
+    fn replays_enqueues_reschedules_and_completions() {
+        let path = TempPath::new("replay");
+        let later = SystemTime::UNIX_EPOCH + std::time::Duration::from_secs(2_000_000_000);
+        let (journal, recovered) = Journal::open(&path.0).unwrap();
+        assert!(recovered.is_empty());
+
+        let sent = journal.append(&request("sent"), None).unwrap();
+        let scheduled = journal
+            .append(&request("scheduled"), Some(SystemTime::UNIX_EPOCH))
+            .unwrap();
+        let pending = journal.append(&request("pending"), None).unwrap();
+        scheduled.reschedule(later);
+        sent.complete();
+        drop((scheduled, pending, journal));
+
+        let (journal, recovered) = Journal::open(&path.0).unwrap();
+        assert_eq!(
+            tokens(&recovered),
+            [("scheduled", Some(later)), ("pending", None)]
+        );
+
+        // Recovered entries keep their IDs, and new ones do not reuse them
+        let next = journal.append(&request("next"), None).unwrap();
+        assert_eq!(next.id, Some(3));
+        drop(recovered);
+        let (_, recovered) = Journal::open(&path.0).unwrap();
+        assert_eq!(tokens(&recovered).len(), 3);
+    }
+
+    #[test]
+    fn skips_a_torn_last_line() {
+        let path = TempPath::new("torn-tail");
+        let (journal, _) = Journal::open(&path.0).unwrap();
+        let entry = journal.append(&request("kept"), None).unwrap();
+        drop((entry, journal));
+        let mut file = OpenOptions::new().append(true).open(&path.0).unwrap();
+        file.write_all(br#"{"op":"enqueue","id":1,"req"#).unwrap();
+
+        let (journal, recovered) = Journal::open(&path.0).unwrap();
+        assert_eq!(tokens(&recovered), [("kept", None)]);
+        let entry = journal.append(&request("after"), None).unwrap();
+        drop((entry, recovered, journal));
+
+        let (_, recovered) = Journal::open(&path.0).unwrap();
+        assert_eq!(tokens(&recovered), [("kept", None), ("after", None)]);
+    }
+
+    #[test]
+    fn starts_a_new_line_after_a_failed_write() {
+        let path = TempPath::new("failed-write");
+        let (journal, _) = Journal::open(&path.0).unwrap();
+        let first = journal.append(&request("first"), None).unwrap();
+
+        // A write that fails on a handle that cannot be cut back either leaves its part of a
+        // line behind
+        let writable = {
+            let mut state = journal.state.lock().unwrap();
+            std::mem::replace(&mut state.file, File::open(&path.0).unwrap())
+        };
+        assert!(journal.append(&request("lost"), None).is_err());
+        assert!(journal.state.lock().unwrap().torn);
+        let mut file = OpenOptions::new().append(true).open(&path.0).unwrap();
+        file.write_all(br#"{"op":"enqueue","id":1,"req"#).unwrap();
+        journal.state.lock().unwrap().file = writable;
+
+        let second = journal.append(&request("second"), None).unwrap();
+        drop((first, second, journal));
+        let (_, recovered) = Journal::open(&path.0).unwrap();
+        assert_eq!(tokens(&recovered), [("first", None), ("second", None)]);
+    }
+}
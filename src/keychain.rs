@@ -0,0 +1,113 @@
+//! Loading auth keys and client certificates from the macOS Keychain.
+
+use crate::error::ApnsError;
+use openssl::bn::{BigNum, BigNumContext};
+use openssl::ec::{EcGroup, EcKey, EcPoint};
+use openssl::nid::Nid;
+use openssl::pkcs12::Pkcs12;
+use openssl::pkey::{PKey, Private};
+use openssl::rsa::Rsa;
+use openssl::x509::X509;
+use security_framework::item::{ItemClass, ItemSearchOptions, Reference, SearchResult};
+use security_framework::key::SecKey;
+
+/// The length of a P-256 private key exported by the Keychain: the uncompressed public
+/// point (`04 || X || Y`) followed by the private scalar.
+const P256_EXTERNAL_LEN: usize = 65 + 32;
+
+/// The password of the PKCS#12 archive a Keychain identity is repackaged into. The archive
+/// only lives in memory between the Keychain and the TLS backend.
+const ARCHIVE_PASSWORD: &str = "apnrs-keychain";
+
+/// Reads the private key labelled `label` from the Keychain, such as an auth key imported
+/// with `security import AuthKey_ABC123.p8`, as PKCS#8 PEM.
+pub(crate) fn auth_key_pem(label: &str) -> Result<Vec<u8>, ApnsError> {
+    let key = find(ItemClass::key(), label, |reference| match reference {
+        Reference::Key(key) => Some(key),
+        _ => None,
+    })?;
+    let key = private_key(&key, label)?;
+    key.private_key_to_pem_pkcs8()
+        .map_err(|e| invalid(label, e))
+}
+
+/// Reads the identity (certificate and private key) labelled `label` from the Keychain,
+/// as a PKCS#12 archive and its password.
+pub(crate) fn identity_pkcs12(label: &str) -> Result<(Vec<u8>, String), ApnsError> {
+    let identity = find(ItemClass::identity(), label, |reference| match reference {
+        Reference::Identity(identity) => Some(identity),
+        _ => None,
+    })?;
+    let keychain_error = |e: security_framework::base::Error| {
+        ApnsError::Config(format!(
+            "cannot read keychain identity \"{}\": {}",
+            label, e
+        ))
+    };
+    let certificate = identity.certificate().map_err(keychain_error)?;
+    let key = identity.private_key().map_err(keychain_error)?;
+
+    let certificate = X509::from_der(&certificate.to_der()).map_err(|e| invalid(label, e))?;
+    let key = private_key(&key, label)?;
+    let archive = Pkcs12::builder()
+        .name(label)
+        .pkey(&key)
+        .cert(&certificate)
+        .build2(ARCHIVE_PASSWORD)
+        .and_then(|archive| archive.to_der())
+        .map_err(|e| invalid(label, e))?;
+    Ok((archive, ARCHIVE_PASSWORD.to_string()))
+}
+
+/// Finds the single item of `class` labelled `label`.
+fn find<T>(
+    class: ItemClass,
+    label: &str,
+    select: impl Fn(Reference) -> Option<T>,
+) -> Result<T, ApnsError> {
+    let results = ItemSearchOptions::new()
+        .class(class)
+        .label(label)
+        .load_refs(true)
+        .search()
+        .map_err(|e| ApnsError::Config(format!("keychain item \"{}\" not found: {}", label, e)))?;
+    results
+        .into_iter()
+        .find_map(|result| match result {
+            SearchResult::Ref(reference) => select(reference),
+            _ => None,
+        })
+        .ok_or_else(|| ApnsError::Config(format!("keychain item \"{}\" not found", label)))
+}
+
+/// Exports a Keychain private key, which must have been imported as extractable.
+///
+/// The Keychain exports EC keys in ANSI X9.63 form and RSA keys as PKCS#1 DER.
+fn private_key(key: &SecKey, label: &str) -> Result<PKey<Private>, ApnsError> {
+    let data = key.external_representation().ok_or_else(|| {
+        ApnsError::Config(format!(
+            "keychain key \"{}\" cannot be exported; import it as extractable",
+            label
+        ))
+    })?;
+    let data = data.bytes();
+    let key = if data.len() == P256_EXTERNAL_LEN && data[0] == 0x04 {
+        p256_key(data).and_then(PKey::from_ec_key)
+    } else {
+        Rsa::private_key_from_der(data).and_then(PKey::from_rsa)
+    };
+    key.map_err(|e| invalid(label, e))
+}
+
+/// Builds a P-256 key from its X9.63 export.
+fn p256_key(data: &[u8]) -> Result<EcKey<Private>, openssl::error::ErrorStack> {
+    let group = EcGroup::from_curve_name(Nid::X9_62_PRIME256V1)?;
+    let mut ctx = BigNumContext::new()?;
+    let public = EcPoint::from_bytes(&group, &data[..65], &mut ctx)?;
+    let private = BigNum::from_slice(&data[65..])?;
+    EcKey::from_private_components(&group, &private, &public)
+}
+
+fn invalid(label: &str, e: openssl::error::ErrorStack) -> ApnsError {
+    ApnsError::Config(format!("invalid keychain item \"{}\": {}", label, e))
+}
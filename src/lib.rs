@@ -10,20 +10,26 @@
 //!
 //! ```rust,no_run
 //! extern crate apnrs;
-//! use apnrs::{send_push_notification, ApnsPayload, Aps};
+//! use apnrs::{send_push_notification, ApnsPayload, Aps, Badge, Sound};
 //!
 //! #[tokio::main]
 //! async fn main() {
 //!     let payload = ApnsPayload {
 //!         aps: Aps {
 //!             alert: "Hello, world!".to_string(),
-//!             content_available: 1,
-//!             badge: Some(1),
-//!             sound: Some("default".to_string()),
+//!             content_available: None,
+//!             badge: Badge::Set(1),
+//!             sound: Sound::Default,
 //!             category: None,
 //!             thread_id: None,
+//!             summary_arg: None,
+//!             summary_arg_count: None,
+//!             launch_image: None,
+//!             alert_fields: Default::default(),
+//!             extra_fields: Default::default(),
 //!         },
 //!         custom_key: Some("custom_value".to_string()),
+//!         custom_fields: Default::default(),
 //!     };
 //!
 //!     let response = send_push_notification(
@@ -48,19 +54,450 @@
 //! * [`ApnsPayload`](struct.ApnsPayload.html) - Represents the entire payload sent to the APNs.
 //! * [`Aps`](struct.Aps.html) - Represents the APNs (Apple Push Notification service) payload.
 //! * [`Claims`](struct.Claims.html) - Represents the claims used for generating the JWT token.
+//! * [`ApnsClient`](struct.ApnsClient.html) - A reusable client holding credentials and a pooled connection.
+//! * [`ApnsSender`](struct.ApnsSender.html) - A background dispatcher delivering queued notifications with a worker pool.
+//! * [`ApnsSenderHandle`](struct.ApnsSenderHandle.html) - A cloneable handle feeding notifications to one background dispatch task, for many producer tasks.
+//! * [`ApnsResponse`](struct.ApnsResponse.html) - APNs' answer to a notification, with its status, `apns-id`, and rejection reason parsed.
+//! * [`PayloadPreview`](struct.PayloadPreview.html) - The exact JSON a payload is sent as, with its byte count and the limit for its push type.
+//! * [`PreparedPayload`](struct.PreparedPayload.html) - A payload serialized and size-checked once, shared by every send of a fan-out.
+//! * [`FanOut`](struct.FanOut.html) - Sends one prepared payload to a stream of device tokens, pulling tokens only as fast as the concurrency and rate limits allow.
+//! * [`FanOutResults`](struct.FanOutResults.html) - The results of a fan-out as a stream, owning the tasks of its requests in flight so dropping or cancelling it aborts them.
+//! * [`OutgoingRequest`](struct.OutgoingRequest.html) - The headers and payload of a request about to be sent, which a [`SendHook`](trait.SendHook.html) may change or block.
+//! * [`PreparedRequest`](struct.PreparedRequest.html) - The request a send would have made to APNs, returned in dry-run mode or by `ApnsClient::prepare_request` for sending with `ApnsClient::execute`.
+//! * [`Simulator`](struct.Simulator.html) - Writes notifications as `.apns` files for the iOS Simulator, and optionally pushes them with `xcrun simctl push`, instead of sending them to APNs.
+//! * [`CancellationToken`](struct.CancellationToken.html) - Aborts a campaign, fan-out, or sender mid-flight, leaving a partial report of what was delivered.
+//! * [`ReceiptId`](struct.ReceiptId.html) - Identifies a notification scheduled with `ApnsSender::enqueue_at`, to cancel or reschedule it.
+//! * [`Quota`](struct.Quota.html) - A per-app rate limit and daily quota, so one tenant sharing a sender cannot starve the others.
+//! * [`QuietHours`](struct.QuietHours.html) - A nightly window of recipients' local time during which a sender holds notifications back.
+//! * [`RetryPolicy`](struct.RetryPolicy.html) - How often the sender retries transient failures, and optionally which rejections count as transient.
+//! * [`ExponentialBackoff`](struct.ExponentialBackoff.html), [`FixedBackoff`](struct.FixedBackoff.html), [`FibonacciBackoff`](struct.FibonacciBackoff.html), and [`DecorrelatedJitter`](struct.DecorrelatedJitter.html) - [`Backoff`](trait.Backoff.html) strategies spacing out a retry policy's retries and cool-downs.
+//! * [`DefaultClassifier`](struct.DefaultClassifier.html) - Decides whether a failure is retried, permanent, or a dead token.
+//! * [`SystemClock`](struct.SystemClock.html) - The default [`Clock`](trait.Clock.html) provider tokens are issued at; supply your own for tests or skewed hosts.
+//! * [`ClientStats`](struct.ClientStats.html) - A snapshot of a client's send counts, failures, and latency.
+//! * [`SendEvent`](struct.SendEvent.html) - Describes one request made to APNs, as published by `ApnsClient::subscribe_events`.
+//! * [`TraceContext`](struct.TraceContext.html) - The W3C `traceparent` of the request that caused a send, recorded on its tracing span and send event.
+//! * [`Notification`](struct.Notification.html) - A notification of a specific push type, such as a VoIP push, validated against that type's rules.
+//! * [`MdmNotification`](struct.MdmNotification.html) - An MDM push carrying a managed device's push magic.
+//! * [`WebPushPayload`](struct.WebPushPayload.html) - The payload of a Safari web push, with its alert and URL arguments.
+//! * [`DeviceToken`](struct.DeviceToken.html) - A device token checked to be hex-encoded and of a valid length, accepted by every send API.
+//! * [`PushRequest`](struct.PushRequest.html) - A single notification addressed to one device.
+//! * [`MemoryProvider`](struct.MemoryProvider.html) - A [`PushProvider`](trait.PushProvider.html) recording requests instead of sending them, for testing code written against the trait.
+//! * [`Profile`](struct.Profile.html) - The bundle ID and send defaults of one app, registered on a client by name.
+//! * [`DefaultTrimStrategy`](struct.DefaultTrimStrategy.html) - Shrinks payloads rejected with `413 PayloadTooLarge`.
+//! * [`LiveActivityPayload`](struct.LiveActivityPayload.html) - The payload of a Live Activity update or end push.
+//! * [`LiveActivityStartPayload`](struct.LiveActivityStartPayload.html) - The payload of a push that starts a Live Activity remotely.
+//! * [`LocalizedAlert`](struct.LocalizedAlert.html) - Alert text in several languages, resolved with a locale fallback chain.
+//! * [`AlertTranslations`](struct.AlertTranslations.html) - A table of alert titles and bodies with placeholders per locale, resolved into an `Alert`.
+//! * [`MemoryTokenStore`](struct.MemoryTokenStore.html) - The default in-memory store of device tokens APNs reported as invalid.
+//! * [`MemoryDeviceRegistry`](struct.MemoryDeviceRegistry.html) - An in-memory [`DeviceRegistry`](trait.DeviceRegistry.html) of the devices each user registered, for `ApnsClient::send_to_user`.
+//! * [`UserDevice`](struct.UserDevice.html) - A device token a user registered, with the topic its app is sent on.
+//! * [`ChannelClient`](struct.ChannelClient.html) - Creates, reads, and deletes broadcast channels and broadcasts Live Activity updates to them.
+//! * [`MemorySigningAudit`](struct.MemorySigningAudit.html) - An in-memory audit trail of signed provider tokens, recording hashes and claims only.
+//! * [`TokenHasher`](struct.TokenHasher.html) - Replaces device tokens with an HMAC before they reach analytics sinks.
+//! * [`AuthKeySecret`](struct.AuthKeySecret.html) - An auth key with its team and key IDs, fetched from a secrets manager by a [`SecretSource`](trait.SecretSource.html).
+//! * [`Credentials`](struct.Credentials.html) - The auth key of another team, for signing a single request with [`SendOptions`](struct.SendOptions.html).
+//! * [`Template`](struct.Template.html) - A reusable notification with `{placeholder}`s in its alert, rendered into a payload per recipient.
+//! * [`CustomDataSchema`](struct.CustomDataSchema.html) - A JSON Schema the custom data of payloads is checked against before sending.
+//! * [`AppRegistry`](struct.AppRegistry.html) - Routes each topic to the credentials and environment of its app, so one client serves many tenants.
+//! * [`Linter`](struct.Linter.html) - Finds Apple-specific mistakes in a notification, such as an alert at background priority or an over-long collapse ID, as warnings and errors.
+//! * [`RuleSet`](struct.RuleSet.html) - Apple's payload constraints as data, extensible with your own [`Rule`](struct.Rule.html)s.
+//! * [`Campaign`](struct.Campaign.html) - A pausable send to many devices whose state can be exported and resumed elsewhere.
+//! * [`Canary`](struct.Canary.html) - Sends a campaign to a sample of its devices first, and only to the rest if few of the sample failed.
+//! * [`BatchReport`](struct.BatchReport.html) - Counts a batch's delivered, unregistered, throttled, failed, and duplicate notifications, with its invalid tokens and duration.
+//! * [`ResultsWriter`](struct.ResultsWriter.html) - Streams send results to newline-delimited JSON, with periodic flushes and size-based file rotation.
+//! * [`ProxyConfig`](struct.ProxyConfig.html) - An HTTP or SOCKS5 proxy, with credentials and a no-proxy list, that requests to APNs go through.
+//! * [`HealthReport`](struct.HealthReport.html) - What `ApnsClient::health_check` found: the endpoint, connection, latency, and provider token age.
+//! * [`ShutdownReport`](struct.ShutdownReport.html) - How many pending sends `ApnsClient::shutdown` flushed and how many it dropped.
+//! * [`CapacityPlan`](struct.CapacityPlan.html) - Estimates the duration, peak connections, and memory of a send for capacity planning.
+//! * [`LocalSigner`](struct.LocalSigner.html) - The default [`TokenSigner`](trait.TokenSigner.html), signing provider tokens with a PEM key in memory.
+//! * [`CoreClient`](struct.CoreClient.html) - Signs and sends notifications through a pluggable [`Transport`](trait.Transport.html), on any async runtime.
+//! * [`H2Transport`](struct.H2Transport.html) - A [`Transport`](trait.Transport.html) holding one HTTP/2 connection to APNs, built directly on `h2`.
+//! * [`CustomPayload`](struct.CustomPayload.html) - The `aps` dictionary with typed custom data, such as a struct deriving `ApnsCustomData`.
+//!
+//! ## Enums
+//!
+//! * [`CertificatePin`](enum.CertificatePin.html) - A root certificate or public key the APNs server must present, for detecting TLS interception.
+//! * [`Badge`](enum.Badge.html) - Sets, clears, or leaves unchanged the badge of the app icon.
+//! * [`Sound`](enum.Sound.html) - The default sound, a named sound file, or silence for an alert.
+//! * [`ConnectionStrategy`](enum.ConnectionStrategy.html) - How a client with several HTTP/2 connections spreads requests across them.
+//! * [`TrafficClass`](enum.TrafficClass.html) - How urgently a sender dispatches a queued notification, so marketing traffic cannot delay transactional pushes.
+//! * [`DeliveryEvent`](enum.DeliveryEvent.html) - A step in the life of a notification, or a provider token refresh or throttled sender, as streamed by `ApnsClient::events`.
+//! * [`Alert`](enum.Alert.html) - An alert as resolved text or as loc-key and loc-args for the device to localize.
 //!
 //! ## Functions
 //! 
 //! * [`send_push_notification`](fn.send_push_notification.html) - Sends a push notification to an Apple device using APNs.
+//! * [`validate_topic`](fn.validate_topic.html) - Checks that a topic is a well-formed bundle identifier.
+//! * [`validate_topic_for`](fn.validate_topic_for.html) - Checks that a topic is well-formed and carries the suffix a push type requires.
+//! * [`validate_website_push_id`](fn.validate_website_push_id.html) - Checks that a topic is a website push ID such as `web.com.example`.
+//! * [`validate_against_rules`](fn.validate_against_rules.html) - Checks a payload against Apple's documented constraints for its push type.
+//! * [`fallback_chain`](fn.fallback_chain.html) - Lists the locales tried when localizing an alert, most specific first.
+//! * [`debug::inspect_token`](debug/fn.inspect_token.html) - Decodes a client's cached provider token into its key ID, team ID, issue time, and remaining validity, for diagnosing `InvalidProviderToken`.
+//!
+//! ## Macros
+//!
+//! * [`apns_payload!`](macro.apns_payload.html) - Builds a payload from JSON-like syntax, rejecting unknown `aps` keys at compile time.
+//!
+//! ## Features
+//!
+//! * `tokio` (default) - Adds [`ApnsClient`](struct.ApnsClient.html), [`ApnsSender`](struct.ApnsSender.html), and everything else
+//!   that runs on tokio and connects through reqwest. Without it, but with `signing`, the crate is a runtime-independent
+//!   core of payloads, provider token signing, and request construction, sending through [`CoreClient`](struct.CoreClient.html).
+//! * `signing` (enabled by `tokio` and `h2`) - Adds provider token signing with `jsonwebtoken` and OpenSSL, and everything
+//!   built on it: [`LocalSigner`](struct.LocalSigner.html), [`Credentials`](struct.Credentials.html),
+//!   [`AppRegistry`](struct.AppRegistry.html), [`TokenHasher`](struct.TokenHasher.html),
+//!   [`ApnsResponse`](struct.ApnsResponse.html), and [`CoreClient`](struct.CoreClient.html). Without it the crate is only
+//!   payload types and validation, with a small dependency tree.
+//! * `h2` - Adds [`H2Transport`](struct.H2Transport.html), a transport for [`CoreClient`](struct.CoreClient.html) speaking
+//!   HTTP/2 over `h2` and `tokio-native-tls`, with control over its single connection and without reqwest or hyper.
+//! * `native-tls` (default) - Connects to APNs with the platform's TLS library.
+//! * `rustls` - Connects with rustls instead, e.g. for static musl builds; disable default features to drop `native-tls`.
+//! * `socks` - Accepts `socks5://` URLs in [`ProxyConfig`](struct.ProxyConfig.html).
+//! * `tower` - Implements `tower::Service<PushRequest>` for [`ApnsClient`](struct.ApnsClient.html).
+//! * `sink` - Adds [`ApnsSender::sink`](struct.ApnsSender.html#method.sink), a `futures::Sink<PushRequest>` feeding the sender's queue with backpressure.
+//! * `journal` - Adds [`ApnsSenderBuilder::journal`](struct.ApnsSenderBuilder.html#method.journal), recording queued notifications on disk so they are resumed after a restart.
+//! * `keychain` - On macOS, adds [`ApnsClientBuilder::auth_key_keychain`](struct.ApnsClientBuilder.html#method.auth_key_keychain)
+//!   and [`ApnsClientBuilder::certificate_keychain`](struct.ApnsClientBuilder.html#method.certificate_keychain),
+//!   loading the auth key or client certificate from the Keychain by label instead of from an exported file.
+//! * `vault`, `gcp-secrets`, `aws-secrets` - Add [`VaultSecret`](struct.VaultSecret.html), [`GcpSecret`](struct.GcpSecret.html),
+//!   and [`AwsSecret`](struct.AwsSecret.html), [`SecretSource`](trait.SecretSource.html)s fetching the auth key and its
+//!   IDs from HashiCorp Vault, GCP Secret Manager, or AWS Secrets Manager at startup and on rotation.
+//! * `cli` - Builds the `apnrs` command-line tool (`apnrs send --key key.p8 --team T --key-id K --topic com.app --token … --alert "hi" --sandbox`,
+//!   `apnrs broadcast --input devices.csv --alert "Hi {name}" …` to send a templated alert to every token in a CSV or JSONL
+//!   file and write each outcome to a results file, `apnrs tail --socket <path>` to follow a process serving events with `ApnsClient::serve_events`, or `apnrs plan`
+//!   to estimate a send's duration, connections, and memory, or `apnrs token` to decode the provider token a key signs).
+//! * `bench` - Builds the `apnrs-bench` tool measuring throughput, p50/p99 latency, and error rates at a given
+//!   concurrency against a simulated APNs (`apnrs-bench mock`) or the sandbox (`apnrs-bench sandbox`), for tuning
+//!   connection and in-flight settings.
+//! * `derive` - Adds `#[derive(ApnsCustomData)]`, turning a struct into custom data for a [`CustomPayload`](struct.CustomPayload.html)
+//!   and rejecting reserved keys such as `aps` at compile time.
+//! * `uniffi` - Adds [`PushClient`](struct.PushClient.html), a UniFFI interface for building a client and sending notifications
+//!   from Kotlin, Swift, or Python, and the `uniffi-bindgen` tool generating the bindings.
+//! * `capi` - Adds a C API (`apnrs_client_new`, `apnrs_send`, `apnrs_last_error`, `apnrs_client_free`), declared in
+//!   `include/apnrs.h`, for linking the crate built as a `cdylib` into C and C++ services.
+//! * `server` - Adds [`server::Relay`](server/struct.Relay.html), a small axum server accepting `POST /push` with a
+//!   [`PushRequest`](struct.PushRequest.html) as JSON and forwarding it through an [`ApnsClient`](struct.ApnsClient.html).
+//! * `schema` - Adds [`CustomDataSchema`](struct.CustomDataSchema.html) and
+//!   [`ApnsClientBuilder::custom_data_schema`](struct.ApnsClientBuilder.html#method.custom_data_schema), rejecting payloads
+//!   whose custom data does not match a JSON Schema before they are sent.
+//! * `chrono` - Adds accessors reading APNs' epoch timestamps as `chrono::DateTime<Utc>`, such as
+//!   [`ApnsResponse::unregistered_at_utc`](struct.ApnsResponse.html#method.unregistered_at_utc), and
+//!   [`PushRequest::recipient_time_zone`](struct.PushRequest.html#method.recipient_time_zone), applying quiet hours in
+//!   the recipient's IANA time zone across daylight saving time changes.
+//! * `time` - Adds accessors reading APNs' epoch timestamps as `time::OffsetDateTime`, such as
+//!   [`ApnsResponse::unregistered_at_offset`](struct.ApnsResponse.html#method.unregistered_at_offset).
+//! * `config` - Adds [`ApnsConfig`](struct.ApnsConfig.html), loadable from TOML and `APNS_*` environment variables
+//!   and validated up front with every problem reported at once.
+//! * `delivery-log` - Adds [`DeliveryLog`](struct.DeliveryLog.html), recording every request (`apns-id`, hashed token,
+//!   topic, status, reason, latency, timestamp) in SQLite and querying what was sent to a set of devices over a time range.
+//! * `metrics` - Emits `apns_notifications_sent_total`, `apns_notifications_failed_total`,
+//!   `apns_request_duration_seconds`, `apns_token_refreshes_total`, and `apns_connection_errors_total`
+//!   through the `metrics` facade. Which labels are attached, and how many values each may take, is set with
+//!   [`MetricLabels`](struct.MetricLabels.html).
+//! * `logging` - Emits `log` records for provider token signing (info), requests sent and APNs' answers (debug),
+//!   rejections, trimmed payloads, and transport failures (warn), and sender retries (info), with device tokens
+//!   shortened to their first six and last four characters, and with
+//!   [`ApnsClientBuilder::wire_logging`](struct.ApnsClientBuilder.html#method.wire_logging), every request and response
+//!   in full under the `apnrs::wire` target, with the provider token redacted.
+//! * `tracing` - Emits `tracing` spans and events around token signing, request dispatch, and response handling.
+//! * `webhook` - Adds [`FailureWebhook`](struct.FailureWebhook.html), posting batches of failed sends (token, reason, timestamp,
+//!   `apns-id`) as JSON to a webhook URL, retrying posts the webhook does not accept.
 
+#[cfg(feature = "signing")]
 extern crate jsonwebtoken as jwt;
 
-use jwt::{encode, EncodingKey, Header};
-use reqwest::header::{HeaderMap, HeaderValue, AUTHORIZATION, CONTENT_TYPE};
-use reqwest::Response;
+#[cfg(all(feature = "tokio", not(any(feature = "native-tls", feature = "rustls"))))]
+compile_error!("apnrs needs a TLS backend: enable the `native-tls` or `rustls` feature");
+
+mod audit;
+mod backoff;
+mod badge;
+#[cfg(feature = "tokio")]
+mod campaign;
+#[cfg(feature = "capi")]
+mod capi;
+#[cfg(feature = "tokio")]
+mod channel;
+mod classify;
+mod clock;
+#[cfg(feature = "tokio")]
+mod client;
+#[cfg(feature = "config")]
+mod config;
+mod custom_data;
+mod datetime;
+#[cfg(feature = "tokio")]
+pub mod debug;
+#[cfg(feature = "delivery-log")]
+mod delivery_log;
+mod device_registry;
+mod device_token;
+mod dry_run;
+mod environment;
+mod error;
+#[cfg(feature = "tokio")]
+mod events;
+#[cfg(feature = "tokio")]
+mod fanout;
+#[cfg(feature = "tokio")]
+mod handle;
+#[cfg(feature = "tokio")]
+mod health;
+#[cfg(feature = "tokio")]
+mod hooks;
+#[cfg(feature = "h2")]
+mod h2_transport;
+#[cfg(feature = "uniffi")]
+mod ffi;
+mod lint;
+mod live_activity;
+mod localize;
+mod macros;
+mod mdm;
+mod notification;
+mod options;
+#[cfg(feature = "tokio")]
+mod pinning;
+mod plan;
+#[cfg(feature = "tokio")]
+mod prepared;
+mod preview;
+mod profile;
+#[cfg(feature = "tokio")]
+mod provider;
+mod privacy;
+#[cfg(feature = "tokio")]
+mod proxy;
+mod push_type;
+mod quiet_hours;
+#[cfg(feature = "signing")]
+mod registry;
+#[cfg(feature = "tokio")]
+mod quota;
+#[cfg(feature = "tokio")]
+mod report;
+mod request;
+#[cfg(feature = "signing")]
+mod response;
+#[cfg(feature = "tokio")]
+mod results;
+mod retry;
+mod rules;
+mod schema;
+#[cfg(feature = "tokio")]
+mod secrets;
+#[cfg(feature = "tokio")]
+mod sender;
+#[cfg(feature = "server")]
+pub mod server;
+#[cfg(feature = "tokio")]
+mod schedule;
+#[cfg(feature = "tokio")]
+mod spool;
+#[cfg(feature = "tokio")]
+mod shard;
+#[cfg(feature = "tokio")]
+mod shutdown;
+#[cfg(feature = "signing")]
+mod signer;
+#[cfg(feature = "tokio")]
+mod simulator;
+mod sound;
+#[cfg(feature = "tower")]
+mod service;
+#[cfg(feature = "journal")]
+mod journal;
+#[cfg(all(feature = "keychain", target_os = "macos"))]
+mod keychain;
+#[cfg(feature = "sink")]
+mod sink;
+mod stats;
+mod telemetry;
+mod template;
+#[cfg(feature = "signing")]
+mod token;
+mod token_store;
+mod topic;
+mod trace_context;
+#[cfg(feature = "signing")]
+mod transport;
+mod trim;
+mod value;
+mod web_push;
+#[cfg(feature = "webhook")]
+mod webhook;
+
+pub use audit::{MemorySigningAudit, SigningAudit, SigningReason, SigningRecord};
+pub use backoff::{Backoff, DecorrelatedJitter, ExponentialBackoff, FibonacciBackoff, FixedBackoff};
+pub use badge::Badge;
+#[cfg(feature = "tokio")]
+pub use campaign::{Campaign, CampaignProgress, Canary, CanaryReport, DryRunReport};
+#[cfg(feature = "capi")]
+pub use capi::{
+    apnrs_client_free, apnrs_client_new, apnrs_last_error, apnrs_send, ApnrsClient,
+};
+#[cfg(feature = "tokio")]
+pub use channel::{ChannelClient, ChannelInfo, MessageStoragePolicy};
+pub use classify::{DefaultClassifier, FailureClass, FailureClassifier};
+pub use clock::{Clock, SystemClock};
+#[cfg(feature = "tokio")]
+pub use client::{
+    ApnsClient, ApnsClientBuilder, SendOutcome, DEFAULT_CONNECTION_RETRIES,
+    DEFAULT_CONNECT_TIMEOUT, DEFAULT_KEEPALIVE_INTERVAL, DEFAULT_KEEPALIVE_TIMEOUT,
+    DEFAULT_POOL_IDLE_TIMEOUT, DEFAULT_REQUEST_TIMEOUT, DEFAULT_SEND_DEADLINE, FAST_PATH_BUDGET,
+};
+#[cfg(feature = "config")]
+pub use config::{ApnsConfig, ConfigProblem};
+pub use custom_data::{ApnsCustomData, CustomPayload};
+#[cfg(feature = "delivery-log")]
+pub use delivery_log::DeliveryLog;
+#[cfg(feature = "derive")]
+pub use apnrs_derive::ApnsCustomData;
+pub use device_registry::{DeviceRegistry, MemoryDeviceRegistry, UserDevice};
+pub use device_token::{
+    DeviceToken, DeviceTokenError, MAX_DEVICE_TOKEN_LEN, MIN_DEVICE_TOKEN_LEN,
+};
+pub use dry_run::PreparedRequest;
+pub use environment::Environment;
+pub use error::{ApnsError, ErrorReason, TransportError};
+#[cfg(feature = "tokio")]
+pub use events::{DeliveryEvent, SendEvent};
+#[cfg(feature = "tokio")]
+pub use fanout::{FanOut, FanOutResults};
+#[cfg(feature = "tokio")]
+pub use handle::ApnsSenderHandle;
+#[cfg(feature = "tokio")]
+pub use health::HealthReport;
+#[cfg(feature = "tokio")]
+pub use hooks::{OutgoingRequest, SendContext, SendHook};
+#[cfg(feature = "h2")]
+pub use h2_transport::{H2Transport, DEFAULT_H2_CONNECT_TIMEOUT, DEFAULT_H2_REQUEST_TIMEOUT};
+#[cfg(feature = "uniffi")]
+pub use ffi::{ClientConfig, NotificationPayload, PushClient, PushError, SendResult};
+pub use lint::{LintIssue, Linter, Severity, MAX_COLLAPSE_ID_LEN};
+pub use live_activity::{
+    LiveActivityAlert, LiveActivityAps, LiveActivityEvent, LiveActivityPayload,
+    LiveActivityStartAps, LiveActivityStartPayload,
+};
+pub use localize::{fallback_chain, Alert, AlertTranslations, LocaleResolution, LocalizedAlert};
+pub use mdm::MdmNotification;
+pub use notification::{Notification, NotificationError, Priority};
+#[cfg(feature = "signing")]
+pub use options::Credentials;
+pub use options::SendOptions;
+#[cfg(feature = "tokio")]
+pub use pinning::CertificatePin;
+pub use plan::{CapacityEstimate, CapacityPlan, DEFAULT_STREAMS_PER_CONNECTION};
+#[cfg(feature = "tokio")]
+pub use prepared::PreparedPayload;
+pub use preview::PayloadPreview;
+pub use profile::Profile;
+#[cfg(feature = "tokio")]
+pub use provider::{MemoryProvider, ProviderFuture, PushProvider};
+#[cfg(feature = "signing")]
+pub use privacy::TokenHasher;
+#[cfg(feature = "tokio")]
+pub use proxy::ProxyConfig;
+pub use push_type::PushType;
+pub use quiet_hours::QuietHours;
+#[cfg(feature = "tokio")]
+pub use quota::Quota;
+#[cfg(feature = "signing")]
+pub use registry::{AppRegistry, RegisteredApp};
+#[cfg(feature = "tokio")]
+pub use report::BatchReport;
+pub use request::{PushRequest, TrafficClass};
+#[cfg(feature = "signing")]
+pub use response::ApnsResponse;
+#[cfg(feature = "tokio")]
+pub use results::{ResultRecord, ResultsWriter};
+pub use retry::RetryPolicy;
+pub use rules::{validate_against_rules, Check, Rule, RuleSet, RuleViolation, ValueKind};
+#[cfg(feature = "schema")]
+pub use schema::CustomDataSchema;
+pub use schema::SchemaViolation;
+#[cfg(feature = "aws-secrets")]
+pub use secrets::AwsSecret;
+#[cfg(feature = "gcp-secrets")]
+pub use secrets::GcpSecret;
+#[cfg(feature = "vault")]
+pub use secrets::VaultSecret;
+#[cfg(feature = "tokio")]
+pub use secrets::{AuthKeySecret, SecretFuture, SecretSource};
+#[cfg(feature = "tokio")]
+pub use sender::{ApnsSender, ApnsSenderBuilder, Receipt, ReceiptId};
+#[cfg(feature = "tokio")]
+pub use shard::ConnectionStrategy;
+#[cfg(feature = "tokio")]
+pub use shutdown::ShutdownReport;
+#[cfg(feature = "signing")]
+pub use signer::{LocalSigner, SignFuture, SignerError, TokenSigner};
+#[cfg(feature = "tokio")]
+pub use simulator::Simulator;
+pub use sound::Sound;
+#[cfg(feature = "sink")]
+pub use sink::PushSink;
+pub use stats::ClientStats;
+pub use telemetry::{MetricLabel, MetricLabels};
+pub use template::{Template, TemplateError};
+pub use token_store::{MemoryTokenStore, TokenStore};
+pub use topic::{
+    validate_topic, validate_topic_for, validate_website_push_id, TopicError, MAX_BUNDLE_ID_LEN,
+    TOPIC_SUFFIXES,
+};
+pub use trace_context::TraceContext;
+#[cfg(feature = "signing")]
+pub use transport::{CoreClient, Transport, TransportFuture};
+pub use trim::{
+    DefaultTrimStrategy, TrimReport, TrimStrategy, MAX_PAYLOAD_SIZE, MAX_VOIP_PAYLOAD_SIZE,
+};
+pub use value::PayloadError;
+pub use web_push::{WebPushAlert, WebPushAps, WebPushPayload};
+#[cfg(feature = "webhook")]
+pub use webhook::FailureWebhook;
+
+#[cfg(feature = "uniffi")]
+uniffi::setup_scaffolding!();
+
+// Used by code generated with `#[derive(ApnsCustomData)]`
+#[doc(hidden)]
+pub use serde as __serde;
+// Used by `apns_payload!`
+#[doc(hidden)]
+pub use serde_json as __serde_json;
+
+// Public types use `http` rather than HTTP-client types, so the transport can change
+// without breaking callers.
+pub use bytes::Bytes;
+pub use http;
+pub use http::{HeaderMap, StatusCode};
+// Re-exported for `ApnsClientBuilder::http_client`, so a client built with it matches the
+// version the crate sends through.
+#[cfg(feature = "tokio")]
+pub use reqwest;
+// Re-exported for the errors `DeliveryLog` returns.
+#[cfg(feature = "delivery-log")]
+pub use rusqlite;
+// Re-exported for `PushRequest::recipient_time_zone`, so zones parse with the same time
+// zone database the crate resolves them with.
+#[cfg(feature = "chrono")]
+pub use chrono_tz;
+// Re-exported for aborting campaigns, fan-outs, and senders mid-flight.
+#[cfg(feature = "tokio")]
+pub use tokio_util::sync::CancellationToken;
+
+#[cfg(feature = "tokio")]
+use signer::sign_provider_token;
+#[cfg(feature = "tokio")]
+use http::header::{HeaderValue, AUTHORIZATION, CONTENT_TYPE};
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+#[cfg(feature = "tokio")]
 use std::fs;
-use std::time::{SystemTime, UNIX_EPOCH};
+#[cfg(feature = "tokio")]
+use std::sync::{Arc, Mutex, OnceLock};
+#[cfg(feature = "tokio")]
+use zeroize::Zeroizing;
 
 /// Represents the claims used for generating the JWT token.
 ///
@@ -78,21 +515,246 @@ pub struct Claims {
 ///
 /// # Fields
 ///
-/// * `alert` - The alert message to be displayed.
-/// * `content_available` - Indicates if new content is available (set to 1).
-/// * `badge` - The number to display as the badge of the app icon.
-/// * `sound` - The name of the sound file to play for an alert.
+/// * `alert` - The alert message to be displayed; left out of the JSON when empty, as for a
+///   background push.
+/// * `content_available` - Marks the notification as a background update, waking the app to
+///   fetch new content. Sent as `"content-available": 1` when `Some(true)` and left out
+///   otherwise, since APNs treats any `content-available` key as a request to wake the app.
+/// * `badge` - Whether to set, clear, or leave unchanged the badge of the app icon.
+/// * `sound` - The sound to play for an alert, if any.
 /// * `category` - The category of the notification.
 /// * `thread_id` - The thread identifier for the notification.
-#[derive(Debug, Serialize, Deserialize)]
+/// * `summary_arg` - The text shown in the summary of a group of notifications sharing the
+///   thread, such as a sender's name ("3 more messages from Anna"). Sent as the alert
+///   dictionary's `summary-arg`.
+/// * `summary_arg_count` - How many items the notification adds to the summary (defaults to
+///   1 on the device). Sent as the alert dictionary's `summary-arg-count`.
+/// * `launch_image` - The name of the launch image or storyboard shown when the user opens
+///   the app from the notification. Sent as the alert dictionary's `launch-image`.
+/// * `alert_fields` - Further keys of the alert dictionary, such as `title`, `subtitle`, or
+///   `loc-key`.
+/// * `extra_fields` - Further keys of the `aps` dictionary, such as `interruption-level` or
+///   `mutable-content`.
+///
+/// With any of `summary_arg`, `summary_arg_count`, `launch_image`, or `alert_fields` set, the
+/// alert is sent as a dictionary with the alert text as its `body`. Deserializing keeps keys
+/// the struct has no field for in `alert_fields` and `extra_fields`, so a payload parsed from
+/// JSON serializes back to the same keys; a field that would overwrite one of the struct's
+/// own keys fails serialization. Fields that are `None`, an unchanged badge, and no sound are
+/// left out of the JSON rather than sent as `null`, so they take no room in the payload size
+/// limit.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(into = "WireAps", from = "WireAps")]
 pub struct Aps {
     pub alert: String,
-    #[serde(rename = "content-available")]
-    pub content_available: u8,
-    pub badge: Option<u32>,
-    pub sound: Option<String>,
+    pub content_available: Option<bool>,
+    pub badge: Badge,
+    pub sound: Sound,
     pub category: Option<String>,
     pub thread_id: Option<String>,
+    pub summary_arg: Option<String>,
+    pub summary_arg_count: Option<u32>,
+    pub launch_image: Option<String>,
+    pub alert_fields: HashMap<String, serde_json::Value>,
+    pub extra_fields: HashMap<String, serde_json::Value>,
+}
+
+impl Aps {
+    /// Creates the `aps` dictionary of a silent background push, with `content_available`
+    /// set and no alert, badge, or sound.
+    ///
+    /// Send it with the [`Background`](PushType::Background) push type at
+    /// [`Priority::Normal`], as Apple requires.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use apnrs::Aps;
+    ///
+    /// let aps = Aps::background();
+    /// assert_eq!(serde_json::to_string(&aps).unwrap(), r#"{"content-available":1}"#);
+    /// ```
+    pub fn background() -> Self {
+        Aps {
+            alert: String::new(),
+            content_available: Some(true),
+            badge: Badge::Unchanged,
+            sound: Sound::None,
+            category: None,
+            thread_id: None,
+            summary_arg: None,
+            summary_arg_count: None,
+            launch_image: None,
+            alert_fields: HashMap::new(),
+            extra_fields: HashMap::new(),
+        }
+    }
+}
+
+/// The `aps` keys [`Aps`] has fields for.
+const APS_KEYS: &[&str] = &[
+    "alert",
+    "content-available",
+    "badge",
+    "sound",
+    "category",
+    "thread-id",
+];
+
+/// The alert dictionary keys [`Aps`] has fields for.
+const ALERT_KEYS: &[&str] = &["body", "summary-arg", "summary-arg-count", "launch-image"];
+
+/// Serializes further keys of a dictionary, failing if one of them is in `reserved`.
+fn serialize_fields<S: serde::Serializer>(
+    fields: &HashMap<String, serde_json::Value>,
+    reserved: &[&str],
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    if let Some(key) = reserved.iter().find(|key| fields.contains_key(**key)) {
+        return Err(serde::ser::Error::custom(format!(
+            "field \"{}\" collides with a key of the payload",
+            key
+        )));
+    }
+    fields.serialize(serializer)
+}
+
+fn serialize_extra_fields<S: serde::Serializer>(
+    fields: &HashMap<String, serde_json::Value>,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    serialize_fields(fields, APS_KEYS, serializer)
+}
+
+fn serialize_alert_fields<S: serde::Serializer>(
+    fields: &HashMap<String, serde_json::Value>,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    serialize_fields(fields, ALERT_KEYS, serializer)
+}
+
+/// The JSON form of [`Aps`], with the alert as text or as a dictionary.
+#[derive(Serialize, Deserialize)]
+struct WireAps {
+    #[serde(default, skip_serializing_if = "WireAlert::is_empty")]
+    alert: WireAlert,
+    #[serde(
+        rename = "content-available",
+        default,
+        skip_serializing_if = "is_unset",
+        serialize_with = "serialize_flag",
+        deserialize_with = "deserialize_flag"
+    )]
+    content_available: Option<bool>,
+    #[serde(default, skip_serializing_if = "Badge::is_unchanged")]
+    badge: Badge,
+    #[serde(default, skip_serializing_if = "Sound::is_none")]
+    sound: Sound,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    category: Option<String>,
+    #[serde(rename = "thread-id", alias = "thread_id", skip_serializing_if = "Option::is_none")]
+    thread_id: Option<String>,
+    #[serde(flatten, serialize_with = "serialize_extra_fields")]
+    extra_fields: HashMap<String, serde_json::Value>,
+}
+
+/// Returns `true` for a `content-available` flag that is not set, which is sent by leaving
+/// out the key.
+fn is_unset(flag: &Option<bool>) -> bool {
+    *flag != Some(true)
+}
+
+/// Serializes a set `content-available` flag as 1.
+fn serialize_flag<S: serde::Serializer>(_: &Option<bool>, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_u8(1)
+}
+
+/// Reads a `content-available` flag, which is set by any value but 0.
+fn deserialize_flag<'de, D: serde::Deserializer<'de>>(deserializer: D) -> Result<Option<bool>, D::Error> {
+    u8::deserialize(deserializer).map(|flag| Some(flag != 0))
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(untagged)]
+enum WireAlert {
+    Text(String),
+    Dictionary {
+        #[serde(default, skip_serializing_if = "String::is_empty")]
+        body: String,
+        #[serde(rename = "summary-arg", skip_serializing_if = "Option::is_none")]
+        summary_arg: Option<String>,
+        #[serde(rename = "summary-arg-count", skip_serializing_if = "Option::is_none")]
+        summary_arg_count: Option<u32>,
+        #[serde(rename = "launch-image", skip_serializing_if = "Option::is_none")]
+        launch_image: Option<String>,
+        #[serde(flatten, serialize_with = "serialize_alert_fields")]
+        fields: HashMap<String, serde_json::Value>,
+    },
+}
+
+impl Default for WireAlert {
+    fn default() -> Self {
+        WireAlert::Text(String::new())
+    }
+}
+
+impl WireAlert {
+    /// Returns `true` for an alert with no text, which is sent by leaving out the key.
+    fn is_empty(&self) -> bool {
+        matches!(self, WireAlert::Text(text) if text.is_empty())
+    }
+}
+
+impl From<Aps> for WireAps {
+    fn from(aps: Aps) -> Self {
+        let alert = match (aps.summary_arg, aps.summary_arg_count, aps.launch_image) {
+            (None, None, None) if aps.alert_fields.is_empty() => WireAlert::Text(aps.alert),
+            (summary_arg, summary_arg_count, launch_image) => WireAlert::Dictionary {
+                body: aps.alert,
+                summary_arg,
+                summary_arg_count,
+                launch_image,
+                fields: aps.alert_fields,
+            },
+        };
+        WireAps {
+            alert,
+            content_available: aps.content_available,
+            badge: aps.badge,
+            sound: aps.sound,
+            category: aps.category,
+            thread_id: aps.thread_id,
+            extra_fields: aps.extra_fields,
+        }
+    }
+}
+
+impl From<WireAps> for Aps {
+    fn from(wire: WireAps) -> Self {
+        let (alert, summary_arg, summary_arg_count, launch_image, alert_fields) = match wire.alert {
+            WireAlert::Text(alert) => (alert, None, None, None, HashMap::new()),
+            WireAlert::Dictionary {
+                body,
+                summary_arg,
+                summary_arg_count,
+                launch_image,
+                fields,
+            } => (body, summary_arg, summary_arg_count, launch_image, fields),
+        };
+        Aps {
+            alert,
+            content_available: wire.content_available,
+            badge: wire.badge,
+            sound: wire.sound,
+            category: wire.category,
+            thread_id: wire.thread_id,
+            summary_arg,
+            summary_arg_count,
+            launch_image,
+            alert_fields,
+            extra_fields: wire.extra_fields,
+        }
+    }
 }
 
 /// Represents the entire payload sent to the APNs.
@@ -100,23 +762,121 @@ pub struct Aps {
 /// # Fields
 ///
 /// * `aps` - The APS payload.
-/// * `custom_key` - Any additional custom data to be sent with the notification.
-#[derive(Debug, Serialize, Deserialize)]
+/// * `custom_key` - Any additional custom data to be sent with the notification; left out
+///   of the JSON when `None`.
+/// * `custom_fields` - Further custom keys, sent at the top level of the payload next to
+///   `aps`. A field named `aps` or `custom_key` would collide with the payload's own keys,
+///   so serializing such a payload fails. For data with a fixed shape, prefer a
+///   [`CustomPayload`] with typed custom data.
+///
+/// # Example
+///
+/// ```rust
+/// use apnrs::{ApnsPayload, Aps, Badge, Sound};
+///
+/// let mut payload = ApnsPayload {
+///     aps: Aps {
+///         alert: "Hello, world!".to_string(),
+///         content_available: None,
+///         badge: Badge::Unchanged,
+///         sound: Sound::None,
+///         category: None,
+///         thread_id: None,
+///         summary_arg: None,
+///         summary_arg_count: None,
+///         launch_image: None,
+///         alert_fields: Default::default(),
+///         extra_fields: Default::default(),
+///     },
+///     custom_key: None,
+///     custom_fields: Default::default(),
+/// };
+/// assert_eq!(
+///     serde_json::to_string(&payload).unwrap(),
+///     r#"{"aps":{"alert":"Hello, world!"}}"#
+/// );
+///
+/// payload.aps.badge = Badge::Set(3);
+/// payload.aps.category = Some("MESSAGE".to_string());
+/// payload.custom_key = Some("chat-7".to_string());
+/// assert_eq!(
+///     serde_json::to_string(&payload).unwrap(),
+///     r#"{"aps":{"alert":"Hello, world!","badge":3,"category":"MESSAGE"},"custom_key":"chat-7"}"#
+/// );
+///
+/// payload.custom_fields.insert("unread".to_string(), serde_json::json!(4));
+/// assert_eq!(serde_json::to_value(&payload).unwrap()["unread"], 4);
+///
+/// payload.custom_fields.insert("aps".to_string(), serde_json::json!({}));
+/// assert!(serde_json::to_string(&payload).is_err());
+/// payload.custom_fields.clear();
+///
+/// payload.aps.launch_image = Some("Welcome".to_string());
+/// assert_eq!(
+///     serde_json::to_value(&payload).unwrap()["aps"]["alert"],
+///     serde_json::json!({ "body": "Hello, world!", "launch-image": "Welcome" })
+/// );
+///
+/// // Payloads parsed from JSON keep every key
+/// let logged = serde_json::json!({
+///     "aps": {
+///         "alert": { "title": "Smoke alarm", "body": "Kitchen" },
+///         "sound": { "critical": 1, "name": "alarm.caf", "volume": 1.0 },
+///         "interruption-level": "critical"
+///     },
+///     "event-id": 7
+/// });
+/// let parsed: ApnsPayload = serde_json::from_value(logged.clone()).unwrap();
+/// assert_eq!(parsed.aps.alert_fields["title"], "Smoke alarm");
+/// assert_eq!(serde_json::to_value(&parsed).unwrap(), logged);
+/// ```
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ApnsPayload {
     pub aps: Aps,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub custom_key: Option<String>,
+    #[serde(flatten, default, serialize_with = "custom_data::serialize_custom_fields")]
+    pub custom_fields: HashMap<String, serde_json::Value>,
 }
 
-/// Retrieves the current Unix timestamp.
+/// Parses an auth key, reusing the signer parsed from the same key by the previous call, so
+/// repeated sends do not parse it again.
+///
+/// Only the signer of the most recently used key is kept, recognized by the SHA-256 of the
+/// key. It holds the parsed private key, so a rotated key is parsed on its first use and the
+/// signer of the old one is dropped, and its key wiped, once no send is using it.
 ///
 /// # Returns
 ///
-/// The current time in seconds since the Unix epoch.
-fn get_current_unix_time() -> u64 {
-    SystemTime::now()
-        .duration_since(UNIX_EPOCH)
-        .expect("Time went backwards")
-        .as_secs()
+/// The signer, or an error if the key is not an EC key.
+#[cfg(feature = "tokio")]
+fn parsed_key(pem: &[u8], key_id: &str) -> Result<Arc<LocalSigner>, ApnsError> {
+    type Cached = Option<(([u8; 32], String), Arc<LocalSigner>)>;
+    static SIGNER: OnceLock<Mutex<Cached>> = OnceLock::new();
+
+    let mut cached = SIGNER.get_or_init(Mutex::default).lock().unwrap();
+    let id = (openssl::sha::sha256(pem), key_id.to_string());
+    if let Some((_, signer)) = cached.as_ref().filter(|(cached_id, _)| *cached_id == id) {
+        return Ok(signer.clone());
+    }
+    let signer = Arc::new(LocalSigner::from_pem(pem, key_id)?);
+    *cached = Some((id, signer.clone()));
+    Ok(signer)
+}
+
+/// Signs a provider token with the given auth key.
+///
+/// # Returns
+///
+/// The encoded JWT, wiped from memory when dropped, or an error if signing failed.
+#[cfg(feature = "tokio")]
+async fn sign_token(signer: &LocalSigner, team_id: &str) -> Result<Zeroizing<String>, ApnsError> {
+    let claims = Claims {
+        iss: team_id.to_string(),
+        iat: SystemClock.unix_time(),
+    };
+
+    Ok(Zeroizing::new(sign_provider_token(signer, &claims).await?))
 }
 
 /// Sends a push notification to an Apple device using APNs.
@@ -131,23 +891,40 @@ fn get_current_unix_time() -> u64 {
 /// * `payload` - The payload of the notification.
 /// * `prod` - A boolean indicating whether to use the production or sandbox environment.
 ///
+/// The auth key is parsed on the first call and reused by later calls with the same key,
+/// until a call with another key replaces it. For fan-outs, prefer an [`ApnsClient`], which
+/// also reuses the provider token and connection.
+///
 /// # Returns
 ///
-/// A `Result` containing either the HTTP response from the APNs server or a `reqwest::Error`.
+/// A `Result` containing either the parsed response from the APNs server or an `ApnsError`:
+/// `ApnsError::Io` if the auth key cannot be read, `ApnsError::Config` if it is not an EC
+/// key, `ApnsError::Signer` if signing the provider token fails, `ApnsError::InvalidHeader`
+/// if the topic is not a valid header value, `ApnsError::Serialization` if the payload cannot
+/// be serialized, such as when a custom field is named `aps`, or `ApnsError::Http` if the
+/// HTTP client cannot be built or the request fails.
 ///
 /// # Example
 ///
 /// ```rust,no_run
+/// # use apnrs::{send_push_notification, ApnsPayload, Aps, Badge, Sound};
+/// # async fn run() {
 /// let payload = ApnsPayload {
 ///     aps: Aps {
 ///         alert: "Hello, world!".to_string(),
-///         content_available: 1,
-///         badge: Some(1),
-///         sound: Some("default".to_string()),
+///         content_available: None,
+///         badge: Badge::Set(1),
+///         sound: Sound::Default,
 ///         category: None,
 ///         thread_id: None,
+///         summary_arg: None,
+///         summary_arg_count: None,
+///         launch_image: None,
+///         alert_fields: Default::default(),
+///         extra_fields: Default::default(),
 ///     },
 ///     custom_key: Some("custom_value".to_string()),
+///     custom_fields: Default::default(),
 /// };
 ///
 /// let response = send_push_notification(
@@ -164,7 +941,9 @@ fn get_current_unix_time() -> u64 {
 ///     Ok(res) => println!("Notification sent: {:?}", res),
 ///     Err(e) => eprintln!("Error sending notification: {:?}", e),
 /// }
+/// # }
 /// ```
+#[cfg(feature = "tokio")]
 pub async fn send_push_notification(
     auth_key_path: &str,
     team_id: &str,
@@ -173,59 +952,35 @@ pub async fn send_push_notification(
     topic: &str,
     payload: ApnsPayload,
     prod: bool
-) -> Result<Response, reqwest::Error> {
+) -> Result<ApnsResponse, ApnsError> {
     // Read the key from file
-    let key = fs::read_to_string(auth_key_path).expect("Unable to read file");
+    let key = Zeroizing::new(fs::read(auth_key_path)?);
 
     // Create the JWT token
-    let claims = Claims {
-        iss: team_id.to_string(),
-        iat: get_current_unix_time(),
-    };
-
-    let header = Header {
-        alg: jwt::Algorithm::ES256,
-        kid: Some(key_id.to_string()),
-        ..Default::default()
-    };
-
-    let token = encode(
-        &header,
-        &claims,
-        &EncodingKey::from_ec_pem(key.as_bytes()).unwrap(),
-    )
-    .unwrap();
+    let signer = parsed_key(&key, key_id)?;
+    let token = sign_token(&signer, team_id).await?;
 
     // Prepare the headers and body for the HTTP request
-    let url = if prod {
-        format!(
-            "https://api.push.apple.com/3/device/{}",
-            device_token
-        )
+    let environment = if prod {
+        Environment::Production
     } else {
-        format!(
-            "https://api.sandbox.push.apple.com/3/device/{}",
-            device_token
-        )
+        Environment::Sandbox
     };
+    let url = environment.device_url(device_token);
 
-    let body = serde_json::to_string(&payload).expect("Failed to serialize payload");
+    let body = serde_json::to_string(&payload)?;
 
     let mut headers = HeaderMap::new();
-    headers.insert("apns-topic", HeaderValue::from_str(topic).unwrap());
-    headers.insert(
-        AUTHORIZATION,
-        HeaderValue::from_str(&format!("bearer {}", token)).unwrap(),
-    );
+    headers.insert("apns-topic", HeaderValue::from_str(topic)?);
+    let mut authorization = HeaderValue::from_str(&Zeroizing::new(format!("bearer {}", *token)))?;
+    authorization.set_sensitive(true);
+    headers.insert(AUTHORIZATION, authorization);
     headers.insert(CONTENT_TYPE, HeaderValue::from_static("application/json"));
 
     // Create an HTTP/2 client and send the request
-    let client = reqwest::Client::builder()
-        .http2_prior_knowledge()
-        .build()
-        .expect("Failed to build client");
+    let client = client::http_builder().build()?;
 
     let response = client.post(&url).headers(headers).body(body).send().await?;
 
-    Ok(response)
+    Ok(ApnsResponse::read(response).await?)
 }
@@ -0,0 +1,272 @@
+//! Linting of whole notifications for Apple-specific mistakes, beyond the payload rules.
+
+use crate::notification::{Notification, Priority};
+use crate::push_type::PushType;
+use crate::request::PushRequest;
+use crate::rules::RuleSet;
+use serde::Serialize;
+use serde_json::{json, Value};
+use std::fmt;
+
+/// The longest `apns-collapse-id` APNs accepts, in bytes.
+pub const MAX_COLLAPSE_ID_LEN: usize = 64;
+
+/// How serious a [`LintIssue`] is.
+///
+/// # Variants
+///
+/// * `Warning` - APNs accepts the notification, but it likely does not behave as intended.
+/// * `Error` - APNs rejects the notification, or the device drops it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum Severity {
+    Warning,
+    Error,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Severity::Warning => f.write_str("warning"),
+            Severity::Error => f.write_str("error"),
+        }
+    }
+}
+
+/// A mistake found by a [`Linter`].
+///
+/// # Fields
+///
+/// * `severity` - Whether the mistake keeps the notification from being delivered.
+/// * `key` - The dotted path of the offending payload key, such as `aps.sound.critical`, or
+///   the offending header, such as `apns-priority`.
+/// * `message` - What is wrong and how to fix it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LintIssue {
+    pub severity: Severity,
+    pub key: String,
+    pub message: String,
+}
+
+impl LintIssue {
+    /// Returns `true` if the issue is an error.
+    pub fn is_error(&self) -> bool {
+        self.severity == Severity::Error
+    }
+}
+
+impl fmt::Display for LintIssue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}: {}", self.severity, self.key, self.message)
+    }
+}
+
+/// Checks a notification for mistakes Apple's payload rules alone do not catch, such as an
+/// alert sent at the priority of a background push or a collapse ID APNs rejects.
+///
+/// Every violation of [`RuleSet::apple`] and a payload over the size limit of the push type
+/// are reported as errors, alongside checks that look at the payload together with the
+/// push type, priority, and collapse ID it is sent with.
+///
+/// # Example
+///
+/// ```rust
+/// use apnrs::{Linter, Priority, PushType, Severity};
+/// use serde_json::json;
+///
+/// let payload = json!({
+///     "aps": { "content-available": 1, "alert": "Sync done", "sound": { "name": "x.caf", "critical": 1 } }
+/// });
+/// let issues = Linter::new(PushType::Background)
+///     .priority(Priority::Immediate)
+///     .collapse_id("sync")
+///     .lint(&payload);
+///
+/// let keys: Vec<&str> = issues.iter().map(|issue| issue.key.as_str()).collect();
+/// assert_eq!(keys, ["aps.alert", "aps.sound", "apns-priority", "aps.sound.critical"]);
+/// assert_eq!(issues[3].severity, Severity::Warning);
+/// ```
+#[derive(Debug, Clone)]
+pub struct Linter {
+    push_type: PushType,
+    priority: Option<Priority>,
+    collapse_id: Option<String>,
+    rules: RuleSet,
+}
+
+impl Linter {
+    /// Creates a linter for notifications of `push_type`, sent without an `apns-priority`
+    /// or `apns-collapse-id`.
+    pub fn new(push_type: PushType) -> Self {
+        Linter {
+            push_type,
+            priority: None,
+            collapse_id: None,
+            rules: RuleSet::apple(),
+        }
+    }
+
+    /// Sets the priority the notification is sent with.
+    pub fn priority(mut self, priority: Priority) -> Self {
+        self.priority = Some(priority);
+        self
+    }
+
+    /// Sets the `apns-collapse-id` the notification is sent with.
+    pub fn collapse_id(mut self, collapse_id: impl Into<String>) -> Self {
+        self.collapse_id = Some(collapse_id.into());
+        self
+    }
+
+    /// Replaces the payload rules checked (defaults to [`RuleSet::apple`]).
+    pub fn rules(mut self, rules: RuleSet) -> Self {
+        self.rules = rules;
+        self
+    }
+
+    /// Lints `payload`.
+    ///
+    /// # Returns
+    ///
+    /// Every issue found, errors from the payload rules first; empty if none.
+    pub fn lint<P: Serialize + ?Sized>(&self, payload: &P) -> Vec<LintIssue> {
+        let payload = match serde_json::to_value(payload) {
+            Ok(payload) => payload,
+            Err(e) => {
+                return vec![issue(Severity::Error, "payload", e.to_string())];
+            }
+        };
+        let mut issues: Vec<LintIssue> = self
+            .rules
+            .validate(&payload, self.push_type)
+            .err()
+            .unwrap_or_default()
+            .into_iter()
+            .map(|violation| issue(Severity::Error, &violation.key, violation.message))
+            .collect();
+
+        let size = payload.to_string().len();
+        let limit = self.push_type.max_payload_size();
+        if size > limit {
+            let message = format!("is {} bytes, over the {} byte limit", size, limit);
+            issues.push(issue(Severity::Error, "payload", message));
+        }
+        if let Some(collapse_id) = &self.collapse_id {
+            if collapse_id.len() > MAX_COLLAPSE_ID_LEN {
+                let message = format!(
+                    "is {} bytes, over the {} byte limit",
+                    collapse_id.len(),
+                    MAX_COLLAPSE_ID_LEN
+                );
+                issues.push(issue(Severity::Error, "apns-collapse-id", message));
+            }
+        }
+        self.lint_priority(&payload, &mut issues);
+        lint_interruption(&payload, &mut issues);
+        if self.push_type == PushType::Alert && shows_nothing(&payload) {
+            let message = "has no alert, badge, or sound, so the notification shows nothing; \
+                           send content updates as background pushes";
+            issues.push(issue(Severity::Warning, "aps", message));
+        }
+        issues
+    }
+
+    /// Checks that the priority suits the push type and the payload.
+    fn lint_priority(&self, payload: &Value, issues: &mut Vec<LintIssue>) {
+        let Some(priority) = self.priority else {
+            return;
+        };
+        let has_alert = payload.pointer("/aps/alert").is_some();
+        match (self.push_type, priority) {
+            (PushType::Background, Priority::Immediate) => issues.push(issue(
+                Severity::Error,
+                "apns-priority",
+                "background pushes must be sent with priority 5 or 1",
+            )),
+            (PushType::Location, Priority::Low) => issues.push(issue(
+                Severity::Error,
+                "apns-priority",
+                "location pushes must be sent with priority 10 or 5",
+            )),
+            (PushType::Alert, Priority::Normal | Priority::Low) if has_alert => issues.push(issue(
+                Severity::Warning,
+                "apns-priority",
+                "alerts below priority 10 are delivered when it saves the device power, \
+                 and may arrive late",
+            )),
+            _ => {}
+        }
+    }
+}
+
+/// Warns about interruption levels that need an entitlement or capability the app may lack.
+fn lint_interruption(payload: &Value, issues: &mut Vec<LintIssue>) {
+    if payload.pointer("/aps/sound/critical") == Some(&json!(1)) {
+        issues.push(issue(
+            Severity::Warning,
+            "aps.sound.critical",
+            "critical alerts need the com.apple.developer.usernotifications.critical-alerts \
+             entitlement; without it the sound plays as a regular alert",
+        ));
+    }
+    match payload
+        .pointer("/aps/interruption-level")
+        .and_then(Value::as_str)
+    {
+        Some("critical") => issues.push(issue(
+            Severity::Warning,
+            "aps.interruption-level",
+            "critical alerts need the com.apple.developer.usernotifications.critical-alerts \
+             entitlement; without it the notification is delivered as active",
+        )),
+        Some("time-sensitive") => issues.push(issue(
+            Severity::Warning,
+            "aps.interruption-level",
+            "time-sensitive notifications need the Time Sensitive Notifications capability; \
+             without it the notification is delivered as active",
+        )),
+        _ => {}
+    }
+}
+
+/// Returns `true` if an alert push would neither show, sound, badge, nor wake the app.
+fn shows_nothing(payload: &Value) -> bool {
+    let Some(aps) = payload.get("aps").and_then(Value::as_object) else {
+        return false;
+    };
+    let alert_is_empty = match aps.get("alert") {
+        None => true,
+        Some(Value::String(alert)) => alert.is_empty(),
+        Some(_) => false,
+    };
+    alert_is_empty
+        && !["badge", "sound", "content-available"]
+            .iter()
+            .any(|key| aps.contains_key(*key))
+}
+
+fn issue(severity: Severity, key: &str, message: impl Into<String>) -> LintIssue {
+    LintIssue {
+        severity,
+        key: key.to_string(),
+        message: message.into(),
+    }
+}
+
+impl Notification {
+    /// Lints the notification with its push type and priority; see [`Linter`].
+    pub fn lint(&self) -> Vec<LintIssue> {
+        let mut linter = Linter::new(self.push_type);
+        linter.priority = self.priority;
+        linter.lint(&self.payload)
+    }
+}
+
+impl PushRequest {
+    /// Lints the request as a push of its push type (alert unless its options say otherwise)
+    /// with its collapse ID; see [`Linter`].
+    pub fn lint(&self) -> Vec<LintIssue> {
+        let mut linter = Linter::new(self.options.push_type.unwrap_or(PushType::Alert));
+        linter.collapse_id = self.options.collapse_id.clone();
+        linter.lint(&self.payload)
+    }
+}
@@ -0,0 +1,443 @@
+//! Payloads that start, update, and end Live Activities.
+
+#[cfg(any(feature = "chrono", feature = "time"))]
+use crate::datetime;
+use crate::datetime::from_unix_secs;
+use serde::{Deserialize, Serialize};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// What a Live Activity push does to the activity.
+///
+/// # Variants
+///
+/// * `Start` - Starts a new activity remotely (iOS 17.2 and later).
+/// * `Update` - Replaces the activity's content state.
+/// * `End` - Ends the activity, showing the final content state until it is dismissed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LiveActivityEvent {
+    Start,
+    Update,
+    End,
+}
+
+/// The `aps` dictionary of a Live Activity push.
+///
+/// # Fields
+///
+/// * `timestamp` - When the content state was produced, in seconds since the Unix epoch.
+///   The system ignores pushes older than the activity's current state.
+/// * `event` - Whether the push updates or ends the activity.
+/// * `content_state` - The new content state, matching the app's `ContentState` type.
+/// * `stale_date` - When the content becomes outdated, in seconds since the Unix epoch.
+/// * `dismissal_date` - When an ended activity is removed from the Lock Screen, in seconds
+///   since the Unix epoch.
+/// * `relevance_score` - How the activity ranks against the app's other activities.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LiveActivityAps<S> {
+    pub timestamp: u64,
+    pub event: LiveActivityEvent,
+    #[serde(rename = "content-state")]
+    pub content_state: S,
+    #[serde(rename = "stale-date", skip_serializing_if = "Option::is_none")]
+    pub stale_date: Option<u64>,
+    #[serde(rename = "dismissal-date", skip_serializing_if = "Option::is_none")]
+    pub dismissal_date: Option<u64>,
+    #[serde(rename = "relevance-score", skip_serializing_if = "Option::is_none")]
+    pub relevance_score: Option<f64>,
+}
+
+impl<S> LiveActivityAps<S> {
+    /// Returns when the content state was produced.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use apnrs::LiveActivityPayload;
+    /// use serde_json::json;
+    /// use std::time::{Duration, UNIX_EPOCH};
+    ///
+    /// let payload = LiveActivityPayload::update(json!({ "eta": 12 }))
+    ///     .timestamp(1_700_000_000)
+    ///     .stale_after(Duration::from_secs(60));
+    /// assert_eq!(payload.aps.timestamp_time(), UNIX_EPOCH + Duration::from_secs(1_700_000_000));
+    /// assert_eq!(payload.aps.stale_time(), Some(UNIX_EPOCH + Duration::from_secs(1_700_000_060)));
+    /// ```
+    pub fn timestamp_time(&self) -> SystemTime {
+        from_unix_secs(self.timestamp)
+    }
+
+    /// Returns when the content becomes outdated, if it does.
+    pub fn stale_time(&self) -> Option<SystemTime> {
+        self.stale_date.map(from_unix_secs)
+    }
+
+    /// Returns when an ended activity is removed from the Lock Screen, if set.
+    pub fn dismissal_time(&self) -> Option<SystemTime> {
+        self.dismissal_date.map(from_unix_secs)
+    }
+
+    /// Returns [`timestamp_time`](LiveActivityAps::timestamp_time) as a `chrono` date-time
+    /// in UTC.
+    #[cfg(feature = "chrono")]
+    pub fn timestamp_utc(&self) -> chrono::DateTime<chrono::Utc> {
+        datetime::utc(self.timestamp_time())
+    }
+
+    /// Returns [`stale_time`](LiveActivityAps::stale_time) as a `chrono` date-time in UTC.
+    #[cfg(feature = "chrono")]
+    pub fn stale_date_utc(&self) -> Option<chrono::DateTime<chrono::Utc>> {
+        self.stale_time().map(datetime::utc)
+    }
+
+    /// Returns [`dismissal_time`](LiveActivityAps::dismissal_time) as a `chrono` date-time
+    /// in UTC.
+    #[cfg(feature = "chrono")]
+    pub fn dismissal_date_utc(&self) -> Option<chrono::DateTime<chrono::Utc>> {
+        self.dismissal_time().map(datetime::utc)
+    }
+
+    /// Returns [`timestamp_time`](LiveActivityAps::timestamp_time) as a `time` date-time
+    /// at UTC.
+    #[cfg(feature = "time")]
+    pub fn timestamp_offset(&self) -> time::OffsetDateTime {
+        datetime::offset(self.timestamp_time())
+    }
+
+    /// Returns [`stale_time`](LiveActivityAps::stale_time) as a `time` date-time at UTC.
+    #[cfg(feature = "time")]
+    pub fn stale_date_offset(&self) -> Option<time::OffsetDateTime> {
+        self.stale_time().map(datetime::offset)
+    }
+
+    /// Returns [`dismissal_time`](LiveActivityAps::dismissal_time) as a `time` date-time
+    /// at UTC.
+    #[cfg(feature = "time")]
+    pub fn dismissal_date_offset(&self) -> Option<time::OffsetDateTime> {
+        self.dismissal_time().map(datetime::offset)
+    }
+}
+
+/// The payload of a Live Activity push, with a content state of type `S`.
+///
+/// # Example
+///
+/// ```rust
+/// use apnrs::LiveActivityPayload;
+/// use serde::Serialize;
+///
+/// #[derive(Serialize)]
+/// struct Score {
+///     home: u32,
+///     away: u32,
+/// }
+///
+/// let payload = LiveActivityPayload::update(Score { home: 2, away: 1 }).timestamp(1_700_000_000);
+/// let json = serde_json::to_value(&payload).unwrap();
+///
+/// assert_eq!(json["aps"]["event"], "update");
+/// assert_eq!(json["aps"]["content-state"]["home"], 2);
+/// assert_eq!(json["aps"]["timestamp"], 1_700_000_000);
+/// ```
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LiveActivityPayload<S> {
+    pub aps: LiveActivityAps<S>,
+}
+
+impl<S: Serialize> LiveActivityPayload<S> {
+    /// Creates a push updating the activity to `content_state`, timestamped now.
+    pub fn update(content_state: S) -> Self {
+        Self::new(LiveActivityEvent::Update, content_state)
+    }
+
+    /// Creates a push ending the activity with a final `content_state`, timestamped now.
+    pub fn end(content_state: S) -> Self {
+        Self::new(LiveActivityEvent::End, content_state)
+    }
+
+    fn new(event: LiveActivityEvent, content_state: S) -> Self {
+        LiveActivityPayload {
+            aps: LiveActivityAps {
+                timestamp: now_secs(),
+                event,
+                content_state,
+                stale_date: None,
+                dismissal_date: None,
+                relevance_score: None,
+            },
+        }
+    }
+
+    /// Sets when the content state was produced, in seconds since the Unix epoch.
+    pub fn timestamp(mut self, timestamp: u64) -> Self {
+        self.aps.timestamp = timestamp;
+        self
+    }
+
+    /// Sets when the content becomes outdated, in seconds since the Unix epoch.
+    pub fn stale_date(mut self, stale_date: u64) -> Self {
+        self.aps.stale_date = Some(stale_date);
+        self
+    }
+
+    /// Sets when the content becomes outdated, as a point in time such as a `SystemTime`,
+    /// `time::OffsetDateTime`, or `chrono::DateTime`.
+    pub fn stale_at(self, time: impl Into<SystemTime>) -> Self {
+        self.stale_date(unix_secs(time.into()))
+    }
+
+    /// Sets the content to become outdated `after` its timestamp, so set the timestamp
+    /// first if it is not now.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use apnrs::LiveActivityPayload;
+    /// use serde_json::json;
+    /// use std::time::Duration;
+    ///
+    /// let payload = LiveActivityPayload::update(json!({ "eta": 12 }))
+    ///     .timestamp(1_700_000_000)
+    ///     .stale_after(Duration::from_secs(15 * 60));
+    /// assert_eq!(payload.aps.stale_date, Some(1_700_000_900));
+    /// ```
+    pub fn stale_after(self, after: Duration) -> Self {
+        let stale_date = self.aps.timestamp.saturating_add(after.as_secs());
+        self.stale_date(stale_date)
+    }
+
+    /// Sets when an ended activity is removed from the Lock Screen, in seconds since the
+    /// Unix epoch.
+    pub fn dismissal_date(mut self, dismissal_date: u64) -> Self {
+        self.aps.dismissal_date = Some(dismissal_date);
+        self
+    }
+
+    /// Sets when an ended activity is removed from the Lock Screen, as a point in time such
+    /// as a `SystemTime`, `time::OffsetDateTime`, or `chrono::DateTime`.
+    pub fn dismiss_at(self, time: impl Into<SystemTime>) -> Self {
+        self.dismissal_date(unix_secs(time.into()))
+    }
+
+    /// Sets an ended activity to be removed from the Lock Screen `after` the push's
+    /// timestamp, so set the timestamp first if it is not now.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use apnrs::LiveActivityPayload;
+    /// use serde_json::json;
+    /// use std::time::{Duration, UNIX_EPOCH};
+    ///
+    /// let payload = LiveActivityPayload::end(json!({ "home": 3, "away": 1 }))
+    ///     .timestamp(1_700_000_000)
+    ///     .dismiss_after(Duration::from_secs(3600));
+    /// assert_eq!(payload.aps.dismissal_date, Some(1_700_003_600));
+    ///
+    /// let payload = payload.dismiss_at(UNIX_EPOCH + Duration::from_secs(1_700_007_200));
+    /// assert_eq!(payload.aps.dismissal_date, Some(1_700_007_200));
+    /// ```
+    pub fn dismiss_after(self, after: Duration) -> Self {
+        let dismissal_date = self.aps.timestamp.saturating_add(after.as_secs());
+        self.dismissal_date(dismissal_date)
+    }
+
+    /// Sets how the activity ranks against the app's other activities.
+    pub fn relevance_score(mut self, score: f64) -> Self {
+        self.aps.relevance_score = Some(score);
+        self
+    }
+}
+
+/// The alert shown when a Live Activity is started remotely.
+///
+/// # Fields
+///
+/// * `title` - The title of the alert.
+/// * `body` - The text of the alert.
+/// * `sound` - The name of the sound file to play, if any.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LiveActivityAlert {
+    pub title: String,
+    pub body: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sound: Option<String>,
+}
+
+/// The `aps` dictionary of a push that starts a Live Activity.
+///
+/// # Fields
+///
+/// * `timestamp` - When the content state was produced, in seconds since the Unix epoch.
+/// * `event` - Always [`LiveActivityEvent::Start`].
+/// * `content_state` - The initial content state, matching the app's `ContentState` type.
+/// * `attributes_type` - The name of the app's `ActivityAttributes` type.
+/// * `attributes` - The activity's static attributes, matching that type.
+/// * `alert` - The alert shown when the activity starts.
+/// * `stale_date` - When the content becomes outdated, in seconds since the Unix epoch.
+/// * `relevance_score` - How the activity ranks against the app's other activities.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LiveActivityStartAps<A, S> {
+    pub timestamp: u64,
+    pub event: LiveActivityEvent,
+    #[serde(rename = "content-state")]
+    pub content_state: S,
+    #[serde(rename = "attributes-type")]
+    pub attributes_type: String,
+    pub attributes: A,
+    pub alert: LiveActivityAlert,
+    #[serde(rename = "stale-date", skip_serializing_if = "Option::is_none")]
+    pub stale_date: Option<u64>,
+    #[serde(rename = "relevance-score", skip_serializing_if = "Option::is_none")]
+    pub relevance_score: Option<f64>,
+}
+
+impl<A, S> LiveActivityStartAps<A, S> {
+    /// Returns when the content state was produced.
+    pub fn timestamp_time(&self) -> SystemTime {
+        from_unix_secs(self.timestamp)
+    }
+
+    /// Returns when the content becomes outdated, if it does.
+    pub fn stale_time(&self) -> Option<SystemTime> {
+        self.stale_date.map(from_unix_secs)
+    }
+
+    /// Returns [`timestamp_time`](LiveActivityStartAps::timestamp_time) as a `chrono`
+    /// date-time in UTC.
+    #[cfg(feature = "chrono")]
+    pub fn timestamp_utc(&self) -> chrono::DateTime<chrono::Utc> {
+        datetime::utc(self.timestamp_time())
+    }
+
+    /// Returns [`stale_time`](LiveActivityStartAps::stale_time) as a `chrono` date-time in
+    /// UTC.
+    #[cfg(feature = "chrono")]
+    pub fn stale_date_utc(&self) -> Option<chrono::DateTime<chrono::Utc>> {
+        self.stale_time().map(datetime::utc)
+    }
+
+    /// Returns [`timestamp_time`](LiveActivityStartAps::timestamp_time) as a `time`
+    /// date-time at UTC.
+    #[cfg(feature = "time")]
+    pub fn timestamp_offset(&self) -> time::OffsetDateTime {
+        datetime::offset(self.timestamp_time())
+    }
+
+    /// Returns [`stale_time`](LiveActivityStartAps::stale_time) as a `time` date-time at
+    /// UTC.
+    #[cfg(feature = "time")]
+    pub fn stale_date_offset(&self) -> Option<time::OffsetDateTime> {
+        self.stale_time().map(datetime::offset)
+    }
+}
+
+/// The payload of a push that starts a Live Activity, with attributes of type `A` and a
+/// content state of type `S`.
+///
+/// Start pushes are sent to the app's push-to-start token, from
+/// `Activity.pushToStartTokenUpdates`, rather than to an activity's push token.
+///
+/// # Example
+///
+/// ```rust
+/// use apnrs::LiveActivityStartPayload;
+/// use serde_json::json;
+///
+/// let payload = LiveActivityStartPayload::new(
+///     "MatchAttributes",
+///     json!({ "homeTeam": "Lions", "awayTeam": "Tigers" }),
+///     json!({ "home": 0, "away": 0 }),
+/// )
+/// .alert("Kick-off", "Lions vs Tigers has started")
+/// .sound("default")
+/// .timestamp(1_700_000_000);
+/// let json = serde_json::to_value(&payload).unwrap();
+///
+/// assert_eq!(json["aps"]["event"], "start");
+/// assert_eq!(json["aps"]["attributes-type"], "MatchAttributes");
+/// assert_eq!(json["aps"]["attributes"]["homeTeam"], "Lions");
+/// assert_eq!(json["aps"]["alert"]["title"], "Kick-off");
+/// ```
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LiveActivityStartPayload<A, S> {
+    pub aps: LiveActivityStartAps<A, S>,
+}
+
+impl<A: Serialize, S: Serialize> LiveActivityStartPayload<A, S> {
+    /// Creates a push starting an activity of `attributes_type` with the given attributes
+    /// and initial `content_state`, timestamped now and with an empty alert.
+    pub fn new(attributes_type: impl Into<String>, attributes: A, content_state: S) -> Self {
+        LiveActivityStartPayload {
+            aps: LiveActivityStartAps {
+                timestamp: now_secs(),
+                event: LiveActivityEvent::Start,
+                content_state,
+                attributes_type: attributes_type.into(),
+                attributes,
+                alert: LiveActivityAlert {
+                    title: String::new(),
+                    body: String::new(),
+                    sound: None,
+                },
+                stale_date: None,
+                relevance_score: None,
+            },
+        }
+    }
+
+    /// Sets the title and text of the alert shown when the activity starts.
+    pub fn alert(mut self, title: impl Into<String>, body: impl Into<String>) -> Self {
+        self.aps.alert.title = title.into();
+        self.aps.alert.body = body.into();
+        self
+    }
+
+    /// Sets the sound played with the alert.
+    pub fn sound(mut self, sound: impl Into<String>) -> Self {
+        self.aps.alert.sound = Some(sound.into());
+        self
+    }
+
+    /// Sets when the content state was produced, in seconds since the Unix epoch.
+    pub fn timestamp(mut self, timestamp: u64) -> Self {
+        self.aps.timestamp = timestamp;
+        self
+    }
+
+    /// Sets when the content becomes outdated, in seconds since the Unix epoch.
+    pub fn stale_date(mut self, stale_date: u64) -> Self {
+        self.aps.stale_date = Some(stale_date);
+        self
+    }
+
+    /// Sets when the content becomes outdated, as a point in time such as a `SystemTime`,
+    /// `time::OffsetDateTime`, or `chrono::DateTime`.
+    pub fn stale_at(self, time: impl Into<SystemTime>) -> Self {
+        self.stale_date(unix_secs(time.into()))
+    }
+
+    /// Sets the content to become outdated `after` its timestamp, so set the timestamp
+    /// first if it is not now.
+    pub fn stale_after(self, after: Duration) -> Self {
+        let stale_date = self.aps.timestamp.saturating_add(after.as_secs());
+        self.stale_date(stale_date)
+    }
+
+    /// Sets how the activity ranks against the app's other activities.
+    pub fn relevance_score(mut self, score: f64) -> Self {
+        self.aps.relevance_score = Some(score);
+        self
+    }
+}
+
+fn now_secs() -> u64 {
+    unix_secs(SystemTime::now())
+}
+
+/// Returns `time` in seconds since the Unix epoch, or 0 if it is earlier.
+fn unix_secs(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
@@ -0,0 +1,426 @@
+//! Server-side localization of alert text with locale fallback.
+
+use crate::{Aps, Template, TemplateError};
+use std::borrow::Borrow;
+use std::collections::HashMap;
+use std::hash::Hash;
+
+/// Alert text in several languages, resolved for a device's locale before sending.
+///
+/// A locale is resolved by walking its fallback chain, dropping one subtag at a time
+/// (`fr-CA` → `fr`) and finally using the default text. Locale tags are matched
+/// case-insensitively, and `_` is treated like `-`.
+///
+/// # Example
+///
+/// ```rust
+/// use apnrs::LocalizedAlert;
+///
+/// let alert = LocalizedAlert::new("Your order has shipped")
+///     .translation("fr", "Votre commande a été expédiée")
+///     .translation("de", "Ihre Bestellung wurde versandt");
+///
+/// let (text, resolution) = alert.resolve("fr-CA");
+/// assert_eq!(text, "Votre commande a été expédiée");
+/// assert_eq!(resolution.used.as_deref(), Some("fr"));
+/// assert!(resolution.is_fallback());
+///
+/// let (text, resolution) = alert.resolve("ja");
+/// assert_eq!(text, "Your order has shipped");
+/// assert_eq!(resolution.used, None);
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct LocalizedAlert {
+    default: String,
+    // Keyed by normalized tag, holding the tag as registered and its text
+    translations: HashMap<String, (String, String)>,
+}
+
+/// Which translation was used for a requested locale.
+///
+/// # Fields
+///
+/// * `requested` - The locale the alert was resolved for.
+/// * `used` - The locale of the translation that was sent, as it was registered, or `None`
+///   if no locale in the fallback chain had a translation and the default text, or the
+///   keys for the device to localize, were sent instead.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LocaleResolution {
+    pub requested: String,
+    pub used: Option<String>,
+}
+
+impl LocaleResolution {
+    /// Returns `true` if the requested locale had no translation of its own, so a less
+    /// specific locale or the default text was sent instead.
+    pub fn is_fallback(&self) -> bool {
+        match &self.used {
+            Some(used) => normalize(used) != normalize(&self.requested),
+            None => true,
+        }
+    }
+}
+
+impl LocalizedAlert {
+    /// Creates an alert with the text sent when no translation matches.
+    pub fn new(default: impl Into<String>) -> Self {
+        LocalizedAlert {
+            default: default.into(),
+            translations: HashMap::new(),
+        }
+    }
+
+    /// Adds the text for `locale`, replacing any earlier text for the same locale.
+    pub fn translation(mut self, locale: impl Into<String>, text: impl Into<String>) -> Self {
+        let locale = locale.into();
+        self.translations
+            .insert(normalize(&locale), (locale, text.into()));
+        self
+    }
+
+    /// Returns the text for `locale`, following its fallback chain, and which locale it
+    /// came from.
+    pub fn resolve(&self, locale: &str) -> (&str, LocaleResolution) {
+        for candidate in fallback_chain(locale) {
+            if let Some((tag, text)) = self.translations.get(&candidate) {
+                return (
+                    text,
+                    LocaleResolution {
+                        requested: locale.to_string(),
+                        used: Some(tag.clone()),
+                    },
+                );
+            }
+        }
+
+        (
+            &self.default,
+            LocaleResolution {
+                requested: locale.to_string(),
+                used: None,
+            },
+        )
+    }
+}
+
+/// The alert of a notification, as text or as keys into the app's `Localizable.strings`.
+///
+/// Built by [`AlertTranslations::alert`], and written into a payload with
+/// [`apply_to`](Alert::apply_to).
+///
+/// # Variants
+///
+/// * `Text` - Text already in the recipient's language, with an optional title.
+/// * `LocKey` - Keys and arguments the device looks up in its own strings files, for
+///   locales the server has no translation for.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Alert {
+    Text {
+        title: Option<String>,
+        body: String,
+    },
+    LocKey {
+        title_loc_key: Option<String>,
+        title_loc_args: Vec<String>,
+        loc_key: String,
+        loc_args: Vec<String>,
+    },
+}
+
+/// The alert keys an [`Alert`] sets, cleared before it is applied.
+const ALERT_KEYS: [&str; 5] = [
+    "title",
+    "title-loc-key",
+    "title-loc-args",
+    "loc-key",
+    "loc-args",
+];
+
+impl Alert {
+    /// Writes the alert into `aps`: the body as its alert text and the title or keys as
+    /// alert fields, replacing any title or keys it already had.
+    pub fn apply_to(&self, aps: &mut Aps) {
+        for key in ALERT_KEYS {
+            aps.alert_fields.remove(key);
+        }
+        match self {
+            Alert::Text { title, body } => {
+                aps.alert = body.clone();
+                if let Some(title) = title {
+                    aps.alert_fields
+                        .insert("title".to_string(), title.clone().into());
+                }
+            }
+            Alert::LocKey {
+                title_loc_key,
+                title_loc_args,
+                loc_key,
+                loc_args,
+            } => {
+                aps.alert = String::new();
+                if let Some(title_loc_key) = title_loc_key {
+                    aps.alert_fields
+                        .insert("title-loc-key".to_string(), title_loc_key.clone().into());
+                    if !title_loc_args.is_empty() {
+                        aps.alert_fields
+                            .insert("title-loc-args".to_string(), title_loc_args.clone().into());
+                    }
+                }
+                aps.alert_fields
+                    .insert("loc-key".to_string(), loc_key.clone().into());
+                if !loc_args.is_empty() {
+                    aps.alert_fields
+                        .insert("loc-args".to_string(), loc_args.clone().into());
+                }
+            }
+        }
+    }
+}
+
+/// A translation table of alert titles and bodies with `{placeholder}`s, resolved for a
+/// recipient's locale and variables into an [`Alert`].
+///
+/// The locale's fallback chain is walked as for [`LocalizedAlert`]. A locale with no
+/// translation gets the loc-key form if keys were set with
+/// [`loc_key`](AlertTranslations::loc_key), so the device localizes the alert itself, and
+/// the default text otherwise. An empty title means the alert has none.
+///
+/// # Example
+///
+/// ```rust
+/// use apnrs::{Alert, AlertTranslations};
+/// use std::collections::HashMap;
+///
+/// let translations = AlertTranslations::new("Order shipped", "{item} is on its way")?
+///     .translation("fr", "Commande expédiée", "{item} est en route")?
+///     .loc_key("ORDER_SHIPPED", ["item"]);
+/// let vars = HashMap::from([("item", "Lamp")]);
+///
+/// let (alert, resolution) = translations.alert("fr-CA", &vars)?;
+/// assert_eq!(
+///     alert,
+///     Alert::Text {
+///         title: Some("Commande expédiée".to_string()),
+///         body: "Lamp est en route".to_string(),
+///     }
+/// );
+/// assert_eq!(resolution.used.as_deref(), Some("fr"));
+///
+/// let (alert, _) = translations.alert("ja", &vars)?;
+/// assert_eq!(
+///     alert,
+///     Alert::LocKey {
+///         title_loc_key: None,
+///         title_loc_args: vec![],
+///         loc_key: "ORDER_SHIPPED".to_string(),
+///         loc_args: vec!["Lamp".to_string()],
+///     }
+/// );
+/// # Ok::<(), apnrs::TemplateError>(())
+/// ```
+#[derive(Debug, Clone)]
+pub struct AlertTranslations {
+    default: AlertText,
+    // Keyed by normalized tag, holding the tag as registered and its text
+    translations: HashMap<String, (String, AlertText)>,
+    title_loc_key: Option<LocKey>,
+    loc_key: Option<LocKey>,
+}
+
+/// A parsed title and body.
+#[derive(Debug, Clone)]
+struct AlertText {
+    title: Option<Template>,
+    body: Template,
+}
+
+/// A key into the app's strings files and the variables filling its arguments, in order.
+#[derive(Debug, Clone)]
+struct LocKey {
+    key: String,
+    args: Vec<String>,
+}
+
+impl AlertTranslations {
+    /// Creates a table with the title and body sent when no translation matches.
+    ///
+    /// # Returns
+    ///
+    /// The table, or a `TemplateError` if the title or body cannot be parsed.
+    pub fn new(title: &str, body: &str) -> Result<Self, TemplateError> {
+        Ok(AlertTranslations {
+            default: AlertText::new(title, body)?,
+            translations: HashMap::new(),
+            title_loc_key: None,
+            loc_key: None,
+        })
+    }
+
+    /// Adds the title and body for `locale`, replacing any earlier ones for the same
+    /// locale.
+    ///
+    /// # Returns
+    ///
+    /// The table, or a `TemplateError` if the title or body cannot be parsed.
+    pub fn translation(
+        mut self,
+        locale: impl Into<String>,
+        title: &str,
+        body: &str,
+    ) -> Result<Self, TemplateError> {
+        let locale = locale.into();
+        let text = AlertText::new(title, body)?;
+        self.translations.insert(normalize(&locale), (locale, text));
+        Ok(self)
+    }
+
+    /// Sends locales with no translation the body as `loc-key`, with the values of the
+    /// variables named in `args` as its `loc-args`, instead of the default text.
+    pub fn loc_key<I, S>(mut self, key: impl Into<String>, args: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.loc_key = Some(LocKey::new(key, args));
+        self
+    }
+
+    /// Sends the title as `title-loc-key`, with the values of the variables named in `args`
+    /// as its `title-loc-args`, whenever the body is sent as a `loc-key`.
+    pub fn title_loc_key<I, S>(mut self, key: impl Into<String>, args: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.title_loc_key = Some(LocKey::new(key, args));
+        self
+    }
+
+    /// Resolves the alert for `locale`, filling its placeholders or arguments from `vars`.
+    ///
+    /// # Returns
+    ///
+    /// The alert and which locale it came from, or `TemplateError::MissingVariables` if
+    /// `vars` has no value for a placeholder or argument of the alert.
+    pub fn alert<K, V>(
+        &self,
+        locale: &str,
+        vars: &HashMap<K, V>,
+    ) -> Result<(Alert, LocaleResolution), TemplateError>
+    where
+        K: Borrow<str> + Hash + Eq,
+        V: AsRef<str>,
+    {
+        let found = fallback_chain(locale)
+            .into_iter()
+            .find_map(|candidate| self.translations.get(&candidate));
+        let resolution = LocaleResolution {
+            requested: locale.to_string(),
+            used: found.map(|(tag, _)| tag.clone()),
+        };
+        let alert = match (found, &self.loc_key) {
+            (Some((_, text)), _) => text.render(vars)?,
+            (None, Some(loc_key)) => Alert::LocKey {
+                title_loc_key: self.title_loc_key.as_ref().map(|title| title.key.clone()),
+                title_loc_args: match &self.title_loc_key {
+                    Some(title) => title.args(vars)?,
+                    None => Vec::new(),
+                },
+                loc_key: loc_key.key.clone(),
+                loc_args: loc_key.args(vars)?,
+            },
+            (None, None) => self.default.render(vars)?,
+        };
+        Ok((alert, resolution))
+    }
+}
+
+impl AlertText {
+    fn new(title: &str, body: &str) -> Result<Self, TemplateError> {
+        let title = match title.is_empty() {
+            true => None,
+            false => Some(Template::new(title)?),
+        };
+        Ok(AlertText {
+            title,
+            body: Template::new(body)?,
+        })
+    }
+
+    fn render<K, V>(&self, vars: &HashMap<K, V>) -> Result<Alert, TemplateError>
+    where
+        K: Borrow<str> + Hash + Eq,
+        V: AsRef<str>,
+    {
+        let title = match &self.title {
+            Some(title) => Some(title.render(vars)?.aps.alert),
+            None => None,
+        };
+        Ok(Alert::Text {
+            title,
+            body: self.body.render(vars)?.aps.alert,
+        })
+    }
+}
+
+impl LocKey {
+    fn new<I, S>(key: impl Into<String>, args: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        LocKey {
+            key: key.into(),
+            args: args.into_iter().map(Into::into).collect(),
+        }
+    }
+
+    /// Returns the values of the arguments, in order.
+    fn args<K, V>(&self, vars: &HashMap<K, V>) -> Result<Vec<String>, TemplateError>
+    where
+        K: Borrow<str> + Hash + Eq,
+        V: AsRef<str>,
+    {
+        let missing: Vec<String> = self
+            .args
+            .iter()
+            .filter(|name| !vars.contains_key(name.as_str()))
+            .cloned()
+            .collect();
+        if !missing.is_empty() {
+            return Err(TemplateError::MissingVariables { names: missing });
+        }
+        Ok(self
+            .args
+            .iter()
+            .map(|name| vars[name.as_str()].as_ref().to_string())
+            .collect())
+    }
+}
+
+/// Returns the normalized locales tried for `locale`, most specific first.
+///
+/// # Example
+///
+/// ```rust
+/// use apnrs::fallback_chain;
+///
+/// assert_eq!(fallback_chain("zh_Hant_TW"), vec!["zh-hant-tw", "zh-hant", "zh"]);
+/// ```
+pub fn fallback_chain(locale: &str) -> Vec<String> {
+    let mut current = normalize(locale);
+    let mut chain = Vec::new();
+    while !current.is_empty() {
+        chain.push(current.clone());
+        match current.rfind('-') {
+            Some(index) => current.truncate(index),
+            None => break,
+        }
+    }
+    chain
+}
+
+/// Lowercases a locale tag and uses `-` as its only separator.
+fn normalize(locale: &str) -> String {
+    locale.trim().replace('_', "-").to_ascii_lowercase()
+}
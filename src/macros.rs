@@ -0,0 +1,251 @@
+//! The `apns_payload!` macro.
+
+/// Builds a payload as a `serde_json::Value` from JSON-like syntax, checking at compile time
+/// that it has an `aps` dictionary and that every key of `aps`, its `alert`, and its `sound`
+/// is one APNs reads.
+///
+/// Keys of `aps` and its dictionaries are written either as APNs spells them (`"thread-id"`)
+/// or as Rust identifiers (`thread_id`), which are sent with hyphens. Other top-level keys are
+/// custom data, sent as written. Values follow [`serde_json::json!`], so they may be literals,
+/// nested JSON, or any expression implementing `Serialize`.
+///
+/// The result suits payloads an [`ApnsPayload`](crate::ApnsPayload) cannot hold, such as an
+/// alert with a title; send it with [`ApnsClient::prepare`](crate::ApnsClient::prepare) and
+/// [`send_prepared`](crate::ApnsClient::send_prepared).
+///
+/// # Example
+///
+/// ```rust
+/// use apnrs::apns_payload;
+///
+/// let sender = "Ada";
+/// let payload = apns_payload!({
+///     aps: {
+///         alert: { title: "Your move", body: format!("{} played e4", sender) },
+///         badge: 1,
+///         thread_id: "game-42",
+///     },
+///     "gameId": 42,
+/// });
+/// assert_eq!(payload["aps"]["alert"]["body"], "Ada played e4");
+/// assert_eq!(payload["aps"]["thread-id"], "game-42");
+/// assert_eq!(payload["gameId"], 42);
+/// ```
+///
+/// Misspelled `aps` keys and payloads without `aps` do not compile:
+///
+/// ```rust,compile_fail
+/// let payload = apnrs::apns_payload!({ aps: { alert: "Hi", bagde: 1 } });
+/// ```
+///
+/// ```rust,compile_fail
+/// let payload = apnrs::apns_payload!({ "gameId": 42 });
+/// ```
+#[macro_export]
+macro_rules! apns_payload {
+    ({ $($body:tt)* }) => {{
+        let mut payload = $crate::__serde_json::Map::new();
+        $crate::__apns_payload!(@top payload (no_aps) $($body)*);
+        $crate::__serde_json::Value::Object(payload)
+    }};
+}
+
+/// Implements [`apns_payload!`], one key at a time.
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __apns_payload {
+    // The top level: `aps` and custom data. The state records whether `aps` was seen.
+    (@top $map:ident (no_aps)) => {
+        ::core::compile_error!("an APNs payload needs an `aps` dictionary");
+    };
+    (@top $map:ident (aps)) => {};
+    (@top $map:ident ($seen:ident) aps : { $($aps:tt)* } $(, $($rest:tt)*)?) => {
+        $crate::__apns_payload!(@dict aps $map ("aps") { $($aps)* });
+        $crate::__apns_payload!(@top $map (aps) $($($rest)*)?);
+    };
+    (@top $map:ident ($seen:ident) "aps" : { $($aps:tt)* } $(, $($rest:tt)*)?) => {
+        $crate::__apns_payload!(@dict aps $map ("aps") { $($aps)* });
+        $crate::__apns_payload!(@top $map (aps) $($($rest)*)?);
+    };
+    (@top $map:ident ($seen:ident) aps : $($rest:tt)*) => {
+        ::core::compile_error!("`aps` must be a dictionary");
+    };
+    (@top $map:ident ($seen:ident) "aps" : $($rest:tt)*) => {
+        ::core::compile_error!("`aps` must be a dictionary");
+    };
+    (@top $map:ident ($seen:ident) $key:literal : $($rest:tt)*) => {
+        $crate::__apns_payload!(@value top $map ($seen) ($key) [] $($rest)*);
+    };
+    (@top $map:ident ($seen:ident) $key:ident : $($rest:tt)*) => {
+        $crate::__apns_payload!(@value top $map ($seen) (::core::stringify!($key)) [] $($rest)*);
+    };
+
+    // The `aps` dictionary, whose `alert` and `sound` may be dictionaries themselves.
+    (@aps $map:ident ()) => {};
+    (@aps $map:ident () alert : { $($alert:tt)* } $(, $($rest:tt)*)?) => {
+        $crate::__apns_payload!(@dict alert $map ("alert") { $($alert)* });
+        $crate::__apns_payload!(@aps $map () $($($rest)*)?);
+    };
+    (@aps $map:ident () "alert" : { $($alert:tt)* } $(, $($rest:tt)*)?) => {
+        $crate::__apns_payload!(@dict alert $map ("alert") { $($alert)* });
+        $crate::__apns_payload!(@aps $map () $($($rest)*)?);
+    };
+    (@aps $map:ident () sound : { $($sound:tt)* } $(, $($rest:tt)*)?) => {
+        $crate::__apns_payload!(@dict sound $map ("sound") { $($sound)* });
+        $crate::__apns_payload!(@aps $map () $($($rest)*)?);
+    };
+    (@aps $map:ident () "sound" : { $($sound:tt)* } $(, $($rest:tt)*)?) => {
+        $crate::__apns_payload!(@dict sound $map ("sound") { $($sound)* });
+        $crate::__apns_payload!(@aps $map () $($($rest)*)?);
+    };
+    (@aps $map:ident () $key:tt : $($rest:tt)*) => {
+        $crate::__apns_payload!(@value aps $map () ($crate::__apns_payload!(@aps_key $key)) [] $($rest)*);
+    };
+
+    // The `alert` and `sound` dictionaries.
+    (@alert $map:ident ()) => {};
+    (@alert $map:ident () $key:tt : $($rest:tt)*) => {
+        $crate::__apns_payload!(@value alert $map () ($crate::__apns_payload!(@alert_key $key)) [] $($rest)*);
+    };
+    (@sound $map:ident ()) => {};
+    (@sound $map:ident () $key:tt : $($rest:tt)*) => {
+        $crate::__apns_payload!(@value sound $map () ($crate::__apns_payload!(@sound_key $key)) [] $($rest)*);
+    };
+
+    // Inserts the dictionary `{ ... }` under `$key`, filling it at `$level`.
+    (@dict $level:ident $map:ident ($key:expr) { $($body:tt)* }) => {
+        $map.insert(::std::string::String::from($key), {
+            let mut dict = $crate::__serde_json::Map::new();
+            $crate::__apns_payload!(@$level dict () $($body)*);
+            $crate::__serde_json::Value::Object(dict)
+        });
+    };
+
+    // Collects a value's tokens up to the next comma, inserts it, and continues at `$level`.
+    (@value $level:ident $map:ident ($($state:tt)*) ($($key:tt)*) [] , $($rest:tt)*) => {
+        ::core::compile_error!(::core::concat!(
+            "missing value for `",
+            ::core::stringify!($($key)*),
+            "`"
+        ));
+    };
+    (@value $level:ident $map:ident ($($state:tt)*) ($($key:tt)*) [$($value:tt)+] , $($rest:tt)*) => {
+        $map.insert(
+            ::std::string::String::from($($key)*),
+            $crate::__serde_json::json!($($value)+),
+        );
+        $crate::__apns_payload!(@$level $map ($($state)*) $($rest)*);
+    };
+    (@value $level:ident $map:ident ($($state:tt)*) ($($key:tt)*) [$($value:tt)+]) => {
+        $map.insert(
+            ::std::string::String::from($($key)*),
+            $crate::__serde_json::json!($($value)+),
+        );
+        $crate::__apns_payload!(@$level $map ($($state)*));
+    };
+    (@value $level:ident $map:ident ($($state:tt)*) ($($key:tt)*) [$($value:tt)*] $next:tt $($rest:tt)*) => {
+        $crate::__apns_payload!(@value $level $map ($($state)*) ($($key)*) [$($value)* $next] $($rest)*);
+    };
+    (@value $level:ident $map:ident ($($state:tt)*) ($($key:tt)*) []) => {
+        ::core::compile_error!(::core::concat!(
+            "missing value for `",
+            ::core::stringify!($($key)*),
+            "`"
+        ))
+    };
+
+    // The keys APNs reads in `aps`.
+    (@aps_key badge) => { "badge" };
+    (@aps_key "badge") => { "badge" };
+    (@aps_key category) => { "category" };
+    (@aps_key "category") => { "category" };
+    (@aps_key thread_id) => { "thread-id" };
+    (@aps_key "thread-id") => { "thread-id" };
+    (@aps_key content_available) => { "content-available" };
+    (@aps_key "content-available") => { "content-available" };
+    (@aps_key mutable_content) => { "mutable-content" };
+    (@aps_key "mutable-content") => { "mutable-content" };
+    (@aps_key target_content_id) => { "target-content-id" };
+    (@aps_key "target-content-id") => { "target-content-id" };
+    (@aps_key interruption_level) => { "interruption-level" };
+    (@aps_key "interruption-level") => { "interruption-level" };
+    (@aps_key relevance_score) => { "relevance-score" };
+    (@aps_key "relevance-score") => { "relevance-score" };
+    (@aps_key filter_criteria) => { "filter-criteria" };
+    (@aps_key "filter-criteria") => { "filter-criteria" };
+    (@aps_key url_args) => { "url-args" };
+    (@aps_key "url-args") => { "url-args" };
+    (@aps_key timestamp) => { "timestamp" };
+    (@aps_key "timestamp") => { "timestamp" };
+    (@aps_key event) => { "event" };
+    (@aps_key "event") => { "event" };
+    (@aps_key content_state) => { "content-state" };
+    (@aps_key "content-state") => { "content-state" };
+    (@aps_key stale_date) => { "stale-date" };
+    (@aps_key "stale-date") => { "stale-date" };
+    (@aps_key dismissal_date) => { "dismissal-date" };
+    (@aps_key "dismissal-date") => { "dismissal-date" };
+    (@aps_key attributes_type) => { "attributes-type" };
+    (@aps_key "attributes-type") => { "attributes-type" };
+    (@aps_key attributes) => { "attributes" };
+    (@aps_key "attributes") => { "attributes" };
+    (@aps_key input_push_channel) => { "input-push-channel" };
+    (@aps_key "input-push-channel") => { "input-push-channel" };
+    (@aps_key input_push_token) => { "input-push-token" };
+    (@aps_key "input-push-token") => { "input-push-token" };
+    (@aps_key alert) => { "alert" };
+    (@aps_key "alert") => { "alert" };
+    (@aps_key sound) => { "sound" };
+    (@aps_key "sound") => { "sound" };
+    (@aps_key $key:tt) => {
+        ::core::compile_error!(::core::concat!("unknown aps key `", ::core::stringify!($key), "`"))
+    };
+
+    // The keys APNs reads in an alert dictionary.
+    (@alert_key title) => { "title" };
+    (@alert_key "title") => { "title" };
+    (@alert_key subtitle) => { "subtitle" };
+    (@alert_key "subtitle") => { "subtitle" };
+    (@alert_key body) => { "body" };
+    (@alert_key "body") => { "body" };
+    (@alert_key launch_image) => { "launch-image" };
+    (@alert_key "launch-image") => { "launch-image" };
+    (@alert_key title_loc_key) => { "title-loc-key" };
+    (@alert_key "title-loc-key") => { "title-loc-key" };
+    (@alert_key title_loc_args) => { "title-loc-args" };
+    (@alert_key "title-loc-args") => { "title-loc-args" };
+    (@alert_key subtitle_loc_key) => { "subtitle-loc-key" };
+    (@alert_key "subtitle-loc-key") => { "subtitle-loc-key" };
+    (@alert_key subtitle_loc_args) => { "subtitle-loc-args" };
+    (@alert_key "subtitle-loc-args") => { "subtitle-loc-args" };
+    (@alert_key loc_key) => { "loc-key" };
+    (@alert_key "loc-key") => { "loc-key" };
+    (@alert_key loc_args) => { "loc-args" };
+    (@alert_key "loc-args") => { "loc-args" };
+    (@alert_key summary_arg) => { "summary-arg" };
+    (@alert_key "summary-arg") => { "summary-arg" };
+    (@alert_key summary_arg_count) => { "summary-arg-count" };
+    (@alert_key "summary-arg-count") => { "summary-arg-count" };
+    (@alert_key $key:tt) => {
+        ::core::compile_error!(::core::concat!("unknown alert key `", ::core::stringify!($key), "`"))
+    };
+
+    // The keys APNs reads in a sound dictionary.
+    (@sound_key critical) => { "critical" };
+    (@sound_key "critical") => { "critical" };
+    (@sound_key name) => { "name" };
+    (@sound_key "name") => { "name" };
+    (@sound_key volume) => { "volume" };
+    (@sound_key "volume") => { "volume" };
+    (@sound_key $key:tt) => {
+        ::core::compile_error!(::core::concat!("unknown sound key `", ::core::stringify!($key), "`"))
+    };
+
+    (@$level:ident $map:ident ($($state:tt)*) $($unexpected:tt)+) => {
+        ::core::compile_error!(::core::concat!(
+            "expected `key: value` in the payload, found `",
+            ::core::stringify!($($unexpected)+),
+            "`"
+        ));
+    };
+}
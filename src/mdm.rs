@@ -0,0 +1,38 @@
+//! Mobile Device Management pushes.
+
+use serde::{Deserialize, Serialize};
+
+/// An MDM push, asking a managed device to contact its MDM server.
+///
+/// The payload carries only the device's push magic, the string it reported in its
+/// `TokenUpdate` check-in. MDM pushes must be sent with a certificate; see
+/// [`ApnsClient::send_mdm`](crate::ApnsClient::send_mdm).
+///
+/// # Fields
+///
+/// * `push_magic` - The device's push magic, serialized as `mdm`.
+///
+/// # Example
+///
+/// ```rust
+/// use apnrs::MdmNotification;
+///
+/// let wake = MdmNotification::new("8A2C7E4D-6B0F-4E1A-9C3D-2F5B7A1E0C6D");
+/// let json = serde_json::to_value(&wake).unwrap();
+///
+/// assert_eq!(json, serde_json::json!({ "mdm": "8A2C7E4D-6B0F-4E1A-9C3D-2F5B7A1E0C6D" }));
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MdmNotification {
+    #[serde(rename = "mdm")]
+    pub push_magic: String,
+}
+
+impl MdmNotification {
+    /// Creates an MDM push for the device with the given push magic.
+    pub fn new(push_magic: impl Into<String>) -> Self {
+        MdmNotification {
+            push_magic: push_magic.into(),
+        }
+    }
+}
@@ -0,0 +1,568 @@
+//! Notifications for push types with their own payload rules, such as VoIP, background,
+//! location, complication, File Provider, Live Activity, and Push to Talk pushes.
+
+use crate::live_activity::LiveActivityPayload;
+use crate::push_type::PushType;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::error::Error;
+use std::fmt;
+
+/// Describes why a notification does not follow the rules of its push type.
+///
+/// # Variants
+///
+/// * `NotAnObject` - The payload is not a JSON object.
+/// * `AlertNotAllowed` - The payload has an `aps.alert`, which the push type does not allow.
+/// * `TooLarge` - The serialized payload is larger than the push type allows.
+/// * `ApsKeyNotAllowed` - The `aps` dictionary has a key the push type does not allow, such as
+///   `badge` or `sound` in a background push.
+/// * `MissingContentAvailable` - A background or complication push lacks
+///   `"content-available": 1`.
+/// * `PriorityNotAllowed` - The priority is not allowed for the push type.
+/// * `MissingKey` - The payload lacks a key the push type requires, such as
+///   `container-identifier` in a File Provider push.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum NotificationError {
+    NotAnObject,
+    AlertNotAllowed {
+        push_type: PushType,
+    },
+    TooLarge {
+        push_type: PushType,
+        size: usize,
+        limit: usize,
+    },
+    ApsKeyNotAllowed {
+        push_type: PushType,
+        key: String,
+    },
+    MissingContentAvailable,
+    PriorityNotAllowed {
+        push_type: PushType,
+        priority: Priority,
+    },
+    MissingKey {
+        push_type: PushType,
+        key: &'static str,
+    },
+}
+
+impl fmt::Display for NotificationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            NotificationError::NotAnObject => write!(f, "payload is not a JSON object"),
+            NotificationError::AlertNotAllowed { push_type } => {
+                write!(f, "{} pushes must not contain aps.alert", push_type)
+            }
+            NotificationError::TooLarge {
+                push_type,
+                size,
+                limit,
+            } => write!(
+                f,
+                "payload is {} bytes, the maximum for {} pushes is {}",
+                size, push_type, limit
+            ),
+            NotificationError::ApsKeyNotAllowed { push_type, key } => {
+                write!(f, "{} pushes must not contain aps.{}", push_type, key)
+            }
+            NotificationError::MissingContentAvailable => write!(
+                f,
+                "background and complication pushes must contain \"content-available\": 1"
+            ),
+            NotificationError::PriorityNotAllowed {
+                push_type,
+                priority,
+            } => write!(
+                f,
+                "{} pushes must not be sent with priority {}",
+                push_type,
+                priority.as_str()
+            ),
+            NotificationError::MissingKey { push_type, key } => {
+                write!(f, "{} pushes must contain \"{}\"", push_type, key)
+            }
+        }
+    }
+}
+
+impl Error for NotificationError {}
+
+/// The delivery priority of a notification, sent as the `apns-priority` header.
+///
+/// # Variants
+///
+/// * `Immediate` - `10`, delivered immediately. The default for alerts.
+/// * `Normal` - `5`, delivered at a time that conserves the device's power.
+/// * `Low` - `1`, delivered when it suits the device, and possibly not at all.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Priority {
+    Immediate,
+    Normal,
+    Low,
+}
+
+impl Priority {
+    /// Returns the header value for this priority.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Priority::Immediate => "10",
+            Priority::Normal => "5",
+            Priority::Low => "1",
+        }
+    }
+
+    /// Returns the priority Apple recommends for `payload` sent as `push_type`.
+    ///
+    /// Background pushes, and alert pushes that only set `content-available` without an
+    /// alert, badge, or sound, get `Normal`; APNs rejects or throttles them at `Immediate`.
+    /// Alerts, VoIP, Push to Talk, and location pushes get `Immediate`, and Live Activity
+    /// updates get `Immediate` if they alert and `Normal` otherwise, so they do not use up
+    /// the activity's update budget. Complication, File Provider, and MDM pushes get `None`,
+    /// leaving the choice to APNs.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use apnrs::{Priority, PushType};
+    /// use serde_json::json;
+    ///
+    /// let silent = json!({ "aps": { "content-available": 1 }, "sync": "inbox" });
+    /// assert_eq!(Priority::recommended(None, &silent), Some(Priority::Normal));
+    ///
+    /// let alert = json!({ "aps": { "alert": "New message", "content-available": 1 } });
+    /// assert_eq!(Priority::recommended(None, &alert), Some(Priority::Immediate));
+    ///
+    /// let update = json!({ "aps": { "event": "update", "content-state": {} } });
+    /// assert_eq!(
+    ///     Priority::recommended(Some(PushType::LiveActivity), &update),
+    ///     Some(Priority::Normal)
+    /// );
+    /// ```
+    pub fn recommended(push_type: Option<PushType>, payload: &Value) -> Option<Priority> {
+        let aps = payload.get("aps");
+        let has = |key: &str| aps.and_then(|aps| aps.get(key)).is_some_and(is_set);
+        match push_type.unwrap_or(PushType::Alert) {
+            PushType::Background => Some(Priority::Normal),
+            PushType::Alert if !(has("alert") || has("badge") || has("sound")) => {
+                match has("content-available") {
+                    true => Some(Priority::Normal),
+                    false => Some(Priority::Immediate),
+                }
+            }
+            PushType::LiveActivity if !has("alert") => Some(Priority::Normal),
+            PushType::Alert
+            | PushType::LiveActivity
+            | PushType::Voip
+            | PushType::PushToTalk
+            | PushType::Location => Some(Priority::Immediate),
+            PushType::Complication | PushType::FileProvider | PushType::Mdm => None,
+        }
+    }
+}
+
+/// Returns `false` for a key that is present but does nothing, such as an empty alert or a
+/// `content-available` of 0.
+fn is_set(value: &Value) -> bool {
+    match value {
+        Value::Null | Value::Bool(false) => false,
+        Value::String(text) => !text.is_empty(),
+        Value::Number(number) => number.as_f64() != Some(0.0),
+        _ => true,
+    }
+}
+
+/// A notification of a specific push type, whose payload is checked against that type's
+/// rules before it is sent.
+///
+/// # Fields
+///
+/// * `push_type` - The push type, sent as `apns-push-type` and used to suffix the topic.
+/// * `priority` - The priority sent as `apns-priority`, or `None` to let APNs use its default.
+/// * `payload` - The JSON payload.
+///
+/// # Example
+///
+/// ```rust
+/// use apnrs::{Notification, NotificationError, PushType};
+/// use serde_json::json;
+///
+/// let call = Notification::voip(json!({ "caller": "Anna", "call_id": "42" }));
+/// assert_eq!(call.push_type, PushType::Voip);
+/// assert!(call.validate().is_ok());
+///
+/// let call = Notification::voip(json!({ "aps": { "alert": "Incoming call" } }));
+/// assert_eq!(
+///     call.validate(),
+///     Err(NotificationError::AlertNotAllowed { push_type: PushType::Voip })
+/// );
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct Notification {
+    pub push_type: PushType,
+    pub priority: Option<Priority>,
+    pub payload: Value,
+}
+
+impl Notification {
+    /// Creates an alert showing `title` and `body`, sent with the `alert` push type at
+    /// [`Priority::Immediate`].
+    ///
+    /// Add your own keys with [`custom`](Notification::custom); for badges, sounds, and the
+    /// rest of the `aps` dictionary, use an [`ApnsPayload`](crate::ApnsPayload) instead.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use apnrs::{Notification, Priority, PushType};
+    /// use serde_json::json;
+    ///
+    /// let alert = Notification::alert("Order shipped", "Your order is on its way");
+    ///
+    /// assert_eq!(alert.push_type, PushType::Alert);
+    /// assert_eq!(alert.priority, Some(Priority::Immediate));
+    /// assert_eq!(
+    ///     alert.payload,
+    ///     json!({ "aps": { "alert": { "title": "Order shipped", "body": "Your order is on its way" } } })
+    /// );
+    /// assert!(alert.validate().is_ok());
+    /// ```
+    pub fn alert(title: &str, body: &str) -> Self {
+        Notification {
+            push_type: PushType::Alert,
+            priority: Some(Priority::Immediate),
+            payload: json!({ "aps": { "alert": { "title": title, "body": body } } }),
+        }
+    }
+
+    /// Creates a PushKit VoIP notification carrying `custom_data`, which must be a JSON
+    /// object.
+    ///
+    /// VoIP pushes are sent with the `voip` push type under the `.voip` topic, may be up to
+    /// 5 KB, and must not contain an alert; the app reports the call to CallKit itself.
+    pub fn voip(custom_data: Value) -> Self {
+        Notification {
+            push_type: PushType::Voip,
+            priority: None,
+            payload: custom_data,
+        }
+    }
+
+    /// Creates a silent background push that wakes the app to fetch new content.
+    ///
+    /// The payload is `{"aps": {"content-available": 1}}`, sent with the `background` push
+    /// type at [`Priority::Normal`], as Apple requires. Add your own keys with
+    /// [`custom`](Notification::custom).
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use apnrs::{Notification, Priority};
+    /// use serde_json::json;
+    ///
+    /// let refresh = Notification::background().custom("inbox", json!(3));
+    ///
+    /// assert_eq!(refresh.priority, Some(Priority::Normal));
+    /// assert_eq!(refresh.payload, json!({ "aps": { "content-available": 1 }, "inbox": 3 }));
+    /// assert!(refresh.validate().is_ok());
+    /// ```
+    pub fn background() -> Self {
+        Notification {
+            push_type: PushType::Background,
+            priority: Some(Priority::Normal),
+            payload: json!({ "aps": { "content-available": 1 } }),
+        }
+    }
+
+    /// Creates a location query push, which launches the app's Location Push Service
+    /// Extension to report the device's location.
+    ///
+    /// Location pushes are sent with the `location` push type under the `.location-query`
+    /// topic at [`Priority::Immediate`]; they may use [`Priority::Normal`] but not
+    /// [`Priority::Low`], and must not contain an alert. Add your own keys with
+    /// [`custom`](Notification::custom).
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use apnrs::{Notification, NotificationError, Priority, PushType};
+    /// use serde_json::json;
+    ///
+    /// let query = Notification::location().custom("request_id", json!("r-17"));
+    /// assert_eq!(query.push_type.topic_for("com.example.app"), "com.example.app.location-query");
+    /// assert!(query.validate().is_ok());
+    ///
+    /// let query = Notification::location().priority(Priority::Low);
+    /// assert_eq!(
+    ///     query.validate(),
+    ///     Err(NotificationError::PriorityNotAllowed {
+    ///         push_type: PushType::Location,
+    ///         priority: Priority::Low,
+    ///     })
+    /// );
+    /// ```
+    pub fn location() -> Self {
+        Notification {
+            push_type: PushType::Location,
+            priority: Some(Priority::Immediate),
+            payload: json!({}),
+        }
+    }
+
+    /// Creates a watchOS complication push, which wakes the app to update its complications.
+    ///
+    /// The payload is `{"aps": {"content-available": 1}}`, sent with the `complication` push
+    /// type under the `.complication` topic. Add your own keys with
+    /// [`custom`](Notification::custom).
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use apnrs::Notification;
+    /// use serde_json::json;
+    ///
+    /// let update = Notification::complication().custom("steps", json!(8214));
+    ///
+    /// assert_eq!(update.push_type.topic_for("com.example.app"), "com.example.app.complication");
+    /// assert_eq!(update.payload, json!({ "aps": { "content-available": 1 }, "steps": 8214 }));
+    /// assert!(update.validate().is_ok());
+    /// ```
+    pub fn complication() -> Self {
+        Notification {
+            push_type: PushType::Complication,
+            priority: None,
+            payload: json!({ "aps": { "content-available": 1 } }),
+        }
+    }
+
+    /// Creates a File Provider push, which tells a File Provider extension that the content
+    /// of a container has changed.
+    ///
+    /// The payload is `{"container-identifier": ..., "domain": ...}`, sent with the
+    /// `fileprovider` push type under the `.pushkit.fileprovider` topic. `domain` is the
+    /// identifier of the `NSFileProviderDomain` to signal, and can be left out for apps with a
+    /// single domain.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use apnrs::Notification;
+    /// use serde_json::json;
+    ///
+    /// let change = Notification::file_provider(
+    ///     "NSFileProviderWorkingSetContainerItemIdentifier",
+    ///     Some("personal"),
+    /// );
+    ///
+    /// assert_eq!(
+    ///     change.push_type.topic_for("com.example.app"),
+    ///     "com.example.app.pushkit.fileprovider"
+    /// );
+    /// assert_eq!(
+    ///     change.payload,
+    ///     json!({
+    ///         "container-identifier": "NSFileProviderWorkingSetContainerItemIdentifier",
+    ///         "domain": "personal",
+    ///     })
+    /// );
+    /// assert!(change.validate().is_ok());
+    /// ```
+    pub fn file_provider(container_identifier: &str, domain: Option<&str>) -> Self {
+        let mut payload = json!({ "container-identifier": container_identifier });
+        if let Some(domain) = domain {
+            payload["domain"] = json!(domain);
+        }
+        Notification {
+            push_type: PushType::FileProvider,
+            priority: None,
+            payload,
+        }
+    }
+
+    /// Creates a push updating a Live Activity to `content_state`, the JSON of the app's
+    /// `ContentState`, timestamped now.
+    ///
+    /// Live Activity pushes are sent with the `liveactivity` push type under the
+    /// `.push-type.liveactivity` topic. The priority is [`Priority::Normal`], which does not
+    /// count against the activity's budget of high-priority updates; raise it with
+    /// [`priority`](Notification::priority) for updates the user must see at once. For stale
+    /// and dismissal dates or ending the activity, use a
+    /// [`LiveActivityPayload`](crate::LiveActivityPayload) instead.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use apnrs::{Notification, Priority};
+    /// use serde_json::json;
+    ///
+    /// let score = Notification::live_activity(json!({ "home": 2, "away": 1 }));
+    ///
+    /// assert_eq!(
+    ///     score.push_type.topic_for("com.example.app"),
+    ///     "com.example.app.push-type.liveactivity"
+    /// );
+    /// assert_eq!(score.priority, Some(Priority::Normal));
+    /// assert_eq!(score.payload["aps"]["event"], "update");
+    /// assert_eq!(score.payload["aps"]["content-state"], json!({ "home": 2, "away": 1 }));
+    /// ```
+    pub fn live_activity(content_state: Value) -> Self {
+        let payload = serde_json::to_value(LiveActivityPayload::update(content_state))
+            .expect("a Live Activity payload of JSON serializes");
+        Notification {
+            push_type: PushType::LiveActivity,
+            priority: Some(Priority::Normal),
+            payload,
+        }
+    }
+
+    /// Creates a Push to Talk notification carrying `custom_data`, which must be a JSON
+    /// object, such as the active speaker of a channel.
+    ///
+    /// Push to Talk pushes (iOS 16 and later) are sent with the `pushtotalk` push type under
+    /// the `.voip-ptt` topic at [`Priority::Immediate`], and must not contain an alert; the
+    /// system hands the payload to the app's `PTChannelManager` delegate.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use apnrs::{Notification, Priority};
+    /// use serde_json::json;
+    ///
+    /// let speaker = Notification::push_to_talk(json!({ "activeSpeaker": "Anna" }));
+    ///
+    /// assert_eq!(speaker.push_type.topic_for("com.example.app"), "com.example.app.voip-ptt");
+    /// assert_eq!(speaker.priority, Some(Priority::Immediate));
+    /// assert!(speaker.validate().is_ok());
+    /// ```
+    pub fn push_to_talk(custom_data: Value) -> Self {
+        Notification {
+            push_type: PushType::PushToTalk,
+            priority: Some(Priority::Immediate),
+            payload: custom_data,
+        }
+    }
+
+    /// Sets a custom key at the root of the payload, outside the `aps` dictionary.
+    ///
+    /// Has no effect if the payload is not a JSON object.
+    pub fn custom(mut self, key: impl Into<String>, value: Value) -> Self {
+        if let Some(root) = self.payload.as_object_mut() {
+            root.insert(key.into(), value);
+        }
+        self
+    }
+
+    /// Sets the priority sent as `apns-priority`.
+    pub fn priority(mut self, priority: Priority) -> Self {
+        self.priority = Some(priority);
+        self
+    }
+
+    /// Checks the payload against the rules of the push type.
+    ///
+    /// # Returns
+    ///
+    /// `Ok(())` if the notification can be sent, or a `NotificationError` describing the
+    /// first problem.
+    pub fn validate(&self) -> Result<(), NotificationError> {
+        if !self.payload.is_object() {
+            return Err(NotificationError::NotAnObject);
+        }
+        let aps = self.payload.get("aps");
+        if matches!(
+            self.push_type,
+            PushType::Voip | PushType::Location | PushType::PushToTalk
+        ) && aps.and_then(|aps| aps.get("alert")).is_some()
+        {
+            return Err(NotificationError::AlertNotAllowed {
+                push_type: self.push_type,
+            });
+        }
+        if self.push_type == PushType::Location && self.priority == Some(Priority::Low) {
+            return Err(NotificationError::PriorityNotAllowed {
+                push_type: self.push_type,
+                priority: Priority::Low,
+            });
+        }
+        if self.push_type == PushType::Background {
+            validate_background(aps, self.priority)?;
+        }
+        if self.push_type == PushType::Complication
+            && aps.and_then(|aps| aps.get("content-available")) != Some(&json!(1))
+        {
+            return Err(NotificationError::MissingContentAvailable);
+        }
+        if self.push_type == PushType::FileProvider {
+            validate_file_provider(&self.payload)?;
+        }
+
+        let size = self.payload.to_string().len();
+        let limit = self.push_type.max_payload_size();
+        if size > limit {
+            return Err(NotificationError::TooLarge {
+                push_type: self.push_type,
+                size,
+                limit,
+            });
+        }
+
+        Ok(())
+    }
+}
+
+/// Checks that a File Provider push names a container, and has no alert.
+fn validate_file_provider(payload: &Value) -> Result<(), NotificationError> {
+    let push_type = PushType::FileProvider;
+    if payload
+        .get("aps")
+        .and_then(|aps| aps.get("alert"))
+        .is_some()
+    {
+        return Err(NotificationError::AlertNotAllowed { push_type });
+    }
+    if !payload
+        .get("container-identifier")
+        .is_some_and(Value::is_string)
+    {
+        return Err(NotificationError::MissingKey {
+            push_type,
+            key: "container-identifier",
+        });
+    }
+    Ok(())
+}
+
+/// Checks that a background push only asks for a content fetch, at a low enough priority.
+fn validate_background(
+    aps: Option<&Value>,
+    priority: Option<Priority>,
+) -> Result<(), NotificationError> {
+    let push_type = PushType::Background;
+    if let Some(aps) = aps.and_then(Value::as_object) {
+        if aps.contains_key("alert") {
+            return Err(NotificationError::AlertNotAllowed { push_type });
+        }
+        if let Some(key) = ["badge", "sound"]
+            .into_iter()
+            .find(|key| aps.contains_key(*key))
+        {
+            return Err(NotificationError::ApsKeyNotAllowed {
+                push_type,
+                key: key.to_string(),
+            });
+        }
+    }
+    if aps.and_then(|aps| aps.get("content-available")) != Some(&json!(1)) {
+        return Err(NotificationError::MissingContentAvailable);
+    }
+    // APNs rejects background pushes sent at priority 10, and defaults to 10 when unset
+    match priority {
+        Some(Priority::Normal) | Some(Priority::Low) => Ok(()),
+        priority => Err(NotificationError::PriorityNotAllowed {
+            push_type,
+            priority: priority.unwrap_or(Priority::Immediate),
+        }),
+    }
+}
@@ -0,0 +1,401 @@
+//! Settings that apply to a single send.
+
+#[cfg(feature = "signing")]
+use crate::clock::{Clock, SystemClock};
+use crate::environment::Environment;
+use crate::error::ApnsError;
+use crate::notification::Priority;
+use crate::push_type::PushType;
+#[cfg(feature = "signing")]
+use crate::signer::{LocalSigner, TokenSigner};
+#[cfg(feature = "signing")]
+use crate::token::TokenCache;
+use crate::trace_context::TraceContext;
+use http::header::{HeaderName, HeaderValue, AUTHORIZATION, CONTENT_LENGTH, CONTENT_TYPE, HOST};
+use http::HeaderMap;
+use serde::de::Error as _;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::collections::BTreeMap;
+#[cfg(feature = "signing")]
+use std::fmt;
+#[cfg(feature = "signing")]
+use std::fs;
+#[cfg(feature = "signing")]
+use std::path::Path;
+#[cfg(feature = "signing")]
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+#[cfg(feature = "signing")]
+use zeroize::Zeroizing;
+
+/// Token-based (`.p8`) credentials of another team or key, for sending a single request
+/// on behalf of a provider other than the client's own.
+///
+/// Provider tokens signed with these credentials are cached in the value itself, so keep
+/// one `Credentials` per team and clone it rather than creating one per send; APNs throttles
+/// providers that sign new tokens too often.
+///
+/// # Example
+///
+/// ```rust,no_run
+/// use apnrs::Credentials;
+///
+/// # fn run() -> Result<(), apnrs::ApnsError> {
+/// let partner = Credentials::from_key_path("path/to/partner/key", "PARTNER_TEAM", "PARTNER_KEY")?;
+/// # Ok(())
+/// # }
+/// ```
+#[cfg(feature = "signing")]
+#[derive(Clone)]
+pub struct Credentials {
+    tokens: Arc<TokenCache>,
+}
+
+#[cfg(feature = "signing")]
+impl Credentials {
+    /// Creates credentials from an APNs auth key, PEM-encoded or DER-encoded as PKCS#8 or
+    /// SEC1 (see [`LocalSigner::from_pem`]).
+    ///
+    /// # Returns
+    ///
+    /// The credentials, or an `ApnsError` if the key is not a PEM- or DER-encoded EC key.
+    pub fn new(
+        key_pem: &[u8],
+        team_id: impl Into<String>,
+        key_id: impl Into<String>,
+    ) -> Result<Self, ApnsError> {
+        Ok(Self::from_signer(
+            LocalSigner::from_pem(key_pem, key_id)?,
+            team_id,
+        ))
+    }
+
+    /// Creates credentials that sign provider tokens for `team_id` with `signer`, such as a
+    /// key kept in a KMS or HSM.
+    pub fn from_signer(signer: impl TokenSigner + 'static, team_id: impl Into<String>) -> Self {
+        Credentials {
+            tokens: Arc::new(TokenCache::new(
+                Arc::new(signer),
+                team_id.into(),
+                None,
+                Arc::new(SystemClock),
+            )),
+        }
+    }
+
+    /// Creates credentials from the APNs auth key (`.p8`) at `path`.
+    ///
+    /// # Returns
+    ///
+    /// The credentials, or an `ApnsError` if the key cannot be read or parsed.
+    pub fn from_key_path(
+        path: impl AsRef<Path>,
+        team_id: impl Into<String>,
+        key_id: impl Into<String>,
+    ) -> Result<Self, ApnsError> {
+        Self::new(&Zeroizing::new(fs::read(path)?), team_id, key_id)
+    }
+
+    /// Sets the clock provider tokens signed with these credentials are issued at (defaults to
+    /// the [`SystemClock`]).
+    ///
+    /// The credentials start with an empty token cache; copies made before this call keep
+    /// their own.
+    pub fn clock(self, clock: impl Clock + 'static) -> Self {
+        Credentials {
+            tokens: Arc::new(self.tokens.with_clock(Arc::new(clock))),
+        }
+    }
+
+    /// Returns the token cache requests with these credentials are signed from.
+    pub(crate) fn tokens(&self) -> &TokenCache {
+        &self.tokens
+    }
+}
+
+#[cfg(feature = "signing")]
+impl fmt::Debug for Credentials {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Credentials").finish_non_exhaustive()
+    }
+}
+
+/// Per-request settings for [`ApnsClient::send_with`](crate::ApnsClient::send_with),
+/// [`send_raw`](crate::ApnsClient::send_raw), [`send_prepared`](crate::ApnsClient::send_prepared),
+/// and [fan-outs](crate::FanOut::options), bundling every request header APNs reads so new
+/// ones do not need new parameters.
+///
+/// Without explicit credentials, a request is signed with the credentials registered on the
+/// client for its app (see [`ApnsClientBuilder::credentials`](crate::ApnsClientBuilder::credentials)),
+/// and otherwise with the client's own auth key. A push type or priority set here takes
+/// precedence over one the send method derives, such as that of a
+/// [`Notification`](crate::Notification).
+///
+/// Options serialize with the [`PushRequest`](crate::PushRequest) carrying them, so a queued
+/// notification keeps its settings across a restart. Explicit credentials are the exception:
+/// they hold a signing key and are never serialized, so requests meant to be persisted should
+/// name registered credentials with [`app`](SendOptions::app) instead.
+///
+/// # Example
+///
+/// ```rust,no_run
+/// use apnrs::{ApnsClient, ApnsPayload, Credentials, Priority, PushType, SendOptions};
+/// use std::time::Duration;
+///
+/// # async fn run(client: ApnsClient, payload: ApnsPayload, partner: Credentials) -> Result<(), apnrs::ApnsError> {
+/// let options = SendOptions::new()
+///     .credentials(partner)
+///     .push_type(PushType::Alert)
+///     .priority(Priority::Normal)
+///     .expire_after(Duration::from_secs(3600))
+///     .collapse_id("order-42");
+/// client
+///     .send_with("DEVICE_TOKEN", "com.partner.app", &payload, &options)
+///     .await?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SendOptions {
+    #[cfg(feature = "signing")]
+    #[serde(skip)]
+    pub(crate) credentials: Option<Credentials>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) app: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) push_type: Option<PushType>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) priority: Option<Priority>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) expiration: Option<SystemTime>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) collapse_id: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) apns_id: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) environment: Option<Environment>,
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub(crate) dry_run: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) trace_context: Option<TraceContext>,
+    #[serde(
+        default,
+        skip_serializing_if = "HeaderMap::is_empty",
+        serialize_with = "serialize_headers",
+        deserialize_with = "deserialize_headers"
+    )]
+    pub(crate) headers: HeaderMap,
+    /// The connection to send on, pinned by
+    /// [`send_all_to`](crate::ApnsClient::send_all_to) instead of picked per request.
+    #[cfg(feature = "tokio")]
+    #[serde(skip)]
+    pub(crate) connection: Option<usize>,
+}
+
+impl SendOptions {
+    /// Creates options that change nothing about the send.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Signs the request with `credentials` instead of the client's own auth key.
+    ///
+    /// The request still goes over the client's connections to its environment; only the
+    /// `authorization` header differs.
+    #[cfg(feature = "signing")]
+    pub fn credentials(mut self, credentials: Credentials) -> Self {
+        self.credentials = Some(credentials);
+        self
+    }
+
+    /// Signs the request with the credentials registered on the client under the logical
+    /// app name `app`, instead of those registered for the topic's bundle ID.
+    pub fn app(mut self, app: impl Into<String>) -> Self {
+        self.app = Some(app.into());
+        self
+    }
+
+    /// Sends the request with `push_type` as `apns-push-type`, which also sets the payload
+    /// size limit it is checked against.
+    ///
+    /// Unlike [`send_notification`](crate::ApnsClient::send_notification), the topic is
+    /// sent as given, so include the push type's suffix (see [`PushType::topic_for`]).
+    pub fn push_type(mut self, push_type: PushType) -> Self {
+        self.push_type = Some(push_type);
+        self
+    }
+
+    /// Sends the request with `priority` as `apns-priority` (defaults to APNs' own default,
+    /// `10` for alerts).
+    pub fn priority(mut self, priority: Priority) -> Self {
+        self.priority = Some(priority);
+        self
+    }
+
+    /// Sends the request with an `apns-expiration`, so APNs keeps retrying delivery to an
+    /// offline device until `expiration`, such as a `SystemTime`, `time::OffsetDateTime`, or
+    /// `chrono::DateTime`, and then discards the notification.
+    ///
+    /// The Unix epoch itself asks APNs to try only once; see
+    /// [`expire_immediately`](SendOptions::expire_immediately).
+    pub fn expiration(mut self, expiration: impl Into<SystemTime>) -> Self {
+        self.expiration = Some(expiration.into());
+        self
+    }
+
+    /// Sends the request with an `apns-expiration` `after` from now, as
+    /// [`expiration`](SendOptions::expiration) does.
+    pub fn expire_after(self, after: Duration) -> Self {
+        self.expiration(SystemTime::now() + after)
+    }
+
+    /// Sends the request with `apns-expiration: 0`, so APNs delivers the notification only
+    /// if the device is online and does not store it otherwise.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use apnrs::SendOptions;
+    /// use std::time::UNIX_EPOCH;
+    ///
+    /// let options = SendOptions::new().expire_immediately();
+    /// assert_eq!(options.expiration_header().as_deref(), Some("0"));
+    /// assert_eq!(SendOptions::new().expiration(UNIX_EPOCH).expiration_header().as_deref(), Some("0"));
+    /// ```
+    pub fn expire_immediately(self) -> Self {
+        self.expiration(UNIX_EPOCH)
+    }
+
+    /// Returns the `apns-expiration` value the request is sent with: the expiration in
+    /// seconds since the Unix epoch, or `None` if it has none.
+    pub fn expiration_header(&self) -> Option<String> {
+        self.expiration.map(|expiration| {
+            expiration
+                .duration_since(UNIX_EPOCH)
+                .map_or(0, |since| since.as_secs())
+                .to_string()
+        })
+    }
+
+    /// Sends the request to `environment` instead of the one the client would pick for the
+    /// topic, for example to reach a development build from a production client.
+    ///
+    /// The request still goes through the client's connection pool, which keeps separate
+    /// connections to each APNs host, so one client can send to sandbox and production tokens
+    /// side by side. It is signed with the client's credentials, but it is not resent to the
+    /// other environment on `BadDeviceToken` even with
+    /// [`environment_fallback`](crate::ApnsClientBuilder::environment_fallback).
+    pub fn environment(mut self, environment: Environment) -> Self {
+        self.environment = Some(environment);
+        self
+    }
+
+    /// Sends the request with an `apns-collapse-id` of at most 64 bytes, so the device shows
+    /// only the latest of the notifications sharing it.
+    pub fn collapse_id(mut self, collapse_id: impl Into<String>) -> Self {
+        self.collapse_id = Some(collapse_id.into());
+        self
+    }
+
+    /// Sends the request with `apns_id`, a canonical UUID, instead of letting APNs assign
+    /// one, so every attempt at the same notification carries the same ID.
+    pub fn apns_id(mut self, apns_id: impl Into<String>) -> Self {
+        self.apns_id = Some(apns_id.into());
+        self
+    }
+
+    /// Correlates the send with the distributed trace of the request that caused it, such
+    /// as the API request queueing the push.
+    ///
+    /// The trace and parent span IDs are recorded on the send's `apns.send` tracing span,
+    /// and the trace ID on its [`SendEvent`](crate::SendEvent) and so in a
+    /// [`DeliveryLog`](crate::DeliveryLog). Nothing is sent to APNs.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use apnrs::{SendOptions, TraceContext};
+    ///
+    /// # fn run(traceparent: &str) -> Result<(), apnrs::ApnsError> {
+    /// let options = SendOptions::new().trace_context(traceparent.parse::<TraceContext>()?);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn trace_context(mut self, context: TraceContext) -> Self {
+        self.trace_context = Some(context);
+        self
+    }
+
+    /// Sends the request with the extra header `name: value`, for request headers Apple
+    /// introduces before this crate supports them.
+    ///
+    /// The header replaces one of the same name the client would send, such as
+    /// `apns-priority`, but headers the client must control, namely `authorization`,
+    /// `apns-topic`, `content-type`, `content-length`, and `host`, are refused. The request
+    /// still goes through send hooks, which see the header.
+    ///
+    /// # Returns
+    ///
+    /// The options, or an `ApnsError` if the name or value is not a valid header or the
+    /// header is one the client controls.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use apnrs::SendOptions;
+    ///
+    /// # fn run() -> Result<(), apnrs::ApnsError> {
+    /// let options = SendOptions::new().header("apns-unreleased-feature", "1")?;
+    /// assert!(SendOptions::new().header("authorization", "bearer x").is_err());
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn header(mut self, name: &str, value: &str) -> Result<Self, ApnsError> {
+        let (name, value) = checked_header(name, value)?;
+        self.headers.insert(name, value);
+        Ok(self)
+    }
+
+    /// Prepares the request without sending it, as in a client's
+    /// [dry-run mode](crate::ApnsClientBuilder::dry_run).
+    pub fn dry_run(mut self, dry_run: bool) -> Self {
+        self.dry_run = dry_run;
+        self
+    }
+}
+
+/// Parses an extra request header, refusing those the client must control.
+fn checked_header(name: &str, value: &str) -> Result<(HeaderName, HeaderValue), ApnsError> {
+    let name = HeaderName::from_bytes(name.as_bytes())
+        .map_err(|e| ApnsError::Config(format!("invalid request header: {}", e)))?;
+    let reserved = [AUTHORIZATION, CONTENT_TYPE, CONTENT_LENGTH, HOST];
+    if reserved.contains(&name) || name == "apns-topic" {
+        return Err(ApnsError::Config(format!(
+            "the {} header is set by the client and cannot be overridden",
+            name
+        )));
+    }
+    Ok((name, HeaderValue::from_str(value)?))
+}
+
+fn is_false(flag: &bool) -> bool {
+    !*flag
+}
+
+/// Serializes extra request headers as a map of names to values.
+fn serialize_headers<S: Serializer>(headers: &HeaderMap, serializer: S) -> Result<S::Ok, S::Error> {
+    let headers = headers
+        .iter()
+        .map(|(name, value)| Ok((name.as_str(), value.to_str()?)))
+        .collect::<Result<BTreeMap<_, _>, http::header::ToStrError>>()
+        .map_err(serde::ser::Error::custom)?;
+    headers.serialize(serializer)
+}
+
+/// Deserializes extra request headers, checking them as [`SendOptions::header`] does.
+fn deserialize_headers<'de, D: Deserializer<'de>>(deserializer: D) -> Result<HeaderMap, D::Error> {
+    BTreeMap::<String, String>::deserialize(deserializer)?
+        .iter()
+        .map(|(name, value)| checked_header(name, value).map_err(D::Error::custom))
+        .collect()
+}
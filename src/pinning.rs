@@ -0,0 +1,166 @@
+//! Certificate pinning for connections to APNs.
+
+use crate::error::ApnsError;
+use openssl::base64;
+use openssl::hash::{hash, MessageDigest};
+use openssl::x509::X509;
+
+/// A certificate or public key the APNs server must present, set with
+/// [`ApnsClientBuilder::pin`](crate::ApnsClientBuilder::pin).
+///
+/// Register several pins of the same kind to rotate without downtime: pin the current and the
+/// next key (or root) before Apple switches, and drop the old pin afterwards.
+///
+/// # Variants
+///
+/// * `PublicKey` - The SHA-256 of the server certificate's `SubjectPublicKeyInfo`. A
+///   connection whose leaf certificate carries none of the pinned keys is rejected with
+///   [`ApnsError::PinMismatch`].
+/// * `Root` - A DER-encoded root certificate. Once a root is pinned, only the pinned roots are
+///   trusted to issue the server's certificate, instead of the system trust store, so the TLS
+///   handshake itself fails for a chain issued by any other authority.
+///
+/// # Example
+///
+/// ```rust,no_run
+/// use apnrs::{ApnsClient, CertificatePin};
+///
+/// # fn run(apple_root_pem: &[u8]) -> Result<(), apnrs::ApnsError> {
+/// let client = ApnsClient::builder()
+///     .auth_key_path("path/to/auth/key")
+///     .team_id("TEAM_ID")
+///     .key_id("KEY_ID")
+///     .pin(CertificatePin::root_pem(apple_root_pem)?)
+///     // the current key, and the one Apple is rotating to
+///     .pin(CertificatePin::public_key_sha256("sha256//9bNH9Q0JmF2gQj6Xq5t1J8s2xYd3Ak7wZ0cV4eR6uLo=")?)
+///     .pin(CertificatePin::public_key_sha256("qP2tW8yZr1mN5bK7cE0vH3jD6fG9aS4uL2xT8oI1pQw=")?)
+///     .build()?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CertificatePin {
+    PublicKey([u8; 32]),
+    Root(Vec<u8>),
+}
+
+impl CertificatePin {
+    /// Pins a public key by the base64 SHA-256 of its `SubjectPublicKeyInfo`, optionally
+    /// prefixed with `sha256//` as in curl's `--pinnedpubkey`.
+    ///
+    /// The value for a certificate in `cert.pem` is printed by:
+    ///
+    /// ```sh
+    /// openssl x509 -in cert.pem -pubkey -noout | openssl pkey -pubin -outform der \
+    ///     | openssl dgst -sha256 -binary | base64
+    /// ```
+    ///
+    /// # Returns
+    ///
+    /// The pin, or an `ApnsError` if `pin` is not the base64 encoding of 32 bytes.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use apnrs::CertificatePin;
+    ///
+    /// let pin = CertificatePin::public_key_sha256("AAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAA=").unwrap();
+    /// assert_eq!(pin, CertificatePin::PublicKey([0; 32]));
+    /// assert!(CertificatePin::public_key_sha256("not a pin").is_err());
+    /// ```
+    pub fn public_key_sha256(pin: &str) -> Result<Self, ApnsError> {
+        let encoded = pin.trim().trim_start_matches("sha256//");
+        let digest = base64::decode_block(encoded)
+            .ok()
+            .and_then(|bytes| <[u8; 32]>::try_from(bytes).ok())
+            .ok_or_else(|| {
+                ApnsError::Config(format!(
+                    "{:?} is not a base64-encoded SHA-256 public key pin",
+                    pin
+                ))
+            })?;
+        Ok(CertificatePin::PublicKey(digest))
+    }
+
+    /// Pins the public key of a PEM-encoded certificate, such as Apple's current server
+    /// certificate.
+    ///
+    /// # Returns
+    ///
+    /// The pin, or an `ApnsError` if `pem` is not a PEM-encoded certificate.
+    pub fn public_key_of_pem(pem: &[u8]) -> Result<Self, ApnsError> {
+        let certificate = X509::from_pem(pem).map_err(invalid_certificate)?;
+        let digest = public_key_digest(&certificate).map_err(invalid_certificate)?;
+        Ok(CertificatePin::PublicKey(digest))
+    }
+
+    /// Pins a PEM-encoded root certificate, such as the root Apple issues APNs server
+    /// certificates from.
+    ///
+    /// # Returns
+    ///
+    /// The pin, or an `ApnsError` if `pem` is not a PEM-encoded certificate.
+    pub fn root_pem(pem: &[u8]) -> Result<Self, ApnsError> {
+        let certificate = X509::from_pem(pem).map_err(invalid_certificate)?;
+        Ok(CertificatePin::Root(
+            certificate.to_der().map_err(invalid_certificate)?,
+        ))
+    }
+}
+
+fn invalid_certificate(e: openssl::error::ErrorStack) -> ApnsError {
+    ApnsError::Config(format!("invalid pinned certificate: {}", e))
+}
+
+/// Returns the SHA-256 of the certificate's `SubjectPublicKeyInfo`.
+fn public_key_digest(certificate: &X509) -> Result<[u8; 32], openssl::error::ErrorStack> {
+    let spki = certificate.public_key()?.public_key_to_der()?;
+    let digest = hash(MessageDigest::sha256(), &spki)?;
+    let mut bytes = [0; 32];
+    bytes.copy_from_slice(&digest);
+    Ok(bytes)
+}
+
+/// The public keys a client accepts from the APNs server.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct PublicKeyPins {
+    digests: Vec<[u8; 32]>,
+}
+
+impl PublicKeyPins {
+    pub(crate) fn new(pins: &[CertificatePin]) -> Self {
+        PublicKeyPins {
+            digests: pins
+                .iter()
+                .filter_map(|pin| match pin {
+                    CertificatePin::PublicKey(digest) => Some(*digest),
+                    CertificatePin::Root(_) => None,
+                })
+                .collect(),
+        }
+    }
+
+    /// Returns whether any public key is pinned, so responses carry TLS details to check.
+    pub(crate) fn is_empty(&self) -> bool {
+        self.digests.is_empty()
+    }
+
+    /// Checks that the connection a response arrived on presented a pinned public key.
+    pub(crate) fn check(&self, response: &reqwest::Response) -> Result<(), ApnsError> {
+        if self.is_empty() {
+            return Ok(());
+        }
+        let presented = response
+            .extensions()
+            .get::<reqwest::tls::TlsInfo>()
+            .and_then(|info| info.peer_certificate())
+            .and_then(|der| X509::from_der(der).ok())
+            .and_then(|certificate| public_key_digest(&certificate).ok());
+        match presented {
+            Some(digest) if self.digests.contains(&digest) => Ok(()),
+            presented => Err(ApnsError::PinMismatch(
+                presented.map(|digest| base64::encode_block(&digest)),
+            )),
+        }
+    }
+}
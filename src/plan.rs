@@ -0,0 +1,164 @@
+//! Capacity planning estimates for sizing senders before a large send.
+
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+/// How many requests APNs lets run at once on one HTTP/2 connection, by default.
+pub const DEFAULT_STREAMS_PER_CONNECTION: usize = 1000;
+
+/// Memory held by one open connection: TLS buffers, HTTP/2 flow-control windows, and the
+/// connection's state.
+const CONNECTION_OVERHEAD: u64 = 128 * 1024;
+
+/// Memory held by one request besides its payload: headers, the provider token, and the
+/// buffered response.
+const REQUEST_OVERHEAD: u64 = 2 * 1024;
+
+/// Memory held by one queued notification besides its payload: its device token, topic,
+/// and the channel slot.
+const QUEUED_OVERHEAD: u64 = 512;
+
+/// The inputs of a capacity estimate: how much is sent, and how the sender is configured.
+///
+/// Estimates assume every request takes `latency` and none is retried, so treat them as a
+/// lower bound on duration and an upper bound on throughput. Measure the latency with
+/// [`ClientStats`](crate::ClientStats) on a real send for better numbers.
+///
+/// # Example
+///
+/// ```rust
+/// use apnrs::CapacityPlan;
+/// use std::time::Duration;
+///
+/// let estimate = CapacityPlan::new(1_000_000, 512)
+///     .concurrency(200)
+///     .rate_limit(1_000.0)
+///     .latency(Duration::from_millis(100))
+///     .estimate();
+///
+/// // 200 workers could do 2,000 requests per second, but the rate limit caps them at 1,000
+/// assert_eq!(estimate.throughput, 1_000.0);
+/// assert_eq!(estimate.duration, Duration::from_secs(1_000));
+/// assert_eq!(estimate.peak_in_flight, 100);
+/// assert_eq!(estimate.peak_connections, 1);
+/// ```
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CapacityPlan {
+    devices: u64,
+    payload_size: usize,
+    concurrency: usize,
+    rate_limit: Option<f64>,
+    latency: Duration,
+    streams_per_connection: usize,
+    queue_capacity: usize,
+}
+
+/// What a [`CapacityPlan`] is estimated to take.
+///
+/// # Fields
+///
+/// * `duration` - How long sending to every device takes.
+/// * `throughput` - How many notifications are sent per second.
+/// * `peak_in_flight` - How many requests are waiting for APNs at once.
+/// * `peak_connections` - How many HTTP/2 connections carry those requests.
+/// * `peak_memory_bytes` - How much memory the connections, in-flight requests, and a full
+///   queue hold at once.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CapacityEstimate {
+    pub duration: Duration,
+    pub throughput: f64,
+    pub peak_in_flight: usize,
+    pub peak_connections: usize,
+    pub peak_memory_bytes: u64,
+}
+
+impl CapacityPlan {
+    /// Creates a plan for sending a `payload_size`-byte payload to `devices` devices with the
+    /// sender's defaults: 4 workers, a queue of 1024, no rate limit, and 100ms per request.
+    pub fn new(devices: u64, payload_size: usize) -> Self {
+        CapacityPlan {
+            devices,
+            payload_size,
+            concurrency: 4,
+            rate_limit: None,
+            latency: Duration::from_millis(100),
+            streams_per_connection: DEFAULT_STREAMS_PER_CONNECTION,
+            queue_capacity: 1024,
+        }
+    }
+
+    /// Sets how many requests are in flight at once, i.e. the sender's
+    /// [`workers`](crate::ApnsSenderBuilder::workers).
+    pub fn concurrency(mut self, concurrency: usize) -> Self {
+        self.concurrency = concurrency.max(1);
+        self
+    }
+
+    /// Caps throughput at `per_second` notifications per second, i.e. the sender's
+    /// [`rate_limit`](crate::ApnsSenderBuilder::rate_limit).
+    pub fn rate_limit(mut self, per_second: f64) -> Self {
+        self.rate_limit = Some(per_second).filter(|rate| *rate > 0.0);
+        self
+    }
+
+    /// Sets how long APNs takes to answer one request.
+    pub fn latency(mut self, latency: Duration) -> Self {
+        self.latency = latency;
+        self
+    }
+
+    /// Sets how many requests one connection carries at once (defaults to
+    /// [`DEFAULT_STREAMS_PER_CONNECTION`]).
+    pub fn streams_per_connection(mut self, streams: usize) -> Self {
+        self.streams_per_connection = streams.max(1);
+        self
+    }
+
+    /// Sets the sender's [`queue_capacity`](crate::ApnsSenderBuilder::queue_capacity).
+    pub fn queue_capacity(mut self, capacity: usize) -> Self {
+        self.queue_capacity = capacity.max(1);
+        self
+    }
+
+    /// Estimates the duration, connections, and memory of the send.
+    pub fn estimate(&self) -> CapacityEstimate {
+        let latency = self.latency.as_secs_f64();
+        let unlimited = if latency > 0.0 {
+            self.concurrency as f64 / latency
+        } else {
+            f64::INFINITY
+        };
+        let throughput = self
+            .rate_limit
+            .map_or(unlimited, |rate| rate.min(unlimited));
+
+        let duration = if throughput.is_finite() {
+            Duration::from_secs_f64(self.devices as f64 / throughput)
+        } else {
+            Duration::ZERO
+        };
+        // Little's law: requests in flight are arrivals per second times time in flight
+        let devices = usize::try_from(self.devices).unwrap_or(usize::MAX);
+        let in_flight = if throughput.is_finite() {
+            (throughput * latency).ceil() as usize
+        } else {
+            self.concurrency
+        };
+        let peak_in_flight = in_flight.clamp(1, self.concurrency).min(devices.max(1));
+        let peak_connections = peak_in_flight.div_ceil(self.streams_per_connection);
+
+        let payload = self.payload_size as u64;
+        let queued = self.queue_capacity.min(devices);
+        let peak_memory_bytes = peak_connections as u64 * CONNECTION_OVERHEAD
+            + peak_in_flight as u64 * (payload + REQUEST_OVERHEAD)
+            + queued as u64 * (payload + QUEUED_OVERHEAD);
+
+        CapacityEstimate {
+            duration,
+            throughput,
+            peak_in_flight,
+            peak_connections,
+            peak_memory_bytes,
+        }
+    }
+}
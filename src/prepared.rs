@@ -0,0 +1,55 @@
+//! Payloads serialized once and shared by every request they are sent with.
+
+use crate::trim::TrimReport;
+use bytes::Bytes;
+
+/// A payload serialized and size-checked once, for sending the same notification to many
+/// devices.
+///
+/// Create one with [`ApnsClient::prepare`](crate::ApnsClient::prepare) and send it with
+/// [`ApnsClient::send_prepared`](crate::ApnsClient::send_prepared). Cloning it, or sending
+/// it, shares the serialized body instead of copying it.
+///
+/// # Example
+///
+/// ```rust,no_run
+/// use apnrs::{ApnsClient, ApnsPayload};
+///
+/// # fn run(client: ApnsClient, payload: ApnsPayload) -> Result<(), apnrs::ApnsError> {
+/// let prepared = client.prepare(&payload)?;
+/// println!("{} bytes", prepared.len());
+/// assert!(prepared.trimmed().is_none());
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone)]
+pub struct PreparedPayload {
+    json: Bytes,
+    trimmed: Option<TrimReport>,
+}
+
+impl PreparedPayload {
+    pub(crate) fn new(json: Bytes, trimmed: Option<TrimReport>) -> Self {
+        PreparedPayload { json, trimmed }
+    }
+
+    /// Returns the JSON bytes of the request body.
+    pub fn as_bytes(&self) -> &Bytes {
+        &self.json
+    }
+
+    /// Returns the length of the body in bytes.
+    pub fn len(&self) -> usize {
+        self.json.len()
+    }
+
+    /// Returns `true` if the body is empty.
+    pub fn is_empty(&self) -> bool {
+        self.json.is_empty()
+    }
+
+    /// Returns what the trim strategy removed to make the payload fit, if anything.
+    pub fn trimmed(&self) -> Option<&TrimReport> {
+        self.trimmed.as_ref()
+    }
+}
@@ -0,0 +1,128 @@
+//! Previews of the JSON a payload is sent as, with its size against the APNs limit.
+
+use crate::notification::Notification;
+use crate::push_type::PushType;
+use crate::ApnsPayload;
+use serde::Serialize;
+use std::fmt;
+
+/// The exact JSON a payload is sent as, with its size and the limit APNs enforces for it.
+///
+/// Sending serializes payloads the same way, so a payload whose preview fits is not rejected
+/// for its size, and one that does not is trimmed or refused before reaching APNs.
+///
+/// # Fields
+///
+/// * `json` - The JSON bytes of the request body.
+/// * `size` - The length of `json` in bytes, which is what the limit counts.
+/// * `limit` - The largest payload APNs accepts for the push type, in bytes.
+///
+/// # Example
+///
+/// ```rust
+/// use apnrs::{ApnsPayload, Aps, Badge, PushType, Sound};
+///
+/// let payload = ApnsPayload {
+///     aps: Aps {
+///         alert: "Hello, world!".to_string(),
+///         content_available: None,
+///         badge: Badge::Set(1),
+///         sound: Sound::None,
+///         category: None,
+///         thread_id: None,
+///         summary_arg: None,
+///         summary_arg_count: None,
+///         launch_image: None,
+///         alert_fields: Default::default(),
+///         extra_fields: Default::default(),
+///     },
+///     custom_key: None,
+///     custom_fields: Default::default(),
+/// };
+///
+/// let preview = payload.preview()?;
+/// assert_eq!(preview.as_str(), r#"{"aps":{"alert":"Hello, world!","badge":1}}"#);
+/// assert_eq!((preview.size, preview.limit), (43, 4096));
+/// assert!(preview.fits());
+/// assert_eq!(payload.preview_for(PushType::Voip)?.limit, 5120);
+///
+/// let mut clashing = payload.clone();
+/// clashing.custom_fields.insert("aps".to_string(), "overwritten".into());
+/// assert!(clashing.preview().is_err());
+/// # Ok::<(), serde_json::Error>(())
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PayloadPreview {
+    pub json: Vec<u8>,
+    pub size: usize,
+    pub limit: usize,
+}
+
+impl PayloadPreview {
+    /// Serializes `payload` as it would be sent with `push_type`.
+    fn new<P: Serialize + ?Sized>(
+        payload: &P,
+        push_type: PushType,
+    ) -> Result<Self, serde_json::Error> {
+        let json = serde_json::to_vec(payload)?;
+        Ok(PayloadPreview {
+            size: json.len(),
+            limit: push_type.max_payload_size(),
+            json,
+        })
+    }
+
+    /// Returns the JSON as text.
+    pub fn as_str(&self) -> &str {
+        std::str::from_utf8(&self.json).expect("serde_json writes UTF-8")
+    }
+
+    /// Returns `true` if the payload is within the limit.
+    pub fn fits(&self) -> bool {
+        self.size <= self.limit
+    }
+
+    /// Returns how many bytes the payload exceeds the limit by, or 0 if it fits.
+    pub fn excess(&self) -> usize {
+        self.size.saturating_sub(self.limit)
+    }
+}
+
+impl fmt::Display for PayloadPreview {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{}", self.as_str())?;
+        write!(f, "{} of {} bytes", self.size, self.limit)
+    }
+}
+
+impl ApnsPayload {
+    /// Previews the payload as it would be sent as an alert push.
+    ///
+    /// # Returns
+    ///
+    /// The preview, or the error sending would fail with, such as a custom, `aps`, or alert
+    /// field colliding with a key the payload sets itself.
+    pub fn preview(&self) -> Result<PayloadPreview, serde_json::Error> {
+        self.preview_for(PushType::Alert)
+    }
+
+    /// Previews the payload as it would be sent with `push_type`, whose limit may differ.
+    ///
+    /// # Returns
+    ///
+    /// The preview, or the error sending would fail with.
+    pub fn preview_for(&self, push_type: PushType) -> Result<PayloadPreview, serde_json::Error> {
+        PayloadPreview::new(self, push_type)
+    }
+}
+
+impl Notification {
+    /// Previews the notification's payload, against the limit of its push type.
+    ///
+    /// # Returns
+    ///
+    /// The preview, or the error sending would fail with.
+    pub fn preview(&self) -> Result<PayloadPreview, serde_json::Error> {
+        PayloadPreview::new(&self.payload, self.push_type)
+    }
+}
@@ -0,0 +1,144 @@
+//! Keyed hashing of device tokens before they reach analytics sinks, and masking of tokens
+//! and provider tokens in log records and `Debug` output.
+
+use http::header::AUTHORIZATION;
+use http::HeaderMap;
+#[cfg(all(feature = "logging", feature = "tokio"))]
+use http::Response;
+#[cfg(feature = "signing")]
+use openssl::hash::MessageDigest;
+#[cfg(feature = "signing")]
+use openssl::pkey::{PKey, Private};
+#[cfg(feature = "signing")]
+use openssl::sign::Signer;
+use std::fmt;
+
+/// Replaces device tokens with an HMAC-SHA256 of the token under a key you supply.
+///
+/// The same token always hashes to the same value under the same key, so analytics can
+/// still count and correlate devices without ever receiving a raw token. Keep the key
+/// secret: anyone holding it can test whether a known token produced a given hash.
+///
+/// # Example
+///
+/// ```rust
+/// use apnrs::TokenHasher;
+///
+/// let hasher = TokenHasher::new(b"analytics-secret").unwrap();
+/// let hashed = hasher.hash("3f2a9c1e");
+///
+/// assert_eq!(hashed.len(), 64);
+/// assert_eq!(hashed, hasher.hash("3f2a9c1e"));
+/// assert_ne!(hashed, TokenHasher::new(b"other-secret").unwrap().hash("3f2a9c1e"));
+/// ```
+#[cfg(feature = "signing")]
+#[derive(Clone)]
+pub struct TokenHasher {
+    key: PKey<Private>,
+}
+
+#[cfg(feature = "signing")]
+impl TokenHasher {
+    /// Creates a hasher with the given HMAC key.
+    ///
+    /// # Returns
+    ///
+    /// The hasher, or an error if OpenSSL cannot use the key.
+    pub fn new(key: &[u8]) -> Result<Self, openssl::error::ErrorStack> {
+        Ok(TokenHasher {
+            key: PKey::hmac(key)?,
+        })
+    }
+
+    /// Returns the hex-encoded HMAC-SHA256 of `device_token`.
+    pub fn hash(&self, device_token: &str) -> String {
+        Signer::new(MessageDigest::sha256(), &self.key)
+            .and_then(|mut signer| {
+                signer.update(device_token.as_bytes())?;
+                signer.sign_to_vec()
+            })
+            .map(|mac| mac.iter().map(|byte| format!("{:02x}", byte)).collect())
+            .expect("HMAC-SHA256 with a valid key does not fail")
+    }
+}
+
+#[cfg(feature = "signing")]
+impl fmt::Debug for TokenHasher {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TokenHasher").finish_non_exhaustive()
+    }
+}
+
+/// Shortens a device token to its first six and last four characters for log records and
+/// `Debug` output, such as `3f2a9c…8a0f`, so they identify a device to an operator without
+/// exposing the token.
+pub(crate) fn mask_token(token: &str) -> String {
+    match (token.get(..6), token.get(token.len().saturating_sub(4)..)) {
+        (Some(head), Some(tail)) if token.len() > 12 => format!("{}…{}", head, tail),
+        _ => "…".to_string(),
+    }
+}
+
+/// Masks the device token ending a request URL, such as `…/3/device/3f2a9c…8a0f`.
+pub(crate) fn mask_url(url: &str) -> String {
+    match url.rsplit_once('/') {
+        Some((base, token)) if base.ends_with("/3/device") => {
+            format!("{}/{}", base, mask_token(token))
+        }
+        _ => url.to_string(),
+    }
+}
+
+/// Formats request headers for `Debug` output with the provider token left out.
+pub(crate) struct RedactedHeaders<'a>(pub(crate) &'a HeaderMap);
+
+impl fmt::Debug for RedactedHeaders<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_map()
+            .entries(self.0.iter().map(|(name, value)| {
+                let value: &dyn fmt::Debug = if name == AUTHORIZATION {
+                    &"bearer <redacted>"
+                } else {
+                    value
+                };
+                (name, value)
+            }))
+            .finish()
+    }
+}
+
+/// The log target of [wire logging](crate::ApnsClientBuilder::wire_logging).
+#[cfg(all(feature = "logging", feature = "tokio"))]
+pub(crate) const WIRE_LOG_TARGET: &str = "apnrs::wire";
+
+/// Formats a request for wire logging, in the style of `curl -v`, with the provider token
+/// and device token redacted.
+#[cfg(all(feature = "logging", feature = "tokio"))]
+pub(crate) fn wire_request(url: &str, headers: &HeaderMap, body: &[u8]) -> String {
+    let mut lines = vec![format!("> POST {}", mask_url(url))];
+    lines.extend(headers.iter().map(|(name, value)| match value.to_str() {
+        _ if name == AUTHORIZATION => format!("> {}: bearer <redacted>", name),
+        Ok(value) => format!("> {}: {}", name, value),
+        Err(_) => format!("> {}: <binary>", name),
+    }));
+    lines.push(">".to_string());
+    lines.push(format!("> {}", String::from_utf8_lossy(body)));
+    lines.join("\n")
+}
+
+/// Formats a response for wire logging, in the style of `curl -v`.
+#[cfg(all(feature = "logging", feature = "tokio"))]
+pub(crate) fn wire_response(response: &Response<bytes::Bytes>) -> String {
+    let mut lines = vec![format!("< {}", response.status())];
+    lines.extend(
+        response
+            .headers()
+            .iter()
+            .map(|(name, value)| format!("< {}: {}", name, value.to_str().unwrap_or("<binary>"))),
+    );
+    if !response.body().is_empty() {
+        lines.push("<".to_string());
+        lines.push(format!("< {}", String::from_utf8_lossy(response.body())));
+    }
+    lines.join("\n")
+}
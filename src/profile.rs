@@ -0,0 +1,117 @@
+//! Named per-app defaults, so call sites do not repeat them.
+
+use crate::environment::Environment;
+use crate::notification::Priority;
+#[cfg(feature = "signing")]
+use crate::options::Credentials;
+use crate::options::SendOptions;
+use crate::push_type::PushType;
+
+/// The bundle ID and send defaults of one app, registered on a client under a name with
+/// [`ApnsClientBuilder::profile`](crate::ApnsClientBuilder::profile) and sent with
+/// [`ApnsClient::send_with_profile`](crate::ApnsClient::send_with_profile).
+///
+/// Settings left unset fall back to the client's own: its credentials for the bundle ID,
+/// its environment, and the push type and priority APNs defaults to.
+///
+/// # Example
+///
+/// ```rust
+/// use apnrs::{Environment, Priority, Profile, PushType};
+///
+/// let profile = Profile::new("com.example.app")
+///     .push_type(PushType::Voip)
+///     .priority(Priority::Immediate)
+///     .environment(Environment::Sandbox);
+/// assert_eq!(profile.topic(), "com.example.app.voip");
+/// ```
+#[derive(Debug, Clone)]
+pub struct Profile {
+    bundle_id: String,
+    environment: Option<Environment>,
+    push_type: Option<PushType>,
+    priority: Option<Priority>,
+    #[cfg(feature = "signing")]
+    credentials: Option<Credentials>,
+}
+
+impl Profile {
+    /// Creates a profile sending to the app with `bundle_id` with the client's defaults.
+    pub fn new(bundle_id: impl Into<String>) -> Self {
+        Profile {
+            bundle_id: bundle_id.into(),
+            environment: None,
+            push_type: None,
+            priority: None,
+            #[cfg(feature = "signing")]
+            credentials: None,
+        }
+    }
+
+    /// Sends to `environment` instead of the client's environment for the bundle ID.
+    pub fn environment(mut self, environment: Environment) -> Self {
+        self.environment = Some(environment);
+        self
+    }
+
+    /// Sends with `push_type` as `apns-push-type`, and to the topic with its suffix, such as
+    /// `.voip`.
+    pub fn push_type(mut self, push_type: PushType) -> Self {
+        self.push_type = Some(push_type);
+        self
+    }
+
+    /// Sends with `priority` as `apns-priority`.
+    pub fn priority(mut self, priority: Priority) -> Self {
+        self.priority = Some(priority);
+        self
+    }
+
+    /// Signs requests with `credentials` instead of those the client would use for the
+    /// bundle ID.
+    #[cfg(feature = "signing")]
+    pub fn credentials(mut self, credentials: Credentials) -> Self {
+        self.credentials = Some(credentials);
+        self
+    }
+
+    /// Returns the bundle ID of the app.
+    pub fn bundle_id(&self) -> &str {
+        &self.bundle_id
+    }
+
+    /// Returns the topic notifications are sent to: the bundle ID, with the suffix of the
+    /// profile's push type if it has one.
+    pub fn topic(&self) -> String {
+        match self.push_type {
+            Some(push_type) => push_type.topic_for(&self.bundle_id),
+            None => self.bundle_id.clone(),
+        }
+    }
+
+    /// Returns `true` if the profile signs with its own credentials.
+    #[cfg(feature = "tokio")]
+    pub(crate) fn has_credentials(&self) -> bool {
+        self.credentials.is_some()
+    }
+
+    /// Returns the per-request settings the profile sends with, to adjust further before
+    /// sending with [`ApnsClient::send_with`](crate::ApnsClient::send_with).
+    pub fn options(&self) -> SendOptions {
+        let mut options = SendOptions::new();
+        if let Some(environment) = self.environment {
+            options = options.environment(environment);
+        }
+        if let Some(push_type) = self.push_type {
+            options = options.push_type(push_type);
+        }
+        if let Some(priority) = self.priority {
+            options = options.priority(priority);
+        }
+        #[cfg(feature = "signing")]
+        if let Some(credentials) = &self.credentials {
+            options = options.credentials(credentials.clone());
+        }
+        options
+    }
+}
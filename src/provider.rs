@@ -0,0 +1,227 @@
+//! A provider-neutral interface for sending notifications, and an in-memory provider for
+//! tests.
+
+use crate::notification::NotificationError;
+use crate::topic::validate_request_topic;
+use crate::trim::MAX_PAYLOAD_SIZE;
+use crate::{ApnsClient, ApnsError, ApnsResponse, ErrorReason, PushRequest, PushType, SendOutcome};
+use bytes::Bytes;
+use http::{HeaderValue, Response, StatusCode};
+use std::collections::HashMap;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Mutex;
+
+/// How many requests [`ApnsClient`]'s [`PushProvider::send_batch`] keeps in flight.
+const DEFAULT_BATCH_CONCURRENCY: usize = 16;
+
+/// The future returned by [`PushProvider`]'s methods.
+pub type ProviderFuture<'a, T> = Pin<Box<dyn Future<Output = T> + Send + 'a>>;
+
+/// Something that delivers [`PushRequest`]s, implemented by [`ApnsClient`] and
+/// [`MemoryProvider`].
+///
+/// Write application code against the trait to test it with a `MemoryProvider`, or to swap
+/// in another provider later, without changing call sites.
+///
+/// # Example
+///
+/// ```rust,no_run
+/// use apnrs::{ApnsError, ApnsPayload, MemoryProvider, PushProvider, PushRequest};
+///
+/// async fn notify_shipped(
+///     provider: &impl PushProvider,
+///     token: &str,
+///     payload: ApnsPayload,
+/// ) -> Result<(), ApnsError> {
+///     provider.send(&PushRequest::new(token, "com.example.shop", payload)).await?;
+///     Ok(())
+/// }
+///
+/// # async fn run(payload: ApnsPayload) -> Result<(), ApnsError> {
+/// // In a test
+/// let provider = MemoryProvider::new();
+/// notify_shipped(&provider, "DEVICE_TOKEN", payload).await?;
+/// assert_eq!(provider.sent()[0].topic, "com.example.shop");
+/// # Ok(())
+/// # }
+/// ```
+pub trait PushProvider: Send + Sync {
+    /// Sends one notification.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing either the outcome of the request or an `ApnsError`.
+    fn send<'a>(
+        &'a self,
+        request: &'a PushRequest,
+    ) -> ProviderFuture<'a, Result<SendOutcome, ApnsError>>;
+
+    /// Sends several notifications, which may differ in device token, topic, and payload.
+    ///
+    /// The default implementation sends them one after another.
+    ///
+    /// # Returns
+    ///
+    /// One result per request, in the same order as `requests`.
+    fn send_batch<'a>(
+        &'a self,
+        requests: &'a [PushRequest],
+    ) -> ProviderFuture<'a, Vec<Result<SendOutcome, ApnsError>>> {
+        Box::pin(async move {
+            let mut outcomes = Vec::with_capacity(requests.len());
+            for request in requests {
+                outcomes.push(self.send(request).await);
+            }
+            outcomes
+        })
+    }
+}
+
+impl PushProvider for ApnsClient {
+    fn send<'a>(
+        &'a self,
+        request: &'a PushRequest,
+    ) -> ProviderFuture<'a, Result<SendOutcome, ApnsError>> {
+        Box::pin(self.send_request(request))
+    }
+
+    /// Sends the notifications with [`ApnsClient::send_batch`], 16 at a time.
+    fn send_batch<'a>(
+        &'a self,
+        requests: &'a [PushRequest],
+    ) -> ProviderFuture<'a, Vec<Result<SendOutcome, ApnsError>>> {
+        Box::pin(ApnsClient::send_batch(
+            self,
+            requests,
+            DEFAULT_BATCH_CONCURRENCY,
+        ))
+    }
+}
+
+/// A [`PushProvider`] that records every request instead of sending it, for tests.
+///
+/// Requests are accepted with a `200 OK` unless their device token was registered with
+/// [`reject`](MemoryProvider::reject), in which case APNs' rejection is simulated.
+///
+/// Requests an [`ApnsClient`] would refuse before sending are refused with the same
+/// error and not recorded: a malformed topic, a payload over the limit of its push type
+/// (payloads are not trimmed), or a push type that needs certificate authentication unless
+/// the provider was built with [`certificate`](MemoryProvider::certificate).
+///
+/// # Example
+///
+/// ```rust
+/// use apnrs::{ErrorReason, MemoryProvider, StatusCode};
+///
+/// let provider = MemoryProvider::new().reject("STALE_TOKEN", StatusCode::GONE, ErrorReason::Unregistered);
+/// ```
+#[derive(Default)]
+pub struct MemoryProvider {
+    rejections: HashMap<String, (StatusCode, ErrorReason)>,
+    certificate: bool,
+    sent: Mutex<Vec<PushRequest>>,
+}
+
+impl MemoryProvider {
+    /// Creates a provider accepting every request.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Answers requests to `device_token` with `status` and `reason`, as APNs would reject
+    /// them.
+    pub fn reject(
+        mut self,
+        device_token: impl Into<String>,
+        status: StatusCode,
+        reason: ErrorReason,
+    ) -> Self {
+        self.rejections
+            .insert(device_token.into(), (status, reason));
+        self
+    }
+
+    /// Accepts push types that need certificate authentication, such as `mdm`, as a client
+    /// built with a certificate does.
+    pub fn certificate(mut self) -> Self {
+        self.certificate = true;
+        self
+    }
+
+    /// Returns every request sent so far, in the order they were sent, including rejected
+    /// ones.
+    pub fn sent(&self) -> Vec<PushRequest> {
+        self.sent.lock().unwrap().clone()
+    }
+
+    /// Forgets the requests sent so far.
+    pub fn clear(&self) {
+        self.sent.lock().unwrap().clear();
+    }
+
+    /// Builds the response APNs would give to `request`.
+    ///
+    /// # Returns
+    ///
+    /// The response, or the `ApnsError` the client would fail with before sending the
+    /// request, such as `ApnsError::InvalidHeader` for an `apns-id` that is not a valid
+    /// header value.
+    fn answer(&self, request: &PushRequest) -> Result<Response<Bytes>, ApnsError> {
+        let push_type = request.options.push_type;
+        if let Some(push_type) = push_type.filter(PushType::requires_certificate) {
+            if !self.certificate {
+                return Err(ApnsError::CertificateRequired(push_type));
+            }
+        }
+        validate_request_topic(&request.topic, push_type)?;
+        let size = serde_json::to_vec(&request.payload)?.len();
+        let limit = push_type.map_or(MAX_PAYLOAD_SIZE, |t| t.max_payload_size());
+        if size > limit {
+            return Err(NotificationError::TooLarge {
+                push_type: push_type.unwrap_or(PushType::Alert),
+                size,
+                limit,
+            }
+            .into());
+        }
+
+        let mut response = match self.rejections.get(&request.device_token) {
+            Some((status, reason)) => {
+                let body = serde_json::json!({ "reason": reason });
+                let mut response = Response::new(Bytes::from(body.to_string()));
+                *response.status_mut() = *status;
+                response
+            }
+            None => Response::new(Bytes::new()),
+        };
+        if let Some(apns_id) = request.options.apns_id.as_deref() {
+            response
+                .headers_mut()
+                .insert("apns-id", HeaderValue::from_str(apns_id)?);
+        }
+        Ok(response)
+    }
+}
+
+impl PushProvider for MemoryProvider {
+    fn send<'a>(
+        &'a self,
+        request: &'a PushRequest,
+    ) -> ProviderFuture<'a, Result<SendOutcome, ApnsError>> {
+        let answer = self.answer(request);
+        if answer.is_ok() {
+            self.sent.lock().unwrap().push(request.clone());
+        }
+        Box::pin(async move {
+            Ok(SendOutcome {
+                response: ApnsResponse::from_raw(answer?),
+                trimmed: None,
+                failure: None,
+                locale: None,
+                dry_run: None,
+                fallback: None,
+            })
+        })
+    }
+}
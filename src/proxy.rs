@@ -0,0 +1,94 @@
+//! Sending through an HTTP or SOCKS proxy.
+
+use crate::error::ApnsError;
+use std::fmt;
+
+/// A proxy all requests to APNs are sent through, set with
+/// [`ApnsClientBuilder::proxy`](crate::ApnsClientBuilder::proxy).
+///
+/// `http://` and `https://` URLs name an HTTP proxy, which APNs traffic tunnels through with
+/// `CONNECT`; `socks5://` and `socks5h://` URLs name a SOCKS5 proxy and need the `socks`
+/// feature. Without a configured proxy, the client honours the `HTTPS_PROXY` and `NO_PROXY`
+/// environment variables.
+///
+/// # Example
+///
+/// ```rust,no_run
+/// use apnrs::{ApnsClient, ProxyConfig};
+///
+/// # fn run(password: &str) -> Result<(), apnrs::ApnsError> {
+/// let client = ApnsClient::builder()
+///     .auth_key_path("path/to/auth/key")
+///     .team_id("TEAM_ID")
+///     .key_id("KEY_ID")
+///     .proxy(
+///         ProxyConfig::new("http://egress.internal:3128")
+///             .basic_auth("push-service", password)
+///             .no_proxy("localhost,.internal"),
+///     )
+///     .build()?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone)]
+pub struct ProxyConfig {
+    url: String,
+    credentials: Option<(String, String)>,
+    no_proxy: Option<String>,
+}
+
+impl ProxyConfig {
+    /// Sends requests through the proxy at `url`.
+    pub fn new(url: impl Into<String>) -> Self {
+        ProxyConfig {
+            url: url.into(),
+            credentials: None,
+            no_proxy: None,
+        }
+    }
+
+    /// Authenticates with the proxy using HTTP basic authentication, or username and password
+    /// authentication for SOCKS5.
+    pub fn basic_auth(mut self, username: impl Into<String>, password: impl Into<String>) -> Self {
+        self.credentials = Some((username.into(), password.into()));
+        self
+    }
+
+    /// Connects directly to the hosts in `hosts`, a comma-separated list in the format of the
+    /// `NO_PROXY` environment variable: host names, `.domain` suffixes, IP addresses, and
+    /// CIDR ranges.
+    pub fn no_proxy(mut self, hosts: impl Into<String>) -> Self {
+        self.no_proxy = Some(hosts.into());
+        self
+    }
+
+    /// Returns the proxy for the HTTP client.
+    ///
+    /// # Returns
+    ///
+    /// The proxy, or an `ApnsError` if the URL is not a valid proxy URL.
+    pub(crate) fn to_reqwest(&self) -> Result<reqwest::Proxy, ApnsError> {
+        let mut proxy = reqwest::Proxy::all(&self.url)
+            .map_err(|e| ApnsError::Config(format!("invalid proxy URL {:?}: {}", self.url, e)))?;
+        if let Some((username, password)) = &self.credentials {
+            proxy = proxy.basic_auth(username, password);
+        }
+        if let Some(hosts) = &self.no_proxy {
+            proxy = proxy.no_proxy(reqwest::NoProxy::from_string(hosts));
+        }
+        Ok(proxy)
+    }
+}
+
+impl fmt::Debug for ProxyConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ProxyConfig")
+            .field("url", &self.url)
+            .field(
+                "username",
+                &self.credentials.as_ref().map(|(username, _)| username),
+            )
+            .field("no_proxy", &self.no_proxy)
+            .finish()
+    }
+}
@@ -0,0 +1,127 @@
+//! Values of the `apns-push-type` header.
+
+use crate::topic::bundle_id;
+use crate::trim::{MAX_PAYLOAD_SIZE, MAX_VOIP_PAYLOAD_SIZE};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// The type of a notification, sent as the `apns-push-type` header.
+///
+/// # Variants
+///
+/// * `Alert` - A notification that displays an alert, plays a sound, or badges the app icon.
+/// * `Background` - A silent notification delivering content in the background.
+/// * `Location` - A request for the device's location, for location push service extensions.
+/// * `Voip` - An incoming VoIP call, delivered through PushKit.
+/// * `Complication` - An update for a watchOS app's complication.
+/// * `FileProvider` - A change to a File Provider extension's content.
+/// * `Mdm` - A request for a managed device to contact its MDM server.
+/// * `LiveActivity` - An update to a Live Activity.
+/// * `PushToTalk` - An update for a Push to Talk channel.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PushType {
+    Alert,
+    Background,
+    Location,
+    Voip,
+    Complication,
+    FileProvider,
+    Mdm,
+    LiveActivity,
+    PushToTalk,
+}
+
+impl PushType {
+    /// Returns the header value for this push type.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            PushType::Alert => "alert",
+            PushType::Background => "background",
+            PushType::Location => "location",
+            PushType::Voip => "voip",
+            PushType::Complication => "complication",
+            PushType::FileProvider => "fileprovider",
+            PushType::Mdm => "mdm",
+            PushType::LiveActivity => "liveactivity",
+            PushType::PushToTalk => "pushtotalk",
+        }
+    }
+
+    /// Returns the suffix Apple requires on the bundle ID in the topic for this push type,
+    /// if any.
+    pub fn topic_suffix(&self) -> Option<&'static str> {
+        match self {
+            PushType::Location => Some(".location-query"),
+            PushType::Voip => Some(".voip"),
+            PushType::Complication => Some(".complication"),
+            PushType::FileProvider => Some(".pushkit.fileprovider"),
+            PushType::LiveActivity => Some(".push-type.liveactivity"),
+            PushType::PushToTalk => Some(".voip-ptt"),
+            PushType::Alert | PushType::Background | PushType::Mdm => None,
+        }
+    }
+
+    /// Returns the push type an `apns-push-type` header value names, if any.
+    #[cfg(feature = "tokio")]
+    pub(crate) fn from_header(value: &str) -> Option<PushType> {
+        [
+            PushType::Alert,
+            PushType::Background,
+            PushType::Location,
+            PushType::Voip,
+            PushType::Complication,
+            PushType::FileProvider,
+            PushType::Mdm,
+            PushType::LiveActivity,
+            PushType::PushToTalk,
+        ]
+        .into_iter()
+        .find(|push_type| push_type.as_str() == value)
+    }
+
+    /// Returns `true` if APNs only accepts this push type from providers authenticated with a
+    /// certificate, rejecting token-based (`.p8`) authentication.
+    pub fn requires_certificate(&self) -> bool {
+        matches!(self, PushType::Mdm)
+    }
+
+    /// Returns the maximum size, in bytes, of a payload of this push type.
+    pub fn max_payload_size(&self) -> usize {
+        match self {
+            PushType::Voip => MAX_VOIP_PAYLOAD_SIZE,
+            _ => MAX_PAYLOAD_SIZE,
+        }
+    }
+
+    /// Returns the `apns-topic` for this push type: the bundle ID in `topic` followed by this
+    /// push type's suffix, if it has one.
+    ///
+    /// `topic` may be the bare bundle ID or already carry a suffix; the suffix of another push
+    /// type is replaced, so a notification cannot go out under a topic its push type does not
+    /// accept.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use apnrs::PushType;
+    ///
+    /// assert_eq!(PushType::LiveActivity.topic_for("com.example.app"), "com.example.app.push-type.liveactivity");
+    /// assert_eq!(PushType::Voip.topic_for("com.example.app.voip"), "com.example.app.voip");
+    /// assert_eq!(PushType::Complication.topic_for("com.example.app.voip"), "com.example.app.complication");
+    /// assert_eq!(PushType::Alert.topic_for("com.example.app.voip"), "com.example.app");
+    /// ```
+    pub fn topic_for(&self, topic: &str) -> String {
+        let bundle_id = bundle_id(topic);
+        match self.topic_suffix() {
+            Some(suffix) => format!("{}{}", bundle_id, suffix),
+            None => bundle_id.to_string(),
+        }
+    }
+}
+
+impl fmt::Display for PushType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
@@ -0,0 +1,158 @@
+//! Holding notifications back while it is night where their recipients are.
+
+use crate::ApnsError;
+#[cfg(feature = "chrono")]
+use chrono::Offset;
+#[cfg(feature = "chrono")]
+use chrono::TimeZone;
+#[cfg(feature = "chrono")]
+use chrono_tz::Tz;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Seconds in a day.
+const SECONDS_PER_DAY: i64 = 24 * 60 * 60;
+
+/// A daily window of local time, such as 22:00 to 08:00, during which a sender holds
+/// notifications back instead of waking their recipients.
+///
+/// Register it with [`ApnsSenderBuilder::quiet_hours`](crate::ApnsSenderBuilder::quiet_hours).
+/// It applies to notifications whose recipient's offset from UTC is known, set with
+/// [`PushRequest::recipient_utc_offset`](crate::PushRequest::recipient_utc_offset), or
+/// whose recipient's time zone is, set with `PushRequest::recipient_time_zone` under the
+/// `chrono` feature.
+///
+/// # Example
+///
+/// ```rust
+/// use apnrs::QuietHours;
+/// use std::time::{Duration, UNIX_EPOCH};
+///
+/// let quiet = QuietHours::new((22, 0), (8, 0))?;
+/// let hour = 60 * 60;
+///
+/// // 21:30 UTC is 23:30 at UTC+2, so the notification waits for 08:00 there, 06:00 UTC
+/// let late = UNIX_EPOCH + Duration::from_secs(21 * hour + 30 * 60);
+/// let morning = UNIX_EPOCH + Duration::from_secs(30 * hour);
+/// assert_eq!(quiet.release_at(late, 2 * hour as i32), Some(morning));
+///
+/// // Noon is outside the window, so it is sent right away
+/// assert_eq!(quiet.release_at(UNIX_EPOCH + Duration::from_secs(12 * hour), 0), None);
+/// # Ok::<(), apnrs::ApnsError>(())
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct QuietHours {
+    /// When the window opens, in seconds after local midnight.
+    start: i64,
+    /// When the window closes, in seconds after local midnight.
+    end: i64,
+}
+
+impl QuietHours {
+    /// Creates a window from `start` to `end`, each an `(hour, minute)` of local time. A
+    /// window whose end is earlier than its start spans midnight.
+    ///
+    /// # Returns
+    ///
+    /// The window, or `ApnsError::Config` if a time is not a valid time of day or both are
+    /// the same.
+    pub fn new(start: (u8, u8), end: (u8, u8)) -> Result<Self, ApnsError> {
+        let start = seconds_after_midnight(start)?;
+        let end = seconds_after_midnight(end)?;
+        if start == end {
+            return Err(ApnsError::Config(
+                "quiet hours must not start and end at the same time".to_string(),
+            ));
+        }
+        Ok(QuietHours { start, end })
+    }
+
+    /// Returns `true` if `time` falls within the window for a recipient `utc_offset`
+    /// seconds east of UTC.
+    pub fn contains(&self, time: SystemTime, utc_offset: i32) -> bool {
+        let now = local_time_of_day(time, utc_offset);
+        if self.start < self.end {
+            self.start <= now && now < self.end
+        } else {
+            now >= self.start || now < self.end
+        }
+    }
+
+    /// Returns when a notification due at `time`, for a recipient `utc_offset` seconds east
+    /// of UTC, may be sent: when the window closes if `time` falls within it, or `None` if
+    /// it can be sent at `time`.
+    pub fn release_at(&self, time: SystemTime, utc_offset: i32) -> Option<SystemTime> {
+        if !self.contains(time, utc_offset) {
+            return None;
+        }
+        let now = local_time_of_day(time, utc_offset);
+        let wait = (self.end - now).rem_euclid(SECONDS_PER_DAY) as u64;
+        let subsec = time
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .subsec_nanos();
+        Some(time + Duration::from_secs(wait) - Duration::from_nanos(subsec.into()))
+    }
+
+    /// Returns `true` if `time` falls within the window for a recipient in `zone`, at the
+    /// offset from UTC the zone has at `time`.
+    #[cfg(feature = "chrono")]
+    pub fn contains_in(&self, time: SystemTime, zone: Tz) -> bool {
+        self.contains(time, utc_offset_in(zone, time))
+    }
+
+    /// Returns when a notification due at `time`, for a recipient in `zone`, may be sent, as
+    /// [`release_at`](QuietHours::release_at) does, with the window closing at its local end
+    /// even if the zone's offset changes before then, such as when daylight saving time
+    /// starts overnight.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use apnrs::chrono_tz::America::New_York;
+    /// use apnrs::QuietHours;
+    /// use std::time::{Duration, UNIX_EPOCH};
+    ///
+    /// let quiet = QuietHours::new((22, 0), (8, 0))?;
+    ///
+    /// // 00:30 EST on 10 March 2024; clocks move forward at 02:00, so 08:00 is 12:00 UTC
+    /// let night = UNIX_EPOCH + Duration::from_secs(1_710_048_600);
+    /// let morning = UNIX_EPOCH + Duration::from_secs(1_710_072_000);
+    /// assert_eq!(quiet.release_at_in(night, New_York), Some(morning));
+    /// # Ok::<(), apnrs::ApnsError>(())
+    /// ```
+    #[cfg(feature = "chrono")]
+    pub fn release_at_in(&self, time: SystemTime, zone: Tz) -> Option<SystemTime> {
+        let release = self.release_at(time, utc_offset_in(zone, time))?;
+        // The window ends at its local time under the offset in force when it ends
+        let offset = utc_offset_in(zone, release);
+        Some(self.release_at(time, offset).unwrap_or(release))
+    }
+}
+
+/// Returns how many seconds east of UTC the local time of `zone` is at `time`.
+#[cfg(feature = "chrono")]
+fn utc_offset_in(zone: Tz, time: SystemTime) -> i32 {
+    let utc = crate::datetime::utc(time).naive_utc();
+    zone.offset_from_utc_datetime(&utc).fix().local_minus_utc()
+}
+
+/// Returns `(hour, minute)` in seconds after midnight, checking it is a time of day.
+fn seconds_after_midnight((hour, minute): (u8, u8)) -> Result<i64, ApnsError> {
+    if hour > 23 || minute > 59 {
+        return Err(ApnsError::Config(format!(
+            "{:02}:{:02} is not a time of day",
+            hour, minute
+        )));
+    }
+    Ok(i64::from(hour) * 3600 + i64::from(minute) * 60)
+}
+
+/// Returns the local time of day at `time`, `utc_offset` seconds east of UTC, in seconds
+/// after midnight.
+fn local_time_of_day(time: SystemTime, utc_offset: i32) -> i64 {
+    let secs = time
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs() as i64)
+        .unwrap_or(0);
+    (secs + i64::from(utc_offset)).rem_euclid(SECONDS_PER_DAY)
+}
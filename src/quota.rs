@@ -0,0 +1,169 @@
+//! Per-app rate limits and daily quotas for senders shared by several tenants.
+
+use crate::sender::Pacer;
+use crate::stats::StatsRecorder;
+use crate::topic::bundle_id;
+use crate::ApnsError;
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Seconds in a UTC day, after which daily quotas start over.
+const SECONDS_PER_DAY: u64 = 24 * 60 * 60;
+
+/// Limits how fast, and how much a day, an [`ApnsSender`](crate::ApnsSender) sends for one
+/// app, so one tenant's campaign cannot starve the others sharing the sender.
+///
+/// Register it with [`ApnsSenderBuilder::quota`](crate::ApnsSenderBuilder::quota).
+///
+/// # Example
+///
+/// ```rust,no_run
+/// use apnrs::{ApnsClient, ApnsSender, Quota};
+///
+/// # fn run(client: ApnsClient) {
+/// let sender = ApnsSender::builder(client)
+///     .rate_limit(2000.0)
+///     .quota("com.acme.app", Quota::new().rate_limit(500.0).daily_limit(1_000_000))
+///     .quota("com.globex.app", Quota::new().rate_limit(100.0))
+///     .build();
+/// # }
+/// ```
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Quota {
+    rate_limit: Option<f64>,
+    daily_limit: Option<u64>,
+}
+
+impl Quota {
+    /// Creates a quota without any limit.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Caps the notifications queued for the app at `per_second` a second, spaced evenly
+    /// (defaults to no limit).
+    pub fn rate_limit(mut self, per_second: f64) -> Self {
+        self.rate_limit = Some(per_second).filter(|rate| *rate > 0.0);
+        self
+    }
+
+    /// Caps the notifications accepted for the app at `limit` per UTC day (defaults to no
+    /// limit).
+    pub fn daily_limit(mut self, limit: u64) -> Self {
+        self.daily_limit = Some(limit);
+        self
+    }
+}
+
+/// The quotas of a sender, keyed by bundle ID.
+#[derive(Default)]
+pub(crate) struct Quotas {
+    apps: HashMap<String, AppQuota>,
+}
+
+/// The state of one app's quota.
+struct AppQuota {
+    pacer: Option<Pacer>,
+    daily_limit: Option<u64>,
+    /// The UTC day, in days since the Unix epoch, and how many notifications were accepted
+    /// on it.
+    used: Mutex<(u64, u64)>,
+}
+
+impl Quotas {
+    /// Creates the state of `quotas`, keyed by bundle ID.
+    pub(crate) fn new(quotas: HashMap<String, Quota>) -> Self {
+        let apps = quotas
+            .into_iter()
+            .map(|(app, quota)| {
+                let state = AppQuota {
+                    pacer: quota.rate_limit.map(Pacer::new),
+                    daily_limit: quota.daily_limit,
+                    used: Mutex::new((today(), 0)),
+                };
+                (app, state)
+            })
+            .collect();
+        Quotas { apps }
+    }
+
+    /// Counts a notification to `topic` against the daily quota of its app.
+    ///
+    /// # Returns
+    ///
+    /// `Ok(())` if the app has quota left, or `ApnsError::QuotaExceeded` if it used up its
+    /// quota for the day, which is also counted in `stats`.
+    pub(crate) fn admit(&self, topic: &str, stats: &StatsRecorder) -> Result<(), ApnsError> {
+        let app = bundle_id(topic);
+        let Some(AppQuota {
+            daily_limit: Some(limit),
+            used,
+            ..
+        }) = self.apps.get(app)
+        else {
+            return Ok(());
+        };
+        let mut used = used.lock().unwrap();
+        let day = today();
+        if used.0 != day {
+            *used = (day, 0);
+        }
+        if used.1 >= *limit {
+            stats.quota_exceeded(app);
+            return Err(ApnsError::QuotaExceeded {
+                app: app.to_string(),
+                limit: *limit,
+            });
+        }
+        used.1 += 1;
+        Ok(())
+    }
+
+    /// Gives back a notification to `topic` that [`admit`](Quotas::admit) counted but that
+    /// was not queued after all, such as because the workers had stopped.
+    pub(crate) fn refund(&self, topic: &str) {
+        let Some(AppQuota {
+            daily_limit: Some(_),
+            used,
+            ..
+        }) = self.apps.get(bundle_id(topic))
+        else {
+            return;
+        };
+        let mut used = used.lock().unwrap();
+        // A notification admitted yesterday no longer counts against today's quota
+        if used.0 == today() {
+            used.1 = used.1.saturating_sub(1);
+        }
+    }
+
+    /// Waits for the next free slot under the rate limit of the app of `topic`, if it has
+    /// one.
+    pub(crate) async fn pace(&self, topic: &str) {
+        let quota = self.apps.get(bundle_id(topic));
+        if let Some(pacer) = quota.and_then(|quota| quota.pacer.as_ref()) {
+            pacer.wait().await;
+        }
+    }
+
+    /// Returns how many more notifications the app with `bundle_id` may send today, or
+    /// `None` if it has no daily quota.
+    pub(crate) fn remaining(&self, bundle_id: &str) -> Option<u64> {
+        let quota = self.apps.get(bundle_id)?;
+        let limit = quota.daily_limit?;
+        let used = quota.used.lock().unwrap();
+        Some(match used.0 == today() {
+            true => limit.saturating_sub(used.1),
+            false => limit,
+        })
+    }
+}
+
+/// Returns the current UTC day, in days since the Unix epoch.
+fn today() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs() / SECONDS_PER_DAY)
+        .unwrap_or(0)
+}
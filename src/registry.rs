@@ -0,0 +1,97 @@
+//! Routing of topics to the credentials and environment of each app.
+
+use crate::environment::Environment;
+use crate::options::Credentials;
+use crate::topic::bundle_id;
+use std::collections::HashMap;
+
+/// The credentials and environment of every app a client sends for, keyed by bundle ID.
+///
+/// A client given a registry with
+/// [`ApnsClientBuilder::app_registry`](crate::ApnsClientBuilder::app_registry) looks up the
+/// bundle ID of each notification's topic, with suffixes such as `.voip` removed, then signs
+/// the request with that app's credentials and sends it to that app's environment. Topics of unregistered apps fall back to the client's own auth key and
+/// environment.
+///
+/// # Example
+///
+/// ```rust,no_run
+/// use apnrs::{ApnsClient, AppRegistry, Credentials, Environment};
+///
+/// # fn run() -> Result<(), apnrs::ApnsError> {
+/// let registry = AppRegistry::new()
+///     .register(
+///         "com.acme.shop",
+///         Credentials::from_key_path("keys/acme.p8", "ACME_TEAM", "ACME_KEY")?,
+///         Environment::Production,
+///     )
+///     .register(
+///         "com.globex.tv.beta",
+///         Credentials::from_key_path("keys/globex.p8", "GLOBEX_TEAM", "GLOBEX_KEY")?,
+///         Environment::Sandbox,
+///     );
+///
+/// let client = ApnsClient::builder().app_registry(registry).build()?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct AppRegistry {
+    apps: HashMap<String, RegisteredApp>,
+}
+
+/// The credentials and environment notifications for one app are sent with.
+///
+/// # Fields
+///
+/// * `credentials` - The auth key requests for the app are signed with.
+/// * `environment` - The APNs environment notifications for the app are sent to.
+#[derive(Debug, Clone)]
+pub struct RegisteredApp {
+    pub credentials: Credentials,
+    pub environment: Environment,
+}
+
+impl AppRegistry {
+    /// Creates an empty registry.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Registers the app with the given bundle ID, replacing any earlier registration.
+    pub fn register(
+        mut self,
+        bundle_id: impl Into<String>,
+        credentials: Credentials,
+        environment: Environment,
+    ) -> Self {
+        self.apps.insert(
+            bundle_id.into(),
+            RegisteredApp {
+                credentials,
+                environment,
+            },
+        );
+        self
+    }
+
+    /// Returns the app notifications to `topic` are sent for, if it is registered.
+    pub fn get(&self, topic: &str) -> Option<&RegisteredApp> {
+        self.apps.get(bundle_id(topic))
+    }
+
+    /// Returns the bundle IDs of the registered apps, in no particular order.
+    pub fn bundle_ids(&self) -> impl Iterator<Item = &str> {
+        self.apps.keys().map(String::as_str)
+    }
+
+    /// Returns the number of registered apps.
+    pub fn len(&self) -> usize {
+        self.apps.len()
+    }
+
+    /// Returns `true` if no app is registered.
+    pub fn is_empty(&self) -> bool {
+        self.apps.is_empty()
+    }
+}
@@ -0,0 +1,112 @@
+//! Summaries of the outcomes of a batch of sends.
+
+use crate::classify::{DefaultClassifier, FailureClass, FailureClassifier};
+use crate::{ApnsError, SendOutcome};
+use http::StatusCode;
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+/// A summary of a batch of sends, such as one run of a [`Campaign`](crate::Campaign).
+///
+/// Build one for any other batch by passing each result to [`record`](BatchReport::record)
+/// and setting `duration` when the batch is done.
+///
+/// # Fields
+///
+/// * `delivered` - Notifications APNs accepted.
+/// * `unregistered` - Notifications not delivered because the device token is no longer
+///   valid, whether APNs rejected them or they were skipped.
+/// * `throttled` - Notifications APNs rejected with `429 Too Many Requests`.
+/// * `failed` - Notifications that were not delivered for any other reason.
+/// * `duplicates` - Notifications not sent because their device token already appeared in
+///   the batch.
+/// * `invalid_tokens` - The device tokens counted in `unregistered`, once each in the order
+///   they were recorded.
+/// * `duration` - How long the batch took.
+///
+/// # Example
+///
+/// ```rust
+/// use apnrs::{ApnsError, BatchReport, ErrorReason, StatusCode};
+///
+/// let mut report = BatchReport::new();
+/// report.record("TOKEN_1", &Err(ApnsError::InvalidToken));
+/// report.record(
+///     "TOKEN_2",
+///     &Err(ApnsError::Rejected {
+///         status: StatusCode::TOO_MANY_REQUESTS,
+///         reason: Some(ErrorReason::TooManyRequests),
+///     }),
+/// );
+///
+/// assert_eq!((report.unregistered, report.throttled), (1, 1));
+/// assert_eq!(report.invalid_tokens, vec!["TOKEN_1"]);
+/// assert_eq!(report.total(), 2);
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BatchReport {
+    pub delivered: usize,
+    pub unregistered: usize,
+    pub throttled: usize,
+    pub failed: usize,
+    #[serde(default)]
+    pub duplicates: usize,
+    pub invalid_tokens: Vec<String>,
+    pub duration: Duration,
+}
+
+impl BatchReport {
+    /// Creates an empty report.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Counts the result of sending to `device_token`.
+    ///
+    /// Device tokens are considered invalid when [`DefaultClassifier`] classifies the failure
+    /// as a dead token, as it does for `410 Unregistered`, `BadDeviceToken`, and tokens the
+    /// client's token store already marked invalid.
+    pub fn record(&mut self, device_token: &str, result: &Result<SendOutcome, ApnsError>) {
+        let class = match result {
+            Ok(outcome) if outcome.response.is_success() => {
+                self.delivered += 1;
+                return;
+            }
+            Ok(outcome) => {
+                let response = &outcome.response;
+                if response.status == StatusCode::TOO_MANY_REQUESTS {
+                    self.throttled += 1;
+                    return;
+                }
+                DefaultClassifier.classify_rejection(response.status, response.error.as_ref())
+            }
+            Err(ApnsError::Rejected { status, .. }) if *status == StatusCode::TOO_MANY_REQUESTS => {
+                self.throttled += 1;
+                return;
+            }
+            Err(ApnsError::DuplicateToken) => {
+                self.duplicates += 1;
+                return;
+            }
+            Err(error) => DefaultClassifier.classify_error(error),
+        };
+
+        if class == FailureClass::DeadToken {
+            self.unregistered += 1;
+            if !self
+                .invalid_tokens
+                .iter()
+                .any(|token| token == device_token)
+            {
+                self.invalid_tokens.push(device_token.to_string());
+            }
+        } else {
+            self.failed += 1;
+        }
+    }
+
+    /// Returns the number of results recorded.
+    pub fn total(&self) -> usize {
+        self.delivered + self.unregistered + self.throttled + self.failed + self.duplicates
+    }
+}
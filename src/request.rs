@@ -0,0 +1,342 @@
+//! Self-contained descriptions of a single push.
+
+use crate::privacy::mask_token;
+use crate::topic::{validate_request_topic, TopicError};
+#[cfg(feature = "tokio")]
+use crate::{ApnsError, SendOutcome};
+use crate::{ApnsPayload, SendOptions};
+#[cfg(feature = "tokio")]
+use openssl::rand::rand_bytes;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+#[cfg(feature = "tokio")]
+use std::future::Future;
+use std::time::{Duration, SystemTime};
+
+/// A single notification addressed to one device, with everything needed to send it.
+///
+/// The same value is accepted by [`ApnsClient::send_request`](crate::ApnsClient::send_request),
+/// an [`ApnsSender`](crate::ApnsSender)'s queue, its [sink](crate::ApnsSender::sink), and the
+/// client's `tower::Service`, and it serializes, so a notification can be built in one place,
+/// persisted, and replayed later.
+///
+/// # Fields
+///
+/// * `device_token` - The device token of the target device.
+/// * `topic` - The topic (usually the app's bundle ID) for the notification.
+/// * `payload` - The payload of the notification.
+/// * `options` - The per-request settings it is sent with, such as its push type, priority,
+///   `apns-collapse-id`, and `apns-id`. An [`ApnsSender`](crate::ApnsSender) generates an
+///   `apns-id` when the notification is enqueued without one, and sends every retry with it,
+///   so APNs and the app see one notification. Serialized next to the other fields; see
+///   [`SendOptions`] for what is not serialized.
+/// * `class` - How urgently a sender dispatches the notification relative to others queued
+///   with it.
+/// * `deadline` - When a sender gives up on the notification, if it has not been delivered
+///   by then.
+/// * `recipient_utc_offset` - How many seconds east of UTC the recipient's local time is,
+///   if known, so a sender with [quiet hours](crate::ApnsSenderBuilder::quiet_hours) holds
+///   the notification back during the recipient's night.
+/// * `recipient_time_zone` - The recipient's IANA time zone, if known, used for quiet hours
+///   instead of `recipient_utc_offset` so they follow daylight saving time. Requires the
+///   `chrono` feature.
+///
+/// # Example
+///
+/// ```rust
+/// use apnrs::{ApnsPayload, Priority, PushRequest, SendOptions};
+///
+/// # fn run(payload: ApnsPayload) -> Result<(), serde_json::Error> {
+/// let request = PushRequest::new("DEVICE_TOKEN", "com.example.app", payload)
+///     .options(SendOptions::new().priority(Priority::Normal))
+///     .collapse_id("score");
+///
+/// let json = serde_json::to_string(&request)?;
+/// let replayed: PushRequest = serde_json::from_str(&json)?;
+/// assert_eq!(serde_json::to_string(&replayed)?, json);
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone, Serialize, Deserialize)]
+pub struct PushRequest {
+    pub device_token: String,
+    pub topic: String,
+    pub payload: ApnsPayload,
+    #[serde(flatten)]
+    pub options: SendOptions,
+    #[serde(default)]
+    pub class: TrafficClass,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub deadline: Option<SystemTime>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub recipient_utc_offset: Option<i32>,
+    #[cfg(feature = "chrono")]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub recipient_time_zone: Option<chrono_tz::Tz>,
+}
+
+impl fmt::Debug for PushRequest {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut debug = f.debug_struct("PushRequest");
+        debug
+            .field("device_token", &mask_token(&self.device_token))
+            .field("topic", &self.topic)
+            .field("payload", &self.payload)
+            .field("options", &self.options)
+            .field("class", &self.class)
+            .field("deadline", &self.deadline)
+            .field("recipient_utc_offset", &self.recipient_utc_offset);
+        #[cfg(feature = "chrono")]
+        debug.field("recipient_time_zone", &self.recipient_time_zone);
+        debug.finish()
+    }
+}
+
+/// How urgently a queued notification is dispatched, so bulk traffic cannot hold up
+/// notifications users are waiting for.
+///
+/// Senders keep a queue per class and their workers always take from the highest class
+/// with notifications waiting, so a burst of marketing pushes does not delay one-time
+/// passcodes. Each class has its own capacity, so a full marketing queue does not block
+/// enqueueing transactional notifications either.
+///
+/// # Variants
+///
+/// * `Transactional` - Notifications users are waiting for, such as one-time passcodes or
+///   messages, dispatched before any other.
+/// * `Standard` - Everything not tagged otherwise.
+/// * `Marketing` - Bulk traffic such as campaigns, dispatched only when nothing else waits.
+///
+/// # Example
+///
+/// ```rust
+/// use apnrs::{ApnsPayload, PushRequest, TrafficClass};
+///
+/// # fn run(payload: ApnsPayload) {
+/// let request = PushRequest::new("DEVICE_TOKEN", "com.example.app", payload)
+///     .class(TrafficClass::Transactional);
+/// assert!(TrafficClass::Transactional < TrafficClass::Marketing);
+/// # }
+/// ```
+#[derive(
+    Debug, Clone, Copy, Default, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize,
+)]
+#[serde(rename_all = "snake_case")]
+pub enum TrafficClass {
+    Transactional,
+    #[default]
+    Standard,
+    Marketing,
+}
+
+impl TrafficClass {
+    /// Every class, in the order they are dispatched.
+    pub const ALL: [TrafficClass; 3] = [
+        TrafficClass::Transactional,
+        TrafficClass::Standard,
+        TrafficClass::Marketing,
+    ];
+
+    /// Returns the position of the class in [`ALL`](TrafficClass::ALL).
+    #[cfg(feature = "tokio")]
+    pub(crate) fn rank(self) -> usize {
+        self as usize
+    }
+}
+
+impl PushRequest {
+    /// Creates a request delivering `payload` to `device_token` under `topic`.
+    pub fn new(
+        device_token: impl Into<String>,
+        topic: impl Into<String>,
+        payload: ApnsPayload,
+    ) -> Self {
+        PushRequest {
+            device_token: device_token.into(),
+            topic: topic.into(),
+            payload,
+            options: SendOptions::new(),
+            class: TrafficClass::default(),
+            deadline: None,
+            recipient_utc_offset: None,
+            #[cfg(feature = "chrono")]
+            recipient_time_zone: None,
+        }
+    }
+
+    /// Sends the notification with an `apns-collapse-id`, so the device shows only the
+    /// latest notification sharing it, and an [`ApnsSender`](crate::ApnsSender) with a
+    /// [`coalesce_window`](crate::ApnsSenderBuilder::coalesce_window) sends only the latest.
+    pub fn collapse_id(mut self, collapse_id: impl Into<String>) -> Self {
+        self.options = self.options.collapse_id(collapse_id);
+        self
+    }
+
+    /// Sends the notification with `apns_id`, a canonical UUID, instead of one APNs or the
+    /// sender generates.
+    pub fn apns_id(mut self, apns_id: impl Into<String>) -> Self {
+        self.options = self.options.apns_id(apns_id);
+        self
+    }
+
+    /// Sends the notification with `options`, replacing any set before, including a
+    /// collapse ID or `apns-id`.
+    pub fn options(mut self, options: SendOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    /// Tags the notification with a traffic class, which decides how soon a sender
+    /// dispatches it (defaults to [`TrafficClass::Standard`]).
+    pub fn class(mut self, class: TrafficClass) -> Self {
+        self.class = class;
+        self
+    }
+
+    /// Gives up on the notification if it has not been delivered by `deadline` (defaults to
+    /// no deadline).
+    ///
+    /// A sender checks the deadline while the notification is queued, waiting out APNs'
+    /// `Retry-After`, or backing off between retries: once it has passed, or a wait would
+    /// run past it, the notification is dropped and its receipt resolves to
+    /// `ApnsError::DeadlineExceeded` holding the time that was left. A send in flight when
+    /// the deadline passes is abandoned. Use it for notifications that are worthless late,
+    /// such as a ride arriving or a one-time passcode.
+    ///
+    /// The deadline is kept in the sender's journal, so a notification resumed after a
+    /// restart is still dropped once it is stale.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use apnrs::{ApnsPayload, PushRequest};
+    /// use std::time::{Duration, SystemTime};
+    ///
+    /// # fn run(payload: ApnsPayload) {
+    /// let request = PushRequest::new("DEVICE_TOKEN", "com.example.app", payload)
+    ///     .deadline(SystemTime::now() + Duration::from_secs(60));
+    /// assert!(request.deadline.is_some());
+    /// # }
+    /// ```
+    pub fn deadline(mut self, deadline: SystemTime) -> Self {
+        self.deadline = Some(deadline);
+        self
+    }
+
+    /// Gives up on the notification if it has not been delivered within `within` from now,
+    /// as [`deadline`](PushRequest::deadline) does.
+    pub fn give_up_after(self, within: Duration) -> Self {
+        self.deadline(SystemTime::now() + within)
+    }
+
+    /// Sets how many seconds east of UTC the recipient's local time is, such as `-18000`
+    /// for New York in winter, so a sender with
+    /// [quiet hours](crate::ApnsSenderBuilder::quiet_hours) holds the notification back
+    /// during the recipient's night (defaults to unknown, which is never held).
+    ///
+    /// Apps can report the offset with the device token, e.g. from
+    /// `TimeZone.current.secondsFromGMT()`, and report it again when it changes.
+    pub fn recipient_utc_offset(mut self, seconds: i32) -> Self {
+        self.recipient_utc_offset = Some(seconds);
+        self
+    }
+
+    /// Sets the recipient's IANA time zone, such as `America/New_York`, so a sender with
+    /// [quiet hours](crate::ApnsSenderBuilder::quiet_hours) holds the notification back
+    /// during the recipient's night, at the offset the zone has on that night (defaults to
+    /// unknown).
+    ///
+    /// Unlike a [`recipient_utc_offset`](PushRequest::recipient_utc_offset), which it takes
+    /// precedence over, the zone follows daylight saving time, so a notification held across
+    /// a change of offset is still released at the end of the window in local time. Apps can
+    /// report the zone with the device token, e.g. from `TimeZone.current.identifier`.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use apnrs::chrono_tz::Tz;
+    /// use apnrs::{ApnsPayload, PushRequest};
+    ///
+    /// # fn run(payload: ApnsPayload) -> Result<(), apnrs::chrono_tz::ParseError> {
+    /// let zone: Tz = "America/New_York".parse()?;
+    /// let request = PushRequest::new("DEVICE_TOKEN", "com.example.app", payload)
+    ///     .recipient_time_zone(zone);
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[cfg(feature = "chrono")]
+    pub fn recipient_time_zone(mut self, zone: chrono_tz::Tz) -> Self {
+        self.recipient_time_zone = Some(zone);
+        self
+    }
+
+    /// Checks that the topic is a well-formed bundle ID carrying the suffix the push type
+    /// in the options requires, as sending does, so a request built from untrusted input can
+    /// be rejected before it is queued; see [`validate_topic_for`](crate::validate_topic_for).
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use apnrs::{ApnsPayload, PushRequest, PushType, SendOptions, TopicError};
+    ///
+    /// # fn run(payload: ApnsPayload) {
+    /// let request = PushRequest::new("DEVICE_TOKEN", "com.example.app", payload)
+    ///     .options(SendOptions::new().push_type(PushType::Voip));
+    /// assert!(matches!(request.validate_topic(), Err(TopicError::WrongSuffix { .. })));
+    /// # }
+    /// ```
+    pub fn validate_topic(&self) -> Result<(), TopicError> {
+        validate_request_topic(&self.topic, self.options.push_type)
+    }
+
+    /// Returns how long is left until the notification's deadline, zero once it has
+    /// passed, or `None` if it has no deadline.
+    #[cfg(feature = "tokio")]
+    pub(crate) fn time_left(&self) -> Option<Duration> {
+        self.deadline.map(|deadline| {
+            deadline
+                .duration_since(SystemTime::now())
+                .unwrap_or_default()
+        })
+    }
+
+    /// Runs `send`, abandoning it with `ApnsError::DeadlineExceeded` if the notification's
+    /// deadline passes first.
+    #[cfg(feature = "tokio")]
+    pub(crate) async fn within_deadline<F>(&self, send: F) -> Result<SendOutcome, ApnsError>
+    where
+        F: Future<Output = Result<SendOutcome, ApnsError>>,
+    {
+        match self.time_left() {
+            None => send.await,
+            Some(left) if left.is_zero() => Err(ApnsError::DeadlineExceeded(left)),
+            Some(left) => tokio::time::timeout(left, send)
+                .await
+                .unwrap_or(Err(ApnsError::DeadlineExceeded(left))),
+        }
+    }
+
+    /// Returns the `apns-id` of the notification, generating one first if it has none.
+    #[cfg(feature = "tokio")]
+    pub(crate) fn assign_apns_id(&mut self) -> &str {
+        self.options.apns_id.get_or_insert_with(new_apns_id)
+    }
+}
+
+/// Generates a random (version 4) UUID in the lowercase canonical form APNs expects.
+#[cfg(feature = "tokio")]
+pub(crate) fn new_apns_id() -> String {
+    let mut bytes = [0u8; 16];
+    rand_bytes(&mut bytes).expect("OpenSSL failed to generate random bytes");
+    bytes[6] = (bytes[6] & 0x0f) | 0x40;
+    bytes[8] = (bytes[8] & 0x3f) | 0x80;
+    let hex: String = bytes.iter().map(|b| format!("{:02x}", b)).collect();
+    format!(
+        "{}-{}-{}-{}-{}",
+        &hex[..8],
+        &hex[8..12],
+        &hex[12..16],
+        &hex[16..20],
+        &hex[20..]
+    )
+}
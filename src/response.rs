@@ -0,0 +1,171 @@
+//! Typed APNs responses.
+
+#[cfg(any(feature = "chrono", feature = "time"))]
+use crate::datetime;
+use crate::datetime::from_unix_millis;
+use crate::error::{ErrorBody, ErrorReason};
+use bytes::Bytes;
+use http::header::RETRY_AFTER;
+use http::{Response, StatusCode};
+use std::time::{Duration, SystemTime};
+
+/// APNs' answer to a notification, with the details callers need already parsed.
+///
+/// The client reads the body to its end before returning, so the HTTP/2 stream is closed
+/// and its flow-control window released whether or not the response is looked at.
+///
+/// # Fields
+///
+/// * `status` - The HTTP status APNs answered with.
+/// * `apns_id` - The `apns-id` of the notification: the one it was sent with, or the one APNs
+///   assigned if it was sent without.
+/// * `apns_unique_id` - The `apns-unique-id` APNs assigns to each notification in the sandbox
+///   environment, for looking it up in the Push Notifications Console; `None` in production.
+/// * `error` - The reason APNs gave for rejecting the notification, if it was rejected.
+/// * `unregistered_at` - For `410 Unregistered`, when APNs last confirmed the token was no
+///   longer valid, in milliseconds since the Unix epoch; see
+///   [`unregistered_time`](ApnsResponse::unregistered_time) for it as a point in time.
+///
+/// # Example
+///
+/// ```rust,no_run
+/// use apnrs::{ApnsClient, ApnsPayload, ErrorReason};
+///
+/// # async fn run(client: ApnsClient, payload: ApnsPayload) -> Result<(), apnrs::ApnsError> {
+/// let outcome = client.send("DEVICE_TOKEN", "com.example.app", &payload).await?;
+/// let response = outcome.response;
+/// match response.error {
+///     None => println!("accepted as {:?}", response.apns_id),
+///     Some(ErrorReason::Unregistered) => println!("gone since {:?}", response.unregistered_at),
+///     Some(reason) => println!("{} {}", response.status, reason),
+/// }
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct ApnsResponse {
+    pub status: StatusCode,
+    pub apns_id: Option<String>,
+    pub apns_unique_id: Option<String>,
+    pub error: Option<ErrorReason>,
+    pub unregistered_at: Option<u64>,
+    raw: Response<Bytes>,
+}
+
+impl ApnsResponse {
+    /// Returns when APNs last confirmed the token was no longer valid, for
+    /// `410 Unregistered`.
+    pub fn unregistered_time(&self) -> Option<SystemTime> {
+        self.unregistered_at.map(from_unix_millis)
+    }
+
+    /// Returns [`unregistered_time`](ApnsResponse::unregistered_time) as a `chrono`
+    /// date-time in UTC.
+    ///
+    /// # Example
+    ///
+    /// ```rust,no_run
+    /// use apnrs::{ApnsClient, ApnsPayload};
+    ///
+    /// # async fn run(client: ApnsClient, payload: ApnsPayload) -> Result<(), apnrs::ApnsError> {
+    /// let outcome = client.send("DEVICE_TOKEN", "com.example.app", &payload).await?;
+    /// if let Some(since) = outcome.response.unregistered_at_utc() {
+    ///     println!("unregistered since {}", since.to_rfc3339());
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[cfg(feature = "chrono")]
+    pub fn unregistered_at_utc(&self) -> Option<chrono::DateTime<chrono::Utc>> {
+        self.unregistered_time().map(datetime::utc)
+    }
+
+    /// Returns [`unregistered_time`](ApnsResponse::unregistered_time) as a `time`
+    /// date-time at UTC.
+    #[cfg(feature = "time")]
+    pub fn unregistered_at_offset(&self) -> Option<time::OffsetDateTime> {
+        self.unregistered_time().map(datetime::offset)
+    }
+
+    /// Parses a buffered APNs response.
+    pub(crate) fn from_raw(raw: Response<Bytes>) -> Self {
+        let header = |name: &str| {
+            raw.headers()
+                .get(name)
+                .and_then(|v| v.to_str().ok())
+                .map(str::to_string)
+        };
+        let body = if raw.status().is_success() {
+            None
+        } else {
+            serde_json::from_slice::<ErrorBody>(raw.body()).ok()
+        };
+        ApnsResponse {
+            status: raw.status(),
+            apns_id: header("apns-id"),
+            apns_unique_id: header("apns-unique-id"),
+            unregistered_at: body.as_ref().and_then(|b| b.timestamp),
+            error: body.map(|b| b.reason),
+            raw,
+        }
+    }
+
+    /// Buffers a response and parses it.
+    ///
+    /// The body is read to its end even though APNs only sends one on rejections, so the
+    /// stream never stays half-consumed on the shared connection.
+    ///
+    /// # Returns
+    ///
+    /// The response, or a `reqwest::Error` if its body could not be read.
+    #[cfg(feature = "tokio")]
+    pub(crate) async fn read(response: reqwest::Response) -> Result<Self, reqwest::Error> {
+        let mut builder = Response::builder()
+            .status(response.status())
+            .version(response.version());
+        if let Some(headers) = builder.headers_mut() {
+            *headers = response.headers().clone();
+        }
+        let body = response.bytes().await?;
+        let raw = builder
+            .body(body)
+            .expect("status and headers were taken from a valid response");
+        Ok(Self::from_raw(raw))
+    }
+
+    /// Returns `true` if APNs accepted the notification.
+    pub fn is_success(&self) -> bool {
+        self.status.is_success()
+    }
+
+    /// Returns `true` if APNs reported the device token as no longer registered.
+    pub fn is_unregistered(&self) -> bool {
+        self.status == StatusCode::GONE || self.error == Some(ErrorReason::Unregistered)
+    }
+
+    /// Returns how long APNs asked to wait before sending again, from the `Retry-After`
+    /// header it may add to `429 TooManyRequests` and `503 ServiceUnavailable` responses.
+    ///
+    /// The header may give a number of seconds or an HTTP date; a date in the past is a
+    /// wait of zero.
+    pub fn retry_after(&self) -> Option<Duration> {
+        let value = self.raw.headers().get(RETRY_AFTER)?.to_str().ok()?.trim();
+        match value.parse::<u64>() {
+            Ok(seconds) => Some(Duration::from_secs(seconds)),
+            Err(_) => {
+                let date = httpdate::parse_http_date(value).ok()?;
+                Some(date.duration_since(SystemTime::now()).unwrap_or_default())
+            }
+        }
+    }
+
+    /// Returns the underlying HTTP response, for headers and details not parsed here.
+    pub fn raw(&self) -> &Response<Bytes> {
+        &self.raw
+    }
+
+    /// Returns the underlying HTTP response, consuming this one.
+    pub fn into_raw(self) -> Response<Bytes> {
+        self.raw
+    }
+}
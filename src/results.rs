@@ -0,0 +1,238 @@
+//! Streaming send results to newline-delimited JSON, for campaigns too large to keep in memory.
+
+use crate::privacy::mask_token;
+use crate::{ApnsError, SendOutcome};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::io;
+use std::path::PathBuf;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use tokio::fs::File;
+use tokio::io::{AsyncWrite, AsyncWriteExt};
+
+/// How often buffered results are flushed by default.
+const DEFAULT_FLUSH_INTERVAL: Duration = Duration::from_secs(1);
+
+/// The result of one send, as written by a [`ResultsWriter`].
+///
+/// # Fields
+///
+/// * `timestamp_ms` - When the result was written, in milliseconds since the Unix epoch.
+/// * `device_token` - The device token the notification was sent to.
+/// * `status` - The HTTP status APNs answered with, or `None` if it did not answer.
+/// * `reason` - The reason APNs gave for rejecting the notification, if any.
+/// * `apns_id` - The `apns-id` APNs assigned to the notification, if it answered.
+/// * `error` - The error that kept the notification from being sent, if any.
+#[derive(Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ResultRecord {
+    pub timestamp_ms: u64,
+    pub device_token: String,
+    pub status: Option<u16>,
+    pub reason: Option<String>,
+    pub apns_id: Option<String>,
+    pub error: Option<String>,
+}
+
+impl fmt::Debug for ResultRecord {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ResultRecord")
+            .field("timestamp_ms", &self.timestamp_ms)
+            .field("device_token", &mask_token(&self.device_token))
+            .field("status", &self.status)
+            .field("reason", &self.reason)
+            .field("apns_id", &self.apns_id)
+            .field("error", &self.error)
+            .finish()
+    }
+}
+
+impl ResultRecord {
+    /// Describes the result of sending to `device_token`.
+    pub fn new(device_token: &str, result: &Result<SendOutcome, ApnsError>) -> Self {
+        let timestamp_ms = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |d| d.as_millis() as u64);
+        match result {
+            Ok(outcome) => ResultRecord {
+                timestamp_ms,
+                device_token: device_token.to_string(),
+                status: Some(outcome.response.status.as_u16()),
+                reason: outcome
+                    .response
+                    .error
+                    .as_ref()
+                    .map(|r| r.as_str().to_string()),
+                apns_id: outcome.response.apns_id.clone(),
+                error: None,
+            },
+            Err(error) => ResultRecord {
+                timestamp_ms,
+                device_token: device_token.to_string(),
+                status: None,
+                reason: None,
+                apns_id: None,
+                error: Some(error.to_string()),
+            },
+        }
+    }
+}
+
+/// Where a [`ResultsWriter`] writes to.
+enum Output {
+    /// Any writer, written to indefinitely.
+    Writer(Box<dyn AsyncWrite + Send + Unpin>),
+    /// A file that is replaced by a numbered successor once it reaches `max_bytes`.
+    Rotating {
+        file: File,
+        path: PathBuf,
+        max_bytes: u64,
+        generation: u32,
+    },
+}
+
+/// Writes send results as newline-delimited JSON, one [`ResultRecord`] per line.
+///
+/// Results are buffered and flushed at most every second (see
+/// [`flush_interval`](ResultsWriter::flush_interval)), so writing stays cheap however many
+/// results a campaign produces, and nothing is kept in memory once flushed. Call
+/// [`finish`](ResultsWriter::finish) when done to flush the rest.
+///
+/// # Example
+///
+/// ```rust,no_run
+/// use apnrs::{ApnsClient, Campaign, ResultsWriter};
+///
+/// # async fn run(client: ApnsClient, mut campaign: Campaign) -> Result<(), apnrs::ApnsError> {
+/// // results.ndjson, then results.ndjson.1, results.ndjson.2, ... of up to 64 MB each
+/// let mut results = ResultsWriter::rotating("results.ndjson", 64 * 1024 * 1024).await?;
+/// campaign.run_until_recording(&client, || false, &mut results).await?;
+/// results.finish().await?;
+/// # Ok(())
+/// # }
+/// ```
+pub struct ResultsWriter {
+    output: Output,
+    buffer: Vec<u8>,
+    written: u64,
+    flush_interval: Duration,
+    last_flush: Instant,
+}
+
+impl ResultsWriter {
+    /// Creates a writer streaming results to `writer`, such as a socket or an open file.
+    pub fn new(writer: impl AsyncWrite + Send + Unpin + 'static) -> Self {
+        Self::with_output(Output::Writer(Box::new(writer)))
+    }
+
+    /// Creates a writer streaming results to the file at `path`, starting a new file named
+    /// `path.1`, `path.2`, and so on whenever the current one reaches `max_bytes`.
+    ///
+    /// # Returns
+    ///
+    /// The writer, or an error if the first file cannot be created.
+    pub async fn rotating(path: impl Into<PathBuf>, max_bytes: u64) -> io::Result<Self> {
+        let path = path.into();
+        let file = File::create(&path).await?;
+        Ok(Self::with_output(Output::Rotating {
+            file,
+            path,
+            max_bytes: max_bytes.max(1),
+            generation: 0,
+        }))
+    }
+
+    fn with_output(output: Output) -> Self {
+        ResultsWriter {
+            output,
+            buffer: Vec::new(),
+            written: 0,
+            flush_interval: DEFAULT_FLUSH_INTERVAL,
+            last_flush: Instant::now(),
+        }
+    }
+
+    /// Sets how long results may stay buffered before they are flushed (defaults to one
+    /// second). A zero interval flushes after every result.
+    pub fn flush_interval(mut self, interval: Duration) -> Self {
+        self.flush_interval = interval;
+        self
+    }
+
+    /// Writes the result of sending to `device_token`.
+    pub async fn write(
+        &mut self,
+        device_token: &str,
+        result: &Result<SendOutcome, ApnsError>,
+    ) -> io::Result<()> {
+        self.write_record(&ResultRecord::new(device_token, result))
+            .await
+    }
+
+    /// Writes a record, flushing if the flush interval has passed.
+    pub async fn write_record(&mut self, record: &ResultRecord) -> io::Result<()> {
+        serde_json::to_writer(&mut self.buffer, record)?;
+        self.buffer.push(b'\n');
+        if self.last_flush.elapsed() >= self.flush_interval {
+            self.flush().await?;
+        }
+        Ok(())
+    }
+
+    /// Writes out every buffered result.
+    pub async fn flush(&mut self) -> io::Result<()> {
+        if !self.buffer.is_empty() {
+            self.rotate_if_full().await?;
+            let buffer = std::mem::take(&mut self.buffer);
+            match &mut self.output {
+                Output::Writer(writer) => writer.write_all(&buffer).await?,
+                Output::Rotating { file, .. } => file.write_all(&buffer).await?,
+            }
+            self.written += buffer.len() as u64;
+            // Reuse the allocation for the next batch
+            self.buffer = buffer;
+            self.buffer.clear();
+        }
+        match &mut self.output {
+            Output::Writer(writer) => writer.flush().await?,
+            Output::Rotating { file, .. } => file.flush().await?,
+        }
+        self.last_flush = Instant::now();
+        Ok(())
+    }
+
+    /// Flushes the remaining results and shuts the output down.
+    pub async fn finish(mut self) -> io::Result<()> {
+        self.flush().await?;
+        match &mut self.output {
+            Output::Writer(writer) => writer.shutdown().await,
+            Output::Rotating { file, .. } => file.shutdown().await,
+        }
+    }
+
+    /// Starts the next file if writing the buffer would take the current one past its limit.
+    ///
+    /// A file always receives at least one batch, so a batch larger than the limit is not
+    /// split across files.
+    async fn rotate_if_full(&mut self) -> io::Result<()> {
+        let Output::Rotating {
+            file,
+            path,
+            max_bytes,
+            generation,
+        } = &mut self.output
+        else {
+            return Ok(());
+        };
+        if self.written == 0 || self.written + self.buffer.len() as u64 <= *max_bytes {
+            return Ok(());
+        }
+
+        file.shutdown().await?;
+        *generation += 1;
+        let mut next = path.as_os_str().to_owned();
+        next.push(format!(".{}", generation));
+        *file = File::create(next).await?;
+        self.written = 0;
+        Ok(())
+    }
+}
@@ -0,0 +1,260 @@
+//! Retry settings for the sender's workers.
+
+use crate::backoff::{Backoff, ExponentialBackoff};
+#[cfg(feature = "tokio")]
+use crate::classify::FailureClass;
+use crate::ErrorReason;
+use http::StatusCode;
+use std::fmt;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// How often and how quickly the sender retries notifications classified as
+/// [`FailureClass::Retry`](crate::FailureClass::Retry).
+///
+/// Delays grow exponentially from `base_delay`, doubling after each attempt, and never
+/// exceed `max_delay`; another [`Backoff`] strategy can replace the doubling with
+/// [`backoff`](RetryPolicy::backoff). When APNs answers with a `Retry-After` header, the
+/// sender waits at least that long, still capped at `max_delay`.
+///
+/// Which rejections are retried is up to the sender's
+/// [`FailureClassifier`](crate::FailureClassifier) unless the policy lists the retryable
+/// reasons or statuses itself, with [`retry_reasons`](RetryPolicy::retry_reasons) and
+/// [`retry_statuses`](RetryPolicy::retry_statuses).
+///
+/// # Example
+///
+/// ```rust
+/// use apnrs::RetryPolicy;
+/// use std::time::Duration;
+///
+/// let policy = RetryPolicy::new()
+///     .max_retries(5)
+///     .base_delay(Duration::from_millis(200));
+/// ```
+#[derive(Clone)]
+pub struct RetryPolicy {
+    max_retries: u32,
+    base_delay: Duration,
+    max_delay: Duration,
+    backoff: Option<Arc<dyn Backoff>>,
+    cooldown: Option<Arc<dyn Backoff>>,
+    retry_reasons: Option<Vec<ErrorReason>>,
+    retry_statuses: Option<Vec<StatusCode>>,
+}
+
+impl fmt::Debug for RetryPolicy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RetryPolicy")
+            .field("max_retries", &self.max_retries)
+            .field("base_delay", &self.base_delay)
+            .field("max_delay", &self.max_delay)
+            .field("custom_backoff", &self.backoff.is_some())
+            .field("custom_cooldown", &self.cooldown.is_some())
+            .field("retry_reasons", &self.retry_reasons)
+            .field("retry_statuses", &self.retry_statuses)
+            .finish()
+    }
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        RetryPolicy {
+            max_retries: 3,
+            base_delay: Duration::from_secs(1),
+            max_delay: Duration::from_secs(60),
+            backoff: None,
+            cooldown: None,
+            retry_reasons: None,
+            retry_statuses: None,
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Creates the default policy: 3 retries starting at one second, capped at one minute.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates a policy that never retries.
+    pub fn none() -> Self {
+        Self::default().max_retries(0)
+    }
+
+    /// Sets how many times a notification is retried after the first attempt.
+    pub fn max_retries(mut self, max_retries: u32) -> Self {
+        self.max_retries = max_retries;
+        self
+    }
+
+    /// Sets the delay before the first retry of the default exponential backoff.
+    pub fn base_delay(mut self, delay: Duration) -> Self {
+        self.base_delay = delay;
+        self
+    }
+
+    /// Sets the upper bound for the delay between attempts.
+    pub fn max_delay(mut self, delay: Duration) -> Self {
+        self.max_delay = delay;
+        self
+    }
+
+    /// Spaces out retries with `backoff` instead of doubling the delay from the base delay.
+    ///
+    /// Its delays are still capped at the maximum delay, and stretched to a longer
+    /// `Retry-After` APNs asks for.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use apnrs::{DecorrelatedJitter, RetryPolicy};
+    /// use std::time::Duration;
+    ///
+    /// let policy = RetryPolicy::new()
+    ///     .backoff(DecorrelatedJitter::new(Duration::from_millis(500)))
+    ///     .max_delay(Duration::from_secs(30));
+    /// assert!(policy.delay(4) >= Duration::from_millis(500));
+    /// assert!(policy.delay(4) <= Duration::from_secs(30));
+    /// ```
+    pub fn backoff(mut self, backoff: impl Backoff + 'static) -> Self {
+        self.backoff = Some(Arc::new(backoff));
+        self
+    }
+
+    /// Sets how long a sender holds off every worker once APNs throttles it with a
+    /// `Retry-After` header (defaults to the retry [`backoff`](RetryPolicy::backoff)).
+    ///
+    /// The pause lasts at least as long as APNs asked, capped at the maximum delay, so a
+    /// cool-down that grows faster than the retries backs away from a struggling APNs
+    /// sooner.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use apnrs::{FixedBackoff, RetryPolicy};
+    /// use std::time::Duration;
+    ///
+    /// let policy = RetryPolicy::new().cooldown(FixedBackoff::new(Duration::from_secs(30)));
+    /// assert_eq!(policy.cooldown_after(1, Duration::from_secs(5)), Duration::from_secs(30));
+    /// ```
+    pub fn cooldown(mut self, backoff: impl Backoff + 'static) -> Self {
+        self.cooldown = Some(Arc::new(backoff));
+        self
+    }
+
+    /// Retries exactly the rejections with one of `reasons`, instead of those the classifier
+    /// considers transient.
+    ///
+    /// Rejections with a reason not in the list are not retried, even if the classifier
+    /// would; those without a reason are decided by [`retry_statuses`](RetryPolicy::retry_statuses)
+    /// or the classifier.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use apnrs::{ErrorReason, RetryPolicy, StatusCode};
+    ///
+    /// // Only retry throttling, never a server error that may have been delivered
+    /// let policy = RetryPolicy::new().retry_reasons([
+    ///     ErrorReason::TooManyRequests,
+    ///     ErrorReason::ServiceUnavailable,
+    ///     ErrorReason::ExpiredProviderToken,
+    /// ]);
+    /// assert_eq!(
+    ///     policy.retryable(StatusCode::INTERNAL_SERVER_ERROR, Some(&ErrorReason::InternalServerError)),
+    ///     Some(false)
+    /// );
+    /// ```
+    pub fn retry_reasons(mut self, reasons: impl IntoIterator<Item = ErrorReason>) -> Self {
+        self.retry_reasons = Some(reasons.into_iter().collect());
+        self
+    }
+
+    /// Retries exactly the rejections with one of `statuses`, instead of those the classifier
+    /// considers transient.
+    ///
+    /// Statuses decide rejections without a reason, and every rejection if no
+    /// [`retry_reasons`](RetryPolicy::retry_reasons) are set.
+    pub fn retry_statuses(mut self, statuses: impl IntoIterator<Item = StatusCode>) -> Self {
+        self.retry_statuses = Some(statuses.into_iter().collect());
+        self
+    }
+
+    /// Returns whether the policy retries a rejection with `status` and `reason`, or `None`
+    /// if it leaves the decision to the classifier.
+    pub fn retryable(&self, status: StatusCode, reason: Option<&ErrorReason>) -> Option<bool> {
+        match (&self.retry_reasons, reason, &self.retry_statuses) {
+            (Some(reasons), Some(reason), _) => Some(reasons.contains(reason)),
+            (_, _, Some(statuses)) => Some(statuses.contains(&status)),
+            _ => None,
+        }
+    }
+
+    /// Adjusts the classifier's `class` for a rejection to the retryable reasons and
+    /// statuses of the policy.
+    #[cfg(feature = "tokio")]
+    pub(crate) fn reclassify(
+        &self,
+        class: FailureClass,
+        status: StatusCode,
+        reason: Option<&ErrorReason>,
+    ) -> FailureClass {
+        match self.retryable(status, reason) {
+            Some(true) => FailureClass::Retry,
+            Some(false) if class == FailureClass::Retry => FailureClass::Permanent,
+            _ => class,
+        }
+    }
+
+    /// Returns how many retries are allowed.
+    pub fn retries(&self) -> u32 {
+        self.max_retries
+    }
+
+    /// Returns the delay before retry number `retry` (starting at 1).
+    pub fn delay(&self, retry: u32) -> Duration {
+        match &self.backoff {
+            Some(backoff) => self.delay_with(backoff.as_ref(), retry),
+            None => self.delay_with(&ExponentialBackoff::new(self.base_delay), retry),
+        }
+    }
+
+    /// Returns the delay before retry number `retry` (starting at 1) of a request APNs asked
+    /// to hold off for `retry_after`: the longer of the two, capped at the maximum delay.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use apnrs::RetryPolicy;
+    /// use std::time::Duration;
+    ///
+    /// let policy = RetryPolicy::new();
+    /// assert_eq!(policy.delay_after(1, Duration::from_secs(10)), Duration::from_secs(10));
+    /// assert_eq!(policy.delay_after(1, Duration::from_secs(600)), Duration::from_secs(60));
+    /// ```
+    pub fn delay_after(&self, retry: u32, retry_after: Duration) -> Duration {
+        self.delay(retry).max(retry_after).min(self.max_delay)
+    }
+
+    /// Returns how long a sender holds off after APNs throttled retry number `retry`
+    /// (starting at 1) with a `Retry-After` of `retry_after`: the longer of the cool-down
+    /// and `retry_after`, capped at the maximum delay.
+    pub fn cooldown_after(&self, retry: u32, retry_after: Duration) -> Duration {
+        match &self.cooldown {
+            Some(cooldown) => self
+                .delay_with(cooldown.as_ref(), retry)
+                .max(retry_after)
+                .min(self.max_delay),
+            None => self.delay_after(retry, retry_after),
+        }
+    }
+
+    /// Returns the delay `backoff` picks before retry number `retry`, feeding it each
+    /// earlier delay capped at the maximum delay.
+    fn delay_with(&self, backoff: &dyn Backoff, retry: u32) -> Duration {
+        (1..=retry.max(1)).fold(Duration::ZERO, |previous, retry| {
+            backoff.delay(retry, previous).min(self.max_delay)
+        })
+    }
+}
@@ -0,0 +1,412 @@
+//! Apple's documented payload constraints, encoded as data that can be extended at runtime.
+
+use crate::push_type::PushType;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::fmt;
+
+/// The JSON type a payload value may have.
+///
+/// # Variants
+///
+/// * `String` - A JSON string.
+/// * `Number` - Any JSON number.
+/// * `Integer` - A JSON number without a fractional part.
+/// * `Bool` - `true` or `false`.
+/// * `Object` - A JSON object.
+/// * `Array` - A JSON array.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ValueKind {
+    String,
+    Number,
+    Integer,
+    Bool,
+    Object,
+    Array,
+}
+
+impl ValueKind {
+    /// Returns the name of this kind, as used in rule files.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ValueKind::String => "string",
+            ValueKind::Number => "number",
+            ValueKind::Integer => "integer",
+            ValueKind::Bool => "bool",
+            ValueKind::Object => "object",
+            ValueKind::Array => "array",
+        }
+    }
+
+    /// Returns `true` if `value` is of this kind.
+    pub fn matches(&self, value: &Value) -> bool {
+        match self {
+            ValueKind::String => value.is_string(),
+            ValueKind::Number => value.is_number(),
+            ValueKind::Integer => value.is_i64() || value.is_u64(),
+            ValueKind::Bool => value.is_boolean(),
+            ValueKind::Object => value.is_object(),
+            ValueKind::Array => value.is_array(),
+        }
+    }
+}
+
+/// What a [`Rule`] checks about its key.
+///
+/// # Variants
+///
+/// * `Required` - The key must be present.
+/// * `Forbidden` - The key must not be present.
+/// * `Kind` - If present, the value must be one of `kinds`.
+/// * `Range` - If present and a number, the value must lie within `min` and `max`, inclusive.
+/// * `OneOf` - If present, the value must equal one of `values`.
+/// * `Requires` - If present, the key `key` must be present too.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "check", rename_all = "snake_case")]
+pub enum Check {
+    Required,
+    Forbidden,
+    Kind {
+        kinds: Vec<ValueKind>,
+    },
+    Range {
+        #[serde(default)]
+        min: Option<f64>,
+        #[serde(default)]
+        max: Option<f64>,
+    },
+    OneOf {
+        values: Vec<Value>,
+    },
+    Requires {
+        key: String,
+    },
+}
+
+/// One constraint on a payload key.
+///
+/// Rules are plain data and (de)serialize as JSON, so an updated rule from Apple's
+/// documentation can be shipped as configuration instead of waiting for a crate release.
+///
+/// # Fields
+///
+/// * `key` - The dotted path of the key, such as `aps.badge` or `aps.sound.volume`.
+/// * `push_types` - The push types the rule applies to, or every push type if empty.
+/// * `check` - What is checked about the key.
+///
+/// # Example
+///
+/// ```rust
+/// use apnrs::{Check, Rule};
+///
+/// let rule: Rule = serde_json::from_str(
+///     r#"{ "key": "aps.relevance-score", "check": "range", "min": 0.0, "max": 1.0 }"#,
+/// )
+/// .unwrap();
+///
+/// assert_eq!(rule.check, Check::Range { min: Some(0.0), max: Some(1.0) });
+/// assert!(rule.push_types.is_empty());
+/// ```
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Rule {
+    pub key: String,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub push_types: Vec<PushType>,
+    #[serde(flatten)]
+    pub check: Check,
+}
+
+impl Rule {
+    /// Creates a rule applying `check` to `key` for every push type.
+    pub fn new(key: impl Into<String>, check: Check) -> Self {
+        Rule {
+            key: key.into(),
+            push_types: Vec::new(),
+            check,
+        }
+    }
+
+    /// Restricts the rule to the given push types.
+    pub fn for_push_types(mut self, push_types: impl IntoIterator<Item = PushType>) -> Self {
+        self.push_types = push_types.into_iter().collect();
+        self
+    }
+
+    /// Returns `true` if the rule applies to payloads of `push_type`.
+    pub fn applies_to(&self, push_type: PushType) -> bool {
+        self.push_types.is_empty() || self.push_types.contains(&push_type)
+    }
+
+    /// Checks `payload` against this rule.
+    ///
+    /// # Returns
+    ///
+    /// `None` if the payload satisfies the rule, or a description of the violation.
+    pub fn check(&self, payload: &Value) -> Option<String> {
+        let value = lookup(payload, &self.key);
+        match (&self.check, value) {
+            (Check::Required, None) => Some("is required".to_string()),
+            (Check::Forbidden, Some(_)) => Some("is not allowed".to_string()),
+            (Check::Kind { kinds }, Some(value)) if !kinds.iter().any(|k| k.matches(value)) => {
+                Some(format!("must be of type {}", describe_kinds(kinds)))
+            }
+            (Check::Range { min, max }, Some(value)) => {
+                let number = value.as_f64()?;
+                let below = min.is_some_and(|min| number < min);
+                let above = max.is_some_and(|max| number > max);
+                (below || above).then(|| format!("must be {}", describe_range(*min, *max)))
+            }
+            (Check::OneOf { values }, Some(value)) if !values.contains(value) => {
+                let values: Vec<String> = values.iter().map(Value::to_string).collect();
+                Some(format!("must be one of {}", values.join(", ")))
+            }
+            (Check::Requires { key }, Some(_)) if lookup(payload, key).is_none() => {
+                Some(format!("requires {}", key))
+            }
+            _ => None,
+        }
+    }
+}
+
+/// A payload key that breaks a [`Rule`].
+///
+/// # Fields
+///
+/// * `key` - The dotted path of the offending key.
+/// * `push_type` - The push type the payload was checked for.
+/// * `message` - What is wrong with the key.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RuleViolation {
+    pub key: String,
+    pub push_type: PushType,
+    pub message: String,
+}
+
+impl fmt::Display for RuleViolation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} ({} push): {}",
+            self.key, self.push_type, self.message
+        )
+    }
+}
+
+/// A set of [`Rule`]s payloads are checked against.
+///
+/// [`RuleSet::apple`] holds the constraints from Apple's payload documentation; rules added
+/// with [`rule`](RuleSet::rule) or [`extend`](RuleSet::extend) are checked alongside them.
+///
+/// # Example
+///
+/// ```rust
+/// use apnrs::{Check, PushType, Rule, RuleSet};
+/// use serde_json::json;
+///
+/// let rules = RuleSet::apple().rule(
+///     Rule::new("aps.thread-id", Check::Required).for_push_types([PushType::Alert]),
+/// );
+///
+/// let problems = rules
+///     .validate(&json!({ "aps": { "alert": "Hi", "badge": -1 } }), PushType::Alert)
+///     .unwrap_err();
+/// assert_eq!(problems.len(), 2);
+/// assert_eq!(problems[0].to_string(), "aps.badge (alert push): must be at least 0");
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct RuleSet {
+    rules: Vec<Rule>,
+}
+
+impl RuleSet {
+    /// Creates a rule set without any rules.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates a rule set holding the constraints from Apple's payload documentation.
+    pub fn apple() -> Self {
+        use PushType::*;
+        use ValueKind::{Integer, Number, Object, String};
+
+        let silent_types = [Voip, Background, Location, PushToTalk];
+        let content_types = [Background, Complication];
+        let levels = ["passive", "active", "time-sensitive", "critical"];
+        RuleSet::new()
+            .rule(kind("aps", &[Object]))
+            .rule(kind("aps.alert", &[String, Object]))
+            .rule(forbidden("aps.alert").for_push_types(silent_types))
+            .rule(kind("aps.badge", &[Integer]))
+            .rule(range("aps.badge", Some(0.0), None))
+            .rule(forbidden("aps.badge").for_push_types([Background]))
+            .rule(kind("aps.sound", &[String, Object]))
+            .rule(forbidden("aps.sound").for_push_types([Background]))
+            .rule(one_of("aps.sound.critical", [json!(0), json!(1)]))
+            .rule(range("aps.sound.volume", Some(0.0), Some(1.0)))
+            .rule(one_of("aps.content-available", [json!(1)]))
+            .rule(required("aps.content-available").for_push_types(content_types))
+            .rule(one_of("aps.mutable-content", [json!(1)]))
+            .rule(requires("aps.mutable-content", "aps.alert"))
+            .rule(kind("aps.thread-id", &[String]))
+            .rule(kind("aps.alert.summary-arg", &[String]))
+            .rule(kind("aps.alert.summary-arg-count", &[Integer]))
+            .rule(range("aps.alert.summary-arg-count", Some(1.0), None))
+            .rule(kind("aps.category", &[String]))
+            .rule(kind("aps.target-content-id", &[String]))
+            .rule(kind("aps.filter-criteria", &[String]))
+            .rule(one_of("aps.interruption-level", levels.map(|l| json!(l))))
+            .rule(kind("aps.relevance-score", &[Number]))
+            .rule(range("aps.relevance-score", Some(0.0), Some(1.0)))
+            .rule(required("aps.timestamp").for_push_types([LiveActivity]))
+            .rule(kind("aps.timestamp", &[Integer]))
+            .rule(required("aps.event").for_push_types([LiveActivity]))
+            .rule(one_of(
+                "aps.event",
+                ["start", "update", "end"].map(|e| json!(e)),
+            ))
+            .rule(kind("aps.dismissal-date", &[Integer]))
+            .rule(kind("aps.stale-date", &[Integer]))
+            .rule(requires("aps.attributes-type", "aps.attributes"))
+            .rule(required("container-identifier").for_push_types([FileProvider]))
+            .rule(kind("container-identifier", &[String]))
+    }
+
+    /// Adds a rule, checked after the rules already in the set.
+    pub fn rule(mut self, rule: Rule) -> Self {
+        self.rules.push(rule);
+        self
+    }
+
+    /// Adds several rules, such as rules deserialized from a configuration file.
+    pub fn extend(mut self, rules: impl IntoIterator<Item = Rule>) -> Self {
+        self.rules.extend(rules);
+        self
+    }
+
+    /// Returns the rules in the set, in the order they are checked.
+    pub fn rules(&self) -> &[Rule] {
+        &self.rules
+    }
+
+    /// Checks `payload` against every rule applying to `push_type`.
+    ///
+    /// # Returns
+    ///
+    /// `Ok(())` if no rule is broken, or every violation found, in rule order.
+    pub fn validate(&self, payload: &Value, push_type: PushType) -> Result<(), Vec<RuleViolation>> {
+        let violations: Vec<RuleViolation> = self
+            .rules
+            .iter()
+            .filter(|rule| rule.applies_to(push_type))
+            .filter_map(|rule| {
+                rule.check(payload).map(|message| RuleViolation {
+                    key: rule.key.clone(),
+                    push_type,
+                    message,
+                })
+            })
+            .collect();
+
+        if violations.is_empty() {
+            Ok(())
+        } else {
+            Err(violations)
+        }
+    }
+}
+
+/// Checks `payload` against the constraints from Apple's payload documentation for
+/// `push_type`; see [`RuleSet::apple`].
+///
+/// To check additional rules, build a [`RuleSet`] and call [`RuleSet::validate`] instead.
+///
+/// # Arguments
+///
+/// * `payload` - The JSON payload.
+/// * `push_type` - The push type the payload will be sent with.
+///
+/// # Returns
+///
+/// `Ok(())` if the payload follows the rules, or every violation found.
+///
+/// # Example
+///
+/// ```rust
+/// use apnrs::{validate_against_rules, PushType};
+/// use serde_json::json;
+///
+/// assert!(validate_against_rules(&json!({ "aps": { "alert": "Hi", "badge": 3 } }), PushType::Alert).is_ok());
+///
+/// let problems = validate_against_rules(&json!({ "aps": { "badge": 1 } }), PushType::Background).unwrap_err();
+/// let keys: Vec<&str> = problems.iter().map(|p| p.key.as_str()).collect();
+/// assert_eq!(keys, ["aps.badge", "aps.content-available"]);
+/// ```
+pub fn validate_against_rules(
+    payload: &Value,
+    push_type: PushType,
+) -> Result<(), Vec<RuleViolation>> {
+    RuleSet::apple().validate(payload, push_type)
+}
+
+fn required(key: &str) -> Rule {
+    Rule::new(key, Check::Required)
+}
+
+fn forbidden(key: &str) -> Rule {
+    Rule::new(key, Check::Forbidden)
+}
+
+fn kind(key: &str, kinds: &[ValueKind]) -> Rule {
+    Rule::new(
+        key,
+        Check::Kind {
+            kinds: kinds.to_vec(),
+        },
+    )
+}
+
+fn range(key: &str, min: Option<f64>, max: Option<f64>) -> Rule {
+    Rule::new(key, Check::Range { min, max })
+}
+
+fn one_of(key: &str, values: impl IntoIterator<Item = Value>) -> Rule {
+    Rule::new(
+        key,
+        Check::OneOf {
+            values: values.into_iter().collect(),
+        },
+    )
+}
+
+fn requires(key: &str, other: &str) -> Rule {
+    Rule::new(
+        key,
+        Check::Requires {
+            key: other.to_string(),
+        },
+    )
+}
+
+/// Returns the value at a dotted path such as `aps.sound.volume`.
+fn lookup<'a>(payload: &'a Value, path: &str) -> Option<&'a Value> {
+    path.split('.')
+        .try_fold(payload, |value, key| value.get(key))
+}
+
+/// Lists value kinds for an error message, such as `string or object`.
+fn describe_kinds(kinds: &[ValueKind]) -> String {
+    let names: Vec<&str> = kinds.iter().map(ValueKind::as_str).collect();
+    names.join(" or ")
+}
+
+/// Describes an inclusive range for an error message.
+fn describe_range(min: Option<f64>, max: Option<f64>) -> String {
+    match (min, max) {
+        (Some(min), Some(max)) => format!("between {} and {}", min, max),
+        (Some(min), None) => format!("at least {}", min),
+        (None, Some(max)) => format!("at most {}", max),
+        (None, None) => "a number".to_string(),
+    }
+}
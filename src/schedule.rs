@@ -0,0 +1,270 @@
+//! Holding notifications back until the time they are scheduled for.
+
+#[cfg(feature = "journal")]
+use crate::journal::JournalEntry;
+use crate::sender::{Job, Queue, ReceiptId};
+use crate::{ApnsClient, ApnsError, PushRequest, SendOutcome};
+use std::collections::{BTreeMap, HashMap};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime};
+use tokio::sync::{oneshot, Notify};
+
+/// The longest the scheduler sleeps before checking the clock again.
+const MAX_WAIT: Duration = Duration::from_secs(60);
+
+/// A notification waiting for its delivery time.
+pub(crate) struct Scheduled {
+    pub(crate) request: PushRequest,
+    pub(crate) reply: oneshot::Sender<Result<SendOutcome, ApnsError>>,
+    #[cfg(feature = "journal")]
+    pub(crate) entry: Option<JournalEntry>,
+}
+
+/// The scheduled notifications of a sender, and the task handing them to its workers once
+/// they are due.
+#[derive(Default)]
+pub(crate) struct Scheduler {
+    next_id: AtomicU64,
+    state: Mutex<State>,
+    changed: Notify,
+}
+
+#[derive(Default)]
+struct State {
+    due: BTreeMap<(SystemTime, ReceiptId), Scheduled>,
+    times: HashMap<ReceiptId, SystemTime>,
+    /// The notification held for each device token and collapse ID while coalescing.
+    coalescing: HashMap<(String, String), ReceiptId>,
+    /// The sender was dropped, so the task exits once nothing is scheduled.
+    released: bool,
+    /// The client shut down and the task has exited.
+    stopped: bool,
+}
+
+impl Scheduler {
+    /// Returns an ID no other receipt of the sender has.
+    pub(crate) fn next_id(&self) -> ReceiptId {
+        ReceiptId(self.next_id.fetch_add(1, Ordering::Relaxed))
+    }
+
+    /// Holds `scheduled` until `deliver_at`.
+    ///
+    /// # Returns
+    ///
+    /// `Err(ApnsError::ClientShutDown)` if the client has shut down.
+    pub(crate) fn insert(
+        &self,
+        id: ReceiptId,
+        deliver_at: SystemTime,
+        scheduled: Scheduled,
+    ) -> Result<(), ApnsError> {
+        let mut state = self.lock();
+        if state.stopped {
+            return Err(ApnsError::ClientShutDown);
+        }
+        state.due.insert((deliver_at, id), scheduled);
+        state.times.insert(id, deliver_at);
+        drop(state);
+        self.changed.notify_one();
+        Ok(())
+    }
+
+    /// Holds `scheduled` until `deliver_at`, or in place of the notification already held
+    /// for its device token and collapse ID, whose receipt resolves to
+    /// `ApnsError::Superseded`.
+    ///
+    /// # Returns
+    ///
+    /// The ID the notification is held under, or `Err(ApnsError::ClientShutDown)` if the
+    /// client has shut down.
+    pub(crate) fn coalesce(
+        &self,
+        deliver_at: SystemTime,
+        scheduled: Scheduled,
+    ) -> Result<ReceiptId, ApnsError> {
+        let key = coalescing_key(&scheduled.request).expect("a collapse ID");
+        let mut state = self.lock();
+        if state.stopped {
+            return Err(ApnsError::ClientShutDown);
+        }
+        if let Some(&id) = state.coalescing.get(&key) {
+            let time = state.times[&id];
+            let held = state.due.get_mut(&(time, id)).expect("a held notification");
+            let superseded = std::mem::replace(held, scheduled);
+            drop(state);
+            #[cfg(feature = "journal")]
+            if let Some(entry) = superseded.entry {
+                entry.complete();
+            }
+            let _ = superseded.reply.send(Err(ApnsError::Superseded));
+            return Ok(id);
+        }
+
+        let id = self.next_id();
+        state.due.insert((deliver_at, id), scheduled);
+        state.times.insert(id, deliver_at);
+        state.coalescing.insert(key, id);
+        drop(state);
+        self.changed.notify_one();
+        Ok(id)
+    }
+
+    /// Drops the notification scheduled under `id`, resolving its receipt to
+    /// `ApnsError::Cancelled`.
+    ///
+    /// # Returns
+    ///
+    /// `true` if the notification was still scheduled.
+    pub(crate) fn cancel(&self, id: ReceiptId) -> bool {
+        let scheduled = {
+            let mut state = self.lock();
+            let Some(deliver_at) = state.times.remove(&id) else {
+                return false;
+            };
+            let scheduled = state.due.remove(&(deliver_at, id));
+            if let Some(scheduled) = &scheduled {
+                state.forget_coalescing(id, &scheduled.request);
+            }
+            scheduled
+        };
+        if let Some(scheduled) = scheduled {
+            #[cfg(feature = "journal")]
+            if let Some(entry) = scheduled.entry {
+                entry.complete();
+            }
+            let _ = scheduled.reply.send(Err(ApnsError::Cancelled));
+        }
+        self.changed.notify_one();
+        true
+    }
+
+    /// Moves the notification scheduled under `id` to `deliver_at`.
+    ///
+    /// # Returns
+    ///
+    /// `true` if the notification was still scheduled.
+    pub(crate) fn reschedule(&self, id: ReceiptId, deliver_at: SystemTime) -> bool {
+        let mut state = self.lock();
+        let Some(time) = state.times.get_mut(&id) else {
+            return false;
+        };
+        let previous = std::mem::replace(time, deliver_at);
+        let Some(scheduled) = state.due.remove(&(previous, id)) else {
+            return false;
+        };
+        #[cfg(feature = "journal")]
+        if let Some(entry) = &scheduled.entry {
+            entry.reschedule(deliver_at);
+        }
+        state.due.insert((deliver_at, id), scheduled);
+        drop(state);
+        self.changed.notify_one();
+        true
+    }
+
+    /// Lets the task exit once the notifications still scheduled have been handed over.
+    pub(crate) fn release(&self) {
+        self.lock().released = true;
+        self.changed.notify_one();
+    }
+
+    /// Hands notifications to the workers as they fall due, until the sender is dropped and
+    /// nothing is scheduled, or the client shuts down.
+    pub(crate) async fn run(&self, client: ApnsClient, queue: Queue) {
+        loop {
+            let (due, next) = self.take_due();
+            for scheduled in due {
+                dispatch(&client, &queue, scheduled).await;
+            }
+            if next.is_none() && self.lock().released {
+                return;
+            }
+
+            // Wake at least once a minute, so a change of the wall clock is noticed
+            let wait = next
+                .map_or(MAX_WAIT, |next| {
+                    next.duration_since(SystemTime::now()).unwrap_or_default()
+                })
+                .min(MAX_WAIT);
+            tokio::select! {
+                _ = tokio::time::sleep(wait) => {}
+                _ = self.changed.notified() => {}
+                _ = client.lifecycle().closed() => break,
+            }
+        }
+
+        // Left in the journal, scheduled notifications are resumed on the next start
+        let scheduled = {
+            let mut state = self.lock();
+            state.stopped = true;
+            state.times.clear();
+            state.coalescing.clear();
+            std::mem::take(&mut state.due)
+        };
+        for scheduled in scheduled.into_values() {
+            let _ = scheduled.reply.send(Err(ApnsError::ClientShutDown));
+        }
+    }
+
+    /// Removes the notifications that are due.
+    ///
+    /// # Returns
+    ///
+    /// The due notifications, and when the next one is due, if any is left.
+    fn take_due(&self) -> (Vec<Scheduled>, Option<SystemTime>) {
+        let now = SystemTime::now();
+        let mut state = self.lock();
+        let mut due = Vec::new();
+        while let Some(entry) = state.due.first_entry() {
+            let (deliver_at, id) = *entry.key();
+            if deliver_at > now {
+                return (due, Some(deliver_at));
+            }
+            let scheduled = entry.remove();
+            state.times.remove(&id);
+            state.forget_coalescing(id, &scheduled.request);
+            due.push(scheduled);
+        }
+        (due, None)
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, State> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+impl State {
+    /// Stops coalescing into the notification held under `id`, once it is due or cancelled.
+    fn forget_coalescing(&mut self, id: ReceiptId, request: &PushRequest) {
+        if let Some(key) = coalescing_key(request) {
+            if self.coalescing.get(&key) == Some(&id) {
+                self.coalescing.remove(&key);
+            }
+        }
+    }
+}
+
+/// Returns the device token and collapse ID notifications are coalesced by, if the request
+/// has a collapse ID.
+fn coalescing_key(request: &PushRequest) -> Option<(String, String)> {
+    let collapse_id = request.options.collapse_id.clone()?;
+    Some((request.device_token.clone(), collapse_id))
+}
+
+/// Hands a due notification to the workers.
+async fn dispatch(client: &ApnsClient, queue: &Queue, scheduled: Scheduled) {
+    let Some(pending) = client.lifecycle().start() else {
+        let _ = scheduled.reply.send(Err(ApnsError::ClientShutDown));
+        return;
+    };
+    let job = Job {
+        request: scheduled.request,
+        reply: scheduled.reply,
+        pending,
+        #[cfg(feature = "journal")]
+        entry: scheduled.entry,
+    };
+    // A closed queue resolves the receipt to `ApnsError::QueueClosed`
+    let _ = queue.push(client, job).await;
+}
@@ -0,0 +1,129 @@
+//! Validation of a payload's custom data against a JSON Schema, enabled with the `schema`
+//! feature.
+
+#[cfg(feature = "schema")]
+use crate::error::ApnsError;
+#[cfg(feature = "schema")]
+use crate::notification::NotificationError;
+#[cfg(feature = "schema")]
+use serde::Serialize;
+#[cfg(feature = "schema")]
+use serde_json::Value;
+use std::fmt;
+#[cfg(feature = "schema")]
+use std::sync::Arc;
+
+/// A part of a payload's custom data that does not match its [`CustomDataSchema`].
+///
+/// # Fields
+///
+/// * `path` - The JSON Pointer of the offending value within the custom data, such as
+///   `/order/id`, or an empty string for the custom data as a whole.
+/// * `message` - What is wrong with the value.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SchemaViolation {
+    pub path: String,
+    pub message: String,
+}
+
+impl fmt::Display for SchemaViolation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let path = if self.path.is_empty() {
+            "/"
+        } else {
+            &self.path
+        };
+        write!(f, "{}: {}", path, self.message)
+    }
+}
+
+/// A JSON Schema the custom data of every payload must match, checked before sending so
+/// malformed data never reaches the app.
+///
+/// The custom data is the payload's top-level object without its `aps` key, so the schema
+/// describes only the keys your app parses. Set it on a client with
+/// [`ApnsClientBuilder::custom_data_schema`](crate::ApnsClientBuilder::custom_data_schema),
+/// or check payloads directly with [`validate`](CustomDataSchema::validate).
+///
+/// # Example
+///
+/// ```rust
+/// use apnrs::{ApnsError, CustomDataSchema};
+/// use serde_json::json;
+///
+/// let schema = CustomDataSchema::new(&json!({
+///     "type": "object",
+///     "properties": { "order-id": { "type": "integer" } },
+///     "required": ["order-id"]
+/// }))
+/// .unwrap();
+///
+/// assert!(schema.validate(&json!({ "aps": { "alert": "hi" }, "order-id": 42 })).is_ok());
+///
+/// match schema.validate(&json!({ "aps": { "alert": "hi" }, "order-id": "42" })) {
+///     Err(ApnsError::InvalidCustomData(violations)) => {
+///         assert_eq!(violations[0].path, "/order-id");
+///     }
+///     other => panic!("unexpected {:?}", other),
+/// }
+/// ```
+#[cfg(feature = "schema")]
+#[derive(Clone)]
+pub struct CustomDataSchema {
+    validator: Arc<jsonschema::Validator>,
+}
+
+#[cfg(feature = "schema")]
+impl CustomDataSchema {
+    /// Compiles `schema`, in any JSON Schema draft it declares with `$schema` (the latest
+    /// by default).
+    ///
+    /// References to other documents are not fetched, so the schema must be
+    /// self-contained.
+    ///
+    /// # Returns
+    ///
+    /// The schema, or `ApnsError::Config` if it is not a valid JSON Schema.
+    pub fn new(schema: &Value) -> Result<Self, ApnsError> {
+        let validator = jsonschema::validator_for(schema)
+            .map_err(|e| ApnsError::Config(format!("invalid custom data schema: {}", e)))?;
+        Ok(CustomDataSchema {
+            validator: Arc::new(validator),
+        })
+    }
+
+    /// Checks the custom data of `payload` against the schema.
+    ///
+    /// # Returns
+    ///
+    /// `Ok(())` if it matches, `ApnsError::InvalidCustomData` with every violation if it does
+    /// not, or another `ApnsError` if the payload is not a JSON object.
+    pub fn validate<P: Serialize + ?Sized>(&self, payload: &P) -> Result<(), ApnsError> {
+        let Value::Object(mut custom_data) = serde_json::to_value(payload)? else {
+            return Err(NotificationError::NotAnObject.into());
+        };
+        custom_data.remove("aps");
+        let custom_data = Value::Object(custom_data);
+
+        let violations: Vec<SchemaViolation> = self
+            .validator
+            .iter_errors(&custom_data)
+            .map(|error| SchemaViolation {
+                path: error.instance_path().to_string(),
+                message: error.to_string(),
+            })
+            .collect();
+        if violations.is_empty() {
+            Ok(())
+        } else {
+            Err(ApnsError::InvalidCustomData(violations))
+        }
+    }
+}
+
+#[cfg(feature = "schema")]
+impl fmt::Debug for CustomDataSchema {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CustomDataSchema").finish_non_exhaustive()
+    }
+}
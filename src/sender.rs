@@ -0,0 +1,1244 @@
+//! A background dispatcher that delivers queued notifications with a pool of workers.
+
+#[cfg(feature = "journal")]
+use crate::journal::{Journal, JournalEntry, Recovered};
+#[cfg(feature = "logging")]
+use crate::privacy::mask_token;
+use crate::quota::Quotas;
+use crate::request::new_apns_id;
+use crate::schedule::{Scheduled, Scheduler};
+use crate::shutdown::PendingGuard;
+use crate::spool::{replay, Spool};
+use crate::{
+    ApnsClient, ApnsError, DefaultClassifier, FailureClass, FailureClassifier, PushRequest,
+    QuietHours, Quota, RetryPolicy, SendOutcome, TrafficClass,
+};
+use futures_util::future::join_all;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::fmt;
+use std::future::{self, Future};
+use std::hash::{Hash, Hasher};
+#[cfg(feature = "journal")]
+use std::path::Path;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use std::time::{Duration, SystemTime};
+use tokio::sync::{mpsc, oneshot, Mutex};
+use tokio::time::Instant;
+use tokio_util::sync::CancellationToken;
+
+/// A queued notification together with the channel its outcome is reported on.
+pub(crate) struct Job {
+    pub(crate) request: PushRequest,
+    pub(crate) reply: oneshot::Sender<Result<SendOutcome, ApnsError>>,
+    /// Keeps the notification counted as pending on the client until it is delivered or
+    /// discarded.
+    pub(crate) pending: PendingGuard,
+    /// Where the notification is recorded in the sender's journal, if it has one.
+    #[cfg(feature = "journal")]
+    pub(crate) entry: Option<JournalEntry>,
+}
+
+impl Job {
+    /// Removes the job from the sender's journal, once it needs no further delivery
+    /// attempts.
+    pub(crate) fn settle(&mut self) {
+        #[cfg(feature = "journal")]
+        if let Some(entry) = self.entry.take() {
+            entry.complete();
+        }
+    }
+}
+
+/// How jobs are distributed to the workers.
+#[derive(Clone)]
+pub(crate) enum Queue {
+    /// All workers pull from one shared lane.
+    Shared(Lane),
+    /// Each worker owns a lane; a device token always maps to the same lane.
+    Lanes(Vec<Lane>),
+}
+
+/// The sending half of a lane: one channel per [`TrafficClass`], in dispatch order.
+#[derive(Clone)]
+pub(crate) struct Lane(pub(crate) Vec<mpsc::Sender<Job>>);
+
+/// The receiving half of a lane.
+pub(crate) struct LaneReceiver(Vec<mpsc::Receiver<Job>>);
+
+/// Creates a lane with room for `capacity` jobs of each class.
+pub(crate) fn lane(capacity: usize) -> (Lane, LaneReceiver) {
+    let (txs, rxs) = TrafficClass::ALL
+        .iter()
+        .map(|_| mpsc::channel(capacity))
+        .unzip();
+    (Lane(txs), LaneReceiver(rxs))
+}
+
+impl Lane {
+    /// Returns the channel for jobs of `class`.
+    pub(crate) fn sender(&self, class: TrafficClass) -> &mpsc::Sender<Job> {
+        &self.0[class.rank()]
+    }
+}
+
+impl LaneReceiver {
+    /// Receives the next job of the highest class that has one waiting.
+    ///
+    /// # Returns
+    ///
+    /// The job, or `None` once every channel is closed and drained.
+    pub(crate) async fn recv(&mut self) -> Option<Job> {
+        future::poll_fn(|cx| {
+            let mut open = false;
+            for rx in &mut self.0 {
+                match rx.poll_recv(cx) {
+                    Poll::Ready(Some(job)) => return Poll::Ready(Some(job)),
+                    Poll::Ready(None) => {}
+                    Poll::Pending => open = true,
+                }
+            }
+            if open {
+                Poll::Pending
+            } else {
+                Poll::Ready(None)
+            }
+        })
+        .await
+    }
+
+    /// Receives the next job and, without waiting for more, up to `limit - 1` others
+    /// already waiting, highest class first.
+    ///
+    /// # Returns
+    ///
+    /// The jobs, or none once every channel is closed and drained.
+    pub(crate) async fn recv_batch(&mut self, limit: usize) -> Vec<Job> {
+        let Some(first) = self.recv().await else {
+            return Vec::new();
+        };
+        let mut batch = vec![first];
+        while batch.len() < limit {
+            match self.0.iter_mut().find_map(|rx| rx.try_recv().ok()) {
+                Some(job) => batch.push(job),
+                None => break,
+            }
+        }
+        batch
+    }
+}
+
+/// A dispatcher that owns a bounded queue and a pool of worker tasks delivering
+/// notifications through an [`ApnsClient`].
+///
+/// Dropping the sender stops accepting new notifications; the workers finish what is
+/// already queued or scheduled and then exit.
+///
+/// # Example
+///
+/// ```rust,no_run
+/// use apnrs::{ApnsClient, ApnsSender, PushRequest};
+///
+/// # async fn run(client: ApnsClient, request: PushRequest) -> Result<(), apnrs::ApnsError> {
+/// let sender = ApnsSender::builder(client)
+///     .workers(8)
+///     .rate_limit(500.0)
+///     .ordered_per_token(true)
+///     .build();
+///
+/// let receipt = sender.enqueue(request).await?;
+/// let outcome = receipt.await?;
+/// println!("Notification sent: {:?}", outcome.response.status);
+/// # Ok(())
+/// # }
+/// ```
+pub struct ApnsSender {
+    pub(crate) client: ApnsClient,
+    pub(crate) queue: Queue,
+    pub(crate) intake: Intake,
+    spool: Option<Arc<Spool>>,
+}
+
+/// The steps a notification goes through before it reaches the queue, shared by a sender
+/// and its [sinks](ApnsSender::sink).
+#[derive(Clone)]
+pub(crate) struct Intake {
+    pub(crate) client: ApnsClient,
+    scheduler: Arc<Scheduler>,
+    coalesce_window: Option<Duration>,
+    quiet_hours: Option<QuietHours>,
+    quotas: Arc<Quotas>,
+    cancel: CancellationToken,
+    #[cfg(feature = "journal")]
+    journal: Option<Arc<Journal>>,
+    /// How many notifications may be queued in memory only while the journal cannot be
+    /// written, or `None` to refuse them instead.
+    #[cfg(feature = "journal")]
+    journal_fallback: Option<usize>,
+}
+
+/// What [`Intake::screen`] did with a notification.
+pub(crate) enum Screened {
+    /// Handed to the scheduler, to be queued once its coalescing window or the recipient's
+    /// quiet hours end.
+    Held(Receipt),
+    /// Admitted by its app's quota, to be queued once its app's pace allows.
+    Due(Box<PushRequest>),
+}
+
+/// A builder for [`ApnsSender`].
+pub struct ApnsSenderBuilder {
+    client: ApnsClient,
+    workers: usize,
+    batch_size: usize,
+    queue_capacity: usize,
+    ordered_per_token: bool,
+    rate_limit: Option<f64>,
+    coalesce_window: Option<Duration>,
+    quiet_hours: Option<QuietHours>,
+    quotas: HashMap<String, Quota>,
+    retry_policy: RetryPolicy,
+    classifier: Arc<dyn FailureClassifier>,
+    cancel: CancellationToken,
+    spool_probe_interval: Option<Duration>,
+    #[cfg(feature = "journal")]
+    journal: Option<(Arc<Journal>, Recovered)>,
+    #[cfg(feature = "journal")]
+    journal_fallback: Option<usize>,
+}
+
+/// State shared by all workers of a sender.
+pub(crate) struct Worker {
+    pub(crate) client: ApnsClient,
+    pacer: Option<Pacer>,
+    retry_policy: RetryPolicy,
+    classifier: Arc<dyn FailureClassifier>,
+    /// When APNs' last `Retry-After` ends; no worker sends before then.
+    resume_at: Mutex<Instant>,
+    /// Once cancelled, jobs are failed with `ApnsError::Cancelled` instead of sent.
+    pub(crate) cancel: CancellationToken,
+    /// Holds notifications while APNs cannot be reached, if the sender spools outages.
+    spool: Option<Arc<Spool>>,
+    /// How many ready jobs a worker takes from the queue and sends at once.
+    batch_size: usize,
+}
+
+/// Spaces requests evenly so all workers together stay under a rate limit.
+pub(crate) struct Pacer {
+    interval: Duration,
+    next: Mutex<Instant>,
+}
+
+impl ApnsSenderBuilder {
+    /// Sets the number of worker tasks, i.e. how many notifications are in flight at once
+    /// (defaults to 4), times the [`batch_size`](ApnsSenderBuilder::batch_size).
+    pub fn workers(mut self, workers: usize) -> Self {
+        self.workers = workers.max(1);
+        self
+    }
+
+    /// Lets each worker take up to `batch_size` notifications that are waiting in the queue
+    /// at once and send them concurrently (defaults to 1).
+    ///
+    /// A worker never waits for a batch to fill; it takes the next notification and any
+    /// others already waiting, then takes more once the whole batch is answered. Up to
+    /// `workers × batch_size` notifications are then in flight, multiplexed over the client's
+    /// connections, which drains large backlogs with fewer tasks. Senders that are
+    /// [`ordered_per_token`](ApnsSenderBuilder::ordered_per_token) send one notification
+    /// per worker at a time regardless, as concurrent sends could arrive out of order.
+    ///
+    /// # Example
+    ///
+    /// ```rust,no_run
+    /// use apnrs::{ApnsClient, ApnsSender};
+    ///
+    /// # fn run(client: ApnsClient) {
+    /// let sender = ApnsSender::builder(client)
+    ///     .workers(4)
+    ///     .batch_size(32)
+    ///     .build();
+    /// # }
+    /// ```
+    pub fn batch_size(mut self, batch_size: usize) -> Self {
+        self.batch_size = batch_size.max(1);
+        self
+    }
+
+    /// Sets how many notifications of each [`TrafficClass`] may wait in the queue before
+    /// [`enqueue`](ApnsSender::enqueue) waits for room (defaults to 1024).
+    pub fn queue_capacity(mut self, capacity: usize) -> Self {
+        self.queue_capacity = capacity.max(1);
+        self
+    }
+
+    /// Guarantees that notifications to the same device token are delivered in the order
+    /// they were enqueued (defaults to `false`).
+    ///
+    /// Each token is pinned to a single worker lane, so a slow send delays later
+    /// notifications that share its lane. The order only holds within a [`TrafficClass`];
+    /// a transactional notification still overtakes marketing ones queued before it.
+    pub fn ordered_per_token(mut self, ordered: bool) -> Self {
+        self.ordered_per_token = ordered;
+        self
+    }
+
+    /// Caps the requests all workers together send at `per_second` a second, retries
+    /// included (defaults to no limit).
+    ///
+    /// Requests are spaced evenly rather than sent in bursts, so a limit below APNs'
+    /// throttling threshold avoids `TooManyRequests` rejections during large sends.
+    pub fn rate_limit(mut self, per_second: f64) -> Self {
+        self.rate_limit = Some(per_second).filter(|rate| *rate > 0.0);
+        self
+    }
+
+    /// Holds notifications with a [`collapse_id`](PushRequest::collapse_id) for `window`
+    /// before queuing them, sending only the latest one per device token and collapse ID
+    /// (defaults to sending every notification right away).
+    ///
+    /// Bursts of updates to the same state, such as a score or an order status, are then
+    /// sent as one notification. The receipts of the notifications replaced during the
+    /// window resolve to `ApnsError::Superseded`, and the latest one keeps the
+    /// [`ReceiptId`] of the first.
+    pub fn coalesce_window(mut self, window: Duration) -> Self {
+        self.coalesce_window = Some(window).filter(|window| !window.is_zero());
+        self
+    }
+
+    /// Holds notifications back while it is within `quiet_hours` in their recipient's local
+    /// time, releasing them when the window closes (defaults to sending at any hour).
+    ///
+    /// Only notifications with a
+    /// [`recipient_utc_offset`](PushRequest::recipient_utc_offset), or a
+    /// `recipient_time_zone` under the `chrono` feature, are held, so leave them unset on
+    /// notifications that must never wait, such as one-time passcodes. Held
+    /// notifications are scheduled as by [`enqueue_at`](ApnsSender::enqueue_at): their
+    /// receipts resolve once they are sent, and they can be cancelled or rescheduled with
+    /// the receipt's [`id`](Receipt::id). Notifications scheduled for a time within the
+    /// window are moved to its end.
+    ///
+    /// # Example
+    ///
+    /// ```rust,no_run
+    /// use apnrs::{ApnsClient, ApnsSender, PushRequest, QuietHours};
+    ///
+    /// # async fn run(client: ApnsClient, request: PushRequest) -> Result<(), apnrs::ApnsError> {
+    /// let sender = ApnsSender::builder(client)
+    ///     .quiet_hours(QuietHours::new((22, 0), (8, 0))?)
+    ///     .build();
+    ///
+    /// // Held until 08:00 in New York if it is night there
+    /// sender.enqueue(request.recipient_utc_offset(-5 * 60 * 60)).await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn quiet_hours(mut self, quiet_hours: QuietHours) -> Self {
+        self.quiet_hours = Some(quiet_hours);
+        self
+    }
+
+    /// Limits the notifications this sender queues for the app with `bundle_id`, under
+    /// every topic of the app such as its `.voip` topic, so one tenant's campaign cannot
+    /// starve the other apps sharing the sender (defaults to no limit).
+    ///
+    /// [`enqueue`](ApnsSender::enqueue) waits for the app's next slot under the quota's rate
+    /// limit before queuing a notification, so a throttled app holds back its own producers
+    /// rather than filling the queue ahead of the others. Once the app has used up its daily
+    /// limit, notifications for it fail with `ApnsError::QuotaExceeded` until midnight UTC,
+    /// and are counted in [`ClientStats::quota_exceeded`](crate::ClientStats). Notifications
+    /// held back by [`enqueue_at`](ApnsSender::enqueue_at) or for
+    /// [coalescing](ApnsSenderBuilder::coalesce_window) count against the daily limit when
+    /// they are held, and are not paced. A notification that fails to be queued or held,
+    /// such as with `ApnsError::ClientShutDown`, does not count. Setting a quota for the same
+    /// app again replaces it.
+    pub fn quota(mut self, bundle_id: impl Into<String>, quota: Quota) -> Self {
+        self.quotas.insert(bundle_id.into(), quota);
+        self
+    }
+
+    /// Sets how notifications classified as [`FailureClass::Retry`] are retried
+    /// (defaults to [`RetryPolicy::new`]).
+    ///
+    /// When APNs answers with a `Retry-After` header, every worker holds off for that long,
+    /// or for the policy's [`cooldown`](RetryPolicy::cooldown) if longer, up to the policy's
+    /// maximum delay, before sending anything else.
+    pub fn retry_policy(mut self, policy: RetryPolicy) -> Self {
+        self.retry_policy = policy;
+        self
+    }
+
+    /// Replaces the classifier deciding whether a failed notification is retried
+    /// (defaults to [`DefaultClassifier`]).
+    pub fn classifier(mut self, classifier: impl FailureClassifier + 'static) -> Self {
+        self.classifier = Arc::new(classifier);
+        self
+    }
+
+    /// Aborts the sender once `token` is cancelled, such as when an operator stops a
+    /// campaign mid-flight (defaults to a token that is never cancelled).
+    ///
+    /// Notifications in flight are abandoned, and those still queued or scheduled are not
+    /// sent: their receipts resolve to `ApnsError::Cancelled`, and they are removed from the
+    /// [`journal`](ApnsSenderBuilder::journal). Receipts of notifications APNs already
+    /// answered resolve as usual, so recording every receipt in a
+    /// [`BatchReport`](crate::BatchReport) reports what was delivered before the
+    /// cancellation. Once cancelled, [`enqueue`](ApnsSender::enqueue) fails with
+    /// `ApnsError::Cancelled`.
+    ///
+    /// # Example
+    ///
+    /// ```rust,no_run
+    /// use apnrs::{ApnsClient, ApnsSender, BatchReport, CancellationToken, PushRequest};
+    ///
+    /// # async fn run(client: ApnsClient, requests: Vec<PushRequest>) -> Result<(), apnrs::ApnsError> {
+    /// let cancel = CancellationToken::new();
+    /// let sender = ApnsSender::builder(client)
+    ///     .cancellation_token(cancel.clone())
+    ///     .build();
+    ///
+    /// let mut receipts = Vec::new();
+    /// for request in requests {
+    ///     let token = request.device_token.clone();
+    ///     receipts.push((token, sender.enqueue(request).await?));
+    /// }
+    ///
+    /// // Aborted by an operator, e.g. from an admin endpoint
+    /// cancel.cancel();
+    ///
+    /// let mut report = BatchReport::new();
+    /// for (token, receipt) in receipts {
+    ///     report.record(&token, &receipt.await);
+    /// }
+    /// println!("delivered {} before the abort", report.delivered);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn cancellation_token(mut self, token: CancellationToken) -> Self {
+        self.cancel = token;
+        self
+    }
+
+    /// Holds notifications back while APNs cannot be reached, instead of failing them, and
+    /// sends them in order once it can again (defaults to off).
+    ///
+    /// Once a notification fails with `ApnsError::Http` after every retry the
+    /// [`retry_policy`](ApnsSenderBuilder::retry_policy) allows, it and those the other
+    /// workers are sending are spooled rather than failed, and the workers stop taking
+    /// notifications from the queue, so [`enqueue`](ApnsSender::enqueue) waits for room
+    /// once it is full rather than memory growing for as long as the outage lasts. APNs is
+    /// probed every `probe_interval`; as soon as a connection succeeds, the spooled
+    /// notifications are sent one at a time, oldest first, and then the workers carry on
+    /// with the queue. Receipts resolve once their notifications are finally sent.
+    ///
+    /// Notifications whose [deadline](PushRequest::deadline) passes while they are spooled
+    /// fail with `ApnsError::DeadlineExceeded`, and those whose
+    /// [`apns-expiration`](crate::SendOptions::expiration) passes fail with
+    /// `ApnsError::Expired`. Spooled and queued notifications are only kept in memory
+    /// unless the sender has a [`journal`](ApnsSenderBuilder::journal), with which they
+    /// survive a restart during the outage; they also keep [`ApnsClient::shutdown`] waiting
+    /// until its timeout.
+    ///
+    /// # Example
+    ///
+    /// ```rust,no_run
+    /// use apnrs::{ApnsClient, ApnsSender};
+    /// use std::time::Duration;
+    ///
+    /// # fn run(client: ApnsClient) {
+    /// let sender = ApnsSender::builder(client)
+    ///     .spool_outages(Duration::from_secs(30))
+    ///     .build();
+    /// println!("{} notifications waiting for APNs", sender.spooled());
+    /// # }
+    /// ```
+    pub fn spool_outages(mut self, probe_interval: Duration) -> Self {
+        self.spool_probe_interval = Some(probe_interval);
+        self
+    }
+
+    /// Records queued notifications in an append-only journal at `path`, so those not yet
+    /// delivered when the process stops are sent once a sender is built with the same
+    /// journal again.
+    ///
+    /// Notifications stay in the journal until a worker has finished with them, including
+    /// those still queued when [`ApnsClient::shutdown`] times out, so delivery is at least
+    /// once: a notification in flight during a crash is sent again after the restart.
+    /// Writes are not synced to disk, so the journal survives the process crashing but not
+    /// the machine losing power. Only one sender may use a journal at a time.
+    ///
+    /// # Returns
+    ///
+    /// The builder, or an `ApnsError` if the journal cannot be read or created.
+    ///
+    /// # Example
+    ///
+    /// ```rust,no_run
+    /// use apnrs::{ApnsClient, ApnsSender};
+    ///
+    /// # fn run(client: ApnsClient) -> Result<(), apnrs::ApnsError> {
+    /// let sender = ApnsSender::builder(client)
+    ///     .journal("/var/lib/push/queue.journal")?
+    ///     .build();
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[cfg(feature = "journal")]
+    pub fn journal(mut self, path: impl AsRef<Path>) -> Result<Self, ApnsError> {
+        self.journal = Some(Journal::open(path.as_ref())?);
+        Ok(self)
+    }
+
+    /// Keeps queuing notifications in memory while the [`journal`](ApnsSenderBuilder::journal)
+    /// cannot be written, such as when the disk is full, instead of failing every
+    /// [`enqueue`](ApnsSender::enqueue) (defaults to off).
+    ///
+    /// At most `limit` notifications are queued without being journaled at a time, each
+    /// counting until it is settled or dropped, such as by an aborted worker; beyond that,
+    /// notifications fail with the journal's error again. Those queued in memory are
+    /// lost if the process stops before they are sent. Every notification the journal fails
+    /// to record publishes a [`DeliveryEvent::JournalFailed`](crate::DeliveryEvent) and is
+    /// logged as an error, so the degraded durability does not go unnoticed.
+    ///
+    /// # Example
+    ///
+    /// ```rust,no_run
+    /// use apnrs::{ApnsClient, ApnsSender};
+    ///
+    /// # fn run(client: ApnsClient) -> Result<(), apnrs::ApnsError> {
+    /// let sender = ApnsSender::builder(client)
+    ///     .journal("/var/lib/push/queue.journal")?
+    ///     .journal_fallback(10_000)
+    ///     .build();
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[cfg(feature = "journal")]
+    pub fn journal_fallback(mut self, limit: usize) -> Self {
+        self.journal_fallback = Some(limit);
+        self
+    }
+
+    /// Spawns the workers onto the current tokio runtime and returns the sender.
+    ///
+    /// Notifications recovered from the [`journal`](ApnsSenderBuilder::journal) are queued
+    /// ahead of new ones, in the background, or scheduled again if they were scheduled.
+    ///
+    /// # Panics
+    ///
+    /// Panics if called outside of a tokio runtime.
+    pub fn build(self) -> ApnsSender {
+        let spool = self
+            .spool_probe_interval
+            .map(|every| Arc::new(Spool::new(every)));
+        let worker = Arc::new(Worker {
+            client: self.client.clone(),
+            pacer: self.rate_limit.map(Pacer::new),
+            retry_policy: self.retry_policy,
+            classifier: self.classifier,
+            resume_at: Mutex::new(Instant::now()),
+            cancel: self.cancel.clone(),
+            spool: spool.clone(),
+            batch_size: if self.ordered_per_token {
+                1
+            } else {
+                self.batch_size
+            },
+        });
+
+        let queue = if self.ordered_per_token {
+            let capacity = (self.queue_capacity / self.workers).max(1);
+            let lanes = (0..self.workers)
+                .map(|_| {
+                    let (tx, rx) = lane(capacity);
+                    let rx = Arc::new(Mutex::new(rx));
+                    tokio::spawn(run_worker(worker.clone(), rx));
+                    tx
+                })
+                .collect();
+            Queue::Lanes(lanes)
+        } else {
+            let (tx, rx) = lane(self.queue_capacity);
+            let rx = Arc::new(Mutex::new(rx));
+            for _ in 0..self.workers {
+                tokio::spawn(run_worker(worker.clone(), rx.clone()));
+            }
+            Queue::Shared(tx)
+        };
+
+        let scheduler = Arc::new(Scheduler::default());
+        let (client, tasks) = (self.client.clone(), scheduler.clone());
+        let scheduled_queue = queue.clone();
+        tokio::spawn(async move { tasks.run(client, scheduled_queue).await });
+
+        #[cfg(feature = "journal")]
+        let journal = self.journal.map(|(journal, recovered)| {
+            let mut immediate = Vec::new();
+            for (entry, request, deliver_at) in recovered {
+                let Some(deliver_at) = deliver_at else {
+                    immediate.push((entry, request));
+                    continue;
+                };
+                // Nobody waits for the outcome; the worker ignores the closed channel
+                let (reply, _) = oneshot::channel();
+                let scheduled = Scheduled {
+                    request,
+                    reply,
+                    entry: Some(entry),
+                };
+                let _ = scheduler.insert(scheduler.next_id(), deliver_at, scheduled);
+            }
+            if !immediate.is_empty() {
+                tokio::spawn(resume(self.client.clone(), queue.clone(), immediate));
+            }
+            journal
+        });
+
+        ApnsSender {
+            intake: Intake {
+                client: self.client.clone(),
+                scheduler,
+                coalesce_window: self.coalesce_window,
+                quiet_hours: self.quiet_hours,
+                quotas: Arc::new(Quotas::new(self.quotas)),
+                cancel: self.cancel,
+                #[cfg(feature = "journal")]
+                journal,
+                #[cfg(feature = "journal")]
+                journal_fallback: self.journal_fallback,
+            },
+            client: self.client,
+            queue,
+            spool,
+        }
+    }
+}
+
+impl ApnsSender {
+    /// Returns a builder for a sender delivering through `client`.
+    pub fn builder(client: ApnsClient) -> ApnsSenderBuilder {
+        ApnsSenderBuilder {
+            client,
+            workers: 4,
+            batch_size: 1,
+            queue_capacity: 1024,
+            ordered_per_token: false,
+            rate_limit: None,
+            coalesce_window: None,
+            quiet_hours: None,
+            quotas: HashMap::new(),
+            retry_policy: RetryPolicy::default(),
+            classifier: Arc::new(DefaultClassifier),
+            cancel: CancellationToken::new(),
+            spool_probe_interval: None,
+            #[cfg(feature = "journal")]
+            journal: None,
+            #[cfg(feature = "journal")]
+            journal_fallback: None,
+        }
+    }
+
+    /// Adds a notification to the queue, waiting for room if the queue is full.
+    ///
+    /// With a [`coalesce_window`](ApnsSenderBuilder::coalesce_window), a notification with
+    /// a collapse ID is instead held for the window and returns right away.
+    ///
+    /// # Returns
+    ///
+    /// A [`Receipt`] that resolves once the notification has been sent,
+    /// `ApnsError::QueueClosed` if the workers have stopped, `ApnsError::ClientShutDown`
+    /// if the client is shutting down, `ApnsError::Cancelled` if the sender's
+    /// [cancellation token](ApnsSenderBuilder::cancellation_token) was cancelled, or
+    /// `ApnsError::InvalidTopic` if the topic does not fit the request's push type; see
+    /// [`PushRequest::validate_topic`], or `ApnsError::QuotaExceeded` if the app of the
+    /// topic used up its daily [quota](ApnsSenderBuilder::quota).
+    pub async fn enqueue(&self, request: PushRequest) -> Result<Receipt, ApnsError> {
+        let request = match self.intake.screen(request)? {
+            Screened::Held(receipt) => return Ok(receipt),
+            Screened::Due(request) => *request,
+        };
+        let request = self.intake.pace(request).await;
+        let (job, receipt) = self.intake.job(request)?;
+        if let Err(job) = self.queue.try_push(&self.client, job).await {
+            self.intake.refund(&job.request);
+            return Err(ApnsError::QueueClosed);
+        }
+        Ok(receipt)
+    }
+
+    /// Holds a notification back until `deliver_at`, then adds it to the queue.
+    ///
+    /// The notification can be [cancelled](ApnsSender::cancel) or
+    /// [rescheduled](ApnsSender::reschedule) with the receipt's [`id`](Receipt::id) until
+    /// it is due. A time in the past queues it right away. Scheduled notifications do not
+    /// hold up [`ApnsClient::shutdown`]; they are dropped with it, unless the sender has a
+    /// journal to resume them from.
+    ///
+    /// # Returns
+    ///
+    /// A [`Receipt`] that resolves once the notification has been sent, or to
+    /// `ApnsError::Cancelled` if it was cancelled, or `ApnsError::ClientShutDown` if the
+    /// client is shutting down. Fails with `ApnsError::QuotaExceeded` if the app of the
+    /// topic used up its daily [quota](ApnsSenderBuilder::quota).
+    ///
+    /// # Example
+    ///
+    /// ```rust,no_run
+    /// use apnrs::{ApnsSender, PushRequest};
+    /// use std::time::{Duration, SystemTime};
+    ///
+    /// # async fn run(sender: ApnsSender, request: PushRequest) -> Result<(), apnrs::ApnsError> {
+    /// let receipt = sender.enqueue_at(request, SystemTime::now() + Duration::from_secs(3600))?;
+    /// let id = receipt.id();
+    ///
+    /// // The user snoozed the reminder
+    /// sender.reschedule(id, SystemTime::now() + Duration::from_secs(7200));
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn enqueue_at(
+        &self,
+        request: PushRequest,
+        deliver_at: SystemTime,
+    ) -> Result<Receipt, ApnsError> {
+        self.intake.hold(request, deliver_at, false)
+    }
+
+    /// Holds a notification back for `delay`, then adds it to the queue, as
+    /// [`enqueue_at`](ApnsSender::enqueue_at) does.
+    pub fn enqueue_after(
+        &self,
+        request: PushRequest,
+        delay: Duration,
+    ) -> Result<Receipt, ApnsError> {
+        self.enqueue_at(request, SystemTime::now() + delay)
+    }
+
+    /// Cancels a notification scheduled with [`enqueue_at`](ApnsSender::enqueue_at), whose
+    /// receipt then resolves to `ApnsError::Cancelled`.
+    ///
+    /// # Returns
+    ///
+    /// `true` if the notification was cancelled, or `false` if it was already due or
+    /// `id` names no scheduled notification.
+    pub fn cancel(&self, id: ReceiptId) -> bool {
+        self.intake.scheduler.cancel(id)
+    }
+
+    /// Moves a notification scheduled with [`enqueue_at`](ApnsSender::enqueue_at) to
+    /// `deliver_at`.
+    ///
+    /// # Returns
+    ///
+    /// `true` if the notification was rescheduled, or `false` if it was already due or
+    /// `id` names no scheduled notification.
+    pub fn reschedule(&self, id: ReceiptId, deliver_at: SystemTime) -> bool {
+        self.intake.scheduler.reschedule(id, deliver_at)
+    }
+
+    /// Returns how many more notifications the app with `bundle_id` may queue today under
+    /// its daily [quota](ApnsSenderBuilder::quota), or `None` if it has no daily limit.
+    pub fn quota_remaining(&self, bundle_id: &str) -> Option<u64> {
+        self.intake.quotas.remaining(bundle_id)
+    }
+
+    /// Returns how many notifications are held back until APNs can be reached again, with
+    /// [`spool_outages`](ApnsSenderBuilder::spool_outages).
+    pub fn spooled(&self) -> usize {
+        self.spool.as_ref().map_or(0, |spool| spool.len())
+    }
+}
+
+impl Intake {
+    /// Checks a notification the way [`ApnsSender::enqueue`] does before queuing it, and
+    /// holds it back if it is to be coalesced or it is within the recipient's quiet hours.
+    pub(crate) fn screen(&self, request: PushRequest) -> Result<Screened, ApnsError> {
+        if self.cancel.is_cancelled() {
+            return Err(ApnsError::Cancelled);
+        }
+        request.validate_topic()?;
+        if let (Some(window), Some(_)) = (self.coalesce_window, &request.options.collapse_id) {
+            let receipt = self.hold(request, SystemTime::now() + window, true)?;
+            return Ok(Screened::Held(receipt));
+        }
+        if let Some(release_at) = self.quiet_until(&request, SystemTime::now()) {
+            return Ok(Screened::Held(self.hold(request, release_at, false)?));
+        }
+        self.quotas
+            .admit(&request.topic, self.client.stats_recorder())?;
+        Ok(Screened::Due(Box::new(request)))
+    }
+
+    /// Waits until the [rate limit](Quota::rate_limit) of the app of `request` allows
+    /// queuing it.
+    pub(crate) fn pace(&self, request: PushRequest) -> impl Future<Output = PushRequest> + Send {
+        let quotas = self.quotas.clone();
+        async move {
+            quotas.pace(&request.topic).await;
+            request
+        }
+    }
+
+    /// Creates the job for a due notification, recording it in the journal.
+    ///
+    /// A notification that cannot be queued is refunded to its app's quota.
+    pub(crate) fn job(&self, mut request: PushRequest) -> Result<(Job, Receipt), ApnsError> {
+        let Some(pending) = self.client.lifecycle().start() else {
+            self.refund(&request);
+            return Err(ApnsError::ClientShutDown);
+        };
+        let apns_id = request.assign_apns_id().to_string();
+        let (reply, rx) = oneshot::channel();
+        #[cfg(feature = "journal")]
+        let entry = self
+            .journal_entry(&request, None)
+            .inspect_err(|_| self.refund(&request))?;
+        let job = Job {
+            request,
+            reply,
+            pending,
+            #[cfg(feature = "journal")]
+            entry,
+        };
+        let receipt = Receipt {
+            id: self.scheduler.next_id(),
+            apns_id,
+            rx,
+        };
+        Ok((job, receipt))
+    }
+
+    /// Hands a notification to the scheduler until `deliver_at`, replacing the one held for
+    /// the same device token and collapse ID if `coalesce` is set.
+    ///
+    /// A `deliver_at` within the recipient's quiet hours is moved to their end.
+    fn hold(
+        &self,
+        mut request: PushRequest,
+        deliver_at: SystemTime,
+        coalesce: bool,
+    ) -> Result<Receipt, ApnsError> {
+        let deliver_at = self.quiet_until(&request, deliver_at).unwrap_or(deliver_at);
+        if self.client.lifecycle().is_closed() {
+            return Err(ApnsError::ClientShutDown);
+        }
+        if self.cancel.is_cancelled() {
+            return Err(ApnsError::Cancelled);
+        }
+        self.quotas
+            .admit(&request.topic, self.client.stats_recorder())?;
+        let topic = request.topic.clone();
+        let apns_id = request.assign_apns_id().to_string();
+        let (reply, rx) = oneshot::channel();
+        let schedule = || {
+            #[cfg(feature = "journal")]
+            let entry = self.journal_entry(&request, Some(deliver_at))?;
+            let scheduled = Scheduled {
+                request,
+                reply,
+                #[cfg(feature = "journal")]
+                entry,
+            };
+            if coalesce {
+                return self.scheduler.coalesce(deliver_at, scheduled);
+            }
+            let id = self.scheduler.next_id();
+            self.scheduler.insert(id, deliver_at, scheduled)?;
+            Ok(id)
+        };
+        // A notification that was not scheduled does not count against the quota
+        let id = schedule().inspect_err(|_| self.quotas.refund(&topic))?;
+        Ok(Receipt { id, apns_id, rx })
+    }
+
+    /// Gives back the quota a due notification was counted against, when it could not be
+    /// queued after all.
+    pub(crate) fn refund(&self, request: &PushRequest) {
+        self.quotas.refund(&request.topic);
+    }
+
+    /// Records a notification in the journal, or, with a
+    /// [`journal_fallback`](ApnsSenderBuilder::journal_fallback), queues it in memory only
+    /// if the journal cannot be written.
+    #[cfg(feature = "journal")]
+    fn journal_entry(
+        &self,
+        request: &PushRequest,
+        deliver_at: Option<SystemTime>,
+    ) -> Result<Option<JournalEntry>, ApnsError> {
+        let Some(journal) = &self.journal else {
+            return Ok(None);
+        };
+        let error = match journal.append(request, deliver_at) {
+            Ok(entry) => return Ok(Some(entry)),
+            Err(error) => error,
+        };
+        let Some(entry) = self
+            .journal_fallback
+            .and_then(|limit| journal.hold_unwritten(limit))
+        else {
+            return Err(error);
+        };
+        #[cfg(feature = "tracing")]
+        tracing::error!(
+            %error,
+            "failed to write the journal; queuing the notification in memory only"
+        );
+        #[cfg(feature = "logging")]
+        log::error!(
+            "failed to write the journal; queuing the notification in memory only: {}",
+            error
+        );
+        self.client
+            .event_publisher()
+            .journal_failed(&error.to_string());
+        Ok(Some(entry))
+    }
+
+    /// Returns when the quiet hours of the recipient of `request` end, if `at` falls within
+    /// them.
+    fn quiet_until(&self, request: &PushRequest, at: SystemTime) -> Option<SystemTime> {
+        let quiet_hours = self.quiet_hours?;
+        #[cfg(feature = "chrono")]
+        if let Some(zone) = request.recipient_time_zone {
+            return quiet_hours.release_at_in(at, zone);
+        }
+        quiet_hours.release_at(at, request.recipient_utc_offset?)
+    }
+}
+
+impl Drop for ApnsSender {
+    fn drop(&mut self) {
+        self.intake.scheduler.release();
+    }
+}
+
+impl Queue {
+    /// Hands a job to the workers, waiting for room in its lane.
+    ///
+    /// # Returns
+    ///
+    /// `Ok(())`, or `ApnsError::QueueClosed` if the workers have stopped.
+    pub(crate) async fn push(&self, client: &ApnsClient, job: Job) -> Result<(), ApnsError> {
+        self.try_push(client, job)
+            .await
+            .map_err(|_| ApnsError::QueueClosed)
+    }
+
+    /// Hands a job to the workers, waiting for room in its lane.
+    ///
+    /// # Returns
+    ///
+    /// `Ok(())`, or the settled job if the workers have stopped.
+    pub(crate) async fn try_push(&self, client: &ApnsClient, job: Job) -> Result<(), Box<Job>> {
+        let lane = match self {
+            Queue::Shared(lane) => lane,
+            Queue::Lanes(lanes) => &lanes[lane_for(&job.request.device_token, lanes.len())],
+        };
+        let tx = lane.sender(job.request.class);
+
+        report_enqueued(client, &job.request);
+        if let Err(mpsc::error::SendError(mut job)) = tx.send(job).await {
+            client.stats_recorder().dequeued();
+            job.settle();
+            return Err(Box::new(job));
+        }
+        Ok(())
+    }
+}
+
+/// Counts a job that is about to be handed to the workers, before a worker can pick it up
+/// so the depth never underflows, and reports it so the event precedes those of its send.
+pub(crate) fn report_enqueued(client: &ApnsClient, request: &PushRequest) {
+    client.stats_recorder().enqueued();
+    client.event_publisher().enqueued(
+        &request.topic,
+        &request.device_token,
+        request.options.apns_id.as_deref(),
+    );
+}
+
+/// Queues the notifications recovered from a journal, until the workers stop or the client
+/// shuts down.
+#[cfg(feature = "journal")]
+async fn resume(client: ApnsClient, queue: Queue, recovered: Vec<(JournalEntry, PushRequest)>) {
+    #[cfg(feature = "tracing")]
+    tracing::info!(
+        count = recovered.len(),
+        "resuming notifications from the journal"
+    );
+    for (entry, request) in recovered {
+        // Left in the journal, they are resumed on the next start instead
+        let Some(pending) = client.lifecycle().start() else {
+            return;
+        };
+        // Nobody waits for the outcome; the worker ignores the closed channel
+        let (reply, _) = oneshot::channel();
+        let job = Job {
+            request,
+            reply,
+            pending,
+            entry: Some(entry),
+        };
+        if queue.push(&client, job).await.is_err() {
+            return;
+        }
+    }
+}
+
+/// Identifies a notification accepted by an [`ApnsSender`], for
+/// [cancelling](ApnsSender::cancel) or [rescheduling](ApnsSender::reschedule) it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct ReceiptId(pub(crate) u64);
+
+impl fmt::Display for ReceiptId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+/// Resolves to the outcome of a queued notification once a worker has sent it.
+pub struct Receipt {
+    pub(crate) id: ReceiptId,
+    pub(crate) apns_id: String,
+    pub(crate) rx: oneshot::Receiver<Result<SendOutcome, ApnsError>>,
+}
+
+impl Receipt {
+    /// Returns the ID of the notification, unique within its sender.
+    pub fn id(&self) -> ReceiptId {
+        self.id
+    }
+
+    /// Returns the `apns-id` the notification is sent with, on every attempt.
+    pub fn apns_id(&self) -> &str {
+        &self.apns_id
+    }
+}
+
+impl Future for Receipt {
+    type Output = Result<SendOutcome, ApnsError>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        Pin::new(&mut self.rx)
+            .poll(cx)
+            .map(|result| result.unwrap_or(Err(ApnsError::QueueClosed)))
+    }
+}
+
+/// Picks the lane a device token is pinned to.
+pub(crate) fn lane_for(device_token: &str, lanes: usize) -> usize {
+    let mut hasher = DefaultHasher::new();
+    device_token.hash(&mut hasher);
+    (hasher.finish() % lanes as u64) as usize
+}
+
+/// Delivers jobs from `rx` one at a time, highest class first, until the queue is closed
+/// and drained.
+async fn run_worker(worker: Arc<Worker>, rx: Arc<Mutex<LaneReceiver>>) {
+    loop {
+        // During an outage, notifications wait in the bounded queue
+        if let Some(spool) = &worker.spool {
+            spool.reachable().await;
+        }
+        let batch = rx.lock().await.recv_batch(worker.batch_size).await;
+        if batch.is_empty() {
+            break;
+        }
+        join_all(batch.into_iter().map(|job| handle(&worker, job))).await;
+    }
+}
+
+/// Delivers a job taken from the queue and reports its outcome, unless it is spooled.
+async fn handle(worker: &Arc<Worker>, mut job: Job) {
+    worker.client.stats_recorder().dequeued();
+
+    // Those taken as the outage began wait behind the ones already spooled
+    if let Some(spool) = &worker.spool {
+        let Some(reachable) = spool.divert(job) else {
+            return;
+        };
+        job = reachable;
+    }
+
+    // A shutdown that timed out discards whatever is still queued, leaving it in the
+    // journal to be resumed
+    let result = if worker.client.lifecycle().is_aborted() {
+        Err(ApnsError::ClientShutDown)
+    } else {
+        // A cancelled sender drains its queue without sending
+        let result = tokio::select! {
+            biased;
+            _ = worker.cancel.cancelled() => Err(ApnsError::Cancelled),
+            result = worker.deliver(&job.request) => result,
+        };
+        if let (Err(ApnsError::Http(_)), Some(spool)) = (&result, &worker.spool) {
+            if spool.spill(job) {
+                tokio::spawn(replay(worker.clone(), spool.clone()));
+            }
+            return;
+        }
+        job.settle();
+        result
+    };
+
+    // The caller may have dropped the receipt; that is not an error
+    let _ = job.reply.send(result);
+    drop(job.pending);
+}
+
+impl Worker {
+    /// Sends a notification, retrying failures the classifier considers transient.
+    pub(crate) async fn deliver(&self, request: &PushRequest) -> Result<SendOutcome, ApnsError> {
+        if self.client.token_store().is_invalid(&request.device_token) {
+            return Err(ApnsError::InvalidToken);
+        }
+
+        // Notifications resumed from an older journal may have no ID yet
+        let mut options = request.options.clone();
+        if options.apns_id.is_none() {
+            options = options.apns_id(new_apns_id());
+        }
+        let mut retry = 0;
+        loop {
+            let resume_at = *self.resume_at.lock().await;
+            past_deadline(request, resume_at)?;
+            tokio::time::sleep_until(resume_at).await;
+            if let Some(pacer) = &self.pacer {
+                pacer.wait().await;
+            }
+            let send = self.client.dispatch(
+                &request.device_token,
+                &request.topic,
+                None,
+                None,
+                &request.payload,
+                &options,
+            );
+            let mut result = request.within_deadline(send).await;
+
+            let class = match &mut result {
+                Ok(outcome) if outcome.response.is_success() => return result,
+                Ok(outcome) => {
+                    let status = outcome.response.status;
+                    let reason = outcome.response.error.as_ref();
+                    let class = self.retry_policy.reclassify(
+                        self.classifier.classify_rejection(status, reason),
+                        status,
+                        reason,
+                    );
+                    outcome.failure = Some(class);
+                    class
+                }
+                Err(e) => {
+                    let class = self.classifier.classify_error(e);
+                    match e {
+                        ApnsError::Rejected { status, reason } => {
+                            self.retry_policy
+                                .reclassify(class, *status, reason.as_ref())
+                        }
+                        _ => class,
+                    }
+                }
+            };
+
+            #[cfg(feature = "tracing")]
+            if class == FailureClass::Critical {
+                tracing::error!(topic = %request.topic, "notification failed with a critical error");
+            }
+            #[cfg(feature = "logging")]
+            if class == FailureClass::Critical {
+                log::error!(
+                    "notification to {} on {} failed with a critical error",
+                    mask_token(&request.device_token),
+                    request.topic
+                );
+            }
+
+            // A throttled APNs holds off every worker, not only the one it answered
+            let retry_after = result
+                .as_ref()
+                .ok()
+                .and_then(|outcome| outcome.response.retry_after());
+            if let Some(retry_after) = retry_after {
+                let pause = self.retry_policy.cooldown_after(retry + 1, retry_after);
+                let mut resume_at = self.resume_at.lock().await;
+                *resume_at = (*resume_at).max(Instant::now() + pause);
+                self.client.event_publisher().circuit_opened(pause);
+            }
+
+            if class != FailureClass::Retry || retry >= self.retry_policy.retries() {
+                return result;
+            }
+            retry += 1;
+            let delay = match retry_after {
+                Some(retry_after) => self.retry_policy.delay_after(retry, retry_after),
+                None => self.retry_policy.delay(retry),
+            };
+            #[cfg(feature = "logging")]
+            log::info!(
+                "retrying notification to {} on {} in {:?} (retry {} of {})",
+                mask_token(&request.device_token),
+                request.topic,
+                delay,
+                retry,
+                self.retry_policy.retries()
+            );
+            past_deadline(request, Instant::now() + delay)?;
+            let reason = match &result {
+                Ok(outcome) => match &outcome.response.error {
+                    Some(reason) => reason.as_str().to_string(),
+                    None => outcome.response.status.to_string(),
+                },
+                Err(e) => e.to_string(),
+            };
+            self.client.event_publisher().retried(
+                &request.topic,
+                &request.device_token,
+                options.apns_id.as_deref(),
+                retry,
+                &reason,
+            );
+            tokio::time::sleep(delay).await;
+        }
+    }
+}
+
+/// Fails with `ApnsError::DeadlineExceeded` if waiting until `until` would take the
+/// notification past its deadline.
+fn past_deadline(request: &PushRequest, until: Instant) -> Result<(), ApnsError> {
+    match request.time_left() {
+        Some(left) if Instant::now() + left < until || left.is_zero() => {
+            Err(ApnsError::DeadlineExceeded(left))
+        }
+        _ => Ok(()),
+    }
+}
+
+impl Pacer {
+    /// Creates a pacer allowing `per_second` requests a second.
+    ///
+    /// Rates so low that the gap between requests would exceed a year are paced at one
+    /// request a year, which keeps the gap representable.
+    pub(crate) fn new(per_second: f64) -> Self {
+        const MAX_INTERVAL: Duration = Duration::from_secs(365 * 24 * 60 * 60);
+        let interval = Duration::try_from_secs_f64(1.0 / per_second).unwrap_or(MAX_INTERVAL);
+        Pacer {
+            interval: interval.min(MAX_INTERVAL),
+            next: Mutex::new(Instant::now()),
+        }
+    }
+
+    /// Waits for the next free slot.
+    pub(crate) async fn wait(&self) {
+        let slot = {
+            let mut next = self.next.lock().await;
+            // Idle time does not bank slots for a later burst
+            let slot = (*next).max(Instant::now());
+            *next = slot + self.interval;
+            slot
+        };
+        tokio::time::sleep_until(slot).await;
+    }
+}
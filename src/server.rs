@@ -0,0 +1,203 @@
+//! A minimal HTTP relay forwarding pushes to APNs, enabled with the `server` feature, so
+//! services written in other languages can send through an [`ApnsClient`] over plain HTTP.
+//!
+//! The relay accepts `POST /push` with a [`PushRequest`] as its JSON body, the same shape the
+//! request serializes to, and answers with APNs' status and a JSON body:
+//!
+//! * `{"status": 200, "apns_id": "..."}` when APNs accepted the notification.
+//! * `{"status": 410, "apns_id": "...", "reason": "Unregistered", "unregistered_at": ...}`
+//!   when it rejected it, with the same HTTP status.
+//! * `{"error": "...", "code": "APNRS-..."}` when the notification could not be sent, with
+//!   `400` for an invalid request, `401` for a missing or wrong bearer token, `403` when a
+//!   [`SendHook`](crate::SendHook) blocked it, `502` or `504` when APNs could not be reached
+//!   in time, and `500` otherwise.
+//!
+//! # Example
+//!
+//! ```rust,no_run
+//! use apnrs::server::Relay;
+//! use apnrs::ApnsClient;
+//!
+//! #[tokio::main]
+//! async fn main() -> Result<(), Box<dyn std::error::Error>> {
+//!     let client = ApnsClient::builder()
+//!         .auth_key_path("path/to/key")
+//!         .team_id("TEAM_ID")
+//!         .key_id("KEY_ID")
+//!         .build()?;
+//!
+//!     Relay::new(client)
+//!         .bearer_token(std::env::var("RELAY_TOKEN")?)
+//!         .serve(([0, 0, 0, 0], 8080).into())
+//!         .await?;
+//!     Ok(())
+//! }
+//! ```
+//!
+//! Other services then send with, for example:
+//!
+//! ```text
+//! curl -X POST http://push-relay:8080/push \
+//!     -H "authorization: Bearer $RELAY_TOKEN" \
+//!     -d '{"device_token": "...", "topic": "com.example.app", "payload": {"aps": {"alert": "Hi"}}}'
+//! ```
+
+use crate::{ApnsClient, ApnsError, ErrorReason, PushRequest};
+use axum::extract::rejection::JsonRejection;
+use axum::extract::State;
+use axum::response::{IntoResponse, Response};
+use axum::routing::post;
+use axum::{Json, Router};
+use http::header::AUTHORIZATION;
+use http::{HeaderMap, StatusCode};
+use serde::Serialize;
+use std::io;
+use std::net::SocketAddr;
+use std::sync::Arc;
+
+/// A relay forwarding `POST /push` requests to APNs through an [`ApnsClient`].
+///
+/// See the [module documentation](self) for the request and response formats.
+pub struct Relay {
+    client: ApnsClient,
+    bearer_token: Option<String>,
+}
+
+/// What the handler needs, shared by every request.
+struct RelayState {
+    client: ApnsClient,
+    authorization: Option<String>,
+}
+
+/// The body answering a notification APNs accepted or rejected.
+#[derive(Serialize)]
+struct Answered<'a> {
+    status: u16,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    apns_id: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    reason: Option<&'a ErrorReason>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    unregistered_at: Option<u64>,
+}
+
+/// The body answering a notification that could not be sent.
+#[derive(Serialize)]
+struct Failed {
+    error: String,
+    code: &'static str,
+}
+
+impl Relay {
+    /// Creates a relay sending through `client`, accepting every request.
+    pub fn new(client: ApnsClient) -> Self {
+        Relay {
+            client,
+            bearer_token: None,
+        }
+    }
+
+    /// Only accepts requests with an `authorization: Bearer <token>` header.
+    ///
+    /// Anyone who can reach a relay without one can send notifications to your users, so
+    /// set a token unless the relay is only reachable from trusted hosts.
+    pub fn bearer_token(mut self, token: impl Into<String>) -> Self {
+        self.bearer_token = Some(token.into());
+        self
+    }
+
+    /// Returns the relay's routes, to serve with other routes or middleware of your own.
+    pub fn router(self) -> Router {
+        let state = RelayState {
+            client: self.client,
+            authorization: self.bearer_token.map(|token| format!("Bearer {}", token)),
+        };
+        Router::new()
+            .route("/push", post(push))
+            .with_state(Arc::new(state))
+    }
+
+    /// Serves the relay on `address` until the process exits.
+    ///
+    /// # Returns
+    ///
+    /// An error if the address cannot be bound or the server fails.
+    pub async fn serve(self, address: SocketAddr) -> io::Result<()> {
+        axum::Server::try_bind(&address)
+            .map_err(io::Error::other)?
+            .serve(self.router().into_make_service())
+            .await
+            .map_err(io::Error::other)
+    }
+}
+
+/// Handles `POST /push`.
+async fn push(
+    State(state): State<Arc<RelayState>>,
+    headers: HeaderMap,
+    request: Result<Json<PushRequest>, JsonRejection>,
+) -> Response {
+    if let Some(expected) = &state.authorization {
+        let given = headers.get(AUTHORIZATION).map(|value| value.as_bytes());
+        let authorized = given.is_some_and(|given| {
+            given.len() == expected.len() && openssl::memcmp::eq(given, expected.as_bytes())
+        });
+        if !authorized {
+            return failed(
+                StatusCode::UNAUTHORIZED,
+                "missing or wrong bearer token".to_string(),
+                "APNRS-RELAY-UNAUTHORIZED",
+            );
+        }
+    }
+    let Json(request) = match request {
+        Ok(request) => request,
+        Err(rejection) => {
+            return failed(
+                StatusCode::BAD_REQUEST,
+                rejection.body_text(),
+                "APNRS-RELAY-BAD-REQUEST",
+            )
+        }
+    };
+    match state.client.send_request(&request).await {
+        Ok(outcome) => {
+            let response = &outcome.response;
+            let body = Answered {
+                status: response.status.as_u16(),
+                apns_id: response.apns_id.as_deref(),
+                reason: response.error.as_ref(),
+                unregistered_at: response.unregistered_at,
+            };
+            (response.status, Json(body)).into_response()
+        }
+        Err(e) => failed(status_for(&e), e.to_string(), e.code()),
+    }
+}
+
+/// Returns the relay's answer to a request it could not forward.
+fn failed(status: StatusCode, error: String, code: &'static str) -> Response {
+    (status, Json(Failed { error, code })).into_response()
+}
+
+/// Returns the HTTP status the relay answers `error` with.
+fn status_for(error: &ApnsError) -> StatusCode {
+    match error {
+        ApnsError::Serialization(_)
+        | ApnsError::InvalidHeader(_)
+        | ApnsError::InvalidTopic(_)
+        | ApnsError::InvalidNotification(_)
+        | ApnsError::InvalidDeviceToken(_)
+        | ApnsError::InvalidPayload(_)
+        | ApnsError::InvalidTemplate(_)
+        | ApnsError::InvalidCustomData(_)
+        | ApnsError::InvalidToken
+        | ApnsError::CertificateRequired(_) => StatusCode::BAD_REQUEST,
+        ApnsError::Blocked(_) => StatusCode::FORBIDDEN,
+        ApnsError::Http(_) | ApnsError::PinMismatch(_) => StatusCode::BAD_GATEWAY,
+        ApnsError::DeadlineExceeded(_) => StatusCode::GATEWAY_TIMEOUT,
+        ApnsError::ClientShutDown => StatusCode::SERVICE_UNAVAILABLE,
+        ApnsError::Rejected { status, .. } => *status,
+        _ => StatusCode::INTERNAL_SERVER_ERROR,
+    }
+}
@@ -0,0 +1,27 @@
+//! [`tower::Service`](https://docs.rs/tower) integration, enabled with the `tower` feature.
+
+use crate::{ApnsClient, ApnsError, PushRequest, SendOutcome};
+use std::future::Future;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use tower_service::Service;
+
+/// Sends each request with [`ApnsClient::send_request`], so the client can be wrapped in tower
+/// middleware such as timeouts, load shedding, or retries.
+///
+/// The client is always ready; connection pooling and HTTP/2 multiplexing are handled
+/// internally.
+impl Service<PushRequest> for ApnsClient {
+    type Response = SendOutcome;
+    type Error = ApnsError;
+    type Future = Pin<Box<dyn Future<Output = Result<SendOutcome, ApnsError>> + Send>>;
+
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, request: PushRequest) -> Self::Future {
+        let client = self.clone();
+        Box::pin(async move { client.send_request(&request).await })
+    }
+}
@@ -0,0 +1,111 @@
+//! Spreading requests over several HTTP/2 connections to APNs.
+
+use std::ops::Deref;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// How a client with several [connections](crate::ApnsClientBuilder::connections) picks the
+/// one each request goes out on.
+///
+/// # Variants
+///
+/// * `RoundRobin` - Requests take the connections in turn.
+/// * `LeastLoaded` - Each request takes the connection with the fewest requests in flight,
+///   so one slowed down by a congested path or a throttled stream limit gets fewer.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum ConnectionStrategy {
+    #[default]
+    RoundRobin,
+    LeastLoaded,
+}
+
+/// The HTTP clients of a client, each with a pool of its own and so a connection of its own
+/// to each APNs host.
+pub(crate) struct Shards {
+    shards: Vec<Shard>,
+    strategy: ConnectionStrategy,
+    next: AtomicUsize,
+}
+
+struct Shard {
+    http: reqwest::Client,
+    in_flight: AtomicUsize,
+}
+
+/// A connection picked for one request, counted as in flight until dropped.
+pub(crate) struct ShardGuard<'a> {
+    shard: &'a Shard,
+}
+
+impl Shards {
+    /// Creates shards from at least one HTTP client.
+    pub(crate) fn new(clients: Vec<reqwest::Client>, strategy: ConnectionStrategy) -> Self {
+        debug_assert!(!clients.is_empty());
+        let shards = clients
+            .into_iter()
+            .map(|http| Shard {
+                http,
+                in_flight: AtomicUsize::new(0),
+            })
+            .collect();
+        Shards {
+            shards,
+            strategy,
+            next: AtomicUsize::new(0),
+        }
+    }
+
+    /// Returns the HTTP client of the first connection, for requests other than sends.
+    pub(crate) fn primary(&self) -> &reqwest::Client {
+        &self.shards[0].http
+    }
+
+    /// Returns every HTTP client.
+    pub(crate) fn all(&self) -> impl Iterator<Item = &reqwest::Client> {
+        self.shards.iter().map(|shard| &shard.http)
+    }
+
+    /// Chooses a connection with the client's strategy, for requests that should all go out
+    /// on the same one.
+    ///
+    /// # Returns
+    ///
+    /// The index of the connection, to pass to [`pick`](Shards::pick).
+    pub(crate) fn choose(&self) -> usize {
+        let len = self.shards.len();
+        match self.strategy {
+            _ if len == 1 => 0,
+            ConnectionStrategy::RoundRobin => self.next.fetch_add(1, Ordering::Relaxed) % len,
+            ConnectionStrategy::LeastLoaded => {
+                // Start the scan at a rotating shard, so ties do not all land on the first
+                let start = self.next.fetch_add(1, Ordering::Relaxed);
+                (0..len)
+                    .map(|i| (start + i) % len)
+                    .min_by_key(|&i| self.shards[i].in_flight.load(Ordering::Relaxed))
+                    .expect("a client has at least one connection")
+            }
+        }
+    }
+
+    /// Picks the connection the next request goes out on: the `pinned` one, if the request
+    /// must use the connection its earlier requests did, or else one chosen by the strategy.
+    pub(crate) fn pick(&self, pinned: Option<usize>) -> ShardGuard<'_> {
+        let index = pinned.unwrap_or_else(|| self.choose());
+        let shard = &self.shards[index % self.shards.len()];
+        shard.in_flight.fetch_add(1, Ordering::Relaxed);
+        ShardGuard { shard }
+    }
+}
+
+impl Deref for ShardGuard<'_> {
+    type Target = reqwest::Client;
+
+    fn deref(&self) -> &reqwest::Client {
+        &self.shard.http
+    }
+}
+
+impl Drop for ShardGuard<'_> {
+    fn drop(&mut self) {
+        self.shard.in_flight.fetch_sub(1, Ordering::Relaxed);
+    }
+}
@@ -0,0 +1,118 @@
+//! Graceful shutdown: refusing new sends and waiting for pending ones.
+
+use serde::{Deserialize, Serialize};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::Notify;
+
+/// What [`ApnsClient::shutdown`](crate::ApnsClient::shutdown) managed to deliver.
+///
+/// # Fields
+///
+/// * `flushed` - Sends and queued notifications that completed before the timeout.
+/// * `dropped` - Those still pending at the timeout. Queued notifications among them are
+///   discarded without being sent; sends already waiting for APNs may still complete.
+/// * `elapsed` - How long the shutdown took.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ShutdownReport {
+    pub flushed: usize,
+    pub dropped: usize,
+    pub elapsed: Duration,
+}
+
+/// Tracks the sends and queued notifications of a client, and whether it still accepts
+/// new ones.
+#[derive(Debug, Default)]
+pub(crate) struct Lifecycle {
+    pending: AtomicUsize,
+    closed: AtomicBool,
+    aborted: AtomicBool,
+    closing: Notify,
+    drained: Notify,
+}
+
+impl Lifecycle {
+    /// Counts a new send or queued notification until the returned guard is dropped.
+    ///
+    /// # Returns
+    ///
+    /// The guard, or `None` if the client is shutting down.
+    pub(crate) fn start(self: &Arc<Self>) -> Option<PendingGuard> {
+        // Count first, so a concurrent shutdown either sees this send or refuses it
+        self.pending.fetch_add(1, Ordering::SeqCst);
+        let guard = PendingGuard {
+            lifecycle: self.clone(),
+        };
+        if self.closed.load(Ordering::SeqCst) {
+            return None;
+        }
+        Some(guard)
+    }
+
+    /// Returns `true` once the client no longer accepts sends.
+    pub(crate) fn is_closed(&self) -> bool {
+        self.closed.load(Ordering::SeqCst)
+    }
+
+    /// Resolves once the client stops accepting sends.
+    pub(crate) async fn closed(&self) {
+        let notified = self.closing.notified();
+        tokio::pin!(notified);
+        notified.as_mut().enable();
+        if !self.is_closed() {
+            notified.await;
+        }
+    }
+
+    /// Returns `true` once the shutdown timed out, so queued notifications are discarded.
+    pub(crate) fn is_aborted(&self) -> bool {
+        self.aborted.load(Ordering::SeqCst)
+    }
+
+    /// Stops accepting sends, waits up to `timeout` for the pending ones, and discards
+    /// whatever is still queued after that.
+    pub(crate) async fn shutdown(&self, timeout: Duration) -> ShutdownReport {
+        let started = tokio::time::Instant::now();
+        self.closed.store(true, Ordering::SeqCst);
+        self.closing.notify_waiters();
+        let pending = self.pending.load(Ordering::SeqCst);
+
+        if tokio::time::timeout(timeout, self.drained()).await.is_err() {
+            self.aborted.store(true, Ordering::SeqCst);
+        }
+        let dropped = self.pending.load(Ordering::SeqCst).min(pending);
+        ShutdownReport {
+            flushed: pending - dropped,
+            dropped,
+            elapsed: started.elapsed(),
+        }
+    }
+
+    /// Resolves once nothing is pending.
+    async fn drained(&self) {
+        loop {
+            let notified = self.drained.notified();
+            tokio::pin!(notified);
+            notified.as_mut().enable();
+            if self.pending.load(Ordering::SeqCst) == 0 {
+                return;
+            }
+            notified.await;
+        }
+    }
+}
+
+/// Keeps a send or queued notification counted as pending while it is alive.
+#[derive(Debug)]
+pub(crate) struct PendingGuard {
+    lifecycle: Arc<Lifecycle>,
+}
+
+impl Drop for PendingGuard {
+    fn drop(&mut self) {
+        if self.lifecycle.pending.fetch_sub(1, Ordering::SeqCst) == 1 {
+            self.lifecycle.drained.notify_waiters();
+        }
+    }
+}
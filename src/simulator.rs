@@ -0,0 +1,129 @@
+//! Delivering notifications to the iOS Simulator instead of APNs, for local development.
+
+use crate::error::{ApnsError, TransportError};
+use crate::topic::bundle_id;
+use serde_json::{Map, Value};
+use std::path::{Path, PathBuf};
+use tokio::process::Command;
+
+/// The key telling the Simulator which app an `.apns` file is for.
+const TARGET_BUNDLE_KEY: &str = "Simulator Target Bundle";
+
+/// Writes every notification a client sends as an `.apns` file the iOS Simulator accepts,
+/// instead of sending it to APNs, and optionally pushes it to a booted Simulator with
+/// `xcrun simctl push`.
+///
+/// Register it with [`ApnsClientBuilder::simulator`](crate::ApnsClientBuilder::simulator).
+/// Requests are prepared exactly as for APNs, so the code under development runs the same
+/// path as in production, and are answered as [dry runs](crate::SendOutcome): `200 OK`,
+/// with the request that was prepared. Each file is named after the notification's
+/// `apns-id`, and holds its payload with `"Simulator Target Bundle"` set to the bundle ID
+/// of its topic. The files can also be dragged onto a Simulator window.
+///
+/// # Example
+///
+/// ```rust,no_run
+/// use apnrs::{ApnsClient, Simulator};
+///
+/// # fn run() -> Result<(), apnrs::ApnsError> {
+/// let client = ApnsClient::builder()
+///     .auth_key_path("path/to/key")
+///     .team_id("TEAM_ID")
+///     .key_id("KEY_ID")
+///     .simulator(Simulator::new("target/pushes").push_to("booted"))
+///     .build()?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone)]
+pub struct Simulator {
+    directory: PathBuf,
+    device: Option<String>,
+}
+
+impl Simulator {
+    /// Writes `.apns` files to `directory`, creating it if needed, without pushing them.
+    pub fn new(directory: impl Into<PathBuf>) -> Self {
+        Simulator {
+            directory: directory.into(),
+            device: None,
+        }
+    }
+
+    /// Also pushes each file to the Simulator `device`, a UDID or `booted`, with
+    /// `xcrun simctl push`, which requires macOS with Xcode installed.
+    ///
+    /// A push `simctl` rejects fails the send with [`ApnsError::Http`].
+    pub fn push_to(mut self, device: impl Into<String>) -> Self {
+        self.device = Some(device.into());
+        self
+    }
+
+    /// Returns the contents of the `.apns` file for a notification with `payload` sent to
+    /// `topic`.
+    ///
+    /// # Returns
+    ///
+    /// The payload with `"Simulator Target Bundle"` added, or an `ApnsError` if `payload` is
+    /// not a JSON object.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use apnrs::Simulator;
+    ///
+    /// let file = Simulator::apns_file("com.example.app.voip", br#"{"aps":{"alert":"Hi"}}"#)?;
+    /// let file: serde_json::Value = serde_json::from_slice(&file)?;
+    /// assert_eq!(file["Simulator Target Bundle"], "com.example.app");
+    /// assert_eq!(file["aps"]["alert"], "Hi");
+    /// # Ok::<(), apnrs::ApnsError>(())
+    /// ```
+    pub fn apns_file(topic: &str, payload: &[u8]) -> Result<Vec<u8>, ApnsError> {
+        let mut file: Map<String, Value> = serde_json::from_slice(payload)?;
+        file.insert(
+            TARGET_BUNDLE_KEY.to_string(),
+            Value::String(bundle_id(topic).to_string()),
+        );
+        Ok(serde_json::to_vec_pretty(&file)?)
+    }
+
+    /// Returns the path the `.apns` file of the notification with `apns_id` is written to.
+    pub fn path_for(&self, apns_id: &str) -> PathBuf {
+        self.directory.join(format!("{}.apns", apns_id))
+    }
+
+    /// Writes the `.apns` file of a notification, and pushes it if a device is set.
+    pub(crate) async fn deliver(
+        &self,
+        topic: &str,
+        apns_id: &str,
+        payload: &[u8],
+    ) -> Result<(), ApnsError> {
+        let file = Self::apns_file(topic, payload)?;
+        let path = self.path_for(apns_id);
+        tokio::fs::create_dir_all(&self.directory).await?;
+        tokio::fs::write(&path, file).await?;
+        match &self.device {
+            Some(device) => simctl_push(device, bundle_id(topic), &path).await,
+            None => Ok(()),
+        }
+    }
+}
+
+/// Runs `xcrun simctl push` for the file at `path`.
+async fn simctl_push(device: &str, bundle_id: &str, path: &Path) -> Result<(), ApnsError> {
+    let output = Command::new("xcrun")
+        .args(["simctl", "push", device, bundle_id])
+        .arg(path)
+        .output()
+        .await?;
+    if output.status.success() {
+        return Ok(());
+    }
+    Err(ApnsError::Http(TransportError::new(format!(
+        "xcrun simctl push to {} failed ({}): {}",
+        device,
+        output.status,
+        String::from_utf8_lossy(&output.stderr).trim()
+    ))))
+}
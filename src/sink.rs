@@ -0,0 +1,147 @@
+//! [`futures::Sink`](https://docs.rs/futures) integration, enabled with the `sink` feature.
+
+use crate::sender::{lane_for, report_enqueued, Intake, Job, Queue, Screened};
+use crate::{ApnsError, ApnsSender, PushRequest};
+use futures_sink::Sink;
+use std::future::Future;
+use std::pin::Pin;
+use std::task::{ready, Context, Poll};
+use tokio_util::sync::PollSender;
+
+/// Feeds notifications into the queue of an [`ApnsSender`], so a stream of requests can be
+/// forwarded to APNs with backpressure.
+///
+/// A request goes through the same steps as with [`enqueue`](ApnsSender::enqueue): it is
+/// refused once the sender's cancellation token is cancelled or its app used up its daily
+/// quota, held back for the coalescing window or the recipient's quiet hours, paced to its
+/// app's rate limit, journaled, and reported as enqueued. `poll_ready` is pending while
+/// the request is paced or its lane of the queue is full, so `forward` and `send_all` stop
+/// pulling from upstream until workers catch up, and one app cannot crowd out the others
+/// beyond its rate. Requests to the same device token keep their order when the sender was
+/// built with `ordered_per_token`. No [`Receipt`](crate::Receipt) is returned, so outcomes
+/// are only observed through stats, events, and unregistered hooks.
+///
+/// Closing the sink stops it from accepting requests; the sender and its other sinks keep
+/// running.
+///
+/// # Example
+///
+/// ```rust,no_run
+/// use apnrs::{ApnsSender, PushRequest};
+/// use futures_util::stream::{self, StreamExt};
+/// use futures_util::SinkExt;
+///
+/// # async fn run(sender: ApnsSender, requests: Vec<PushRequest>) -> Result<(), apnrs::ApnsError> {
+/// stream::iter(requests).map(Ok).forward(sender.sink()).await?;
+///
+/// // Or keep the sink open to feed it from several streams:
+/// let mut sink = sender.sink();
+/// # let more: Vec<PushRequest> = Vec::new();
+/// sink.send_all(&mut stream::iter(more).map(Ok)).await?;
+/// # Ok(())
+/// # }
+/// ```
+pub struct PushSink {
+    intake: Intake,
+    /// One sender per class for each lane, as in [`Lane`](crate::sender::Lane).
+    lanes: Vec<Vec<PollSender<Job>>>,
+    /// A request accepted by `start_send` that is not in the queue yet.
+    pending: Option<Pending>,
+}
+
+/// Where a request accepted by a [`PushSink`] is on its way to the queue.
+enum Pending {
+    /// Waiting for its app's rate limit.
+    Paced(Pin<Box<dyn Future<Output = PushRequest> + Send>>),
+    /// Waiting for room in its lane.
+    Queued(usize, Box<Job>),
+}
+
+impl ApnsSender {
+    /// Returns a [`Sink`] feeding notifications into this sender's queue.
+    pub fn sink(&self) -> PushSink {
+        let lanes = match &self.queue {
+            Queue::Shared(lane) => vec![lane],
+            Queue::Lanes(lanes) => lanes.iter().collect(),
+        };
+        let lanes = lanes
+            .into_iter()
+            .map(|lane| lane.0.iter().cloned().map(PollSender::new).collect())
+            .collect();
+        PushSink {
+            intake: self.intake.clone(),
+            lanes,
+            pending: None,
+        }
+    }
+}
+
+impl PushSink {
+    /// Moves the pending request into its lane once its app's pace allows and the lane has
+    /// room.
+    fn poll_pending(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), ApnsError>> {
+        if let Some(Pending::Paced(pace)) = &mut self.pending {
+            let request = ready!(pace.as_mut().poll(cx));
+            self.pending = None;
+            let (job, _) = self.intake.job(request)?;
+            let lane = lane_for(&job.request.device_token, self.lanes.len());
+            self.pending = Some(Pending::Queued(lane, Box::new(job)));
+        }
+        let Some(Pending::Queued(lane, job)) = &self.pending else {
+            return Poll::Ready(Ok(()));
+        };
+        let tx = &mut self.lanes[*lane][job.request.class.rank()];
+        let reserved = ready!(tx.poll_reserve(cx));
+        let Some(Pending::Queued(_, mut job)) = self.pending.take() else {
+            unreachable!("pending request");
+        };
+        if reserved.is_err() {
+            job.settle();
+            self.intake.refund(&job.request);
+            return Poll::Ready(Err(ApnsError::QueueClosed));
+        }
+
+        let client = &self.intake.client;
+        report_enqueued(client, &job.request);
+        if let Err(e) = tx.send_item(*job) {
+            client.stats_recorder().dequeued();
+            if let Some(mut job) = e.into_inner() {
+                job.settle();
+                self.intake.refund(&job.request);
+            }
+            return Poll::Ready(Err(ApnsError::QueueClosed));
+        }
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl Sink<PushRequest> for PushSink {
+    type Error = ApnsError;
+
+    fn poll_ready(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), ApnsError>> {
+        self.get_mut().poll_pending(cx)
+    }
+
+    fn start_send(self: Pin<&mut Self>, request: PushRequest) -> Result<(), ApnsError> {
+        let this = self.get_mut();
+        // Nobody waits for the outcome of a held request; the scheduler ignores the
+        // dropped receipt
+        if let Screened::Due(request) = this.intake.screen(request)? {
+            this.pending = Some(Pending::Paced(Box::pin(this.intake.pace(*request))));
+        }
+        Ok(())
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), ApnsError>> {
+        self.get_mut().poll_pending(cx)
+    }
+
+    fn poll_close(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), ApnsError>> {
+        let this = self.get_mut();
+        ready!(this.poll_pending(cx))?;
+        for tx in this.lanes.iter_mut().flatten() {
+            tx.close();
+        }
+        Poll::Ready(Ok(()))
+    }
+}
@@ -0,0 +1,172 @@
+//! The sound played for an alert.
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::hash::{Hash, Hasher};
+
+/// The sound a notification plays, sent as the `sound` key.
+///
+/// # Variants
+///
+/// * `Default` - The system's default notification sound, sent as `"default"`.
+/// * `Named` - The name of a sound file in the app bundle or its `Library/Sounds` folder.
+/// * `Critical` - A critical alert sound, played even when the device is muted or in Do Not
+///   Disturb, sent as a dictionary with `critical` set to 1. `name` is `"default"` or a
+///   sound file, and `volume` a level from 0 to 1. Critical alerts need an entitlement from
+///   Apple.
+/// * `None` - Plays no sound; the `sound` key is omitted.
+///
+/// # Example
+///
+/// ```rust
+/// use apnrs::{ApnsPayload, Aps, Badge, Sound};
+///
+/// let payload = ApnsPayload {
+///     aps: Aps {
+///         alert: "Your order has shipped".to_string(),
+///         content_available: None,
+///         badge: Badge::Unchanged,
+///         sound: Sound::Named("chime.caf".to_string()),
+///         category: None,
+///         thread_id: None,
+///         summary_arg: None,
+///         summary_arg_count: None,
+///         launch_image: None,
+///         alert_fields: Default::default(),
+///         extra_fields: Default::default(),
+///     },
+///     custom_key: None,
+///     custom_fields: Default::default(),
+/// };
+///
+/// let json = serde_json::to_value(&payload).unwrap();
+/// assert_eq!(json["aps"]["sound"], "chime.caf");
+///
+/// let alarm = Sound::Critical { name: "alarm.caf".to_string(), volume: Some(0.8) };
+/// assert_eq!(
+///     serde_json::to_value(&alarm).unwrap(),
+///     serde_json::json!({ "critical": 1, "name": "alarm.caf", "volume": 0.8 })
+/// );
+/// ```
+#[derive(Debug, Clone, PartialEq, Default)]
+pub enum Sound {
+    Default,
+    Named(String),
+    Critical {
+        name: String,
+        volume: Option<f64>,
+    },
+    #[default]
+    None,
+}
+
+impl Eq for Sound {}
+
+impl Hash for Sound {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        std::mem::discriminant(self).hash(state);
+        match self {
+            Sound::Named(name) => name.hash(state),
+            Sound::Critical { name, volume } => {
+                name.hash(state);
+                volume.map(f64::to_bits).hash(state);
+            }
+            Sound::Default | Sound::None => {}
+        }
+    }
+}
+
+/// The dictionary form of the `sound` key.
+#[derive(Serialize, Deserialize)]
+struct SoundDictionary {
+    #[serde(default)]
+    critical: u8,
+    #[serde(default = "default_name")]
+    name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    volume: Option<f64>,
+}
+
+/// The sound a sound dictionary without a `name` plays.
+fn default_name() -> String {
+    "default".to_string()
+}
+
+/// The forms the `sound` key comes in.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum WireSound {
+    Name(Option<String>),
+    Dictionary(SoundDictionary),
+}
+
+impl Sound {
+    /// Returns `true` if no sound is played.
+    pub fn is_none(&self) -> bool {
+        *self == Sound::None
+    }
+
+    /// Returns the name of the sound played, or `None` if no sound is played.
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Sound::Default => Some("default"),
+            Sound::Named(name) | Sound::Critical { name, .. } => Some(name),
+            Sound::None => None,
+        }
+    }
+}
+
+impl From<&str> for Sound {
+    /// Plays the default sound for `"default"`, or the named sound otherwise.
+    fn from(name: &str) -> Self {
+        match name {
+            "default" => Sound::Default,
+            name => Sound::Named(name.to_string()),
+        }
+    }
+}
+
+impl From<String> for Sound {
+    /// Plays the default sound for `"default"`, or the named sound otherwise.
+    fn from(name: String) -> Self {
+        match name.as_str() {
+            "default" => Sound::Default,
+            _ => Sound::Named(name),
+        }
+    }
+}
+
+impl From<Option<String>> for Sound {
+    /// Plays no sound for `None`.
+    fn from(name: Option<String>) -> Self {
+        name.map_or(Sound::None, Sound::from)
+    }
+}
+
+impl Serialize for Sound {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self {
+            Sound::Critical { name, volume } => SoundDictionary {
+                critical: 1,
+                name: name.clone(),
+                volume: *volume,
+            }
+            .serialize(serializer),
+            _ => self.as_str().serialize(serializer),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for Sound {
+    /// Reads the sound name, or a sound dictionary; one without `critical` set is read as
+    /// its name alone.
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        Ok(match WireSound::deserialize(deserializer)? {
+            WireSound::Name(name) => Sound::from(name),
+            WireSound::Dictionary(sound) if sound.critical != 0 => Sound::Critical {
+                name: sound.name,
+                volume: sound.volume,
+            },
+            WireSound::Dictionary(sound) => Sound::from(sound.name),
+        })
+    }
+}
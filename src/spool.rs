@@ -0,0 +1,230 @@
+//! Holding a sender's notifications while APNs cannot be reached, and replaying them in
+//! order once it can.
+
+use crate::sender::{Job, Worker};
+use crate::{ApnsError, PushRequest, SendOutcome};
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime};
+use tokio::sync::Notify;
+
+/// The notifications a sender holds back during an APNs outage, in the order they were
+/// taken from the queue.
+///
+/// Workers stop taking notifications from the queue while the outage lasts, so the spool
+/// only holds those they had already taken, at most one per worker, and the rest wait in
+/// the bounded queue, holding up `enqueue` once it is full. Spooled jobs keep their journal
+/// entries, so with a journal they are resumed after a restart like any other queued
+/// notification.
+pub(crate) struct Spool {
+    /// How often APNs is probed while it cannot be reached.
+    probe_interval: Duration,
+    state: Mutex<State>,
+    /// Wakes the workers waiting for the outage to end.
+    ended: Notify,
+}
+
+struct State {
+    jobs: VecDeque<Job>,
+    /// Set from the first send APNs could not be reached for until the spool is replayed.
+    outage: bool,
+}
+
+impl Spool {
+    /// Creates an empty spool probing APNs every `probe_interval` during an outage.
+    pub(crate) fn new(probe_interval: Duration) -> Self {
+        Spool {
+            probe_interval,
+            state: Mutex::new(State {
+                jobs: VecDeque::new(),
+                outage: false,
+            }),
+            ended: Notify::new(),
+        }
+    }
+
+    /// Waits until no outage is under way, so workers leave the queue alone meanwhile.
+    pub(crate) async fn reachable(&self) {
+        loop {
+            let ended = self.ended.notified();
+            tokio::pin!(ended);
+            // Registered before checking, so an outage ending in between is not missed
+            ended.as_mut().enable();
+            if !self.lock().outage {
+                return;
+            }
+            ended.await;
+        }
+    }
+
+    /// Returns how many notifications are waiting for APNs to be reachable again.
+    pub(crate) fn len(&self) -> usize {
+        self.lock().jobs.len()
+    }
+
+    /// Spools `job` without sending it if an outage is under way, so it is not sent ahead
+    /// of those already waiting.
+    ///
+    /// # Returns
+    ///
+    /// The job back if APNs is reachable, for the worker to send it as usual.
+    pub(crate) fn divert(&self, job: Job) -> Option<Job> {
+        let mut state = self.lock();
+        if !state.outage {
+            return Some(job);
+        }
+        state.jobs.push_back(job);
+        None
+    }
+
+    /// Spools `job`, which failed because APNs could not be reached.
+    ///
+    /// # Returns
+    ///
+    /// `true` if this starts an outage, so the caller starts replaying the spool.
+    pub(crate) fn spill(&self, job: Job) -> bool {
+        let mut state = self.lock();
+        state.jobs.push_back(job);
+        !std::mem::replace(&mut state.outage, true)
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, State> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Takes the oldest spooled job, or ends the outage once none are left.
+    fn next(&self) -> Option<Job> {
+        let mut state = self.lock();
+        let job = state.jobs.pop_front();
+        if job.is_none() {
+            state.outage = false;
+            self.ended.notify_waiters();
+        }
+        job
+    }
+
+    /// Puts a job that could not be replayed back at the head of the spool.
+    fn unnext(&self, job: Job) {
+        self.lock().jobs.push_front(job);
+    }
+
+    /// Fails every spooled job whose deadline or expiration has passed.
+    fn drop_stale(&self) {
+        let mut dropped = Vec::new();
+        {
+            let mut state = self.lock();
+            let mut live = VecDeque::new();
+            for job in state.jobs.drain(..) {
+                match stale(&job.request) {
+                    Some(error) => dropped.push((job, error)),
+                    None => live.push_back(job),
+                }
+            }
+            state.jobs = live;
+        }
+        for (job, error) in dropped {
+            finish(job, Err(error));
+        }
+    }
+
+    /// Fails every spooled job with `error`, removing them from the journal if `settle`
+    /// is set, and ends the outage.
+    fn fail_all(&self, error: fn() -> ApnsError, settle: bool) {
+        let jobs = {
+            let mut state = self.lock();
+            state.outage = false;
+            std::mem::take(&mut state.jobs)
+        };
+        self.ended.notify_waiters();
+        for mut job in jobs {
+            if settle {
+                job.settle();
+            }
+            let _ = job.reply.send(Err(error()));
+        }
+    }
+}
+
+/// Probes APNs every probe interval until it can be reached, then sends the spooled
+/// notifications one at a time, oldest first, until the spool is empty.
+///
+/// Notifications whose deadline or `apns-expiration` passes while they wait are failed
+/// instead of sent. A shutdown that times out leaves them in the journal, and a cancelled
+/// sender fails them with `ApnsError::Cancelled`.
+pub(crate) async fn replay(worker: Arc<Worker>, spool: Arc<Spool>) {
+    #[cfg(feature = "tracing")]
+    tracing::warn!("APNs cannot be reached; spooling notifications until it can");
+    #[cfg(feature = "logging")]
+    log::warn!("APNs cannot be reached; spooling notifications until it can");
+    loop {
+        tokio::select! {
+            biased;
+            _ = worker.cancel.cancelled() => {
+                spool.fail_all(|| ApnsError::Cancelled, true);
+                return;
+            }
+            _ = tokio::time::sleep(spool.probe_interval) => {}
+        }
+        if worker.client.lifecycle().is_aborted() {
+            spool.fail_all(|| ApnsError::ClientShutDown, false);
+            return;
+        }
+        spool.drop_stale();
+        if worker.client.connect().await.is_err() {
+            continue;
+        }
+
+        #[cfg(feature = "tracing")]
+        tracing::info!(
+            count = spool.len(),
+            "APNs is reachable again; replaying spooled notifications"
+        );
+        #[cfg(feature = "logging")]
+        log::info!(
+            "APNs is reachable again; replaying {} spooled notifications",
+            spool.len()
+        );
+        loop {
+            // Taking the last one ends the outage, and with it this task
+            let Some(job) = spool.next() else { return };
+            if worker.client.lifecycle().is_aborted() {
+                spool.unnext(job);
+                spool.fail_all(|| ApnsError::ClientShutDown, false);
+                return;
+            }
+            if let Some(error) = stale(&job.request) {
+                finish(job, Err(error));
+                continue;
+            }
+            let result = tokio::select! {
+                biased;
+                _ = worker.cancel.cancelled() => Err(ApnsError::Cancelled),
+                result = worker.deliver(&job.request) => result,
+            };
+            // Lost again; wait for the next probe to succeed
+            if let Err(ApnsError::Http(_)) = result {
+                spool.unnext(job);
+                break;
+            }
+            finish(job, result);
+        }
+    }
+}
+
+/// Returns the error a spooled notification fails with if its deadline or
+/// `apns-expiration` has passed, or `None` if it may still be sent.
+fn stale(request: &PushRequest) -> Option<ApnsError> {
+    if let Some(left) = request.time_left().filter(Duration::is_zero) {
+        return Some(ApnsError::DeadlineExceeded(left));
+    }
+    match request.options.expiration {
+        Some(expiration) if expiration <= SystemTime::now() => Some(ApnsError::Expired),
+        _ => None,
+    }
+}
+
+/// Completes a spooled job with `result`.
+fn finish(mut job: Job, result: Result<SendOutcome, ApnsError>) {
+    job.settle();
+    let _ = job.reply.send(result);
+}
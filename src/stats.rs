@@ -0,0 +1,126 @@
+//! Counters kept by the client independently of any external metrics system.
+
+#[cfg(feature = "tokio")]
+use crate::ErrorReason;
+use std::collections::HashMap;
+#[cfg(feature = "tokio")]
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+#[cfg(feature = "tokio")]
+use std::sync::Mutex;
+use std::time::Duration;
+
+/// A point-in-time snapshot of a client's activity.
+///
+/// # Fields
+///
+/// * `sent` - Requests made to APNs, including those that failed before APNs answered.
+/// * `succeeded` - Requests APNs accepted.
+/// * `failed` - Requests APNs rejected or that could not be delivered.
+/// * `failed_by_reason` - Failures keyed by APNs reason, by HTTP status if APNs gave no
+///   reason, or by `transport` for connection errors.
+/// * `average_latency` - The mean time between dispatching a request and its outcome.
+/// * `queue_depth` - Notifications waiting in the queues of senders built on this client.
+/// * `quota_exceeded` - Notifications senders refused because their app had used up its
+///   daily [quota](crate::Quota), keyed by bundle ID.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ClientStats {
+    pub sent: u64,
+    pub succeeded: u64,
+    pub failed: u64,
+    pub failed_by_reason: HashMap<String, u64>,
+    pub average_latency: Duration,
+    pub queue_depth: usize,
+    pub quota_exceeded: HashMap<String, u64>,
+}
+
+/// The live counters behind [`ClientStats`].
+#[cfg(feature = "tokio")]
+#[derive(Default)]
+pub(crate) struct StatsRecorder {
+    sent: AtomicU64,
+    succeeded: AtomicU64,
+    failed_by_reason: Mutex<HashMap<String, u64>>,
+    latency_micros: AtomicU64,
+    queue_depth: AtomicUsize,
+    quota_exceeded: Mutex<HashMap<String, u64>>,
+}
+
+#[cfg(feature = "tokio")]
+impl StatsRecorder {
+    /// Records a response from APNs.
+    pub(crate) fn response_received(
+        &self,
+        status: u16,
+        reason: Option<&ErrorReason>,
+        latency: Duration,
+    ) {
+        self.record_latency(latency);
+        if (200..300).contains(&status) {
+            self.succeeded.fetch_add(1, Ordering::Relaxed);
+        } else {
+            let key = reason
+                .map(|r| r.as_str().to_string())
+                .unwrap_or_else(|| status.to_string());
+            self.record_failure(key);
+        }
+    }
+
+    /// Records a request that failed before APNs answered.
+    pub(crate) fn transport_failed(&self, latency: Duration) {
+        self.record_latency(latency);
+        self.record_failure("transport".to_string());
+    }
+
+    /// Records a notification entering a sender's queue.
+    pub(crate) fn enqueued(&self) {
+        self.queue_depth.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Records a notification leaving a sender's queue.
+    pub(crate) fn dequeued(&self) {
+        self.queue_depth.fetch_sub(1, Ordering::Relaxed);
+    }
+
+    /// Records a notification refused because the app with `bundle_id` used up its quota.
+    pub(crate) fn quota_exceeded(&self, bundle_id: &str) {
+        *self
+            .quota_exceeded
+            .lock()
+            .unwrap()
+            .entry(bundle_id.to_string())
+            .or_insert(0) += 1;
+    }
+
+    /// Returns a snapshot of the current counters.
+    pub(crate) fn snapshot(&self) -> ClientStats {
+        let sent = self.sent.load(Ordering::Relaxed);
+        let succeeded = self.succeeded.load(Ordering::Relaxed);
+        let failed_by_reason = self.failed_by_reason.lock().unwrap().clone();
+        let latency_micros = self.latency_micros.load(Ordering::Relaxed);
+
+        ClientStats {
+            sent,
+            succeeded,
+            failed: failed_by_reason.values().sum(),
+            failed_by_reason,
+            average_latency: Duration::from_micros(latency_micros.checked_div(sent).unwrap_or(0)),
+            queue_depth: self.queue_depth.load(Ordering::Relaxed),
+            quota_exceeded: self.quota_exceeded.lock().unwrap().clone(),
+        }
+    }
+
+    fn record_latency(&self, latency: Duration) {
+        self.sent.fetch_add(1, Ordering::Relaxed);
+        self.latency_micros
+            .fetch_add(latency.as_micros() as u64, Ordering::Relaxed);
+    }
+
+    fn record_failure(&self, key: String) {
+        *self
+            .failed_by_reason
+            .lock()
+            .unwrap()
+            .entry(key)
+            .or_insert(0) += 1;
+    }
+}
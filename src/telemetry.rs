@@ -0,0 +1,267 @@
+//! Metrics emitted through the [`metrics`](https://docs.rs/metrics) facade, enabled with the
+//! `metrics` feature.
+//!
+//! Without the feature every function here is a no-op, so call sites need no `cfg`.
+//!
+//! # Metrics
+//!
+//! * `apns_notifications_sent_total` - Requests answered by APNs, labelled by `status`.
+//! * `apns_notifications_failed_total` - Requests that were rejected or could not be delivered,
+//!   labelled by `reason` (the APNs reason, the HTTP status if none was given, or `transport`
+//!   for connection errors).
+//! * `apns_request_duration_seconds` - Histogram of request latency.
+//! * `apns_token_refreshes_total` - Provider tokens signed.
+//! * `apns_connection_errors_total` - Requests that failed at the connection level.
+//!
+//! The first three may also be labelled by `topic`, `push_type`, and `tenant`, as configured
+//! with [`MetricLabels`].
+
+use crate::push_type::PushType;
+#[cfg(feature = "tokio")]
+use crate::ErrorReason;
+use std::collections::{HashMap, HashSet};
+use std::sync::Mutex;
+#[cfg(feature = "tokio")]
+use std::time::Duration;
+
+/// The value a label takes once it has reached its cardinality cap.
+const OVERFLOW_VALUE: &str = "other";
+
+/// A label that can be attached to request metrics.
+///
+/// # Variants
+///
+/// * `Status` - The HTTP status APNs answered with, on `apns_notifications_sent_total`.
+/// * `Reason` - Why a request failed, on `apns_notifications_failed_total`.
+/// * `Topic` - The `apns-topic` of the request.
+/// * `PushType` - The `apns-push-type` of the request, or `none` if it had none.
+/// * `Tenant` - The fixed tenant name set with [`MetricLabels::tenant`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum MetricLabel {
+    Status,
+    Reason,
+    Topic,
+    PushType,
+    Tenant,
+}
+
+impl MetricLabel {
+    /// Returns the label's key as it appears on emitted metrics.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            MetricLabel::Status => "status",
+            MetricLabel::Reason => "reason",
+            MetricLabel::Topic => "topic",
+            MetricLabel::PushType => "push_type",
+            MetricLabel::Tenant => "tenant",
+        }
+    }
+}
+
+/// Which labels are attached to request metrics, and how many distinct values each may take.
+///
+/// By default only `status` and `reason` are attached, with no cap. Once a capped label has
+/// seen its maximum number of distinct values, further values are reported as `other`, so
+/// an unbounded set of topics cannot create an unbounded number of series. Device tokens are
+/// never used as labels.
+///
+/// # Example
+///
+/// ```rust
+/// use apnrs::{MetricLabel, MetricLabels};
+///
+/// let labels = MetricLabels::new()
+///     .with(MetricLabel::Topic)
+///     .max_values(MetricLabel::Topic, 20)
+///     .tenant("acme");
+///
+/// assert!(labels.is_enabled(MetricLabel::Topic));
+/// assert!(labels.is_enabled(MetricLabel::Tenant));
+/// ```
+#[derive(Debug, Clone)]
+pub struct MetricLabels {
+    enabled: HashSet<MetricLabel>,
+    tenant: Option<String>,
+    max_values: HashMap<MetricLabel, usize>,
+}
+
+impl Default for MetricLabels {
+    fn default() -> Self {
+        MetricLabels {
+            enabled: [MetricLabel::Status, MetricLabel::Reason]
+                .into_iter()
+                .collect(),
+            tenant: None,
+            max_values: HashMap::new(),
+        }
+    }
+}
+
+impl MetricLabels {
+    /// Creates the default configuration, attaching `status` and `reason`.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates a configuration attaching no labels at all.
+    pub fn none() -> Self {
+        MetricLabels {
+            enabled: HashSet::new(),
+            ..Self::default()
+        }
+    }
+
+    /// Attaches `label` to request metrics.
+    ///
+    /// `Tenant` is only attached once a tenant name is set with [`tenant`](MetricLabels::tenant).
+    pub fn with(mut self, label: MetricLabel) -> Self {
+        self.enabled.insert(label);
+        self
+    }
+
+    /// Stops attaching `label` to request metrics.
+    pub fn without(mut self, label: MetricLabel) -> Self {
+        self.enabled.remove(&label);
+        self
+    }
+
+    /// Attaches a `tenant` label with the given value to request metrics.
+    pub fn tenant(mut self, tenant: impl Into<String>) -> Self {
+        self.tenant = Some(tenant.into());
+        self.with(MetricLabel::Tenant)
+    }
+
+    /// Caps how many distinct values `label` may take; later values are reported as `other`.
+    pub fn max_values(mut self, label: MetricLabel, max: usize) -> Self {
+        self.max_values.insert(label, max);
+        self
+    }
+
+    /// Returns `true` if `label` is attached to request metrics.
+    pub fn is_enabled(&self, label: MetricLabel) -> bool {
+        match label {
+            MetricLabel::Tenant => self.tenant.is_some() && self.enabled.contains(&label),
+            label => self.enabled.contains(&label),
+        }
+    }
+}
+
+/// Applies a [`MetricLabels`] configuration, remembering the values seen for capped labels.
+pub(crate) struct Labeler {
+    config: MetricLabels,
+    seen: Mutex<HashMap<MetricLabel, HashSet<String>>>,
+}
+
+/// The request being measured.
+pub(crate) struct RequestLabels<'a> {
+    pub(crate) topic: &'a str,
+    pub(crate) push_type: Option<PushType>,
+}
+
+#[cfg_attr(not(feature = "metrics"), allow(dead_code))]
+impl Labeler {
+    pub(crate) fn new(config: MetricLabels) -> Self {
+        Labeler {
+            config,
+            seen: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Returns the enabled labels shared by every request metric.
+    fn request_labels(&self, request: &RequestLabels<'_>) -> Vec<(&'static str, String)> {
+        let mut labels = Vec::new();
+        self.push(&mut labels, MetricLabel::Topic, request.topic);
+        self.push(
+            &mut labels,
+            MetricLabel::PushType,
+            request.push_type.map(|t| t.as_str()).unwrap_or("none"),
+        );
+        if let Some(tenant) = &self.config.tenant {
+            self.push(&mut labels, MetricLabel::Tenant, tenant);
+        }
+        labels
+    }
+
+    /// Appends `label` with `value`, if it is enabled, applying its cardinality cap.
+    fn push(&self, labels: &mut Vec<(&'static str, String)>, label: MetricLabel, value: &str) {
+        if !self.config.is_enabled(label) {
+            return;
+        }
+        let value = match self.config.max_values.get(&label) {
+            None => value.to_string(),
+            Some(&max) => {
+                let mut seen = self.seen.lock().unwrap();
+                let values = seen.entry(label).or_default();
+                if values.contains(value) || values.len() < max {
+                    values.insert(value.to_string());
+                    value.to_string()
+                } else {
+                    OVERFLOW_VALUE.to_string()
+                }
+            }
+        };
+        labels.push((label.as_str(), value));
+    }
+}
+
+/// Records a response from APNs.
+#[cfg(feature = "tokio")]
+pub(crate) fn response_received(
+    labeler: &Labeler,
+    request: &RequestLabels<'_>,
+    status: u16,
+    reason: Option<&ErrorReason>,
+    latency: Duration,
+) {
+    #[cfg(feature = "metrics")]
+    {
+        let common = labeler.request_labels(request);
+        let status_label = status.to_string();
+
+        let mut labels = Vec::new();
+        labeler.push(&mut labels, MetricLabel::Status, &status_label);
+        labels.extend(common.iter().cloned());
+        metrics::counter!("apns_notifications_sent_total", &labels).increment(1);
+        metrics::histogram!("apns_request_duration_seconds", &common).record(latency.as_secs_f64());
+        if !(200..300).contains(&status) {
+            let reason = reason.map(|r| r.as_str()).unwrap_or(&status_label);
+            let mut labels = Vec::new();
+            labeler.push(&mut labels, MetricLabel::Reason, reason);
+            labels.extend(common);
+            metrics::counter!("apns_notifications_failed_total", &labels).increment(1);
+        }
+    }
+    #[cfg(not(feature = "metrics"))]
+    let _ = (labeler, request, status, reason, latency);
+}
+
+/// Records a request that failed before APNs answered.
+#[cfg(feature = "tokio")]
+pub(crate) fn transport_failed(
+    labeler: &Labeler,
+    request: &RequestLabels<'_>,
+    error: &reqwest::Error,
+    latency: Duration,
+) {
+    #[cfg(feature = "metrics")]
+    {
+        let common = labeler.request_labels(request);
+        metrics::histogram!("apns_request_duration_seconds", &common).record(latency.as_secs_f64());
+        let mut labels = Vec::new();
+        labeler.push(&mut labels, MetricLabel::Reason, "transport");
+        labels.extend(common);
+        metrics::counter!("apns_notifications_failed_total", &labels).increment(1);
+        if error.is_connect() || error.is_request() {
+            metrics::counter!("apns_connection_errors_total").increment(1);
+        }
+    }
+    #[cfg(not(feature = "metrics"))]
+    let _ = (labeler, request, error, latency);
+}
+
+/// Records a newly signed provider token.
+#[cfg(feature = "signing")]
+pub(crate) fn token_refreshed() {
+    #[cfg(feature = "metrics")]
+    metrics::counter!("apns_token_refreshes_total").increment(1);
+}
@@ -0,0 +1,253 @@
+//! Reusable notification templates with `{placeholder}` substitution.
+
+use crate::{ApnsPayload, Aps, Badge, Sound};
+use std::borrow::Borrow;
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+use std::hash::Hash;
+
+/// Describes why a template could not be parsed or rendered.
+///
+/// Positions are byte offsets into the alert text.
+///
+/// # Variants
+///
+/// * `UnclosedPlaceholder` - A `{` is never closed by a `}`.
+/// * `UnmatchedBrace` - A `}` closes no placeholder; write `}}` for a literal brace.
+/// * `EmptyPlaceholder` - A placeholder has no name, as in `{}`.
+/// * `MissingVariables` - Rendering was given no value for these placeholders, listed once
+///   each in the order they first appear.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TemplateError {
+    UnclosedPlaceholder { position: usize },
+    UnmatchedBrace { position: usize },
+    EmptyPlaceholder { position: usize },
+    MissingVariables { names: Vec<String> },
+}
+
+impl fmt::Display for TemplateError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TemplateError::UnclosedPlaceholder { position } => {
+                write!(f, "placeholder at position {} is never closed", position)
+            }
+            TemplateError::UnmatchedBrace { position } => write!(
+                f,
+                "unmatched `}}` at position {}; write `}}}}` for a literal brace",
+                position
+            ),
+            TemplateError::EmptyPlaceholder { position } => {
+                write!(f, "placeholder at position {} has no name", position)
+            }
+            TemplateError::MissingVariables { names } => {
+                write!(f, "no value for placeholders {}", names.join(", "))
+            }
+        }
+    }
+}
+
+impl Error for TemplateError {}
+
+/// One piece of a parsed alert.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Segment {
+    Text(String),
+    Placeholder(String),
+}
+
+/// A reusable notification whose alert text has `{name}` placeholders, rendered into a
+/// payload for each recipient.
+///
+/// The sound, category, thread ID, badge, and `content-available` flag are fixed by the
+/// template. Write `{{` and `}}` for literal braces; substituted values are inserted as they
+/// are, so braces in them need no escaping.
+///
+/// # Example
+///
+/// ```rust
+/// use apnrs::{Sound, Template, TemplateError};
+/// use std::collections::HashMap;
+///
+/// let template = Template::new("{username} liked your photo {{{photo}}}")
+///     .unwrap()
+///     .sound("default")
+///     .category("LIKE");
+/// assert_eq!(template.placeholders(), vec!["username", "photo"]);
+///
+/// let vars = HashMap::from([("username", "ana"), ("photo", "beach.jpg")]);
+/// let payload = template.render(&vars).unwrap();
+/// assert_eq!(payload.aps.alert, "ana liked your photo {beach.jpg}");
+/// assert_eq!(payload.aps.sound, Sound::Default);
+///
+/// let vars = HashMap::from([("username", "ana")]);
+/// assert_eq!(
+///     template.render(&vars).unwrap_err(),
+///     TemplateError::MissingVariables { names: vec!["photo".to_string()] }
+/// );
+/// ```
+#[derive(Debug, Clone)]
+pub struct Template {
+    alert: Vec<Segment>,
+    sound: Sound,
+    category: Option<String>,
+    thread_id: Option<String>,
+    badge: Badge,
+    content_available: bool,
+    custom_key: Option<String>,
+}
+
+impl Template {
+    /// Parses `alert` into a template with no sound, category, or thread ID, leaving the
+    /// badge unchanged.
+    ///
+    /// # Returns
+    ///
+    /// The template, or a `TemplateError` if a brace in `alert` is unbalanced or a
+    /// placeholder has no name.
+    pub fn new(alert: &str) -> Result<Self, TemplateError> {
+        Ok(Template {
+            alert: parse(alert)?,
+            sound: Sound::None,
+            category: None,
+            thread_id: None,
+            badge: Badge::Unchanged,
+            content_available: false,
+            custom_key: None,
+        })
+    }
+
+    /// Sets the sound played by every rendered notification.
+    pub fn sound(mut self, sound: impl Into<Sound>) -> Self {
+        self.sound = sound.into();
+        self
+    }
+
+    /// Sets the notification category.
+    pub fn category(mut self, category: impl Into<String>) -> Self {
+        self.category = Some(category.into());
+        self
+    }
+
+    /// Sets the thread identifier notifications are grouped by.
+    pub fn thread_id(mut self, thread_id: impl Into<String>) -> Self {
+        self.thread_id = Some(thread_id.into());
+        self
+    }
+
+    /// Sets the badge of the app icon.
+    pub fn badge(mut self, badge: impl Into<Badge>) -> Self {
+        self.badge = badge.into();
+        self
+    }
+
+    /// Marks rendered notifications as background updates.
+    pub fn content_available(mut self, content_available: bool) -> Self {
+        self.content_available = content_available;
+        self
+    }
+
+    /// Sets the payload's `custom_key`.
+    pub fn custom_key(mut self, custom_key: impl Into<String>) -> Self {
+        self.custom_key = Some(custom_key.into());
+        self
+    }
+
+    /// Returns the names of the alert's placeholders, once each in the order they first
+    /// appear.
+    pub fn placeholders(&self) -> Vec<&str> {
+        let mut names: Vec<&str> = Vec::new();
+        for segment in &self.alert {
+            if let Segment::Placeholder(name) = segment {
+                if !names.contains(&name.as_str()) {
+                    names.push(name);
+                }
+            }
+        }
+        names
+    }
+
+    /// Renders a payload, replacing each placeholder with its value in `vars`. Variables
+    /// no placeholder uses are ignored.
+    ///
+    /// # Returns
+    ///
+    /// The payload, or `TemplateError::MissingVariables` listing every placeholder `vars`
+    /// has no value for.
+    pub fn render<K, V>(&self, vars: &HashMap<K, V>) -> Result<ApnsPayload, TemplateError>
+    where
+        K: Borrow<str> + Hash + Eq,
+        V: AsRef<str>,
+    {
+        let missing: Vec<String> = self
+            .placeholders()
+            .into_iter()
+            .filter(|name| !vars.contains_key(*name))
+            .map(str::to_string)
+            .collect();
+        if !missing.is_empty() {
+            return Err(TemplateError::MissingVariables { names: missing });
+        }
+
+        let mut alert = String::new();
+        for segment in &self.alert {
+            match segment {
+                Segment::Text(text) => alert.push_str(text),
+                Segment::Placeholder(name) => alert.push_str(vars[name.as_str()].as_ref()),
+            }
+        }
+        Ok(ApnsPayload {
+            aps: Aps {
+                alert,
+                content_available: self.content_available.then_some(true),
+                badge: self.badge,
+                sound: self.sound.clone(),
+                category: self.category.clone(),
+                thread_id: self.thread_id.clone(),
+                summary_arg: None,
+                summary_arg_count: None,
+                launch_image: None,
+                alert_fields: Default::default(),
+                extra_fields: Default::default(),
+            },
+            custom_key: self.custom_key.clone(),
+            custom_fields: Default::default(),
+        })
+    }
+}
+
+/// Splits `source` into literal text and placeholders, unescaping `{{` and `}}`.
+fn parse(source: &str) -> Result<Vec<Segment>, TemplateError> {
+    let mut segments = Vec::new();
+    let mut text = String::new();
+    let mut chars = source.char_indices().peekable();
+    while let Some((position, c)) = chars.next() {
+        match c {
+            '{' if chars.next_if(|&(_, next)| next == '{').is_some() => text.push('{'),
+            '}' if chars.next_if(|&(_, next)| next == '}').is_some() => text.push('}'),
+            '}' => return Err(TemplateError::UnmatchedBrace { position }),
+            '{' => {
+                let start = position + 1;
+                let end = source[start..]
+                    .find(['{', '}'])
+                    .map(|offset| start + offset)
+                    .filter(|&end| source[end..].starts_with('}'))
+                    .ok_or(TemplateError::UnclosedPlaceholder { position })?;
+                let name = &source[start..end];
+                if name.trim().is_empty() {
+                    return Err(TemplateError::EmptyPlaceholder { position });
+                }
+                if !text.is_empty() {
+                    segments.push(Segment::Text(std::mem::take(&mut text)));
+                }
+                segments.push(Segment::Placeholder(name.to_string()));
+                while chars.next_if(|&(index, _)| index <= end).is_some() {}
+            }
+            c => text.push(c),
+        }
+    }
+    if !text.is_empty() {
+        segments.push(Segment::Text(text));
+    }
+    Ok(segments)
+}
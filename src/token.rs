@@ -0,0 +1,230 @@
+//! Caching of signed provider tokens and the headers built from them.
+
+use crate::audit::{token_hash, SigningAudit, SigningReason, SigningRecord};
+use crate::clock::Clock;
+use crate::error::ApnsError;
+use crate::signer::{sign_provider_token, TokenSigner};
+use crate::telemetry;
+use crate::Claims;
+use futures_util::lock::Mutex;
+use http::header::{HeaderMap, HeaderValue, AUTHORIZATION, CONTENT_TYPE};
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, Instant};
+use zeroize::Zeroizing;
+
+/// How long a provider token is reused before a new one is signed.
+///
+/// APNs rejects tokens older than an hour and throttles providers that sign new ones more
+/// often than every 20 minutes.
+pub(crate) const TOKEN_LIFETIME: Duration = Duration::from_secs(50 * 60);
+
+/// How long a provider token is kept even if APNs rejects it as expired, since APNs answers
+/// `TooManyProviderTokenUpdates` to providers that sign new ones more often.
+const MIN_REUSE_INTERVAL: Duration = Duration::from_secs(20 * 60);
+
+/// The headers shared by every request signed with one provider token.
+struct Signed {
+    headers: HeaderMap,
+    issued_at: Instant,
+    iat: u64,
+    /// Why the token was discarded before it expired, if it was.
+    discarded: Option<SigningReason>,
+}
+
+impl Signed {
+    /// Returns `true` if the token can still be used.
+    fn is_fresh(&self) -> bool {
+        self.discarded.is_none() && self.issued_at.elapsed() < TOKEN_LIFETIME
+    }
+}
+
+/// Signs provider tokens on demand and reuses them until they are due for renewal.
+///
+/// Only one request signs a new token at a time; the others wait for it instead of asking
+/// the signer too, which matters when signing is a call to a remote KMS.
+/// Together with [`TokenCache::invalidate`] keeping young tokens, this stops concurrent
+/// requests from signing more than one token between renewals.
+pub(crate) struct TokenCache {
+    signer: RwLock<Arc<dyn TokenSigner>>,
+    team_id: String,
+    current: RwLock<Option<Signed>>,
+    refresh: Mutex<()>,
+    audit: Option<Arc<dyn SigningAudit>>,
+    clock: Arc<dyn Clock>,
+}
+
+impl TokenCache {
+    /// Creates a cache signing tokens for `team_id` with `signer`, issued at the time told
+    /// by `clock`, reporting every signed token to `audit` if given.
+    pub(crate) fn new(
+        signer: Arc<dyn TokenSigner>,
+        team_id: String,
+        audit: Option<Arc<dyn SigningAudit>>,
+        clock: Arc<dyn Clock>,
+    ) -> Self {
+        TokenCache {
+            signer: RwLock::new(signer),
+            team_id,
+            current: RwLock::new(None),
+            refresh: Mutex::new(()),
+            audit,
+            clock,
+        }
+    }
+
+    /// Creates an empty cache signing with the same key as this one, issuing tokens at the
+    /// time told by `clock`.
+    pub(crate) fn with_clock(&self, clock: Arc<dyn Clock>) -> Self {
+        let signer = self.signer.read().unwrap().clone();
+        TokenCache::new(signer, self.team_id.clone(), self.audit.clone(), clock)
+    }
+
+    /// Returns the audit trail signed tokens are reported to, if any.
+    #[cfg(feature = "tokio")]
+    pub(crate) fn audit(&self) -> Option<&dyn SigningAudit> {
+        self.audit.as_deref()
+    }
+
+    /// Returns the `authorization` and `content-type` headers, signing a new provider token
+    /// only if the cached one is missing or due for renewal.
+    pub(crate) async fn headers(&self) -> Result<HeaderMap, ApnsError> {
+        Ok(self.refreshing_headers().await?.0)
+    }
+
+    /// Returns the headers like [`headers`](Self::headers), and the key ID of the new token
+    /// if one was signed for them.
+    pub(crate) async fn refreshing_headers(
+        &self,
+    ) -> Result<(HeaderMap, Option<String>), ApnsError> {
+        if let Some(headers) = self.fresh_headers() {
+            return Ok((headers, None));
+        }
+
+        let _refresh = self.refresh.lock().await;
+        // Another request may have renewed the token while this one waited for the lock
+        if let Some(headers) = self.fresh_headers() {
+            return Ok((headers, None));
+        }
+
+        let signer = self.signer.read().unwrap().clone();
+        let claims = Claims {
+            iss: self.team_id.clone(),
+            iat: self.clock.unix_time(),
+        };
+        let signing = sign_provider_token(&*signer, &claims);
+        #[cfg(feature = "tracing")]
+        let signing = tracing::Instrument::instrument(
+            signing,
+            tracing::debug_span!("apns.sign_token", key_id = %signer.key_id()),
+        );
+        // Wiped when dropped; the header below is marked sensitive so it is never logged or
+        // indexed by HPACK, but its copies in the HTTP client are beyond reach
+        let token = Zeroizing::new(signing.await?);
+        telemetry::token_refreshed();
+        #[cfg(feature = "logging")]
+        log::info!(
+            "signed a new provider token for team {} with key {}",
+            claims.iss,
+            signer.key_id()
+        );
+
+        let mut authorization =
+            HeaderValue::from_str(&Zeroizing::new(format!("bearer {}", *token)))?;
+        authorization.set_sensitive(true);
+        let mut headers = HeaderMap::new();
+        headers.insert(AUTHORIZATION, authorization);
+        headers.insert(CONTENT_TYPE, HeaderValue::from_static("application/json"));
+
+        let mut current = self.current.write().unwrap();
+        if let Some(audit) = &self.audit {
+            let previous = current.as_ref();
+            audit.record(SigningRecord {
+                token_sha256: token_hash(&token),
+                team_id: claims.iss.clone(),
+                key_id: signer.key_id().to_string(),
+                issued_at: claims.iat,
+                previous_issued_at: previous.map(|signed| signed.iat),
+                reason: match previous {
+                    None => SigningReason::Initial,
+                    Some(signed) => signed.discarded.unwrap_or(SigningReason::Expired),
+                },
+            });
+        }
+        // The key may have been rotated while this token was being signed; it still serves
+        // this request, but the next one signs with the new key
+        let rotated = !Arc::ptr_eq(&signer, &self.signer.read().unwrap());
+        *current = Some(Signed {
+            headers: headers.clone(),
+            issued_at: Instant::now(),
+            iat: claims.iat,
+            discarded: rotated.then_some(SigningReason::Rotated),
+        });
+
+        Ok((headers, Some(signer.key_id().to_string())))
+    }
+
+    /// Returns the team ID tokens are signed for.
+    #[cfg(feature = "tokio")]
+    pub(crate) fn team_id(&self) -> &str {
+        &self.team_id
+    }
+
+    /// Returns the headers of the cached token, if it can still be used.
+    fn fresh_headers(&self) -> Option<HeaderMap> {
+        self.current
+            .read()
+            .unwrap()
+            .as_ref()
+            .filter(|signed| signed.is_fresh())
+            .map(|signed| signed.headers.clone())
+    }
+
+    /// Returns how long ago the cached token was signed, if it can still be used.
+    #[cfg(feature = "tokio")]
+    pub(crate) fn age(&self) -> Option<Duration> {
+        self.current
+            .read()
+            .unwrap()
+            .as_ref()
+            .filter(|signed| signed.is_fresh())
+            .map(|signed| signed.issued_at.elapsed())
+    }
+
+    /// Returns the current time tokens are issued at, in seconds since the Unix epoch.
+    #[cfg(feature = "tokio")]
+    pub(crate) fn unix_time(&self) -> u64 {
+        self.clock.unix_time()
+    }
+
+    /// Discards the cached token after APNs rejected it as expired, so the next request
+    /// signs a new one.
+    ///
+    /// A token signed less than 20 minutes ago is kept: the rejection then came from a
+    /// request made with an older token, or from a skewed clock a new token would not fix,
+    /// and signing again would risk `TooManyProviderTokenUpdates`.
+    ///
+    /// # Returns
+    ///
+    /// `true` if the next request signs a new token.
+    pub(crate) fn invalidate(&self) -> bool {
+        match &mut *self.current.write().unwrap() {
+            Some(signed) if signed.issued_at.elapsed() >= MIN_REUSE_INTERVAL => {
+                signed.discarded.get_or_insert(SigningReason::Invalidated);
+                true
+            }
+            Some(signed) => signed.discarded.is_some(),
+            None => true,
+        }
+    }
+
+    /// Replaces the signer and discards the cached token, so the next request signs a new
+    /// one with the new key.
+    #[cfg(feature = "tokio")]
+    pub(crate) fn rotate(&self, signer: Arc<dyn TokenSigner>) {
+        let mut current = self.current.write().unwrap();
+        *self.signer.write().unwrap() = signer;
+        if let Some(signed) = &mut *current {
+            signed.discarded = Some(SigningReason::Rotated);
+        }
+    }
+}
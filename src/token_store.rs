@@ -0,0 +1,77 @@
+//! Remembering device tokens APNs has reported as no longer valid.
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+/// Stores device tokens that must no longer be sent to.
+///
+/// The client records every token APNs answers `410 Unregistered` for, and batch sends skip
+/// tokens the store already knows are invalid. Implement this trait to persist tokens in
+/// your own database; [`MemoryTokenStore`] is used when none is configured.
+///
+/// # Example
+///
+/// ```rust
+/// use apnrs::{MemoryTokenStore, TokenStore};
+///
+/// let store = MemoryTokenStore::new();
+/// store.mark_invalid("DEVICE_TOKEN", Some(1_700_000_000_000));
+///
+/// assert!(store.is_invalid("DEVICE_TOKEN"));
+/// assert_eq!(store.list_invalid(), vec!["DEVICE_TOKEN".to_string()]);
+/// ```
+pub trait TokenStore: Send + Sync {
+    /// Records that `device_token` is invalid, and when APNs last confirmed it if known
+    /// (milliseconds since the Unix epoch).
+    fn mark_invalid(&self, device_token: &str, timestamp: Option<u64>);
+
+    /// Returns `true` if `device_token` is known to be invalid.
+    fn is_invalid(&self, device_token: &str) -> bool;
+
+    /// Returns every token known to be invalid.
+    fn list_invalid(&self) -> Vec<String>;
+}
+
+/// A [`TokenStore`] that keeps invalid tokens in memory for the lifetime of the process.
+#[derive(Debug, Default)]
+pub struct MemoryTokenStore {
+    tokens: Mutex<HashMap<String, Option<u64>>>,
+}
+
+impl MemoryTokenStore {
+    /// Creates an empty store.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns when APNs last confirmed `device_token` was invalid, if it is in the store
+    /// and APNs gave a timestamp.
+    pub fn invalidated_at(&self, device_token: &str) -> Option<u64> {
+        self.tokens
+            .lock()
+            .unwrap()
+            .get(device_token)
+            .copied()
+            .flatten()
+    }
+}
+
+impl TokenStore for MemoryTokenStore {
+    fn mark_invalid(&self, device_token: &str, timestamp: Option<u64>) {
+        let mut tokens = self.tokens.lock().unwrap();
+        let entry = tokens.entry(device_token.to_string()).or_default();
+        if timestamp > *entry {
+            *entry = timestamp;
+        }
+    }
+
+    fn is_invalid(&self, device_token: &str) -> bool {
+        self.tokens.lock().unwrap().contains_key(device_token)
+    }
+
+    fn list_invalid(&self) -> Vec<String> {
+        let mut tokens: Vec<String> = self.tokens.lock().unwrap().keys().cloned().collect();
+        tokens.sort();
+        tokens
+    }
+}
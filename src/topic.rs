@@ -0,0 +1,223 @@
+//! Validation of `apns-topic` values.
+
+use crate::push_type::PushType;
+use std::error::Error;
+use std::fmt;
+
+/// The maximum length of a bundle identifier accepted by App Store Connect.
+pub const MAX_BUNDLE_ID_LEN: usize = 155;
+
+/// Topic suffixes Apple defines for special push types.
+pub const TOPIC_SUFFIXES: &[&str] = &[
+    ".voip",
+    ".voip-ptt",
+    ".complication",
+    ".pushkit.fileprovider",
+    ".location-query",
+    ".push-type.liveactivity",
+];
+
+/// Describes why a topic is not a valid `apns-topic` value.
+///
+/// # Variants
+///
+/// * `Empty` - The topic is empty.
+/// * `Whitespace` - The topic contains whitespace at the given byte offset.
+/// * `InvalidCharacter` - The topic contains a character other than `A-Z`, `a-z`, `0-9`, `-`, or `.`.
+/// * `TooLong` - The bundle identifier is longer than [`MAX_BUNDLE_ID_LEN`].
+/// * `NotReverseDns` - The bundle identifier has fewer than two dot-separated components.
+/// * `EmptyComponent` - The bundle identifier has an empty component (e.g. `com..app` or a trailing dot).
+/// * `NotWebsitePushId` - The topic of a Safari web push does not start with `web.`.
+/// * `WrongSuffix` - The topic does not end in the suffix `push_type` requires, such as
+///   `.voip`, or carries a suffix when `push_type` has none; `expected` is the suffix it
+///   requires, if any.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TopicError {
+    Empty,
+    Whitespace {
+        index: usize,
+    },
+    InvalidCharacter {
+        character: char,
+        index: usize,
+    },
+    TooLong {
+        len: usize,
+        max: usize,
+    },
+    NotReverseDns,
+    EmptyComponent,
+    NotWebsitePushId,
+    WrongSuffix {
+        push_type: PushType,
+        expected: Option<&'static str>,
+    },
+}
+
+impl fmt::Display for TopicError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TopicError::Empty => write!(f, "topic is empty"),
+            TopicError::Whitespace { index } => {
+                write!(f, "topic contains whitespace at position {}", index)
+            }
+            TopicError::InvalidCharacter { character, index } => write!(
+                f,
+                "topic contains invalid character {:?} at position {}",
+                character, index
+            ),
+            TopicError::TooLong { len, max } => write!(
+                f,
+                "bundle identifier is {} characters long, the maximum is {}",
+                len, max
+            ),
+            TopicError::NotReverseDns => write!(
+                f,
+                "topic is not a reverse-DNS bundle identifier such as com.example.app"
+            ),
+            TopicError::EmptyComponent => {
+                write!(f, "bundle identifier contains an empty component")
+            }
+            TopicError::NotWebsitePushId => {
+                write!(f, "topic is not a website push ID such as web.com.example")
+            }
+            TopicError::WrongSuffix {
+                push_type,
+                expected: Some(suffix),
+            } => write!(f, "{} pushes need a topic ending in {}", push_type, suffix),
+            TopicError::WrongSuffix {
+                push_type,
+                expected: None,
+            } => write!(f, "{} pushes need the bare bundle ID as topic", push_type),
+        }
+    }
+}
+
+impl Error for TopicError {}
+
+/// Checks that `topic` is a bundle identifier, optionally followed by one of the
+/// [`TOPIC_SUFFIXES`] Apple defines for special push types.
+///
+/// # Returns
+///
+/// `Ok(())` if the topic is well-formed, or a `TopicError` describing the first problem.
+///
+/// # Example
+///
+/// ```rust
+/// use apnrs::{validate_topic, TopicError};
+///
+/// assert!(validate_topic("com.example.app").is_ok());
+/// assert!(validate_topic("com.example.app.voip").is_ok());
+/// assert_eq!(validate_topic("com.example.app "), Err(TopicError::Whitespace { index: 15 }));
+/// ```
+pub fn validate_topic(topic: &str) -> Result<(), TopicError> {
+    if topic.is_empty() {
+        return Err(TopicError::Empty);
+    }
+
+    for (index, character) in topic.char_indices() {
+        if character.is_whitespace() {
+            return Err(TopicError::Whitespace { index });
+        }
+        if !(character.is_ascii_alphanumeric() || character == '-' || character == '.') {
+            return Err(TopicError::InvalidCharacter { character, index });
+        }
+    }
+
+    let bundle_id = bundle_id(topic);
+
+    if bundle_id.len() > MAX_BUNDLE_ID_LEN {
+        return Err(TopicError::TooLong {
+            len: bundle_id.len(),
+            max: MAX_BUNDLE_ID_LEN,
+        });
+    }
+    if bundle_id.split('.').any(str::is_empty) {
+        return Err(TopicError::EmptyComponent);
+    }
+    if bundle_id.split('.').count() < 2 {
+        return Err(TopicError::NotReverseDns);
+    }
+
+    Ok(())
+}
+
+/// Checks that `topic` is well-formed, as [`validate_topic`] does, and carries exactly the
+/// suffix Apple requires for `push_type`, such as `.voip` for VoIP pushes and none for
+/// alerts, so a mismatched topic fails before the request is made instead of APNs answering
+/// `TopicDisallowed` or `DeviceTokenNotForTopic`.
+///
+/// # Returns
+///
+/// `Ok(())` if the topic is well-formed and fits the push type, or a `TopicError` describing
+/// the first problem.
+///
+/// # Example
+///
+/// ```rust
+/// use apnrs::{validate_topic_for, PushType, TopicError};
+///
+/// assert!(validate_topic_for("com.example.app.voip", PushType::Voip).is_ok());
+/// assert_eq!(
+///     validate_topic_for("com.example.app", PushType::Voip),
+///     Err(TopicError::WrongSuffix { push_type: PushType::Voip, expected: Some(".voip") })
+/// );
+/// assert_eq!(
+///     validate_topic_for("com.example.app.voip", PushType::Alert),
+///     Err(TopicError::WrongSuffix { push_type: PushType::Alert, expected: None })
+/// );
+/// ```
+pub fn validate_topic_for(topic: &str, push_type: PushType) -> Result<(), TopicError> {
+    validate_topic(topic)?;
+    if push_type.topic_for(topic) != topic {
+        return Err(TopicError::WrongSuffix {
+            push_type,
+            expected: push_type.topic_suffix(),
+        });
+    }
+    Ok(())
+}
+
+/// Checks `topic` with [`validate_topic_for`] if the push type is known, or with
+/// [`validate_topic`] otherwise.
+pub(crate) fn validate_request_topic(
+    topic: &str,
+    push_type: Option<PushType>,
+) -> Result<(), TopicError> {
+    match push_type {
+        Some(push_type) => validate_topic_for(topic, push_type),
+        None => validate_topic(topic),
+    }
+}
+
+/// Returns the bundle identifier of `topic`, without any of the [`TOPIC_SUFFIXES`].
+pub(crate) fn bundle_id(topic: &str) -> &str {
+    TOPIC_SUFFIXES
+        .iter()
+        .find_map(|suffix| topic.strip_suffix(suffix))
+        .unwrap_or(topic)
+}
+
+/// Checks that `topic` is a website push ID, such as `web.com.example`, as required for
+/// Safari web pushes.
+///
+/// # Returns
+///
+/// `Ok(())` if the topic is well-formed, or a `TopicError` describing the first problem.
+///
+/// # Example
+///
+/// ```rust
+/// use apnrs::{validate_website_push_id, TopicError};
+///
+/// assert!(validate_website_push_id("web.com.example").is_ok());
+/// assert_eq!(validate_website_push_id("com.example"), Err(TopicError::NotWebsitePushId));
+/// ```
+pub fn validate_website_push_id(topic: &str) -> Result<(), TopicError> {
+    validate_topic(topic)?;
+    match topic.strip_prefix("web.") {
+        Some(domain) if domain.contains('.') => Ok(()),
+        _ => Err(TopicError::NotWebsitePushId),
+    }
+}
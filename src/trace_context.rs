@@ -0,0 +1,145 @@
+//! Correlating sends with the distributed trace of the request that caused them.
+
+use crate::ApnsError;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::str::FromStr;
+
+/// The trace a send belongs to, in the form of a W3C `traceparent`: the ID of the trace and
+/// of the span that caused the send, such as the API request handler queueing a push.
+///
+/// Attach it to a send with [`SendOptions::trace_context`](crate::SendOptions::trace_context).
+/// The IDs are recorded on the send's `apns.send` tracing span, as `trace_id` and
+/// `parent_span_id`, and the trace ID on its [`SendEvent`](crate::SendEvent), so a push can
+/// be found from the trace of the request that sent it and the other way round. It is kept
+/// with a queued [`PushRequest`](crate::PushRequest), so retries, journaled notifications,
+/// and notifications spooled during an outage still carry it.
+///
+/// This crate does not depend on OpenTelemetry; take the IDs from the incoming
+/// `traceparent` header, or from the current OpenTelemetry span context as hex.
+///
+/// # Example
+///
+/// ```rust
+/// use apnrs::{SendOptions, TraceContext};
+///
+/// // The traceparent header of the API request being handled
+/// let traceparent = "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01";
+/// let context: TraceContext = traceparent.parse()?;
+/// assert_eq!(context.trace_id(), "4bf92f3577b34da6a3ce929d0e0e4736");
+/// assert_eq!(context.parent_id(), "00f067aa0ba902b7");
+/// assert_eq!(context.to_string(), traceparent);
+///
+/// let options = SendOptions::new().trace_context(context);
+///
+/// assert!("00-00000000000000000000000000000000-00f067aa0ba902b7-01"
+///     .parse::<TraceContext>()
+///     .is_err());
+/// # Ok::<(), apnrs::ApnsError>(())
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct TraceContext {
+    trace_id: String,
+    parent_id: String,
+    sampled: bool,
+}
+
+impl TraceContext {
+    /// Creates a sampled context from a trace ID of 32 hex digits and a parent span ID of
+    /// 16, as OpenTelemetry formats them.
+    ///
+    /// # Returns
+    ///
+    /// The context, or `ApnsError::Config` if an ID is not hex of the right length, or is all
+    /// zeros, which W3C Trace Context reserves as invalid.
+    pub fn new(trace_id: &str, parent_id: &str) -> Result<Self, ApnsError> {
+        Ok(TraceContext {
+            trace_id: checked_id("trace ID", trace_id, 32)?,
+            parent_id: checked_id("parent span ID", parent_id, 16)?,
+            sampled: true,
+        })
+    }
+
+    /// Sets whether the caller sampled the trace (defaults to `true`), as the `sampled`
+    /// flag of the `traceparent`.
+    pub fn sampled(mut self, sampled: bool) -> Self {
+        self.sampled = sampled;
+        self
+    }
+
+    /// Returns the trace ID as 32 lowercase hex digits.
+    pub fn trace_id(&self) -> &str {
+        &self.trace_id
+    }
+
+    /// Returns the ID of the span that caused the send as 16 lowercase hex digits.
+    pub fn parent_id(&self) -> &str {
+        &self.parent_id
+    }
+
+    /// Returns `true` if the caller sampled the trace.
+    pub fn is_sampled(&self) -> bool {
+        self.sampled
+    }
+}
+
+/// Formats the context as a version `00` `traceparent` header value.
+impl fmt::Display for TraceContext {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let flags = if self.sampled { "01" } else { "00" };
+        write!(f, "00-{}-{}-{}", self.trace_id, self.parent_id, flags)
+    }
+}
+
+/// Parses a `traceparent` header value.
+///
+/// Versions after `00` are accepted as long as they start with the fields of version `00`,
+/// as W3C Trace Context asks of parsers.
+impl FromStr for TraceContext {
+    type Err = ApnsError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || ApnsError::Config(format!("{:?} is not a valid traceparent", s));
+        let mut fields = s.trim().split('-');
+        let (Some(version), Some(trace_id), Some(parent_id), Some(flags)) =
+            (fields.next(), fields.next(), fields.next(), fields.next())
+        else {
+            return Err(invalid());
+        };
+        let version = u8::from_str_radix(version, 16).map_err(|_| invalid())?;
+        let flags = u8::from_str_radix(flags, 16).map_err(|_| invalid())?;
+        if version == 0xff || (version == 0 && fields.next().is_some()) {
+            return Err(invalid());
+        }
+        Ok(TraceContext::new(trace_id, parent_id)
+            .map_err(|_| invalid())?
+            .sampled(flags & 0x01 == 0x01))
+    }
+}
+
+impl TryFrom<String> for TraceContext {
+    type Error = ApnsError;
+
+    fn try_from(traceparent: String) -> Result<Self, Self::Error> {
+        traceparent.parse()
+    }
+}
+
+impl From<TraceContext> for String {
+    fn from(context: TraceContext) -> Self {
+        context.to_string()
+    }
+}
+
+/// Checks that `id` is `len` hex digits and not all zeros, returning it in lowercase.
+fn checked_id(name: &str, id: &str, len: usize) -> Result<String, ApnsError> {
+    let is_hex = id.len() == len && id.bytes().all(|b| b.is_ascii_hexdigit());
+    if !is_hex || id.bytes().all(|b| b == b'0') {
+        return Err(ApnsError::Config(format!(
+            "{} must be {} hex digits and not all zeros, got {:?}",
+            name, len, id
+        )));
+    }
+    Ok(id.to_ascii_lowercase())
+}
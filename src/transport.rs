@@ -0,0 +1,285 @@
+//! Sending through a pluggable HTTP/2 transport, on any async runtime.
+
+use crate::dry_run::PreparedRequest;
+use crate::environment::Environment;
+use crate::error::{ApnsError, ErrorReason, TransportError};
+use crate::notification::{Notification, NotificationError, Priority};
+use crate::options::{Credentials, SendOptions};
+use crate::push_type::PushType;
+use crate::response::ApnsResponse;
+use crate::topic::validate_request_topic;
+use crate::trim::MAX_PAYLOAD_SIZE;
+use crate::ApnsPayload;
+use bytes::Bytes;
+use http::header::{HeaderMap, HeaderValue};
+use http::Response;
+use serde::Serialize;
+use std::fmt;
+use std::future::Future;
+use std::pin::Pin;
+
+/// The future returned by [`Transport::send`].
+pub type TransportFuture<'a> =
+    Pin<Box<dyn Future<Output = Result<Response<Bytes>, TransportError>> + Send + 'a>>;
+
+/// An HTTP/2 client that delivers requests to APNs for a [`CoreClient`].
+///
+/// Implement it over the HTTP client of your runtime, such as one built on `async-std` or
+/// `smol`, to send notifications without tokio.
+///
+/// # Example
+///
+/// ```rust,no_run
+/// use apnrs::http::Response;
+/// use apnrs::{Bytes, PreparedRequest, Transport, TransportError, TransportFuture};
+///
+/// struct Http2Client;
+///
+/// # async fn post(url: String, headers: apnrs::HeaderMap, body: Vec<u8>) -> Result<Response<Bytes>, std::io::Error> { unimplemented!() }
+/// impl Transport for Http2Client {
+///     fn send(&self, request: PreparedRequest) -> TransportFuture<'_> {
+///         Box::pin(async move {
+///             // Post `request.body` to `request.url` with `request.headers` over HTTP/2
+///             post(request.url, request.headers, request.body)
+///                 .await
+///                 .map_err(TransportError::new)
+///         })
+///     }
+/// }
+/// ```
+pub trait Transport: Send + Sync {
+    /// Sends `request` and buffers APNs' response.
+    ///
+    /// # Returns
+    ///
+    /// The response, whatever its status, or a `TransportError` if none was received.
+    fn send(&self, request: PreparedRequest) -> TransportFuture<'_>;
+}
+
+/// A client that prepares and signs requests without depending on an async runtime,
+/// delivering them through a [`Transport`].
+///
+/// Payload serialization, validation, and provider token signing are the same as
+/// [`ApnsClient`](crate::ApnsClient)'s, but the connection, timeouts, retries, and queueing
+/// are left to the transport and the caller. Only token-based authentication is supported.
+///
+/// # Example
+///
+/// ```rust,no_run
+/// use apnrs::{ApnsPayload, CoreClient, Credentials, Environment, Transport};
+///
+/// # async fn run(transport: impl Transport, payload: ApnsPayload) -> Result<(), apnrs::ApnsError> {
+/// let credentials = Credentials::from_key_path("path/to/auth/key", "TEAM_ID", "KEY_ID")?;
+/// let client = CoreClient::new(transport, credentials).environment(Environment::Sandbox);
+///
+/// let response = client.send("DEVICE_TOKEN", "com.example.app", &payload).await?;
+/// println!("{} {:?}", response.status, response.error);
+/// # Ok(())
+/// # }
+/// ```
+pub struct CoreClient<T> {
+    transport: T,
+    credentials: Credentials,
+    environment: Environment,
+}
+
+impl<T: Transport> CoreClient<T> {
+    /// Creates a client signing provider tokens with `credentials` and sending to production
+    /// through `transport`.
+    pub fn new(transport: T, credentials: Credentials) -> Self {
+        CoreClient {
+            transport,
+            credentials,
+            environment: Environment::Production,
+        }
+    }
+
+    /// Sets the APNs environment notifications are sent to.
+    pub fn environment(mut self, environment: Environment) -> Self {
+        self.environment = environment;
+        self
+    }
+
+    /// Returns the transport requests are sent through.
+    pub fn transport(&self) -> &T {
+        &self.transport
+    }
+
+    /// Sends a push notification.
+    ///
+    /// # Returns
+    ///
+    /// APNs' response, whatever its status, or an `ApnsError` if the notification could not be
+    /// prepared or delivered.
+    pub async fn send(
+        &self,
+        device_token: &str,
+        topic: &str,
+        payload: &ApnsPayload,
+    ) -> Result<ApnsResponse, ApnsError> {
+        self.send_with(device_token, topic, payload, &SendOptions::new())
+            .await
+    }
+
+    /// Sends any serializable payload with per-request settings: its credentials, collapse
+    /// ID, and `apns-id`.
+    ///
+    /// # Returns
+    ///
+    /// APNs' response, whatever its status, or an `ApnsError` if the notification could not be
+    /// prepared or delivered.
+    pub async fn send_with<P: Serialize + Sync + ?Sized>(
+        &self,
+        device_token: &str,
+        topic: &str,
+        payload: &P,
+        options: &SendOptions,
+    ) -> Result<ApnsResponse, ApnsError> {
+        let request = self
+            .request(device_token, topic, None, None, payload, options)
+            .await?;
+        self.deliver(request, options).await
+    }
+
+    /// Sends a notification of a specific push type, validated against its push type's rules.
+    ///
+    /// # Returns
+    ///
+    /// APNs' response, whatever its status, or an `ApnsError` if the notification is invalid,
+    /// needs a certificate, or could not be delivered.
+    pub async fn send_notification(
+        &self,
+        device_token: &str,
+        topic: &str,
+        notification: &Notification,
+    ) -> Result<ApnsResponse, ApnsError> {
+        notification.validate()?;
+        let push_type = notification.push_type;
+        if push_type.requires_certificate() {
+            return Err(ApnsError::CertificateRequired(push_type));
+        }
+        let topic = push_type.topic_for(topic);
+        let options = SendOptions::new();
+        let request = self
+            .request(
+                device_token,
+                &topic,
+                Some(push_type),
+                notification.priority,
+                &notification.payload,
+                &options,
+            )
+            .await?;
+        self.deliver(request, &options).await
+    }
+
+    /// Builds the request a send would make, without sending it.
+    ///
+    /// # Returns
+    ///
+    /// The signed request, or an `ApnsError` if the topic or payload is invalid or the
+    /// provider token could not be signed.
+    pub async fn prepare<P: Serialize + Sync + ?Sized>(
+        &self,
+        device_token: &str,
+        topic: &str,
+        payload: &P,
+        options: &SendOptions,
+    ) -> Result<PreparedRequest, ApnsError> {
+        self.request(device_token, topic, None, None, payload, options)
+            .await
+    }
+
+    async fn request<P: Serialize + Sync + ?Sized>(
+        &self,
+        device_token: &str,
+        topic: &str,
+        push_type: Option<PushType>,
+        priority: Option<Priority>,
+        payload: &P,
+        options: &SendOptions,
+    ) -> Result<PreparedRequest, ApnsError> {
+        let push_type = options.push_type.or(push_type);
+        let priority = options.priority.or(priority);
+        validate_request_topic(topic, push_type)?;
+        let body = serde_json::to_vec(payload)?;
+        let limit = push_type.map_or(MAX_PAYLOAD_SIZE, |t| t.max_payload_size());
+        if body.len() > limit {
+            return Err(NotificationError::TooLarge {
+                push_type: push_type.unwrap_or(PushType::Alert),
+                size: body.len(),
+                limit,
+            }
+            .into());
+        }
+
+        let credentials = options.credentials.as_ref().unwrap_or(&self.credentials);
+        let mut headers = credentials.tokens().headers().await?;
+        headers.insert("apns-topic", HeaderValue::from_str(topic)?);
+        if let Some(push_type) = push_type {
+            headers.insert(
+                "apns-push-type",
+                HeaderValue::from_static(push_type.as_str()),
+            );
+        }
+        if let Some(priority) = priority {
+            headers.insert("apns-priority", HeaderValue::from_static(priority.as_str()));
+        }
+        insert_option_headers(&mut headers, options)?;
+        let environment = options.environment.unwrap_or(self.environment);
+        Ok(PreparedRequest {
+            url: environment.device_url(device_token),
+            headers,
+            body,
+        })
+    }
+
+    /// Sends a prepared request; if APNs reports the provider token expired and the cache
+    /// lets it be discarded, sends the request once more with a newly signed one.
+    async fn deliver(
+        &self,
+        request: PreparedRequest,
+        options: &SendOptions,
+    ) -> Result<ApnsResponse, ApnsError> {
+        let credentials = options.credentials.as_ref().unwrap_or(&self.credentials);
+        let mut retry = request.clone();
+        let response = ApnsResponse::from_raw(self.transport.send(request).await?);
+        if response.error != Some(ErrorReason::ExpiredProviderToken) {
+            return Ok(response);
+        }
+        if !credentials.tokens().invalidate() {
+            return Ok(response);
+        }
+        retry.headers.extend(credentials.tokens().headers().await?);
+        Ok(ApnsResponse::from_raw(self.transport.send(retry).await?))
+    }
+}
+
+impl<T> fmt::Debug for CoreClient<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CoreClient")
+            .field("environment", &self.environment)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Adds the `apns-expiration`, `apns-collapse-id`, `apns-id`, and extra headers `options`
+/// ask for.
+pub(crate) fn insert_option_headers(
+    headers: &mut HeaderMap,
+    options: &SendOptions,
+) -> Result<(), ApnsError> {
+    if let Some(expiration) = options.expiration_header() {
+        headers.insert("apns-expiration", HeaderValue::from_str(&expiration)?);
+    }
+    if let Some(collapse_id) = &options.collapse_id {
+        headers.insert("apns-collapse-id", HeaderValue::from_str(collapse_id)?);
+    }
+    if let Some(apns_id) = &options.apns_id {
+        headers.insert("apns-id", HeaderValue::from_str(apns_id)?);
+    }
+    for (name, value) in &options.headers {
+        headers.insert(name.clone(), value.clone());
+    }
+    Ok(())
+}
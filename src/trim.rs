@@ -0,0 +1,215 @@
+//! Strategies for shrinking payloads that APNs rejected as too large.
+
+use serde_json::Value;
+
+/// The maximum size, in bytes, of a regular notification payload.
+pub const MAX_PAYLOAD_SIZE: usize = 4096;
+
+/// The maximum size, in bytes, of a VoIP notification payload.
+pub const MAX_VOIP_PAYLOAD_SIZE: usize = 5120;
+
+/// Describes what a [`TrimStrategy`] removed from a payload.
+///
+/// # Fields
+///
+/// * `removed_keys` - The custom keys that were dropped, in the order they were removed.
+/// * `alert_truncated_bytes` - How many bytes were cut from the alert text (0 if untouched).
+/// * `original_size` - The serialized size of the payload before trimming.
+/// * `trimmed_size` - The serialized size of the payload after trimming.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TrimReport {
+    pub removed_keys: Vec<String>,
+    pub alert_truncated_bytes: usize,
+    pub original_size: usize,
+    pub trimmed_size: usize,
+}
+
+impl TrimReport {
+    /// Returns `true` if the strategy did not change the payload.
+    pub fn is_unchanged(&self) -> bool {
+        self.removed_keys.is_empty() && self.alert_truncated_bytes == 0
+    }
+}
+
+/// A strategy used to shrink a payload after APNs answered `413 PayloadTooLarge`.
+///
+/// Implementations receive the payload as a JSON value and should modify it in
+/// place until its serialized size fits within `limit`, reporting what was removed.
+pub trait TrimStrategy: Send + Sync {
+    /// Shrinks `payload` so that it serializes to at most `limit` bytes, if possible.
+    fn trim(&self, payload: &mut Value, limit: usize) -> TrimReport;
+}
+
+/// The default trimming strategy.
+///
+/// Custom keys are dropped in the configured priority order first; if the payload
+/// is still too large the alert text is truncated and suffixed with an ellipsis.
+///
+/// # Example
+///
+/// ```rust
+/// use apnrs::DefaultTrimStrategy;
+///
+/// let strategy = DefaultTrimStrategy::new()
+///     .drop_keys(["debug_info", "custom_key"])
+///     .truncate_alert(true);
+/// ```
+#[derive(Debug, Clone)]
+pub struct DefaultTrimStrategy {
+    droppable_keys: Vec<String>,
+    truncate_alert: bool,
+    ellipsis: String,
+}
+
+impl Default for DefaultTrimStrategy {
+    fn default() -> Self {
+        DefaultTrimStrategy {
+            droppable_keys: Vec::new(),
+            truncate_alert: true,
+            ellipsis: "\u{2026}".to_string(),
+        }
+    }
+}
+
+impl DefaultTrimStrategy {
+    /// Creates a strategy that only truncates the alert text.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the custom keys that may be dropped, in the order they should be removed.
+    ///
+    /// The `aps` dictionary is never dropped, even if listed.
+    pub fn drop_keys<I, S>(mut self, keys: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.droppable_keys = keys.into_iter().map(Into::into).collect();
+        self
+    }
+
+    /// Sets whether the alert text may be truncated once no droppable keys remain.
+    pub fn truncate_alert(mut self, truncate: bool) -> Self {
+        self.truncate_alert = truncate;
+        self
+    }
+
+    /// Sets the suffix appended to a truncated alert (defaults to `…`).
+    pub fn ellipsis(mut self, ellipsis: impl Into<String>) -> Self {
+        self.ellipsis = ellipsis.into();
+        self
+    }
+}
+
+impl TrimStrategy for DefaultTrimStrategy {
+    fn trim(&self, payload: &mut Value, limit: usize) -> TrimReport {
+        let original_size = serialized_size(payload);
+        let mut report = TrimReport {
+            original_size,
+            trimmed_size: original_size,
+            ..Default::default()
+        };
+
+        // Drop custom keys in priority order
+        for key in &self.droppable_keys {
+            if report.trimmed_size <= limit {
+                break;
+            }
+            if key == "aps" {
+                continue;
+            }
+            if let Some(root) = payload.as_object_mut() {
+                if root.remove(key).is_some() {
+                    report.removed_keys.push(key.clone());
+                    report.trimmed_size = serialized_size(payload);
+                }
+            }
+        }
+
+        // Truncate the alert text as a last resort
+        if self.truncate_alert && report.trimmed_size > limit {
+            let excess = report.trimmed_size - limit;
+            if let Some(text) = alert_text_mut(payload) {
+                let before = text.len();
+                shorten(text, excess, &self.ellipsis);
+                report.alert_truncated_bytes = before.saturating_sub(text.len());
+            }
+            report.trimmed_size = serialized_size(payload);
+
+            // JSON escaping can make the first cut too small, so keep shortening
+            while report.trimmed_size > limit {
+                let excess = report.trimmed_size - limit;
+                let Some(text) = alert_text_mut(payload) else {
+                    break;
+                };
+                if text.is_empty() {
+                    break;
+                }
+                let before = text.len();
+                shorten(text, excess, &self.ellipsis);
+                report.alert_truncated_bytes += before.saturating_sub(text.len());
+                report.trimmed_size = serialized_size(payload);
+            }
+        }
+
+        report
+    }
+}
+
+/// Returns the number of bytes `value` occupies once serialized.
+fn serialized_size(value: &Value) -> usize {
+    serde_json::to_vec(value).map(|v| v.len()).unwrap_or(0)
+}
+
+/// Locates the alert text, either `aps.alert` itself or `aps.alert.body`.
+fn alert_text_mut(payload: &mut Value) -> Option<&mut String> {
+    match payload.get_mut("aps")?.get_mut("alert")? {
+        Value::String(text) => Some(text),
+        Value::Object(alert) => match alert.get_mut("body")? {
+            Value::String(text) => Some(text),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+/// Removes enough trailing characters from `text` to shrink its serialized form by at
+/// least `excess` bytes, appending `ellipsis` if any text remains.
+fn shorten(text: &mut String, excess: usize, ellipsis: &str) {
+    let (mut cut, needed) = match text.strip_suffix(ellipsis) {
+        Some(base) => (base.len(), excess),
+        None => (text.len(), excess + escaped_len(ellipsis)),
+    };
+
+    let mut removed = 0;
+    for (index, c) in text[..cut].char_indices().rev() {
+        if removed >= needed {
+            break;
+        }
+        removed += escaped_char_len(c);
+        cut = index;
+    }
+    if removed < needed {
+        cut = 0;
+    }
+
+    text.truncate(cut);
+    if cut > 0 {
+        text.push_str(ellipsis);
+    }
+}
+
+/// Returns the number of bytes `text` occupies inside a JSON string literal.
+fn escaped_len(text: &str) -> usize {
+    text.chars().map(escaped_char_len).sum()
+}
+
+/// Returns the number of bytes `c` occupies inside a JSON string literal.
+fn escaped_char_len(c: char) -> usize {
+    match c {
+        '"' | '\\' | '\n' | '\r' | '\t' | '\u{08}' | '\u{0c}' => 2,
+        c if (c as u32) < 0x20 => 6,
+        c => c.len_utf8(),
+    }
+}
@@ -0,0 +1,109 @@
+//! Conversions between payloads and `serde_json::Value`.
+
+use crate::push_type::PushType;
+use crate::rules::{validate_against_rules, RuleViolation};
+use crate::ApnsPayload;
+use serde_json::Value;
+use std::error::Error;
+use std::fmt;
+
+/// Describes why a JSON value cannot be converted into an [`ApnsPayload`].
+///
+/// # Variants
+///
+/// * `NotAnObject` - The value is not a JSON object.
+/// * `MissingAps` - The value has no `aps` dictionary.
+/// * `RuleViolations` - The payload breaks Apple's rules for alert pushes; see
+///   [`validate_against_rules`](crate::validate_against_rules).
+/// * `Malformed` - A value has the wrong type for its field, such as a string `badge`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PayloadError {
+    NotAnObject,
+    MissingAps,
+    RuleViolations(Vec<RuleViolation>),
+    Malformed { message: String },
+}
+
+impl fmt::Display for PayloadError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PayloadError::NotAnObject => write!(f, "payload is not a JSON object"),
+            PayloadError::MissingAps => write!(f, "payload has no aps dictionary"),
+            PayloadError::RuleViolations(violations) => {
+                let violations: Vec<String> = violations.iter().map(|v| v.to_string()).collect();
+                write!(f, "{}", violations.join("; "))
+            }
+            PayloadError::Malformed { message } => write!(f, "{}", message),
+        }
+    }
+}
+
+impl Error for PayloadError {}
+
+/// Converts a JSON value, such as a payload loaded from configuration or a database, into
+/// a payload.
+///
+/// The `aps` dictionary is checked against Apple's rules for alert pushes. Top-level keys other
+/// than `aps` and `custom_key` are kept in `custom_fields`, and `aps` and alert dictionary
+/// keys an [`Aps`](crate::Aps) has no field for in its `extra_fields` and `alert_fields`, so
+/// nothing is dropped. `content-available` defaults to 0, and the thread ID may be given as
+/// `thread-id` or `thread_id`.
+///
+/// # Example
+///
+/// ```rust
+/// use apnrs::{ApnsPayload, Badge, PayloadError};
+/// use serde_json::{json, Value};
+///
+/// let payload = ApnsPayload::try_from(json!({
+///     "aps": { "alert": "Your order has shipped", "badge": 2, "thread-id": "orders" },
+///     "custom_key": "order-42"
+/// }))
+/// .unwrap();
+/// assert_eq!(payload.aps.badge, Badge::Set(2));
+///
+/// let value = Value::try_from(payload).unwrap();
+/// assert_eq!(value["aps"]["alert"], "Your order has shipped");
+///
+/// let payload = ApnsPayload::try_from(json!({ "aps": { "alert": "Hi" }, "order-id": 42 })).unwrap();
+/// assert_eq!(payload.custom_fields["order-id"], 42);
+///
+/// let payload = ApnsPayload::try_from(json!({
+///     "aps": { "alert": { "title": "Hi", "body": "There" }, "interruption-level": "passive" }
+/// }))
+/// .unwrap();
+/// assert_eq!(payload.aps.alert_fields["title"], "Hi");
+/// assert_eq!(payload.aps.extra_fields["interruption-level"], "passive");
+///
+/// let error = ApnsPayload::try_from(json!({ "aps": { "alert": "Hi", "badge": "two" } }));
+/// assert!(matches!(error, Err(PayloadError::RuleViolations(_))));
+/// ```
+impl TryFrom<Value> for ApnsPayload {
+    type Error = PayloadError;
+
+    fn try_from(value: Value) -> Result<Self, PayloadError> {
+        let Value::Object(payload) = &value else {
+            return Err(PayloadError::NotAnObject);
+        };
+        let Some(Value::Object(_)) = payload.get("aps") else {
+            return Err(PayloadError::MissingAps);
+        };
+        validate_against_rules(&value, PushType::Alert).map_err(PayloadError::RuleViolations)?;
+
+        serde_json::from_value(value).map_err(|e| PayloadError::Malformed {
+            message: e.to_string(),
+        })
+    }
+}
+
+/// Converts a payload into the JSON value it is sent as.
+///
+/// Fails like sending would if a custom, `aps`, or alert field collides with a key the
+/// payload sets itself, such as a custom field named `aps`.
+impl TryFrom<ApnsPayload> for Value {
+    type Error = serde_json::Error;
+
+    fn try_from(payload: ApnsPayload) -> Result<Self, serde_json::Error> {
+        serde_json::to_value(payload)
+    }
+}
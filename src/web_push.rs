@@ -0,0 +1,126 @@
+//! Safari web push payloads.
+
+use crate::notification::NotificationError;
+use crate::push_type::PushType;
+use serde::{Deserialize, Serialize};
+
+/// The alert of a Safari web push.
+///
+/// # Fields
+///
+/// * `title` - The title of the notification.
+/// * `body` - The text of the notification.
+/// * `action` - The label of the button that opens the website, if not the default `Show`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct WebPushAlert {
+    pub title: String,
+    pub body: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub action: Option<String>,
+}
+
+/// The `aps` dictionary of a Safari web push.
+///
+/// # Fields
+///
+/// * `alert` - The alert to display.
+/// * `url_args` - The values substituted into the `urlFormatString` of the website's push
+///   package when the notification is clicked, serialized as `url-args`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct WebPushAps {
+    pub alert: WebPushAlert,
+    #[serde(rename = "url-args")]
+    pub url_args: Vec<String>,
+}
+
+/// The payload of a Safari web push, sent to a website push ID such as `web.com.example`.
+///
+/// # Fields
+///
+/// * `aps` - The alert and URL arguments.
+///
+/// # Example
+///
+/// ```rust
+/// use apnrs::WebPushPayload;
+/// use serde_json::json;
+///
+/// let payload = WebPushPayload::new("Flight A998 now boarding", "Boarding has begun at gate 12.")
+///     .action("View")
+///     .url_args(["boarding", "A998"]);
+///
+/// assert_eq!(
+///     serde_json::to_value(&payload).unwrap(),
+///     json!({
+///         "aps": {
+///             "alert": {
+///                 "title": "Flight A998 now boarding",
+///                 "body": "Boarding has begun at gate 12.",
+///                 "action": "View",
+///             },
+///             "url-args": ["boarding", "A998"],
+///         }
+///     })
+/// );
+/// assert!(payload.validate().is_ok());
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct WebPushPayload {
+    pub aps: WebPushAps,
+}
+
+impl WebPushPayload {
+    /// Creates a web push with the given title and text, and no URL arguments.
+    pub fn new(title: impl Into<String>, body: impl Into<String>) -> Self {
+        WebPushPayload {
+            aps: WebPushAps {
+                alert: WebPushAlert {
+                    title: title.into(),
+                    body: body.into(),
+                    action: None,
+                },
+                url_args: Vec::new(),
+            },
+        }
+    }
+
+    /// Sets the label of the button that opens the website.
+    pub fn action(mut self, action: impl Into<String>) -> Self {
+        self.aps.alert.action = Some(action.into());
+        self
+    }
+
+    /// Sets the values substituted into the website's `urlFormatString`, in order.
+    pub fn url_args<I, S>(mut self, url_args: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.aps.url_args = url_args.into_iter().map(Into::into).collect();
+        self
+    }
+
+    /// Checks that the alert has the title and text Safari requires.
+    ///
+    /// # Returns
+    ///
+    /// `Ok(())` if the payload can be sent, or a `NotificationError` naming the first empty
+    /// field.
+    pub fn validate(&self) -> Result<(), NotificationError> {
+        let alert = &self.aps.alert;
+        let missing = if alert.title.is_empty() {
+            Some("aps.alert.title")
+        } else if alert.body.is_empty() {
+            Some("aps.alert.body")
+        } else {
+            None
+        };
+        match missing {
+            Some(key) => Err(NotificationError::MissingKey {
+                push_type: PushType::Alert,
+                key,
+            }),
+            None => Ok(()),
+        }
+    }
+}
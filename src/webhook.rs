@@ -0,0 +1,200 @@
+//! Posting failed sends to a webhook, enabled with the `webhook` feature.
+
+use crate::{ApnsClient, ApnsError, RetryPolicy, SendEvent};
+use http::header::{HeaderName, HeaderValue};
+use http::HeaderMap;
+use serde::Serialize;
+use std::mem;
+use std::time::Duration;
+use tokio::sync::broadcast::error::RecvError;
+use tokio::sync::broadcast::Receiver;
+use tokio::task::JoinHandle;
+use tokio::time::MissedTickBehavior;
+
+/// How many failures are posted together by default.
+const DEFAULT_BATCH_SIZE: usize = 100;
+
+/// How long failures wait for a batch to fill by default.
+const DEFAULT_FLUSH_INTERVAL: Duration = Duration::from_secs(5);
+
+/// The JSON body posted to the webhook.
+#[derive(Serialize)]
+struct Batch<'a> {
+    failures: &'a [SendEvent],
+}
+
+/// Posts a JSON summary of every failed send of a client to a webhook, for teams that want
+/// failures in an existing alerting or ticketing system without writing a consumer.
+///
+/// Failures are the [`SendEvent`]s with a status other than `200 OK`, including requests
+/// that failed in transit. They are posted in batches as
+/// `{"failures": [{"timestamp_ms": ..., "topic": ..., "device_token": ..., "status": ...,
+/// "reason": ..., "apns_id": ..., "latency_ms": ...}]}`, once
+/// [`batch_size`](FailureWebhook::batch_size) have collected or
+/// [`flush_interval`](FailureWebhook::flush_interval) has passed. A batch the webhook does not
+/// accept is retried with the [`retry_policy`](FailureWebhook::retry_policy) and then
+/// dropped. Device tokens are hashed if the client was built with a
+/// [`TokenHasher`](crate::TokenHasher).
+///
+/// Events arriving while a batch is being posted are buffered; if the webhook falls more
+/// than 1024 events behind, the oldest are skipped.
+///
+/// # Example
+///
+/// ```rust,no_run
+/// use apnrs::{ApnsClient, FailureWebhook};
+/// use std::time::Duration;
+///
+/// # async fn run(client: ApnsClient) -> Result<(), apnrs::ApnsError> {
+/// FailureWebhook::new("https://hooks.example.com/apns-failures")
+///     .header("authorization", "Bearer WEBHOOK_SECRET")?
+///     .batch_size(50)
+///     .flush_interval(Duration::from_secs(10))
+///     .spawn(&client);
+/// # Ok(())
+/// # }
+/// ```
+pub struct FailureWebhook {
+    url: String,
+    headers: HeaderMap,
+    batch_size: usize,
+    flush_interval: Duration,
+    retry_policy: RetryPolicy,
+    http: reqwest::Client,
+}
+
+impl FailureWebhook {
+    /// Creates a webhook posting to `url`.
+    pub fn new(url: impl Into<String>) -> Self {
+        FailureWebhook {
+            url: url.into(),
+            headers: HeaderMap::new(),
+            batch_size: DEFAULT_BATCH_SIZE,
+            flush_interval: DEFAULT_FLUSH_INTERVAL,
+            retry_policy: RetryPolicy::default(),
+            http: reqwest::Client::new(),
+        }
+    }
+
+    /// Sends `name: value` with every post, such as an `authorization` header.
+    ///
+    /// # Returns
+    ///
+    /// The webhook, or an `ApnsError` if the name or value is not a valid header.
+    pub fn header(mut self, name: &str, value: &str) -> Result<Self, ApnsError> {
+        let name = HeaderName::from_bytes(name.as_bytes())
+            .map_err(|e| ApnsError::Config(format!("invalid webhook header: {}", e)))?;
+        self.headers.insert(name, HeaderValue::from_str(value)?);
+        Ok(self)
+    }
+
+    /// Sets how many failures are posted together (defaults to 100).
+    pub fn batch_size(mut self, size: usize) -> Self {
+        self.batch_size = size.max(1);
+        self
+    }
+
+    /// Sets how long failures wait for a batch to fill before being posted anyway
+    /// (defaults to 5 seconds).
+    pub fn flush_interval(mut self, interval: Duration) -> Self {
+        self.flush_interval = interval.max(Duration::from_millis(1));
+        self
+    }
+
+    /// Sets how posts the webhook fails or answers with an error are retried (defaults to
+    /// [`RetryPolicy::default`]).
+    pub fn retry_policy(mut self, policy: RetryPolicy) -> Self {
+        self.retry_policy = policy;
+        self
+    }
+
+    /// Uses `http` to post to the webhook, for custom TLS roots or proxies.
+    pub fn http_client(mut self, http: reqwest::Client) -> Self {
+        self.http = http;
+        self
+    }
+
+    /// Starts posting the failures of `client`.
+    ///
+    /// The task runs until the client and all its clones are dropped, posting what is left
+    /// first; abort the returned handle to stop it earlier.
+    ///
+    /// # Panics
+    ///
+    /// Panics if called outside of a tokio runtime.
+    pub fn spawn(self, client: &ApnsClient) -> JoinHandle<()> {
+        tokio::spawn(self.run(client.subscribe_events()))
+    }
+
+    /// Collects failures from `events` and posts them in batches.
+    async fn run(self, mut events: Receiver<SendEvent>) {
+        let mut batch = Vec::new();
+        let mut flush = tokio::time::interval(self.flush_interval);
+        flush.set_missed_tick_behavior(MissedTickBehavior::Delay);
+        loop {
+            tokio::select! {
+                event = events.recv() => match event {
+                    Ok(event) if event.status != Some(200) => {
+                        batch.push(event);
+                        if batch.len() >= self.batch_size {
+                            self.post(&mem::take(&mut batch)).await;
+                        }
+                    }
+                    Ok(_) => {}
+                    Err(RecvError::Lagged(_skipped)) => {
+                        #[cfg(feature = "tracing")]
+                        tracing::warn!(skipped = _skipped, "failure webhook fell behind; events skipped");
+                    }
+                    Err(RecvError::Closed) => break,
+                },
+                _ = flush.tick() => {
+                    if !batch.is_empty() {
+                        self.post(&mem::take(&mut batch)).await;
+                    }
+                }
+            }
+        }
+        if !batch.is_empty() {
+            self.post(&batch).await;
+        }
+    }
+
+    /// Posts one batch, retrying failed posts.
+    async fn post(&self, failures: &[SendEvent]) {
+        let mut retry = 0;
+        loop {
+            let result = self
+                .http
+                .post(&self.url)
+                .headers(self.headers.clone())
+                .json(&Batch { failures })
+                .send()
+                .await;
+            let _error = match result {
+                Ok(response) => {
+                    let status = response.status();
+                    // Reading the body to its end lets the connection be reused
+                    let _ = response.bytes().await;
+                    if status.is_success() {
+                        return;
+                    }
+                    status.to_string()
+                }
+                Err(e) => e.to_string(),
+            };
+            if retry >= self.retry_policy.retries() {
+                #[cfg(feature = "tracing")]
+                tracing::warn!(error = %_error, count = failures.len(), "failure webhook gave up on a batch");
+                #[cfg(feature = "logging")]
+                log::warn!(
+                    "failure webhook gave up on a batch of {} after {}",
+                    failures.len(),
+                    _error
+                );
+                return;
+            }
+            retry += 1;
+            tokio::time::sleep(self.retry_policy.delay(retry)).await;
+        }
+    }
+}
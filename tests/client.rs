@@ -0,0 +1,41 @@
+#![cfg(feature = "tokio")]
+
+mod support;
+
+use apnrs::{ApnsError, ErrorReason};
+use support::mock::MockApns;
+
+#[tokio::test]
+async fn verifies_credentials_only_on_bad_device_token() {
+    let apns = MockApns::start(|_| (400, Some("BadDeviceToken"))).await;
+    let client = apns.client_builder().build().unwrap();
+
+    client.verify_credentials("com.example.app").await.unwrap();
+    assert_eq!(apns.received().len(), 1);
+    let stats = client.stats();
+    assert_eq!(stats.sent, 1);
+    assert_eq!(stats.failed_by_reason["BadDeviceToken"], 1);
+}
+
+#[tokio::test]
+async fn returns_the_reason_credentials_are_not_confirmed() {
+    for (answer, reason) in [
+        ("InvalidProviderToken", ErrorReason::InvalidProviderToken),
+        ("TopicDisallowed", ErrorReason::TopicDisallowed),
+        (
+            "DeviceTokenNotForTopic",
+            ErrorReason::DeviceTokenNotForTopic,
+        ),
+        ("Unregistered", ErrorReason::Unregistered),
+    ] {
+        let apns = MockApns::start(move |_| (400, Some(answer))).await;
+        let client = apns.client_builder().build().unwrap();
+
+        let result = client.verify_credentials("com.example.app").await;
+        assert!(
+            matches!(&result, Err(ApnsError::Rejected { reason: Some(r), .. }) if *r == reason),
+            "{:?}",
+            result
+        );
+    }
+}
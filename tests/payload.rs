@@ -0,0 +1,82 @@
+use apnrs::{ApnsPayload, Aps, Badge, PushType, Sound};
+use serde_json::{json, Value};
+
+fn sample() -> ApnsPayload {
+    ApnsPayload {
+        aps: Aps {
+            alert: "Hello, world!".to_string(),
+            content_available: None,
+            badge: Badge::Set(1),
+            sound: Sound::None,
+            category: None,
+            thread_id: None,
+            summary_arg: None,
+            summary_arg_count: None,
+            launch_image: None,
+            alert_fields: Default::default(),
+            extra_fields: Default::default(),
+        },
+        custom_key: None,
+        custom_fields: Default::default(),
+    }
+}
+
+#[test]
+fn preview_reports_the_json_and_limit() {
+    let mut payload = sample();
+    payload.custom_fields.insert("unread".to_string(), json!(4));
+
+    let preview = payload.preview().unwrap();
+    assert_eq!(preview.json, serde_json::to_vec(&payload).unwrap());
+    assert_eq!((preview.size, preview.limit), (preview.json.len(), 4096));
+    assert_eq!(payload.preview_for(PushType::Voip).unwrap().limit, 5120);
+}
+
+#[test]
+fn preview_fails_for_colliding_keys() {
+    for key in ["aps", "custom_key"] {
+        let mut payload = sample();
+        payload
+            .custom_fields
+            .insert(key.to_string(), json!("overwritten"));
+        let error = payload.preview().unwrap_err();
+        assert!(error.to_string().contains(key), "{}", error);
+    }
+
+    let mut payload = sample();
+    payload
+        .aps
+        .extra_fields
+        .insert("badge".to_string(), json!(2));
+    assert!(payload.preview().is_err());
+
+    let mut payload = sample();
+    payload
+        .aps
+        .alert_fields
+        .insert("body".to_string(), json!("Again"));
+    assert!(payload.preview_for(PushType::Voip).is_err());
+}
+
+#[test]
+fn value_conversion_round_trips() {
+    let mut payload = sample();
+    payload
+        .custom_fields
+        .insert("order-id".to_string(), json!(42));
+
+    let value = Value::try_from(payload).unwrap();
+    assert_eq!(
+        value,
+        json!({ "aps": { "alert": "Hello, world!", "badge": 1 }, "order-id": 42 })
+    );
+    let parsed = ApnsPayload::try_from(value.clone()).unwrap();
+    assert_eq!(Value::try_from(parsed).unwrap(), value);
+}
+
+#[test]
+fn value_conversion_fails_for_colliding_keys() {
+    let mut payload = sample();
+    payload.custom_fields.insert("aps".to_string(), json!({}));
+    assert!(Value::try_from(payload).is_err());
+}
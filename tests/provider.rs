@@ -0,0 +1,74 @@
+#![cfg(feature = "tokio")]
+
+use apnrs::{
+    ApnsError, ApnsPayload, ErrorReason, MemoryProvider, PushProvider, PushRequest, PushType,
+    SendOptions, StatusCode,
+};
+use serde_json::json;
+
+fn request(token: &str, topic: &str) -> PushRequest {
+    let payload = ApnsPayload::try_from(json!({ "aps": { "alert": "Hello" } })).unwrap();
+    PushRequest::new(token, topic, payload)
+}
+
+#[tokio::test]
+async fn records_accepted_and_rejected_requests() {
+    let provider =
+        MemoryProvider::new().reject("STALE", StatusCode::GONE, ErrorReason::Unregistered);
+
+    let sent = request("FRESH", "com.example.app")
+        .options(SendOptions::new().apns_id("4f1e8d2a-0c3b-4a5e-9f6d-7b8c9d0e1f2a"));
+    let outcome = provider.send(&sent).await.unwrap();
+    assert_eq!(outcome.response.status, StatusCode::OK);
+    assert_eq!(
+        outcome.response.apns_id.as_deref(),
+        Some("4f1e8d2a-0c3b-4a5e-9f6d-7b8c9d0e1f2a")
+    );
+
+    let outcome = provider
+        .send(&request("STALE", "com.example.app"))
+        .await
+        .unwrap();
+    assert_eq!(outcome.response.status, StatusCode::GONE);
+    assert_eq!(outcome.response.error, Some(ErrorReason::Unregistered));
+    assert_eq!(provider.sent().len(), 2);
+}
+
+#[tokio::test]
+async fn refuses_what_the_client_refuses_without_recording_it() {
+    let provider = MemoryProvider::new();
+
+    let error = provider.send(&request("TOKEN", "")).await.unwrap_err();
+    assert!(matches!(error, ApnsError::InvalidTopic(_)), "{:?}", error);
+
+    let mut oversized = request("TOKEN", "com.example.app");
+    oversized
+        .payload
+        .custom_fields
+        .insert("blob".to_string(), json!("x".repeat(5000)));
+    let error = provider.send(&oversized).await.unwrap_err();
+    assert!(
+        matches!(error, ApnsError::InvalidNotification(_)),
+        "{:?}",
+        error
+    );
+
+    let mdm = request("TOKEN", "com.apple.mgmt.External.0b5f4e1e")
+        .options(SendOptions::new().push_type(PushType::Mdm));
+    let error = provider.send(&mdm).await.unwrap_err();
+    assert!(
+        matches!(error, ApnsError::CertificateRequired(PushType::Mdm)),
+        "{:?}",
+        error
+    );
+
+    let invalid_id =
+        request("TOKEN", "com.example.app").options(SendOptions::new().apns_id("not\na header"));
+    let error = provider.send(&invalid_id).await.unwrap_err();
+    assert!(matches!(error, ApnsError::InvalidHeader(_)), "{:?}", error);
+
+    assert!(provider.sent().is_empty());
+    let provider = MemoryProvider::new().certificate();
+    provider.send(&mdm).await.unwrap();
+    assert_eq!(provider.sent().len(), 1);
+}
@@ -0,0 +1,51 @@
+#![cfg(feature = "chrono")]
+
+mod support;
+
+use apnrs::chrono_tz::America::New_York;
+use apnrs::{PushRequest, QuietHours};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use support::request;
+
+fn at(secs: u64) -> SystemTime {
+    UNIX_EPOCH + Duration::from_secs(secs)
+}
+
+#[test]
+fn follows_the_zone_offset_on_the_night() {
+    let quiet = QuietHours::new((22, 0), (8, 0)).unwrap();
+
+    // 23:00 EDT on 30 June 2024 is held until 08:00 EDT; noon EDT is not held
+    assert_eq!(
+        quiet.release_at_in(at(1_719_802_800), New_York),
+        Some(at(1_719_835_200))
+    );
+    assert!(!quiet.contains_in(at(1_719_849_600), New_York));
+    assert_eq!(quiet.release_at_in(at(1_719_849_600), New_York), None);
+}
+
+#[test]
+fn releases_at_local_end_across_offset_changes() {
+    let quiet = QuietHours::new((22, 0), (8, 0)).unwrap();
+
+    // Clocks move forward at 02:00 on 10 March 2024: 00:30 EST is held until 08:00 EDT
+    assert_eq!(
+        quiet.release_at_in(at(1_710_048_600), New_York),
+        Some(at(1_710_072_000))
+    );
+    // Clocks move back at 02:00 on 3 November 2024: 01:30 EDT is held until 08:00 EST
+    assert_eq!(
+        quiet.release_at_in(at(1_730_611_800), New_York),
+        Some(at(1_730_638_800))
+    );
+}
+
+#[test]
+fn time_zones_survive_serialization() {
+    let request = request("TOKEN").recipient_time_zone(New_York);
+    let json = serde_json::to_value(&request).unwrap();
+    assert_eq!(json["recipient_time_zone"], "America/New_York");
+
+    let replayed: PushRequest = serde_json::from_value(json).unwrap();
+    assert_eq!(replayed.recipient_time_zone, Some(New_York));
+}
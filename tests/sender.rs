@@ -0,0 +1,24 @@
+#![cfg(feature = "tokio")]
+
+mod support;
+
+use apnrs::{ApnsError, ApnsSender, Quota};
+use std::time::Duration;
+use support::{client_builder, request};
+
+#[tokio::test]
+async fn refunds_the_quota_of_notifications_that_are_not_queued() {
+    let client = client_builder().dry_run(true).build().unwrap();
+    let sender = ApnsSender::builder(client.clone())
+        .quota("com.example.app", Quota::new().daily_limit(2))
+        .build();
+
+    let receipt = sender.enqueue(request("TOKEN")).await.unwrap();
+    receipt.await.unwrap();
+    assert_eq!(sender.quota_remaining("com.example.app"), Some(1));
+
+    client.shutdown(Duration::from_secs(1)).await;
+    let result = sender.enqueue(request("TOKEN")).await;
+    assert!(matches!(result, Err(ApnsError::ClientShutDown)));
+    assert_eq!(sender.quota_remaining("com.example.app"), Some(1));
+}
@@ -0,0 +1,115 @@
+#![cfg(feature = "tokio")]
+
+mod support;
+
+use apnrs::{ApnsClient, ApnsError, ApnsSender, ApnsSenderBuilder, RetryPolicy};
+use std::time::{Duration, SystemTime};
+use support::mock::MockApns;
+use support::request;
+
+/// Returns a builder for a sender with one worker that spools as soon as APNs cannot be
+/// reached, probing it every 50 milliseconds.
+fn spooling(client: ApnsClient) -> ApnsSenderBuilder {
+    ApnsSender::builder(client)
+        .workers(1)
+        .retry_policy(RetryPolicy::none())
+        .spool_outages(Duration::from_millis(50))
+}
+
+/// Waits up to five seconds for `done` to hold.
+async fn wait_until(done: impl Fn() -> bool) {
+    for _ in 0..500 {
+        if done() {
+            return;
+        }
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+    panic!("timed out");
+}
+
+#[tokio::test]
+async fn replays_in_order_once_apns_is_reachable() {
+    let apns = MockApns::start(|_| (200, None)).await;
+    apns.set_down(true);
+    let sender = spooling(apns.client_builder().build().unwrap()).build();
+
+    let mut receipts = Vec::new();
+    for token in ["A", "B", "C"] {
+        receipts.push(sender.enqueue(request(token)).await.unwrap());
+    }
+    wait_until(|| sender.spooled() == 1).await;
+    // Several probes fail in the meantime
+    tokio::time::sleep(Duration::from_millis(200)).await;
+    assert!(apns.received().is_empty());
+
+    apns.set_down(false);
+    for receipt in receipts {
+        receipt.await.unwrap();
+    }
+    assert_eq!(apns.received(), ["A", "B", "C"]);
+    assert_eq!(sender.spooled(), 0);
+}
+
+#[tokio::test]
+async fn fails_notifications_that_go_stale_while_spooled() {
+    let apns = MockApns::start(|_| (200, None)).await;
+    apns.set_down(true);
+    let sender = spooling(apns.client_builder().build().unwrap())
+        .workers(2)
+        .build();
+
+    let late = sender
+        .enqueue(request("LATE").give_up_after(Duration::from_millis(300)))
+        .await
+        .unwrap();
+    let expired = SystemTime::now() + Duration::from_millis(300);
+    let mut expiring = request("EXPIRING");
+    expiring.options = expiring.options.expiration(expired);
+    let expiring = sender.enqueue(expiring).await.unwrap();
+    let fresh = sender.enqueue(request("FRESH")).await.unwrap();
+    wait_until(|| sender.spooled() == 2).await;
+
+    // Dropped by the probes while APNs is still down
+    assert!(matches!(late.await, Err(ApnsError::DeadlineExceeded(_))));
+    assert!(matches!(expiring.await, Err(ApnsError::Expired)));
+
+    apns.set_down(false);
+    fresh.await.unwrap();
+    assert_eq!(apns.received(), ["FRESH"]);
+}
+
+#[cfg(feature = "journal")]
+#[tokio::test]
+async fn cancelling_during_an_outage_settles_the_journal() {
+    use apnrs::CancellationToken;
+
+    let path = std::env::temp_dir().join(format!("apnrs-spool-{}.journal", std::process::id()));
+    let apns = MockApns::start(|_| (200, None)).await;
+    apns.set_down(true);
+    let cancel = CancellationToken::new();
+    let sender = spooling(apns.client_builder().build().unwrap())
+        .journal(&path)
+        .unwrap()
+        .cancellation_token(cancel.clone())
+        .build();
+
+    let receipt = sender.enqueue(request("CANCELLED")).await.unwrap();
+    wait_until(|| sender.spooled() == 1).await;
+    cancel.cancel();
+    assert!(matches!(receipt.await, Err(ApnsError::Cancelled)));
+    drop(sender);
+    // Compacted away once nothing in it is pending
+    assert_eq!(std::fs::metadata(&path).unwrap().len(), 0);
+
+    // A sender resuming the journal has nothing left to send
+    apns.set_down(false);
+    let resumed = spooling(apns.client_builder().build().unwrap())
+        .journal(&path)
+        .unwrap()
+        .build();
+    let receipt = resumed.enqueue(request("NEXT")).await.unwrap();
+    receipt.await.unwrap();
+    tokio::time::sleep(Duration::from_millis(200)).await;
+    assert_eq!(apns.received(), ["NEXT"]);
+    let _ = std::fs::remove_file(&path);
+}
@@ -0,0 +1,196 @@
+//! A stand-in for APNs, reached through a local proxy so clients keep their usual URLs.
+
+use super::client_builder;
+use apnrs::{ApnsClientBuilder, CertificatePin, ProxyConfig};
+use bytes::Bytes;
+use http::{Response, StatusCode};
+use openssl::asn1::Asn1Time;
+use openssl::bn::BigNum;
+use openssl::ec::{EcGroup, EcKey};
+use openssl::hash::MessageDigest;
+use openssl::nid::Nid;
+use openssl::pkey::{PKey, Private};
+use openssl::x509::extension::{BasicConstraints, KeyUsage, SubjectAlternativeName};
+use openssl::x509::{X509Name, X509};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio_native_tls::native_tls::{self, Identity};
+use tokio_native_tls::TlsAcceptor;
+
+/// How the mock answers a request: the status and the reason APNs would give for it.
+type Answer = dyn Fn(&str) -> (u16, Option<&'static str>) + Send + Sync;
+
+/// An APNs endpoint that answers each notification by its device token, records the tokens
+/// in the order they arrive, and drops every connection while it is down.
+pub struct MockApns {
+    url: String,
+    root: Vec<u8>,
+    state: Arc<State>,
+}
+
+struct State {
+    answer: Box<Answer>,
+    down: AtomicBool,
+    received: Mutex<Vec<String>>,
+}
+
+impl MockApns {
+    /// Starts an endpoint answering every request with `answer`.
+    pub async fn start(
+        answer: impl Fn(&str) -> (u16, Option<&'static str>) + Send + Sync + 'static,
+    ) -> MockApns {
+        let (root, identity) = certificates();
+        let acceptor = TlsAcceptor::from(native_tls::TlsAcceptor::new(identity).unwrap());
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let state = Arc::new(State {
+            answer: Box::new(answer),
+            down: AtomicBool::new(false),
+            received: Mutex::new(Vec::new()),
+        });
+
+        let accepting = state.clone();
+        tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                tokio::spawn(serve(stream, acceptor.clone(), accepting.clone()));
+            }
+        });
+        MockApns { url, root, state }
+    }
+
+    /// Returns a builder for a client that sends to this endpoint.
+    pub fn client_builder(&self) -> ApnsClientBuilder {
+        client_builder()
+            .proxy(ProxyConfig::new(&self.url))
+            .pin(CertificatePin::Root(self.root.clone()))
+            .connection_retries(0)
+    }
+
+    /// Takes the endpoint down, dropping open connections at their next request, or brings it
+    /// back up.
+    pub fn set_down(&self, down: bool) {
+        self.state.down.store(down, Ordering::SeqCst);
+    }
+
+    /// Returns the device tokens of the requests answered so far, in the order they arrived.
+    pub fn received(&self) -> Vec<String> {
+        self.state.received.lock().unwrap().clone()
+    }
+}
+
+/// Accepts a proxy tunnel and serves HTTP/2 over TLS inside it.
+async fn serve(mut stream: TcpStream, acceptor: TlsAcceptor, state: Arc<State>) {
+    let mut head = Vec::new();
+    while !head.ends_with(b"\r\n\r\n") {
+        let mut byte = [0];
+        if stream.read(&mut byte).await.unwrap_or(0) == 0 {
+            return;
+        }
+        head.push(byte[0]);
+    }
+    if state.down.load(Ordering::SeqCst) {
+        return;
+    }
+    stream
+        .write_all(b"HTTP/1.1 200 Connection Established\r\n\r\n")
+        .await
+        .unwrap();
+
+    let Ok(stream) = acceptor.accept(stream).await else {
+        return;
+    };
+    let Ok(mut connection) = h2::server::handshake(stream).await else {
+        return;
+    };
+    while let Some(Ok((request, mut respond))) = connection.accept().await {
+        if state.down.load(Ordering::SeqCst) {
+            return;
+        }
+        // Connection probes are answered without being recorded
+        let (status, reason) = match request.uri().path().strip_prefix("/3/device/") {
+            Some(device_token) => {
+                state
+                    .received
+                    .lock()
+                    .unwrap()
+                    .push(device_token.to_string());
+                (state.answer)(device_token)
+            }
+            None => (405, Some("MethodNotAllowed")),
+        };
+        let response = Response::builder()
+            .status(StatusCode::from_u16(status).unwrap())
+            .header("apns-id", "00000000-0000-0000-0000-000000000000")
+            .body(())
+            .unwrap();
+        let body = match reason {
+            Some(reason) => format!(r#"{{"reason":"{}"}}"#, reason),
+            None => String::new(),
+        };
+        let Ok(mut send) = respond.send_response(response, body.is_empty()) else {
+            continue;
+        };
+        if !body.is_empty() {
+            let _ = send.send_data(Bytes::from(body), true);
+        }
+    }
+}
+
+/// Generates a root certificate, returned as DER for pinning, and the identity of a server
+/// certificate it issued for the APNs hosts.
+fn certificates() -> (Vec<u8>, Identity) {
+    let (root_key, root) = certificate("apnrs test root", None);
+    let (key, leaf) = certificate("api.push.apple.com", Some((&root_key, &root)));
+    let mut chain = leaf.to_pem().unwrap();
+    chain.extend(root.to_pem().unwrap());
+    let identity = Identity::from_pkcs8(&chain, &key.private_key_to_pem_pkcs8().unwrap()).unwrap();
+    (root.to_der().unwrap(), identity)
+}
+
+/// Generates a P-256 key and a certificate for it, self-signed as a root unless `issuer`
+/// signs it for the APNs hosts.
+fn certificate(name: &str, issuer: Option<(&PKey<Private>, &X509)>) -> (PKey<Private>, X509) {
+    let group = EcGroup::from_curve_name(Nid::X9_62_PRIME256V1).unwrap();
+    let key = PKey::from_ec_key(EcKey::generate(&group).unwrap()).unwrap();
+    let mut subject = X509Name::builder().unwrap();
+    subject.append_entry_by_nid(Nid::COMMONNAME, name).unwrap();
+    let subject = subject.build();
+
+    let mut builder = X509::builder().unwrap();
+    builder.set_version(2).unwrap();
+    let serial = BigNum::from_u32(if issuer.is_some() { 2 } else { 1 }).unwrap();
+    builder
+        .set_serial_number(&serial.to_asn1_integer().unwrap())
+        .unwrap();
+    builder.set_subject_name(&subject).unwrap();
+    builder.set_pubkey(&key).unwrap();
+    builder
+        .set_not_before(&Asn1Time::days_from_now(0).unwrap())
+        .unwrap();
+    builder
+        .set_not_after(&Asn1Time::days_from_now(1).unwrap())
+        .unwrap();
+    match issuer {
+        None => {
+            builder.set_issuer_name(&subject).unwrap();
+            let constraints = BasicConstraints::new().critical().ca().build().unwrap();
+            builder.append_extension(constraints).unwrap();
+            let usage = KeyUsage::new().critical().key_cert_sign().build().unwrap();
+            builder.append_extension(usage).unwrap();
+            builder.sign(&key, MessageDigest::sha256()).unwrap();
+        }
+        Some((issuer_key, issuer)) => {
+            builder.set_issuer_name(issuer.subject_name()).unwrap();
+            let hosts = SubjectAlternativeName::new()
+                .dns("api.push.apple.com")
+                .dns("api.sandbox.push.apple.com")
+                .build(&builder.x509v3_context(Some(issuer), None))
+                .unwrap();
+            builder.append_extension(hosts).unwrap();
+            builder.sign(issuer_key, MessageDigest::sha256()).unwrap();
+        }
+    }
+    (key, builder.build())
+}
@@ -0,0 +1,28 @@
+//! Helpers shared by the integration tests.
+
+#![allow(dead_code)]
+
+#[cfg(feature = "tokio")]
+pub mod mock;
+
+use apnrs::{ApnsClient, ApnsClientBuilder, ApnsPayload, PushRequest};
+use openssl::ec::{EcGroup, EcKey};
+use openssl::nid::Nid;
+use openssl::pkey::PKey;
+use serde_json::json;
+
+/// Returns a builder for a client signing with a freshly generated auth key.
+pub fn client_builder() -> ApnsClientBuilder {
+    let group = EcGroup::from_curve_name(Nid::X9_62_PRIME256V1).unwrap();
+    let key = PKey::from_ec_key(EcKey::generate(&group).unwrap()).unwrap();
+    ApnsClient::builder()
+        .auth_key_pem(key.private_key_to_pem_pkcs8().unwrap())
+        .team_id("TEAM_ID")
+        .key_id("KEY_ID")
+}
+
+/// Returns a request with a small alert payload.
+pub fn request(device_token: &str) -> PushRequest {
+    let payload = ApnsPayload::try_from(json!({ "aps": { "alert": "Hello" } })).unwrap();
+    PushRequest::new(device_token, "com.example.app", payload)
+}